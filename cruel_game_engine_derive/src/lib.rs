@@ -0,0 +1,207 @@
+//! `#[derive(Inspect)]` for cruel_game_engine, see `cruel_game_engine::inspect`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, LitStr};
+
+/// Draws every field with its own `Inspect` impl, labeled with its name. Fields take
+/// `#[inspect(...)]` options:
+///
+/// - `skip` leaves the field out
+/// - `label = "..."` instead of the field's name
+/// - `color` for `[f32; 3]` and `[f32; 4]` colors
+/// - `speed = ..`, `min = ..` and `max = ..` for numbers
+///
+/// Enums show the fields of the variant they hold.
+#[proc_macro_derive(Inspect, attributes(inspect))]
+pub fn derive_inspect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = quote!(::cruel_game_engine);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let mut widgets = Vec::new();
+            for (i, field) in data.fields.iter().enumerate() {
+                let (access, field_name) = match &field.ident {
+                    Some(ident) => (quote!(&mut self.#ident), ident.to_string()),
+                    None => {
+                        let index = syn::Index::from(i);
+                        (
+                            quote!(&mut self.#index),
+                            unnamed_label(i, &data.fields, name),
+                        )
+                    }
+                };
+                widgets.push(widget(access, &field_name, &field.attrs)?);
+            }
+            quote!(#(#widgets)*)
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let variant_name = &variant.ident;
+                let mut widgets = Vec::new();
+                let pattern = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let idents: Vec<_> = fields
+                            .named
+                            .iter()
+                            .map(|field| field.ident.clone())
+                            .collect();
+                        for field in &fields.named {
+                            let ident = field.ident.as_ref().unwrap();
+                            widgets.push(widget(quote!(#ident), &ident.to_string(), &field.attrs)?);
+                        }
+                        quote!(Self::#variant_name { #(#idents),* })
+                    }
+                    Fields::Unnamed(fields) => {
+                        let bindings: Vec<_> = (0..fields.unnamed.len())
+                            .map(|i| format_ident!("field_{}", i))
+                            .collect();
+                        for (i, (field, binding)) in
+                            fields.unnamed.iter().zip(&bindings).enumerate()
+                        {
+                            let label = unnamed_label(i, &variant.fields, variant_name);
+                            widgets.push(widget(quote!(#binding), &label, &field.attrs)?);
+                        }
+                        quote!(Self::#variant_name(#(#bindings),*))
+                    }
+                    Fields::Unit => quote!(Self::#variant_name),
+                };
+                arms.push(quote!(#pattern => { #(#widgets)* }));
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Inspect can't be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::inspect::Inspect for #name #type_generics #where_clause {
+            fn inspect(&mut self, ui: &mut #krate::inspect::egui::Ui, label: &str) -> bool {
+                #krate::inspect::fields(ui, label, |ui| {
+                    let mut changed = false;
+                    #body
+                    changed
+                })
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    skip: bool,
+    label: Option<String>,
+    color: bool,
+    speed: Option<Expr>,
+    min: Option<Expr>,
+    max: Option<Expr>,
+}
+
+fn field_options(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("inspect")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("color") {
+                options.color = true;
+            } else if meta.path.is_ident("label") {
+                options.label = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("speed") {
+                options.speed = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("min") {
+                options.min = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("max") {
+                options.max = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected skip, label, color, speed, min or max"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+// `access` is a `&mut` to the field
+fn widget(access: TokenStream2, name: &str, attrs: &[Attribute]) -> syn::Result<TokenStream2> {
+    let krate = quote!(::cruel_game_engine);
+    let options = field_options(attrs)?;
+    if options.skip {
+        return Ok(TokenStream2::new());
+    }
+    let label = options.label.unwrap_or_else(|| sentence_case(name));
+
+    if options.color {
+        return Ok(quote!(changed |= #krate::inspect::color(ui, #label, #access);));
+    }
+    if options.speed.is_some() || options.min.is_some() || options.max.is_some() {
+        let speed = options
+            .speed
+            .map(|speed| quote!(options.speed = (#speed) as f64;));
+        let min = options.min.map(|min| quote!(options.min = (#min) as f64;));
+        let max = options.max.map(|max| quote!(options.max = (#max) as f64;));
+        return Ok(quote! {
+            let mut options = #krate::inspect::NumberOptions::default();
+            #speed
+            #min
+            #max
+            changed |= #krate::inspect::number(ui, #label, #access, options);
+        });
+    }
+    Ok(quote!(changed |= #krate::inspect::Inspect::inspect(#access, ui, #label);))
+}
+
+// A lone unnamed field is labeled with its struct or variant, the others by position
+fn unnamed_label(index: usize, fields: &Fields, owner: &syn::Ident) -> String {
+    match fields.len() {
+        1 => owner.to_string(),
+        _ => index.to_string(),
+    }
+}
+
+// play_on_start is shown as "Play on start", PlayOnStart too
+fn sentence_case(name: &str) -> String {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in name.chars() {
+        if c == '_' || (c.is_uppercase() && !word.is_empty()) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            if c == '_' {
+                continue;
+            }
+        }
+        word.push(c.to_ascii_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    let sentence = words.join(" ");
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => sentence,
+    }
+}
@@ -0,0 +1,49 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec2 FragColor;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+vec2 hammersley(uint i, uint count) {
+    return vec2(float(i) / float(count), float(bitfieldReverse(i)) * 2.3283064365386963e-10);
+}
+
+vec3 importanceSampleGGX(vec2 xi, vec3 normal, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    return vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta); // The normal is +Z here
+}
+
+float geometrySchlickGGX(float NdotV, float roughness) {
+    float k = roughness * roughness / 2.0; // The IBL remapping of k
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+// Scale and bias to F0 of the specular BRDF, indexed by (NdotV, roughness)
+void main() {
+    float NdotV = max(texCoord.x, 0.001);
+    float roughness = texCoord.y;
+    vec3 view = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+
+    vec2 result = vec2(0.0);
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec3 halfway = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), vec3(0.0, 0.0, 1.0), roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+
+        float NdotL = max(light.z, 0.0);
+        float NdotH = max(halfway.z, 0.0);
+        float VdotH = max(dot(view, halfway), 0.0);
+        if (NdotL > 0.0) {
+            float geometry = geometrySchlickGGX(NdotV, roughness) * geometrySchlickGGX(NdotL, roughness);
+            float visibility = geometry * VdotH / (NdotH * NdotV);
+            float fresnel = pow(1.0 - VdotH, 5.0);
+            result += vec2((1.0 - fresnel) * visibility, fresnel * visibility);
+        }
+    }
+
+    FragColor = result / float(SAMPLE_COUNT);
+}
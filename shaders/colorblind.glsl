@@ -0,0 +1,35 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform mat3 simulation; // Linear RGB to how the color looks with the deficiency
+uniform bool correct;    // Daltonize instead of simulating
+uniform float strength;
+
+vec3 to_linear(vec3 color) {
+    return pow(color, vec3(2.2));
+}
+
+vec3 to_srgb(vec3 color) {
+    return pow(color, vec3(1.0 / 2.2));
+}
+
+void main() {
+    vec3 color = texture(image, texCoord).rgb;
+    vec3 linear = to_linear(color);
+    vec3 simulated = clamp(simulation * linear, 0.0, 1.0);
+
+    vec3 result;
+    if (correct) {
+        // Move the information that is lost into the channels that are still visible
+        vec3 error = linear - simulated;
+        vec3 shift = vec3(0.0, 0.7 * error.r + error.g, 0.7 * error.r + error.b);
+        result = to_srgb(clamp(linear + shift, 0.0, 1.0));
+    } else {
+        result = to_srgb(simulated);
+    }
+
+    FragColor = vec4(mix(color, result, strength), 1.0);
+}
@@ -0,0 +1,29 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform sampler2D equirect;
+uniform int face; // 0 to 5 in the +X, -X, +Y, -Y, +Z, -Z order of GL_TEXTURE_CUBE_MAP_POSITIVE_X + face
+
+const float PI = 3.14159265359;
+
+// Direction through a texel of a cubemap face, from the face table in the GL spec
+vec3 cubeDirection(int face, vec2 uv) {
+    vec2 p = uv * 2.0 - 1.0;
+    switch (face) {
+        case 0: return normalize(vec3(1.0, -p.y, -p.x));
+        case 1: return normalize(vec3(-1.0, -p.y, p.x));
+        case 2: return normalize(vec3(p.x, 1.0, p.y));
+        case 3: return normalize(vec3(p.x, -1.0, -p.y));
+        case 4: return normalize(vec3(p.x, -p.y, 1.0));
+        default: return normalize(vec3(-p.x, -p.y, -1.0));
+    }
+}
+
+void main() {
+    vec3 direction = cubeDirection(face, texCoord);
+    // The first image row is the top of the sky
+    vec2 uv = vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, 0.5 - asin(direction.y) / PI);
+    FragColor = vec4(texture(equirect, uv).rgb, 1.0);
+}
@@ -0,0 +1,34 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform sampler2D depth;
+uniform sampler2D mask; // 0 unexplored, 0.5 explored, 1 visible
+
+uniform mat4 inverse_view_projection;
+uniform vec4 mask_transform; // xy: world position of the mask's corner, zw: 1 / world size
+uniform float explored_brightness;
+
+void main() {
+    vec3 color = texture(image, texCoord).rgb;
+
+    // Back to world space to find where this pixel is on the fog grid
+    vec4 clip = vec4(texCoord * 2.0 - 1.0, texture(depth, texCoord).r * 2.0 - 1.0, 1.0);
+    vec4 world = inverse_view_projection * clip;
+    world /= world.w;
+
+    vec2 mask_uv = (world.xy - mask_transform.xy) * mask_transform.zw;
+    float reveal = 0.0;
+    if (all(greaterThanEqual(mask_uv, vec2(0.0))) && all(lessThanEqual(mask_uv, vec2(1.0)))) {
+        reveal = texture(mask, mask_uv).r;
+    }
+
+    // Explored but not visible cells are dimmed, unexplored ones are black
+    float brightness = reveal <= 0.5
+        ? mix(0.0, explored_brightness, reveal * 2.0)
+        : mix(explored_brightness, 1.0, reveal * 2.0 - 1.0);
+
+    FragColor = vec4(color * brightness, 1.0);
+}
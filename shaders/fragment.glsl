@@ -1,12 +1,137 @@
 #version 460 core
 
-in vec3 vertexColor;
+in vec4 vertexColor;
 in vec2 texCoord;
+in vec2 texCoord1;
+in vec3 worldNormal;
+in vec4 worldTangent;
+in vec3 worldPosition;
 out vec4 FragColor;
 
 uniform sampler2D image;
+uniform bool has_vertex_colors; // Otherwise the attribute is missing and reads as black
+uniform sampler2D normal_map;
+uniform bool has_normal_map;
+uniform int normal_map_uv; // Which UV set each map reads, see uvSet
+uniform sampler2D occlusion_map;
+uniform bool has_occlusion_map;
+uniform int occlusion_map_uv;
+uniform float occlusion_strength;
+
+uniform float metallic;
+uniform float roughness;
+uniform vec3 emissive;
+uniform float clearcoat;
+uniform float clearcoat_roughness;
+uniform float transmission;
+uniform vec3 camera_position;
+
+// Image based lighting, see environment.rs
+uniform bool has_environment;
+uniform samplerCube irradiance_map;
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform float prefiltered_mips; // Highest mip of the prefiltered map, roughness 1
+
+// One directional light until the scene has lights of its own
+const vec3 light_direction = normalize(vec3(-0.4, -0.8, -0.5)); // Where the light travels
+const float ambient = 0.25;
+
+// View modes are #defines in front of this file, see view_mode.rs
+const float depth_range = 100.0; // Distance that shows as white in the depth view
+
+vec2 uvSet(int set) {
+    return set == 1 ? texCoord1 : texCoord;
+}
 
 void main() {
-    // FragColor = vec4(vertexColor, 1.0);
-    FragColor = (texture(image, texCoord) + vec4(vertexColor, 1.0)) / 2;
+    vec4 albedo = texture(image, texCoord);
+    if (has_vertex_colors) {
+        albedo *= vertexColor;
+    }
+
+#if defined(VIEW_UNLIT)
+    FragColor = albedo;
+    return;
+#elif defined(VIEW_DEPTH)
+    FragColor = vec4(vec3(clamp(distance(camera_position, worldPosition) / depth_range, 0.0, 1.0)), 1.0);
+    return;
+#elif defined(VIEW_OVERDRAW)
+    FragColor = vec4(0.1, 0.04, 0.01, 1.0); // Added up by blending, see SceneNode::render
+    return;
+#elif defined(VIEW_UV_CHECKER)
+    // Tinted by the UV so flipped and rotated islands stand out
+    vec2 cell = floor(texCoord * 8.0);
+    float checker = mod(cell.x + cell.y, 2.0);
+    FragColor = vec4(mix(vec3(0.2), vec3(fract(texCoord), 1.0), checker), 1.0);
+    return;
+#endif
+
+    // Meshes without normals stay unlit
+    if (dot(worldNormal, worldNormal) < 1e-8) {
+#ifdef VIEW_NORMALS
+        FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+#else
+        FragColor = vec4(albedo.rgb + emissive, albedo.a);
+#endif
+        return;
+    }
+
+    vec3 normal = normalize(worldNormal);
+    if (has_normal_map && dot(worldTangent.xyz, worldTangent.xyz) > 1e-8) {
+        // Tangent space to world space
+        vec3 tangent = normalize(worldTangent.xyz - normal * dot(normal, worldTangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * worldTangent.w;
+        vec3 sampled = texture(normal_map, uvSet(normal_map_uv)).xyz * 2.0 - 1.0;
+        normal = normalize(mat3(tangent, bitangent, normal) * sampled);
+    }
+
+#ifdef VIEW_NORMALS
+    FragColor = vec4(normal * 0.5 + 0.5, 1.0);
+    return;
+#endif
+
+    // Only indirect light is occluded, the sun has no baked shadows to double up with
+    float occlusion = 1.0;
+    if (has_occlusion_map) {
+        occlusion = mix(1.0, texture(occlusion_map, uvSet(occlusion_map_uv)).r, occlusion_strength);
+    }
+
+    float diffuse = max(dot(normal, -light_direction), 0.0);
+    if (!has_environment) {
+        FragColor = vec4(albedo.rgb * (ambient * occlusion + (1.0 - ambient) * diffuse) + emissive, albedo.a);
+        return;
+    }
+
+    // Split sum ambient, metals reflect the environment tinted by their color
+    vec3 view = normalize(camera_position - worldPosition);
+    float NdotV = max(dot(normal, view), 0.0);
+    vec3 F0 = mix(vec3(0.04), albedo.rgb, metallic);
+    vec3 fresnel = F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(1.0 - NdotV, 5.0);
+    vec3 kD = (1.0 - fresnel) * (1.0 - metallic);
+
+    // Transmission trades diffuse for what's behind the surface, glass of IOR 1.5 bending
+    // the view into the environment since the rest of the scene isn't available here
+    vec3 irradiance = texture(irradiance_map, normal).rgb;
+    vec3 behind = textureLod(prefiltered_map, refract(-view, normal, 1.0 / 1.5), roughness * prefiltered_mips).rgb;
+    vec3 prefiltered = textureLod(prefiltered_map, reflect(-view, normal), roughness * prefiltered_mips).rgb;
+    vec2 brdf = texture(brdf_lut, vec2(NdotV, roughness)).rg;
+    vec3 ambientLight = (kD * albedo.rgb * mix(irradiance, behind, transmission) + prefiltered * (fresnel * brdf.x + brdf.y)) * occlusion;
+
+    // The sun stays a plain diffuse light on top
+    vec3 direct = kD * albedo.rgb * (1.0 - ambient) * diffuse * (1.0 - transmission);
+    vec3 color = ambientLight + direct;
+
+    // Clearcoat is a second dielectric layer on the geometric normal, the base only gets
+    // what it lets through
+    if (clearcoat > 0.0) {
+        vec3 coatNormal = normalize(worldNormal);
+        float coatNdotV = max(dot(coatNormal, view), 0.0);
+        float coatFresnel = (0.04 + 0.96 * pow(1.0 - coatNdotV, 5.0)) * clearcoat;
+        vec3 coatReflection = textureLod(prefiltered_map, reflect(-view, coatNormal), clearcoat_roughness * prefiltered_mips).rgb;
+        vec2 coatBrdf = texture(brdf_lut, vec2(coatNdotV, clearcoat_roughness)).rg;
+        color = color * (1.0 - coatFresnel) + coatReflection * (0.04 * coatBrdf.x + coatBrdf.y) * clearcoat;
+    }
+
+    FragColor = vec4(color + emissive, albedo.a);
 }
@@ -0,0 +1,17 @@
+#version 460 core
+
+in vec2 uv;
+in vec4 uiColor;
+in float font;
+out vec4 FragColor;
+
+uniform sampler2D image;
+
+void main() {
+    vec4 texel = texture(image, uv);
+    // The font atlas only has coverage, in the red channel
+    if (font > 0.5) {
+        texel = vec4(1.0, 1.0, 1.0, texel.r);
+    }
+    FragColor = texel * uiColor;
+}
@@ -0,0 +1,19 @@
+#version 460 core
+
+layout (location = 0) in vec2 aPos;   // Pixels from the viewport's top left
+layout (location = 1) in vec2 aUV;
+layout (location = 2) in vec4 aColor;
+layout (location = 3) in float aFont; // 1 when the texture is the font atlas
+
+out vec2 uv;
+out vec4 uiColor;
+out float font;
+
+uniform mat4 projection;
+
+void main() {
+    uv = aUV;
+    uiColor = aColor;
+    font = aFont;
+    gl_Position = projection * vec4(aPos, 0.0, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 460 core
+
+in vec2 local;
+in vec4 highlightColor;
+out vec4 FragColor;
+
+uniform int hexagon; // Pointy top hexagons are cut out of the quad
+uniform float inset; // Gap between neighbouring highlights, 0 to 1
+
+void main() {
+    vec2 p = abs(local) / (1.0 - inset);
+    if (hexagon != 0) {
+        if (p.x > 0.8660254 || p.y + p.x * 0.5773503 > 1.0) {
+            discard;
+        }
+    } else if (max(p.x, p.y) > 1.0) {
+        discard;
+    }
+
+    FragColor = highlightColor;
+}
@@ -0,0 +1,17 @@
+#version 460 core
+
+layout (location = 0) in vec2 corner; // -1 to 1, one quad shared by every instance
+layout (location = 1) in vec3 center; // Per instance, world space
+layout (location = 2) in vec4 color;  // Per instance
+
+out vec2 local;
+out vec4 highlightColor;
+
+uniform mat4 view_projection;
+uniform float half_extent; // World units from the center to the quad's edge
+
+void main() {
+    local = corner;
+    highlightColor = color;
+    gl_Position = view_projection * vec4(center + vec3(corner * half_extent, 0.0), 1.0);
+}
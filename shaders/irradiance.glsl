@@ -0,0 +1,44 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform samplerCube environment;
+uniform int face;
+
+const float PI = 3.14159265359;
+
+vec3 cubeDirection(int face, vec2 uv) {
+    vec2 p = uv * 2.0 - 1.0;
+    switch (face) {
+        case 0: return normalize(vec3(1.0, -p.y, -p.x));
+        case 1: return normalize(vec3(-1.0, -p.y, p.x));
+        case 2: return normalize(vec3(p.x, 1.0, p.y));
+        case 3: return normalize(vec3(p.x, -1.0, -p.y));
+        case 4: return normalize(vec3(p.x, -p.y, 1.0));
+        default: return normalize(vec3(-p.x, -p.y, -1.0));
+    }
+}
+
+// Cosine weighted average of the hemisphere around the normal, the diffuse part of the split sum
+void main() {
+    vec3 normal = cubeDirection(face, texCoord);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    const float delta = 0.025;
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += delta) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += delta) {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangentSample.x * right + tangentSample.y * up + tangentSample.z * normal;
+            // A blurrier mip keeps the sparse samples from aliasing on small bright spots
+            irradiance += textureLod(environment, direction, 3.0).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    FragColor = vec4(PI * irradiance / samples, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 460 core
+
+in vec2 local;
+in vec4 particleColor;
+out vec4 FragColor;
+
+void main() {
+    // Soft round particles, fading out towards the edge of the quad
+    float falloff = 1.0 - smoothstep(0.5, 1.0, length(local));
+    if (falloff <= 0.0) {
+        discard;
+    }
+    FragColor = vec4(particleColor.rgb, particleColor.a * falloff);
+}
@@ -0,0 +1,76 @@
+#version 460 core
+
+layout (local_size_x = 256) in;
+
+// A particle is dead once its age reaches its lifetime, a zeroed buffer is all dead particles
+struct Particle {
+    vec4 positionAndAge;
+    vec4 velocityAndLifetime;
+};
+
+layout (std430, binding = 0) buffer Particles {
+    Particle particles[];
+};
+
+layout (std430, binding = 1) buffer Spawned {
+    uint spawned; // Reset to 0 before each dispatch
+};
+
+uniform uint particle_count; // Slots in the buffer, alive or not
+uniform uint spawn_count;
+uniform uint seed;
+uniform float delta_time;
+uniform vec3 origin;
+uniform vec3 direction; // Normalized
+uniform float cos_spread;
+uniform vec2 lifetime;  // Min and max
+uniform vec2 speed;
+uniform vec3 gravity;
+
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352dU;
+    x ^= x >> 15;
+    x *= 0x846ca68bU;
+    x ^= x >> 16;
+    return x;
+}
+
+// 0 to 1
+float random(inout uint state) {
+    state = hash(state);
+    return float(state >> 8) / 16777216.0;
+}
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= particle_count) {
+        return;
+    }
+
+    Particle particle = particles[index];
+    if (particle.positionAndAge.w < particle.velocityAndLifetime.w) {
+        particle.velocityAndLifetime.xyz += gravity * delta_time;
+        particle.positionAndAge.xyz += particle.velocityAndLifetime.xyz * delta_time;
+        particle.positionAndAge.w += delta_time;
+    } else if (spawn_count > 0u && atomicAdd(spawned, 1u) < spawn_count) {
+        // Dead slots take the new particles, uniform over the spherical cap like on the CPU
+        uint state = hash(index ^ hash(seed));
+        float cosTheta = 1.0 - random(state) * (1.0 - cos_spread);
+        float sinTheta = sqrt(max(1.0 - cosTheta * cosTheta, 0.0));
+        float phi = random(state) * 6.28318530718;
+        vec3 helper = abs(direction.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 0.0, 1.0);
+        vec3 tangent = normalize(cross(direction, helper));
+        vec3 bitangent = cross(direction, tangent);
+        vec3 velocity = direction * cosTheta
+            + tangent * (sinTheta * cos(phi))
+            + bitangent * (sinTheta * sin(phi));
+
+        particle.positionAndAge = vec4(origin, 0.0);
+        particle.velocityAndLifetime = vec4(
+            velocity * mix(speed.x, speed.y, random(state)),
+            max(mix(lifetime.x, lifetime.y, random(state)), 0.01)
+        );
+    }
+    particles[index] = particle;
+}
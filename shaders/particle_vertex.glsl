@@ -0,0 +1,57 @@
+#version 460 core
+
+layout (location = 0) in vec2 corner;         // -1 to 1, one quad shared by every instance
+
+#ifdef GPU_PARTICLES
+// Simulated by particle_simulate.glsl, one instance per slot whether it's alive or not
+struct Particle {
+    vec4 positionAndAge;
+    vec4 velocityAndLifetime;
+};
+
+layout (std430, binding = 0) readonly buffer Particles {
+    Particle particles[];
+};
+
+uniform float size;
+uniform float size_over_lifetime[CURVE_SAMPLES]; // Sampled evenly from 0 to 1
+uniform vec4 color_over_lifetime[CURVE_SAMPLES];
+#else
+layout (location = 1) in vec4 centerAndSize;  // Per instance, world space center and width
+layout (location = 2) in vec4 color;          // Per instance
+#endif
+
+out vec2 local;
+out vec4 particleColor;
+
+uniform mat4 view_projection;
+uniform vec3 camera_right; // World space, the quads always face the camera
+uniform vec3 camera_up;
+
+void main() {
+    local = corner;
+
+#ifdef GPU_PARTICLES
+    Particle particle = particles[gl_InstanceID];
+    float age = particle.positionAndAge.w;
+    float lifetime = particle.velocityAndLifetime.w;
+    if (age >= lifetime) {
+        // Outside the clip volume, so dead slots draw nothing
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        particleColor = vec4(0.0);
+        return;
+    }
+
+    float x = clamp(age / lifetime, 0.0, 1.0) * float(CURVE_SAMPLES - 1);
+    int i = min(int(x), CURVE_SAMPLES - 2);
+    float f = x - float(i);
+    float width = size * mix(size_over_lifetime[i], size_over_lifetime[i + 1], f);
+    particleColor = mix(color_over_lifetime[i], color_over_lifetime[i + 1], f);
+    vec4 centerAndSize = vec4(particle.positionAndAge.xyz, width);
+#else
+    particleColor = color;
+#endif
+
+    vec3 offset = (camera_right * corner.x + camera_up * corner.y) * centerAndSize.w * 0.5;
+    gl_Position = view_projection * vec4(centerAndSize.xyz + offset, 1.0);
+}
@@ -0,0 +1,71 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform sampler2D image;
+uniform sampler2D depth;
+
+uniform float near_plane;
+uniform float far_plane;
+uniform float focus_distance; // World units from the camera
+uniform float blur_radius;    // Pixels at the strongest blur, 0 disables depth of field
+
+uniform float exposure; // Stops
+uniform float contrast;
+uniform float saturation;
+uniform float vignette;
+uniform int filter_mode; // 0 none, 1 grayscale, 2 sepia
+
+const vec2 disk[12] = vec2[](
+    vec2(-0.326, -0.406), vec2(-0.840, -0.074), vec2(-0.696, 0.457),
+    vec2(-0.203, 0.621), vec2(0.962, -0.195), vec2(0.473, -0.480),
+    vec2(0.519, 0.767), vec2(0.185, -0.893), vec2(0.507, 0.064),
+    vec2(0.896, 0.412), vec2(-0.322, -0.933), vec2(-0.792, -0.598)
+);
+
+float linear_depth(vec2 uv) {
+    float z = texture(depth, uv).r * 2.0 - 1.0;
+    return (2.0 * near_plane * far_plane) / (far_plane + near_plane - z * (far_plane - near_plane));
+}
+
+float circle_of_confusion(vec2 uv) {
+    float distance = linear_depth(uv);
+    return clamp(abs(distance - focus_distance) / max(focus_distance, 0.001), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(image, texCoord).rgb;
+
+    if (blur_radius > 0.0) {
+        vec2 texel = 1.0 / vec2(textureSize(image, 0));
+        float radius = circle_of_confusion(texCoord) * blur_radius;
+        vec3 sum = color;
+        float weight = 1.0;
+        for (int i = 0; i < 12; i++) {
+            vec2 uv = texCoord + disk[i] * radius * texel;
+            // Sharp foreground shouldn't bleed into the blurred background
+            float w = circle_of_confusion(uv);
+            sum += texture(image, uv).rgb * w;
+            weight += w;
+        }
+        color = sum / weight;
+    }
+
+    color *= exp2(exposure);
+    color = (color - 0.5) * contrast + 0.5;
+
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luminance), color, saturation);
+
+    if (filter_mode == 1) {
+        color = vec3(luminance);
+    } else if (filter_mode == 2) {
+        color = vec3(luminance) * vec3(1.07, 0.74, 0.43);
+    }
+
+    vec2 centered = texCoord - 0.5;
+    color *= 1.0 - vignette * dot(centered, centered) * 2.0;
+
+    FragColor = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
@@ -0,0 +1,74 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform samplerCube environment;
+uniform int face;
+uniform float roughness;
+uniform float environment_size; // Width of a face of the environment's first mip
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+vec3 cubeDirection(int face, vec2 uv) {
+    vec2 p = uv * 2.0 - 1.0;
+    switch (face) {
+        case 0: return normalize(vec3(1.0, -p.y, -p.x));
+        case 1: return normalize(vec3(-1.0, -p.y, p.x));
+        case 2: return normalize(vec3(p.x, 1.0, p.y));
+        case 3: return normalize(vec3(p.x, -1.0, -p.y));
+        case 4: return normalize(vec3(p.x, -p.y, 1.0));
+        default: return normalize(vec3(-p.x, -p.y, -1.0));
+    }
+}
+
+vec2 hammersley(uint i, uint count) {
+    return vec2(float(i) / float(count), float(bitfieldReverse(i)) * 2.3283064365386963e-10);
+}
+
+vec3 importanceSampleGGX(vec2 xi, vec3 normal, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 halfway = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * halfway.x + bitangent * halfway.y + normal * halfway.z);
+}
+
+float distributionGGX(float NdotH, float roughness) {
+    float a2 = roughness * roughness * roughness * roughness;
+    float denominator = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+// The specular part of the split sum, assuming the view direction equals the normal
+void main() {
+    vec3 normal = cubeDirection(face, texCoord);
+    vec3 view = normal;
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec3 halfway = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), normal, roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+        float NdotL = dot(normal, light);
+        if (NdotL > 0.0) {
+            // Sample a blurrier mip where few samples cover a large solid angle
+            float NdotH = max(dot(normal, halfway), 0.0);
+            float pdf = distributionGGX(NdotH, roughness) * 0.25 + 0.0001;
+            float texelSolidAngle = 4.0 * PI / (6.0 * environment_size * environment_size);
+            float sampleSolidAngle = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+            float mip = roughness == 0.0 ? 0.0 : 0.5 * log2(sampleSolidAngle / texelSolidAngle);
+
+            color += textureLod(environment, light, mip).rgb * NdotL;
+            weight += NdotL;
+        }
+    }
+
+    FragColor = vec4(color / max(weight, 0.0001), 1.0);
+}
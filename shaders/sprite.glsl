@@ -0,0 +1,11 @@
+#version 460 core
+
+in vec2 uv;
+in vec4 spriteColor;
+out vec4 FragColor;
+
+uniform sampler2D image;
+
+void main() {
+    FragColor = texture(image, uv) * spriteColor;
+}
@@ -0,0 +1,17 @@
+#version 460 core
+
+layout (location = 0) in vec2 corner;         // -0.5 to 0.5, one quad shared by every instance
+layout (location = 1) in vec4 centerAndSize;  // Per instance, XY plane center and size
+layout (location = 2) in vec4 color;          // Per instance
+layout (location = 3) in vec4 uvRect;         // Per instance, the sheet frame as offset and size
+
+out vec2 uv;
+out vec4 spriteColor;
+
+uniform mat4 view_projection;
+
+void main() {
+    uv = uvRect.xy + (corner + 0.5) * uvRect.zw;
+    spriteColor = color;
+    gl_Position = view_projection * vec4(centerAndSize.xy + corner * centerAndSize.zw, 0.0, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 460 core
+
+in vec2 uv;
+in vec4 textColor;
+out vec4 FragColor;
+
+uniform sampler2D atlas; // Glyph coverage in the red channel
+
+void main() {
+    float coverage = texture(atlas, uv).r;
+    if (coverage <= 0.0) {
+        discard;
+    }
+    FragColor = vec4(textColor.rgb, textColor.a * coverage);
+}
@@ -0,0 +1,16 @@
+#version 460 core
+
+layout (location = 0) in vec3 aPos;   // World space, laid out on the CPU
+layout (location = 1) in vec2 aUV;    // Into the font atlas
+layout (location = 2) in vec4 aColor;
+
+out vec2 uv;
+out vec4 textColor;
+
+uniform mat4 view_projection;
+
+void main() {
+    uv = aUV;
+    textColor = aColor;
+    gl_Position = view_projection * vec4(aPos, 1.0);
+}
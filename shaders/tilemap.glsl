@@ -0,0 +1,10 @@
+#version 460 core
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+uniform sampler2D atlas;
+
+void main() {
+    FragColor = texture(atlas, texCoord);
+}
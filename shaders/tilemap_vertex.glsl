@@ -0,0 +1,13 @@
+#version 460 core
+
+layout (location = 0) in vec2 position; // XY plane
+layout (location = 1) in vec2 aTexCoord; // Into the atlas
+
+out vec2 texCoord;
+
+uniform mat4 view_projection;
+
+void main() {
+    texCoord = aTexCoord;
+    gl_Position = view_projection * vec4(position, 0.0, 1.0);
+}
@@ -1,17 +1,38 @@
 #version 460 core
 
-layout (location = 0) in vec3 aPos;  // Position attribute
-layout (location = 1) in vec2 aTexCoord;  // Texture coordinate attribute
-layout (location = 2) in vec3 aColor; // Color attribute
+// Locations match the ATTRIB_ constants in mesh.rs, missing attributes read as zero
+layout (location = 0) in vec3 aPos;      // Position attribute
+layout (location = 1) in vec3 aNormal;   // Normal attribute
+layout (location = 2) in vec4 aTangent;  // Tangent attribute, w is the bitangent's sign
+layout (location = 3) in vec2 aTexCoord; // Texture coordinate attribute
+layout (location = 4) in vec2 aTexCoord1; // Second UV set, lightmaps and detail maps
+layout (location = 5) in vec4 aColor;    // Color attribute, alpha is 1 for RGB colors
+layout (location = 9) in mat4 aInstanceModel; // Per instance, only read when instanced is set
 
-out vec3 vertexColor; // Output color to the fragment shader
+out vec4 vertexColor; // Output color to the fragment shader
 out vec2 texCoord;
+out vec2 texCoord1;
+out vec3 worldNormal;
+out vec4 worldTangent;
+out vec3 worldPosition;
 
 uniform mat4 camMatrix;
+uniform mat4 model;
+uniform bool instanced; // camMatrix is then only the view projection
 
 void main() {
+    mat4 instanceModel = instanced ? aInstanceModel : mat4(1.0);
+    mat4 world = model * instanceModel;
+
     texCoord = aTexCoord;
+    texCoord1 = aTexCoord1;
     // gl_Position = vec4(aPos.x - 0.2 * aPos.y, aPos.y, 0.0, 1.0); // Convert 2D to 4D position
-    gl_Position = camMatrix * vec4(aPos, 1.0);
+    gl_Position = camMatrix * instanceModel * vec4(aPos, 1.0);
     vertexColor = aColor; // Pass color to fragment shader
+
+    // The inverse transpose keeps normals perpendicular under non-uniform scale
+    mat3 normalMatrix = transpose(inverse(mat3(world)));
+    worldNormal = normalMatrix * aNormal;
+    worldTangent = vec4(mat3(world) * aTangent.xyz, aTangent.w);
+    worldPosition = (world * vec4(aPos, 1.0)).xyz;
 }
@@ -0,0 +1,246 @@
+use glow::HasContext;
+
+use crate::{
+    cvars::{CVarRegistry, CVarValue},
+    opengl, shaders,
+    viewport::Viewport,
+};
+
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorblindMode {
+    Off,
+    Protanopia,   // No red cones
+    Deuteranopia, // No green cones
+    Tritanopia,   // No blue cones
+}
+
+impl ColorblindMode {
+    pub const ALL: [ColorblindMode; 4] = [
+        ColorblindMode::Off,
+        ColorblindMode::Protanopia,
+        ColorblindMode::Deuteranopia,
+        ColorblindMode::Tritanopia,
+    ];
+
+    /// The value stored in the `acc_colorblind_mode` cvar.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorblindMode::Off => "off",
+            ColorblindMode::Protanopia => "protanopia",
+            ColorblindMode::Deuteranopia => "deuteranopia",
+            ColorblindMode::Tritanopia => "tritanopia",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorblindMode::Off => "Off",
+            ColorblindMode::Protanopia => "Protanopia (red-blind)",
+            ColorblindMode::Deuteranopia => "Deuteranopia (green-blind)",
+            ColorblindMode::Tritanopia => "Tritanopia (blue-blind)",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    // Machado et al. 2009 at full severity, rows of a linear RGB matrix
+    fn simulation_matrix(&self) -> [f32; 9] {
+        match self {
+            ColorblindMode::Off => [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            ColorblindMode::Protanopia => [
+                0.152286, 1.052583, -0.204868, //
+                0.114503, 0.786281, 0.099216, //
+                -0.003882, -0.048116, 1.051998,
+            ],
+            ColorblindMode::Deuteranopia => [
+                0.367322, 0.860646, -0.227968, //
+                0.280085, 0.672501, 0.047413, //
+                -0.011820, 0.042940, 0.968881,
+            ],
+            ColorblindMode::Tritanopia => [
+                1.255528, -0.076749, -0.178779, //
+                -0.078411, 0.930809, 0.147602, //
+                0.004733, 0.691367, 0.303900,
+            ],
+        }
+    }
+}
+
+/// The accessibility cvars in one place so the renderer and the UI read them the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccessibilitySettings {
+    pub colorblind_mode: ColorblindMode,
+    pub simulate: bool, // Show what a colorblind player sees instead of correcting for it
+    pub strength: f32,
+    pub ui_scale: f32,
+}
+
+impl AccessibilitySettings {
+    pub fn from_cvars(cvars: &CVarRegistry) -> Self {
+        let mode = cvars
+            .get("acc_colorblind_mode")
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+
+        Self {
+            colorblind_mode: ColorblindMode::from_name(&mode).unwrap_or(ColorblindMode::Off),
+            simulate: cvars.get_bool("acc_colorblind_simulate"),
+            strength: cvars.get_float("acc_colorblind_strength").clamp(0.0, 1.0),
+            ui_scale: cvars
+                .get_float("ui_scale")
+                .clamp(MIN_UI_SCALE, MAX_UI_SCALE),
+        }
+    }
+}
+
+/// Options menu section for the accessibility cvars, usable from the editor or a game's own menus.
+pub fn settings_ui(ui: &mut egui::Ui, cvars: &mut CVarRegistry) {
+    let settings = AccessibilitySettings::from_cvars(cvars);
+    let mut edited = settings;
+
+    egui::Grid::new("Accessibility")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Colorblind filter");
+            egui::ComboBox::from_id_salt("ColorblindMode")
+                .selected_text(edited.colorblind_mode.label())
+                .show_ui(ui, |ui| {
+                    for mode in ColorblindMode::ALL {
+                        ui.selectable_value(&mut edited.colorblind_mode, mode, mode.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Filter");
+            ui.add_enabled_ui(edited.colorblind_mode != ColorblindMode::Off, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut edited.simulate, false, "Correct");
+                    ui.radio_value(&mut edited.simulate, true, "Simulate");
+                });
+            });
+            ui.end_row();
+
+            ui.label("Filter strength");
+            ui.add_enabled(
+                edited.colorblind_mode != ColorblindMode::Off,
+                egui::Slider::new(&mut edited.strength, 0.0..=1.0),
+            );
+            ui.end_row();
+
+            ui.label("UI scale");
+            ui.add(
+                egui::Slider::new(&mut edited.ui_scale, MIN_UI_SCALE..=MAX_UI_SCALE).step_by(0.05),
+            );
+            ui.end_row();
+        });
+
+    if edited == settings {
+        return;
+    }
+
+    let changes = [
+        (
+            "acc_colorblind_mode",
+            CVarValue::Str(edited.colorblind_mode.name().to_string()),
+        ),
+        ("acc_colorblind_simulate", CVarValue::Bool(edited.simulate)),
+        ("acc_colorblind_strength", CVarValue::Float(edited.strength)),
+        ("ui_scale", CVarValue::Float(edited.ui_scale)),
+    ];
+    for (name, value) in changes {
+        if let Err(e) = cvars.set(name, value) {
+            log::error!("{}", e);
+        }
+    }
+}
+
+/// Post process pass that simulates or corrects for color blindness over the viewport.
+pub struct ColorblindFilter {
+    program: glow::Program,
+    vertex_array: glow::VertexArray, // Empty, core profile needs one bound to draw
+    texture: glow::Texture,          // Copy of the viewport the pass samples from
+}
+
+impl ColorblindFilter {
+    pub fn new(gl: &glow::Context) -> Result<Self, String> {
+        let program =
+            shaders::load_program(gl, "shaders/fullscreen.glsl", "shaders/colorblind.glsl")?;
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array()?;
+            let texture = opengl::create_copy_texture(gl)?;
+
+            Ok(Self {
+                program,
+                vertex_array,
+                texture,
+            })
+        }
+    }
+
+    /// Filters the viewport of the bound framebuffer in place, call after the scene has been rendered.
+    pub fn apply(&self, gl: &glow::Context, viewport: &Viewport, settings: &AccessibilitySettings) {
+        if settings.colorblind_mode == ColorblindMode::Off || settings.strength <= 0.0 {
+            return;
+        }
+        if viewport.width <= 0 || viewport.height <= 0 {
+            return;
+        }
+
+        unsafe {
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+            gl.copy_tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8,
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                0,
+            );
+
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            gl.use_program(Some(self.program));
+            gl.uniform_1_i32(gl.get_uniform_location(self.program, "image").as_ref(), 0);
+            gl.uniform_matrix_3_f32_slice(
+                gl.get_uniform_location(self.program, "simulation").as_ref(),
+                true, // Rows, GLSL wants columns
+                &settings.colorblind_mode.simulation_matrix(),
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "correct").as_ref(),
+                !settings.simulate as i32,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "strength").as_ref(),
+                settings.strength,
+            );
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_texture(self.texture);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+// Small static meshes that share a material merged into one buffer and drawn with one call.
+// Their vertices are moved to world space when the batch is built, so a batch is only used
+// while none of its meshes have moved, they're drawn on their own otherwise.
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+use crate::{
+    data::{MaterialOverride, StaticPrimitiveInstance},
+    error::{EngineError, EngineResult},
+    handles::{MeshHandle, TextureHandle},
+    loader::AssetLoader,
+    mesh::{self, StaticMesh, ATTRIB_NORMAL, ATTRIB_POSITION, ATTRIB_TANGENT},
+    opengl::{Layout, StaticRenderData},
+    scene_graph::SceneNode,
+};
+
+pub const BATCH_MAX_TRIANGLES: usize = 1024; // Bigger meshes gain little from sharing a draw
+const MIN_BATCH_MESHES: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+struct BatchMember {
+    mesh: usize, // Into the scene's static meshes
+    handle: MeshHandle,
+    world: Matrix4<f32>, // What it was built with
+}
+
+// Where a primitive's material comes from. Every mesh instance makes its own GL textures for
+// it, so those can't tell two copies of the same prop apart.
+#[derive(Debug, Clone, PartialEq)]
+enum MaterialSource {
+    Override(MaterialOverride),
+    Mesh(MeshHandle, usize), // The primitive's own, by primitive index
+}
+
+impl MaterialSource {
+    fn of(static_mesh: &StaticMesh, primitive: &StaticPrimitiveInstance) -> Self {
+        match &primitive.material {
+            Some(material) => Self::Override(material.clone()),
+            None => Self::Mesh(static_mesh.handle, primitive.primitive_index),
+        }
+    }
+}
+
+/// Merged buffers only, the textures stay with the meshes. It's drawn with its first mesh's
+/// material.
+pub struct StaticBatch {
+    pub render_data: StaticRenderData,
+    material: MaterialSource,
+    base_color: Option<TextureHandle>,
+    members: Vec<BatchMember>,
+}
+
+impl StaticBatch {
+    pub fn meshes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.members.iter().map(|member| member.mesh)
+    }
+
+    /// The primitive whose material and base color map the batch is drawn with.
+    pub fn material<'a>(
+        &self,
+        scene: &'a SceneNode,
+    ) -> Option<(&'a StaticPrimitiveInstance, Option<glow::Texture>)> {
+        let static_mesh = scene.static_meshes.get(self.members.first()?.mesh)?;
+        let base_color = static_mesh.base_color_map.map(|(_, texture)| texture);
+        Some((static_mesh.primitives.first()?, base_color))
+    }
+
+    /// False once one of its meshes moved, was removed or got another material since the
+    /// batch was built.
+    pub fn is_current(&self, scene: &SceneNode) -> bool {
+        self.members.iter().all(|member| {
+            let Some(static_mesh) = scene.static_meshes.get(member.mesh) else {
+                return false;
+            };
+            let same_material = match static_mesh.primitives.as_slice() {
+                [primitive] => MaterialSource::of(static_mesh, primitive) == self.material,
+                _ => false,
+            };
+            static_mesh.handle == member.handle
+                && same_material
+                && static_mesh.base_color_map.map(|(handle, _)| handle) == self.base_color
+                && scene.static_mesh_world_matrix(member.mesh) == member.world
+        })
+    }
+
+    pub fn destroy(&self, context: &glow::Context) {
+        self.render_data.destroy(context);
+    }
+}
+
+// Meshes that can go in the same batch
+struct Group {
+    material: MaterialSource,
+    base_color: Option<TextureHandle>,
+    layouts: Vec<Layout>,
+    stride: i32,
+    members: Vec<BatchMember>,
+}
+
+/// Batches every group of at least two small meshes with the same material and vertex layout.
+/// Meshes with lower detail levels or more than one primitive are left out.
+pub fn build(
+    context: &glow::Context,
+    scene: &SceneNode,
+    asset_loader: &AssetLoader,
+) -> Vec<StaticBatch> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (i, static_mesh) in scene.static_meshes.iter().enumerate() {
+        let Some(primitive) = batchable_primitive(static_mesh) else {
+            continue;
+        };
+        let Some(loaded) = asset_loader
+            .loaded_mesh_data
+            .get(&static_mesh.handle)
+            .and_then(|mesh| mesh.primitives.get(primitive.primitive_index))
+        else {
+            continue;
+        };
+        if loaded.mode != glow::TRIANGLES || loaded.vertex_data.joints.is_some() {
+            continue;
+        }
+        let layouts = mesh::determine_layouts(&loaded.vertex_data);
+        let Ok(stride) = mesh::calculate_stride(&layouts) else {
+            continue;
+        };
+
+        let member = BatchMember {
+            mesh: i,
+            handle: static_mesh.handle,
+            world: scene.static_mesh_world_matrix(i),
+        };
+        let material = MaterialSource::of(static_mesh, primitive);
+        let base_color = static_mesh.base_color_map.map(|(handle, _)| handle);
+        let group = groups.iter_mut().find(|group| {
+            group.material == material
+                && group.base_color == base_color
+                && same_layouts(&group.layouts, &layouts)
+        });
+        match group {
+            Some(group) => group.members.push(member),
+            None => groups.push(Group {
+                material,
+                base_color,
+                layouts,
+                stride,
+                members: vec![member],
+            }),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| group.members.len() >= MIN_BATCH_MESHES)
+        .filter_map(|group| match merge(context, scene, asset_loader, group) {
+            Ok(batch) => Some(batch),
+            Err(e) => {
+                log::error!("Failed to build a static batch: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn batchable_primitive(static_mesh: &StaticMesh) -> Option<&StaticPrimitiveInstance> {
+    let [primitive] = static_mesh.primitives.as_slice() else {
+        return None;
+    };
+    let small = static_mesh.triangle_count(0) <= BATCH_MAX_TRIANGLES;
+    (small && static_mesh.lods.is_empty() && primitive.upload.is_done()).then_some(primitive)
+}
+
+fn same_layouts(a: &[Layout], b: &[Layout]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            (a.index, a.size, a.gl_type, a.offset) == (b.index, b.size, b.gl_type, b.offset)
+        })
+}
+
+// Interleaves every member's vertices in world space, one after another
+fn merge(
+    context: &glow::Context,
+    scene: &SceneNode,
+    asset_loader: &AssetLoader,
+    group: Group,
+) -> EngineResult<StaticBatch> {
+    let floats_per_vertex = group.stride as usize / std::mem::size_of::<f32>();
+    let offset_of = |index: u32| {
+        let layout = group.layouts.iter().find(|layout| layout.index == index);
+        layout.map(|layout| layout.offset / std::mem::size_of::<f32>())
+    };
+    let (normal, tangent) = (offset_of(ATTRIB_NORMAL), offset_of(ATTRIB_TANGENT));
+    let position = offset_of(ATTRIB_POSITION).unwrap_or(0);
+
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for member in &group.members {
+        let static_mesh = &scene.static_meshes[member.mesh];
+        let primitive_index = static_mesh.primitives[0].primitive_index;
+        let loaded = asset_loader
+            .loaded_mesh_data
+            .get(&member.handle)
+            .and_then(|mesh| mesh.primitives.get(primitive_index))
+            .ok_or(EngineError::MissingMesh(member.handle))?;
+
+        let base = (vertices.len() / floats_per_vertex) as u32;
+        let mut interleaved = mesh::interleave_vertex_data(&loaded.vertex_data)?;
+        let to_world_normal = normal_matrix(&member.world);
+        for vertex in interleaved.chunks_exact_mut(floats_per_vertex) {
+            let p = &mut vertex[position..position + 3];
+            let world = member.world.transform_point(Point3::new(p[0], p[1], p[2]));
+            p.copy_from_slice(&[world.x, world.y, world.z]);
+            for offset in [normal, tangent].into_iter().flatten() {
+                let v = &mut vertex[offset..offset + 3];
+                let direction = (to_world_normal * Vector3::new(v[0], v[1], v[2])).normalize();
+                v.copy_from_slice(&[direction.x, direction.y, direction.z]);
+            }
+        }
+        vertices.extend(interleaved);
+
+        let vertex_count = loaded.vertex_data.positions.len() as u32;
+        match &loaded.indices {
+            Some(member_indices) => indices.extend(member_indices.iter().map(|i| base + i)),
+            None => indices.extend(base..base + vertex_count),
+        }
+    }
+
+    let render_data =
+        StaticRenderData::new(context, &vertices, &indices, group.stride, group.layouts)?;
+    Ok(StaticBatch {
+        render_data,
+        material: group.material,
+        base_color: group.base_color,
+        members: group.members,
+    })
+}
+
+// Tangents get it too, close enough for the small props that end up in batches
+fn normal_matrix(world: &Matrix4<f32>) -> Matrix3<f32> {
+    let linear = Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate());
+    linear
+        .invert()
+        .map_or(linear, |inverse| inverse.transpose())
+}
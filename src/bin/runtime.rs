@@ -0,0 +1,7 @@
+// The shipped game, see `runtime::export_game` for what it expects next to it
+fn main() {
+    if let Err(e) = cruel_game_engine::runtime::run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
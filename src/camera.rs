@@ -1,7 +1,14 @@
-use cgmath::SquareMatrix;
+use cgmath::{InnerSpace, Rotation3, SquareMatrix};
 use egui::Pos2;
 
-#[derive(Debug)]
+/// Where a perspective camera's aspect ratio comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AspectMode {
+    Viewport, // Follows what it's drawn into, see `fit_viewport`
+    Fixed,
+}
+
+#[derive(Debug, Clone)]
 pub struct PerspectiveCamera {
     pub name: String,
 
@@ -14,6 +21,7 @@ pub struct PerspectiveCamera {
 
     pub fov: f32, // in deg
     pub aspect_ratio: f32,
+    pub aspect_mode: AspectMode,
     pub width: u32,
     pub height: u32,
     pub near_plane: f32,
@@ -42,11 +50,20 @@ pub trait Camera {
     fn get_height(&self) -> u32;
 
     fn get_up(&self) -> cgmath::Vector3<f32>;
+    fn set_up(&mut self, new: cgmath::Vector3<f32>);
     fn get_first_click(&self) -> bool;
     fn set_first_click(&mut self, value: bool);
 
     fn get_last_mouse_pos(&self) -> Pos2;
     fn set_last_mouse_pos(&mut self, new: Pos2);
+
+    // For the Properties panel, which has fields for each kind
+    fn as_perspective_mut(&mut self) -> Option<&mut PerspectiveCamera> {
+        None
+    }
+    fn as_orthographic_mut(&mut self) -> Option<&mut OrthographicCamera> {
+        None
+    }
 }
 
 impl PerspectiveCamera {
@@ -74,6 +91,7 @@ impl PerspectiveCamera {
 
             fov,
             aspect_ratio,
+            aspect_mode: AspectMode::Viewport,
 
             width,
             height,
@@ -102,6 +120,15 @@ impl PerspectiveCamera {
     pub fn set_far_plane(&mut self, far_plane: f32) {
         self.far_plane = far_plane;
     }
+
+    /// Takes the size it's drawn at, and its aspect ratio too unless that's fixed.
+    pub fn fit_viewport(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        if self.aspect_mode == AspectMode::Viewport {
+            self.aspect_ratio = width as f32 / height.max(1) as f32;
+        }
+    }
 }
 
 impl Camera for PerspectiveCamera {
@@ -154,6 +181,10 @@ impl Camera for PerspectiveCamera {
         self.up
     }
 
+    fn set_up(&mut self, new: cgmath::Vector3<f32>) {
+        self.up = new
+    }
+
     fn get_first_click(&self) -> bool {
         self.first_click
     }
@@ -185,6 +216,10 @@ impl Camera for PerspectiveCamera {
     fn set_last_mouse_pos(&mut self, new: Pos2) {
         self.last_mouse_pos = new
     }
+
+    fn as_perspective_mut(&mut self) -> Option<&mut PerspectiveCamera> {
+        Some(self)
+    }
 }
 
 #[derive(Debug)]
@@ -202,6 +237,7 @@ pub struct OrthographicCamera {
     pub right: f32,
     pub bottom: f32,
     pub top: f32,
+    pub aspect_mode: AspectMode, // With Viewport, left and right follow top and bottom
     pub near_plane: f32,
     pub far_plane: f32,
     pub speed: f32,
@@ -238,6 +274,7 @@ impl OrthographicCamera {
             right: 10.0,
             bottom: -10.0,
             top: 10.0,
+            aspect_mode: AspectMode::Viewport,
             near_plane: 0.1,
             far_plane: 100.0,
             speed: 0.4,
@@ -246,6 +283,20 @@ impl OrthographicCamera {
             last_mouse_pos: Pos2::new(0.0, 0.0),
         }
     }
+
+    /// Takes the size it's drawn at. Unless the aspect is fixed, left and right are moved so
+    /// the box has the same shape, keeping its height and middle.
+    pub fn fit_viewport(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        if self.aspect_mode == AspectMode::Viewport {
+            let aspect_ratio = width as f32 / height.max(1) as f32;
+            let half_width = (self.top - self.bottom) / 2.0 * aspect_ratio;
+            let middle = (self.left + self.right) / 2.0;
+            self.left = middle - half_width;
+            self.right = middle + half_width;
+        }
+    }
 }
 
 impl Camera for OrthographicCamera {
@@ -296,6 +347,10 @@ impl Camera for OrthographicCamera {
         self.up
     }
 
+    fn set_up(&mut self, new: cgmath::Vector3<f32>) {
+        self.up = new
+    }
+
     fn get_first_click(&self) -> bool {
         self.first_click
     }
@@ -327,4 +382,309 @@ impl Camera for OrthographicCamera {
     fn set_last_mouse_pos(&mut self, new: Pos2) {
         self.last_mouse_pos = new
     }
+
+    fn as_orthographic_mut(&mut self) -> Option<&mut OrthographicCamera> {
+        Some(self)
+    }
+}
+
+/// The Properties panel fields of a perspective camera. Returns true when something changed.
+pub fn perspective_camera_ui(ui: &mut egui::Ui, camera: &mut PerspectiveCamera) -> bool {
+    let mut changed = false;
+    egui::Grid::new("Perspective Camera")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("FOV");
+            changed |= ui
+                .add(egui::Slider::new(&mut camera.fov, 1.0..=170.0).suffix("°"))
+                .changed();
+            ui.end_row();
+
+            changed |= clip_planes_ui(ui, &mut camera.near_plane, &mut camera.far_plane);
+
+            ui.label("Aspect ratio");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .radio_value(&mut camera.aspect_mode, AspectMode::Viewport, "Viewport")
+                    .changed();
+                changed |= ui
+                    .radio_value(&mut camera.aspect_mode, AspectMode::Fixed, "Fixed")
+                    .changed();
+                if camera.aspect_mode == AspectMode::Fixed {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut camera.aspect_ratio)
+                                .speed(0.01)
+                                .range(0.1..=10.0),
+                        )
+                        .changed();
+                }
+            });
+            ui.end_row();
+
+            changed |= movement_ui(ui, &mut camera.speed, &mut camera.sensitivity);
+        });
+    changed
+}
+
+/// Like `perspective_camera_ui`, with the extents of the box it sees instead of a FOV.
+pub fn orthographic_camera_ui(ui: &mut egui::Ui, camera: &mut OrthographicCamera) -> bool {
+    let mut changed = false;
+    egui::Grid::new("Orthographic Camera")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Aspect ratio");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .radio_value(&mut camera.aspect_mode, AspectMode::Viewport, "Viewport")
+                    .changed();
+                changed |= ui
+                    .radio_value(&mut camera.aspect_mode, AspectMode::Fixed, "Fixed")
+                    .changed();
+            });
+            ui.end_row();
+
+            // Left and right follow the viewport unless the aspect is fixed
+            let fixed = camera.aspect_mode == AspectMode::Fixed;
+            let extents = [
+                ("Left", &mut camera.left, fixed),
+                ("Right", &mut camera.right, fixed),
+                ("Bottom", &mut camera.bottom, true),
+                ("Top", &mut camera.top, true),
+            ];
+            for (label, value, enabled) in extents {
+                ui.label(label);
+                changed |= ui
+                    .add_enabled(enabled, egui::DragValue::new(value).speed(0.1))
+                    .changed();
+                ui.end_row();
+            }
+            // A box without a width or height can't be projected
+            if camera.right - camera.left < 0.01 {
+                camera.right = camera.left + 0.01;
+            }
+            if camera.top - camera.bottom < 0.01 {
+                camera.top = camera.bottom + 0.01;
+            }
+
+            changed |= clip_planes_ui(ui, &mut camera.near_plane, &mut camera.far_plane);
+            changed |= movement_ui(ui, &mut camera.speed, &mut camera.sensitivity);
+        });
+    changed
+}
+
+fn clip_planes_ui(ui: &mut egui::Ui, near_plane: &mut f32, far_plane: &mut f32) -> bool {
+    ui.label("Near plane");
+    let near = ui.add(
+        egui::DragValue::new(near_plane)
+            .speed(0.01)
+            .range(0.001..=*far_plane - 0.001),
+    );
+    ui.end_row();
+    ui.label("Far plane");
+    let far = ui.add(
+        egui::DragValue::new(far_plane)
+            .speed(1.0)
+            .range(*near_plane + 0.001..=100_000.0),
+    );
+    ui.end_row();
+    near.changed() || far.changed()
+}
+
+fn movement_ui(ui: &mut egui::Ui, speed: &mut f32, sensitivity: &mut f32) -> bool {
+    ui.label("Speed");
+    let speed = ui.add(egui::DragValue::new(speed).speed(0.1).range(0.1..=100.0));
+    ui.end_row();
+    ui.label("Sensitivity");
+    let sensitivity = ui.add(
+        egui::DragValue::new(sensitivity)
+            .speed(1.0)
+            .range(1.0..=1000.0),
+    );
+    ui.end_row();
+    speed.changed() || sensitivity.changed()
+}
+
+/// The views the viewport's axis gizmo snaps the editor camera to, named after the side the
+/// camera looks from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisView {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl AxisView {
+    pub const ALL: [AxisView; 6] = [
+        AxisView::PositiveX,
+        AxisView::NegativeX,
+        AxisView::PositiveY,
+        AxisView::NegativeY,
+        AxisView::PositiveZ,
+        AxisView::NegativeZ,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AxisView::PositiveX => "X",
+            AxisView::NegativeX => "-X",
+            AxisView::PositiveY => "Y",
+            AxisView::NegativeY => "-Y",
+            AxisView::PositiveZ => "Z",
+            AxisView::NegativeZ => "-Z",
+        }
+    }
+
+    /// Unit vector from the origin towards the side the camera looks from.
+    pub fn axis(&self) -> cgmath::Vector3<f32> {
+        match self {
+            AxisView::PositiveX => cgmath::Vector3::unit_x(),
+            AxisView::NegativeX => -cgmath::Vector3::unit_x(),
+            AxisView::PositiveY => cgmath::Vector3::unit_y(),
+            AxisView::NegativeY => -cgmath::Vector3::unit_y(),
+            AxisView::PositiveZ => cgmath::Vector3::unit_z(),
+            AxisView::NegativeZ => -cgmath::Vector3::unit_z(),
+        }
+    }
+
+    /// Blender's numpad layout on the number keys: 1 front, 3 right, 7 top and Ctrl for the
+    /// opposite side.
+    pub fn shortcut(&self) -> egui::KeyboardShortcut {
+        let (modifiers, key) = match self {
+            AxisView::PositiveZ => (egui::Modifiers::NONE, egui::Key::Num1),
+            AxisView::NegativeZ => (egui::Modifiers::COMMAND, egui::Key::Num1),
+            AxisView::PositiveX => (egui::Modifiers::NONE, egui::Key::Num3),
+            AxisView::NegativeX => (egui::Modifiers::COMMAND, egui::Key::Num3),
+            AxisView::PositiveY => (egui::Modifiers::NONE, egui::Key::Num7),
+            AxisView::NegativeY => (egui::Modifiers::COMMAND, egui::Key::Num7),
+        };
+        egui::KeyboardShortcut::new(modifiers, key)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AxisView::PositiveX => "Right",
+            AxisView::NegativeX => "Left",
+            AxisView::PositiveY => "Top",
+            AxisView::NegativeY => "Bottom",
+            AxisView::PositiveZ => "Front",
+            AxisView::NegativeZ => "Back",
+        }
+    }
+
+    // Looking straight down or up, Y can't be up
+    fn up(&self) -> cgmath::Vector3<f32> {
+        match self {
+            AxisView::PositiveY => -cgmath::Vector3::unit_z(),
+            AxisView::NegativeY => cgmath::Vector3::unit_z(),
+            _ => cgmath::Vector3::unit_y(),
+        }
+    }
+}
+
+/// Moves the camera onto `view`'s side of `target`, `distance` away and looking at it.
+pub fn look_from(
+    camera: &mut dyn Camera,
+    view: AxisView,
+    target: cgmath::Point3<f32>,
+    distance: f32,
+) {
+    camera.set_position(target + view.axis() * distance);
+    camera.set_orientation(-view.axis());
+    camera.set_up(view.up());
+    camera.update_matrices();
+}
+
+/// WASD + Space/Down to move, drag with the left mouse button to look around.
+pub fn fly(camera: &mut dyn Camera, input: &egui::InputState, delta_time: f32) {
+    if input.key_down(egui::Key::W) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed() * camera.get_orientation() * delta_time,
+        );
+    }
+    if input.key_down(egui::Key::A) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed()
+                    * -cgmath::Vector3::normalize(cgmath::Vector3::cross(
+                        camera.get_orientation(),
+                        camera.get_up(),
+                    ))
+                    * delta_time,
+        );
+    }
+    if input.key_down(egui::Key::S) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed()
+                    * -camera.get_orientation()
+                    * delta_time,
+        );
+    }
+    if input.key_down(egui::Key::D) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed()
+                    * cgmath::Vector3::normalize(cgmath::Vector3::cross(
+                        camera.get_orientation(),
+                        camera.get_up(),
+                    ))
+                    * delta_time,
+        );
+    }
+    if input.key_down(egui::Key::Space) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed() * camera.get_up() * delta_time,
+        );
+    }
+    if input.key_down(egui::Key::ArrowDown) {
+        camera.set_position(
+            camera.get_position()
+                + camera.get_speed() * -camera.get_up() * delta_time,
+        );
+    }
+    if input.pointer.button_down(egui::PointerButton::Primary) {
+        if camera.get_first_click() {
+            if let Some(pos) = input.pointer.hover_pos() {
+                camera.set_last_mouse_pos(pos); // store initial pos
+            }
+            camera.set_first_click(false);
+        }
+
+        if let Some(pos) = input.pointer.hover_pos() {
+            // Calculate delta since last frame
+            let delta_x = pos.x - camera.get_last_mouse_pos().x;
+            let delta_y = pos.y - camera.get_last_mouse_pos().y;
+
+            let rot_x = camera.get_sensitivity() * delta_y / camera.get_height() as f32;
+            let rot_y = camera.get_sensitivity() * delta_x / camera.get_width() as f32;
+
+            let right = camera.get_orientation().cross(camera.get_up()).normalize();
+            let pitch_quat =
+                cgmath::Quaternion::from_axis_angle(right, cgmath::Deg(-rot_x));
+
+            let new_orientation = pitch_quat * camera.get_orientation();
+
+            let up_dot = new_orientation.dot(camera.get_up());
+            if up_dot.abs() < 0.99 {
+                camera.set_orientation(new_orientation);
+            }
+
+            let yaw_quat = cgmath::Quaternion::from_axis_angle(
+                camera.get_up(),
+                cgmath::Deg(-rot_y),
+            );
+            camera.set_orientation(yaw_quat * camera.get_orientation());
+
+            // Update last mouse pos
+            camera.set_last_mouse_pos(pos);
+        }
+    } else {
+        camera.set_first_click(true);
+    }
 }
@@ -0,0 +1,228 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossbeam_channel::{bounded, Sender};
+use glow::HasContext;
+
+use crate::viewport::Viewport;
+
+pub const CAPTURE_DIRECTORY: &str = "captures";
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+// Frames waiting for the writer thread, when it falls behind the render thread blocks
+const MAX_QUEUED_FRAMES: usize = 16;
+
+struct CapturedFrame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>, // RGBA8, bottom row first like glReadPixels returns it
+}
+
+enum CaptureOutput {
+    ImageSequence(PathBuf),
+    Ffmpeg(Child),
+}
+
+fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// RGBA rows of the viewport, bottom row first like OpenGL.
+pub fn read_viewport(gl: &glow::Context, viewport: &Viewport) -> Vec<u8> {
+    let mut pixels = vec![0u8; (viewport.width.max(0) * viewport.height.max(0) * 4) as usize];
+    unsafe {
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+        gl.read_pixels(
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(Some(&mut pixels)),
+        );
+    }
+    pixels
+}
+
+/// Saves the viewport as a PNG in `screenshots/`, the encoding happens on a background thread.
+/// Returns the path the image will be written to.
+pub fn save_screenshot(gl: &glow::Context, viewport: &Viewport) -> Result<PathBuf, String> {
+    let width = viewport.width.max(0) as u32;
+    let height = viewport.height.max(0) as u32;
+    let image = image::RgbaImage::from_raw(width, height, read_viewport(gl, viewport))
+        .ok_or("The viewport is empty")?;
+
+    std::fs::create_dir_all(SCREENSHOT_DIRECTORY)
+        .map_err(|e| format!("Failed to create {}: {}", SCREENSHOT_DIRECTORY, e))?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = PathBuf::from(SCREENSHOT_DIRECTORY).join(format!("screenshot_{}.png", timestamp));
+
+    let output = path.clone();
+    std::thread::spawn(move || {
+        match image::imageops::flip_vertical(&image).save(&output) {
+            Ok(()) => log::info!("Saved screenshot {:?}", output),
+            Err(e) => log::error!("Failed to write {:?}: {}", output, e),
+        }
+    });
+
+    Ok(path)
+}
+
+/// Records the viewport every frame, to an mp4 through ffmpeg when it is on the PATH
+/// and to a PNG sequence otherwise. Encoding happens on a background thread.
+pub struct FrameRecorder {
+    frame_tx: Option<Sender<CapturedFrame>>,
+    worker: Option<JoinHandle<()>>,
+    width: u32,
+    height: u32,
+    pub frames_captured: u64,
+    pub output: PathBuf,
+}
+
+impl FrameRecorder {
+    pub fn start(viewport: &Viewport, fps: u32) -> Result<Self, String> {
+        let width = viewport.width.max(1) as u32;
+        let height = viewport.height.max(1) as u32;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        std::fs::create_dir_all(CAPTURE_DIRECTORY)
+            .map_err(|e| format!("Failed to create {}: {}", CAPTURE_DIRECTORY, e))?;
+
+        let (output, mut capture_output) = if ffmpeg_available() {
+            let path = PathBuf::from(CAPTURE_DIRECTORY).join(format!("capture_{}.mp4", timestamp));
+            let child = Command::new("ffmpeg")
+                .args(["-y", "-loglevel", "error"])
+                .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+                .args(["-s", &format!("{}x{}", width, height)])
+                .args(["-r", &fps.to_string()])
+                .args(["-i", "-"])
+                // GL rows are bottom up, and yuv420p needs even dimensions
+                .args(["-vf", "vflip,pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+                .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                .arg(&path)
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+            (path, CaptureOutput::Ffmpeg(child))
+        } else {
+            let path = PathBuf::from(CAPTURE_DIRECTORY).join(format!("capture_{}", timestamp));
+            std::fs::create_dir_all(&path)
+                .map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+            (path.clone(), CaptureOutput::ImageSequence(path))
+        };
+
+        let (frame_tx, frame_rx) = bounded::<CapturedFrame>(MAX_QUEUED_FRAMES);
+
+        let worker = std::thread::spawn(move || {
+            let mut index = 0u64;
+            for frame in frame_rx {
+                match &mut capture_output {
+                    CaptureOutput::ImageSequence(directory) => {
+                        let Some(image) =
+                            image::RgbaImage::from_raw(frame.width, frame.height, frame.pixels)
+                        else {
+                            continue;
+                        };
+                        let path = directory.join(format!("frame_{:05}.png", index));
+                        if let Err(e) = image::imageops::flip_vertical(&image).save(&path) {
+                            log::error!("Failed to write {:?}: {}", path, e);
+                        }
+                    }
+                    CaptureOutput::Ffmpeg(child) => {
+                        let stdin = child.stdin.as_mut().unwrap();
+                        if let Err(e) = stdin.write_all(&frame.pixels) {
+                            log::error!("Failed to send frame to ffmpeg: {}", e);
+                            break;
+                        }
+                    }
+                }
+                index += 1;
+            }
+
+            if let CaptureOutput::Ffmpeg(mut child) = capture_output {
+                drop(child.stdin.take()); // Closing stdin tells ffmpeg to finish the file
+                match child.wait() {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => log::error!("ffmpeg exited with {}", status),
+                    Err(e) => log::error!("Failed to wait for ffmpeg: {}", e),
+                }
+            }
+        });
+
+        log::info!("Recording {}x{} to {:?}", width, height, output);
+
+        Ok(Self {
+            frame_tx: Some(frame_tx),
+            worker: Some(worker),
+            width,
+            height,
+            frames_captured: 0,
+            output,
+        })
+    }
+
+    /// Reads the viewport from the current framebuffer, call after the scene has been rendered.
+    pub fn capture(&mut self, gl: &glow::Context, viewport: &Viewport) {
+        let Some(frame_tx) = &self.frame_tx else {
+            return;
+        };
+
+        // A video needs every frame to have the same size, resizing mid recording drops frames
+        if viewport.width as u32 != self.width || viewport.height as u32 != self.height {
+            return;
+        }
+
+        let pixels = read_viewport(gl, viewport);
+
+        if frame_tx
+            .send(CapturedFrame {
+                width: self.width,
+                height: self.height,
+                pixels,
+            })
+            .is_ok()
+        {
+            self.frames_captured += 1;
+        }
+    }
+
+    /// Waits for the queued frames to be written.
+    pub fn stop(mut self) {
+        self.finish();
+        log::info!(
+            "Recorded {} frames to {:?}",
+            self.frames_captured,
+            self.output
+        );
+    }
+
+    fn finish(&mut self) {
+        self.frame_tx = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
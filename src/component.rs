@@ -0,0 +1,331 @@
+// What a static mesh can do besides being drawn, added and removed in the Properties panel
+
+use std::{any::Any, fmt, sync::RwLock};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::inspect::Inspect;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentKind {
+    Script,
+    RigidBody,
+    AudioSource,
+    Health,
+    Team,
+    Custom(String), // Registered by the game, see register
+}
+
+impl ComponentKind {
+    pub const ALL: [ComponentKind; 5] = [
+        ComponentKind::Script,
+        ComponentKind::RigidBody,
+        ComponentKind::AudioSource,
+        ComponentKind::Health,
+        ComponentKind::Team,
+    ];
+
+    /// The built in kinds and then every registered one.
+    pub fn all() -> Vec<ComponentKind> {
+        let registry = REGISTRY.read().unwrap();
+        let custom = registry
+            .iter()
+            .map(|ty| ComponentKind::Custom(ty.name.into()));
+        Self::ALL.into_iter().chain(custom).collect()
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            ComponentKind::Script => "Script",
+            ComponentKind::RigidBody => "Rigid Body",
+            ComponentKind::AudioSource => "Audio Source",
+            ComponentKind::Health => "Health",
+            ComponentKind::Team => "Team",
+            ComponentKind::Custom(name) => name,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ComponentKind::Script => "Runs a script from the project's scripts folder",
+            ComponentKind::RigidBody => "Moved by physics instead of staying where it's placed",
+            ComponentKind::AudioSource => "Plays a sound from where the mesh is",
+            ComponentKind::Health => "Can be damaged, see GameplayWorld",
+            ComponentKind::Team => "Isn't hurt by damage from its own team",
+            ComponentKind::Custom(name) => match registered(name) {
+                Some(ty) => ty.description,
+                None => "Not registered by the game, kept as it was saved",
+            },
+        }
+    }
+}
+
+// Each variant's fields are shown by #[derive(Inspect)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Inspect)]
+pub enum Component {
+    Script {
+        path: String,
+    },
+    RigidBody {
+        #[inspect(speed = 0.1, min = 0.001)]
+        mass: f32,
+        kinematic: bool, // Only moved by scripts, pushes others without being pushed
+        gravity: bool,
+    },
+    AudioSource {
+        clip: String,
+        #[inspect(min = 0.0, max = 1.0)]
+        volume: f32,
+        #[inspect(label = "Loop")]
+        looping: bool,
+        play_on_start: bool,
+    },
+    Health {
+        #[inspect(speed = 1.0, min = 1.0)]
+        max: f32,
+        invulnerable: bool,
+    },
+    Team(u32),
+    Custom(#[inspect(label = "")] CustomComponent),
+}
+
+impl Component {
+    pub fn new(kind: ComponentKind) -> Self {
+        match kind {
+            ComponentKind::Script => Component::Script {
+                path: "scripts/script1.rs".to_string(),
+            },
+            ComponentKind::RigidBody => Component::RigidBody {
+                mass: 1.0,
+                kinematic: false,
+                gravity: true,
+            },
+            ComponentKind::AudioSource => Component::AudioSource {
+                clip: String::new(),
+                volume: 1.0,
+                looping: false,
+                play_on_start: true,
+            },
+            ComponentKind::Health => Component::Health {
+                max: 100.0,
+                invulnerable: false,
+            },
+            ComponentKind::Team => Component::Team(0),
+            ComponentKind::Custom(name) => Component::Custom(CustomComponent::new(&name)),
+        }
+    }
+
+    pub fn kind(&self) -> ComponentKind {
+        match self {
+            Component::Script { .. } => ComponentKind::Script,
+            Component::RigidBody { .. } => ComponentKind::RigidBody,
+            Component::AudioSource { .. } => ComponentKind::AudioSource,
+            Component::Health { .. } => ComponentKind::Health,
+            Component::Team(_) => ComponentKind::Team,
+            Component::Custom(custom) => ComponentKind::Custom(custom.name.clone()),
+        }
+    }
+}
+
+/// What a game's own component needs, implemented for anything that derives these.
+pub trait UserComponent: Inspect + Send + Sync {
+    fn save(&self) -> Result<String, String>;
+    fn clone_box(&self) -> Box<dyn UserComponent>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Inspect + Serialize + Clone + Send + Sync + 'static> UserComponent for T {
+    fn save(&self) -> Result<String, String> {
+        ron::to_string(self).map_err(|e| e.to_string())
+    }
+
+    fn clone_box(&self) -> Box<dyn UserComponent> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ComponentType {
+    name: &'static str,
+    description: &'static str,
+    create: fn() -> Box<dyn UserComponent>,
+    load: fn(&str) -> Result<Box<dyn UserComponent>, String>,
+}
+
+static REGISTRY: RwLock<Vec<ComponentType>> = RwLock::new(Vec::new());
+
+fn registered(name: &str) -> Option<ComponentType> {
+    let registry = REGISTRY.read().unwrap();
+    registry.iter().find(|ty| ty.name == name).copied()
+}
+
+fn create<T: UserComponent + Default + 'static>() -> Box<dyn UserComponent> {
+    Box::new(T::default())
+}
+
+fn load<T: UserComponent + DeserializeOwned + 'static>(
+    data: &str,
+) -> Result<Box<dyn UserComponent>, String> {
+    let value: T = ron::from_str(data).map_err(|e| e.to_string())?;
+    Ok(Box::new(value))
+}
+
+/// Adds a game's component to the Add Component menu, `name` is what scenes save it as so it
+/// shouldn't change. Register before loading scenes, or their components of this type are only
+/// kept as they were saved.
+///
+/// ```ignore
+/// #[derive(Default, Clone, Serialize, Deserialize, Inspect)]
+/// struct Spinner {
+///     speed: f32,
+/// }
+///
+/// component::register::<Spinner>("Spinner", "Turns around the Y axis");
+/// ```
+pub fn register<T>(name: &'static str, description: &'static str)
+where
+    T: UserComponent + Default + DeserializeOwned + 'static,
+{
+    let ty = ComponentType {
+        name,
+        description,
+        create: create::<T>,
+        load: load::<T>,
+    };
+
+    let mut registry = REGISTRY.write().unwrap();
+    match registry.iter_mut().find(|other| other.name == name) {
+        Some(other) => {
+            log::warn!("Component '{}' was registered twice, replacing it", name);
+            *other = ty;
+        }
+        None => registry.push(ty),
+    }
+}
+
+/// A component registered by the game, saved as its name and RON.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "SavedCustomComponent", into = "SavedCustomComponent")]
+pub struct CustomComponent {
+    name: String,
+    value: Option<Box<dyn UserComponent>>,
+    saved: String, // Kept for when the type isn't registered
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedCustomComponent {
+    name: String,
+    data: String,
+}
+
+impl CustomComponent {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: registered(name).map(|ty| (ty.create)()),
+            saved: String::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The game's component, if it's registered and of type T.
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.value.as_ref()?.as_any().downcast_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.value.as_mut()?.as_any_mut().downcast_mut()
+    }
+
+    fn data(&self) -> String {
+        let Some(value) = &self.value else {
+            return self.saved.clone();
+        };
+        value.save().unwrap_or_else(|e| {
+            log::error!("Failed to save component '{}': {}", self.name, e);
+            self.saved.clone()
+        })
+    }
+}
+
+impl From<SavedCustomComponent> for CustomComponent {
+    fn from(saved: SavedCustomComponent) -> Self {
+        let value = match registered(&saved.name) {
+            Some(ty) => match (ty.load)(&saved.data) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    log::error!("Failed to load component '{}': {}", saved.name, e);
+                    None
+                }
+            },
+            None => {
+                log::warn!("Component '{}' isn't registered, kept as saved", saved.name);
+                None
+            }
+        };
+
+        Self {
+            name: saved.name,
+            value,
+            saved: saved.data,
+        }
+    }
+}
+
+impl From<CustomComponent> for SavedCustomComponent {
+    fn from(component: CustomComponent) -> Self {
+        Self {
+            data: component.data(),
+            name: component.name,
+        }
+    }
+}
+
+impl Clone for CustomComponent {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            value: self.value.as_ref().map(|value| value.clone_box()),
+            saved: self.saved.clone(),
+        }
+    }
+}
+
+impl PartialEq for CustomComponent {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.data() == other.data()
+    }
+}
+
+impl fmt::Debug for CustomComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomComponent")
+            .field("name", &self.name)
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+impl Inspect for CustomComponent {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        match &mut self.value {
+            Some(value) => value.inspect(ui, label),
+            None => {
+                ui.weak("Not registered by the game, kept as it was saved");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use glow::HasContext;
+
+use crate::jobs;
+
+/// Block compression applied to textures on import, set by the `asset_texture_compression` cvar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextureCompression {
+    #[default]
+    None,
+    Bc1, // RGB with 1 bit alpha, 8:1
+    Bc3, // RGBA, 4:1
+    Bc7, // RGBA, 4:1 but much closer to the original than BC3
+}
+
+impl TextureCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextureCompression::None => "none",
+            TextureCompression::Bc1 => "bc1",
+            TextureCompression::Bc3 => "bc3",
+            TextureCompression::Bc7 => "bc7",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|compression| compression.name().eq_ignore_ascii_case(name))
+    }
+
+    const ALL: [TextureCompression; 4] = [
+        TextureCompression::None,
+        TextureCompression::Bc1,
+        TextureCompression::Bc3,
+        TextureCompression::Bc7,
+    ];
+
+    // Stored in import artifacts
+    pub fn id(&self) -> u32 {
+        match self {
+            TextureCompression::None => 0,
+            TextureCompression::Bc1 => 1,
+            TextureCompression::Bc3 => 3,
+            TextureCompression::Bc7 => 7,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|compression| compression.id() == id)
+    }
+
+    fn block_size(&self) -> usize {
+        match self {
+            TextureCompression::Bc1 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn internal_format(&self) -> Option<u32> {
+        match self {
+            TextureCompression::None => None,
+            TextureCompression::Bc1 => Some(glow::COMPRESSED_RGBA_S3TC_DXT1_EXT),
+            TextureCompression::Bc3 => Some(glow::COMPRESSED_RGBA_S3TC_DXT5_EXT),
+            TextureCompression::Bc7 => Some(glow::COMPRESSED_RGBA_BPTC_UNORM),
+        }
+    }
+
+    /// BC1 and BC3 need S3TC, BC7 is core since OpenGL 4.2.
+    pub fn is_supported(&self, gl: &glow::Context) -> bool {
+        let extensions = gl.supported_extensions();
+        match self {
+            TextureCompression::None => true,
+            TextureCompression::Bc1 | TextureCompression::Bc3 => {
+                extensions.contains("GL_EXT_texture_compression_s3tc")
+            }
+            TextureCompression::Bc7 => {
+                let version = gl.version();
+                (version.major, version.minor) >= (4, 2)
+                    || extensions.contains("GL_ARB_texture_compression_bptc")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressedLevel {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// A full mip chain of block compressed data, largest level first.
+#[derive(Debug, Clone)]
+pub struct CompressedTexture {
+    pub format: TextureCompression,
+    pub levels: Vec<CompressedLevel>,
+}
+
+impl CompressedTexture {
+    pub fn compress(
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        format: TextureCompression,
+    ) -> Option<Self> {
+        if format == TextureCompression::None || width == 0 || height == 0 {
+            return None;
+        }
+
+        // Compressed textures can't generate their own mipmaps
+        let mut chain = vec![(rgba.to_vec(), width, height)];
+        loop {
+            let (pixels, width, height) = chain.last().unwrap();
+            if *width == 1 && *height == 1 {
+                break;
+            }
+            let level = downsample(pixels, *width, *height);
+            chain.push(level);
+        }
+
+        // Every level compresses on its own, so each is a job
+        let mut levels: Vec<CompressedLevel> = chain
+            .iter()
+            .map(|(_, width, height)| CompressedLevel {
+                width: *width,
+                height: *height,
+                data: Vec::new(),
+            })
+            .collect();
+        jobs::global().scope(|jobs| {
+            for (level, (pixels, width, height)) in levels.iter_mut().zip(&chain) {
+                jobs.spawn("compress_mip", move || {
+                    level.data = compress_level(pixels, *width, *height, format);
+                });
+            }
+        });
+
+        Some(Self { format, levels })
+    }
+}
+
+// Half the size with a 2x2 box filter, odd edges repeat their last pixel
+fn downsample(rgba: &[u8], width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut pixels = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            for channel in 0..4 {
+                let mut sum = 0u32;
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(width - 1);
+                    let sy = (y * 2 + dy).min(height - 1);
+                    sum += rgba[((sy * width + sx) * 4 + channel) as usize] as u32;
+                }
+                pixels.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    (pixels, new_width, new_height)
+}
+
+fn compress_level(rgba: &[u8], width: u32, height: u32, format: TextureCompression) -> Vec<u8> {
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut data = Vec::with_capacity((blocks_x * blocks_y) as usize * format.block_size());
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            // Blocks past the edge repeat the last row and column
+            let block: [[u8; 4]; 16] = std::array::from_fn(|i| {
+                let x = (block_x * 4 + i as u32 % 4).min(width - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(height - 1);
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].try_into().unwrap()
+            });
+            match format {
+                TextureCompression::None => {}
+                TextureCompression::Bc1 => data.extend_from_slice(&encode_bc1(&block, true)),
+                TextureCompression::Bc3 => {
+                    data.extend_from_slice(&encode_bc3_alpha(&block));
+                    data.extend_from_slice(&encode_bc1(&block, false));
+                }
+                TextureCompression::Bc7 => data.extend_from_slice(&encode_bc7_mode6(&block)),
+            }
+        }
+    }
+    data
+}
+
+// The two ends of the line that fits the block's colors best, found along the principal axis
+// of the first `channels` channels
+fn principal_endpoints(block: &[[u8; 4]; 16], channels: usize) -> ([f32; 4], [f32; 4]) {
+    let pixels: Vec<[f32; 4]> = block.iter().map(|p| p.map(|c| c as f32)).collect();
+    let mut mean = [0.0f32; 4];
+    for pixel in &pixels {
+        for c in 0..channels {
+            mean[c] += pixel[c] / 16.0;
+        }
+    }
+
+    let mut covariance = [[0.0f32; 4]; 4];
+    for pixel in &pixels {
+        for i in 0..channels {
+            for j in 0..channels {
+                covariance[i][j] += (pixel[i] - mean[i]) * (pixel[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration, a handful of steps is plenty for 16 pixels
+    let mut axis = [1.0f32; 4];
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for i in 0..channels {
+            for j in 0..channels {
+                next[i] += covariance[i][j] * axis[j];
+            }
+        }
+        let length = next[..channels].iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break; // Every pixel is the same
+        }
+        axis = next.map(|v| v / length);
+    }
+
+    let project =
+        |pixel: &[f32; 4]| -> f32 { (0..channels).map(|c| (pixel[c] - mean[c]) * axis[c]).sum() };
+    let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+    for pixel in &pixels {
+        let t = project(pixel);
+        min = min.min(t);
+        max = max.max(t);
+    }
+    if !min.is_finite() || !max.is_finite() {
+        return (mean, mean);
+    }
+
+    let point = |t: f32| -> [f32; 4] {
+        std::array::from_fn(|c| {
+            if c < channels {
+                (mean[c] + axis[c] * t).clamp(0.0, 255.0)
+            } else {
+                mean[c]
+            }
+        })
+    };
+    (point(max), point(min))
+}
+
+fn nearest(palette: &[[f32; 4]], pixel: [u8; 4], channels: usize) -> usize {
+    let distance = |color: &[f32; 4]| -> f32 {
+        (0..channels)
+            .map(|c| (color[c] - pixel[c] as f32).powi(2))
+            .sum()
+    };
+    (0..palette.len())
+        .min_by(|&a, &b| distance(&palette[a]).total_cmp(&distance(&palette[b])))
+        .unwrap_or(0)
+}
+
+fn to_565(color: [f32; 4]) -> u16 {
+    let r = (color[0] * 31.0 / 255.0).round() as u16;
+    let g = (color[1] * 63.0 / 255.0).round() as u16;
+    let b = (color[2] * 31.0 / 255.0).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_565(color: u16) -> [f32; 4] {
+    let r = ((color >> 11) & 31) as f32 * 255.0 / 31.0;
+    let g = ((color >> 5) & 63) as f32 * 255.0 / 63.0;
+    let b = (color & 31) as f32 * 255.0 / 31.0;
+    [r, g, b, 255.0]
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+// Alone, BC1 can make pixels below half alpha transparent. Inside BC3 it's color only.
+fn encode_bc1(block: &[[u8; 4]; 16], punch_through: bool) -> [u8; 8] {
+    let transparent = punch_through && block.iter().any(|pixel| pixel[3] < 128);
+    let (start, end) = principal_endpoints(block, 3);
+    let (mut c0, mut c1) = (to_565(start), to_565(end));
+
+    // c0 > c1 picks four colors, c0 <= c1 three colors and transparent black
+    if (c0 < c1) != transparent {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+
+    let (e0, e1) = (from_565(c0), from_565(c1));
+    let palette: Vec<[f32; 4]> = if transparent {
+        vec![e0, e1, lerp(e0, e1, 0.5)]
+    } else if c0 == c1 {
+        vec![e0]
+    } else {
+        vec![e0, e1, lerp(e0, e1, 1.0 / 3.0), lerp(e0, e1, 2.0 / 3.0)]
+    };
+
+    let mut indices = 0u32;
+    for (i, pixel) in block.iter().enumerate() {
+        let index = if transparent && pixel[3] < 128 {
+            3
+        } else {
+            nearest(&palette, *pixel, 3) as u32
+        };
+        indices |= index << (i * 2);
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0..2].copy_from_slice(&c0.to_le_bytes());
+    bytes[2..4].copy_from_slice(&c1.to_le_bytes());
+    bytes[4..8].copy_from_slice(&indices.to_le_bytes());
+    bytes
+}
+
+fn encode_bc3_alpha(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a0 = block.iter().map(|pixel| pixel[3]).max().unwrap_or(255);
+    let a1 = block.iter().map(|pixel| pixel[3]).min().unwrap_or(255);
+
+    // a0 > a1 gives eight evenly spaced levels
+    let palette: Vec<f32> = if a0 == a1 {
+        vec![a0 as f32]
+    } else {
+        let (a0, a1) = (a0 as f32, a1 as f32);
+        let mut palette = vec![a0, a1];
+        palette.extend((1..7).map(|i| (a0 * (7 - i) as f32 + a1 * i as f32) / 7.0));
+        palette
+    };
+
+    let mut indices = 0u64;
+    for (i, pixel) in block.iter().enumerate() {
+        let alpha = pixel[3] as f32;
+        let index = (0..palette.len())
+            .min_by(|&a, &b| {
+                (palette[a] - alpha)
+                    .abs()
+                    .total_cmp(&(palette[b] - alpha).abs())
+            })
+            .unwrap_or(0) as u64;
+        indices |= index << (i * 3);
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0] = a0;
+    bytes[1] = a1;
+    bytes[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+    bytes
+}
+
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// 7 bits per channel and a parity bit shared by the channels, picking the parity that lands
+// closest to the wanted color
+fn bc7_quantize(color: [f32; 4]) -> ([u8; 4], u8) {
+    let mut best = ([0u8; 4], 0u8, f32::INFINITY);
+    for parity in 0..2u8 {
+        let quantized =
+            color.map(|c| (((c - parity as f32) / 2.0).round()).clamp(0.0, 127.0) as u8);
+        let error: f32 = (0..4)
+            .map(|c| (((quantized[c] << 1) | parity) as f32 - color[c]).powi(2))
+            .sum();
+        if error < best.2 {
+            best = (quantized, parity, error);
+        }
+    }
+    (best.0, best.1)
+}
+
+// Mode 6: one subset, RGBA endpoints and 4 bit indices. Simple and good for most content.
+fn encode_bc7_mode6(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let (start, end) = principal_endpoints(block, 4);
+    let (mut q0, mut p0) = bc7_quantize(start);
+    let (mut q1, mut p1) = bc7_quantize(end);
+
+    let expand = |q: [u8; 4], p: u8| q.map(|c| ((c << 1) | p) as u32);
+    let palette = |e0: [u32; 4], e1: [u32; 4]| -> Vec<[f32; 4]> {
+        BC7_WEIGHTS_4
+            .iter()
+            .map(|&w| std::array::from_fn(|c| (((64 - w) * e0[c] + w * e1[c] + 32) >> 6) as f32))
+            .collect()
+    };
+    let colors = palette(expand(q0, p0), expand(q1, p1));
+    let mut indices: Vec<u32> = block
+        .iter()
+        .map(|pixel| nearest(&colors, *pixel, 4) as u32)
+        .collect();
+
+    // The first pixel's index is stored with its top bit left out, so it must be below 8.
+    // The weights are symmetric, swapping the endpoints just mirrors the indices.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        indices = indices.iter().map(|index| 15 - index).collect();
+    }
+
+    let mut bits = 0u128;
+    let mut position = 0;
+    let mut write = |value: u128, count: u32| {
+        bits |= value << position;
+        position += count;
+    };
+    write(1 << 6, 7); // Mode 6
+    for c in 0..4 {
+        write(q0[c] as u128, 7);
+        write(q1[c] as u128, 7);
+    }
+    write(p0 as u128, 1);
+    write(p1 as u128, 1);
+    for (i, index) in indices.iter().enumerate() {
+        write(*index as u128, if i == 0 { 3 } else { 4 });
+    }
+    bits.to_le_bytes()
+}
@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use cruel_game_engine::{
+    dialogue::DIALOGUE_DIRECTORY,
+    loader::AssetLoader,
+    particles::PARTICLE_DIRECTORY,
+    scene_file::SCENE_EXTENSION,
+    scene_preview::{self, ScenePreviewCache},
+    tilemap::{Tilemap, TILEMAP_DIRECTORY},
+};
+
+use crate::thumbnails::Thumbnails;
+
+// The disk is listed again this often, so files added outside the editor show up
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Build output and caches, never assets
+const IGNORED_DIRECTORIES: [&str; 2] = ["target", "export"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Mesh,
+    Texture,
+    ParticleEffect,
+    Tilemap,
+    Dialogue,
+    Scene,
+    Shader,
+    Script,
+    Other,
+}
+
+impl AssetKind {
+    pub const ALL: [AssetKind; 9] = [
+        AssetKind::Mesh,
+        AssetKind::Texture,
+        AssetKind::ParticleEffect,
+        AssetKind::Tilemap,
+        AssetKind::Dialogue,
+        AssetKind::Scene,
+        AssetKind::Shader,
+        AssetKind::Script,
+        AssetKind::Other,
+    ];
+
+    /// By extension, and for ron files by the directory the editor saves that kind in.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let relative = path.strip_prefix(".").unwrap_or(path);
+        match extension.as_str() {
+            "gltf" | "glb" => AssetKind::Mesh,
+            "png" | "jpg" | "jpeg" | "bmp" | "tga" | "hdr" => AssetKind::Texture,
+            "glsl" | "vert" | "frag" => AssetKind::Shader,
+            "rs" => AssetKind::Script,
+            SCENE_EXTENSION => AssetKind::Scene,
+            "ron" if relative.starts_with(PARTICLE_DIRECTORY) => AssetKind::ParticleEffect,
+            "ron" if relative.starts_with(TILEMAP_DIRECTORY) => AssetKind::Tilemap,
+            "ron" if relative.starts_with(DIALOGUE_DIRECTORY) => AssetKind::Dialogue,
+            _ => AssetKind::Other,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AssetKind::Mesh => "Meshes",
+            AssetKind::Texture => "Textures",
+            AssetKind::ParticleEffect => "Particle effects",
+            AssetKind::Tilemap => "Tilemaps",
+            AssetKind::Dialogue => "Dialogue",
+            AssetKind::Scene => "Scenes",
+            AssetKind::Shader => "Shaders",
+            AssetKind::Script => "Scripts",
+            AssetKind::Other => "Other",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            AssetKind::Mesh => "🧊",
+            AssetKind::Texture => "🖼",
+            AssetKind::ParticleEffect => "✨",
+            AssetKind::Tilemap => "🗺",
+            AssetKind::Dialogue => "💬",
+            AssetKind::Scene => "🎬",
+            AssetKind::Shader => "🎨",
+            AssetKind::Script => "📜",
+            AssetKind::Other => "📄",
+        }
+    }
+}
+
+/// The drag and drop payload of a file dragged out of the browser.
+#[derive(Debug, Clone)]
+pub struct DraggedAsset {
+    pub path: PathBuf,
+    pub kind: AssetKind,
+}
+
+#[derive(Debug, Default)]
+struct Listing {
+    folders: Vec<PathBuf>,
+    files: Vec<(PathBuf, AssetKind)>,
+}
+
+fn list_directory(directory: &Path) -> Listing {
+    let mut listing = Listing::default();
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Failed to list {:?}: {}", directory, e);
+            return listing;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if !IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                listing.folders.push(path);
+            }
+        } else {
+            let kind = AssetKind::from_path(&path);
+            listing.files.push((path, kind));
+        }
+    }
+    listing.folders.sort();
+    listing.files.sort_by(|a, b| a.0.cmp(&b.0));
+    listing
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Paths in the browser start with "./", the ones saved in assets don't.
+pub fn project_relative(path: &Path) -> &Path {
+    path.strip_prefix(".").unwrap_or(path)
+}
+
+/// Where `path` ends up when `from` is moved to `to`, None when it isn't `from` or under it.
+pub fn moved_path(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    let rest = project_relative(path)
+        .strip_prefix(project_relative(from))
+        .ok()?;
+    let to = project_relative(to);
+    Some(match rest.as_os_str().is_empty() {
+        true => to.to_path_buf(),
+        false => to.join(rest),
+    })
+}
+
+// Every tilemap saved in the project, they are the only saved files that point at other assets
+fn saved_tilemaps() -> Vec<(PathBuf, Tilemap)> {
+    let Ok(entries) = std::fs::read_dir(TILEMAP_DIRECTORY) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .filter_map(|path| match Tilemap::load(&path) {
+            Ok(tilemap) => Some((path, tilemap)),
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Uses of a file, or of anything under a directory, by the loaded scenes and saved tilemaps.
+pub fn asset_uses(path: &Path, asset_loader: &AssetLoader) -> usize {
+    let tilemaps = saved_tilemaps()
+        .iter()
+        .filter(|(_, tilemap)| project_relative(&tilemap.atlas).starts_with(project_relative(path)))
+        .count();
+    asset_loader.uses_of_path(path) + tilemaps
+}
+
+/// Moves or renames a file or directory along with its scene previews. Loaded assets and the
+/// saved tilemaps follow it, the open scene is left to the caller.
+pub fn move_asset(from: &Path, to: &Path, asset_loader: &mut AssetLoader) -> Result<(), String> {
+    if to.exists() {
+        return Err(format!("{:?} already exists", to));
+    }
+    asset_loader.rename(from, to)?;
+
+    for sidecar in [scene_preview::thumbnail_path, scene_preview::metadata_path] {
+        let (sidecar_from, sidecar_to) = (sidecar(from), sidecar(to));
+        if sidecar_from.exists() {
+            if let Err(e) = std::fs::rename(&sidecar_from, &sidecar_to) {
+                log::warn!("Failed to move {:?}: {}", sidecar_from, e);
+            }
+        }
+    }
+
+    for (path, mut tilemap) in saved_tilemaps() {
+        if let Some(atlas) = moved_path(&tilemap.atlas, from, to) {
+            tilemap.atlas = atlas;
+            if let Err(e) = tilemap.save(&path) {
+                log::error!("{}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deletes a file or directory and its scene previews. Anything loaded from it stays loaded.
+pub fn delete_asset(path: &Path) -> Result<(), String> {
+    let removed = match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    };
+    removed.map_err(|e| format!("Failed to delete {:?}: {}", path, e))?;
+    for sidecar in [
+        scene_preview::thumbnail_path(path),
+        scene_preview::metadata_path(path),
+    ] {
+        let _ = std::fs::remove_file(sidecar);
+    }
+    Ok(())
+}
+
+/// What was done in the browser, carried out by the editor.
+pub enum BrowserAction {
+    Open(PathBuf, AssetKind),
+    Move(PathBuf, PathBuf), // Renames too
+    Delete(PathBuf),
+}
+
+// The rename or move dialog, `text` is the new name or the folder to move to
+struct PathEdit {
+    path: PathBuf,
+    text: String,
+    moving: bool,
+}
+
+/// Browses the project directory. Opening a file is left to the caller, `show` returns it.
+pub struct ContentBrowser {
+    root: PathBuf,
+    current: PathBuf,
+    selected: Option<PathBuf>,
+    shown_kinds: [bool; AssetKind::ALL.len()],
+    search: String,
+    listings: HashMap<PathBuf, Listing>, // Cached until the next refresh
+    last_refresh: Instant,
+    editing: Option<PathEdit>,
+}
+
+impl ContentBrowser {
+    pub fn new() -> Self {
+        let root = PathBuf::from(".");
+        Self {
+            current: root.clone(),
+            root,
+            selected: None,
+            shown_kinds: [true; AssetKind::ALL.len()],
+            search: String::new(),
+            listings: HashMap::new(),
+            last_refresh: Instant::now(),
+            editing: None,
+        }
+    }
+
+    /// Goes back to the top of the project, after the working directory changed.
+    pub fn reset(&mut self) {
+        self.current = self.root.clone();
+        self.selected = None;
+        self.listings.clear();
+        self.editing = None;
+    }
+
+    fn listing(&mut self, directory: &Path) -> &Listing {
+        self.listings
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| list_directory(directory))
+    }
+
+    fn folder_tree(
+        &mut self,
+        ui: &mut egui::Ui,
+        directory: &Path,
+        action: &mut Option<BrowserAction>,
+    ) {
+        let folders = self.listing(directory).folders.clone();
+        for folder in folders {
+            let name = file_name(&folder);
+            let selected = self.current == folder;
+            let id = ui.make_persistent_id(&folder);
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| {
+                    let response = ui.selectable_label(selected, format!("📁 {}", name));
+                    if response.clicked() {
+                        self.current = folder.clone();
+                    }
+                    drop_target(&response, &folder, action);
+                })
+                .body(|ui| self.folder_tree(ui, &folder, action));
+        }
+    }
+
+    // Rename, move and delete, for files and folders alike
+    fn file_menu(
+        &mut self,
+        response: &egui::Response,
+        path: &Path,
+        action: &mut Option<BrowserAction>,
+    ) {
+        response.context_menu(|ui| {
+            if ui.button("✏ Rename").clicked() {
+                self.editing = Some(PathEdit {
+                    path: path.to_path_buf(),
+                    text: file_name(path),
+                    moving: false,
+                });
+                ui.close_menu();
+            }
+            if ui.button("📁 Move to...").clicked() {
+                let folder = path.parent().unwrap_or(&self.root);
+                self.editing = Some(PathEdit {
+                    path: path.to_path_buf(),
+                    text: project_relative(folder).to_string_lossy().to_string(),
+                    moving: true,
+                });
+                ui.close_menu();
+            }
+            if ui.button("🗑 Delete").clicked() {
+                *action = Some(BrowserAction::Delete(path.to_path_buf()));
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn edit_window(&mut self, ctx: &egui::Context, action: &mut Option<BrowserAction>) {
+        let Some(edit) = &mut self.editing else {
+            return;
+        };
+        let title = match edit.moving {
+            true => "Move to",
+            false => "Rename",
+        };
+        let mut done = None;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(file_name(&edit.path));
+                let response = ui.text_edit_singleline(&mut edit.text);
+                let entered =
+                    response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                ui.horizontal(|ui| {
+                    if ui.button("OK").clicked() || entered {
+                        done = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = Some(false);
+                    }
+                });
+            });
+        match done {
+            Some(true) => {
+                let to = match edit.moving {
+                    true => self.root.join(&edit.text).join(file_name(&edit.path)),
+                    false => edit.path.with_file_name(&edit.text),
+                };
+                if !edit.text.is_empty() && to != edit.path {
+                    *action = Some(BrowserAction::Move(edit.path.clone(), to));
+                }
+                self.editing = None;
+            }
+            Some(false) => self.editing = None,
+            None => {}
+        }
+    }
+
+    /// Returns what was done to a file, like double clicking it to open it.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        thumbnails: &mut Thumbnails,
+        scene_previews: &mut ScenePreviewCache,
+    ) -> Option<BrowserAction> {
+        if self.last_refresh.elapsed() >= REFRESH_INTERVAL {
+            self.listings.clear();
+            self.last_refresh = Instant::now();
+        }
+
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.current != self.root, egui::Button::new("⬆"))
+                .on_hover_text("Parent folder")
+                .clicked()
+            {
+                if let Some(parent) = self.current.parent() {
+                    self.current = parent.to_path_buf();
+                }
+            }
+            if ui.button("⟳").on_hover_text("Refresh").clicked() {
+                self.listings.clear();
+            }
+            ui.label(self.current.to_string_lossy());
+            ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("Search"));
+            ui.menu_button("Filter", |ui| {
+                for (kind, shown) in AssetKind::ALL.iter().zip(&mut self.shown_kinds) {
+                    ui.checkbox(shown, format!("{} {}", kind.icon(), kind.label()));
+                }
+            });
+        });
+        ui.separator();
+
+        egui::SidePanel::left(ui.id().with("Folders"))
+            .resizable(true)
+            .default_width(160.0)
+            .show_inside(ui, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let root = self.root.clone();
+                    let response = ui.selectable_label(self.current == root, "📁 Project");
+                    if response.clicked() {
+                        self.current = root.clone();
+                    }
+                    drop_target(&response, &root, &mut action);
+                    self.folder_tree(ui, &root, &mut action);
+                });
+            });
+
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            let current = self.current.clone();
+            let search = self.search.to_lowercase();
+            let shown_kinds = self.shown_kinds;
+            let listing = self.listing(&current);
+            let folders = listing.folders.clone();
+            let files: Vec<(PathBuf, AssetKind)> = listing
+                .files
+                .iter()
+                .filter(|(path, kind)| {
+                    let index = AssetKind::ALL.iter().position(|k| k == kind).unwrap();
+                    shown_kinds[index] && file_name(path).to_lowercase().contains(&search)
+                })
+                .cloned()
+                .collect();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for folder in folders {
+                        let response = tile(ui, "📁", &file_name(&folder), false, || None);
+                        drop_target(&response, &folder, &mut action);
+                        self.file_menu(&response, &folder, &mut action);
+                        if response.double_clicked() {
+                            self.current = folder;
+                        }
+                    }
+                    for (path, kind) in files {
+                        let selected = self.selected.as_ref() == Some(&path);
+                        let thumbnail = || thumbnails.get(&path, kind).map(|texture| texture.id());
+                        let response =
+                            tile(ui, kind.icon(), &file_name(&path), selected, thumbnail);
+                        // Scenes show what the Open menu shows for them
+                        let mut response = match kind {
+                            AssetKind::Scene => {
+                                response.on_hover_ui(|ui| scene_previews.ui(ui, &path))
+                            }
+                            _ => response.on_hover_text(path.to_string_lossy()),
+                        };
+                        // Onto a folder to move it, meshes and textures into the viewport too
+                        response = response.interact(egui::Sense::click_and_drag());
+                        response.dnd_set_drag_payload(DraggedAsset {
+                            path: path.clone(),
+                            kind,
+                        });
+                        self.file_menu(&response, &path, &mut action);
+                        if response.clicked() {
+                            self.selected = Some(path.clone());
+                        }
+                        if response.double_clicked() {
+                            action = Some(BrowserAction::Open(path, kind));
+                        }
+                    }
+                });
+            });
+        });
+
+        if let Some(dragged) = egui::DragAndDrop::payload::<DraggedAsset>(ui.ctx()) {
+            let text = format!("{} {}", dragged.kind.icon(), file_name(&dragged.path));
+            let id = ui.id().with("Dragged asset");
+            egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), id, |ui| ui.label(text));
+        }
+
+        self.edit_window(ui.ctx(), &mut action);
+
+        // Shows the files where they are now on the next frame
+        if matches!(
+            action,
+            Some(BrowserAction::Move(..) | BrowserAction::Delete(_))
+        ) {
+            self.listings.clear();
+        }
+        action
+    }
+}
+
+// A file dragged onto a folder is moved into it
+fn drop_target(response: &egui::Response, folder: &Path, action: &mut Option<BrowserAction>) {
+    if let Some(dragged) = response.dnd_release_payload::<DraggedAsset>() {
+        let to = folder.join(file_name(&dragged.path));
+        if to != dragged.path {
+            *action = Some(BrowserAction::Move(dragged.path.clone(), to));
+        }
+    }
+}
+
+// A thumbnail or icon over its name, sized the same for every file so they line up in rows.
+// The thumbnail is only asked for when the tile is on screen.
+fn tile(
+    ui: &mut egui::Ui,
+    icon: &str,
+    name: &str,
+    selected: bool,
+    thumbnail: impl FnOnce() -> Option<egui::TextureId>,
+) -> egui::Response {
+    let size = egui::vec2(80.0, 72.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().interact_selectable(&response, selected);
+        if selected || response.hovered() {
+            ui.painter()
+                .rect_filled(rect, visuals.corner_radius, visuals.weak_bg_fill);
+        }
+        let icon_center = rect.center_top() + egui::vec2(0.0, 24.0);
+        match thumbnail() {
+            Some(texture) => {
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                ui.painter().image(
+                    texture,
+                    egui::Rect::from_center_size(icon_center, egui::vec2(40.0, 40.0)),
+                    uv,
+                    egui::Color32::WHITE,
+                );
+            }
+            None => {
+                ui.painter().text(
+                    icon_center,
+                    egui::Align2::CENTER_CENTER,
+                    icon,
+                    egui::FontId::proportional(28.0),
+                    visuals.text_color(),
+                );
+            }
+        }
+        let galley = ui.painter().layout(
+            name.to_string(),
+            egui::FontId::proportional(11.0),
+            visuals.text_color(),
+            size.x - 6.0,
+        );
+        let top = egui::pos2(rect.center().x - galley.size().x / 2.0, rect.top() + 46.0);
+        ui.painter()
+            .with_clip_rect(rect)
+            .galley(top, galley, visuals.text_color());
+    }
+    response
+}
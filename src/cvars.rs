@@ -0,0 +1,400 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+pub const CVARS_CONFIG_PATH: &str = "cvars.cfg";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CVarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Str(String),
+}
+
+impl CVarValue {
+    /// Parses `text` into a value of the same type as `self`.
+    pub fn parse_same_type(&self, text: &str) -> Result<CVarValue, String> {
+        let text = text.trim();
+        match self {
+            CVarValue::Bool(_) => match text {
+                "1" | "true" | "on" => Ok(CVarValue::Bool(true)),
+                "0" | "false" | "off" => Ok(CVarValue::Bool(false)),
+                _ => Err(format!("Expected a boolean (0/1), got '{}'", text)),
+            },
+            CVarValue::Int(_) => text
+                .parse::<i64>()
+                .map(CVarValue::Int)
+                .map_err(|e| format!("Expected an integer, got '{}': {}", text, e)),
+            CVarValue::Float(_) => text
+                .parse::<f32>()
+                .map(CVarValue::Float)
+                .map_err(|e| format!("Expected a number, got '{}': {}", text, e)),
+            CVarValue::Str(_) => Ok(CVarValue::Str(text.to_string())),
+        }
+    }
+
+    fn same_type(&self, other: &CVarValue) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CVarValue::Bool(_) => "bool",
+            CVarValue::Int(_) => "int",
+            CVarValue::Float(_) => "float",
+            CVarValue::Str(_) => "string",
+        }
+    }
+}
+
+impl fmt::Display for CVarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CVarValue::Bool(b) => write!(f, "{}", if *b { 1 } else { 0 }),
+            CVarValue::Int(i) => write!(f, "{}", i),
+            CVarValue::Float(v) => write!(f, "{}", v),
+            CVarValue::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CVar {
+    pub name: String,
+    pub description: String,
+    pub value: CVarValue,
+    pub default: CVarValue,
+    pub archive: bool, // Written to the config file when saving
+}
+
+// Gets the registry too, whoever set the cvar may be holding its lock
+pub type CVarCallback = Box<dyn FnMut(&CVarValue, &CVarRegistry) + Send>;
+
+pub struct CVarRegistry {
+    cvars: BTreeMap<String, CVar>,
+    callbacks: HashMap<String, Vec<CVarCallback>>,
+}
+
+impl Default for CVarRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CVarRegistry {
+    pub fn new() -> Self {
+        Self {
+            cvars: BTreeMap::new(),
+            callbacks: HashMap::new(),
+        }
+    }
+
+    /// Creates a registry with every engine cvar registered at its default value.
+    pub fn with_engine_defaults() -> Self {
+        let mut cvars = Self::new();
+
+        cvars.register(
+            "r_vsync",
+            CVarValue::Str("on".to_string()),
+            "Wait for vertical sync when swapping buffers (off, on, adaptive)",
+            true,
+        );
+        cvars.register(
+            "r_window_mode",
+            CVarValue::Str("windowed".to_string()),
+            "Editor window mode (windowed, borderless, exclusive), F11 toggles fullscreen",
+            true,
+        );
+        cvars.register(
+            "r_max_fps",
+            CVarValue::Int(0),
+            "Frames per second cap, 0 for none",
+            true,
+        );
+        cvars.register(
+            "r_reactive",
+            CVarValue::Bool(false),
+            "Editor only redraws after input, or while playing, recording or loading",
+            true,
+        );
+        cvars.register(
+            "r_wireframe",
+            CVarValue::Bool(false),
+            "Render the scene as wireframe",
+            false,
+        );
+        cvars.register(
+            "r_fov",
+            CVarValue::Float(45.0),
+            "Editor perspective camera field of view in degrees",
+            true,
+        );
+        cvars.register(
+            "r_upload_budget_kb",
+            CVarValue::Int(4096),
+            "Kilobytes of mesh and texture data sent to the GPU per frame",
+            true,
+        );
+        cvars.register(
+            "r_static_batching",
+            CVarValue::Bool(true),
+            "Merge small static meshes with the same material into batches in play mode and games",
+            true,
+        );
+        cvars.register(
+            "r_gpu_particles",
+            CVarValue::Bool(true),
+            "Simulate particles in compute shaders where OpenGL 4.3 is available (needs a restart)",
+            true,
+        );
+        cvars.register(
+            "r_view_mode",
+            CVarValue::Str("lit".to_string()),
+            "Scene visualization (lit, unlit, normals, depth, overdraw, uv_checker)",
+            false,
+        );
+        cvars.register(
+            "r_name_tags",
+            CVarValue::Bool(false),
+            "Draw the name of every static mesh above it",
+            false,
+        );
+        cvars.register(
+            "r_environment",
+            CVarValue::Str(String::new()),
+            "HDR image for image based lighting, empty for none, read at startup",
+            true,
+        );
+        cvars.register(
+            "asset_texture_compression",
+            CVarValue::Str("none".to_string()),
+            "Block compression for imported textures (none, bc1, bc3, bc7)",
+            true,
+        );
+        cvars.register(
+            "asset_loader_threads",
+            CVarValue::Int(0),
+            "Threads loading assets in parallel, 0 for one per core up to 4, read at startup",
+            true,
+        );
+        cvars.register(
+            "job_threads",
+            CVarValue::Int(0),
+            "Job system worker threads, 0 for one per core but one, read at startup",
+            true,
+        );
+        cvars.register(
+            "asset_packs",
+            CVarValue::Str(String::new()),
+            "Comma separated asset packs to load from before loose files, read at startup",
+            true,
+        );
+        cvars.register(
+            "cam_speed",
+            CVarValue::Float(2.4),
+            "Editor camera movement speed",
+            true,
+        );
+        cvars.register(
+            "cam_sensitivity",
+            CVarValue::Float(100.0),
+            "Editor camera mouse look sensitivity",
+            true,
+        );
+        cvars.register(
+            "plat_backend",
+            CVarValue::Str("none".to_string()),
+            "Platform integration to start with (none, discord), read at startup",
+            true,
+        );
+        cvars.register(
+            "plat_discord_app_id",
+            CVarValue::Str(String::new()),
+            "Discord application id used for rich presence",
+            true,
+        );
+        cvars.register(
+            "tel_enabled",
+            CVarValue::Bool(false),
+            "Report play-test telemetry events, read at startup",
+            true,
+        );
+        cvars.register(
+            "tel_sink",
+            CVarValue::Str("file".to_string()),
+            "Where telemetry goes (file, http)",
+            true,
+        );
+        cvars.register(
+            "tel_endpoint",
+            CVarValue::Str("logs/telemetry.jsonl".to_string()),
+            "Telemetry file path or HTTP endpoint URL",
+            true,
+        );
+        cvars.register(
+            "acc_colorblind_mode",
+            CVarValue::Str("off".to_string()),
+            "Colorblind filter (off, protanopia, deuteranopia, tritanopia)",
+            true,
+        );
+        cvars.register(
+            "acc_colorblind_simulate",
+            CVarValue::Bool(false),
+            "Simulate the color deficiency instead of correcting for it",
+            true,
+        );
+        cvars.register(
+            "acc_colorblind_strength",
+            CVarValue::Float(1.0),
+            "How strongly the colorblind filter is applied, 0 to 1",
+            true,
+        );
+        cvars.register(
+            "ui_scale",
+            CVarValue::Float(1.0),
+            "Scale of the UI and its text",
+            true,
+        );
+
+        cvars
+    }
+
+    pub fn register(&mut self, name: &str, default: CVarValue, description: &str, archive: bool) {
+        let name = name.to_string();
+        self.cvars.insert(
+            name.clone(),
+            CVar {
+                name,
+                description: description.to_string(),
+                value: default.clone(),
+                default,
+                archive,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CVarValue> {
+        self.cvars.get(name).map(|cvar| &cvar.value)
+    }
+
+    pub fn get_bool(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(CVarValue::Bool(true)))
+    }
+
+    pub fn get_int(&self, name: &str) -> i64 {
+        match self.get(name) {
+            Some(CVarValue::Int(i)) => *i,
+            _ => 0,
+        }
+    }
+
+    pub fn get_float(&self, name: &str) -> f32 {
+        match self.get(name) {
+            Some(CVarValue::Float(v)) => *v,
+            Some(CVarValue::Int(i)) => *i as f32,
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_cvar(&self, name: &str) -> Option<&CVar> {
+        self.cvars.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CVar> {
+        self.cvars.values()
+    }
+
+    pub fn set(&mut self, name: &str, value: CVarValue) -> Result<(), String> {
+        let cvar = self
+            .cvars
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown cvar '{}'", name))?;
+
+        if !cvar.value.same_type(&value) {
+            return Err(format!(
+                "Type mismatch for '{}': expected {}, got {}",
+                name,
+                cvar.value.type_name(),
+                value.type_name()
+            ));
+        }
+
+        if cvar.value == value {
+            return Ok(());
+        }
+
+        cvar.value = value.clone();
+
+        // Taken out while they run so they can borrow the registry
+        if let Some(mut callbacks) = self.callbacks.remove(name) {
+            for callback in callbacks.iter_mut() {
+                callback(&value, self);
+            }
+            self.callbacks.insert(name.to_string(), callbacks);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_from_str(&mut self, name: &str, text: &str) -> Result<(), String> {
+        let value = self
+            .cvars
+            .get(name)
+            .ok_or_else(|| format!("Unknown cvar '{}'", name))?
+            .value
+            .parse_same_type(text)?;
+        self.set(name, value)
+    }
+
+    pub fn reset(&mut self, name: &str) -> Result<(), String> {
+        let default = self
+            .cvars
+            .get(name)
+            .ok_or_else(|| format!("Unknown cvar '{}'", name))?
+            .default
+            .clone();
+        self.set(name, default)
+    }
+
+    /// Calls `callback` with the new value every time `name` changes. It must not lock the
+    /// registry again, read other cvars from the one it's given instead.
+    pub fn on_change(&mut self, name: &str, callback: CVarCallback) {
+        self.callbacks
+            .entry(name.to_string())
+            .or_default()
+            .push(callback);
+    }
+
+    /// Loads `name value` lines from a config file, ignoring unknown cvars.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            if let Err(e) = self.set_from_str(name, value) {
+                log::warn!("{:?}:{}: {}", path, line_number + 1, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every archived cvar to a config file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut contents = String::from("// Generated by Cruel Engine, edit with care\n");
+        for cvar in self.cvars.values().filter(|cvar| cvar.archive) {
+            contents.push_str(&format!("{} {}\n", cvar.name, cvar.value));
+        }
+
+        std::fs::write(path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+}
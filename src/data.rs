@@ -1,6 +1,12 @@
 use std::path::PathBuf;
 
-use crate::opengl::{DynamicRenderData, StaticRenderData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::CompressedTexture,
+    opengl::{DynamicRenderData, StaticRenderData},
+    upload::UploadStatus,
+};
 
 #[derive(Debug)]
 pub enum Color {
@@ -28,13 +34,22 @@ pub struct VertexData {
     pub weights: Option<Vec<[f32; 4]>>, // Optional (skinning)                // Optional; None = non-indexed
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LoadedTexture {
     pub name: String,
     pub path: PathBuf,
     pub width: u32,
     pub height: u32,
     pub data: Vec<u8>, // RGBA8 pixels
+    pub compressed: Option<CompressedTexture>, // Uploaded instead of `data` when the GPU supports it
+    pub source: Vec<u8>, // The encoded file when `data` is empty, decoded if `compressed` can't be
+}
+
+/// The material of a primitive in another mesh file, drawn instead of a primitive's own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterialOverride {
+    pub path: PathBuf,
+    pub primitive: usize, // Index into that file's LoadedMesh.primitives
 }
 
 #[derive(Debug)]
@@ -45,9 +60,22 @@ pub struct LoadedMaterial {
     pub occlusion_texture: Option<PathBuf>,
     pub emissive_texture: Option<PathBuf>,
 
+    // Which UV set each texture reads, 0 is TEXCOORD_0 and 1 is TEXCOORD_1 (lightmaps)
+    pub normal_texcoord: u32,
+    pub occlusion_texcoord: u32,
+
     pub base_color_factor: Color, // fallback if no texture
     pub metallic_factor: f32,
     pub roughness_factor: f32,
+    pub occlusion_strength: f32, // 0 ignores the occlusion texture, 1 applies it fully
+    pub emissive_factor: [f32; 3],
+    pub emissive_strength: f32, // KHR_materials_emissive_strength, lets emission go past 1
+
+    // KHR_materials_clearcoat, a clear varnish layer on top like car paint
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+
+    pub transmission_factor: f32, // KHR_materials_transmission, light passing through like glass
 
     pub alpha_mode: bool,
     pub double_sided: bool,
@@ -58,18 +86,35 @@ pub struct LoadedPrimitive {
     pub vertex_data: VertexData,
     pub material: Option<LoadedMaterial>,
     pub indices: Option<Vec<u32>>,
+    pub mode: u32, // GL draw mode, glow::TRIANGLES unless the file says lines or points
 }
 
 #[derive(Debug, Clone)]
 pub struct StaticPrimitiveInstance {
-    pub primitive_index: usize, // Index into LoadedMesh.primitives
+    pub primitive_index: usize, // Index into LoadedMesh.primitives, or the LOD's primitives
     pub render_data: Option<StaticRenderData>, // VAO/VBO/EBO for this primitive
+    pub mode: u32,                             // From LoadedPrimitive.mode
+    pub normal_map: Option<glow::NativeTexture>, // From the material, needs tangents in the vertex data
+    pub normal_map_uv: u32,                      // UV set the normal map reads
+    pub occlusion_map: Option<glow::NativeTexture>, // Darkens ambient light, often a baked lightmap
+    pub occlusion_map_uv: u32,
+    pub occlusion_strength: f32,
+    pub vertex_colors: bool, // COLOR_0 tints the base color
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3], // Factor times strength
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub material: Option<MaterialOverride>, // None when it's drawn with its own
+    pub upload: UploadStatus, // Not drawn until its buffers and normal map are on the GPU
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct DynamicPrimitiveInstance {
     pub primitive_index: usize, // Index into LoadedMesh.primitives
     pub render_data: Option<DynamicRenderData>, // VAO/VBO/EBO for this primitive
+    pub mode: u32,
 }
 
 // StreamPrimitiveInstance
@@ -79,6 +124,19 @@ pub struct LoadedMesh {
     pub name: String,
     pub path: PathBuf,
     pub primitives: Vec<LoadedPrimitive>,
+    pub lods: Vec<Vec<LoadedPrimitive>>, // Imported lower detail levels, from meshes named *_LOD1 and up
+    pub nodes: Vec<LoadedNode>, // The file's scene, parents before their children
+}
+
+/// An object of an imported scene, placed relative to its parent.
+#[derive(Debug, Clone)]
+pub struct LoadedNode {
+    pub name: String,
+    pub parent: Option<usize>,  // Index into LoadedMesh.nodes
+    pub primitives: Vec<usize>, // Indices into LoadedMesh.primitives, empty for a plain transform
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4], // Quaternion in glTF order, xyzw
+    pub scale: [f32; 3],
 }
 
 #[derive(Debug)]
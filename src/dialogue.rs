@@ -0,0 +1,314 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+pub const DIALOGUE_DIRECTORY: &str = "assets/dialogue";
+
+/// Variables that dialogue conditions read and choice effects write, e.g. quest progress flags.
+pub type DialogueVariables = HashMap<String, i64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 6] = [
+        Comparison::Equal,
+        Comparison::NotEqual,
+        Comparison::Less,
+        Comparison::LessOrEqual,
+        Comparison::Greater,
+        Comparison::GreaterOrEqual,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub variable: String,
+    pub comparison: Comparison,
+    pub value: i64, // Unset variables count as 0
+}
+
+impl Condition {
+    pub fn evaluate(&self, variables: &DialogueVariables) -> bool {
+        let current = variables.get(&self.variable).copied().unwrap_or(0);
+        match self.comparison {
+            Comparison::Equal => current == self.value,
+            Comparison::NotEqual => current != self.value,
+            Comparison::Less => current < self.value,
+            Comparison::LessOrEqual => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterOrEqual => current >= self.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Effect {
+    Set(String, i64),
+    Add(String, i64),
+}
+
+impl Effect {
+    pub fn apply(&self, variables: &mut DialogueVariables) {
+        match self {
+            Effect::Set(variable, value) => {
+                variables.insert(variable.clone(), *value);
+            }
+            Effect::Add(variable, delta) => {
+                *variables.entry(variable.clone()).or_insert(0) += delta;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogueChoice {
+    pub text: String,
+    pub target: Option<u32>, // None ends the conversation
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogueNode {
+    pub id: u32,
+    pub speaker: String,
+    pub text: String,
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    #[serde(default)]
+    pub next: Option<u32>, // Followed when the node has no choices, None ends the conversation
+    #[serde(default)]
+    pub effects: Vec<Effect>, // Applied when the node is entered
+}
+
+impl DialogueNode {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            speaker: String::new(),
+            text: String::new(),
+            choices: Vec::new(),
+            next: None,
+            effects: Vec::new(),
+        }
+    }
+}
+
+/// A dialogue graph asset, stored as RON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogueGraph {
+    pub name: String,
+    pub start: u32,
+    pub nodes: Vec<DialogueNode>,
+}
+
+impl DialogueGraph {
+    pub fn new<T: ToString>(name: T) -> Self {
+        Self {
+            name: name.to_string(),
+            start: 0,
+            nodes: vec![DialogueNode::new(0)],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read dialogue {:?}: {}", path, e))?;
+        ron::from_str(&contents).map_err(|e| format!("Failed to parse dialogue {:?}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize dialogue: {}", e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write dialogue {:?}: {}", path, e))
+    }
+
+    pub fn node(&self, id: u32) -> Option<&DialogueNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn next_free_id(&self) -> u32 {
+        self.nodes.iter().map(|node| node.id + 1).max().unwrap_or(0)
+    }
+}
+
+/// Plays a dialogue graph, tracking the current node.
+pub struct DialogueRunner {
+    pub graph: DialogueGraph,
+    current: Option<u32>,
+}
+
+impl DialogueRunner {
+    pub fn new(graph: DialogueGraph) -> Self {
+        Self {
+            graph,
+            current: None,
+        }
+    }
+
+    pub fn start(&mut self, variables: &mut DialogueVariables) {
+        self.enter(Some(self.graph.start), variables);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none()
+    }
+
+    pub fn current_node(&self) -> Option<&DialogueNode> {
+        self.current.and_then(|id| self.graph.node(id))
+    }
+
+    /// Choices of the current node whose conditions pass, with their index into `choices`.
+    pub fn available_choices(
+        &self,
+        variables: &DialogueVariables,
+    ) -> Vec<(usize, &DialogueChoice)> {
+        match self.current_node() {
+            Some(node) => node
+                .choices
+                .iter()
+                .enumerate()
+                .filter(|(_, choice)| {
+                    choice
+                        .condition
+                        .as_ref()
+                        .is_none_or(|condition| condition.evaluate(variables))
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn choose(
+        &mut self,
+        index: usize,
+        variables: &mut DialogueVariables,
+    ) -> Result<(), String> {
+        let choice = self
+            .current_node()
+            .and_then(|node| node.choices.get(index))
+            .ok_or_else(|| format!("No choice at index {}", index))?
+            .clone();
+
+        if let Some(condition) = &choice.condition {
+            if !condition.evaluate(variables) {
+                return Err(format!("Choice '{}' is not available", choice.text));
+            }
+        }
+
+        for effect in &choice.effects {
+            effect.apply(variables);
+        }
+
+        self.enter(choice.target, variables);
+        Ok(())
+    }
+
+    /// Moves past a node that has no choices, or none that are available.
+    pub fn advance(&mut self, variables: &mut DialogueVariables) {
+        let next = self.current_node().and_then(|node| node.next);
+        self.enter(next, variables);
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    fn enter(&mut self, id: Option<u32>, variables: &mut DialogueVariables) {
+        self.current = id;
+
+        match id.and_then(|id| self.graph.node(id)) {
+            Some(node) => {
+                for effect in &node.effects {
+                    effect.apply(variables);
+                }
+            }
+            None => {
+                if let Some(id) = id {
+                    log::warn!("Dialogue '{}' has no node {}", self.graph.name, id);
+                }
+                self.current = None;
+            }
+        }
+    }
+}
+
+/// Default in-game presentation: a box along the bottom of the screen with the line and choices.
+pub fn dialogue_window(
+    ctx: &egui::Context,
+    runner: &mut DialogueRunner,
+    variables: &mut DialogueVariables,
+) {
+    let Some(node) = runner.current_node() else {
+        return;
+    };
+    let speaker = node.speaker.clone();
+    let text = node.text.clone();
+
+    let choices: Vec<(usize, String)> = runner
+        .available_choices(variables)
+        .into_iter()
+        .map(|(i, choice)| (i, choice.text.clone()))
+        .collect();
+
+    let mut chosen = None;
+    let mut advance = false;
+
+    egui::Window::new("Dialogue")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -20.0])
+        .min_width(400.0)
+        .show(ctx, |ui| {
+            if !speaker.is_empty() {
+                ui.strong(&speaker);
+            }
+            ui.label(&text);
+            ui.separator();
+
+            // With every choice's condition failing it continues like a node without any
+            if !choices.is_empty() {
+                for (i, choice_text) in &choices {
+                    if ui.button(choice_text).clicked() {
+                        chosen = Some(*i);
+                    }
+                }
+            } else if ui.button("Continue").clicked() {
+                advance = true;
+            }
+        });
+
+    if let Some(i) = chosen {
+        if let Err(e) = runner.choose(i, variables) {
+            log::error!("{}", e);
+        }
+    } else if advance {
+        runner.advance(variables);
+    }
+}
@@ -0,0 +1,357 @@
+// Editor panels as tabs that can be moved between the sides of the window, floated or closed
+
+use egui::{DragAndDrop, Id, Sense};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelKind {
+    Hierarchy,
+    Properties,
+    Console,
+    ContentBrowser,
+    Ide,
+    Settings,
+    Dialogue,
+    Particles,
+    Profiler,
+}
+
+impl PanelKind {
+    pub const ALL: [PanelKind; 9] = [
+        PanelKind::Hierarchy,
+        PanelKind::Properties,
+        PanelKind::Console,
+        PanelKind::ContentBrowser,
+        PanelKind::Ide,
+        PanelKind::Settings,
+        PanelKind::Dialogue,
+        PanelKind::Particles,
+        PanelKind::Profiler,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            PanelKind::Hierarchy => "Hierarchy",
+            PanelKind::Properties => "Properties",
+            PanelKind::Console => "Console",
+            PanelKind::ContentBrowser => "Content Browser",
+            PanelKind::Ide => "IDE",
+            PanelKind::Settings => "Settings",
+            PanelKind::Dialogue => "Dialogue",
+            PanelKind::Particles => "Particles",
+            PanelKind::Profiler => "Profiler",
+        }
+    }
+}
+
+/// One open panel. The id keeps several panels of the same kind apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tab {
+    pub id: usize,
+    pub kind: PanelKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DockArea {
+    Left,
+    Right,
+    Bottom,
+}
+
+impl DockArea {
+    const ALL: [DockArea; 3] = [DockArea::Left, DockArea::Right, DockArea::Bottom];
+
+    fn label(self) -> &'static str {
+        match self {
+            DockArea::Left => "⬅ Left",
+            DockArea::Right => "➡ Right",
+            DockArea::Bottom => "⬇ Bottom",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Dock {
+    tabs: Vec<Tab>,
+    active: usize,
+}
+
+impl Dock {
+    fn active_tab(&self) -> Option<Tab> {
+        self.tabs.get(self.active).or(self.tabs.last()).copied()
+    }
+}
+
+enum DockAction {
+    Activate(DockArea, usize),
+    Move(Tab, DockArea),
+    Float(Tab),
+    Close(Tab),
+    Open(PanelKind, Option<DockArea>),
+}
+
+/// Where every editor panel currently is: tabbed in one of the docks or in its own window.
+pub struct DockLayout {
+    left: Dock,
+    right: Dock,
+    bottom: Dock,
+    floating: Vec<Tab>,
+    next_id: usize,
+}
+
+impl Default for DockLayout {
+    fn default() -> Self {
+        let mut layout = Self {
+            left: Dock::default(),
+            right: Dock::default(),
+            bottom: Dock::default(),
+            floating: Vec::new(),
+            next_id: 0,
+        };
+        layout.open(PanelKind::Hierarchy, Some(DockArea::Left));
+        layout.open(PanelKind::Properties, Some(DockArea::Right));
+        for kind in [
+            PanelKind::Console,
+            PanelKind::ContentBrowser,
+            PanelKind::Ide,
+            PanelKind::Settings,
+            PanelKind::Dialogue,
+            PanelKind::Particles,
+            PanelKind::Profiler,
+        ] {
+            layout.open(kind, Some(DockArea::Bottom));
+        }
+        layout.bottom.active = 0;
+        layout
+    }
+}
+
+impl DockLayout {
+    fn dock_mut(&mut self, area: DockArea) -> &mut Dock {
+        match area {
+            DockArea::Left => &mut self.left,
+            DockArea::Right => &mut self.right,
+            DockArea::Bottom => &mut self.bottom,
+        }
+    }
+
+    /// Adds a new panel, floating when there's no area. Already open kinds get another instance.
+    pub fn open(&mut self, kind: PanelKind, area: Option<DockArea>) {
+        let tab = Tab {
+            id: self.next_id,
+            kind,
+        };
+        self.next_id += 1;
+        self.insert(tab, area);
+    }
+
+    fn insert(&mut self, tab: Tab, area: Option<DockArea>) {
+        match area {
+            Some(area) => {
+                let dock = self.dock_mut(area);
+                dock.tabs.push(tab);
+                dock.active = dock.tabs.len() - 1;
+            }
+            None => self.floating.push(tab),
+        }
+    }
+
+    fn remove(&mut self, tab: Tab) {
+        self.floating.retain(|floating| *floating != tab);
+        for area in DockArea::ALL {
+            let dock = self.dock_mut(area);
+            if let Some(index) = dock.tabs.iter().position(|docked| *docked == tab) {
+                dock.tabs.remove(index);
+                if dock.active > index || dock.active >= dock.tabs.len() {
+                    dock.active = dock.active.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, action: DockAction) {
+        match action {
+            DockAction::Activate(area, index) => self.dock_mut(area).active = index,
+            DockAction::Move(tab, area) => {
+                self.remove(tab);
+                self.insert(tab, Some(area));
+            }
+            DockAction::Float(tab) => {
+                self.remove(tab);
+                self.insert(tab, None);
+            }
+            DockAction::Close(tab) => self.remove(tab),
+            DockAction::Open(kind, area) => self.open(kind, area),
+        }
+    }
+
+    /// Opens a new floating panel of any kind, or puts every panel back where it started.
+    pub fn panels_menu(&mut self, ui: &mut egui::Ui) {
+        for kind in PanelKind::ALL {
+            if ui.button(kind.title()).clicked() {
+                self.open(kind, None);
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Reset layout").clicked() {
+            *self = Self::default();
+            ui.close_menu();
+        }
+    }
+
+    /// Draws the docks and floating windows, `contents` fills in the panel for a tab. The docks
+    /// are hidden along with the rest of the editor ui when `visible` is false.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        visible: bool,
+        mut contents: impl FnMut(&mut egui::Ui, Tab),
+    ) {
+        let mut actions = Vec::new();
+        // Empty docks only show up while a tab is dragged, so it can be dropped in them
+        let dragging = DragAndDrop::has_payload_of_type::<Tab>(ctx);
+
+        let shown = |dock: &Dock| visible && (dragging || !dock.tabs.is_empty());
+
+        let left = egui::SidePanel::left("Dock left")
+            .min_width(150.0)
+            .resizable(true)
+            .show_animated(ctx, shown(&self.left), |ui| {
+                dock_ui(ui, &self.left, DockArea::Left, &mut actions, &mut contents)
+            });
+        let bottom = egui::TopBottomPanel::bottom("Dock bottom")
+            .min_height(105.0)
+            .resizable(true)
+            .show_animated(ctx, shown(&self.bottom), |ui| {
+                dock_ui(
+                    ui,
+                    &self.bottom,
+                    DockArea::Bottom,
+                    &mut actions,
+                    &mut contents,
+                )
+            });
+        let right = egui::SidePanel::right("Dock right")
+            .min_width(220.0)
+            .resizable(true)
+            .show_animated(ctx, shown(&self.right), |ui| {
+                dock_ui(
+                    ui,
+                    &self.right,
+                    DockArea::Right,
+                    &mut actions,
+                    &mut contents,
+                )
+            });
+
+        // A tab dropped on a dock moves there, dropped anywhere else it gets its own window
+        let mut dropped = false;
+        for (panel, area) in [
+            (left, DockArea::Left),
+            (bottom, DockArea::Bottom),
+            (right, DockArea::Right),
+        ] {
+            if let Some(tab) = panel.and_then(|panel| panel.response.dnd_release_payload::<Tab>()) {
+                actions.push(DockAction::Move(*tab, area));
+                dropped = true;
+            }
+        }
+        if !dropped && ctx.input(|input| input.pointer.any_released()) {
+            if let Some(tab) = DragAndDrop::take_payload::<Tab>(ctx) {
+                actions.push(DockAction::Float(*tab));
+            }
+        }
+
+        for tab in &self.floating {
+            let mut open = true;
+            egui::Window::new(tab.kind.title())
+                .id(Id::new(("Floating panel", tab.id)))
+                .open(&mut open)
+                .default_size([320.0, 240.0])
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Dock:");
+                        for area in DockArea::ALL {
+                            if ui.small_button(area.label()).clicked() {
+                                actions.push(DockAction::Move(*tab, area));
+                            }
+                        }
+                    });
+                    ui.separator();
+                    ui.push_id(tab.id, |ui| contents(ui, *tab));
+                });
+            if !open {
+                actions.push(DockAction::Close(*tab));
+            }
+        }
+
+        for action in actions {
+            self.apply(action);
+        }
+    }
+}
+
+fn dock_ui(
+    ui: &mut egui::Ui,
+    dock: &Dock,
+    area: DockArea,
+    actions: &mut Vec<DockAction>,
+    contents: &mut impl FnMut(&mut egui::Ui, Tab),
+) {
+    let active = dock.active_tab();
+
+    ui.horizontal_wrapped(|ui| {
+        ui.visuals_mut().widgets.inactive.corner_radius = egui::CornerRadius::same(0);
+        ui.visuals_mut().widgets.hovered.corner_radius = egui::CornerRadius::same(5);
+        ui.visuals_mut().widgets.active.corner_radius = egui::CornerRadius::same(5);
+        for (index, tab) in dock.tabs.iter().enumerate() {
+            let response = ui
+                .selectable_label(Some(*tab) == active, tab.kind.title())
+                .interact(Sense::drag())
+                .on_hover_text("Drag to move, right click for more");
+            response.dnd_set_drag_payload(*tab);
+            if response.clicked() {
+                actions.push(DockAction::Activate(area, index));
+            }
+            response.context_menu(|ui| {
+                for other in DockArea::ALL {
+                    if other != area && ui.button(format!("Move {}", other.label())).clicked() {
+                        actions.push(DockAction::Move(*tab, other));
+                        ui.close_menu();
+                    }
+                }
+                if ui.button("🗗 Float").clicked() {
+                    actions.push(DockAction::Float(*tab));
+                    ui.close_menu();
+                }
+                if ui.button("✖ Close").clicked() {
+                    actions.push(DockAction::Close(*tab));
+                    ui.close_menu();
+                }
+            });
+        }
+
+        ui.menu_button("➕", |ui| {
+            for kind in PanelKind::ALL {
+                if ui.button(kind.title()).clicked() {
+                    actions.push(DockAction::Open(kind, Some(area)));
+                    ui.close_menu();
+                }
+            }
+        });
+    });
+
+    ui.separator();
+
+    match active {
+        Some(tab) => {
+            ui.push_id(tab.id, |ui| contents(ui, tab));
+        }
+        None => {
+            ui.weak("Drop a tab here");
+        }
+    }
+
+    // To allow for resizing
+    ui.allocate_space(ui.available_size());
+}
@@ -0,0 +1,344 @@
+use std::path::Path;
+
+use glow::HasContext;
+
+use crate::{
+    error::{EngineError, EngineResult},
+    gl_debug, shaders,
+};
+
+const ENVIRONMENT_SIZE: i32 = 512;
+const IRRADIANCE_SIZE: i32 = 32;
+const PREFILTERED_SIZE: i32 = 128;
+const PREFILTERED_MIPS: i32 = 5; // Roughness 0 to 1 in even steps, the last mip is 8x8
+const BRDF_LUT_SIZE: i32 = 512;
+
+// Texture units the default shader reads the environment from, units 0 and 1 are the
+// base color and the normal map
+pub const IRRADIANCE_UNIT: u32 = 2;
+pub const PREFILTERED_UNIT: u32 = 3;
+pub const BRDF_LUT_UNIT: u32 = 4;
+
+/// Image based lighting baked from an HDR environment, the split sum approximation:
+/// an irradiance cubemap for diffuse, a cubemap prefiltered per roughness for specular
+/// and a lookup table for the BRDF.
+pub struct EnvironmentMap {
+    pub environment: glow::Texture,
+    pub irradiance: glow::Texture,
+    pub prefiltered: glow::Texture,
+    pub brdf_lut: glow::Texture,
+}
+
+impl EnvironmentMap {
+    /// Bakes everything on the GPU, expect it to take a moment for big environments.
+    #[tracing::instrument(name = "EnvironmentMap::from_hdr", skip(gl))]
+    pub fn from_hdr(gl: &glow::Context, path: &Path) -> EngineResult<Self> {
+        let image = image::open(path)
+            .map_err(|e| EngineError::Asset {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?
+            .to_rgb32f();
+
+        let programs = BakePrograms::new(gl)?;
+        let result = unsafe { Self::bake(gl, &programs, &image) };
+        programs.destroy(gl);
+        result
+    }
+
+    unsafe fn bake(
+        gl: &glow::Context,
+        programs: &BakePrograms,
+        image: &image::Rgb32FImage,
+    ) -> EngineResult<Self> {
+        let equirect = gl.create_texture().map_err(EngineError::Gl)?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(equirect));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGB16F as i32,
+            image.width() as i32,
+            image.height() as i32,
+            0,
+            glow::RGB,
+            glow::FLOAT,
+            glow::PixelUnpackData::Slice(Some(bytemuck::cast_slice(image.as_raw()))),
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_T,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        gl_debug::check_errors(gl, "environment tex_image_2d");
+
+        let framebuffer = gl.create_framebuffer().map_err(EngineError::Gl)?;
+        let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+
+        // Everything below draws full screen triangles, the previous state comes back at the end
+        let mut previous_viewport = [0; 4];
+        gl.get_parameter_i32_slice(glow::VIEWPORT, &mut previous_viewport);
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.bind_vertex_array(Some(vertex_array));
+        gl.disable(glow::DEPTH_TEST);
+        gl.disable(glow::CULL_FACE);
+        gl.active_texture(glow::TEXTURE0);
+
+        let environment = create_cubemap(gl, ENVIRONMENT_SIZE, true)?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(equirect));
+        render_cubemap(
+            gl,
+            programs.equirect_to_cube,
+            environment,
+            ENVIRONMENT_SIZE,
+            0,
+            |_| {},
+        );
+        gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(environment));
+        gl.generate_mipmap(glow::TEXTURE_CUBE_MAP);
+
+        let irradiance = create_cubemap(gl, IRRADIANCE_SIZE, false)?;
+        gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(environment));
+        render_cubemap(
+            gl,
+            programs.irradiance,
+            irradiance,
+            IRRADIANCE_SIZE,
+            0,
+            |_| {},
+        );
+
+        let prefiltered = create_cubemap(gl, PREFILTERED_SIZE, true)?;
+        for mip in 0..PREFILTERED_MIPS {
+            let roughness = mip as f32 / (PREFILTERED_MIPS - 1) as f32;
+            let program = programs.prefilter;
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(environment));
+            render_cubemap(
+                gl,
+                program,
+                prefiltered,
+                PREFILTERED_SIZE >> mip,
+                mip,
+                |gl| {
+                    let location = |name: &str| gl.get_uniform_location(program, name);
+                    gl.uniform_1_f32(location("roughness").as_ref(), roughness);
+                    gl.uniform_1_f32(
+                        location("environment_size").as_ref(),
+                        ENVIRONMENT_SIZE as f32,
+                    );
+                },
+            );
+        }
+        gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(prefiltered));
+        gl.tex_parameter_i32(
+            glow::TEXTURE_CUBE_MAP,
+            glow::TEXTURE_MAX_LEVEL,
+            PREFILTERED_MIPS - 1,
+        );
+
+        let brdf_lut = gl.create_texture().map_err(EngineError::Gl)?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(brdf_lut));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RG16F as i32,
+            BRDF_LUT_SIZE,
+            BRDF_LUT_SIZE,
+            0,
+            glow::RG,
+            glow::FLOAT,
+            glow::PixelUnpackData::Slice(None),
+        );
+        for (parameter, value) in [
+            (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+            (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        ] {
+            gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
+        }
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(brdf_lut),
+            0,
+        );
+        gl.viewport(0, 0, BRDF_LUT_SIZE, BRDF_LUT_SIZE);
+        gl.use_program(Some(programs.brdf_lut));
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+
+        gl.use_program(None);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        gl.bind_texture(glow::TEXTURE_CUBE_MAP, None);
+        gl.bind_vertex_array(None);
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        gl.viewport(
+            previous_viewport[0],
+            previous_viewport[1],
+            previous_viewport[2],
+            previous_viewport[3],
+        );
+        gl.delete_vertex_array(vertex_array);
+        gl.delete_framebuffer(framebuffer);
+        gl.delete_texture(equirect);
+        gl_debug::check_errors(gl, "environment bake");
+
+        Ok(Self {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        })
+    }
+
+    /// Binds the maps to their texture units and tells `program` where they are.
+    /// The program has to be in use.
+    pub fn bind(&self, gl: &glow::Context, program: glow::Program) {
+        unsafe {
+            let location = |name: &str| gl.get_uniform_location(program, name);
+            gl.uniform_1_f32(
+                location("prefiltered_mips").as_ref(),
+                (PREFILTERED_MIPS - 1) as f32,
+            );
+
+            for (unit, target, texture) in [
+                (IRRADIANCE_UNIT, glow::TEXTURE_CUBE_MAP, self.irradiance),
+                (PREFILTERED_UNIT, glow::TEXTURE_CUBE_MAP, self.prefiltered),
+                (BRDF_LUT_UNIT, glow::TEXTURE_2D, self.brdf_lut),
+            ] {
+                gl.active_texture(glow::TEXTURE0 + unit);
+                gl.bind_texture(target, Some(texture));
+            }
+            gl.active_texture(glow::TEXTURE0);
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_texture(self.environment);
+            gl.delete_texture(self.irradiance);
+            gl.delete_texture(self.prefiltered);
+            gl.delete_texture(self.brdf_lut);
+        }
+    }
+}
+
+struct BakePrograms {
+    equirect_to_cube: glow::Program,
+    irradiance: glow::Program,
+    prefilter: glow::Program,
+    brdf_lut: glow::Program,
+}
+
+impl BakePrograms {
+    fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let mut programs = Vec::new();
+        for fragment in [
+            "shaders/equirect_to_cube.glsl",
+            "shaders/irradiance.glsl",
+            "shaders/prefilter.glsl",
+            "shaders/brdf_lut.glsl",
+        ] {
+            match shaders::load_program(gl, "shaders/fullscreen.glsl", fragment) {
+                Ok(program) => programs.push(program),
+                Err(e) => {
+                    for program in programs {
+                        unsafe { gl.delete_program(program) };
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(Self {
+            equirect_to_cube: programs[0],
+            irradiance: programs[1],
+            prefilter: programs[2],
+            brdf_lut: programs[3],
+        })
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            for program in [
+                self.equirect_to_cube,
+                self.irradiance,
+                self.prefilter,
+                self.brdf_lut,
+            ] {
+                gl.delete_program(program);
+            }
+        }
+    }
+}
+
+unsafe fn create_cubemap(
+    gl: &glow::Context,
+    size: i32,
+    mipmapped: bool,
+) -> EngineResult<glow::Texture> {
+    let texture = gl.create_texture().map_err(EngineError::Gl)?;
+    gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(texture));
+    let levels = if mipmapped {
+        32 - (size as u32).leading_zeros() as i32
+    } else {
+        1
+    };
+    gl.tex_storage_2d(glow::TEXTURE_CUBE_MAP, levels, glow::RGB16F, size, size);
+
+    let min_filter = if mipmapped {
+        glow::LINEAR_MIPMAP_LINEAR
+    } else {
+        glow::LINEAR
+    };
+    for (parameter, value) in [
+        (glow::TEXTURE_MIN_FILTER, min_filter),
+        (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+        (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+        (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        (glow::TEXTURE_WRAP_R, glow::CLAMP_TO_EDGE),
+    ] {
+        gl.tex_parameter_i32(glow::TEXTURE_CUBE_MAP, parameter, value as i32);
+    }
+    // Filter across face edges, without it the seams show on rough surfaces
+    gl.enable(glow::TEXTURE_CUBE_MAP_SEAMLESS);
+    Ok(texture)
+}
+
+// Draws every face of `target` at `mip` with `program`, whose `face` uniform picks the direction
+unsafe fn render_cubemap(
+    gl: &glow::Context,
+    program: glow::Program,
+    target: glow::Texture,
+    size: i32,
+    mip: i32,
+    set_uniforms: impl Fn(&glow::Context),
+) {
+    gl.use_program(Some(program));
+    let face_uniform = gl.get_uniform_location(program, "face");
+    set_uniforms(gl);
+    gl.viewport(0, 0, size, size);
+
+    for face in 0..6 {
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+            Some(target),
+            mip,
+        );
+        gl.uniform_1_i32(face_uniform.as_ref(), face as i32);
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fmt, path::PathBuf};
+
+use crate::handles::{MeshHandle, TextureHandle};
+
+/// Errors from the renderer, the asset loader and the scene.
+/// Most of them are shown in the console and the editor keeps running.
+#[derive(Debug, Clone)]
+pub enum EngineError {
+    Io { path: PathBuf, message: String },
+    Asset { path: PathBuf, message: String }, // A file that was read but couldn't be parsed
+    ShaderCompile { path: String, log: String },
+    ShaderLink { name: String, log: String },
+    MissingShaderProgram,
+    MissingUniform(String),
+    MissingMesh(MeshHandle),
+    MissingTexture(TextureHandle),
+    MissingMaterial { path: PathBuf, primitive: usize },
+    UnsupportedVertexData(String),
+    NoViewport,
+    Gl(String), // Creating a GL object failed
+}
+
+pub type EngineResult<T> = Result<T, EngineError>;
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io { path, message } => {
+                write!(f, "Failed to read {:?}: {}", path, message)
+            }
+            EngineError::Asset { path, message } => {
+                write!(f, "Failed to load {:?}: {}", path, message)
+            }
+            EngineError::ShaderCompile { path, log } => {
+                write!(f, "Failed to compile {}: {}", path, log)
+            }
+            EngineError::ShaderLink { name, log } => write!(f, "Failed to link {}: {}", name, log),
+            EngineError::MissingShaderProgram => write!(f, "The scene has no shader program"),
+            EngineError::MissingUniform(name) => {
+                write!(f, "Could not find the uniform called '{}'", name)
+            }
+            EngineError::MissingMesh(handle) => {
+                write!(f, "Mesh {:?} is not loaded in the asset loader", handle)
+            }
+            EngineError::MissingTexture(handle) => {
+                write!(f, "Texture {:?} is not loaded in the asset loader", handle)
+            }
+            EngineError::MissingMaterial { path, primitive } => {
+                write!(f, "No material for primitive {} of {:?}", primitive, path)
+            }
+            EngineError::UnsupportedVertexData(message) => {
+                write!(f, "Unsupported vertex data: {}", message)
+            }
+            EngineError::NoViewport => {
+                write!(f, "No viewport, the UI has to be updated before rendering")
+            }
+            EngineError::Gl(message) => write!(f, "OpenGL error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+// Most of the engine still reports errors as strings
+impl From<EngineError> for String {
+    fn from(error: EngineError) -> Self {
+        error.to_string()
+    }
+}
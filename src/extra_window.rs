@@ -0,0 +1,161 @@
+// Windows besides the main editor one. They have their own surface and egui state but share
+// the main window's GL context, which is made current on their surface while they're drawn.
+
+use std::{num::NonZeroU32, sync::Arc};
+
+use egui_glow::Painter;
+use egui_winit::State as EguiState;
+use glutin::{
+    config::Config,
+    context::PossiblyCurrentContext,
+    display::Display,
+    prelude::*,
+    surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
+};
+use winit::{
+    event::WindowEvent,
+    event_loop::ActiveEventLoop,
+    raw_window_handle::HasWindowHandle,
+    window::{Window, WindowId},
+};
+
+use cruel_game_engine::viewport::Viewport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    GameView, // The scene through its active camera, for a second monitor
+}
+
+impl WindowKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WindowKind::GameView => "Game View",
+        }
+    }
+}
+
+pub struct ExtraWindow {
+    pub kind: WindowKind,
+    pub window: Window,
+    surface: Surface<WindowSurface>,
+    egui_context: egui::Context,
+    egui_state: EguiState,
+    painter: Painter,
+}
+
+impl ExtraWindow {
+    /// `display` and `config` have to be the ones the shared context was made with.
+    pub fn new(
+        event_loop: &ActiveEventLoop,
+        kind: WindowKind,
+        display: &Display,
+        config: &Config,
+        context: &PossiblyCurrentContext,
+        gl: Arc<glow::Context>,
+    ) -> Result<Self, String> {
+        let attributes = Window::default_attributes().with_title(kind.label());
+        let window = event_loop
+            .create_window(attributes)
+            .map_err(|e| format!("Failed to create the {} window: {}", kind.label(), e))?;
+
+        let window_handle = window.window_handle().map_err(|e| e.to_string())?;
+        let size = window.inner_size();
+        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            window_handle.into(),
+            NonZeroU32::new(size.width.max(1)).unwrap(),
+            NonZeroU32::new(size.height.max(1)).unwrap(),
+        );
+        let surface = unsafe { display.create_window_surface(config, &surface_attributes) }
+            .map_err(|e| format!("Failed to create the {} surface: {}", kind.label(), e))?;
+
+        // The main window already waits for vsync, waiting here too would halve the frame rate
+        if context.make_current(&surface).is_ok() {
+            let _ = surface.set_swap_interval(context, SwapInterval::DontWait);
+        }
+
+        let egui_context = egui::Context::default();
+        let egui_state = EguiState::new(
+            egui_context.clone(),
+            egui_context.viewport_id(),
+            &window,
+            Some(window.scale_factor() as f32),
+            None,
+            None,
+        );
+        let painter = Painter::new(gl, "", None, false).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            kind,
+            window,
+            surface,
+            egui_context,
+            egui_state,
+            painter,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Gives egui the event, true when it wants the window drawn again.
+    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+        self.egui_state.on_window_event(&self.window, event).repaint
+    }
+
+    pub fn resize(&self, context: &PossiblyCurrentContext) {
+        let size = self.window.inner_size();
+        self.surface.resize(
+            context,
+            NonZeroU32::new(size.width.max(1)).unwrap(),
+            NonZeroU32::new(size.height.max(1)).unwrap(),
+        );
+    }
+
+    /// Draws the scene with `draw_scene` and egui's `ui` over it, then hands the context back
+    /// to `main_surface`.
+    pub fn redraw(
+        &mut self,
+        context: &PossiblyCurrentContext,
+        gl: &glow::Context,
+        main_surface: &Surface<WindowSurface>,
+        draw_scene: impl FnOnce(&Viewport) -> Result<(), String>,
+        ui: impl FnMut(&egui::Context),
+    ) -> Result<(), String> {
+        context
+            .make_current(&self.surface)
+            .map_err(|e| format!("Failed to draw the {}: {}", self.kind.label(), e))?;
+
+        let size = self.window.inner_size();
+        let viewport = Viewport::new(0, 0, size.width as i32, size.height as i32);
+        viewport.clear(gl);
+        let result = draw_scene(&viewport);
+
+        let raw_input = self.egui_state.take_egui_input(&self.window);
+        let full_output = self.egui_context.run(raw_input, ui);
+        self.egui_state
+            .handle_platform_output(&self.window, full_output.platform_output);
+        let clipped_primitives = self
+            .egui_context
+            .tessellate(full_output.shapes, full_output.pixels_per_point);
+        self.painter.paint_and_update_textures(
+            [size.width, size.height],
+            full_output.pixels_per_point,
+            &clipped_primitives,
+            &full_output.textures_delta,
+        );
+
+        let swapped = self
+            .surface
+            .swap_buffers(context)
+            .map_err(|e| e.to_string());
+        context
+            .make_current(main_surface)
+            .map_err(|e| format!("Failed to get the main window back: {}", e))?;
+        result.and(swapped)
+    }
+
+    pub fn destroy(&mut self) {
+        self.painter.destroy();
+    }
+}
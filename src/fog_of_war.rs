@@ -0,0 +1,363 @@
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector2};
+use glow::HasContext;
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    mesh::StaticMeshId,
+    opengl,
+    scene_graph::SceneNode,
+    shaders,
+    viewport::Viewport,
+};
+
+const UNEXPLORED: u8 = 0;
+const EXPLORED: u8 = 128;
+const VISIBLE: u8 = 255;
+
+/// Something that uncovers the fog around it, by static mesh id so it survives deletes and undo.
+#[derive(Debug, Clone, Copy)]
+pub struct Revealer {
+    pub static_mesh: StaticMeshId,
+    pub radius: f32, // World units
+}
+
+/// A grid over the XY plane, the one the orthographic camera looks down on, that remembers
+/// which cells have been seen. Cells in range of a revealer this frame are visible,
+/// cells that were visible before stay explored.
+pub struct FogOfWar {
+    pub origin: Vector2<f32>, // World position of the grid's bottom left corner
+    pub cell_size: f32,
+    width: u32,
+    height: u32,
+    mask: Vec<u8>,
+    texture: Option<glow::Texture>,
+    dirty: bool,
+}
+
+impl FogOfWar {
+    pub fn new(origin: Vector2<f32>, cell_size: f32, width: u32, height: u32) -> Self {
+        Self {
+            origin,
+            cell_size: cell_size.max(f32::EPSILON),
+            width,
+            height,
+            mask: vec![UNEXPLORED; (width * height) as usize],
+            texture: None,
+            dirty: true,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Option<(u32, u32)> {
+        let cell_x = ((x - self.origin.x) / self.cell_size).floor();
+        let cell_y = ((y - self.origin.y) / self.cell_size).floor();
+        if cell_x < 0.0
+            || cell_y < 0.0
+            || cell_x >= self.width as f32
+            || cell_y >= self.height as f32
+        {
+            return None;
+        }
+        Some((cell_x as u32, cell_y as u32))
+    }
+
+    fn value_at(&self, position: Point3<f32>) -> u8 {
+        self.cell(position.x, position.y)
+            .map(|(x, y)| self.mask[(y * self.width + x) as usize])
+            .unwrap_or(UNEXPLORED)
+    }
+
+    pub fn is_visible(&self, position: Point3<f32>) -> bool {
+        self.value_at(position) == VISIBLE
+    }
+
+    pub fn is_explored(&self, position: Point3<f32>) -> bool {
+        self.value_at(position) != UNEXPLORED
+    }
+
+    /// Turns last frame's visible cells into explored ones, call before revealing this frame.
+    pub fn begin_frame(&mut self) {
+        for value in &mut self.mask {
+            if *value == VISIBLE {
+                *value = EXPLORED;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Makes every cell within `radius` of `position` visible.
+    pub fn reveal(&mut self, position: Point3<f32>, radius: f32) {
+        let cells = (radius / self.cell_size).ceil() as i64;
+        let center_x = ((position.x - self.origin.x) / self.cell_size).floor() as i64;
+        let center_y = ((position.y - self.origin.y) / self.cell_size).floor() as i64;
+
+        for y in (center_y - cells).max(0)..=(center_y + cells).min(self.height as i64 - 1) {
+            for x in (center_x - cells).max(0)..=(center_x + cells).min(self.width as i64 - 1) {
+                // Distance from the cell center
+                let world_x = self.origin.x + (x as f32 + 0.5) * self.cell_size;
+                let world_y = self.origin.y + (y as f32 + 0.5) * self.cell_size;
+                let distance_squared =
+                    (world_x - position.x).powi(2) + (world_y - position.y).powi(2);
+
+                let index = (y as u32 * self.width + x as u32) as usize;
+                if distance_squared <= radius * radius && self.mask[index] != VISIBLE {
+                    self.mask[index] = VISIBLE;
+                    self.dirty = true;
+                }
+            }
+        }
+    }
+
+    /// Updates visibility from where the revealing meshes are in the scene.
+    pub fn update(&mut self, scene: &SceneNode, revealers: &[Revealer]) {
+        self.begin_frame();
+        for revealer in revealers {
+            let Some(index) = scene.static_mesh_index(revealer.static_mesh) else {
+                continue;
+            };
+            let world = scene.static_mesh_world_matrix(index);
+            self.reveal(
+                Point3::new(world.w.x, world.w.y, world.w.z),
+                revealer.radius,
+            );
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.mask.fill(UNEXPLORED);
+        self.dirty = true;
+    }
+
+    /// The mask as a single channel texture, re-uploaded when something changed.
+    pub fn texture(&mut self, gl: &glow::Context) -> EngineResult<glow::Texture> {
+        let texture = match self.texture {
+            Some(texture) => texture,
+            None => {
+                let texture = unsafe { gl.create_texture() }.map_err(EngineError::Gl)?;
+                unsafe {
+                    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                    // Linear filtering gives the fog soft edges
+                    gl.tex_parameter_i32(
+                        glow::TEXTURE_2D,
+                        glow::TEXTURE_MIN_FILTER,
+                        glow::LINEAR as i32,
+                    );
+                    gl.tex_parameter_i32(
+                        glow::TEXTURE_2D,
+                        glow::TEXTURE_MAG_FILTER,
+                        glow::LINEAR as i32,
+                    );
+                    gl.tex_parameter_i32(
+                        glow::TEXTURE_2D,
+                        glow::TEXTURE_WRAP_S,
+                        glow::CLAMP_TO_EDGE as i32,
+                    );
+                    gl.tex_parameter_i32(
+                        glow::TEXTURE_2D,
+                        glow::TEXTURE_WRAP_T,
+                        glow::CLAMP_TO_EDGE as i32,
+                    );
+                }
+                self.texture = Some(texture);
+                self.dirty = true;
+                texture
+            }
+        };
+
+        if self.dirty {
+            unsafe {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+                gl.tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    glow::R8 as i32,
+                    self.width as i32,
+                    self.height as i32,
+                    0,
+                    glow::RED,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelUnpackData::Slice(Some(&self.mask)),
+                );
+                gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+                gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+            self.dirty = false;
+        }
+
+        Ok(texture)
+    }
+
+    /// The `mask_transform` uniform: the grid's corner and one over its size in world units.
+    pub fn mask_transform(&self) -> [f32; 4] {
+        [
+            self.origin.x,
+            self.origin.y,
+            1.0 / (self.width as f32 * self.cell_size),
+            1.0 / (self.height as f32 * self.cell_size),
+        ]
+    }
+
+    /// The mask as an image for a minimap, north up.
+    pub fn minimap_image(&self) -> egui::ColorImage {
+        let mut pixels = Vec::with_capacity(self.mask.len());
+        // Image rows go top down, the grid goes bottom up
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let value = self.mask[(y * self.width + x) as usize];
+                pixels.push(egui::Color32::from_gray(value));
+            }
+        }
+        egui::ColorImage {
+            size: [self.width as usize, self.height as usize],
+            pixels,
+        }
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(texture) = self.texture.take() {
+            unsafe { gl.delete_texture(texture) };
+        }
+    }
+}
+
+/// Draws the fog over the viewport using the scene's depth to find each pixel on the grid.
+pub struct FogOfWarPass {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    color: glow::Texture,
+    depth: glow::Texture,
+    pub explored_brightness: f32,
+}
+
+impl FogOfWarPass {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let program =
+            shaders::load_program(gl, "shaders/fullscreen.glsl", "shaders/fog_of_war.glsl")?;
+        unsafe {
+            Ok(Self {
+                program,
+                vertex_array: gl.create_vertex_array().map_err(EngineError::Gl)?,
+                color: opengl::create_copy_texture(gl).map_err(EngineError::Gl)?,
+                depth: opengl::create_copy_texture(gl).map_err(EngineError::Gl)?,
+                explored_brightness: 0.35,
+            })
+        }
+    }
+
+    /// Call after the scene has been rendered with `camera`.
+    pub fn apply(
+        &self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        fog: &mut FogOfWar,
+    ) -> EngineResult<()> {
+        if viewport.width <= 0 || viewport.height <= 0 {
+            return Ok(());
+        }
+
+        let view_projection: Matrix4<f32> = camera.get_projection() * camera.get_view();
+        let inverse_view_projection = view_projection
+            .invert()
+            .ok_or_else(|| EngineError::Gl("The camera matrix can't be inverted".to_string()))?;
+        let inverse_array: &[f32; 16] = inverse_view_projection.as_ref();
+
+        let mask = fog.texture(gl)?;
+
+        unsafe {
+            for (unit, texture, format) in [
+                (glow::TEXTURE1, self.depth, glow::DEPTH_COMPONENT24),
+                (glow::TEXTURE0, self.color, glow::RGBA8),
+            ] {
+                gl.active_texture(unit);
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.copy_tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    format,
+                    viewport.x,
+                    viewport.y,
+                    viewport.width,
+                    viewport.height,
+                    0,
+                );
+            }
+            gl.active_texture(glow::TEXTURE2);
+            gl.bind_texture(glow::TEXTURE_2D, Some(mask));
+
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+
+            gl.use_program(Some(program));
+            gl.uniform_1_i32(location("image").as_ref(), 0);
+            gl.uniform_1_i32(location("depth").as_ref(), 1);
+            gl.uniform_1_i32(location("mask").as_ref(), 2);
+            gl.uniform_matrix_4_f32_slice(
+                location("inverse_view_projection").as_ref(),
+                false,
+                inverse_array,
+            );
+            gl.uniform_4_f32_slice(location("mask_transform").as_ref(), &fog.mask_transform());
+            gl.uniform_1_f32(
+                location("explored_brightness").as_ref(),
+                self.explored_brightness,
+            );
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+
+            for unit in [glow::TEXTURE2, glow::TEXTURE1, glow::TEXTURE0] {
+                gl.active_texture(unit);
+                gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_texture(self.color);
+            gl.delete_texture(self.depth);
+        }
+    }
+}
+
+/// Draws the explored area scaled to `size`. `texture` caches the egui texture between frames.
+pub fn minimap(
+    ui: &mut egui::Ui,
+    fog: &FogOfWar,
+    texture: &mut Option<egui::TextureHandle>,
+    size: egui::Vec2,
+) -> egui::Response {
+    let image = fog.minimap_image();
+    let handle = match texture {
+        Some(handle) => {
+            handle.set(image, egui::TextureOptions::LINEAR);
+            handle
+        }
+        None => texture.insert(ui.ctx().load_texture(
+            "FogOfWarMinimap",
+            image,
+            egui::TextureOptions::LINEAR,
+        )),
+    };
+    ui.image((handle.id(), size))
+}
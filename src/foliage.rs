@@ -0,0 +1,217 @@
+use cgmath::{Matrix4, Point3, Vector3};
+use glow::HasContext;
+
+use crate::{
+    error::{EngineError, EngineResult},
+    gl_state::GlState,
+    handles::MeshHandle,
+    loader::AssetLoader,
+    mesh::{PrimitiveUniforms, StaticMesh},
+    raycast::{self, Ray},
+    rng::Rng,
+    scene_graph::SceneNode,
+};
+
+// Scatter rays start this far above the brush and look straight down
+const SCATTER_RAY_HEIGHT: f32 = 1000.0;
+
+/// Brush settings of the scatter tool.
+#[derive(Debug, Clone)]
+pub struct FoliageBrush {
+    pub radius: f32,
+    pub density: f32, // Instances per square unit per second of painting
+    pub spacing: f32, // Minimum distance between two instances of a layer
+    pub scale: (f32, f32),
+    pub random_rotation: bool, // Around the up axis
+}
+
+impl Default for FoliageBrush {
+    fn default() -> Self {
+        Self {
+            radius: 5.0,
+            density: 2.0,
+            spacing: 0.5,
+            scale: (0.8, 1.2),
+            random_rotation: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FoliageInstance {
+    pub translation: Vector3<f32>,
+    pub rotation: f32, // Degrees around Y
+    pub scale: f32,
+}
+
+impl FoliageInstance {
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from_angle_y(cgmath::Deg(self.rotation))
+            * Matrix4::from_scale(self.scale)
+    }
+}
+
+/// Many copies of one mesh, drawn with a single instanced draw per primitive.
+pub struct FoliageLayer {
+    pub name: String,
+    pub handle: MeshHandle,
+    mesh: StaticMesh, // Only its geometry is used, the transform is ignored
+    instances: Vec<FoliageInstance>,
+    instance_buffer: glow::Buffer,
+    spawn_accumulator: f32, // Fractional instances carried over to the next frame
+    rng: Rng,
+}
+
+impl FoliageLayer {
+    pub fn new(
+        context: &glow::Context,
+        handle: MeshHandle,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Self> {
+        let name = asset_loader
+            .loaded_mesh_data
+            .get(&handle)
+            .map(|mesh| mesh.name.clone())
+            .ok_or(EngineError::MissingMesh(handle))?;
+        let mesh = StaticMesh::new(context, name.clone(), handle, asset_loader)?;
+        let instance_buffer = unsafe { context.create_buffer() }.map_err(EngineError::Gl)?;
+
+        Ok(Self {
+            name,
+            handle,
+            mesh,
+            instances: Vec::new(),
+            instance_buffer,
+            spawn_accumulator: 0.0,
+            rng: Rng::new(0x2545f491),
+        })
+    }
+
+    pub fn instances(&self) -> &[FoliageInstance] {
+        &self.instances
+    }
+
+    /// Scatters instances around `center` for one frame of painting. Each one is dropped
+    /// straight down onto the static meshes of the scene, points over nothing are skipped.
+    pub fn paint(
+        &mut self,
+        context: &glow::Context,
+        scene: &SceneNode,
+        asset_loader: &AssetLoader,
+        center: Point3<f32>,
+        brush: &FoliageBrush,
+        delta_time: f32,
+    ) {
+        let area = std::f32::consts::PI * brush.radius * brush.radius;
+        self.spawn_accumulator += brush.density * area * delta_time;
+
+        let mut added = false;
+        while self.spawn_accumulator >= 1.0 {
+            self.spawn_accumulator -= 1.0;
+
+            // Uniform over the disk
+            let distance = brush.radius * self.rng.next_f32().sqrt();
+            let angle = self.rng.next_f32() * std::f32::consts::TAU;
+            let (x, z) = (
+                center.x + distance * angle.cos(),
+                center.z + distance * angle.sin(),
+            );
+
+            let ray = Ray::new(
+                Point3::new(x, center.y + SCATTER_RAY_HEIGHT, z),
+                -Vector3::unit_y(),
+            );
+            // Against the triangles, bounds alone would leave it floating over the surface
+            let hit =
+                raycast::raycast_triangles(scene, asset_loader, &ray, f32::INFINITY, |_| true);
+            let Some(hit) = hit else {
+                continue;
+            };
+
+            let spacing2 = brush.spacing * brush.spacing;
+            let translation = Vector3::new(hit.point.x, hit.point.y, hit.point.z);
+            let crowded = self.instances.iter().any(|instance| {
+                let offset = instance.translation - translation;
+                offset.x * offset.x + offset.z * offset.z < spacing2
+            });
+            if crowded {
+                continue;
+            }
+
+            let rotation = if brush.random_rotation {
+                self.rng.next_f32() * 360.0
+            } else {
+                0.0
+            };
+            let scale = self.rng.range(brush.scale);
+            self.instances.push(FoliageInstance {
+                translation,
+                rotation,
+                scale,
+            });
+            added = true;
+        }
+
+        if added {
+            self.upload(context);
+        }
+    }
+
+    /// Removes every instance within `radius` of `center` on the ground plane.
+    pub fn erase(&mut self, context: &glow::Context, center: Point3<f32>, radius: f32) {
+        let count = self.instances.len();
+        self.instances.retain(|instance| {
+            let (dx, dz) = (
+                instance.translation.x - center.x,
+                instance.translation.z - center.z,
+            );
+            dx * dx + dz * dz > radius * radius
+        });
+        if self.instances.len() != count {
+            self.upload(context);
+        }
+    }
+
+    pub fn clear(&mut self, context: &glow::Context) {
+        self.instances.clear();
+        self.upload(context);
+    }
+
+    fn upload(&self, context: &glow::Context) {
+        let data: Vec<f32> = self
+            .instances
+            .iter()
+            .flat_map(|instance| {
+                let matrix: [[f32; 4]; 4] = instance.model_matrix().into();
+                matrix.into_iter().flatten()
+            })
+            .collect();
+        unsafe {
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer));
+            context.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&data),
+                glow::DYNAMIC_DRAW,
+            );
+            context.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+
+    /// Draws with the bound program, `camMatrix` must only hold the view projection.
+    pub fn render(&self, state: &mut GlState, uniforms: &PrimitiveUniforms) {
+        self.mesh.render_instanced(
+            state,
+            uniforms,
+            self.instance_buffer,
+            self.instances.len() as i32,
+        );
+    }
+
+    pub fn destroy(&self, context: &glow::Context) {
+        self.mesh.destroy(context);
+        unsafe {
+            context.delete_buffer(self.instance_buffer);
+        }
+    }
+}
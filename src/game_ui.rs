@@ -0,0 +1,385 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use glow::HasContext;
+
+use crate::{
+    error::{EngineError, EngineResult},
+    handles::TextureHandle,
+    loader::AssetLoader,
+    opengl::StreamBuffer,
+    shaders,
+    text::{FontAtlas, GlyphQuad},
+    textures::Texture,
+    viewport::Viewport,
+};
+
+const VERTEX_FLOATS: usize = 9; // Position, UV, color and whether the texture is the font
+const VERTEX_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Where on the viewport an element is placed from. Offsets are in pixels, y going down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// The anchor as a fraction of the viewport and of the element, 0 to 1 from the top left.
+    fn fraction(self) -> [f32; 2] {
+        match self {
+            Anchor::TopLeft => [0.0, 0.0],
+            Anchor::Top => [0.5, 0.0],
+            Anchor::TopRight => [1.0, 0.0],
+            Anchor::Left => [0.0, 0.5],
+            Anchor::Center => [0.5, 0.5],
+            Anchor::Right => [1.0, 0.5],
+            Anchor::BottomLeft => [0.0, 1.0],
+            Anchor::Bottom => [0.5, 1.0],
+            Anchor::BottomRight => [1.0, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum UiElement {
+    Text {
+        text: String,
+        size: f32, // Pixels per line
+        color: [f32; 4],
+    },
+    Image {
+        texture: TextureHandle,
+        size: [f32; 2],
+        color: [f32; 4],
+    },
+    Bar {
+        size: [f32; 2],
+        fraction: f32, // Filled from the left, 0 to 1
+        fill: [f32; 4],
+        background: [f32; 4],
+    },
+}
+
+#[derive(Debug, Clone)]
+struct PlacedElement {
+    anchor: Anchor,
+    offset: [f32; 2],
+    element: UiElement,
+}
+
+/// The HUD for this frame, drawn over the viewport in play mode. Gameplay code fills it every
+/// frame, unlike the editor's egui it isn't tied to any panels.
+#[derive(Debug, Default)]
+pub struct GameUi {
+    elements: Vec<PlacedElement>,
+}
+
+impl GameUi {
+    pub fn add(&mut self, anchor: Anchor, offset: [f32; 2], element: UiElement) {
+        self.elements.push(PlacedElement {
+            anchor,
+            offset,
+            element,
+        });
+    }
+
+    pub fn text(
+        &mut self,
+        anchor: Anchor,
+        offset: [f32; 2],
+        text: &str,
+        size: f32,
+        color: [f32; 4],
+    ) {
+        let text = text.to_string();
+        self.add(anchor, offset, UiElement::Text { text, size, color });
+    }
+
+    pub fn image(
+        &mut self,
+        anchor: Anchor,
+        offset: [f32; 2],
+        texture: TextureHandle,
+        size: [f32; 2],
+    ) {
+        let color = [1.0; 4];
+        self.add(
+            anchor,
+            offset,
+            UiElement::Image {
+                texture,
+                size,
+                color,
+            },
+        );
+    }
+
+    pub fn bar(
+        &mut self,
+        anchor: Anchor,
+        offset: [f32; 2],
+        size: [f32; 2],
+        fraction: f32,
+        fill: [f32; 4],
+    ) {
+        let background = [0.0, 0.0, 0.0, 0.5];
+        let bar = UiElement::Bar {
+            size,
+            fraction,
+            fill,
+            background,
+        };
+        self.add(anchor, offset, bar);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+}
+
+/// Draws a `GameUi` in as few calls as the textures allow, in the order it was filled.
+pub struct GameUiRenderer {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    buffer: StreamBuffer,
+    vertex_data: Vec<f32>,
+    batches: Vec<(glow::Texture, usize)>, // Texture and vertex count, drawn one after another
+    font: FontAtlas,
+    white: glow::Texture,                      // Bound for bars
+    textures: HashMap<TextureHandle, Texture>, // Uploaded the first time an image uses them
+}
+
+impl GameUiRenderer {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let font = FontAtlas::new(gl, FontAtlas::default_font()?)?;
+        let program =
+            shaders::load_program(gl, "shaders/game_ui_vertex.glsl", "shaders/game_ui.glsl")?;
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            let buffer = StreamBuffer::new(gl, VERTEX_BUFFER_BYTES)?;
+            // Pointed at where the frame's vertices went when it's drawn
+            gl.bind_vertex_array(Some(vertex_array));
+            for attribute in 0..4 {
+                gl.enable_vertex_attrib_array(attribute);
+            }
+            gl.bind_vertex_array(None);
+
+            let white = gl.create_texture().map_err(EngineError::Gl)?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(white));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA as i32,
+                1,
+                1,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(&[255; 4])),
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                buffer,
+                vertex_data: Vec::new(),
+                batches: Vec::new(),
+                font,
+                white,
+                textures: HashMap::new(),
+            })
+        }
+    }
+
+    /// Adds a quad in pixels from the viewport's top left, starting a new batch when the
+    /// texture changes.
+    fn push_quad(
+        &mut self,
+        texture: glow::Texture,
+        rect: [f32; 4],
+        uv: [f32; 4],
+        color: [f32; 4],
+        font: bool,
+    ) {
+        let [x0, y0, x1, y1] = rect;
+        let [u0, v0, u1, v1] = uv;
+        let font = if font { 1.0 } else { 0.0 };
+        for (x, y, u, v) in [
+            (x0, y0, u0, v0),
+            (x1, y0, u1, v0),
+            (x1, y1, u1, v1),
+            (x0, y0, u0, v0),
+            (x1, y1, u1, v1),
+            (x0, y1, u0, v1),
+        ] {
+            self.vertex_data.extend_from_slice(&[x, y, u, v]);
+            self.vertex_data.extend_from_slice(&color);
+            self.vertex_data.push(font);
+        }
+        match self.batches.last_mut() {
+            Some((last, count)) if *last == texture => *count += 6,
+            _ => self.batches.push((texture, 6)),
+        }
+    }
+
+    /// Draws everything in `ui` over the viewport and empties it.
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        ui: &mut GameUi,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        // Unloaded textures go, new ones are uploaded
+        self.textures.retain(|handle, texture| {
+            let loaded = asset_loader.loaded_texture_data.contains_key(handle);
+            if !loaded {
+                unsafe { gl.delete_texture(texture.texture) };
+            }
+            loaded
+        });
+
+        self.vertex_data.clear();
+        self.batches.clear();
+        let viewport_size = [viewport.width as f32, viewport.height as f32];
+        for placed in std::mem::take(&mut ui.elements) {
+            let size = match &placed.element {
+                UiElement::Text { text, size, .. } => {
+                    let lines = text.lines().count().max(1) as f32;
+                    [self.font.layout(text).1 * size, lines * size]
+                }
+                UiElement::Image { size, .. } | UiElement::Bar { size, .. } => *size,
+            };
+            // The anchor is the same point of the viewport and of the element
+            let [fx, fy] = placed.anchor.fraction();
+            let left = fx * (viewport_size[0] - size[0]) + placed.offset[0];
+            let top = fy * (viewport_size[1] - size[1]) + placed.offset[1];
+
+            match placed.element {
+                UiElement::Text { text, size, color } => {
+                    let texture = self.font.texture();
+                    // Layout y goes up from the first line's top
+                    for GlyphQuad { rect: [x0, y0, x1, y1], uv } in self.font.layout(&text).0 {
+                        let rect = [
+                            left + x0 * size,
+                            top - y1 * size,
+                            left + x1 * size,
+                            top - y0 * size,
+                        ];
+                        // Screen y goes down, so the glyph's top edge comes first
+                        let uv = [uv[0], uv[3], uv[2], uv[1]];
+                        self.push_quad(texture, rect, uv, color, true);
+                    }
+                }
+                UiElement::Image {
+                    texture,
+                    size,
+                    color,
+                } => {
+                    let texture = match self.textures.entry(texture) {
+                        Entry::Occupied(entry) => entry.into_mut().texture,
+                        Entry::Vacant(entry) => {
+                            let Some(loaded) = asset_loader.loaded_texture_data.get(&texture) else {
+                                continue; // Still loading
+                            };
+                            let uploaded = Texture::from_loaded_data(gl, None, loaded.clone())?;
+                            entry.insert(uploaded).texture
+                        }
+                    };
+                    let rect = [left, top, left + size[0], top + size[1]];
+                    // Textures are stored bottom row first, like the sprites expect
+                    self.push_quad(texture, rect, [0.0, 1.0, 1.0, 0.0], color, false);
+                }
+                UiElement::Bar {
+                    size,
+                    fraction,
+                    fill,
+                    background,
+                } => {
+                    let filled = left + size[0] * fraction.clamp(0.0, 1.0);
+                    let (white, uv) = (self.white, [0.0; 4]);
+                    let rect = [left, top, left + size[0], top + size[1]];
+                    self.push_quad(white, rect, uv, background, false);
+                    self.push_quad(white, [left, top, filled, top + size[1]], uv, fill, false);
+                }
+            }
+        }
+        if self.vertex_data.is_empty() {
+            return Ok(());
+        }
+
+        // Pixels from the top left, y going down
+        let projection = cgmath::ortho(0.0, viewport_size[0], viewport_size[1], 0.0, -1.0, 1.0);
+        let projection: &[f32; 16] = projection.as_ref();
+        unsafe {
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+            gl.use_program(Some(program));
+            gl.uniform_matrix_4_f32_slice(location("projection").as_ref(), false, projection);
+            gl.uniform_1_i32(location("image").as_ref(), 0);
+            gl.active_texture(glow::TEXTURE0);
+
+            let offset = self
+                .buffer
+                .write(gl, bytemuck::cast_slice(&self.vertex_data))? as i32;
+            let stride = (VERTEX_FLOATS * std::mem::size_of::<f32>()) as i32;
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer.buffer()));
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, stride, offset);
+            gl.vertex_attrib_pointer_f32(1, 2, glow::FLOAT, false, stride, offset + 2 * 4);
+            gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, offset + 4 * 4);
+            gl.vertex_attrib_pointer_f32(3, 1, glow::FLOAT, false, stride, offset + 8 * 4);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            let mut first = 0;
+            for &(texture, count) in &self.batches {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.draw_arrays(glow::TRIANGLES, first as i32, count as i32);
+                first += count;
+            }
+
+            gl.bind_vertex_array(None);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.use_program(None);
+            gl.enable(glow::DEPTH_TEST);
+            gl.disable(glow::BLEND);
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        self.font.destroy(gl);
+        self.buffer.destroy(gl);
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_texture(self.white);
+            for texture in self.textures.values() {
+                gl.delete_texture(texture.texture);
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{
+    component::Component,
+    inventory::Inventory,
+    mesh::StaticMeshId,
+    raycast::{raycast_filtered, Ray},
+    scene_graph::SceneNode,
+};
+
+// Gameplay state is keyed by static mesh id, so it stays with its mesh when others are
+// deleted or put back by undo. `SceneNode::static_mesh_index` finds the mesh.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Team(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    pub invulnerable: bool,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerable: false,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Removes up to `amount` health and returns how much was actually removed.
+    pub fn damage(&mut self, amount: f32) -> f32 {
+        if self.invulnerable || self.is_dead() {
+            return 0.0;
+        }
+
+        let applied = amount.min(self.current);
+        self.current -= applied;
+        applied
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.current = (self.current + amount).min(self.max);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DamageEvent {
+    pub target: StaticMeshId,
+    pub amount: f32,
+    pub instigator: Option<StaticMeshId>,
+    pub team: Option<Team>, // Damage from a team never hurts members of the same team
+    pub point: Option<Point3<f32>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Projectile {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub damage: f32,
+    pub team: Option<Team>,
+    pub instigator: Option<StaticMeshId>,
+    pub lifetime: f32, // Seconds left before the projectile is removed
+}
+
+#[derive(Default)]
+pub struct GameplayWorld {
+    pub health: HashMap<StaticMeshId, Health>,
+    pub teams: HashMap<StaticMeshId, Team>,
+    pub inventories: HashMap<StaticMeshId, Inventory>,
+    pub projectiles: Vec<Projectile>,
+
+    pending_damage: Vec<DamageEvent>,
+    applied_damage: Vec<DamageEvent>,
+    deaths: Vec<StaticMeshId>,
+}
+
+impl GameplayWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_health(&mut self, static_mesh: StaticMeshId, max: f32) {
+        self.health.insert(static_mesh, Health::new(max));
+    }
+
+    pub fn set_team(&mut self, static_mesh: StaticMeshId, team: Team) {
+        self.teams.insert(static_mesh, team);
+    }
+
+    /// Gives the meshes with Health and Team components their health and team.
+    pub fn spawn_components(&mut self, scene: &SceneNode) {
+        for mesh in &scene.static_meshes {
+            for component in &mesh.components {
+                match component {
+                    Component::Health { max, invulnerable } => {
+                        let mut health = Health::new(*max);
+                        health.invulnerable = *invulnerable;
+                        self.health.insert(mesh.id, health);
+                    }
+                    Component::Team(team) => self.set_team(mesh.id, Team(*team)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    pub fn give_inventory(
+        &mut self,
+        static_mesh: StaticMeshId,
+        slot_count: usize,
+    ) -> &mut Inventory {
+        self.inventories
+            .entry(static_mesh)
+            .or_insert_with(|| Inventory::new(slot_count))
+    }
+
+    pub fn inventory(&self, static_mesh: StaticMeshId) -> Option<&Inventory> {
+        self.inventories.get(&static_mesh)
+    }
+
+    pub fn inventory_mut(&mut self, static_mesh: StaticMeshId) -> Option<&mut Inventory> {
+        self.inventories.get_mut(&static_mesh)
+    }
+
+    pub fn is_friendly(&self, static_mesh: StaticMeshId, team: Option<Team>) -> bool {
+        match (team, self.teams.get(&static_mesh)) {
+            (Some(a), Some(b)) => a == *b,
+            _ => false,
+        }
+    }
+
+    /// Queues damage, it is applied on the next `update`.
+    pub fn apply_damage(&mut self, event: DamageEvent) {
+        self.pending_damage.push(event);
+    }
+
+    pub fn spawn_projectile(&mut self, projectile: Projectile) {
+        self.projectiles.push(projectile);
+    }
+
+    /// Moves projectiles, tests them against the scene and resolves all queued damage.
+    pub fn update(&mut self, delta_time: f32, scene: &SceneNode) {
+        let mut projectiles = std::mem::take(&mut self.projectiles);
+        let mut hits = Vec::new();
+
+        projectiles.retain_mut(|projectile| {
+            projectile.lifetime -= delta_time;
+            if projectile.lifetime <= 0.0 {
+                return false;
+            }
+
+            let step = projectile.velocity * delta_time;
+            let distance = step.magnitude();
+            if distance <= f32::EPSILON {
+                return true;
+            }
+
+            let ray = Ray::new(projectile.position, step);
+            let hit = raycast_filtered(scene, &ray, distance, |i| {
+                let id = scene.static_meshes[i].id;
+                Some(id) != projectile.instigator && !self.is_friendly(id, projectile.team)
+            });
+
+            match hit {
+                Some(hit) => {
+                    hits.push(DamageEvent {
+                        target: scene.static_meshes[hit.static_mesh].id,
+                        amount: projectile.damage,
+                        instigator: projectile.instigator,
+                        team: projectile.team,
+                        point: Some(hit.point),
+                    });
+                    false
+                }
+                None => {
+                    projectile.position += step;
+                    true
+                }
+            }
+        });
+
+        self.projectiles = projectiles;
+        self.pending_damage.extend(hits);
+
+        for mut event in std::mem::take(&mut self.pending_damage) {
+            if self.is_friendly(event.target, event.team) {
+                continue;
+            }
+
+            let Some(health) = self.health.get_mut(&event.target) else {
+                continue;
+            };
+
+            let was_dead = health.is_dead();
+            event.amount = health.damage(event.amount);
+            if event.amount <= 0.0 {
+                continue;
+            }
+
+            if !was_dead && health.is_dead() {
+                log::info!("Static mesh {} died", event.target.0);
+                self.deaths.push(event.target);
+            }
+
+            self.applied_damage.push(event);
+        }
+    }
+
+    /// Damage that was actually applied since the last call, for hit reactions and UI.
+    pub fn drain_damage_events(&mut self) -> Vec<DamageEvent> {
+        std::mem::take(&mut self.applied_damage)
+    }
+
+    /// Static meshes whose health reached zero since the last call.
+    pub fn drain_deaths(&mut self) -> Vec<StaticMeshId> {
+        std::mem::take(&mut self.deaths)
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+// Vertices closer than this are welded together when smoothing normals
+const WELD_DISTANCE: f32 = 1e-5;
+
+// The angle of a triangle's corner at `at`, MikkTSpace weighs each triangle's contribution to a
+// vertex by it so splitting a face into more triangles doesn't change the result
+fn corner_angle(at: Vector3<f32>, next: Vector3<f32>, previous: Vector3<f32>) -> f32 {
+    let (a, b) = (next - at, previous - at);
+    if a.magnitude2() < 1e-12 || b.magnitude2() < 1e-12 {
+        return 0.0;
+    }
+    a.normalize().dot(b.normalize()).clamp(-1.0, 1.0).acos()
+}
+
+/// Smooth per vertex normals for a triangle list. Vertices at the same position share a normal
+/// even when they were split for UV seams, so the seams don't show in the lighting.
+pub fn generate_normals(positions: &[[f32; 3]], indices: Option<&[u32]>) -> Vec<[f32; 3]> {
+    let vertex_count = positions.len();
+
+    // Every vertex points at the first one found at its position
+    let mut welded = HashMap::new();
+    let group: Vec<usize> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let key = position.map(|value| (value / WELD_DISTANCE).round() as i64);
+            *welded.entry(key).or_insert(i)
+        })
+        .collect();
+
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); vertex_count];
+    let triangle_count = indices.map_or(vertex_count, |indices| indices.len()) / 3;
+    for triangle in 0..triangle_count {
+        let corner = |i: usize| match indices {
+            Some(indices) => indices[triangle * 3 + i] as usize,
+            None => triangle * 3 + i,
+        };
+        let corners = [corner(0), corner(1), corner(2)];
+        if corners.iter().any(|&i| i >= vertex_count) {
+            continue;
+        }
+
+        let [p0, p1, p2] = corners.map(|i| Vector3::from(positions[i]));
+        let face_normal = (p1 - p0).cross(p2 - p0);
+        if face_normal.magnitude2() < 1e-12 {
+            continue; // Degenerate, it has no direction to give
+        }
+        let face_normal = face_normal.normalize();
+        normals[group[corners[0]]] += face_normal * corner_angle(p0, p1, p2);
+        normals[group[corners[1]]] += face_normal * corner_angle(p1, p2, p0);
+        normals[group[corners[2]]] += face_normal * corner_angle(p2, p0, p1);
+    }
+
+    (0..vertex_count)
+        .map(|i| {
+            let normal = normals[group[i]];
+            if normal.magnitude2() < 1e-12 {
+                [0.0, 1.0, 0.0] // Only part of degenerate triangles, anything will do
+            } else {
+                normal.normalize().into()
+            }
+        })
+        .collect()
+}
+
+/// Per vertex tangents in the glTF layout, xyz along the U direction and w the handedness of the
+/// bitangent. Triangle lists only, `indices` of None means every three vertices are a triangle.
+/// Follows MikkTSpace's weighting, though vertices aren't split where the handedness flips.
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: Option<&[u32]>,
+) -> Vec<[f32; 4]> {
+    let vertex_count = positions.len();
+    let mut tangents = vec![Vector3::new(0.0, 0.0, 0.0); vertex_count];
+    let mut bitangents = vec![Vector3::new(0.0, 0.0, 0.0); vertex_count];
+
+    let triangle_count = indices.map_or(vertex_count, |indices| indices.len()) / 3;
+    for triangle in 0..triangle_count {
+        let corner = |i: usize| match indices {
+            Some(indices) => indices[triangle * 3 + i] as usize,
+            None => triangle * 3 + i,
+        };
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+        if a >= vertex_count || b >= vertex_count || c >= vertex_count {
+            continue;
+        }
+
+        let p0 = Vector3::from(positions[a]);
+        let edge1 = Vector3::from(positions[b]) - p0;
+        let edge2 = Vector3::from(positions[c]) - p0;
+        let uv0 = Vector2::from(uvs[a]);
+        let delta1 = Vector2::from(uvs[b]) - uv0;
+        let delta2 = Vector2::from(uvs[c]) - uv0;
+
+        let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue; // No UV area, nothing to learn from this triangle
+        }
+        let r = 1.0 / determinant;
+        let tangent = (edge1 * delta2.y - edge2 * delta1.y) * r;
+        let bitangent = (edge2 * delta1.x - edge1 * delta2.x) * r;
+        if tangent.magnitude2() < 1e-12 || bitangent.magnitude2() < 1e-12 {
+            continue; // Degenerate in space rather than in UVs
+        }
+
+        // Normalized first so only the corner angle decides how much a triangle counts
+        let (tangent, bitangent) = (tangent.normalize(), bitangent.normalize());
+        let p1 = p0 + edge1;
+        let p2 = p0 + edge2;
+        for (vertex, angle) in [
+            (a, corner_angle(p0, p1, p2)),
+            (b, corner_angle(p1, p2, p0)),
+            (c, corner_angle(p2, p0, p1)),
+        ] {
+            tangents[vertex] += tangent * angle;
+            bitangents[vertex] += bitangent * angle;
+        }
+    }
+
+    (0..vertex_count)
+        .map(|i| {
+            let normal = Vector3::from(normals[i]);
+            // Gram-Schmidt, the tangent has to be perpendicular to the normal
+            let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+            if tangent.magnitude2() < 1e-12 {
+                tangent = any_perpendicular(normal);
+            }
+            let tangent = tangent.normalize();
+            let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [tangent.x, tangent.y, tangent.z, handedness]
+        })
+        .collect()
+}
+
+fn any_perpendicular(normal: Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let perpendicular = normal.cross(axis);
+    if perpendicular.magnitude2() < 1e-12 {
+        Vector3::unit_x() // The normal itself is degenerate
+    } else {
+        perpendicular
+    }
+}
+
+/// Vertex clustering: every vertex inside the same grid cell collapses onto the first one found
+/// there and triangles that lose an edge are dropped. Returns indices into the original
+/// vertices, so the vertex buffer can be shared with the full detail version. `resolution` is
+/// the number of cells along the longest side of the bounds.
+pub fn simplify(positions: &[[f32; 3]], indices: Option<&[u32]>, resolution: u32) -> Vec<u32> {
+    let vertex_count = positions.len();
+    let triangle_count = indices.map_or(vertex_count, |indices| indices.len()) / 3;
+    let corner = |triangle: usize, i: usize| match indices {
+        Some(indices) => indices[triangle * 3 + i],
+        None => (triangle * 3 + i) as u32,
+    };
+
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for position in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+    let longest = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+    let cell_size = longest / resolution.max(1) as f32;
+    if cell_size <= 0.0 {
+        return (0..triangle_count * 3)
+            .map(|i| corner(i / 3, i % 3))
+            .collect();
+    }
+
+    let mut cells = std::collections::HashMap::new();
+    let representative: Vec<u32> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let cell: [i32; 3] =
+                std::array::from_fn(|axis| ((position[axis] - min[axis]) / cell_size) as i32);
+            *cells.entry(cell).or_insert(i as u32)
+        })
+        .collect();
+
+    let mut simplified = Vec::new();
+    for triangle in 0..triangle_count {
+        let corners = [
+            corner(triangle, 0),
+            corner(triangle, 1),
+            corner(triangle, 2),
+        ];
+        if corners.iter().any(|&i| i as usize >= vertex_count) {
+            continue;
+        }
+        let [a, b, c] = corners.map(|i| representative[i as usize]);
+        if a != b && b != c && a != c {
+            simplified.extend_from_slice(&[a, b, c]);
+        }
+    }
+    simplified
+}
@@ -0,0 +1,340 @@
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+use crate::{
+    camera::Camera,
+    scene_graph::SceneNode,
+    transform::{GizmoSpace, MeshTransform, Pivot},
+    undo::TransformEdit,
+};
+
+// How far one pixel of mouse movement takes a modal transform
+const TRANSLATE_PER_PIXEL: f32 = 0.01;
+const ROTATE_PER_PIXEL: f32 = 0.5; // Degrees
+const SCALE_PER_PIXEL: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Move",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+}
+
+/// Keys that start a modal transform. S flies the camera backwards, so scaling is on T.
+pub struct ModalKeys {
+    pub translate: egui::Key,
+    pub rotate: egui::Key,
+    pub scale: egui::Key,
+}
+
+impl Default for ModalKeys {
+    fn default() -> Self {
+        Self {
+            translate: egui::Key::G,
+            rotate: egui::Key::R,
+            scale: egui::Key::T,
+        }
+    }
+}
+
+/// What a moved selection's pivot snaps to while Ctrl (vertices) or Shift (surfaces) is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapMode {
+    Vertex,
+    Surface,
+}
+
+impl SnapMode {
+    pub fn held(modifiers: &egui::Modifiers) -> Option<Self> {
+        if modifiers.command {
+            Some(SnapMode::Vertex)
+        } else if modifiers.shift {
+            Some(SnapMode::Surface)
+        } else {
+            None
+        }
+    }
+}
+
+pub enum ModalState {
+    Active,
+    Confirmed(TransformEdit),
+    Cancelled,
+}
+
+/// A keyboard driven transform of the selection: press G, X, type 2.5 and Enter to move
+/// 2.5 units along X. The mouse moves freely until a value is typed, X/Y/Z pick an axis
+/// in the toolbar's space (again for the other space, a third time to go back to free),
+/// Enter or a left click confirms and Escape or a right click puts everything back.
+pub struct ModalTransform {
+    pub mode: GizmoMode,
+    pub axis: Option<usize>,
+    other_space: bool, // The axis was picked twice, it's in the space the toolbar isn't set to
+    typed: String,
+    mouse: egui::Vec2, // Pixels moved since the transform started
+    before: Vec<(usize, MeshTransform)>,
+    pivots: Vec<Vector3<f32>>, // Each object's own, in mesh space
+    snap: Option<(SnapMode, Point3<f32>)>,
+}
+
+impl ModalTransform {
+    pub fn begin(
+        mode: GizmoMode,
+        scene: &SceneNode,
+        selection: &[usize],
+        pivot: Pivot,
+    ) -> Option<Self> {
+        let before: Vec<(usize, MeshTransform)> = selection
+            .iter()
+            .filter_map(|&index| MeshTransform::of(scene, index).map(|t| (index, t)))
+            .collect();
+        if before.is_empty() {
+            return None;
+        }
+        let pivots = before
+            .iter()
+            .map(|&(index, _)| pivot.point(&scene.static_meshes[index]))
+            .collect();
+
+        Some(Self {
+            mode,
+            axis: None,
+            other_space: false,
+            typed: String::new(),
+            mouse: egui::Vec2::ZERO,
+            before,
+            pivots,
+            snap: None,
+        })
+    }
+
+    /// Feeds one frame of input, the scene shows the result right away.
+    pub fn update(
+        &mut self,
+        input: &egui::InputState,
+        scene: &mut SceneNode,
+        space: GizmoSpace,
+        camera: &dyn Camera,
+    ) -> ModalState {
+        let mut confirmed = input.pointer.primary_clicked();
+        let mut cancelled = input.pointer.secondary_clicked();
+
+        for event in &input.events {
+            match event {
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } => match key {
+                    egui::Key::X | egui::Key::Y | egui::Key::Z => {
+                        let axis = match key {
+                            egui::Key::X => 0,
+                            egui::Key::Y => 1,
+                            _ => 2,
+                        };
+                        // Scale is always local, there's no other space to go to
+                        let has_spaces = self.mode != GizmoMode::Scale;
+                        if self.axis != Some(axis) {
+                            self.axis = Some(axis);
+                            self.other_space = false;
+                        } else if has_spaces && !self.other_space {
+                            self.other_space = true;
+                        } else {
+                            self.axis = None;
+                            self.other_space = false;
+                        }
+                    }
+                    egui::Key::Backspace => {
+                        self.typed.pop();
+                    }
+                    egui::Key::Enter => confirmed = true,
+                    egui::Key::Escape => cancelled = true,
+                    _ => {}
+                },
+                egui::Event::Text(text) => {
+                    for c in text.chars() {
+                        let allowed = c.is_ascii_digit()
+                            || (c == '.' && !self.typed.contains('.'))
+                            || (c == '-' && self.typed.is_empty());
+                        if allowed {
+                            self.typed.push(c);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if cancelled {
+            self.cancel(scene);
+            return ModalState::Cancelled;
+        }
+
+        self.mouse += input.pointer.delta();
+        self.preview(scene, self.space(space), camera);
+
+        if confirmed {
+            self.confirm(scene)
+        } else {
+            ModalState::Active
+        }
+    }
+
+    /// Moves the first selected object's pivot to `target` (and the rest along with it) instead
+    /// of following the mouse, None to stop snapping. Only moving snaps.
+    pub fn snap_to(&mut self, target: Option<(SnapMode, Point3<f32>)>) {
+        self.snap = target.filter(|_| self.mode == GizmoMode::Translate);
+    }
+
+    /// The objects being transformed, which snapping shouldn't find.
+    pub fn objects(&self) -> impl Iterator<Item = usize> + '_ {
+        self.before.iter().map(|&(index, _)| index)
+    }
+
+    /// The space axes are in, `toolbar` unless the axis key was pressed twice.
+    pub fn space(&self, toolbar: GizmoSpace) -> GizmoSpace {
+        if self.other_space {
+            toolbar.toggled()
+        } else {
+            toolbar
+        }
+    }
+
+    fn typed_value(&self) -> Option<f32> {
+        match self.typed.as_str() {
+            "" => None,
+            "-" => Some(0.0),
+            typed => typed.parse().ok(),
+        }
+    }
+
+    // Units, degrees or a scale factor depending on the mode
+    fn amount(&self) -> f32 {
+        match (self.typed_value(), self.mode) {
+            (Some(value), _) => value,
+            (None, GizmoMode::Translate) => self.mouse.x * TRANSLATE_PER_PIXEL,
+            (None, GizmoMode::Rotate) => self.mouse.x * ROTATE_PER_PIXEL,
+            (None, GizmoMode::Scale) => (1.0 + self.mouse.x * SCALE_PER_PIXEL).max(0.0),
+        }
+    }
+
+    fn transformed(
+        &self,
+        before: &MeshTransform,
+        space: GizmoSpace,
+        camera: &dyn Camera,
+    ) -> MeshTransform {
+        if let Some((_, target)) = self.snap {
+            // Everything keeps its place relative to the first object
+            let (_, first) = self.before[0];
+            let offset = target.to_vec() - first.point(self.pivots[0]);
+            return MeshTransform {
+                translation: before.translation + offset,
+                ..*before
+            };
+        }
+        let amount = self.amount();
+        match (self.mode, self.axis) {
+            (GizmoMode::Translate, Some(axis)) => before.translated(space, axis, amount),
+            (GizmoMode::Translate, None) => {
+                // Free movement stays in the view plane, a typed value needs an axis
+                if self.typed_value().is_some() {
+                    return *before;
+                }
+                let right = camera.get_orientation().cross(camera.get_up()).normalize();
+                let up = right.cross(camera.get_orientation()).normalize();
+                MeshTransform {
+                    translation: before.translation
+                        + (right * self.mouse.x - up * self.mouse.y) * TRANSLATE_PER_PIXEL,
+                    ..*before
+                }
+            }
+            (GizmoMode::Rotate, Some(axis)) => before.rotated(space, axis, amount),
+            // Around the view direction, like turning a picture on the wall
+            (GizmoMode::Rotate, None) => before.turned(camera.get_orientation(), amount),
+            (GizmoMode::Scale, axis) => {
+                let mut scale = before.scale;
+                match axis {
+                    Some(axis) => scale[axis] *= amount,
+                    None => scale *= amount,
+                }
+                MeshTransform { scale, ..*before }
+            }
+        }
+    }
+
+    fn preview(&self, scene: &mut SceneNode, space: GizmoSpace, camera: &dyn Camera) {
+        for ((index, before), pivot) in self.before.iter().zip(&self.pivots) {
+            let mut after = self.transformed(before, space, camera);
+            if self.mode != GizmoMode::Translate {
+                after = before.around(after, *pivot);
+            }
+            if let Some(mesh) = scene.static_meshes.get_mut(*index) {
+                mesh.constraints.constrain(*before, after).apply_to(mesh);
+                scene.mark_moved(*index);
+            }
+        }
+    }
+
+    fn confirm(&self, scene: &SceneNode) -> ModalState {
+        let changes: Vec<(usize, MeshTransform, MeshTransform)> = self
+            .before
+            .iter()
+            .filter_map(|&(index, before)| {
+                let after = MeshTransform::of(scene, index)?;
+                (after != before).then_some((index, before, after))
+            })
+            .collect();
+        if changes.is_empty() {
+            return ModalState::Cancelled;
+        }
+        ModalState::Confirmed(TransformEdit {
+            name: self.mode.label().to_string(),
+            changes,
+        })
+    }
+
+    pub fn cancel(&self, scene: &mut SceneNode) {
+        for (index, before) in &self.before {
+            if let Some(mesh) = scene.static_meshes.get_mut(*index) {
+                before.apply_to(mesh);
+                scene.mark_moved(*index);
+            }
+        }
+    }
+
+    /// What the viewport overlay shows, e.g. "Move along local X: 2.5".
+    pub fn status(&self, toolbar: GizmoSpace) -> String {
+        if let Some((snap, _)) = self.snap {
+            let target = match snap {
+                SnapMode::Vertex => "vertex",
+                SnapMode::Surface => "surface",
+            };
+            return format!("{} to {}", self.mode.label(), target);
+        }
+        let space = match (self.mode, self.space(toolbar)) {
+            (GizmoMode::Scale, _) | (_, GizmoSpace::Local) => "local ",
+            (_, GizmoSpace::World) => "world ",
+        };
+        let axis = match self.axis {
+            Some(axis) => format!(" along {}{}", space, ["X", "Y", "Z"][axis]),
+            None => String::new(),
+        };
+        let value = match self.typed_value() {
+            Some(_) => format!("{}|", self.typed),
+            None => format!("{:.2}", self.amount()),
+        };
+        let hint = match (self.mode, self.axis, self.typed_value()) {
+            (GizmoMode::Translate, None, Some(_)) => "  (pick an axis with X, Y or Z)",
+            _ => "",
+        };
+        format!("{}{}: {}{}", self.mode.label(), axis, value, hint)
+    }
+}
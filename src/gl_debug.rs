@@ -0,0 +1,99 @@
+use glow::HasContext;
+
+/// Debug contexts are only requested in debug builds, the driver does extra validation for them.
+pub const DEBUG_CONTEXT: bool = cfg!(debug_assertions);
+
+fn source_name(source: u32) -> &'static str {
+    match source {
+        glow::DEBUG_SOURCE_API => "API",
+        glow::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        glow::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        glow::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        glow::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(message_type: u32) -> &'static str {
+    match message_type {
+        glow::DEBUG_TYPE_ERROR => "error",
+        glow::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        glow::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        glow::DEBUG_TYPE_PORTABILITY => "portability",
+        glow::DEBUG_TYPE_PERFORMANCE => "performance",
+        glow::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    }
+}
+
+pub fn error_name(error: u32) -> &'static str {
+    match error {
+        glow::INVALID_ENUM => "GL_INVALID_ENUM",
+        glow::INVALID_VALUE => "GL_INVALID_VALUE",
+        glow::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        glow::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        glow::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        glow::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        glow::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown GL error",
+    }
+}
+
+/// Routes KHR_debug messages to the log. Must be called before the context is shared.
+pub fn install(gl: &mut glow::Context) {
+    if !DEBUG_CONTEXT {
+        return;
+    }
+    if !gl.supports_debug() {
+        log::warn!("GL debug output is not supported by this context");
+        return;
+    }
+
+    unsafe {
+        gl.enable(glow::DEBUG_OUTPUT);
+        // Report on the thread that made the call, so the message lines up with the call in a debugger
+        gl.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl.debug_message_callback(|source, message_type, id, severity, message| {
+            let level = match severity {
+                glow::DEBUG_SEVERITY_HIGH => log::Level::Error,
+                glow::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+                glow::DEBUG_SEVERITY_LOW => log::Level::Info,
+                _ => log::Level::Debug, // Notifications, drivers send a lot of them
+            };
+            log::log!(
+                target: "gl",
+                level,
+                "[{} {} {}] {}",
+                source_name(source),
+                type_name(message_type),
+                id,
+                message
+            );
+        });
+    }
+
+    log::info!("GL debug output enabled");
+}
+
+/// Logs every pending `glGetError` code in debug builds, `what` names the call that was checked.
+/// Catches errors on drivers without KHR_debug too.
+#[track_caller]
+pub fn check_errors(gl: &glow::Context, what: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    loop {
+        let error = unsafe { gl.get_error() };
+        if error == glow::NO_ERROR {
+            break;
+        }
+        log::error!(
+            "{} ({:#x}) after {} at {}",
+            error_name(error),
+            error,
+            what,
+            std::panic::Location::caller()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+// Skips GL calls that wouldn't change anything. It only knows about what went through it, so
+// one is made per pass, and `forget` is called after anything is bound on the plain context.
+
+use glow::HasContext;
+
+const TRACKED_UNITS: usize = 16; // Texture units past these are always bound
+
+pub struct GlState<'a> {
+    gl: &'a glow::Context,
+    // None while it's not known what's bound
+    program: Option<Option<glow::Program>>,
+    vertex_array: Option<Option<glow::VertexArray>>,
+    active_unit: Option<u32>,
+    textures: [Option<Option<glow::Texture>>; TRACKED_UNITS], // 2D textures by unit
+    depth_test: Option<bool>,
+    cull_face: Option<bool>,
+    blend: Option<bool>,
+    depth_func: Option<u32>,
+    blend_func: Option<(u32, u32)>,
+}
+
+impl<'a> GlState<'a> {
+    pub fn new(gl: &'a glow::Context) -> Self {
+        Self {
+            gl,
+            program: None,
+            vertex_array: None,
+            active_unit: None,
+            textures: [None; TRACKED_UNITS],
+            depth_test: None,
+            cull_face: None,
+            blend: None,
+            depth_func: None,
+            blend_func: None,
+        }
+    }
+
+    /// For everything that isn't tracked, like uniforms and draws.
+    pub fn gl(&self) -> &'a glow::Context {
+        self.gl
+    }
+
+    /// Call after changing state on the plain context.
+    pub fn forget(&mut self) {
+        *self = Self::new(self.gl);
+    }
+
+    /// Just the texture units and which one is active, after something bound its own.
+    pub fn forget_textures(&mut self) {
+        self.active_unit = None;
+        self.textures = [None; TRACKED_UNITS];
+    }
+
+    pub fn use_program(&mut self, program: Option<glow::Program>) {
+        if self.program != Some(program) {
+            unsafe { self.gl.use_program(program) };
+            self.program = Some(program);
+        }
+    }
+
+    pub fn bind_vertex_array(&mut self, vertex_array: Option<glow::VertexArray>) {
+        if self.vertex_array != Some(vertex_array) {
+            unsafe { self.gl.bind_vertex_array(vertex_array) };
+            self.vertex_array = Some(vertex_array);
+        }
+    }
+
+    /// Binds a 2D texture to texture unit `unit`, not `TEXTURE0 + unit`.
+    pub fn bind_texture(&mut self, unit: u32, texture: Option<glow::Texture>) {
+        if self.textures.get(unit as usize).copied().flatten() == Some(texture) {
+            return;
+        }
+        if self.active_unit != Some(unit) {
+            unsafe { self.gl.active_texture(glow::TEXTURE0 + unit) };
+            self.active_unit = Some(unit);
+        }
+        unsafe { self.gl.bind_texture(glow::TEXTURE_2D, texture) };
+        if let Some(tracked) = self.textures.get_mut(unit as usize) {
+            *tracked = Some(texture);
+        }
+    }
+
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        set_capability(self.gl, &mut self.depth_test, glow::DEPTH_TEST, enabled);
+    }
+
+    pub fn set_cull_face(&mut self, enabled: bool) {
+        set_capability(self.gl, &mut self.cull_face, glow::CULL_FACE, enabled);
+    }
+
+    pub fn set_blend(&mut self, enabled: bool) {
+        set_capability(self.gl, &mut self.blend, glow::BLEND, enabled);
+    }
+
+    pub fn depth_func(&mut self, func: u32) {
+        if self.depth_func != Some(func) {
+            unsafe { self.gl.depth_func(func) };
+            self.depth_func = Some(func);
+        }
+    }
+
+    pub fn blend_func(&mut self, source: u32, destination: u32) {
+        if self.blend_func != Some((source, destination)) {
+            unsafe { self.gl.blend_func(source, destination) };
+            self.blend_func = Some((source, destination));
+        }
+    }
+}
+
+fn set_capability(gl: &glow::Context, current: &mut Option<bool>, capability: u32, enabled: bool) {
+    if *current == Some(enabled) {
+        return;
+    }
+    unsafe {
+        if enabled {
+            gl.enable(capability);
+        } else {
+            gl.disable(capability);
+        }
+    }
+    *current = Some(enabled);
+}
@@ -0,0 +1,136 @@
+use glow::HasContext;
+
+// Results are read a few frames late so the CPU never waits on the GPU
+const QUERY_FRAMES: usize = 3;
+
+struct PassQueries {
+    name: &'static str,
+    queries: [Option<glow::Query>; QUERY_FRAMES],
+    pending: [bool; QUERY_FRAMES],
+    milliseconds: f32,
+}
+
+/// `GL_TIME_ELAPSED` queries around each render pass, reported as GPU milliseconds.
+pub struct GpuTimers {
+    passes: Vec<PassQueries>,
+    frame: usize,
+    active: Option<usize>, // Only one TIME_ELAPSED query can be running at a time
+}
+
+impl Default for GpuTimers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GpuTimers {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            frame: 0,
+            active: None,
+        }
+    }
+
+    /// Starts timing `name`, passes show up in the order they are first begun.
+    pub fn begin(&mut self, gl: &glow::Context, name: &'static str) {
+        if let Some(active) = self.active {
+            log::warn!(
+                "GPU timer '{}' began while '{}' was still running",
+                name,
+                self.passes[active].name
+            );
+            self.end(gl);
+        }
+
+        let index = match self.passes.iter().position(|pass| pass.name == name) {
+            Some(index) => index,
+            None => {
+                self.passes.push(PassQueries {
+                    name,
+                    queries: [None; QUERY_FRAMES],
+                    pending: [false; QUERY_FRAMES],
+                    milliseconds: 0.0,
+                });
+                self.passes.len() - 1
+            }
+        };
+
+        let slot = self.frame % QUERY_FRAMES;
+        let pass = &mut self.passes[index];
+
+        // The previous result in this slot was never collected, don't overwrite a running query
+        if pass.pending[slot] {
+            return;
+        }
+
+        if pass.queries[slot].is_none() {
+            match unsafe { gl.create_query() } {
+                Ok(query) => pass.queries[slot] = Some(query),
+                Err(e) => {
+                    log::error!("Failed to create GPU timer query: {}", e);
+                    return;
+                }
+            }
+        }
+
+        unsafe {
+            gl.begin_query(glow::TIME_ELAPSED, pass.queries[slot].unwrap());
+        }
+        pass.pending[slot] = true;
+        self.active = Some(index);
+    }
+
+    pub fn end(&mut self, gl: &glow::Context) {
+        if self.active.take().is_some() {
+            unsafe {
+                gl.end_query(glow::TIME_ELAPSED);
+            }
+        }
+    }
+
+    /// Reads back every finished query and moves on to the next frame's slots.
+    pub fn end_frame(&mut self, gl: &glow::Context) {
+        self.end(gl);
+
+        for pass in &mut self.passes {
+            for slot in 0..QUERY_FRAMES {
+                let Some(query) = pass.queries[slot] else {
+                    continue;
+                };
+                if !pass.pending[slot] {
+                    continue;
+                }
+
+                let available =
+                    unsafe { gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) };
+                if available != 0 {
+                    // Nanoseconds, a u32 covers a little over four seconds which is plenty for a pass
+                    let elapsed = unsafe { gl.get_query_parameter_u32(query, glow::QUERY_RESULT) };
+                    pass.milliseconds = elapsed as f32 / 1_000_000.0;
+                    pass.pending[slot] = false;
+                }
+            }
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// The latest GPU time of each pass in milliseconds.
+    pub fn timings(&self) -> Vec<(&'static str, f32)> {
+        self.passes
+            .iter()
+            .map(|pass| (pass.name, pass.milliseconds))
+            .collect()
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        for pass in self.passes.drain(..) {
+            for query in pass.queries.into_iter().flatten() {
+                unsafe {
+                    gl.delete_query(query);
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::Viewport;
+use crate::viewport::Viewport;
 use cgmath::{Deg, Matrix4, Point3, Vector3};
 use glow::HasContext;
 use std::fs;
@@ -0,0 +1,560 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use cgmath::{Matrix4, Point3, Vector2};
+use glow::HasContext;
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    mesh::StaticMeshId,
+    shaders,
+};
+
+const SQRT_3: f32 = 1.732_050_8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridShape {
+    Square { diagonals: bool },
+    Hex, // Pointy top, odd rows are shifted right by half a cell
+}
+
+/// Column and row of a cell, hex grids use odd-r offset coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GridCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl GridCoord {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    // Offset to axial and back, the hex math is much simpler in axial coordinates
+    fn to_axial(self) -> (i32, i32) {
+        (self.x - (self.y - (self.y & 1)) / 2, self.y)
+    }
+
+    fn from_axial(q: i32, r: i32) -> Self {
+        Self::new(q + (r - (r & 1)) / 2, r)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GridCell {
+    pub walkable: bool,
+    pub cost: f32,                      // Cost of stepping into the cell
+    pub occupant: Option<StaticMeshId>, // Like the gameplay components
+}
+
+impl Default for GridCell {
+    fn default() -> Self {
+        Self {
+            walkable: true,
+            cost: 1.0,
+            occupant: None,
+        }
+    }
+}
+
+/// A square or hex board on the XY plane for tactics style prototypes: which cells can be
+/// walked on, who stands where, paths and movement ranges.
+pub struct GridMap {
+    pub shape: GridShape,
+    pub origin: Point3<f32>, // World position of cell (0, 0)'s center
+    pub cell_size: f32,      // Edge length for squares, center to corner for hexes
+    width: i32,
+    height: i32,
+    cells: Vec<GridCell>,
+}
+
+// Min-heap entry for the searches
+#[derive(PartialEq)]
+struct Frontier {
+    priority: f32,
+    coord: GridCoord,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| self.coord.cmp(&other.coord))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl GridMap {
+    pub fn new(
+        shape: GridShape,
+        origin: Point3<f32>,
+        cell_size: f32,
+        width: i32,
+        height: i32,
+    ) -> Self {
+        let width = width.max(0);
+        let height = height.max(0);
+        Self {
+            shape,
+            origin,
+            cell_size: cell_size.max(f32::EPSILON),
+            width,
+            height,
+            cells: vec![GridCell::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn in_bounds(&self, coord: GridCoord) -> bool {
+        coord.x >= 0 && coord.y >= 0 && coord.x < self.width && coord.y < self.height
+    }
+
+    fn index(&self, coord: GridCoord) -> Option<usize> {
+        self.in_bounds(coord)
+            .then(|| (coord.y * self.width + coord.x) as usize)
+    }
+
+    pub fn cell(&self, coord: GridCoord) -> Option<&GridCell> {
+        self.index(coord).map(|index| &self.cells[index])
+    }
+
+    pub fn cell_mut(&mut self, coord: GridCoord) -> Option<&mut GridCell> {
+        self.index(coord).map(|index| &mut self.cells[index])
+    }
+
+    pub fn coords(&self) -> impl Iterator<Item = GridCoord> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| GridCoord::new(x, y)))
+    }
+
+    pub fn set_walkable(&mut self, coord: GridCoord, walkable: bool) {
+        if let Some(cell) = self.cell_mut(coord) {
+            cell.walkable = walkable;
+        }
+    }
+
+    pub fn set_cost(&mut self, coord: GridCoord, cost: f32) {
+        if let Some(cell) = self.cell_mut(coord) {
+            cell.cost = cost.max(0.0);
+        }
+    }
+
+    /// Walkable and nobody standing on it.
+    pub fn is_free(&self, coord: GridCoord) -> bool {
+        self.cell(coord)
+            .is_some_and(|cell| cell.walkable && cell.occupant.is_none())
+    }
+
+    pub fn occupant(&self, coord: GridCoord) -> Option<StaticMeshId> {
+        self.cell(coord).and_then(|cell| cell.occupant)
+    }
+
+    pub fn find_occupant(&self, occupant: StaticMeshId) -> Option<GridCoord> {
+        self.coords()
+            .find(|&coord| self.occupant(coord) == Some(occupant))
+    }
+
+    pub fn occupy(&mut self, coord: GridCoord, occupant: StaticMeshId) -> Result<(), String> {
+        if !self.is_free(coord) {
+            return Err(format!("Cell {:?} is blocked or occupied", coord));
+        }
+        let index = self.index(coord).unwrap();
+        self.cells[index].occupant = Some(occupant);
+        Ok(())
+    }
+
+    /// Clears the cell and returns who was standing there.
+    pub fn vacate(&mut self, coord: GridCoord) -> Option<StaticMeshId> {
+        self.cell_mut(coord).and_then(|cell| cell.occupant.take())
+    }
+
+    pub fn move_occupant(&mut self, from: GridCoord, to: GridCoord) -> Result<(), String> {
+        let occupant = self
+            .occupant(from)
+            .ok_or_else(|| format!("Nobody is standing on {:?}", from))?;
+        self.occupy(to, occupant)?;
+        self.vacate(from);
+        Ok(())
+    }
+
+    pub fn neighbors(&self, coord: GridCoord) -> Vec<GridCoord> {
+        let mut neighbors = Vec::with_capacity(8);
+        match self.shape {
+            GridShape::Square { diagonals } => {
+                for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    neighbors.push(GridCoord::new(coord.x + dx, coord.y + dy));
+                }
+                if diagonals {
+                    for (dx, dy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                        // No cutting corners past blocked cells
+                        let beside = GridCoord::new(coord.x + dx, coord.y);
+                        let above = GridCoord::new(coord.x, coord.y + dy);
+                        if self.cell(beside).is_some_and(|cell| cell.walkable)
+                            && self.cell(above).is_some_and(|cell| cell.walkable)
+                        {
+                            neighbors.push(GridCoord::new(coord.x + dx, coord.y + dy));
+                        }
+                    }
+                }
+            }
+            GridShape::Hex => {
+                let (q, r) = coord.to_axial();
+                for (dq, dr) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)] {
+                    neighbors.push(GridCoord::from_axial(q + dq, r + dr));
+                }
+            }
+        }
+        neighbors.retain(|&neighbor| self.in_bounds(neighbor));
+        neighbors
+    }
+
+    /// Steps between two cells on an empty board.
+    pub fn distance(&self, a: GridCoord, b: GridCoord) -> i32 {
+        let dx = (a.x - b.x).abs();
+        let dy = (a.y - b.y).abs();
+        match self.shape {
+            GridShape::Square { diagonals: false } => dx + dy,
+            GridShape::Square { diagonals: true } => dx.max(dy),
+            GridShape::Hex => {
+                let (aq, ar) = a.to_axial();
+                let (bq, br) = b.to_axial();
+                let (dq, dr) = (aq - bq, ar - br);
+                (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+            }
+        }
+    }
+
+    fn step_cost(&self, from: GridCoord, to: GridCoord) -> f32 {
+        let cost = self.cell(to).map(|cell| cell.cost).unwrap_or(f32::INFINITY);
+        if from.x != to.x && from.y != to.y && matches!(self.shape, GridShape::Square { .. }) {
+            cost * std::f32::consts::SQRT_2
+        } else {
+            cost
+        }
+    }
+
+    fn heuristic(&self, from: GridCoord, to: GridCoord, min_cost: f32) -> f32 {
+        let dx = (from.x - to.x).abs() as f32;
+        let dy = (from.y - to.y).abs() as f32;
+        let steps = match self.shape {
+            // Octile distance, diagonal steps cost more
+            GridShape::Square { diagonals: true } => {
+                dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+            }
+            _ => self.distance(from, to) as f32,
+        };
+        steps * min_cost
+    }
+
+    fn passable(&self, coord: GridCoord, start: GridCoord) -> bool {
+        coord == start || self.is_free(coord)
+    }
+
+    /// Cheapest path from `from` to `to` including both ends, through walkable unoccupied cells.
+    pub fn find_path(&self, from: GridCoord, to: GridCoord) -> Option<Vec<GridCoord>> {
+        if !self.in_bounds(from) || !self.is_free(to) {
+            return None;
+        }
+
+        // Keeps the heuristic admissible when cells are cheaper than one
+        let min_cost = self
+            .cells
+            .iter()
+            .filter(|cell| cell.walkable)
+            .map(|cell| cell.cost)
+            .fold(f32::INFINITY, f32::min)
+            .min(1.0);
+
+        let mut came_from: HashMap<GridCoord, GridCoord> = HashMap::new();
+        let mut costs: HashMap<GridCoord, f32> = HashMap::from([(from, 0.0)]);
+        let mut open = BinaryHeap::from([Frontier {
+            priority: self.heuristic(from, to, min_cost),
+            coord: from,
+        }]);
+
+        while let Some(Frontier { coord, .. }) = open.pop() {
+            if coord == to {
+                let mut path = vec![to];
+                while let Some(&previous) = came_from.get(path.last().unwrap()) {
+                    path.push(previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let cost = costs[&coord];
+            for neighbor in self.neighbors(coord) {
+                if !self.passable(neighbor, from) {
+                    continue;
+                }
+                let new_cost = cost + self.step_cost(coord, neighbor);
+                if costs.get(&neighbor).is_none_or(|&old| new_cost < old) {
+                    costs.insert(neighbor, new_cost);
+                    came_from.insert(neighbor, coord);
+                    open.push(Frontier {
+                        priority: new_cost + self.heuristic(neighbor, to, min_cost),
+                        coord: neighbor,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Every cell a unit on `from` can walk to with `budget` movement, and what it costs to get there.
+    pub fn reachable(&self, from: GridCoord, budget: f32) -> HashMap<GridCoord, f32> {
+        let mut costs: HashMap<GridCoord, f32> = HashMap::new();
+        if !self.in_bounds(from) {
+            return costs;
+        }
+
+        costs.insert(from, 0.0);
+        let mut open = BinaryHeap::from([Frontier {
+            priority: 0.0,
+            coord: from,
+        }]);
+        while let Some(Frontier { priority, coord }) = open.pop() {
+            if priority > costs[&coord] {
+                continue; // Already found a cheaper way here
+            }
+            for neighbor in self.neighbors(coord) {
+                if !self.passable(neighbor, from) {
+                    continue;
+                }
+                let new_cost = priority + self.step_cost(coord, neighbor);
+                if new_cost <= budget && costs.get(&neighbor).is_none_or(|&old| new_cost < old) {
+                    costs.insert(neighbor, new_cost);
+                    open.push(Frontier {
+                        priority: new_cost,
+                        coord: neighbor,
+                    });
+                }
+            }
+        }
+        costs
+    }
+
+    pub fn cell_to_world(&self, coord: GridCoord) -> Point3<f32> {
+        let offset = match self.shape {
+            GridShape::Square { .. } => {
+                Vector2::new(coord.x as f32, coord.y as f32) * self.cell_size
+            }
+            GridShape::Hex => {
+                let (q, r) = coord.to_axial();
+                Vector2::new(SQRT_3 * q as f32 + SQRT_3 / 2.0 * r as f32, 1.5 * r as f32)
+                    * self.cell_size
+            }
+        };
+        Point3::new(
+            self.origin.x + offset.x,
+            self.origin.y + offset.y,
+            self.origin.z,
+        )
+    }
+
+    /// The cell under a world position, ignoring height.
+    pub fn world_to_cell(&self, position: Point3<f32>) -> Option<GridCoord> {
+        let x = (position.x - self.origin.x) / self.cell_size;
+        let y = (position.y - self.origin.y) / self.cell_size;
+        let coord = match self.shape {
+            GridShape::Square { .. } => GridCoord::new(x.round() as i32, y.round() as i32),
+            GridShape::Hex => {
+                // Fractional axial coordinates, rounded in cube space
+                let q = SQRT_3 / 3.0 * x - y / 3.0;
+                let r = 2.0 / 3.0 * y;
+                let s = -q - r;
+                let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+                let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+                if dq > dr && dq > ds {
+                    rq = -rr - rs;
+                } else if dr > ds {
+                    rr = -rq - rs;
+                }
+                GridCoord::from_axial(rq as i32, rr as i32)
+            }
+        };
+        self.in_bounds(coord).then_some(coord)
+    }
+}
+
+/// A cell to draw on top of the board.
+#[derive(Debug, Clone, Copy)]
+pub struct Highlight {
+    pub coord: GridCoord,
+    pub color: [f32; 4],
+}
+
+impl Highlight {
+    pub fn new(coord: GridCoord, color: [f32; 4]) -> Self {
+        Self { coord, color }
+    }
+}
+
+const INSTANCE_FLOATS: usize = 7; // Center and color
+
+/// Draws highlighted cells as one instanced quad each, for movement ranges, paths and targets.
+/// Call from a render pass after the scene so the highlights are depth tested against it.
+pub struct GridHighlighter {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    quad_buffer: glow::Buffer,
+    instance_buffer: glow::Buffer,
+    instance_data: Vec<f32>,
+    pub inset: f32, // Gap between neighbouring cells, 0 to 1
+    pub lift: f32,  // World units above the board, keeps the highlights out of the ground
+}
+
+impl GridHighlighter {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let program = shaders::load_program(
+            gl,
+            "shaders/grid_highlight_vertex.glsl",
+            "shaders/grid_highlight.glsl",
+        )?;
+
+        let corners: [f32; 12] = [
+            -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, //
+            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+        ];
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            let quad_buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+            let instance_buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+
+            gl.bind_vertex_array(Some(vertex_array));
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad_buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&corners),
+                glow::STATIC_DRAW,
+            );
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+
+            let stride = (INSTANCE_FLOATS * std::mem::size_of::<f32>()) as i32;
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(instance_buffer));
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_pointer_f32(1, 3, glow::FLOAT, false, stride, 0);
+            gl.vertex_attrib_divisor(1, 1);
+            gl.enable_vertex_attrib_array(2);
+            gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, 3 * 4);
+            gl.vertex_attrib_divisor(2, 1);
+
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                quad_buffer,
+                instance_buffer,
+                instance_data: Vec::new(),
+                inset: 0.08,
+                lift: 0.01,
+            })
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        camera: &dyn Camera,
+        grid: &GridMap,
+        highlights: &[Highlight],
+    ) {
+        self.instance_data.clear();
+        for highlight in highlights {
+            if !grid.in_bounds(highlight.coord) {
+                continue;
+            }
+            let center = grid.cell_to_world(highlight.coord);
+            self.instance_data
+                .extend_from_slice(&[center.x, center.y, center.z + self.lift]);
+            self.instance_data.extend_from_slice(&highlight.color);
+        }
+        if self.instance_data.is_empty() {
+            return;
+        }
+
+        let view_projection: Matrix4<f32> = camera.get_projection() * camera.get_view();
+        let view_projection: &[f32; 16] = view_projection.as_ref();
+        let half_extent = match grid.shape {
+            GridShape::Square { .. } => grid.cell_size / 2.0,
+            GridShape::Hex => grid.cell_size,
+        };
+
+        unsafe {
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&self.instance_data),
+                glow::STREAM_DRAW,
+            );
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.enable(glow::DEPTH_TEST);
+            gl.depth_mask(false);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+
+            gl.use_program(Some(program));
+            gl.uniform_matrix_4_f32_slice(
+                location("view_projection").as_ref(),
+                false,
+                view_projection,
+            );
+            gl.uniform_1_f32(location("half_extent").as_ref(), half_extent);
+            gl.uniform_1_i32(
+                location("hexagon").as_ref(),
+                (grid.shape == GridShape::Hex) as i32,
+            );
+            gl.uniform_1_f32(location("inset").as_ref(), self.inset.clamp(0.0, 0.99));
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays_instanced(
+                glow::TRIANGLES,
+                0,
+                6,
+                (self.instance_data.len() / INSTANCE_FLOATS) as i32,
+            );
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+
+            gl.depth_mask(true);
+            gl.disable(glow::BLEND);
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.quad_buffer);
+            gl.delete_buffer(self.instance_buffer);
+        }
+    }
+}
@@ -1,20 +1,29 @@
 use std::{
     collections::VecDeque,
     io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
 use super::Viewport;
-use cgmath::{InnerSpace, Rotation3};
+use crate::extra_window::WindowKind;
+use crate::quad_view::{QuadView, ViewportLayout};
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use egui::{Align, CornerRadius, Key, Layout, Pos2};
+use egui::{Align, Key, Layout, Pos2};
+use cgmath::EuclideanSpace;
 use glow::HasContext;
 use winit::window::Window;
 
 use clap::{Arg, Command};
 use shell_words;
 
-fn process_console_command(command: String) -> String {
+fn process_console_command(
+    command: String,
+    cvars: &Mutex<CVarRegistry>,
+    levels: &Sender<LevelRequest>,
+    scenes: &Sender<SceneRef>,
+) -> String {
     // Tokenize command line (split on whitespace) for clap
     let args = shell_words::split(&command).unwrap_or_else(|_| vec![]);
 
@@ -29,6 +38,39 @@ fn process_console_command(command: String) -> String {
                 .about("Adds two numbers")
                 .arg(Arg::new("a").required(true))
                 .arg(Arg::new("b").required(true)),
+        )
+        .subcommand(
+            Command::new("set")
+                .about("Sets a console variable")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("value").required(true)),
+        )
+        .subcommand(
+            Command::new("get")
+                .about("Prints the value of a console variable")
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(
+            Command::new("reset")
+                .about("Resets a console variable to its default value")
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(Command::new("cvarlist").about("Lists all console variables"))
+        .subcommand(
+            Command::new("level")
+                .about("Streams a level in or out, see assets/levels.ron")
+                .arg(Arg::new("action").required(true).value_parser(["load", "unload"]))
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(
+            Command::new("scene")
+                .about("Acts on the open scenes")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("switch")
+                        .about("Switches to an open scene, or opens the saved one by that name")
+                        .arg(Arg::new("name").required(true)),
+                ),
         );
 
     match cli.try_get_matches_from(args) {
@@ -46,33 +88,721 @@ fn process_console_command(command: String) -> String {
                 let b: f64 = sub.get_one::<String>("b").unwrap().parse().unwrap_or(0.0);
                 format!("Result: {}", a + b)
             }
+            Some(("set", sub)) => {
+                let name = sub.get_one::<String>("name").unwrap();
+                let value = sub.get_one::<String>("value").unwrap();
+                match cvars.lock().unwrap().set_from_str(name, value) {
+                    Ok(_) => format!("{} = {}", name, value),
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
+            Some(("get", sub)) => {
+                let name = sub.get_one::<String>("name").unwrap();
+                match cvars.lock().unwrap().get_cvar(name) {
+                    Some(cvar) => format!(
+                        "{} = {} (default: {}) - {}",
+                        cvar.name, cvar.value, cvar.default, cvar.description
+                    ),
+                    None => format!("Error: Unknown cvar '{}'", name),
+                }
+            }
+            Some(("reset", sub)) => {
+                let name = sub.get_one::<String>("name").unwrap();
+                match cvars.lock().unwrap().reset(name) {
+                    Ok(_) => format!("{} reset", name),
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
+            Some(("cvarlist", _)) => cvars
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|cvar| format!("{} = {}", cvar.name, cvar.value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Some(("level", sub)) => {
+                let name = sub.get_one::<String>("name").unwrap().clone();
+                let action = sub.get_one::<String>("action").unwrap();
+                let request = match action.as_str() {
+                    "load" => LevelRequest::Load(name.clone()),
+                    _ => LevelRequest::Unload(name.clone()),
+                };
+                match levels.send(request) {
+                    Ok(()) => format!("Level {}: {} requested", name, action),
+                    Err(_) => "Error: Levels can't be streamed right now".to_string(),
+                }
+            }
+            Some(("scene", sub)) => match sub.subcommand() {
+                Some(("switch", sub)) => {
+                    let name = sub.get_one::<String>("name").unwrap().clone();
+                    match scenes.send(SceneRef::Name(name.clone())) {
+                        Ok(()) => format!("Switching to {}", name),
+                        Err(_) => "Error: Scenes can't be switched right now".to_string(),
+                    }
+                }
+                _ => "Unknown command or syntax error".to_string(),
+            },
             _ => "Unknown command or syntax error".to_string(),
         },
         Err(e) => format!("Error parsing command: {}", e),
     }
 }
 
-#[derive(PartialEq)]
-enum Choice {
-    Console,
-    ContentBrowser,
-    Ide,
-}
-
 use crate::{
-    camera::Camera, loader::AssetLoader, mesh::StaticMesh, scene_graph::{SceneGraph, SelectedObject}, CameraType
+    accessibility, camera::{self, AxisView, Camera}, component::{Component, ComponentKind}, inspect::Inspect, data::MaterialOverride, cvars::{CVarRegistry, CVarValue, CVARS_CONFIG_PATH}, dialogue::{self, Comparison, DIALOGUE_DIRECTORY, Condition, DialogueChoice, DialogueGraph, DialogueNode, DialogueRunner, DialogueVariables, Effect}, foliage::{FoliageBrush, FoliageLayer}, handles::{AssetHandle, MeshHandle, TextureHandle}, loader::{AssetLoader, AssetProgress, LoadStage}, logging::LogLine, mesh::StaticMesh, particles::{self, EmitterSettings, ParticleEffect, ParticleSystem, PARTICLE_DIRECTORY}, photo_mode::PhotoMode, preferences::{EditorPreferences, Theme}, raycast::{self, Ray}, render_list::RenderStats, runtime, scene_file::{SceneFile, SCENE_DIRECTORY, SCENE_EXTENSION}, scene_preview::ScenePreviewCache, scene_graph::{SceneGraph, SceneNode, SceneRef, SelectedObject}, streaming::{LevelRequest, LEVELS_FILE}, socket::Socket, sprites::{self, Sprite}, tilemap::{Tilemap, TILEMAP_DIRECTORY}, tutorial::{self, Tutorial, TutorialOverlay, TUTORIAL_DIRECTORY}, content_browser::{self, AssetKind, BrowserAction, ContentBrowser, DraggedAsset}, dock::{DockLayout, PanelKind}, launcher::Launcher, scene_templates::SceneTemplate, thumbnails::Thumbnails, gizmo::{GizmoMode, ModalKeys, ModalState, ModalTransform, SnapMode}, transform::{GizmoSpace, MeshTransform, Pivot}, undo::{StaticMeshesEdit, TransformEdit, UndoStack}, view_mode::ViewMode, window::{VSync, WindowMode}, CameraType
 };
 
+const AUTOSAVE_DIRECTORY: &str = "autosave";
+
+// How far the axis gizmo puts the camera from what it looks at
+const AXIS_VIEW_DISTANCE: f32 = 20.0;
+
+struct FrameSample {
+    frame_ms: f32, // Timer delta, everything including waiting for vsync
+    cpu_ms: f32,
+    gpu_ms: f32,
+}
+
+// The frame time that only 1% of frames are slower than, which is what "1% low" FPS reports
+fn one_percent_low(samples: impl Iterator<Item = f32>) -> Option<f32> {
+    let mut samples: Vec<f32> = samples.collect();
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(|a, b| a.total_cmp(b));
+    let index = ((samples.len() - 1) as f32 * 0.99).round() as usize;
+    Some(samples[index])
+}
+
+struct ConsoleLine {
+    level: Option<log::Level>, // None for console input and command output
+    text: String,
+}
+
+// A labelled row of x/y/z drag values, laid out like the mesh transform rows
+fn vector3_row(ui: &mut egui::Ui, label: &str, value: &mut cgmath::Vector3<f32>, speed: f64) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.allocate_ui_with_layout(
+            ui.available_size(),
+            Layout::right_to_left(Align::Center),
+            |ui| {
+                // The inputs are in the reverse order
+                ui.add(egui::DragValue::new(&mut value.z).speed(speed));
+                ui.add(egui::DragValue::new(&mut value.y).speed(speed));
+                ui.add(egui::DragValue::new(&mut value.x).speed(speed));
+            },
+        );
+    });
+}
+
+// A node id that may be empty, where empty ends the conversation
+fn optional_id_row(ui: &mut egui::Ui, label: &str, id: &mut Option<u32>) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut enabled = id.is_some();
+        if ui.checkbox(&mut enabled, "").changed() {
+            *id = if enabled { Some(0) } else { None };
+        }
+        match id {
+            Some(id) => {
+                ui.add(egui::DragValue::new(id));
+            }
+            None => {
+                ui.weak("End");
+            }
+        }
+    });
+}
+
+// Drag and drop payload of a static mesh in the hierarchy
+struct HierarchyDrag(usize);
+
+// The text box of an inline rename in the hierarchy
+const RENAME_ID: &str = "Hierarchy rename";
+
+struct Rename {
+    object: SelectedObject,
+    text: String,
+    done: bool, // Applied after the hierarchy was drawn
+}
+
+// A scene's tab above the viewport. The open scene's selection and history are in `Gui` while
+// it's open, switching tabs swaps them.
+struct SceneTab {
+    path: Option<PathBuf>, // None until it's saved
+    saved: SceneFile,      // As it was last saved or opened, for the unsaved marker
+    dirty: bool,
+    loading: Option<(Vec<AssetHandle>, SceneFile)>, // Opened, waiting for its assets
+    selected_object: Option<SelectedObject>,
+    selection: Vec<usize>,
+    undo_stack: UndoStack,
+}
+
+impl SceneTab {
+    fn new(path: Option<PathBuf>, scene: &SceneNode, asset_loader: &AssetLoader) -> Self {
+        Self {
+            path,
+            saved: SceneFile::from_scene(scene, asset_loader),
+            dirty: false,
+            loading: None,
+            selected_object: None,
+            selection: Vec::new(),
+            undo_stack: UndoStack::new(100),
+        }
+    }
+}
+
+enum SceneTabAction {
+    New(SceneTemplate),
+    Open(PathBuf),
+    Switch(usize),
+    Close(usize),
+    Save(Option<PathBuf>), // To where it was saved before when None
+}
+
+// The scene files the File menu can open
+fn saved_scenes() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(SCENE_DIRECTORY) else {
+        return Vec::new();
+    };
+    let mut scenes: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == SCENE_EXTENSION))
+        .collect();
+    scenes.sort();
+    scenes
+}
+
+fn scene_names(scene_graph: &SceneGraph) -> Vec<String> {
+    scene_graph.scenes.iter().map(|scene| scene.name.clone()).collect()
+}
+
+fn release_scene(mut scene: SceneNode, context: &glow::Context, asset_loader: &mut AssetLoader) {
+    scene.release_assets(asset_loader);
+    scene.destroy(context);
+}
+
+// Done to the selection by a shortcut or the hierarchy's context menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectCommand {
+    Duplicate,
+    Delete,
+}
+
+fn components_ui(ui: &mut egui::Ui, mesh: &mut StaticMesh) {
+    let mut removed = None;
+    for (i, component) in mesh.components.iter_mut().enumerate() {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.strong(component.kind().label());
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+            component.inspect(ui, "");
+        });
+    }
+    if let Some(i) = removed {
+        mesh.components.remove(i);
+    }
+
+    ui.menu_button("➕ Add Component", |ui| {
+        for kind in ComponentKind::all() {
+            let present = mesh.components.iter().any(|component| component.kind() == kind);
+            let response = ui
+                .add_enabled(!present, egui::Button::new(kind.label()))
+                .on_hover_text(kind.description())
+                .on_disabled_hover_text("It already has one");
+            if response.clicked() {
+                mesh.components.push(Component::new(kind));
+                ui.close_menu();
+            }
+        }
+    });
+}
+
+// Where the pointer would snap a moving selection to, looking past the selection itself
+// Which of the scene's cameras play mode is seen through
+fn game_camera_ui(ui: &mut egui::Ui, scene: &mut SceneNode) {
+    let selected = scene
+        .active_camera()
+        .map_or("Editor camera", |camera| camera.name.as_str())
+        .to_string();
+    egui::ComboBox::from_id_salt("Game camera")
+        .selected_text(format!("🎥 {}", selected))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut scene.active_camera, None, "Editor camera");
+            for (i, camera) in scene.perspective_cameras.iter().enumerate() {
+                ui.selectable_value(&mut scene.active_camera, Some(i), &camera.name);
+            }
+        })
+        .response
+        .on_hover_text("The camera play mode is seen through");
+}
+
+fn snap_target(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    camera: &dyn Camera,
+    scene: &SceneNode,
+    asset_loader: &AssetLoader,
+    modal: &ModalTransform,
+) -> Option<(SnapMode, cgmath::Point3<f32>)> {
+    let mode = ui.input(|input| SnapMode::held(&input.modifiers))?;
+    let pointer = ui.input(|input| input.pointer.hover_pos())?;
+    let ndc = [
+        (pointer.x - rect.min.x) / rect.width() * 2.0 - 1.0,
+        1.0 - (pointer.y - rect.min.y) / rect.height() * 2.0,
+    ];
+    let ray = Ray::from_screen(camera, ndc)?;
+    let moving: Vec<usize> = modal.objects().collect();
+    let hit = raycast::raycast_triangles(scene, asset_loader, &ray, f32::INFINITY, |i| {
+        !moving.contains(&i)
+    })?;
+    match mode {
+        SnapMode::Surface => Some((mode, hit.point)),
+        SnapMode::Vertex => {
+            let vertex = raycast::nearest_vertex(scene, asset_loader, hit.static_mesh, hit.point)?;
+            Some((mode, vertex))
+        }
+    }
+}
+
+fn material_label(material: Option<&MaterialOverride>) -> String {
+    match material {
+        Some(material) => format!(
+            "{} #{}",
+            material.path.file_name().unwrap_or_default().to_string_lossy(),
+            material.primitive
+        ),
+        None => "Own".to_string(),
+    }
+}
+
+// Every primitive of a static mesh can take the material of one in any loaded mesh, picked from
+// the list or by dropping a mesh file from the content browser for its first one
+fn material_slots_ui(
+    ui: &mut egui::Ui,
+    context: &glow::Context,
+    mesh: &mut StaticMesh,
+    asset_loader: &mut AssetLoader,
+) {
+    let mut choices: Vec<MaterialOverride> = asset_loader
+        .loaded_mesh_data
+        .values()
+        .flat_map(|loaded| {
+            loaded
+                .primitives
+                .iter()
+                .enumerate()
+                .filter(|(_, primitive)| primitive.material.is_some())
+                .map(|(primitive, _)| MaterialOverride {
+                    path: loaded.path.clone(),
+                    primitive,
+                })
+        })
+        .collect();
+    choices.sort_by(|a, b| (&a.path, a.primitive).cmp(&(&b.path, b.primitive)));
+
+    let mut change = None;
+    for (slot, primitive) in mesh.primitives.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("Slot {}", slot));
+            let response = egui::ComboBox::from_id_salt(("MaterialSlot", slot))
+                .selected_text(material_label(primitive.material.as_ref()))
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(primitive.material.is_none(), "Own").clicked() {
+                        change = Some((slot, None));
+                    }
+                    for choice in &choices {
+                        let selected = primitive.material.as_ref() == Some(choice);
+                        if ui.selectable_label(selected, material_label(Some(choice))).clicked() {
+                            change = Some((slot, Some(choice.clone())));
+                        }
+                    }
+                })
+                .response;
+
+            let Some(dragged) = response.dnd_release_payload::<DraggedAsset>() else {
+                return;
+            };
+            if dragged.kind != AssetKind::Mesh {
+                return;
+            }
+            let name = dragged.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let handle = asset_loader.request_mesh(&dragged.path, name);
+            match asset_loader.loaded_mesh_data.get(&handle) {
+                Some(loaded) => {
+                    let material = MaterialOverride {
+                        path: loaded.path.clone(),
+                        primitive: 0,
+                    };
+                    change = Some((slot, Some(material)));
+                }
+                None => log::warn!("{:?} is still loading, drop it again later", dragged.path),
+            }
+        });
+    }
+
+    if let Some((slot, material)) = change {
+        if let Err(e) = mesh.set_primitive_material(context, slot, material, asset_loader) {
+            log::error!("{}", e);
+        }
+    }
+}
+
+fn object_name(scene: &SceneNode, object: SelectedObject) -> Option<&str> {
+    let name = match object {
+        SelectedObject::StaticMesh(i) => &scene.static_meshes.get(i)?.name,
+        SelectedObject::DynamicMesh(i) => &scene.dynamic_meshes.get(i)?.name,
+        SelectedObject::PerspectiveCamera(i) => &scene.perspective_cameras.get(i)?.name,
+        SelectedObject::Sprite(_) => return None,
+    };
+    Some(name)
+}
+
+// `name`, or with the lowest " (n)" behind it that none of `others` has
+fn unique_name(name: &str, others: &[String]) -> String {
+    if !others.iter().any(|other| other == name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !others.contains(candidate))
+        .unwrap()
+}
+
+// Names only have to be unique among objects of the same kind
+fn rename_object(scene: &mut SceneNode, object: SelectedObject, name: &str) {
+    let name = name.trim();
+    let (mut names, index): (Vec<&mut String>, usize) = match object {
+        SelectedObject::StaticMesh(i) => {
+            let names = scene.static_meshes.iter_mut().map(|mesh| &mut mesh.name);
+            (names.collect(), i)
+        }
+        SelectedObject::DynamicMesh(i) => {
+            let names = scene.dynamic_meshes.iter_mut().map(|mesh| &mut mesh.name);
+            (names.collect(), i)
+        }
+        SelectedObject::PerspectiveCamera(i) => {
+            let names = scene.perspective_cameras.iter_mut().map(|camera| &mut camera.name);
+            (names.collect(), i)
+        }
+        SelectedObject::Sprite(_) => return,
+    };
+    if name.is_empty() || index >= names.len() || *names[index] == name {
+        return;
+    }
+    let others: Vec<String> = names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, other)| other.to_string())
+        .collect();
+    *names[index] = unique_name(name, &others);
+}
+
+// What `Gui::hierarchy_mesh` draws the static mesh tree from
+struct HierarchyTree<'a> {
+    scene: &'a SceneNode,
+    children: &'a [Vec<usize>],
+    shown: &'a [bool], // Matches the search, or has a match under it
+    filtering: bool,   // Everything is opened up while searching
+}
+
+const STATIC_MESH_KINDS: &[&str] = &["mesh", "staticmesh"];
+
+// The Hierarchy search box. Words have to be in the name, `t:` picks the types to list.
+struct HierarchyFilter {
+    words: Vec<String>,
+    kinds: Vec<String>,
+}
+
+impl HierarchyFilter {
+    fn parse(search: &str) -> Self {
+        let mut filter = Self {
+            words: Vec::new(),
+            kinds: Vec::new(),
+        };
+        for word in search.to_lowercase().split_whitespace() {
+            match word.strip_prefix("t:") {
+                Some(kind) if !kind.is_empty() => filter.kinds.push(kind.to_string()),
+                Some(_) => {}
+                None => filter.words.push(word.to_string()),
+            }
+        }
+        filter
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.kinds.is_empty()
+    }
+
+    // Headers are opened while searching and left alone otherwise
+    fn open(&self) -> Option<bool> {
+        (!self.is_empty()).then_some(true)
+    }
+
+    // `t:cam` is enough for cameras
+    fn matches(&self, kinds: &[&str], name: &str) -> bool {
+        let kind_matches = self.kinds.is_empty()
+            || self
+                .kinds
+                .iter()
+                .any(|wanted| kinds.iter().any(|kind| kind.starts_with(wanted.as_str())));
+        let name = name.to_lowercase();
+        kind_matches && self.words.iter().all(|word| name.contains(word.as_str()))
+    }
+}
+
+// A hierarchy section that only lists names, hidden while searching if nothing in it matches
+fn hierarchy_labels(
+    ui: &mut egui::Ui,
+    filter: &HierarchyFilter,
+    title: &str,
+    kinds: &[&str],
+    names: impl Iterator<Item = String>,
+) {
+    let names: Vec<String> = names.filter(|name| filter.matches(kinds, name)).collect();
+    if !filter.is_empty() && names.is_empty() {
+        return;
+    }
+    egui::CollapsingHeader::new(title)
+        .open(filter.open())
+        .show(ui, |ui| {
+            for name in names {
+                ui.label(name);
+            }
+        });
+}
+
+// Static meshes in the order the hierarchy lists them, parents before their children
+fn hierarchy_order(roots: &[usize], children: &[Vec<usize>], shown: &[bool]) -> Vec<usize> {
+    let mut order = Vec::new();
+    let mut stack: Vec<usize> = roots.iter().rev().copied().collect();
+    while let Some(index) = stack.pop() {
+        if !shown[index] {
+            continue;
+        }
+        order.push(index);
+        stack.extend(children[index].iter().rev());
+    }
+    order
+}
+
+// Where a world space point is drawn in the viewport, None behind the camera
+fn screen_position(
+    view_projection: &cgmath::Matrix4<f32>,
+    rect: egui::Rect,
+    point: cgmath::Point3<f32>,
+) -> Option<Pos2> {
+    let clip = view_projection * point.to_homogeneous();
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+    Some(Pos2::new(
+        rect.min.x + (x + 1.0) / 2.0 * rect.width(),
+        rect.min.y + (1.0 - y) / 2.0 * rect.height(),
+    ))
+}
+
+// Where a ray meets the ground, or a little in front of the camera when it never does
+fn ground_point(ray: &Ray) -> cgmath::Point3<f32> {
+    let distance = -ray.origin.y / ray.direction.y;
+    if distance.is_finite() && distance > 0.0 {
+        ray.at(distance)
+    } else {
+        ray.at(5.0)
+    }
+}
+
+fn content_browser_assets(ui: &mut egui::Ui, asset_loader: &mut AssetLoader) {
+    let mut in_flight: Vec<&AssetProgress> = asset_loader.in_flight().collect();
+    in_flight.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut cancel = None;
+    for progress in in_flight {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(progress.path.file_name().unwrap_or_default().to_string_lossy());
+            let stage = match &progress.stage {
+                LoadStage::Queued => "Queued".to_string(),
+                LoadStage::Started => "Reading".to_string(),
+                LoadStage::Read { bytes } => format!("Decoding {} KB", bytes / 1024),
+                LoadStage::Decoded => "Finishing".to_string(),
+                LoadStage::Done => "Done".to_string(),
+                LoadStage::Failed(e) => e.clone(),
+                LoadStage::Cancelled => "Cancelled".to_string(),
+            };
+            ui.add(
+                egui::ProgressBar::new(progress.stage.fraction())
+                    .desired_width(120.0)
+                    .text(stage),
+            );
+            if ui.small_button("x").on_hover_text("Cancel").clicked() {
+                cancel = Some(progress.handle);
+            }
+        });
+    }
+    if let Some(handle) = cancel {
+        asset_loader.cancel(handle);
+    }
+
+    ui.separator();
+    egui::Grid::new("ContentBrowserAssets").striped(true).show(ui, |ui| {
+        for mesh in asset_loader.loaded_mesh_data.values() {
+            ui.label("Mesh");
+            ui.label(&mesh.name);
+            ui.weak(mesh.path.to_string_lossy());
+            ui.end_row();
+        }
+        for texture in asset_loader.loaded_texture_data.values() {
+            ui.label("Texture");
+            ui.label(&texture.name);
+            ui.weak(format!("{}x{}", texture.width, texture.height));
+            ui.end_row();
+        }
+    });
+}
+
+fn effects_editor(ui: &mut egui::Ui, effects: &mut Vec<Effect>) {
+    let mut remove = None;
+    for (i, effect) in effects.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let (variable, value) = match effect {
+                Effect::Set(variable, value) => {
+                    ui.label("Set");
+                    (variable, value)
+                }
+                Effect::Add(variable, value) => {
+                    ui.label("Add");
+                    (variable, value)
+                }
+            };
+            ui.add(egui::TextEdit::singleline(variable).desired_width(100.0));
+            ui.add(egui::DragValue::new(value));
+            if ui.button("x").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        effects.remove(i);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add Set").clicked() {
+            effects.push(Effect::Set(String::new(), 1));
+        }
+        if ui.button("Add Add").clicked() {
+            effects.push(Effect::Add(String::new(), 1));
+        }
+    });
+}
+
+fn dialogue_node_editor(ui: &mut egui::Ui, node: &mut DialogueNode) {
+    ui.horizontal(|ui| {
+        ui.label("Id:");
+        ui.add(egui::DragValue::new(&mut node.id));
+        ui.label("Speaker:");
+        ui.text_edit_singleline(&mut node.speaker);
+    });
+    ui.add(
+        egui::TextEdit::multiline(&mut node.text)
+            .desired_rows(2)
+            .hint_text("Line"),
+    );
+
+    ui.label("On enter:");
+    ui.push_id("NodeEffects", |ui| effects_editor(ui, &mut node.effects));
+
+    // Where Continue goes, which is also offered when none of the choices are available
+    let next_label = if node.choices.is_empty() {
+        "Next:"
+    } else {
+        "Next (no choice available):"
+    };
+    optional_id_row(ui, next_label, &mut node.next);
+
+    ui.label("Choices:");
+    let mut remove = None;
+    for (i, choice) in node.choices.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut choice.text);
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                optional_id_row(ui, "Target:", &mut choice.target);
+
+                ui.horizontal(|ui| {
+                    let mut has_condition = choice.condition.is_some();
+                    if ui.checkbox(&mut has_condition, "Condition").changed() {
+                        choice.condition = has_condition.then(|| Condition {
+                            variable: String::new(),
+                            comparison: Comparison::Equal,
+                            value: 0,
+                        });
+                    }
+                    if let Some(condition) = &mut choice.condition {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut condition.variable)
+                                .desired_width(100.0),
+                        );
+                        egui::ComboBox::from_id_salt("Comparison")
+                            .selected_text(condition.comparison.symbol())
+                            .width(40.0)
+                            .show_ui(ui, |ui| {
+                                for comparison in Comparison::ALL {
+                                    ui.selectable_value(
+                                        &mut condition.comparison,
+                                        comparison,
+                                        comparison.symbol(),
+                                    );
+                                }
+                            });
+                        ui.add(egui::DragValue::new(&mut condition.value));
+                    }
+                });
+
+                ui.push_id("ChoiceEffects", |ui| effects_editor(ui, &mut choice.effects));
+            });
+        });
+    }
+    if let Some(i) = remove {
+        node.choices.remove(i);
+    }
+
+    if ui.button("Add Choice").clicked() {
+        node.choices.push(DialogueChoice {
+            text: String::from("..."),
+            target: None,
+            condition: None,
+            effects: Vec::new(),
+        });
+    }
+}
+
 pub struct Gui {
     command_tx: Sender<String>,
     command_result_rx: Receiver<String>,
+    log_rx: Receiver<LogLine>,
 
-    choice: Choice,
-    wireframe: bool,
+    dock: DockLayout,
+    content_browser: ContentBrowser,
+    thumbnails: Thumbnails,
+    scene_previews: ScenePreviewCache,
+    pending_preview: Option<PathBuf>, // Saved this frame, its thumbnail is taken after rendering
+    cvars: Arc<Mutex<CVarRegistry>>,
 
     terminal_input: String,
-    terminal_lines: VecDeque<String>,
+    terminal_lines: VecDeque<ConsoleLine>,
     max_terminal_lines: usize,
+    shown_log_levels: [bool; 5], // Indexed by log::Level as usize - 1
 
     viewport: Option<Viewport>,
 
@@ -81,42 +811,197 @@ pub struct Gui {
     accumulator: Duration,
     last_frame_time: Instant,
     fps: u32,
+    cpu_frame_ms: f32,
+    gpu_pass_ms: Vec<(&'static str, f32)>,
+    render_stats: RenderStats,
+    frame_history: VecDeque<FrameSample>,
+    max_frame_history: usize,
 
     selected_object: Option<SelectedObject>,
+    selection: Vec<usize>, // Static meshes, more than one shows the bulk editor
+    marquee_start: Option<Pos2>, // Where a box selection in the viewport started
+    hierarchy_search: String,
+    renaming: Option<Rename>,
+    object_command: Option<ObjectCommand>, // Carried out once the panels are drawn
+    camera_activation: Option<Option<usize>>, // A new active camera from the hierarchy's menu
+    play_toggled: bool, // Static batches are built when play starts and cleared when it stops
+    window_requests: Vec<WindowKind>,         // Opened by main, it owns the OS windows
     selected_script: Option<usize>,
     selected_material: Option<usize>,
+    sprite_atlas_path: String,
+
+    dialogue_path: String,
+    dialogue: DialogueGraph,
+    dialogue_preview: Option<DialogueRunner>,
+    dialogue_variables: DialogueVariables,
+
+    launcher: Launcher,
+    launcher_open: bool,
+    project_to_open: Option<PathBuf>, // Opened at the start of the next frame, before the scene is borrowed
+    scene_tabs: Vec<SceneTab>, // One for each of the scene graph's scenes
+    scene_tab_action: Option<SceneTabAction>, // Same as the project, before the scene is borrowed
+    save_scene_as: Option<String>,          // The path in the Save As window while it's open
+    pending_scene_close: Option<usize>,     // A tab with unsaved changes, waiting for an answer
+
+    preferences: EditorPreferences,
+    preferences_open: bool,
+    pending_delete: Vec<SelectedObject>, // Waiting for the delete to be confirmed
+    pending_asset_delete: Option<(PathBuf, usize)>, // A file from the content browser and its uses
+    last_autosave: Instant,
+
+    // Dropped in the viewport before they were loaded
+    dropped_meshes: Vec<(MeshHandle, cgmath::Point3<f32>)>,
+    dropped_textures: Vec<(TextureHandle, usize)>, // Onto the static mesh at that index
+    dropped_files: Vec<PathBuf>, // From outside the editor, imported on the next update
+
+    recording: bool,
+    playing: bool, // Only the game UI layer reacts to this so far
+
+    tutorial: TutorialOverlay,
+    photo_mode: PhotoMode,
+
+    undo_stack: UndoStack,
+    transform_edit_before: Option<Vec<(usize, MeshTransform)>>, // Set while a bulk edit is in progress
+    gizmo_space: GizmoSpace,
+    pivot: Pivot,
+    axis_view: Option<(AxisView, cgmath::Point3<f32>)>, // Applied once the ortho camera is active
+    viewport_layout: ViewportLayout,
+    quad_view: QuadView,
+    modal_keys: ModalKeys,
+    modal_transform: Option<ModalTransform>,
+
+    particle_path: String,
+    particle_system: ParticleSystem, // The effect being edited lives in here
+    particle_preview: bool,
+
+    foliage_painting: bool,
+    foliage_brush: FoliageBrush,
+    foliage_layer: usize, // Index into SceneNode.foliage
+    tile_painting: bool,
+    tilemap_index: usize, // Index into SceneNode.tilemaps
+    tile_brush: u32,      // Atlas frame painted
+    tilemap_path: String,
 }
 
 impl Gui {
-    pub fn new() -> Self {
+    pub fn new(
+        cvars: Arc<Mutex<CVarRegistry>>,
+        log_rx: Receiver<LogLine>,
+        level_requests: Sender<LevelRequest>,
+        scene_requests: Sender<SceneRef>,
+    ) -> Self {
         let (command_tx, command_rx) = unbounded();
         let (result_tx, command_result_rx) = unbounded();
 
+        let console_cvars = Arc::clone(&cvars);
+
+        let preferences = EditorPreferences::load();
+        preferences.apply(&mut cvars.lock().unwrap());
+
         let gui = Self {
             command_tx,
             command_result_rx,
+            log_rx,
 
-            choice: Choice::Console,
-            wireframe: false,
+            dock: DockLayout::default(),
+            content_browser: ContentBrowser::new(),
+            thumbnails: Thumbnails::new(),
+            scene_previews: ScenePreviewCache::default(),
+            pending_preview: None,
+            cvars,
             terminal_input: String::new(),
             terminal_lines: VecDeque::new(),
             max_terminal_lines: 100,
+            shown_log_levels: [true, true, true, true, false],
 
             viewport: None,
             frame_count: 0,
             accumulator: Duration::ZERO,
             last_frame_time: Instant::now(),
             fps: 0,
+            cpu_frame_ms: 0.0,
+            gpu_pass_ms: Vec::new(),
+            render_stats: RenderStats::default(),
+            frame_history: VecDeque::new(),
+            max_frame_history: 600,
 
             selected_object: None, // Some(SelectedObject::StaticMesh(0)),
+            selection: Vec::new(),
+            marquee_start: None,
+            hierarchy_search: String::new(),
+            renaming: None,
+            object_command: None,
+            camera_activation: None,
+            play_toggled: false,
+            window_requests: Vec::new(),
             selected_script: None,
             selected_material: None,
+            sprite_atlas_path: String::new(),
+
+            dialogue_path: format!("{}/new_dialogue.ron", DIALOGUE_DIRECTORY),
+            dialogue: DialogueGraph::new("New Dialogue"),
+            dialogue_preview: None,
+            dialogue_variables: DialogueVariables::new(),
+
+            launcher: Launcher::new(),
+            launcher_open: true,
+            project_to_open: None,
+            scene_tabs: Vec::new(),
+            scene_tab_action: None,
+            save_scene_as: None,
+            pending_scene_close: None,
+
+            preferences,
+            preferences_open: false,
+            pending_delete: Vec::new(),
+            pending_asset_delete: None,
+            last_autosave: Instant::now(),
+
+            dropped_meshes: Vec::new(),
+            dropped_textures: Vec::new(),
+            dropped_files: Vec::new(),
+
+            recording: false,
+            playing: false,
+
+            tutorial: TutorialOverlay::new(),
+            photo_mode: PhotoMode::new(egui::Key::F8),
+
+            undo_stack: UndoStack::new(100),
+            transform_edit_before: None,
+            gizmo_space: GizmoSpace::World,
+            pivot: Pivot::Origin,
+            axis_view: None,
+            viewport_layout: ViewportLayout::Single,
+            quad_view: QuadView::new(),
+            modal_keys: ModalKeys::default(),
+            modal_transform: None,
+
+            particle_path: format!("{}/new_effect.ron", PARTICLE_DIRECTORY),
+            particle_system: ParticleSystem::new(
+                ParticleEffect::new("New Effect"),
+                cgmath::Vector3::new(0.0, 0.0, 0.0),
+            ),
+            particle_preview: false,
+
+            foliage_painting: false,
+            foliage_brush: FoliageBrush::default(),
+            foliage_layer: 0,
+            tile_painting: false,
+            tilemap_index: 0,
+            tile_brush: 0,
+            tilemap_path: format!("{}/new_tilemap.ron", TILEMAP_DIRECTORY),
         };
 
         std::thread::spawn(move || {
             while let Ok(command) = command_rx.recv() {
                 // Here you process the command:
-                let output = process_console_command(command);
+                let output = process_console_command(
+                    command,
+                    &console_cvars,
+                    &level_requests,
+                    &scene_requests,
+                );
                 let _ = result_tx.send(output);
             }
         });
@@ -125,31 +1010,2874 @@ impl Gui {
     }
 
     fn append_terminal(&mut self, text: impl Into<String>) {
-        self.terminal_lines.push_back(text.into());
+        self.push_console_line(None, text.into());
+    }
+
+    fn push_console_line(&mut self, level: Option<log::Level>, text: String) {
+        self.terminal_lines.push_back(ConsoleLine { level, text });
         while self.terminal_lines.len() > self.max_terminal_lines {
             self.terminal_lines.pop_front();
         }
     }
 
-    pub fn clear(&self, context: &glow::Context) {
-        unsafe {
-            context.clear_color(0.0, 0.0, 0.0, 1.0);
-            context.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+    pub fn clear(&self, context: &glow::Context) {
+        unsafe {
+            context.clear_color(0.0, 0.0, 0.0, 1.0);
+            context.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    /// The quad layout's other views, empty in the single view layout.
+    pub fn side_views(&mut self, window_height: u32) -> Vec<(&mut dyn Camera, Viewport)> {
+        self.quad_view.views_mut(window_height)
+    }
+
+    pub fn get_viewport(&self, window: &Window) -> Option<Viewport> {
+        if let Some(viewport) = &self.viewport {
+            let window_height = window.inner_size().height;
+            Some(Viewport::new(
+                viewport.x,
+                // Reverse the y since OpenGL uses a different origin
+                window_height as i32 - viewport.y - viewport.height,
+                viewport.width,
+                viewport.height,
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn window_mode_menu(&mut self, ui: &mut egui::Ui) {
+        let current = WindowMode::from_cvars(&self.cvars.lock().unwrap());
+        for mode in WindowMode::ALL {
+            let shortcut = if mode == current.toggled() { "F11" } else { "" };
+            let button = egui::Button::new(mode.label())
+                .selected(mode == current)
+                .shortcut_text(shortcut);
+            if ui.add(button).clicked() {
+                self.set_window_mode(mode);
+                ui.close_menu();
+            }
+        }
+
+        ui.separator();
+        let kind = WindowKind::GameView;
+        if ui
+            .button(format!("Open {}", kind.label()))
+            .on_hover_text("The scene through its active camera, in a window of its own")
+            .clicked()
+        {
+            self.window_requests.push(kind);
+            ui.close_menu();
+        }
+    }
+
+    /// Windows asked for since the last call.
+    pub fn take_window_requests(&mut self) -> Vec<WindowKind> {
+        std::mem::take(&mut self.window_requests)
+    }
+
+    // Main switches the window over when the cvar changes, and it's saved with the others
+    fn set_window_mode(&mut self, mode: WindowMode) {
+        let value = CVarValue::Str(mode.name().to_string());
+        if let Err(e) = self.cvars.lock().unwrap().set("r_window_mode", value) {
+            log::error!("{}", e);
+        }
+    }
+
+    fn settings_panel(&mut self, ui: &mut egui::Ui) {
+        let mut cvars = self.cvars.lock().unwrap();
+
+        ui.collapsing("Accessibility", |ui| {
+            accessibility::settings_ui(ui, &mut cvars);
+        });
+        ui.collapsing("Display", |ui| {
+            let current = VSync::from_cvars(&cvars);
+            let mut vsync = current;
+            egui::ComboBox::from_label("VSync")
+                .selected_text(vsync.label())
+                .show_ui(ui, |ui| {
+                    for mode in VSync::ALL {
+                        ui.selectable_value(&mut vsync, mode, mode.label());
+                    }
+                })
+                .response
+                .on_hover_text("Off runs uncapped, for measuring performance");
+            if vsync != current {
+                let value = CVarValue::Str(vsync.name().to_string());
+                if let Err(e) = cvars.set("r_vsync", value) {
+                    log::error!("{}", e);
+                }
+            }
+
+            let mut max_fps = cvars.get_int("r_max_fps");
+            let response = ui
+                .horizontal(|ui| {
+                    ui.label("FPS cap");
+                    ui.add(egui::DragValue::new(&mut max_fps).range(0..=1000))
+                })
+                .inner
+                .on_hover_text("0 for no cap");
+            if response.changed() {
+                let _ = cvars.set("r_max_fps", CVarValue::Int(max_fps));
+            }
+
+            let mut reactive = cvars.get_bool("r_reactive");
+            if ui
+                .checkbox(&mut reactive, "Only redraw on changes")
+                .on_hover_text("Saves power while the editor is idle")
+                .changed()
+            {
+                let _ = cvars.set("r_reactive", CVarValue::Bool(reactive));
+            }
+        });
+        let names: Vec<String> = cvars.iter().map(|cvar| cvar.name.clone()).collect();
+
+        egui::ScrollArea::vertical()
+            .max_height(100.0)
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                egui::Grid::new("CVars").striped(true).show(ui, |ui| {
+                    for name in names {
+                        let cvar = cvars.get_cvar(&name).unwrap();
+                        ui.label(&cvar.name).on_hover_text(&cvar.description);
+
+                        let mut value = cvar.value.clone();
+                        let changed = match &mut value {
+                            CVarValue::Bool(b) => ui.checkbox(b, "").changed(),
+                            CVarValue::Int(i) => ui.add(egui::DragValue::new(i)).changed(),
+                            CVarValue::Float(f) => {
+                                ui.add(egui::DragValue::new(f).speed(0.1)).changed()
+                            }
+                            CVarValue::Str(s) => ui.text_edit_singleline(s).lost_focus(),
+                        };
+
+                        if changed {
+                            if let Err(e) = cvars.set(&name, value) {
+                                log::error!("{}", e);
+                            }
+                        }
+
+                        if ui.button("Reset").clicked() {
+                            let _ = cvars.reset(&name);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    /// Total frame time from the timer, CPU time of the last frame (without the buffer swap)
+    /// and GPU time of each render pass.
+    pub fn set_frame_timings(
+        &mut self,
+        frame_ms: f32,
+        cpu_frame_ms: f32,
+        gpu_pass_ms: Vec<(&'static str, f32)>,
+    ) {
+        self.cpu_frame_ms = cpu_frame_ms;
+        self.gpu_pass_ms = gpu_pass_ms;
+
+        self.frame_history.push_back(FrameSample {
+            frame_ms,
+            cpu_ms: cpu_frame_ms,
+            gpu_ms: self.gpu_pass_ms.iter().map(|(_, ms)| ms).sum(),
+        });
+        while self.frame_history.len() > self.max_frame_history {
+            self.frame_history.pop_front();
+        }
+    }
+
+    /// Meshes drawn and culled by the main scene's last render list.
+    pub fn set_render_stats(&mut self, stats: RenderStats) {
+        self.render_stats = stats;
+    }
+
+    fn frame_time_plot(&self, ui: &mut egui::Ui) {
+        use egui_plot::{HLine, Legend, Line, LineStyle, Plot, PlotPoints};
+
+        let series = |value: fn(&FrameSample) -> f32| -> PlotPoints {
+            self.frame_history
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| [i as f64, value(sample) as f64])
+                .collect()
+        };
+
+        let frame_low = one_percent_low(self.frame_history.iter().map(|s| s.frame_ms));
+        let gpu_low = one_percent_low(self.frame_history.iter().map(|s| s.gpu_ms));
+
+        Plot::new("FrameTimes")
+            .height(ui.available_height().max(120.0))
+            .legend(Legend::default())
+            .y_axis_label("ms")
+            .include_x(self.max_frame_history as f64)
+            .include_y(0.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new("Frame", series(|s| s.frame_ms)));
+                plot_ui.line(Line::new("CPU", series(|s| s.cpu_ms)));
+                plot_ui.line(Line::new("GPU", series(|s| s.gpu_ms)));
+
+                if let Some(low) = frame_low {
+                    plot_ui.hline(HLine::new("Frame 1% low", low).style(LineStyle::dashed_loose()));
+                }
+                if let Some(low) = gpu_low {
+                    plot_ui.hline(HLine::new("GPU 1% low", low).style(LineStyle::dashed_loose()));
+                }
+            });
+    }
+
+    pub fn photo_mode(&self) -> &PhotoMode {
+        &self.photo_mode
+    }
+
+    pub fn photo_mode_mut(&mut self) -> &mut PhotoMode {
+        &mut self.photo_mode
+    }
+
+    /// Whether the scene at `index` in the scene graph changed since it was saved or opened.
+    pub fn is_scene_unsaved(&self, index: usize) -> bool {
+        self.scene_tabs.get(index).is_some_and(|tab| tab.dirty)
+    }
+
+    /// Remembers where the window is for the next start, called when the editor closes.
+    pub fn save_window_placement(&mut self, window: &Window) {
+        self.preferences.window.update(window);
+        if let Err(e) = self.preferences.save() {
+            log::error!("{}", e);
+        }
+    }
+
+    /// A file dropped on the window from the OS file manager.
+    pub fn drop_file(&mut self, path: PathBuf) {
+        self.dropped_files.push(path);
+    }
+
+    pub fn destroy(&mut self, context: &glow::Context) {
+        self.photo_mode.destroy(context);
+        self.thumbnails.destroy(context);
+    }
+
+    /// Whether the Particles tab's preview is simulated by the renderer instead of in `update`.
+    pub fn set_gpu_particles(&mut self, on_gpu: bool) {
+        self.particle_system.set_on_gpu(on_gpu);
+    }
+
+    /// The effect open in the Particles tab, while its preview is on.
+    pub fn particle_preview(&self) -> Option<&ParticleSystem> {
+        self.particle_preview.then_some(&self.particle_system)
+    }
+
+    /// The scene file whose thumbnail should be taken from this frame's viewport.
+    pub fn take_pending_preview(&mut self) -> Option<PathBuf> {
+        self.pending_preview.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    fn profiler_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| self.profiler_timings(ui));
+            ui.vertical(|ui| self.frame_time_plot(ui));
+        });
+    }
+
+    fn profiler_timings(&self, ui: &mut egui::Ui) {
+        let gpu_total: f32 = self.gpu_pass_ms.iter().map(|(_, ms)| ms).sum();
+
+        egui::Grid::new("GpuTimings").striped(true).show(ui, |ui| {
+            ui.strong("Pass");
+            ui.strong("GPU ms");
+            ui.end_row();
+
+            for (pass, ms) in &self.gpu_pass_ms {
+                ui.label(*pass);
+                ui.label(format!("{:.3}", ms));
+                ui.end_row();
+            }
+
+            ui.strong("GPU total");
+            ui.strong(format!("{:.3}", gpu_total));
+            ui.end_row();
+
+            ui.strong("CPU frame");
+            ui.strong(format!("{:.3}", self.cpu_frame_ms));
+            ui.end_row();
+        });
+
+        ui.separator();
+        egui::Grid::new("RenderStats").show(ui, |ui| {
+            ui.label("Meshes drawn");
+            ui.label(self.render_stats.drawn.to_string());
+            ui.end_row();
+
+            ui.label("Meshes culled");
+            ui.label(self.render_stats.culled.to_string());
+            ui.end_row();
+
+            ui.label("Static batches");
+            ui.label(self.render_stats.batches.to_string());
+            ui.end_row();
+        });
+
+        ui.separator();
+        if self.gpu_pass_ms.is_empty() {
+            ui.weak("No GPU timings yet");
+        } else if gpu_total > self.cpu_frame_ms {
+            ui.label("GPU bound");
+        } else {
+            ui.label("CPU bound");
+        }
+    }
+
+    fn particle_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Path:");
+            ui.text_edit_singleline(&mut self.particle_path);
+
+            if ui.button("Load").clicked() {
+                match ParticleEffect::load(std::path::Path::new(&self.particle_path)) {
+                    Ok(effect) => {
+                        log::info!("Loaded particle effect: {}", self.particle_path);
+                        self.particle_system.effect = effect;
+                        self.particle_system.restart();
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            if ui.button("Save").clicked() {
+                let path = std::path::Path::new(&self.particle_path);
+                match self.particle_system.effect.save(path) {
+                    Ok(()) => log::info!("Saved particle effect: {}", self.particle_path),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            if ui.button("New").clicked() {
+                self.particle_system.effect = ParticleEffect::new("New Effect");
+                self.particle_system.restart();
+            }
+
+            ui.separator();
+            ui.checkbox(&mut self.particle_preview, "▶ Preview")
+                .on_hover_text("Play the effect at the world origin");
+            if ui.button("⟲ Restart").clicked() {
+                self.particle_system.restart();
+            }
+            if self.particle_system.is_on_gpu() {
+                ui.label("Simulated on the GPU");
+            } else {
+                ui.label(format!("{} particles", self.particle_system.particle_count()));
+            }
+        });
+
+        let effect = &mut self.particle_system.effect;
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut effect.name);
+        });
+
+        ui.separator();
+
+        let vector = |ui: &mut egui::Ui, label: &str, value: &mut [f32; 3]| {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                for component in value.iter_mut() {
+                    ui.add(egui::DragValue::new(component).speed(0.05));
+                }
+            });
+        };
+        let range = |ui: &mut egui::Ui, label: &str, value: &mut (f32, f32)| {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(egui::DragValue::new(&mut value.0).speed(0.05).range(0.0..=f32::MAX));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut value.1).speed(0.05).range(value.0..=f32::MAX));
+            });
+        };
+
+        let mut remove_emitter = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, emitter) in effect.emitters.iter_mut().enumerate() {
+                egui::CollapsingHeader::new(&emitter.name)
+                    .id_salt(("Emitter", i))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Name");
+                            ui.text_edit_singleline(&mut emitter.name);
+                            if ui.button("🗑 Remove").clicked() {
+                                remove_emitter = Some(i);
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Rate");
+                            ui.add(egui::DragValue::new(&mut emitter.rate).range(0.0..=10000.0));
+                            ui.label("Burst");
+                            ui.add(egui::DragValue::new(&mut emitter.burst));
+                            ui.label("Max");
+                            ui.add(egui::DragValue::new(&mut emitter.max_particles));
+                        });
+                        range(ui, "Lifetime", &mut emitter.lifetime);
+                        range(ui, "Speed", &mut emitter.speed);
+                        vector(ui, "Direction", &mut emitter.direction);
+                        ui.horizontal(|ui| {
+                            ui.label("Spread");
+                            ui.add(
+                                egui::DragValue::new(&mut emitter.spread)
+                                    .range(0.0..=180.0)
+                                    .suffix("°"),
+                            );
+                        });
+                        vector(ui, "Gravity", &mut emitter.gravity);
+                        vector(ui, "Offset", &mut emitter.offset);
+                        ui.horizontal(|ui| {
+                            ui.label("Size");
+                            ui.add(
+                                egui::DragValue::new(&mut emitter.size)
+                                    .speed(0.01)
+                                    .range(0.0..=f32::MAX),
+                            );
+                            ui.checkbox(&mut emitter.additive, "Additive");
+                        });
+
+                        ui.label("Size over lifetime");
+                        particles::curve_ui(
+                            ui,
+                            &format!("Size{}", i),
+                            &mut emitter.size_over_lifetime,
+                            1.0,
+                        );
+                        ui.label("Color over lifetime");
+                        particles::gradient_ui(ui, &mut emitter.color_over_lifetime);
+                    });
+            }
+
+            if ui.button("➕ Emitter").clicked() {
+                effect.emitters.push(EmitterSettings::default());
+            }
+        });
+
+        if let Some(i) = remove_emitter {
+            effect.emitters.remove(i);
+            // The running particles belong to the old emitter order
+            self.particle_system.restart();
+        }
+    }
+
+    fn dialogue_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Path:");
+            ui.text_edit_singleline(&mut self.dialogue_path);
+
+            if ui.button("Load").clicked() {
+                match DialogueGraph::load(std::path::Path::new(&self.dialogue_path)) {
+                    Ok(graph) => {
+                        log::info!("Loaded dialogue: {}", self.dialogue_path);
+                        self.dialogue = graph;
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            if ui.button("Save").clicked() {
+                match self.dialogue.save(std::path::Path::new(&self.dialogue_path)) {
+                    Ok(()) => log::info!("Saved dialogue: {}", self.dialogue_path),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            if ui.button("New").clicked() {
+                self.dialogue = DialogueGraph::new("New Dialogue");
+            }
+            if ui.button("▶ Preview").clicked() {
+                let mut runner = DialogueRunner::new(self.dialogue.clone());
+                self.dialogue_variables.clear();
+                runner.start(&mut self.dialogue_variables);
+                self.dialogue_preview = Some(runner);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.dialogue.name);
+            ui.label("Start node:");
+            ui.add(egui::DragValue::new(&mut self.dialogue.start));
+        });
+
+        if let Some(runner) = &self.dialogue_preview {
+            let mut variables: Vec<_> = self.dialogue_variables.iter().collect();
+            variables.sort();
+            ui.label(format!(
+                "Previewing '{}', variables: {:?}",
+                runner.graph.name, variables
+            ));
+        }
+
+        ui.separator();
+
+        let mut remove_node = None;
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                for (n, node) in self.dialogue.nodes.iter_mut().enumerate() {
+                    ui.push_id(n, |ui| {
+                        egui::CollapsingHeader::new(format!("Node {}: {}", node.id, node.speaker))
+                            .default_open(false)
+                            .show(ui, |ui| {
+                                dialogue_node_editor(ui, node);
+
+                                if ui.button("Remove Node").clicked() {
+                                    remove_node = Some(n);
+                                }
+                            });
+                    });
+                }
+
+                if ui.button("Add Node").clicked() {
+                    let id = self.dialogue.next_free_id();
+                    self.dialogue.nodes.push(DialogueNode::new(id));
+                }
+            });
+
+        if let Some(n) = remove_node {
+            self.dialogue.nodes.remove(n);
+        }
+    }
+
+    // Turns a finished bulk edit into one undo step
+    fn commit_transform_edit(&mut self, scene: &SceneNode) {
+        let Some(before) = self.transform_edit_before.take() else {
+            return;
+        };
+        let changes: Vec<(usize, MeshTransform, MeshTransform)> = before
+            .into_iter()
+            .filter_map(|(index, before)| {
+                let after = MeshTransform::of(scene, index)?;
+                (after != before).then_some((index, before, after))
+            })
+            .collect();
+        if !changes.is_empty() {
+            self.undo_stack.push(Box::new(TransformEdit {
+                name: format!("Edit {} objects", changes.len()),
+                changes,
+            }));
+        }
+    }
+
+    fn foliage_window(
+        &mut self,
+        ctx: &egui::Context,
+        context: &glow::Context,
+        scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let mut open = self.foliage_painting;
+        egui::Window::new("🌿 Foliage")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if scene.foliage.is_empty() {
+                    ui.weak("Add a layer with Add > Foliage Layer");
+                    return;
+                }
+                self.foliage_layer = self.foliage_layer.min(scene.foliage.len() - 1);
+
+                egui::ComboBox::from_label("Layer")
+                    .selected_text(&scene.foliage[self.foliage_layer].name)
+                    .show_ui(ui, |ui| {
+                        for (i, layer) in scene.foliage.iter().enumerate() {
+                            ui.selectable_value(&mut self.foliage_layer, i, &layer.name);
+                        }
+                    });
+
+                let brush = &mut self.foliage_brush;
+                egui::Grid::new("FoliageBrush").num_columns(2).show(ui, |ui| {
+                    ui.label("Radius");
+                    ui.add(egui::Slider::new(&mut brush.radius, 0.5..=50.0));
+                    ui.end_row();
+
+                    ui.label("Density");
+                    ui.add(egui::Slider::new(&mut brush.density, 0.1..=20.0).logarithmic(true))
+                        .on_hover_text("Instances per square unit per second");
+                    ui.end_row();
+
+                    ui.label("Spacing");
+                    ui.add(egui::Slider::new(&mut brush.spacing, 0.0..=10.0));
+                    ui.end_row();
+
+                    ui.label("Scale");
+                    ui.horizontal(|ui| {
+                        let (min, max) = brush.scale;
+                        ui.add(
+                            egui::DragValue::new(&mut brush.scale.0)
+                                .speed(0.01)
+                                .range(0.01..=max),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut brush.scale.1)
+                                .speed(0.01)
+                                .range(min..=100.0),
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("Rotation");
+                    ui.checkbox(&mut brush.random_rotation, "Random around Y");
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let layer = &mut scene.foliage[self.foliage_layer];
+                    ui.label(format!("{} instances", layer.instances().len()));
+                    if ui.button("Clear").clicked() {
+                        layer.clear(context);
+                    }
+                    if ui.button("Remove layer").clicked() {
+                        let layer = scene.foliage.remove(self.foliage_layer);
+                        layer.destroy(context);
+                        asset_loader.release(AssetHandle::Mesh(layer.handle));
+                    }
+                });
+                ui.weak("Drag in the viewport to paint, hold Shift to erase");
+            });
+        self.foliage_painting = open;
+    }
+
+    // Paints or erases foliage under the pointer while the primary button is held
+    fn paint_foliage(
+        &mut self,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        context: &glow::Context,
+        camera: &dyn Camera,
+        scene: &mut SceneNode,
+        asset_loader: &AssetLoader,
+    ) {
+        if self.foliage_layer >= scene.foliage.len() || !ui.ui_contains_pointer() {
+            return;
+        }
+        // The brush scatters by density per second of painting
+        let (pointer, painting, erasing, delta_time) = ui.input(|input| {
+            (
+                input.pointer.hover_pos(),
+                input.pointer.primary_down(),
+                input.modifiers.shift,
+                input.stable_dt,
+            )
+        });
+        let Some(pointer) = pointer.filter(|_| painting) else {
+            return;
+        };
+
+        let ndc = [
+            (pointer.x - rect.min.x) / rect.width() * 2.0 - 1.0,
+            1.0 - (pointer.y - rect.min.y) / rect.height() * 2.0,
+        ];
+        let Some(hit) = Ray::from_screen(camera, ndc).and_then(|ray| {
+            raycast::raycast_triangles(scene, asset_loader, &ray, f32::INFINITY, |_| true)
+        }) else {
+            return;
+        };
+
+        // Taken out while painting since the layer raycasts against the rest of the scene
+        let mut layers = std::mem::take(&mut scene.foliage);
+        let layer = &mut layers[self.foliage_layer];
+        if erasing {
+            layer.erase(context, hit.point, self.foliage_brush.radius);
+        } else {
+            let brush = &self.foliage_brush;
+            layer.paint(context, scene, asset_loader, hit.point, brush, delta_time);
+        }
+        scene.foliage = layers;
+    }
+
+    /// Switches to another project. The scenes are replaced by an empty one built with the
+    /// project's shaders, and the project's cvars are loaded over the current ones.
+    fn open_project(
+        &mut self,
+        project: &Path,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        if let Err(e) = self.launcher.open(project) {
+            log::error!("{}", e);
+            return;
+        }
+        let config_path = Path::new(CVARS_CONFIG_PATH);
+        if config_path.exists() {
+            if let Err(e) = self.cvars.lock().unwrap().load(config_path) {
+                log::error!("{}", e);
+            }
+        }
+        self.preferences.apply(&mut self.cvars.lock().unwrap());
+        self.content_browser.reset();
+        self.thumbnails.clear();
+
+        let name = project.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let scene = SceneNode::new(&name, context).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            SceneNode::empty(&name)
+        });
+        self.replace_scenes(scene, context, scene_graph, asset_loader);
+
+        let levels = Path::new(LEVELS_FILE);
+        if let Err(e) = scene_graph.levels.load_descriptors(levels, context, asset_loader) {
+            log::error!("{}", e);
+        }
+    }
+
+    fn replace_scenes(
+        &mut self,
+        scene: SceneNode,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        self.dropped_meshes.clear();
+        self.dropped_textures.clear();
+        for scene in scene_graph.scenes.drain(..) {
+            release_scene(*scene, context, asset_loader);
+        }
+        for mut tab in self.scene_tabs.drain(..) {
+            tab.undo_stack.clear();
+            tab.undo_stack.free_discarded(context, asset_loader);
+        }
+
+        self.scene_tabs.push(SceneTab::new(None, &scene, asset_loader));
+        scene_graph.scenes.push(Box::new(scene));
+        scene_graph.current_scene = 0;
+
+        self.selected_object = None;
+        self.selection.clear();
+        self.undo_stack.clear();
+        self.undo_stack.free_discarded(context, asset_loader);
+    }
+
+    // Acts on the tabs at the start of a frame, before the open scene is borrowed
+    fn scene_tab_action(
+        &mut self,
+        action: SceneTabAction,
+        context: &glow::Context,
+        active_camera_type: &mut CameraType,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        match action {
+            SceneTabAction::New(template) => {
+                let name = unique_name("New Scene", &scene_names(scene_graph));
+                match template.create(&name, context, asset_loader) {
+                    Ok(new_scene) => {
+                        let tab = SceneTab::new(None, &new_scene.scene, asset_loader);
+                        let scene = new_scene.scene;
+                        self.add_scene_tab(tab, scene, context, scene_graph, asset_loader);
+                        self.dropped_meshes = new_scene.meshes;
+                        *active_camera_type = new_scene.camera_type;
+                        log::info!("Created a new {} scene", template.label());
+                    }
+                    Err(e) => log::error!("Failed to create the scene: {}", e),
+                }
+            }
+            SceneTabAction::Open(path) => {
+                // Already open, just show it
+                if let Some(index) = self.scene_tabs.iter().position(|tab| {
+                    tab.path.as_deref().map(content_browser::project_relative)
+                        == Some(content_browser::project_relative(&path))
+                }) {
+                    self.switch_scene(index, context, scene_graph, asset_loader);
+                    return;
+                }
+                let file = match SceneFile::load(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        return;
+                    }
+                };
+                let scene = SceneNode::new(&file.name, context).unwrap_or_else(|e| {
+                    log::error!("{}", e);
+                    SceneNode::empty(&file.name)
+                });
+                let mut tab = SceneTab::new(Some(path), &scene, asset_loader);
+                tab.loading = Some((file.request_assets(asset_loader), file));
+                self.add_scene_tab(tab, scene, context, scene_graph, asset_loader);
+            }
+            SceneTabAction::Switch(index) => {
+                self.switch_scene(index, context, scene_graph, asset_loader)
+            }
+            SceneTabAction::Close(index) => {
+                self.close_scene(index, context, scene_graph, asset_loader)
+            }
+            SceneTabAction::Save(path) => {
+                let index = scene_graph.current_scene;
+                let (Some(tab), Some(scene)) =
+                    (self.scene_tabs.get_mut(index), scene_graph.scenes.get(index))
+                else {
+                    return;
+                };
+                let Some(path) = path.or(tab.path.clone()) else {
+                    let file_name = format!("{}.{}", scene.name, SCENE_EXTENSION);
+                    let path = Path::new(SCENE_DIRECTORY).join(file_name);
+                    self.save_scene_as = Some(path.to_string_lossy().to_string());
+                    return;
+                };
+                let file = SceneFile::from_scene(scene, asset_loader);
+                match file.save(&path) {
+                    Ok(()) => {
+                        log::info!("Saved {} to {:?}", scene.name, path);
+                        self.scene_previews.clear();
+                        self.pending_preview = Some(path.clone());
+                        tab.path = Some(path);
+                        tab.saved = file;
+                        tab.dirty = false;
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    }
+
+    // The new scene becomes the open one
+    fn add_scene_tab(
+        &mut self,
+        tab: SceneTab,
+        scene: SceneNode,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        self.scene_tabs.push(tab);
+        scene_graph.scenes.push(Box::new(scene));
+        let index = scene_graph.scenes.len() - 1;
+        self.switch_scene(index, context, scene_graph, asset_loader);
+    }
+
+    // Each tab keeps its own selection and history, the open one's are in `self`
+    fn switch_scene(
+        &mut self,
+        index: usize,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let current = scene_graph.current_scene;
+        if index == current || index >= scene_graph.scenes.len() {
+            return;
+        }
+        if let Some(scene) = scene_graph.scenes.get_mut(current) {
+            self.commit_transform_edit(scene);
+        }
+        self.modal_transform = None;
+        self.renaming = None;
+        self.marquee_start = None;
+        self.dropped_meshes.clear();
+        self.dropped_textures.clear();
+
+        if let Err(e) = scene_graph.set_current_scene(index, context, asset_loader) {
+            log::error!("{}", e);
+            return;
+        }
+        self.swap_tab_state(current);
+        self.swap_tab_state(index);
+    }
+
+    // From the console or a script. A name that isn't open is looked for in the saved scenes.
+    fn request_scene_switch(&mut self, scene: SceneRef, scene_graph: &SceneGraph) {
+        if let Some(index) = scene_graph.find_scene(&scene) {
+            self.scene_tab_action = Some(SceneTabAction::Switch(index));
+            return;
+        }
+        let SceneRef::Name(name) = scene else {
+            log::error!("There is no scene {:?}", scene);
+            return;
+        };
+        let file_name = format!("{}.{}", name, SCENE_EXTENSION);
+        let path = Path::new(SCENE_DIRECTORY).join(file_name);
+        match path.exists() {
+            true => self.scene_tab_action = Some(SceneTabAction::Open(path)),
+            false => log::error!("There is no scene called {}", name),
+        }
+    }
+
+    fn swap_tab_state(&mut self, index: usize) {
+        let Some(tab) = self.scene_tabs.get_mut(index) else {
+            return;
+        };
+        std::mem::swap(&mut self.selection, &mut tab.selection);
+        std::mem::swap(&mut self.selected_object, &mut tab.selected_object);
+        std::mem::swap(&mut self.undo_stack, &mut tab.undo_stack);
+    }
+
+    // There's always a scene open, closing the last one leaves an empty one
+    fn close_scene(
+        &mut self,
+        index: usize,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        if index >= scene_graph.scenes.len() || index >= self.scene_tabs.len() {
+            return;
+        }
+        if scene_graph.scenes.len() == 1 {
+            let scene = SceneNode::new("New Scene", context).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                SceneNode::empty("New Scene")
+            });
+            self.replace_scenes(scene, context, scene_graph, asset_loader);
+            return;
+        }
+
+        // Leave it first so its state is back in its tab
+        let current = scene_graph.current_scene;
+        if index == current {
+            let next = if index + 1 < scene_graph.scenes.len() { index + 1 } else { index - 1 };
+            self.switch_scene(next, context, scene_graph, asset_loader);
+        }
+        let mut tab = self.scene_tabs.remove(index);
+        tab.undo_stack.clear();
+        tab.undo_stack.free_discarded(context, asset_loader);
+        release_scene(*scene_graph.scenes.remove(index), context, asset_loader);
+        if scene_graph.current_scene > index {
+            scene_graph.current_scene -= 1;
+        }
+    }
+
+    // Scenes that were opened instantiate once their assets are in, and the open one is
+    // compared with its file for the unsaved marker
+    fn update_scene_tabs(
+        &mut self,
+        context: &glow::Context,
+        scene_graph: &mut SceneGraph,
+        asset_loader: &mut AssetLoader,
+    ) {
+        // The scenes made outside the editor
+        while self.scene_tabs.len() < scene_graph.scenes.len() {
+            let scene = &scene_graph.scenes[self.scene_tabs.len()];
+            self.scene_tabs.push(SceneTab::new(None, scene, asset_loader));
+        }
+
+        for (tab, scene) in self.scene_tabs.iter_mut().zip(scene_graph.scenes.iter_mut()) {
+            let Some((handles, _)) = &tab.loading else {
+                continue;
+            };
+            let loading = asset_loader
+                .in_flight()
+                .any(|progress| handles.contains(&progress.handle));
+            if !loading {
+                let (_, file) = tab.loading.take().unwrap();
+                file.instantiate(context, scene, asset_loader);
+                tab.saved = SceneFile::from_scene(scene, asset_loader);
+            }
+        }
+
+        let index = scene_graph.current_scene;
+        let current = self.scene_tabs.get_mut(index).zip(scene_graph.scenes.get(index));
+        if let Some((tab, scene)) = current.filter(|(tab, _)| tab.loading.is_none()) {
+            tab.dirty = SceneFile::from_scene(scene, asset_loader) != tab.saved;
+        }
+    }
+
+    // What modal transforms rotate and scale around, only kept until the editor closes
+    fn pivot_ui(&mut self, ui: &mut egui::Ui) {
+        let choices = [
+            Pivot::Origin,
+            Pivot::BoundsCenter,
+            Pivot::BoundsBottom,
+            Pivot::Custom(cgmath::Vector3::new(0.0, 0.0, 0.0)),
+        ];
+        egui::ComboBox::from_id_salt("Pivot")
+            .selected_text(format!("Pivot: {}", self.pivot.label()))
+            .show_ui(ui, |ui| {
+                for choice in choices {
+                    // Picking Custom again keeps the offset
+                    let selected =
+                        std::mem::discriminant(&self.pivot) == std::mem::discriminant(&choice);
+                    if ui.selectable_label(selected, choice.label()).clicked() && !selected {
+                        self.pivot = choice;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("What rotating and scaling turns around");
+
+        if let Pivot::Custom(offset) = &mut self.pivot {
+            for value in [&mut offset.x, &mut offset.y, &mut offset.z] {
+                ui.add(egui::DragValue::new(value).speed(0.05))
+                    .on_hover_text("Offset from the origin, in the object's own space");
+            }
+        }
+    }
+
+    // The world axes as the camera sees them, in the viewport's corner. Clicking one looks at
+    // the selection (or what's in front of the camera) from that side through the ortho camera.
+    fn axis_gizmo(
+        &mut self,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        camera: &dyn Camera,
+        scene: &SceneNode,
+        active_camera_type: &mut CameraType,
+    ) {
+        const RADIUS: f32 = 36.0;
+        let center = rect.right_top() + egui::vec2(-RADIUS - 16.0, RADIUS + 16.0);
+        let painter = ui.painter();
+        painter.circle_filled(center, RADIUS + 12.0, egui::Color32::from_black_alpha(60));
+
+        let view = camera.get_view();
+        let mut axes: Vec<(AxisView, cgmath::Vector3<f32>)> = AxisView::ALL
+            .iter()
+            .map(|&axis| (axis, (view * axis.axis().extend(0.0)).truncate()))
+            .collect();
+        // Furthest first so the nearer ones are drawn over them
+        axes.sort_by(|(_, a), (_, b)| a.z.total_cmp(&b.z));
+
+        for (axis, projected) in axes {
+            let end = center + egui::vec2(projected.x, -projected.y) * RADIUS;
+            let color = match axis {
+                AxisView::PositiveX | AxisView::NegativeX => egui::Color32::from_rgb(220, 70, 70),
+                AxisView::PositiveY | AxisView::NegativeY => egui::Color32::from_rgb(90, 200, 90),
+                AxisView::PositiveZ | AxisView::NegativeZ => egui::Color32::from_rgb(80, 130, 230),
+            };
+            let positive = matches!(
+                axis,
+                AxisView::PositiveX | AxisView::PositiveY | AxisView::PositiveZ
+            );
+            if positive {
+                painter.line_segment([center, end], egui::Stroke::new(2.0, color));
+                painter.circle_filled(end, 9.0, color);
+                painter.text(
+                    end,
+                    egui::Align2::CENTER_CENTER,
+                    axis.label(),
+                    egui::FontId::proportional(11.0),
+                    egui::Color32::BLACK,
+                );
+            } else {
+                painter.circle_stroke(end, 7.0, egui::Stroke::new(1.5, color));
+            }
+
+            let shortcut = ui.ctx().format_shortcut(&axis.shortcut());
+            let response = ui
+                .interact(
+                    egui::Rect::from_center_size(end, egui::vec2(18.0, 18.0)),
+                    ui.id().with(("Axis gizmo", axis.label())),
+                    egui::Sense::click(),
+                )
+                .on_hover_text(format!("{} view ({})", axis.name(), shortcut));
+            if response.clicked() {
+                self.look_from_axis(axis, camera, scene, active_camera_type);
+            }
+        }
+    }
+
+    // Centered on the selection, or what's in front of the camera when nothing is selected
+    fn look_from_axis(
+        &mut self,
+        axis: AxisView,
+        camera: &dyn Camera,
+        scene: &SceneNode,
+        active_camera_type: &mut CameraType,
+    ) {
+        let selected = self
+            .selection
+            .iter()
+            .filter_map(|&index| scene.static_mesh_world_bounds(index))
+            .reduce(|a, b| a.union(&b));
+        let target = match selected {
+            Some(bounds) => bounds.center(),
+            None => camera.get_position() + camera.get_orientation() * 10.0,
+        };
+        self.axis_view = Some((axis, target));
+        *active_camera_type = CameraType::Orthographic;
+    }
+
+    // `names` are the scene graph's, taken before the open scene was borrowed
+    fn scene_tabs_bar(&mut self, ui: &mut egui::Ui, names: &[String], open: usize) {
+        for (index, (tab, name)) in self.scene_tabs.iter().zip(names).enumerate() {
+            let title = match (tab.loading.is_some(), tab.dirty) {
+                (true, _) => format!("⏳ {}", name),
+                (false, true) => format!("● {}", name),
+                (false, false) => name.clone(),
+            };
+            let hover = match &tab.path {
+                Some(path) => path.to_string_lossy().to_string(),
+                None => "Not saved yet".to_string(),
+            };
+            let response = ui.selectable_label(index == open, title).on_hover_text(hover);
+            if response.clicked() {
+                self.scene_tab_action = Some(SceneTabAction::Switch(index));
+            }
+            let close = ui.small_button("✖").on_hover_text("Close");
+            if close.clicked() || response.middle_clicked() {
+                match tab.dirty {
+                    true => self.pending_scene_close = Some(index),
+                    false => self.scene_tab_action = Some(SceneTabAction::Close(index)),
+                }
+            }
+            ui.separator();
+        }
+        ui.menu_button("➕", |ui| {
+            for template in SceneTemplate::ALL {
+                if ui
+                    .button(template.label())
+                    .on_hover_text(template.description())
+                    .clicked()
+                {
+                    self.scene_tab_action = Some(SceneTabAction::New(template));
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("New scene");
+    }
+
+    fn save_scene_as_window(&mut self, ctx: &egui::Context) {
+        let Some(mut path) = self.save_scene_as.take() else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Save Scene As")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let response = ui.text_edit_singleline(&mut path);
+                    save = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                });
+                save |= ui.button("Save").clicked();
+            });
+
+        if save {
+            let mut path = PathBuf::from(path);
+            if path.extension().is_none() {
+                path.set_extension(SCENE_EXTENSION);
+            }
+            self.scene_tab_action = Some(SceneTabAction::Save(Some(path)));
+        } else if open {
+            self.save_scene_as = Some(path);
+        }
+    }
+
+    fn close_scene_window(&mut self, ctx: &egui::Context, names: &[String]) {
+        let Some(index) = self.pending_scene_close.take() else {
+            return;
+        };
+        let name = names.get(index).map_or("the scene", String::as_str);
+        let mut answer = None;
+        egui::Window::new("Close scene")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("{} has unsaved changes. Close it anyway?", name));
+                ui.horizontal(|ui| {
+                    if ui.button("Close").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        match answer {
+            Some(true) => self.scene_tab_action = Some(SceneTabAction::Close(index)),
+            Some(false) => {}
+            None => self.pending_scene_close = Some(index),
+        }
+    }
+
+    fn tilemap_window(
+        &mut self,
+        ctx: &egui::Context,
+        context: &glow::Context,
+        scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let mut open = self.tile_painting;
+        egui::Window::new("🧱 Tilemap")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    ui.text_edit_singleline(&mut self.tilemap_path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Load").clicked() {
+                        match Tilemap::load(std::path::Path::new(&self.tilemap_path)) {
+                            Ok(mut tilemap) => {
+                                tilemap.request_atlas(asset_loader);
+                                scene.tilemaps.push(tilemap);
+                                self.tilemap_index = scene.tilemaps.len() - 1;
+                                log::info!("Loaded tilemap: {}", self.tilemap_path);
+                            }
+                            Err(e) => log::error!("{}", e),
+                        }
+                    }
+                    if let Some(tilemap) = scene.tilemaps.get(self.tilemap_index) {
+                        if ui.button("Save").clicked() {
+                            match tilemap.save(std::path::Path::new(&self.tilemap_path)) {
+                                Ok(()) => log::info!("Saved tilemap: {}", self.tilemap_path),
+                                Err(e) => log::error!("{}", e),
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+
+                if scene.tilemaps.is_empty() {
+                    ui.weak("Add a tilemap with Add > Tilemap or load one");
+                    return;
+                }
+                self.tilemap_index = self.tilemap_index.min(scene.tilemaps.len() - 1);
+
+                egui::ComboBox::from_label("Tilemap")
+                    .selected_text(&scene.tilemaps[self.tilemap_index].name)
+                    .show_ui(ui, |ui| {
+                        for (i, tilemap) in scene.tilemaps.iter().enumerate() {
+                            ui.selectable_value(&mut self.tilemap_index, i, &tilemap.name);
+                        }
+                    });
+
+                let tilemap = &mut scene.tilemaps[self.tilemap_index];
+                let mut layout_changed = false;
+                egui::Grid::new("TilemapSettings").num_columns(2).show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut tilemap.name);
+                    ui.end_row();
+
+                    ui.label("Atlas");
+                    ui.horizontal(|ui| {
+                        layout_changed |= ui
+                            .add(egui::DragValue::new(&mut tilemap.atlas_columns).range(1..=256))
+                            .changed();
+                        ui.label("x");
+                        layout_changed |= ui
+                            .add(egui::DragValue::new(&mut tilemap.atlas_rows).range(1..=256))
+                            .changed();
+                    });
+                    ui.end_row();
+
+                    ui.label("Tile size");
+                    layout_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut tilemap.tile_size)
+                                .speed(0.05)
+                                .range(0.01..=100.0),
+                        )
+                        .changed();
+                    ui.end_row();
+
+                    ui.label("Origin");
+                    ui.horizontal(|ui| {
+                        for value in &mut tilemap.origin {
+                            layout_changed |=
+                                ui.add(egui::DragValue::new(value).speed(0.05)).changed();
+                        }
+                    });
+                    ui.end_row();
+                });
+                if layout_changed {
+                    tilemap.mark_all_dirty();
+                }
+                ui.label(format!(
+                    "{}x{} tiles from {:?}",
+                    tilemap.width(),
+                    tilemap.height(),
+                    tilemap.atlas
+                ));
+
+                // One button per atlas frame, laid out like the atlas
+                ui.label("Tile");
+                self.tile_brush = self.tile_brush.min(tilemap.frame_count().saturating_sub(1));
+                egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                    egui::Grid::new("TilePalette").spacing([2.0, 2.0]).show(ui, |ui| {
+                        for frame in 0..tilemap.frame_count() {
+                            ui.selectable_value(&mut self.tile_brush, frame, frame.to_string());
+                            if (frame + 1) % tilemap.atlas_columns.max(1) == 0 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+                });
+
+                ui.separator();
+                if ui.button("Remove tilemap").clicked() {
+                    let mut tilemap = scene.tilemaps.remove(self.tilemap_index);
+                    tilemap.destroy(context);
+                    tilemap.release_atlas(asset_loader);
+                }
+                ui.weak("Drag in the viewport to paint, hold Shift to erase");
+            });
+        self.tile_painting = open;
+    }
+
+    // Paints or erases tiles under the pointer while the primary button is held
+    fn paint_tiles(
+        &mut self,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        camera: &dyn Camera,
+        scene: &mut SceneNode,
+    ) {
+        if !ui.ui_contains_pointer() {
+            return;
+        }
+        let Some(tilemap) = scene.tilemaps.get_mut(self.tilemap_index) else {
+            return;
+        };
+        let (pointer, painting, erasing) = ui.input(|input| {
+            (
+                input.pointer.hover_pos(),
+                input.pointer.primary_down(),
+                input.modifiers.shift,
+            )
+        });
+        let Some(pointer) = pointer.filter(|_| painting) else {
+            return;
+        };
+
+        let ndc = [
+            (pointer.x - rect.min.x) / rect.width() * 2.0 - 1.0,
+            1.0 - (pointer.y - rect.min.y) / rect.height() * 2.0,
+        ];
+        let Some((x, y)) =
+            Ray::from_screen(camera, ndc).and_then(|ray| tilemap.tile_under_ray(&ray))
+        else {
+            return;
+        };
+        tilemap.set_tile(x, y, (!erasing).then_some(self.tile_brush));
+    }
+
+    // A mesh dropped from the content browser lands where the pointer meets the ground, a texture
+    // goes on the mesh under the pointer
+    fn drop_asset(
+        &mut self,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        camera: &dyn Camera,
+        scene: &SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let Some(pointer) = ui.input(|input| input.pointer.hover_pos()) else {
+            return;
+        };
+        if !rect.contains(pointer) || !ui.input(|input| input.pointer.any_released()) {
+            return;
+        }
+        let Some(dragged) = egui::DragAndDrop::take_payload::<DraggedAsset>(ui.ctx()) else {
+            return;
+        };
+
+        let ndc = [
+            (pointer.x - rect.min.x) / rect.width() * 2.0 - 1.0,
+            1.0 - (pointer.y - rect.min.y) / rect.height() * 2.0,
+        ];
+        let Some(ray) = Ray::from_screen(camera, ndc) else {
+            return;
+        };
+        let name = dragged.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match dragged.kind {
+            AssetKind::Mesh => {
+                let handle = asset_loader.request_mesh(&dragged.path, name);
+                self.dropped_meshes.push((handle, ground_point(&ray)));
+            }
+            AssetKind::Texture => match raycast::raycast(scene, &ray, f32::INFINITY) {
+                Some(hit) => {
+                    let handle = asset_loader.request_texture(&dragged.path, name);
+                    self.dropped_textures.push((handle, hit.static_mesh));
+                }
+                None => log::warn!("Drop a texture onto a mesh to use it as its base color"),
+            },
+            _ => {}
+        }
+    }
+
+    // Loaded where they are, meshes spawn in the middle of the view when the preference is on
+    fn import_dropped_files(&mut self, camera: &dyn Camera, asset_loader: &mut AssetLoader) {
+        for path in std::mem::take(&mut self.dropped_files) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            match AssetKind::from_path(&path) {
+                AssetKind::Mesh => {
+                    log::info!("Importing mesh: {:?}", path);
+                    let handle = asset_loader.request_mesh(&path, name);
+                    if self.preferences.spawn_dropped_meshes {
+                        if let Some(ray) = Ray::from_screen(camera, [0.0, 0.0]) {
+                            self.dropped_meshes.push((handle, ground_point(&ray)));
+                        }
+                    }
+                }
+                AssetKind::Texture => {
+                    log::info!("Importing texture: {:?}", path);
+                    asset_loader.request_texture(&path, name);
+                }
+                _ => log::warn!(
+                    "Can't import {:?}, only glTF meshes and images are supported",
+                    path
+                ),
+            }
+        }
+    }
+
+    fn place_dropped_assets(
+        &mut self,
+        context: &glow::Context,
+        scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let still_loading = |asset_loader: &AssetLoader, handle: AssetHandle| {
+            asset_loader.in_flight().any(|progress| progress.handle == handle)
+        };
+
+        for (handle, point) in std::mem::take(&mut self.dropped_meshes) {
+            let Some(loaded_mesh) = asset_loader.loaded_mesh_data.get(&handle) else {
+                if still_loading(asset_loader, AssetHandle::Mesh(handle)) {
+                    self.dropped_meshes.push((handle, point));
+                }
+                continue;
+            };
+            let name = loaded_mesh.name.clone();
+            let first = scene.static_meshes.len();
+            match scene.add_mesh_asset(context, name.clone(), handle, asset_loader) {
+                Ok(count) => {
+                    // Only the roots move, the rest follow them
+                    for mesh in &mut scene.static_meshes[first..] {
+                        if mesh.attachment.is_none() {
+                            mesh.translation += point.to_vec();
+                        }
+                    }
+                    for _ in 0..count {
+                        asset_loader.retain(AssetHandle::Mesh(handle));
+                    }
+                    log::info!("Added Static Mesh: {}", name);
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+
+        for (handle, index) in std::mem::take(&mut self.dropped_textures) {
+            if !asset_loader.loaded_texture_data.contains_key(&handle) {
+                if still_loading(asset_loader, AssetHandle::Texture(handle)) {
+                    self.dropped_textures.push((handle, index));
+                }
+                continue;
+            }
+            let Some(mesh) = scene.static_meshes.get_mut(index) else {
+                continue;
+            };
+            match mesh.set_base_color_map(context, handle, asset_loader) {
+                Ok(previous) => {
+                    asset_loader.retain(AssetHandle::Texture(handle));
+                    if let Some(previous) = previous {
+                        asset_loader.release(AssetHandle::Texture(previous));
+                    }
+                    log::info!("Set the base color of {}", mesh.name);
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+    }
+
+    // Clicking a mesh in the viewport selects it, Ctrl adds it to the selection. Shift dragging
+    // draws a box that selects every mesh whose middle is in it.
+    fn viewport_selection(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        rect: egui::Rect,
+        camera: &dyn Camera,
+        scene: &mut SceneNode,
+    ) {
+        let (shift, command) = ui.input(|input| (input.modifiers.shift, input.modifiers.command));
+
+        if response.drag_started_by(egui::PointerButton::Primary) && shift {
+            self.marquee_start = response.interact_pointer_pos();
+        }
+        if let Some(start) = self.marquee_start {
+            let Some(end) = ui.input(|input| input.pointer.interact_pos()) else {
+                return;
+            };
+            let marquee = egui::Rect::from_two_pos(start, end);
+            if !response.drag_stopped() {
+                let stroke = ui.visuals().selection.stroke;
+                let fill = ui.visuals().selection.bg_fill.gamma_multiply(0.15);
+                ui.painter().rect(marquee, 0.0, fill, stroke, egui::StrokeKind::Inside);
+                return;
+            }
+            self.marquee_start = None;
+
+            let view_projection = camera.get_projection() * camera.get_view();
+            let inside: Vec<usize> = (0..scene.static_meshes.len())
+                .filter(|&i| {
+                    let center = match scene.static_mesh_world_bounds(i) {
+                        Some(bounds) => bounds.center(),
+                        None => {
+                            let origin = scene.static_mesh_world_matrix(i).w.truncate();
+                            cgmath::Point3::from_vec(origin)
+                        }
+                    };
+                    screen_position(&view_projection, rect, center)
+                        .is_some_and(|position| marquee.contains(position))
+                })
+                .collect();
+            self.commit_transform_edit(scene);
+            if !command {
+                self.selection.clear();
+            }
+            for i in inside {
+                if !self.selection.contains(&i) {
+                    self.selection.push(i);
+                }
+            }
+        } else if response.clicked() {
+            let Some(pointer) = response.interact_pointer_pos() else {
+                return;
+            };
+            let ndc = [
+                (pointer.x - rect.min.x) / rect.width() * 2.0 - 1.0,
+                1.0 - (pointer.y - rect.min.y) / rect.height() * 2.0,
+            ];
+            let hit = Ray::from_screen(camera, ndc)
+                .and_then(|ray| raycast::raycast(scene, &ray, f32::INFINITY));
+            self.commit_transform_edit(scene);
+            match (hit, command) {
+                (Some(hit), true) => {
+                    if self.selection.contains(&hit.static_mesh) {
+                        self.selection.retain(|&index| index != hit.static_mesh);
+                    } else {
+                        self.selection.push(hit.static_mesh);
+                    }
+                }
+                (Some(hit), false) => self.selection = vec![hit.static_mesh],
+                (None, true) => {}
+                (None, false) => self.selection.clear(),
+            }
+        } else {
+            return;
+        }
+
+        self.selected_object = self
+            .selection
+            .last()
+            .map(|&index| SelectedObject::StaticMesh(index));
+        if !self.selection.is_empty() {
+            self.tutorial.notify("object_selected");
+        }
+    }
+
+    /// The Properties panel for several static meshes. Fields that differ show a dash,
+    /// editing one sets it on every selected mesh.
+    fn bulk_transform_editor(&mut self, ui: &mut egui::Ui, scene: &mut SceneNode) {
+        let before: Vec<(usize, MeshTransform)> = self
+            .selection
+            .iter()
+            .filter_map(|&index| MeshTransform::of(scene, index).map(|t| (index, t)))
+            .collect();
+        let Some(&(_, first)) = before.first() else {
+            return;
+        };
+
+        ui.label(format!("{} static meshes selected", before.len()));
+        ui.heading("Transform");
+
+        let component = |t: &MeshTransform, row: usize, axis: usize| {
+            [t.translation, t.rotation, t.scale][row][axis]
+        };
+        let mut edited = None; // (row, axis, value)
+        let mut active = false;
+
+        let rows = [(0, "Translate", 0.05), (1, "Rotate", 1.0), (2, "Scale", 0.01)];
+        for (row, label, speed) in rows {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                // Adds space between the text and inputs
+                ui.allocate_ui_with_layout(
+                    ui.available_size(),
+                    Layout::right_to_left(Align::Center),
+                    |ui| {
+                        // The inputs are in the reverse order
+                        for axis in (0..3).rev() {
+                            let mut value = component(&first, row, axis);
+                            let mixed = before
+                                .iter()
+                                .any(|(_, t)| component(t, row, axis) != value);
+
+                            let mut drag = egui::DragValue::new(&mut value).speed(speed);
+                            if mixed {
+                                drag = drag.custom_formatter(|_, _| "—".to_string());
+                            }
+                            let mut response = ui.add(drag);
+                            if mixed {
+                                response = response.on_hover_text("Mixed values");
+                            }
+
+                            if response.changed() {
+                                edited = Some((row, axis, value));
+                            }
+                            active |= response.dragged() || response.has_focus();
+                        }
+                    },
+                );
+            });
+        }
+
+        if let Some((row, axis, value)) = edited {
+            if self.transform_edit_before.is_none() {
+                self.transform_edit_before = Some(before.clone());
+            }
+            for (index, mesh_before) in &before {
+                let mesh = &mut scene.static_meshes[*index];
+                let vector = match row {
+                    0 => &mut mesh.translation,
+                    1 => &mut mesh.rotation,
+                    _ => &mut mesh.scale,
+                };
+                vector[axis] = value;
+                // Each mesh keeps its own locks
+                mesh.constraints
+                    .constrain(*mesh_before, MeshTransform::from_mesh(mesh))
+                    .apply_to(mesh);
+                scene.mark_moved(*index);
+            }
+        }
+
+        // A drag or a typed value is one step, not one per frame
+        if !active {
+            self.commit_transform_edit(scene);
+        }
+    }
+
+    // One static mesh in the hierarchy and everything attached to it. Dragging it onto another
+    // mesh attaches it there.
+    fn hierarchy_mesh(
+        &mut self,
+        ui: &mut egui::Ui,
+        tree: &HierarchyTree,
+        index: usize,
+        clicked: &mut Option<usize>,
+        reparent: &mut Option<(usize, Option<usize>)>,
+    ) {
+        let mut label = |ui: &mut egui::Ui| {
+            let selected = self.selection.contains(&index);
+            let object = SelectedObject::StaticMesh(index);
+            let name = &tree.scene.static_meshes[index].name;
+            let Some(response) = self.hierarchy_name(ui, object, name, selected) else {
+                return;
+            };
+            let response = response.interact(egui::Sense::click_and_drag());
+            response.dnd_set_drag_payload(HierarchyDrag(index));
+            if response.dnd_hover_payload::<HierarchyDrag>().is_some() {
+                ui.painter().rect_stroke(
+                    response.rect,
+                    2.0,
+                    ui.visuals().selection.stroke,
+                    egui::StrokeKind::Inside,
+                );
+            }
+            if let Some(dragged) = response.dnd_release_payload::<HierarchyDrag>() {
+                if dragged.0 != index {
+                    *reparent = Some((dragged.0, Some(index)));
+                }
+            }
+            if response.clicked() {
+                *clicked = Some(index);
+            }
+            response.context_menu(|ui| {
+                // Right clicking outside the selection selects just that mesh
+                if !self.selection.contains(&index) {
+                    self.selection = vec![index];
+                    self.selected_object = Some(SelectedObject::StaticMesh(index));
+                }
+                if ui.button("Duplicate").clicked() {
+                    self.object_command = Some(ObjectCommand::Duplicate);
+                    ui.close_menu();
+                }
+                if ui.button("Delete").clicked() {
+                    self.object_command = Some(ObjectCommand::Delete);
+                    ui.close_menu();
+                }
+            });
+        };
+
+        let children: Vec<usize> = tree.children[index]
+            .iter()
+            .copied()
+            .filter(|&child| tree.shown[child])
+            .collect();
+        if children.is_empty() {
+            label(ui);
+            return;
+        }
+        let id = ui.make_persistent_id(("Hierarchy mesh", index));
+        let mut state =
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true);
+        if tree.filtering {
+            state.set_open(true);
+        }
+        state.show_header(ui, label).body(|ui| {
+            for child in children {
+                self.hierarchy_mesh(ui, tree, child, clicked, reparent);
+            }
+        });
+    }
+
+    fn hierarchy_panel(
+        &mut self,
+        ui: &mut egui::Ui,
+        current_scene: &mut SceneNode,
+        asset_loader: &AssetLoader,
+    ) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.hierarchy_search)
+                    .hint_text("Search, t:mesh for one type"),
+            )
+            .on_hover_text(
+                "Types: mesh, sprite, dynamic, camera, texture, material, script",
+            );
+            if ui.small_button("✖").on_hover_text("Clear").clicked() {
+                self.hierarchy_search.clear();
+            }
+        });
+        let filter = HierarchyFilter::parse(&self.hierarchy_search);
+
+        egui::CollapsingHeader::new(current_scene.name.clone()).open(filter.open()).show(ui, |ui| {
+            // Attached meshes are listed under their parent
+            let count = current_scene.static_meshes.len();
+            let mut children = vec![Vec::new(); count];
+            let mut roots = Vec::new();
+            for (i, mesh) in current_scene.static_meshes.iter().enumerate() {
+                match mesh.attachment.as_ref().filter(|attachment| attachment.parent < count) {
+                    Some(attachment) => children[attachment.parent].push(i),
+                    None => roots.push(i),
+                }
+            }
+
+            // A match keeps its parents in the tree so it can be found
+            let mut shown = vec![filter.is_empty(); count];
+            for (i, mesh) in current_scene.static_meshes.iter().enumerate() {
+                if !filter.matches(STATIC_MESH_KINDS, &mesh.name) {
+                    continue;
+                }
+                let mut current = Some(i);
+                while let Some(index) = current.filter(|&index| !shown[index]) {
+                    shown[index] = true;
+                    current = current_scene.static_meshes[index]
+                        .attachment
+                        .as_ref()
+                        .map(|attachment| attachment.parent)
+                        .filter(|&parent| parent < count);
+                }
+            }
+
+            let mut clicked = None;
+            let mut reparent = None;
+            if filter.is_empty() || shown.contains(&true) {
+                let header = egui::CollapsingHeader::new("Static Meshes")
+                    .open(filter.open())
+                    .show(ui, |ui| {
+                        let tree = HierarchyTree {
+                            scene: current_scene,
+                            children: &children,
+                            shown: &shown,
+                            filtering: !filter.is_empty(),
+                        };
+                        for &i in roots.iter().filter(|&&i| shown[i]) {
+                            self.hierarchy_mesh(ui, &tree, i, &mut clicked, &mut reparent);
+                        }
+                    });
+                // Dropped on the header, a mesh goes back to the top
+                if let Some(dragged) =
+                    header.header_response.dnd_release_payload::<HierarchyDrag>()
+                {
+                    reparent = Some((dragged.0, None));
+                }
+            }
+
+            if let Some(i) = clicked {
+                let selected = self.selection.contains(&i);
+                self.commit_transform_edit(current_scene);
+                let (shift, command) =
+                    ui.input(|input| (input.modifiers.shift, input.modifiers.command));
+                // Shift click selects everything listed from the first selected mesh to this one,
+                // Ctrl click adds to or removes from the selection
+                if let Some(&anchor) = self.selection.first().filter(|_| shift) {
+                    let order = hierarchy_order(&roots, &children, &shown);
+                    let from = order.iter().position(|&index| index == anchor);
+                    let to = order.iter().position(|&index| index == i);
+                    if let (Some(from), Some(to)) = (from, to) {
+                        let range: Vec<usize> = match from <= to {
+                            true => order[from..=to].to_vec(),
+                            false => order[to..=from].iter().rev().copied().collect(),
+                        };
+                        if command {
+                            self.selection.retain(|index| !range.contains(index));
+                            self.selection.retain(|&index| index != anchor);
+                            self.selection.insert(0, anchor);
+                            self.selection.extend(range.into_iter().skip(1));
+                        } else {
+                            self.selection = range;
+                        }
+                    }
+                } else if command {
+                    if selected {
+                        self.selection.retain(|&index| index != i);
+                    } else {
+                        self.selection.push(i);
+                    }
+                } else {
+                    self.selection = vec![i];
+                }
+                self.selected_object = self
+                    .selection
+                    .last()
+                    .map(|&index| SelectedObject::StaticMesh(index));
+                self.tutorial.notify("object_selected");
+            }
+            if let Some((child, parent)) = reparent {
+                self.commit_transform_edit(current_scene);
+                match current_scene.reparent(child, parent) {
+                    // The history holds local transforms, which mean something else now
+                    Ok(()) => self.undo_stack.clear(),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+
+            let sprites = current_scene.sprites.iter().enumerate().map(|(i, sprite)| {
+                let name = match sprite
+                    .texture
+                    .and_then(|handle| asset_loader.loaded_texture_data.get(&handle))
+                {
+                    Some(texture) => format!("{} {}", i, texture.name),
+                    None => format!("{} Sprite", i),
+                };
+                (SelectedObject::Sprite(i), name)
+            });
+            let sprites = sprites.collect();
+            let dynamic_meshes = current_scene.dynamic_meshes.iter().enumerate();
+            let dynamic_meshes = dynamic_meshes
+                .map(|(i, mesh)| (SelectedObject::DynamicMesh(i), mesh.name.clone()))
+                .collect();
+            let cameras = current_scene.perspective_cameras.iter().enumerate();
+            let cameras = cameras
+                .map(|(i, camera)| (SelectedObject::PerspectiveCamera(i), camera.name.clone()))
+                .collect();
+
+            let active = current_scene.active_camera.map(SelectedObject::PerspectiveCamera);
+
+            let clicked = [
+                self.hierarchy_objects(ui, &filter, "Sprites", &["sprite"], sprites, None),
+                self.hierarchy_objects(
+                    ui,
+                    &filter,
+                    "Dynamic Meshes",
+                    &["dynamic"],
+                    dynamic_meshes,
+                    None,
+                ),
+                self.hierarchy_objects(
+                    ui,
+                    &filter,
+                    "Perspective Cameras",
+                    &["camera"],
+                    cameras,
+                    active,
+                ),
+            ];
+            if let Some(active) = self.camera_activation.take() {
+                current_scene.active_camera = active;
+            }
+            if let Some(object) = clicked.into_iter().flatten().last() {
+                self.commit_transform_edit(current_scene);
+                self.selection.clear();
+                self.selected_object = Some(object);
+            }
+
+            let textures = current_scene.textures.iter().map(|t| t.name.clone());
+            hierarchy_labels(ui, &filter, "Textures", &["texture"], textures);
+            let materials = current_scene.materials.iter().map(|m| m.name.clone());
+            hierarchy_labels(ui, &filter, "Materials", &["material"], materials);
+            let scripts = current_scene.scripts.iter().cloned();
+            hierarchy_labels(ui, &filter, "Scripts", &["script"], scripts);
+        });
+
+        // F2 renames the selection, like in a file manager
+        if ui.ui_contains_pointer()
+            && !ui.ctx().wants_keyboard_input()
+            && ui.input(|input| input.key_pressed(Key::F2))
+        {
+            if let Some(object) = self.selected_object {
+                let name = object_name(current_scene, object).map(str::to_string);
+                if let Some(name) = name {
+                    self.start_rename(ui.ctx(), object, name);
+                }
+            }
+        }
+        if self.renaming.as_ref().is_some_and(|rename| rename.done) {
+            let rename = self.renaming.take().unwrap();
+            rename_object(current_scene, rename.object, &rename.text);
+        }
+    }
+
+    fn start_rename(&mut self, ctx: &egui::Context, object: SelectedObject, name: String) {
+        self.renaming = Some(Rename {
+            object,
+            text: name,
+            done: false,
+        });
+        ctx.memory_mut(|memory| memory.request_focus(egui::Id::new(RENAME_ID)));
+    }
+
+    // The name of an object in the hierarchy, or a text box while it's being renamed. Double
+    // clicking starts a rename. None while renaming.
+    fn hierarchy_name(
+        &mut self,
+        ui: &mut egui::Ui,
+        object: SelectedObject,
+        name: &str,
+        selected: bool,
+    ) -> Option<egui::Response> {
+        if let Some(rename) = self.renaming.as_mut().filter(|rename| rename.object == object) {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut rename.text)
+                    .id(egui::Id::new(RENAME_ID))
+                    .desired_width(160.0),
+            );
+            // Enter or clicking away keeps the name, Escape throws it away
+            if response.lost_focus() {
+                if ui.input(|input| input.key_pressed(Key::Escape)) {
+                    self.renaming = None;
+                } else {
+                    rename.done = true;
+                }
+            }
+            return None;
+        }
+
+        let response = ui.selectable_label(selected, name);
+        let renamable = !matches!(object, SelectedObject::Sprite(_));
+        if renamable && response.double_clicked() {
+            self.start_rename(ui.ctx(), object, name.to_string());
+        }
+        Some(response)
+    }
+
+    // A hierarchy section of objects that can be selected, returns the one that was clicked.
+    // `active` is the camera play mode is seen through, if it's in this section.
+    fn hierarchy_objects(
+        &mut self,
+        ui: &mut egui::Ui,
+        filter: &HierarchyFilter,
+        title: &str,
+        kinds: &[&str],
+        objects: Vec<(SelectedObject, String)>,
+        active: Option<SelectedObject>,
+    ) -> Option<SelectedObject> {
+        let objects: Vec<(SelectedObject, String)> = objects
+            .into_iter()
+            .filter(|(_, name)| filter.matches(kinds, name))
+            .collect();
+        if !filter.is_empty() && objects.is_empty() {
+            return None;
+        }
+        let mut clicked = None;
+        egui::CollapsingHeader::new(title)
+            .open(filter.open())
+            .show(ui, |ui| {
+                for (object, name) in objects {
+                    let selected = self.selected_object == Some(object);
+                    let is_active = active == Some(object);
+                    let response = ui
+                        .horizontal(|ui| {
+                            let response = self.hierarchy_name(ui, object, &name, selected);
+                            if is_active {
+                                ui.weak("🎥 active");
+                            }
+                            response
+                        })
+                        .inner;
+                    let Some(response) = response else {
+                        continue;
+                    };
+                    if response.clicked() {
+                        clicked = Some(object);
+                    }
+                    if let SelectedObject::PerspectiveCamera(index) = object {
+                        response.context_menu(|ui| {
+                            if is_active {
+                                if ui.button("Clear Active Camera").clicked() {
+                                    self.camera_activation = Some(None);
+                                    ui.close_menu();
+                                }
+                            } else if ui.button("Set as Active Camera").clicked() {
+                                self.camera_activation = Some(Some(index));
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                }
+            });
+        clicked
+    }
+
+    // The camera the viewport is seen through. Its FOV, speed and sensitivity are set from the
+    // cvars every frame, so those are changed there
+    fn editor_camera_properties(&mut self, ui: &mut egui::Ui, camera: &mut dyn Camera) {
+        ui.heading("Editor Camera");
+        let changes = if let Some(camera) = camera.as_perspective_mut() {
+            camera::perspective_camera_ui(ui, camera).then(|| {
+                vec![
+                    ("r_fov", CVarValue::Float(camera.fov)),
+                    ("cam_speed", CVarValue::Float(camera.speed)),
+                    ("cam_sensitivity", CVarValue::Float(camera.sensitivity)),
+                ]
+            })
+        } else if let Some(camera) = camera.as_orthographic_mut() {
+            camera::orthographic_camera_ui(ui, camera).then(|| {
+                vec![
+                    ("cam_speed", CVarValue::Float(camera.speed)),
+                    ("cam_sensitivity", CVarValue::Float(camera.sensitivity)),
+                ]
+            })
+        } else {
+            None
+        };
+
+        let mut cvars = self.cvars.lock().unwrap();
+        for (name, value) in changes.into_iter().flatten() {
+            if let Err(e) = cvars.set(name, value) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    fn properties_panel(
+        &mut self,
+        ui: &mut egui::Ui,
+        context: &glow::Context,
+        current_scene: &mut SceneNode,
+        editor_camera: &mut dyn Camera,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let mut remove_mesh = None;
+        let mut remove_sprite = None;
+        if self.selection.len() > 1 {
+            self.bulk_transform_editor(ui, current_scene);
+        } else if let Some(selected) = &mut self.selected_object {
+            match selected {
+                SelectedObject::StaticMesh(index) => {
+                    let index = *index;
+
+                    // Every socket on the other meshes, for the attachment picker
+                    let socket_targets: Vec<(usize, String, String)> = current_scene
+                        .static_meshes
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| *i != index)
+                        .flat_map(|(i, m)| {
+                            m.sockets
+                                .iter()
+                                .map(move |s| (i, m.name.clone(), s.name.clone()))
+                        })
+                        .collect();
+                    let mut attach_request = None;
+
+                    let mesh = current_scene
+                        .static_meshes
+                        .get_mut(index)
+                        .expect("Static mesh not found");
+
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Selected Static Mesh: {}", index));
+                        if ui.button("Remove").clicked() {
+                            remove_mesh = Some(index);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        // Adds space between the text and input
+                        ui.allocate_ui_with_layout(
+                            ui.available_size(),
+                            Layout::right_to_left(Align::Center),
+                            |ui| {
+                                ui.text_edit_singleline(&mut mesh.name);
+                            },
+                        );
+                    });
+
+                    ui.heading("Transform");
+                    let transform_before = MeshTransform::from_mesh(mesh);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Translate");
+                        // Adds space between the text and inputs
+                        ui.allocate_ui_with_layout(
+                            ui.available_size(),
+                            Layout::right_to_left(Align::Center),
+                            |ui| {
+                                // The inputs are in the reverse order
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_translation[2],
+                                    egui::DragValue::new(&mut mesh.translation.z)
+                                        .speed(0.05),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_translation[1],
+                                    egui::DragValue::new(&mut mesh.translation.y)
+                                        .speed(0.05),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_translation[0],
+                                    egui::DragValue::new(&mut mesh.translation.x)
+                                        .speed(0.05),
+                                );
+                            },
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Rotate");
+                        // Adds space between the text and inputs
+                        ui.allocate_ui_with_layout(
+                            ui.available_size(),
+                            Layout::right_to_left(Align::Center),
+                            |ui| {
+                                // The inputs are in the reverse order
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_rotation[2],
+                                    egui::DragValue::new(&mut mesh.rotation.z)
+                                        .speed(1.0),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_rotation[1],
+                                    egui::DragValue::new(&mut mesh.rotation.y)
+                                        .speed(1.0),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_rotation[0],
+                                    egui::DragValue::new(&mut mesh.rotation.x)
+                                        .speed(1.0),
+                                );
+                            },
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Scale");
+                        // Adds space between the text and inputs
+                        ui.allocate_ui_with_layout(
+                            ui.available_size(),
+                            Layout::right_to_left(Align::Center),
+                            |ui| {
+                                // The inputs are in the reverse order
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_scale[2],
+                                    egui::DragValue::new(&mut mesh.scale.z).speed(0.01),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_scale[1],
+                                    egui::DragValue::new(&mut mesh.scale.y).speed(0.01),
+                                );
+                                ui.add_enabled(
+                                    !mesh.constraints.lock_scale[0],
+                                    egui::DragValue::new(&mut mesh.scale.x).speed(0.01),
+                                );
+                            },
+                        );
+                    });
+
+                    mesh.constraints
+                        .constrain(transform_before, MeshTransform::from_mesh(mesh))
+                        .apply_to(mesh);
+                    let mut moved = MeshTransform::from_mesh(mesh) != transform_before;
+
+                    ui.collapsing("Constraints", |ui| {
+                        let constraints = &mut mesh.constraints;
+                        egui::Grid::new("TransformConstraints")
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                for (label, locks) in [
+                                    ("Lock translate", &mut constraints.lock_translation),
+                                    ("Lock rotate", &mut constraints.lock_rotation),
+                                    ("Lock scale", &mut constraints.lock_scale),
+                                ] {
+                                    ui.label(label);
+                                    for (lock, axis) in
+                                        locks.iter_mut().zip(["X", "Y", "Z"])
+                                    {
+                                        ui.checkbox(lock, axis);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        ui.checkbox(&mut constraints.uniform_scale, "🔗 Uniform scale")
+                            .on_hover_text(
+                                "Scaling one axis scales the others by the same factor",
+                            );
+                    });
+
+                    ui.collapsing("Level of detail", |ui| {
+                        ui.label(format!(
+                            "LOD 0: {} triangles",
+                            mesh.triangle_count(0)
+                        ));
+                        // Each level has to stay between its neighbours
+                        let distances: Vec<f32> =
+                            mesh.lods.iter().map(|lod| lod.distance).collect();
+                        let triangles: Vec<usize> = (1..=mesh.lods.len())
+                            .map(|level| mesh.triangle_count(level))
+                            .collect();
+                        for (i, lod) in mesh.lods.iter_mut().enumerate() {
+                            let min = if i == 0 { 0.0 } else { distances[i - 1] };
+                            let max =
+                                distances.get(i + 1).copied().unwrap_or(f32::MAX);
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "LOD {}: {} triangles{}, from",
+                                    i + 1,
+                                    triangles[i],
+                                    if lod.generated { " (generated)" } else { "" }
+                                ));
+                                ui.add(
+                                    egui::DragValue::new(&mut lod.distance)
+                                        .speed(0.5)
+                                        .range(min..=max)
+                                        .suffix(" m"),
+                                );
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Generate")
+                                .on_hover_text("Simplified copies of LOD 0")
+                                .clicked()
+                            {
+                                let generated = mesh.generate_lods(context, asset_loader);
+                                if let Err(e) = generated {
+                                    log::error!("{}", e);
+                                }
+                            }
+                            if ui.button("Clear").clicked() {
+                                mesh.clear_lods(context);
+                            }
+                        });
+                    });
+
+                    ui.collapsing("Materials", |ui| {
+                        material_slots_ui(ui, context, mesh, asset_loader);
+                    });
+
+                    ui.heading("Components");
+                    components_ui(ui, mesh);
+
+                    ui.heading("Sockets");
+
+                    let sockets_before = mesh.sockets.clone();
+                    let mut removed_socket = None;
+                    for (i, socket) in mesh.sockets.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut socket.name);
+                                if ui.button("Remove").clicked() {
+                                    removed_socket = Some(i);
+                                }
+                            });
+                            vector3_row(ui, "Translate", &mut socket.translation, 0.05);
+                            vector3_row(ui, "Rotate", &mut socket.rotation, 1.0);
+                            vector3_row(ui, "Scale", &mut socket.scale, 0.01);
+                        });
+                    }
+
+                    if let Some(i) = removed_socket {
+                        mesh.sockets.remove(i);
+                    }
+
+                    if ui.button("Add Socket").clicked() {
+                        let name = format!("Socket {}", mesh.sockets.len());
+                        mesh.sockets.push(Socket::new(name));
+                    }
+                    // What's attached to the sockets moves with them
+                    moved |= mesh.sockets != sockets_before;
+
+                    ui.heading("Attachment");
+
+                    let current = mesh
+                        .attachment
+                        .as_ref()
+                        .map(|a| (a.parent, a.socket.clone()));
+                    let label = |parent: usize, socket: &str| {
+                        let parent_name = socket_targets
+                            .iter()
+                            .find(|(i, _, _)| *i == parent)
+                            .map(|(_, name, _)| name.as_str())
+                            .unwrap_or("?");
+                        format!("{} / {}", parent_name, socket)
+                    };
+                    let selected_text = match &current {
+                        Some((parent, socket)) => label(*parent, socket),
+                        None => "None".to_string(),
+                    };
+
+                    egui::ComboBox::from_id_salt("Attachment")
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            if ui.selectable_label(current.is_none(), "None").clicked() {
+                                attach_request = Some(None);
+                            }
+                            for (parent, _, socket) in &socket_targets {
+                                let is_current =
+                                    current.as_ref() == Some(&(*parent, socket.clone()));
+                                if ui
+                                    .selectable_label(is_current, label(*parent, socket))
+                                    .clicked()
+                                {
+                                    attach_request = Some(Some((*parent, socket.clone())));
+                                }
+                            }
+                        });
+
+                    if moved {
+                        current_scene.mark_moved(index);
+                    }
+                    match attach_request {
+                        Some(Some((parent, socket))) => {
+                            if let Err(e) =
+                                current_scene.attach_to_socket(index, parent, &socket)
+                            {
+                                log::error!("{}", e);
+                            }
+                        }
+                        Some(None) => current_scene.detach(index),
+                        None => {}
+                    }
+                }
+                SelectedObject::DynamicMesh(index) => {
+                    ui.label(format!("Selected Dynamic Mesh: {}", index));
+                }
+                SelectedObject::PerspectiveCamera(index) => {
+                    ui.label(format!("Selected Perspective Camera: {}", index));
+                    if let Some(camera) = current_scene.perspective_cameras.get_mut(*index) {
+                        ui.horizontal(|ui| {
+                            ui.label("Name");
+                            ui.text_edit_singleline(&mut camera.name);
+                        });
+                        if camera::perspective_camera_ui(ui, camera) {
+                            camera.update_matrices();
+                        }
+                    }
+                }
+                SelectedObject::Sprite(index) => {
+                    let index = *index;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Selected Sprite: {}", index));
+                        if ui.button("Remove").clicked() {
+                            remove_sprite = Some(index);
+                        }
+                    });
+                    if let Some(sprite) = current_scene.sprites.get_mut(index) {
+                        sprites::sprite_ui(ui, sprite, &mut self.sprite_atlas_path);
+                    }
+                } // Add more cases as needed
+            }
+        } else {
+            ui.label("No object selected");
+            ui.separator();
+            self.editor_camera_properties(ui, editor_camera);
+        }
+
+        let remove = remove_mesh
+            .map(SelectedObject::StaticMesh)
+            .or(remove_sprite.map(SelectedObject::Sprite));
+        if let Some(object) = remove {
+            self.request_delete(context, current_scene, asset_loader, vec![object]);
+        }
+    }
+
+    fn object_command(
+        &mut self,
+        context: &glow::Context,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+        command: ObjectCommand,
+    ) {
+        self.commit_transform_edit(current_scene);
+        let objects: Vec<SelectedObject> = match self.selection.is_empty() {
+            true => self.selected_object.into_iter().collect(),
+            false => self
+                .selection
+                .iter()
+                .map(|&index| SelectedObject::StaticMesh(index))
+                .collect(),
+        };
+        match command {
+            ObjectCommand::Duplicate => self.duplicate_meshes(context, current_scene, asset_loader),
+            ObjectCommand::Delete => {
+                self.request_delete(context, current_scene, asset_loader, objects)
+            }
+        }
+    }
+
+    // Copies of the selected static meshes, right after the last mesh. They become the selection.
+    fn duplicate_meshes(
+        &mut self,
+        context: &glow::Context,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let mut originals = self.selection.clone();
+        originals.sort_unstable();
+        originals.dedup();
+        originals.retain(|&index| index < current_scene.static_meshes.len());
+        if originals.is_empty() {
+            return;
+        }
+
+        let first = current_scene.static_meshes.len();
+        let mut copied = Vec::new(); // The original of every copy
+        for &index in &originals {
+            let original = &current_scene.static_meshes[index];
+            let mut copy = match original.duplicate(context, asset_loader) {
+                Ok(copy) => copy,
+                Err(e) => {
+                    log::error!("Failed to duplicate {}: {}", original.name, e);
+                    continue;
+                }
+            };
+            let names: Vec<String> =
+                current_scene.static_meshes.iter().map(|mesh| mesh.name.clone()).collect();
+            copy.name = unique_name(&original.name, &names);
+            asset_loader.retain(AssetHandle::Mesh(copy.handle));
+            if let Some((handle, _)) = copy.base_color_map {
+                asset_loader.retain(AssetHandle::Texture(handle));
+            }
+            current_scene.add_static_mesh(copy);
+            copied.push(index);
+        }
+
+        // Copies of attached meshes whose parent was copied too hang off the parent's copy
+        let copies: Vec<usize> = (first..current_scene.static_meshes.len()).collect();
+        for &copy in &copies {
+            let Some(attachment) = current_scene.static_meshes[copy].attachment.as_mut() else {
+                continue;
+            };
+            if let Some(position) = copied.iter().position(|&index| index == attachment.parent) {
+                attachment.parent = first + position;
+            }
+        }
+        if copies.is_empty() {
+            return;
+        }
+
+        let name = match copies.len() {
+            1 => format!("Duplicate {}", current_scene.static_meshes[first].name),
+            count => format!("Duplicate {} meshes", count),
+        };
+        self.undo_stack
+            .push(Box::new(StaticMeshesEdit::added(name, current_scene, copies.clone())));
+        self.selected_object = copies.last().map(|&index| SelectedObject::StaticMesh(index));
+        self.selection = copies;
+    }
+
+    fn step_history(&mut self, current_scene: &mut SceneNode, redo: bool) {
+        let count = current_scene.static_meshes.len();
+        match redo {
+            true => self.undo_stack.redo(current_scene),
+            false => self.undo_stack.undo(current_scene),
+        }
+        // Meshes were added or removed, the selected indices may be someone else's now
+        if current_scene.static_meshes.len() != count {
+            self.selection.clear();
+            self.selected_object = None;
+            self.dropped_textures.clear();
+        }
+    }
+
+    // Asks first when the preferences say so
+    fn request_delete(
+        &mut self,
+        context: &glow::Context,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+        objects: Vec<SelectedObject>,
+    ) {
+        if objects.is_empty() {
+            return;
+        }
+        if self.preferences.confirm_delete {
+            self.pending_delete = objects;
+        } else {
+            self.delete_objects(context, current_scene, asset_loader, objects);
+        }
+    }
+
+    // Static meshes are kept in the history so the delete can be undone, sprites are gone for good
+    fn delete_objects(
+        &mut self,
+        context: &glow::Context,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+        objects: Vec<SelectedObject>,
+    ) {
+        let mut meshes = Vec::new();
+        let mut sprites = Vec::new();
+        for object in objects {
+            match object {
+                SelectedObject::StaticMesh(index) => meshes.push(index),
+                SelectedObject::Sprite(index) => sprites.push(index),
+                _ => {}
+            }
+        }
+
+        if !meshes.is_empty() {
+            let name = match meshes.as_slice() {
+                [index] => match current_scene.static_meshes.get(*index) {
+                    Some(mesh) => format!("Delete {}", mesh.name),
+                    None => return,
+                },
+                _ => format!("Delete {} meshes", meshes.len()),
+            };
+            let edit = StaticMeshesEdit::delete(name, current_scene, meshes);
+            self.undo_stack.push(Box::new(edit));
+            // Indices moved, so the selection would point at the wrong meshes
+            self.selection.clear();
+            self.dropped_textures.clear();
+        }
+
+        sprites.sort_unstable();
+        sprites.dedup();
+        for index in sprites.into_iter().rev() {
+            if index >= current_scene.sprites.len() {
+                continue;
+            }
+            let sprite = current_scene.sprites.remove(index);
+            if let Some(handle) = sprite.texture {
+                asset_loader.release(AssetHandle::Texture(handle));
+            }
+        }
+        self.selected_object = None;
+        self.undo_stack.free_discarded(context, asset_loader);
+    }
+
+    fn preferences_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("Preferences")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if self.preferences.ui(ui) {
+                    self.preferences.apply(&mut self.cvars.lock().unwrap());
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        match self.preferences.save() {
+                            Ok(()) => log::info!("Saved preferences"),
+                            Err(e) => log::error!("{}", e),
+                        }
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        // Where the window is isn't something you'd set here
+                        let window = std::mem::take(&mut self.preferences.window);
+                        self.preferences = EditorPreferences { window, ..Default::default() };
+                        self.preferences.apply(&mut self.cvars.lock().unwrap());
+                    }
+                });
+            });
+
+        // Closing the window keeps the changes for the next start too
+        if !open {
+            self.preferences_open = false;
+            if let Err(e) = self.preferences.save() {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    // Copies of what can be edited and saved, kept apart so the files they came from aren't
+    // overwritten behind the user's back
+    fn autosave(&self, scene: &SceneNode) {
+        let directory = Path::new(AUTOSAVE_DIRECTORY);
+        let tilemaps = directory.join(TILEMAP_DIRECTORY);
+        if let Err(e) = std::fs::create_dir_all(&tilemaps) {
+            log::error!("Failed to create {:?}: {}", tilemaps, e);
+            return;
+        }
+
+        let mut results = vec![
+            self.cvars.lock().unwrap().save(&directory.join(CVARS_CONFIG_PATH)),
+            self.dialogue.save(&directory.join("dialogue.ron")),
+            self.particle_system.effect.save(&directory.join("particle_effect.ron")),
+        ];
+        for (i, tilemap) in scene.tilemaps.iter().enumerate() {
+            results.push(tilemap.save(&tilemaps.join(format!("{}_{}.ron", i, tilemap.name))));
+        }
+
+        let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+        if errors.is_empty() {
+            log::info!("Autosaved to {:?}", directory);
+        }
+        for e in errors {
+            log::error!("Autosave failed: {}", e);
+        }
+    }
+
+    fn content_browser_panel(
+        &mut self,
+        ui: &mut egui::Ui,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        ui.collapsing("Loaded", |ui| content_browser_assets(ui, asset_loader));
+
+        let browser = self
+            .content_browser
+            .show(ui, &mut self.thumbnails, &mut self.scene_previews);
+        match browser {
+            Some(BrowserAction::Open(path, kind)) => {
+                self.open_asset(&path, kind, current_scene, asset_loader)
+            }
+            Some(BrowserAction::Move(from, to)) => {
+                self.move_asset(&from, &to, current_scene, asset_loader)
+            }
+            Some(BrowserAction::Delete(path)) => {
+                let uses = content_browser::asset_uses(&path, asset_loader);
+                if uses > 0 || self.preferences.confirm_delete {
+                    self.pending_asset_delete = Some((path, uses));
+                } else if let Err(e) = content_browser::delete_asset(&path) {
+                    log::error!("{}", e);
+                }
+            }
+            None => {}
+        }
+    }
+
+    // The open scene's tilemaps and editors follow a moved file, the browser does the saved ones
+    fn move_asset(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        if let Err(e) = content_browser::move_asset(from, to, asset_loader) {
+            log::error!("{}", e);
+            return;
+        }
+        log::info!("Moved {:?} to {:?}", from, to);
+
+        for tilemap in &mut current_scene.tilemaps {
+            if let Some(atlas) = content_browser::moved_path(&tilemap.atlas, from, to) {
+                tilemap.atlas = atlas;
+            }
+        }
+        for path in [
+            &mut self.particle_path,
+            &mut self.dialogue_path,
+            &mut self.tilemap_path,
+        ] {
+            if let Some(moved) = content_browser::moved_path(Path::new(path.as_str()), from, to) {
+                *path = moved.to_string_lossy().to_string();
+            }
+        }
+    }
+
+    // Meshes and textures are loaded for the Add menu, the rest open in their editors
+    fn open_asset(
+        &mut self,
+        path: &Path,
+        kind: AssetKind,
+        current_scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let path_text = path.to_string_lossy().to_string();
+        match kind {
+            AssetKind::Mesh => {
+                asset_loader.request_mesh(path, name);
+            }
+            AssetKind::Texture => {
+                asset_loader.request_texture(path, name);
+            }
+            AssetKind::ParticleEffect => match ParticleEffect::load(path) {
+                Ok(effect) => {
+                    log::info!("Loaded particle effect: {}", path_text);
+                    self.particle_system.effect = effect;
+                    self.particle_system.restart();
+                    self.particle_path = path_text;
+                }
+                Err(e) => log::error!("{}", e),
+            },
+            AssetKind::Dialogue => match DialogueGraph::load(path) {
+                Ok(graph) => {
+                    log::info!("Loaded dialogue: {}", path_text);
+                    self.dialogue = graph;
+                    self.dialogue_path = path_text;
+                }
+                Err(e) => log::error!("{}", e),
+            },
+            AssetKind::Tilemap => match Tilemap::load(path) {
+                Ok(mut tilemap) => {
+                    tilemap.request_atlas(asset_loader);
+                    current_scene.tilemaps.push(tilemap);
+                    self.tilemap_index = current_scene.tilemaps.len() - 1;
+                    log::info!("Loaded tilemap: {}", path_text);
+                    self.tilemap_path = path_text;
+                }
+                Err(e) => log::error!("{}", e),
+            },
+            AssetKind::Scene => {
+                self.scene_tab_action = Some(SceneTabAction::Open(path.to_path_buf()))
+            }
+            AssetKind::Shader | AssetKind::Script | AssetKind::Other => {
+                log::info!("Nothing opens {}", path_text);
+            }
+        }
+    }
+
+    fn console_panel(&mut self, ui: &mut egui::Ui, asset_loader: &AssetLoader) {
+        use egui::{Key, RichText, ScrollArea, TextEdit};
+
+        ui.horizontal(|ui| {
+            ui.label("Show:");
+            for level in log::Level::iter() {
+                let shown = &mut self.shown_log_levels[level as usize - 1];
+                ui.checkbox(shown, level.as_str());
+            }
+        });
+
+        // Output area: scrollable multiline, read-only
+        ScrollArea::vertical()
+            .max_height(100.0)
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.set_min_width(ui.available_width());
+                for line in &self.terminal_lines {
+                    match line.level {
+                        Some(level) => {
+                            if !self.shown_log_levels[level as usize - 1] {
+                                continue;
+                            }
+
+                            let text =
+                                RichText::new(format!("[{}] {}", level, line.text)).monospace();
+                            let text = match level {
+                                log::Level::Error => text.color(egui::Color32::RED),
+                                log::Level::Warn => text.color(egui::Color32::YELLOW),
+                                log::Level::Info => text,
+                                _ => text.weak(),
+                            };
+                            ui.label(text);
+                        }
+                        None => {
+                            ui.monospace(&line.text);
+                        }
+                    }
+                }
+            });
+
+        // Input area: single-line editable input
+        let enter_pressed = {
+            let input = &mut self.terminal_input;
+            ui.add(TextEdit::singleline(input).hint_text("Enter command"))
+                .lost_focus()
+                && ui.input(|i: &egui::InputState| i.key_pressed(Key::Enter))
+        };
+
+        if enter_pressed {
+            let mut input = self.terminal_input.clone();
+            let command = input.trim();
+            if !command.is_empty() {
+                self.append_terminal(format!("> {}", command));
+                // Answered here, the command thread can't see the loaded assets
+                if command == "residency" {
+                    self.append_terminal(asset_loader.residency());
+                } else {
+                    let _ = self.command_tx.send(command.to_string());
+                }
+                self.tutorial.notify("console_command");
+                input.clear();
+            }
         }
     }
 
-    pub fn get_viewport(&self, window: &Window) -> Option<Viewport> {
-        if let Some(viewport) = &self.viewport {
-            let window_height = window.inner_size().height;
-            Some(Viewport::new(
-                viewport.x,
-                // Reverse the y since OpenGL uses a different origin
-                window_height as i32 - viewport.y - viewport.height,
-                viewport.width,
-                viewport.height,
-            ))
+    fn ide_panel(&mut self, ui: &mut egui::Ui, current_scene: &SceneNode) {
+        use egui::TextEdit;
+
+        if self.selected_script == None {
+            let mut file_content = String::from("fn main() {\n    println!(\"Hello World!\");\n}");
+            ui.add(
+                TextEdit::multiline(&mut file_content)
+                    .font(egui::TextStyle::Monospace)
+                    .code_editor()
+                    .desired_width(ui.available_width())
+                    .desired_rows(20),
+            );
+
+            // Save button
+            if ui.button("Save").clicked() {
+                match std::fs::File::create_new("scripts/script1.rs") {
+                    Ok(mut file) => {
+                        log::info!("Saving script ...");
+                        match file.write_all(file_content.as_bytes()) {
+                            Ok(_) => {
+                                log::info!("Saved script!");
+                            }
+                            Err(e) => {
+                                log::error!("Failed to save script: {}", e);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to create script: {}", e);
+                    }
+                }
+            }
         } else {
-            None
+            let script_path = current_scene
+                .scripts
+                .get(self.selected_script.unwrap().clone())
+                .unwrap();
+            let mut file_content = std::fs::read_to_string(script_path).unwrap();
+            ui.add(
+                TextEdit::multiline(&mut file_content)
+                    .font(egui::TextStyle::Monospace)
+                    .code_editor()
+                    .desired_width(ui.available_width())
+                    .desired_rows(20),
+            );
+
+            // Save button
+            if ui.button("Save").clicked() {
+                let path = script_path.clone();
+                let data = file_content.clone();
+                rayon::spawn(move || {
+                    if let Err(e) = std::fs::write(&path, data) {
+                        log::error!("Error saving {}: {}", path, e);
+                    } else {
+                        log::info!("Saved script: {}", path);
+                    }
+                });
+            }
         }
     }
 
@@ -161,7 +3889,7 @@ impl Gui {
         active_camera_type: &mut CameraType,
         camera: &mut dyn Camera,
         scene_graph: &mut SceneGraph,
-        asset_loader: &AssetLoader,
+        asset_loader: &mut AssetLoader,
         delta_time: f64,
     ) -> egui::FullOutput {
         // Calculate the delta time
@@ -180,339 +3908,421 @@ impl Gui {
             self.frame_count = 0;
         }
 
+        // The axis gizmo switched to the orthographic camera last frame, it's the one here now
+        if let Some((view, target)) = self.axis_view.take() {
+            camera::look_from(camera, view, target, AXIS_VIEW_DISTANCE);
+        }
+
+        if let Some(project) = self.project_to_open.take() {
+            self.open_project(&project, context, scene_graph, asset_loader);
+        }
+        self.update_scene_tabs(context, scene_graph, asset_loader);
+        if let Some(scene) = scene_graph.take_switch_request() {
+            self.request_scene_switch(scene, scene_graph);
+        }
+        if let Some(action) = self.scene_tab_action.take() {
+            self.scene_tab_action(action, context, active_camera_type, scene_graph, asset_loader);
+        }
+        let scene_names = scene_names(scene_graph);
+        let open_scene = scene_graph.current_scene;
+        self.thumbnails.update(ctx, context, asset_loader);
+
         let current_scene = scene_graph.current_scene_mut().unwrap();
+        self.import_dropped_files(&*camera, asset_loader);
+        self.place_dropped_assets(context, current_scene, asset_loader);
+
+        if let Some(interval) = self.preferences.autosave_interval() {
+            if self.last_autosave.elapsed() >= interval {
+                self.autosave(current_scene);
+                self.last_autosave = Instant::now();
+            }
+        }
+
+        if self.particle_preview {
+            let delta_time = self.photo_mode.simulation_delta(delta_time as f32);
+            self.particle_system.update(delta_time);
+        }
 
         while let Ok(line) = self.command_result_rx.try_recv() {
             self.append_terminal(line);
         }
 
-        ctx.run(raw_input, |ctx| {
-            egui::SidePanel::left("Hierarchy")
-                .min_width(150.0)
-                .resizable(true)
-                .show(ctx, |ui| {
-                    ui.collapsing(current_scene.name.clone(), |ui| {
-                        ui.collapsing("Static Meshes", |ui| {
-                            for (i, sm) in current_scene.static_meshes.iter().enumerate() {
-                                if ui.button(sm.name.clone()).clicked() {
-                                    self.selected_object = Some(SelectedObject::StaticMesh(i))
-                                }
-                            }
-                        });
-
-                        ui.collapsing("Dynamic Meshes", |ui| {
-                            for sm in &current_scene.dynamic_meshes {
-                                ui.label(sm.name.clone());
-                            }
-                        });
+        while let Ok(line) = self.log_rx.try_recv() {
+            self.push_console_line(Some(line.level), line.message);
+        }
 
-                        ui.collapsing("Perspective Cameras", |ui| {
-                            for sm in &current_scene.perspective_cameras {
-                                ui.label(sm.name.clone());
-                            }
-                        });
+        let editor_fov = self.cvars.lock().unwrap().get_float("r_fov");
+        let panels_visible = !self.photo_mode.ui_hidden();
 
-                        ui.collapsing("Textures", |ui| {
-                            for t in &current_scene.textures {
-                                ui.label(t.name.clone());
-                            }
-                        });
+        ctx.run(raw_input, |ctx| {
+            if ctx.style().visuals.dark_mode != (self.preferences.theme == Theme::Dark) {
+                ctx.set_visuals(self.preferences.theme.visuals());
+            }
 
-                        ui.collapsing("Materials", |ui| {
-                            for m in &current_scene.materials {
-                                ui.label(m.name.clone());
-                            }
-                        });
+            // The click confirming a transform mustn't select something in the viewport too
+            let transforming = self.modal_transform.is_some();
 
-                        ui.collapsing("Scripts", |ui| {
-                            for s in &current_scene.scripts {
-                                ui.label(s.clone());
-                            }
-                        });
-                    });
+            // A modal transform takes over the keyboard and mouse until it's confirmed or cancelled
+            if let Some(mut modal) = self.modal_transform.take() {
+                let state = ctx.input(|input| {
+                    modal.update(input, current_scene, self.gizmo_space, &*camera)
+                });
+                match state {
+                    ModalState::Active => self.modal_transform = Some(modal),
+                    ModalState::Confirmed(edit) => self.undo_stack.push(Box::new(edit)),
+                    ModalState::Cancelled => {}
+                }
+            } else if !ctx.wants_keyboard_input() && !self.photo_mode.is_active() {
+                let keys = &self.modal_keys;
+                let mode = ctx.input(|input| {
+                    if !input.modifiers.is_none() {
+                        None
+                    } else if input.key_pressed(keys.translate) {
+                        Some(GizmoMode::Translate)
+                    } else if input.key_pressed(keys.rotate) {
+                        Some(GizmoMode::Rotate)
+                    } else if input.key_pressed(keys.scale) {
+                        Some(GizmoMode::Scale)
+                    } else {
+                        None
+                    }
                 });
+                if let Some(mode) = mode {
+                    self.commit_transform_edit(current_scene);
+                    self.modal_transform =
+                        ModalTransform::begin(mode, current_scene, &self.selection, self.pivot);
+                }
+            }
 
-            egui::TopBottomPanel::bottom("Bottom panel")
-                .min_height(105.0)
-                .resizable(true)
-                .show(ctx, |ui| {
-                    ui.horizontal(|ui| {
-                        ui.visuals_mut().widgets.inactive.corner_radius = CornerRadius::same(0);
-                        ui.visuals_mut().widgets.hovered.corner_radius = CornerRadius::same(5);
-                        ui.visuals_mut().widgets.active.corner_radius = CornerRadius::same(5);
-                        ui.selectable_value(&mut self.choice, Choice::Console, "Console");
-                        ui.selectable_value(
-                            &mut self.choice,
-                            Choice::ContentBrowser,
-                            "Content Browser",
-                        );
-                        if let Some(script) = self.selected_script {
-                            let mut x = current_scene.scripts.get(script).unwrap().clone();
-                            x.push_str(".rs");
-                            ui.selectable_value(&mut self.choice, Choice::Ide, x);
-                        } else {
-                            ui.selectable_value(&mut self.choice, Choice::Ide, "IDE");
-                        }
-                    });
+            // Text fields have their own undo
+            if self.modal_transform.is_none() && !ctx.wants_keyboard_input() {
+                let (redo, undo) = ctx.input_mut(|input| {
+                    // Redo first, the undo shortcut would also match with shift held
+                    let redo = input.consume_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                        Key::Z,
+                    )) || input.consume_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND,
+                        Key::Y,
+                    ));
+                    let undo = input.consume_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND,
+                        Key::Z,
+                    ));
+                    (redo, undo)
+                });
+                if undo || redo {
+                    self.commit_transform_edit(current_scene);
+                }
+                if undo {
+                    self.step_history(current_scene, false);
+                }
+                if redo {
+                    self.step_history(current_scene, true);
+                }
 
-                    ui.separator();
+                let save = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, Key::S);
+                if ctx.input_mut(|input| input.consume_shortcut(&save)) {
+                    self.scene_tab_action = Some(SceneTabAction::Save(None));
+                }
 
-                    if self.choice == Choice::Console {
-                        use egui::{Key, ScrollArea, TextEdit};
+                let fullscreen = egui::KeyboardShortcut::new(egui::Modifiers::NONE, Key::F11);
+                if ctx.input_mut(|input| input.consume_shortcut(&fullscreen)) {
+                    let mode = WindowMode::from_cvars(&self.cvars.lock().unwrap());
+                    self.set_window_mode(mode.toggled());
+                }
 
-                        // Output area: scrollable multiline, read-only
-                        ScrollArea::vertical()
-                            .max_height(100.0)
-                            .auto_shrink([false; 2])
-                            .stick_to_bottom(true)
-                            .show(ui, |ui| {
-                                ui.set_min_width(ui.available_width());
-                                for line in &self.terminal_lines {
-                                    ui.monospace(line);
-                                }
-                            });
+                let command = ctx.input_mut(|input| {
+                    let duplicate = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, Key::D);
+                    if input.consume_shortcut(&duplicate) {
+                        Some(ObjectCommand::Duplicate)
+                    } else if input.key_pressed(Key::Delete) {
+                        Some(ObjectCommand::Delete)
+                    } else {
+                        None
+                    }
+                });
+                if !self.photo_mode.is_active() && command.is_some() {
+                    self.object_command = command;
+                }
 
-                        // Input area: single-line editable input
-                        let enter_pressed = {
-                            let input = &mut self.terminal_input;
-                            ui.add(TextEdit::singleline(input).hint_text("Enter command"))
-                                .lost_focus()
-                                && ui.input(|i: &egui::InputState| i.key_pressed(Key::Enter))
+                // Number keys like Blender's numpad, 5 goes back and forth to perspective
+                let axis_view = ctx.input_mut(|input| {
+                    AxisView::ALL
+                        .into_iter()
+                        .find(|axis| input.consume_shortcut(&axis.shortcut()))
+                });
+                let toggle = egui::KeyboardShortcut::new(egui::Modifiers::NONE, Key::Num5);
+                let toggle = ctx.input_mut(|input| input.consume_shortcut(&toggle));
+                if !self.photo_mode.is_active() {
+                    if let Some(axis) = axis_view {
+                        self.look_from_axis(axis, &*camera, current_scene, active_camera_type);
+                    } else if toggle {
+                        *active_camera_type = match active_camera_type {
+                            CameraType::Perspective => CameraType::Orthographic,
+                            CameraType::Orthographic => CameraType::Perspective,
                         };
+                    }
+                }
+            }
 
-                        if enter_pressed {
-                            let mut input = self.terminal_input.clone();
-                            let command = input.trim();
-                            if !command.is_empty() {
-                                self.append_terminal(format!("> {}", command));
-                                let _ = self.command_tx.send(command.to_string());
-                                input.clear();
-                            }
-                        }
-                    } else if self.choice == Choice::Ide {
-                        use egui::TextEdit;
+            // The panels are laid out by the dock, which can hold several of the same kind
+            let mut dock = std::mem::take(&mut self.dock);
+            dock.show(ctx, panels_visible, |ui, tab| {
+                self.tutorial.register_region(tab.kind.title(), ui.max_rect());
+                match tab.kind {
+                    PanelKind::Hierarchy => self.hierarchy_panel(ui, current_scene, asset_loader),
+                    PanelKind::Properties => {
+                        self.properties_panel(ui, context, current_scene, camera, asset_loader)
+                    }
+                    PanelKind::Console => self.console_panel(ui, asset_loader),
+                    PanelKind::ContentBrowser => {
+                        self.content_browser_panel(ui, current_scene, asset_loader)
+                    }
+                    PanelKind::Ide => self.ide_panel(ui, current_scene),
+                    PanelKind::Settings => self.settings_panel(ui),
+                    PanelKind::Dialogue => self.dialogue_panel(ui),
+                    PanelKind::Particles => self.particle_panel(ui),
+                    PanelKind::Profiler => self.profiler_panel(ui),
+                }
+            });
+            self.dock = dock;
 
-                        if self.selected_script == None {
-                            let mut file_content =
-                                String::from("fn main() {\n    println!(\"Hello World!\");\n}");
-                            ui.add(
-                                TextEdit::multiline(&mut file_content)
-                                    .font(egui::TextStyle::Monospace)
-                                    .code_editor()
-                                    .desired_width(ui.available_width())
-                                    .desired_rows(20),
-                            );
+            if let Some(command) = self.object_command.take() {
+                self.object_command(context, current_scene, asset_loader, command);
+            }
+            if std::mem::take(&mut self.play_toggled) {
+                let batching = self.cvars.lock().unwrap().get_bool("r_static_batching");
+                match self.playing && batching {
+                    true => current_scene.build_static_batches(context, asset_loader),
+                    false => current_scene.clear_static_batches(context),
+                }
+            }
+            self.undo_stack.free_discarded(context, asset_loader);
 
-                            // Save button
-                            if ui.button("Save").clicked() {
-                                match std::fs::File::create_new("scripts/script1.rs") {
-                                    Ok(mut file) => {
-                                        self.append_terminal("Saving script ...");
-                                        match file.write_all(file_content.as_bytes()) {
-                                            Ok(_) => {
-                                                self.append_terminal("Saved script!");
-                                            }
-                                            Err(e) => {
-                                                self.append_terminal(format!(
-                                                    "ERROR: Failed to Saved script!\n\t{}",
-                                                    e
-                                                ));
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Error: {}", e);
-                                    }
-                                }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                // Made before the toolbar so its buttons stay on top
+                let viewport_response = ui.interact(
+                    ui.max_rect(),
+                    ui.id().with("Viewport selection"),
+                    egui::Sense::click_and_drag(),
+                );
+
+                egui::TopBottomPanel::top("Toolbar")
+                    .resizable(false)
+                    .show_animated_inside(ui, panels_visible, |ui| {
+                        self.tutorial.register_region("Toolbar", ui.max_rect());
+
+                        ui.horizontal(|ui| {
+                            ui.label("Tools:");
+
+                            let play_label = if self.playing { "⏹ Stop" } else { "▶ Play" };
+                            if ui.button(play_label).clicked() {
+                                self.playing = !self.playing;
+                                self.play_toggled = true;
                             }
-                        } else {
-                            let script_path = current_scene
-                                .scripts
-                                .get(self.selected_script.unwrap().clone())
-                                .unwrap();
-                            let mut file_content = std::fs::read_to_string(script_path).unwrap();
-                            ui.add(
-                                TextEdit::multiline(&mut file_content)
-                                    .font(egui::TextStyle::Monospace)
-                                    .code_editor()
-                                    .desired_width(ui.available_width())
-                                    .desired_rows(20),
-                            );
 
-                            // Save button
-                            if ui.button("Save").clicked() {
-                                let path = script_path.clone();
-                                let data = file_content.clone();
-                                rayon::spawn(move || {
-                                    if let Err(e) = std::fs::write(&path, data) {
-                                        eprintln!("Error saving {}: {}", path, e);
-                                    } else {
-                                        println!("Saved script: {}", path);
-                                    }
-                                });
+                            let record_label = if self.recording { "⏹ Stop" } else { "⏺ Record" };
+                            if ui.button(record_label).clicked() {
+                                self.recording = !self.recording;
                             }
-                        }
-                    } else {
-                        ui.heading("Content Browser");
 
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                egui::Image::new(egui::include_image!("../assets/texture.jpg"))
-                                    .max_width(200.0)
-                                    .corner_radius(10),
-                            );
-                        });
-                    }
+                            if ui.button("📷 Photo").clicked() {
+                                self.photo_mode.enter(camera, editor_fov);
+                            }
 
-                    // To allow for resizing
-                    ui.allocate_space(ui.available_size());
-                });
+                            if ui.button("🚀 Projects").clicked() {
+                                self.launcher_open = true;
+                            }
 
-            egui::SidePanel::right("Properties")
-                .min_width(220.0)
-                .resizable(true)
-                .show(ctx, |ui| {
-                    if let Some(selected) = &mut self.selected_object {
-                        match selected {
-                            SelectedObject::StaticMesh(index) => {
-                                let mesh = current_scene
-                                    .static_meshes
-                                    .get_mut(*index)
-                                    .expect("Static mesh not found");
-
-                                ui.label(format!("Selected Static Mesh: {}", index));
-                                ui.horizontal(|ui| {
-                                    ui.label("Name");
-                                    // Adds space between the text and input
-                                    ui.allocate_ui_with_layout(
-                                        ui.available_size(),
-                                        Layout::right_to_left(Align::Center),
-                                        |ui| {
-                                            ui.text_edit_singleline(&mut mesh.name);
-                                        },
-                                    );
+                            ui.menu_button("File", |ui| {
+                                ui.menu_button("New Scene", |ui| {
+                                    for template in SceneTemplate::ALL {
+                                        if ui
+                                            .button(template.label())
+                                            .on_hover_text(template.description())
+                                            .clicked()
+                                        {
+                                            self.scene_tab_action =
+                                                Some(SceneTabAction::New(template));
+                                            ui.close_menu();
+                                        }
+                                    }
                                 });
-
-                                ui.heading("Transform");
-
-                                ui.horizontal(|ui| {
-                                    ui.label("Translate");
-                                    // Adds space between the text and inputs
-                                    ui.allocate_ui_with_layout(
-                                        ui.available_size(),
-                                        Layout::right_to_left(Align::Center),
-                                        |ui| {
-                                            // The inputs are in the reverse order
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.translation.z)
-                                                    .speed(0.05),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.translation.y)
-                                                    .speed(0.05),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.translation.x)
-                                                    .speed(0.05),
-                                            );
-                                        },
-                                    );
+                                ui.menu_button("Open Scene", |ui| {
+                                    let scenes = saved_scenes();
+                                    if scenes.is_empty() {
+                                        ui.weak(format!("No scenes in {}", SCENE_DIRECTORY));
+                                    }
+                                    for path in scenes {
+                                        let name = path.file_stem().unwrap_or_default();
+                                        let button = ui.button(name.to_string_lossy());
+                                        let button = button
+                                            .on_hover_ui(|ui| self.scene_previews.ui(ui, &path));
+                                        if button.clicked() {
+                                            self.scene_tab_action =
+                                                Some(SceneTabAction::Open(path));
+                                            ui.close_menu();
+                                        }
+                                    }
                                 });
+                                ui.separator();
+                                if ui.button("Save Scene").on_hover_text("Ctrl+S").clicked() {
+                                    self.scene_tab_action = Some(SceneTabAction::Save(None));
+                                    ui.close_menu();
+                                }
+                                if ui.button("Save Scene As...").clicked() {
+                                    let file_name =
+                                        format!("{}.{}", current_scene.name, SCENE_EXTENSION);
+                                    let path = Path::new(SCENE_DIRECTORY).join(file_name);
+                                    self.save_scene_as = Some(path.to_string_lossy().to_string());
+                                    ui.close_menu();
+                                }
+                            });
 
-                                ui.horizontal(|ui| {
-                                    ui.label("Rotate");
-                                    // Adds space between the text and inputs
-                                    ui.allocate_ui_with_layout(
-                                        ui.available_size(),
-                                        Layout::right_to_left(Align::Center),
-                                        |ui| {
-                                            // The inputs are in the reverse order
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.rotation.z)
-                                                    .speed(1.0),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.rotation.y)
-                                                    .speed(1.0),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.rotation.x)
-                                                    .speed(1.0),
-                                            );
-                                        },
-                                    );
-                                });
+                            ui.menu_button("🗔 Panels", |ui| self.dock.panels_menu(ui));
+                            ui.menu_button("🖵 Window", |ui| self.window_mode_menu(ui));
 
-                                ui.horizontal(|ui| {
-                                    ui.label("Scale");
-                                    // Adds space between the text and inputs
-                                    ui.allocate_ui_with_layout(
-                                        ui.available_size(),
-                                        Layout::right_to_left(Align::Center),
-                                        |ui| {
-                                            // The inputs are in the reverse order
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.scale.z).speed(0.01),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.scale.y).speed(0.01),
-                                            );
-                                            ui.add(
-                                                egui::DragValue::new(&mut mesh.scale.x).speed(0.01),
-                                            );
-                                        },
-                                    );
-                                });
-                            }
-                            SelectedObject::DynamicMesh(index) => {
-                                ui.label(format!("Selected Dynamic Mesh: {}", index));
+                            if ui.button("📦 Export Game").clicked() {
+                                let environment = self
+                                    .cvars
+                                    .lock()
+                                    .unwrap()
+                                    .get("r_environment")
+                                    .map(|value| value.to_string())
+                                    .filter(|path| !path.is_empty());
+                                // The game starts from the scene's active camera if it has one
+                                let start_camera: &dyn Camera = match current_scene.active_camera()
+                                {
+                                    Some(active) => active,
+                                    None => camera,
+                                };
+                                match runtime::export_game(
+                                    &current_scene.name,
+                                    current_scene,
+                                    start_camera,
+                                    environment.as_deref().map(std::path::Path::new),
+                                    asset_loader,
+                                    std::path::Path::new(runtime::EXPORT_DIRECTORY),
+                                ) {
+                                    Ok(()) => log::info!(
+                                        "Exported the game to {}",
+                                        runtime::EXPORT_DIRECTORY
+                                    ),
+                                    Err(e) => log::error!("{}", e),
+                                }
                             }
-                            SelectedObject::PerspectiveCamera(index) => {
-                                ui.label(format!("Selected Perspective Camera: {}", index));
-                            } // Add more cases as needed
-                        }
-                    } else {
-                        ui.label("No object selected");
-                    }
-                });
 
-            egui::CentralPanel::default().show(ctx, |ui| {
-                egui::TopBottomPanel::top("Toolbar")
-                    .resizable(false)
-                    .show_inside(ui, |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Tools:");
+                            ui.menu_button("Edit", |ui| {
+                                let undo_label = match self.undo_stack.undo_name() {
+                                    Some(name) => format!("Undo {}", name),
+                                    None => "Undo".to_string(),
+                                };
+                                let redo_label = match self.undo_stack.redo_name() {
+                                    Some(name) => format!("Redo {}", name),
+                                    None => "Redo".to_string(),
+                                };
+                                let can_undo = self.undo_stack.undo_name().is_some();
+                                if ui
+                                    .add_enabled(can_undo, egui::Button::new(undo_label))
+                                    .clicked()
+                                {
+                                    self.step_history(current_scene, false);
+                                    ui.close_menu();
+                                }
+                                let can_redo = self.undo_stack.redo_name().is_some();
+                                if ui
+                                    .add_enabled(can_redo, egui::Button::new(redo_label))
+                                    .clicked()
+                                {
+                                    self.step_history(current_scene, true);
+                                    ui.close_menu();
+                                }
+                                ui.separator();
+                                let has_meshes = !self.selection.is_empty();
+                                if ui
+                                    .add_enabled(has_meshes, egui::Button::new("Duplicate"))
+                                    .on_hover_text("Ctrl+D")
+                                    .clicked()
+                                {
+                                    self.object_command = Some(ObjectCommand::Duplicate);
+                                    ui.close_menu();
+                                }
+                                let has_selection = self.selected_object.is_some() || has_meshes;
+                                if ui
+                                    .add_enabled(has_selection, egui::Button::new("Delete"))
+                                    .on_hover_text("Delete")
+                                    .clicked()
+                                {
+                                    self.object_command = Some(ObjectCommand::Delete);
+                                    ui.close_menu();
+                                }
+                                ui.separator();
+                                if ui.button("Preferences...").clicked() {
+                                    self.preferences_open = true;
+                                    ui.close_menu();
+                                }
+                            });
 
-                            if ui.button("▶ Play").clicked() {
-                                println!("Todo!");
-                            }
+                            ui.menu_button("Tutorials", |ui| {
+                                let tutorials = tutorial::find_tutorials(Path::new(TUTORIAL_DIRECTORY));
+                                if tutorials.is_empty() {
+                                    ui.weak("No tutorials found");
+                                }
+                                for path in tutorials {
+                                    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                                    if ui.button(name).clicked() {
+                                        match Tutorial::load(&path) {
+                                            Ok(tutorial) => self.tutorial.start(tutorial),
+                                            Err(e) => log::error!("{}", e),
+                                        }
+                                        ui.close_menu();
+                                    }
+                                }
+                                if self.tutorial.is_running() {
+                                    ui.separator();
+                                    if ui.button("Stop Tutorial").clicked() {
+                                        self.tutorial.stop();
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
 
                             ui.menu_button("Add", |ui| {
                                 ui.menu_button("Mesh", |ui| {
                                     ui.menu_button("Static Mesh", |ui| {
+                                        let mut added = None;
                                         for (handle, loaded_mesh) in &asset_loader.loaded_mesh_data {
                                             let mesh_name = loaded_mesh.name.as_str(); // or placeholder
 
                                             if ui.button(mesh_name).clicked() {
-                                                let static_mesh = StaticMesh::new(
-                                                    context,                     // <-- Pass your glow context!
+                                                match current_scene.add_mesh_asset(
+                                                    context,
                                                     mesh_name.to_string(),
                                                     *handle,
                                                     asset_loader,
-                                                );
-
-                                                current_scene.add_static_mesh(static_mesh);
-
-                                                self.append_terminal(format!("Added Static Mesh: {}", mesh_name));
+                                                ) {
+                                                    Ok(count) => {
+                                                        added = Some((*handle, count));
+                                                        log::info!("Added Static Mesh: {}", mesh_name);
+                                                    }
+                                                    Err(e) => log::error!("{}", e),
+                                                }
                                                 ui.close_menu();
                                             }
                                         }
+                                        // Each static mesh is a use, they're removed one by one
+                                        if let Some((handle, count)) = added {
+                                            for _ in 0..count {
+                                                asset_loader.retain(AssetHandle::Mesh(handle));
+                                            }
+                                        }
                                     });
 
 
                                     if ui.button("Static Mesh").clicked() {
                                         // current_scene.add_static_mesh();
-                                        self.append_terminal("Add Static Mesh ... TODO");
+                                        log::info!("Add Static Mesh ... TODO");
 
                                         
 
@@ -521,51 +4331,182 @@ impl Gui {
                                     if ui.button("Dynamic Mesh").clicked() {
                                         // current_scene.add_static_mesh();
 
-                                        self.append_terminal("Add Dynamic Mesh ... TODO");
+                                        log::info!("Add Dynamic Mesh ... TODO");
+                                        ui.close_menu();
+                                    }
+                                });
+
+                                ui.menu_button("Foliage Layer", |ui| {
+                                    let mut added = None;
+                                    for (handle, loaded_mesh) in &asset_loader.loaded_mesh_data {
+                                        if ui.button(&loaded_mesh.name).clicked() {
+                                            let layer =
+                                                FoliageLayer::new(context, *handle, asset_loader);
+                                            match layer {
+                                                Ok(layer) => {
+                                                    added = Some(layer.handle);
+                                                    current_scene.foliage.push(layer);
+                                                    self.foliage_layer =
+                                                        current_scene.foliage.len() - 1;
+                                                    self.foliage_painting = true;
+                                                }
+                                                Err(e) => log::error!("{}", e),
+                                            }
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    if let Some(handle) = added {
+                                        asset_loader.retain(AssetHandle::Mesh(handle));
+                                    }
+                                });
+
+                                ui.menu_button("Tilemap", |ui| {
+                                    let mut added = None;
+                                    for loaded_texture in asset_loader.loaded_texture_data.values() {
+                                        if ui.button(&loaded_texture.name).clicked() {
+                                            added = Some(Tilemap::new(
+                                                format!("Tilemap {}", current_scene.tilemaps.len()),
+                                                loaded_texture.path.clone(),
+                                                64,
+                                                64,
+                                            ));
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    if let Some(mut tilemap) = added {
+                                        tilemap.request_atlas(asset_loader);
+                                        current_scene.tilemaps.push(tilemap);
+                                        self.tilemap_index = current_scene.tilemaps.len() - 1;
+                                        self.tile_painting = true;
+                                    }
+                                });
+
+                                ui.menu_button("Sprite", |ui| {
+                                    if ui.button("Plain").clicked() {
+                                        current_scene.sprites.push(Sprite::new(None));
                                         ui.close_menu();
                                     }
+                                    let mut added = None;
+                                    for (handle, loaded_texture) in &asset_loader.loaded_texture_data {
+                                        if ui.button(&loaded_texture.name).clicked() {
+                                            current_scene.sprites.push(Sprite::new(Some(*handle)));
+                                            added = Some(*handle);
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    if let Some(handle) = added {
+                                        asset_loader.retain(AssetHandle::Texture(handle));
+                                    }
                                 });
 
                                 ui.menu_button("Camera", |ui| {
                                     if ui.button("Perspective Camera").clicked() {
-                                        self.append_terminal("Add Perspective Camera!");
+                                        log::info!("Add Perspective Camera!");
                                         ui.close_menu();
                                     }
                                     if ui.button("Orthographic Camera").clicked() {
-                                        self.append_terminal("Add Orthographic Camera!");
+                                        log::info!("Add Orthographic Camera!");
                                         ui.close_menu();
                                     }
                                 });
 
                                 ui.menu_button("Light", |ui| {
                                     if ui.button("Point Light").clicked() {
-                                        self.append_terminal("Add Point Light!");
+                                        log::info!("Add Point Light!");
                                         ui.close_menu();
                                     }
 
                                     if ui.button("Spot Light").clicked() {
-                                        self.append_terminal("Add Ambient Light!");
+                                        log::info!("Add Ambient Light!");
                                         ui.close_menu();
                                     }
 
                                     if ui.button("Ambient Light").clicked() {
-                                        self.append_terminal("Add Ambient Light!");
+                                        log::info!("Add Ambient Light!");
                                         ui.close_menu();
                                     }
                                 });
                             });
 
+                            ui.toggle_value(&mut self.foliage_painting, "🌿 Foliage")
+                                .on_hover_text("Scatter instances of a mesh over the scene");
+                            let tiles = ui
+                                .toggle_value(&mut self.tile_painting, "🧱 Tiles")
+                                .on_hover_text("Paint tilemaps, seen through the orthographic camera");
+                            // Tilemaps are only drawn through the orthographic camera
+                            if tiles.changed() && self.tile_painting {
+                                *active_camera_type = CameraType::Orthographic;
+                            }
+
+                            if ui
+                                .button(self.gizmo_space.label())
+                                .on_hover_text(
+                                    "Move and rotate along world axes or the object's own, \
+                                     scaling is always along its own",
+                                )
+                                .clicked()
+                            {
+                                self.gizmo_space = self.gizmo_space.toggled();
+                            }
+                            self.pivot_ui(ui);
+                            game_camera_ui(ui, current_scene);
+
+                            if ui
+                                .button(self.viewport_layout.label())
+                                .on_hover_text("One view, or top, front and right views too")
+                                .clicked()
+                            {
+                                self.viewport_layout = self.viewport_layout.toggled();
+                            }
+
                             if ui.button("Perspective").clicked() {
                                 *active_camera_type = CameraType::Perspective;
                             }
                             if ui.button("Orthographic").clicked() {
                                 *active_camera_type = CameraType::Orthographic;
                             }
+
+                            let mut cvars = self.cvars.lock().unwrap();
+                            let current = cvars
+                                .get("r_view_mode")
+                                .and_then(|value| ViewMode::from_name(&value.to_string()))
+                                .unwrap_or(ViewMode::Lit);
+                            let mut view_mode = current;
+                            egui::ComboBox::from_id_salt("ViewMode")
+                                .selected_text(view_mode.label())
+                                .show_ui(ui, |ui| {
+                                    for mode in ViewMode::ALL {
+                                        ui.selectable_value(&mut view_mode, mode, mode.label());
+                                    }
+                                });
+                            if view_mode != current {
+                                let name = view_mode.name().to_string();
+                                let _ = cvars.set("r_view_mode", CVarValue::Str(name));
+                            }
+                            drop(cvars);
+
+                            let loading: Vec<&AssetProgress> = asset_loader.in_flight().collect();
+                            if !loading.is_empty() {
+                                let fraction = loading
+                                    .iter()
+                                    .map(|progress| progress.stage.fraction())
+                                    .sum::<f32>()
+                                    / loading.len() as f32;
+                                ui.add(
+                                    egui::ProgressBar::new(fraction)
+                                        .desired_width(160.0)
+                                        .text(format!("Loading {} assets", loading.len())),
+                                );
+                            }
                         });
 
-                        ui.checkbox(&mut self.wireframe, "Wireframe");
+                        let mut cvars = self.cvars.lock().unwrap();
+                        let mut wireframe = cvars.get_bool("r_wireframe");
+                        if ui.checkbox(&mut wireframe, "Wireframe").changed() {
+                            let _ = cvars.set("r_wireframe", CVarValue::Bool(wireframe));
+                        }
 
-                        if self.wireframe {
+                        if wireframe {
                             unsafe {
                                 context.polygon_mode(glow::FRONT_AND_BACK, glow::LINE);
                             }
@@ -576,100 +4517,36 @@ impl Gui {
                         }
                     });
 
-                ui.input(|input| {
-                    if input.key_down(egui::Key::W) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed() * camera.get_orientation() * delta_time as f32,
-                        );
-                    }
-                    if input.key_down(egui::Key::A) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed()
-                                    * -cgmath::Vector3::normalize(cgmath::Vector3::cross(
-                                        camera.get_orientation(),
-                                        camera.get_up(),
-                                    ))
-                                    * delta_time as f32,
-                        );
-                    }
-                    if input.key_down(egui::Key::S) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed()
-                                    * -camera.get_orientation()
-                                    * delta_time as f32,
-                        );
-                    }
-                    if input.key_down(egui::Key::D) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed()
-                                    * cgmath::Vector3::normalize(cgmath::Vector3::cross(
-                                        camera.get_orientation(),
-                                        camera.get_up(),
-                                    ))
-                                    * delta_time as f32,
-                        );
-                    }
-                    if input.key_down(egui::Key::Space) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed() * camera.get_up() * delta_time as f32,
-                        );
-                    }
-                    if input.key_down(egui::Key::ArrowDown) {
-                        camera.set_position(
-                            camera.get_position()
-                                + camera.get_speed() * -camera.get_up() * delta_time as f32,
-                        );
-                    }
-                    if input.pointer.button_down(egui::PointerButton::Primary) {
-                        if camera.get_first_click() {
-                            if let Some(pos) = input.pointer.hover_pos() {
-                                camera.set_last_mouse_pos(pos); // store initial pos
-                            }
-                            camera.set_first_click(false);
-                        }
-
-                        if let Some(pos) = input.pointer.hover_pos() {
-                            // Calculate delta since last frame
-                            let delta_x = pos.x - camera.get_last_mouse_pos().x;
-                            let delta_y = pos.y - camera.get_last_mouse_pos().y;
-
-                            let rot_x = camera.get_sensitivity() * (delta_y as f32)
-                                / camera.get_height() as f32;
-                            let rot_y = camera.get_sensitivity() * (delta_x as f32)
-                                / camera.get_width() as f32;
-
-                            let right = camera.get_orientation().cross(camera.get_up()).normalize();
-                            let pitch_quat =
-                                cgmath::Quaternion::from_axis_angle(right, cgmath::Deg(-rot_x));
-
-                            let new_orientation = pitch_quat * camera.get_orientation();
-
-                            let up_dot = new_orientation.dot(camera.get_up());
-                            if up_dot.abs() < 0.99 {
-                                camera.set_orientation(new_orientation);
-                            }
-
-                            let yaw_quat = cgmath::Quaternion::from_axis_angle(
-                                camera.get_up(),
-                                cgmath::Deg(-rot_y),
-                            );
-                            camera.set_orientation(yaw_quat * camera.get_orientation());
+                // Photo mode flies the camera itself, modal transforms, box selection and foliage
+                // painting use the mouse
+                if !self.photo_mode.is_active()
+                    && self.modal_transform.is_none()
+                    && self.marquee_start.is_none()
+                    && !self.foliage_painting
+                    && !self.tile_painting
+                {
+                    ui.input(|input| camera::fly(camera, input, delta_time as f32));
+                }
 
-                            // Update last mouse pos
-                            camera.set_last_mouse_pos(pos);
-                        }
-                    } else {
-                        camera.set_first_click(true);
-                    }
-                });
+                if let Some(modal) = &self.modal_transform {
+                    egui::Area::new(egui::Id::new("ModalTransform"))
+                        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -48.0])
+                        .show(ctx, |ui| {
+                            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                ui.label(modal.status(self.gizmo_space));
+                                ui.weak(
+                                    "X/Y/Z for an axis (twice for the other space), Enter to \
+                                     confirm, Esc to cancel",
+                                );
+                                if modal.mode == GizmoMode::Translate {
+                                    ui.weak("Hold Ctrl to snap to vertices, Shift to surfaces");
+                                }
+                            });
+                        });
+                }
 
                 ui.horizontal(|ui| {
-                    ui.heading(current_scene.name.clone());
+                    self.scene_tabs_bar(ui, &scene_names, open_scene);
                     ui.hyperlink_to("Cruel Engine homepage", "https://www.cruelengine.com");
                     ui.allocate_ui_with_layout(
                         ui.available_size(),
@@ -680,20 +4557,159 @@ impl Gui {
                     );
                 });
 
-                let rect = ui.max_rect();
+                let rect = match self.viewport_layout {
+                    ViewportLayout::Quad if panels_visible => {
+                        let target = camera.get_position() + camera.get_orientation() * 10.0;
+                        self.quad_view.layout(ui, ui.max_rect(), target)
+                    }
+                    _ => {
+                        self.quad_view.hide();
+                        ui.max_rect()
+                    }
+                };
+                self.tutorial.register_region("Viewport", rect);
                 let (x, y) = rect.min.into();
                 let (width, height) = rect.size().into();
 
+                // Set the viewport which the custom graphics will render in. The display's scale
+                // and the UI scale are both in pixels_per_point.
                 let pixels_per_point = ctx.pixels_per_point();
+                self.viewport = Some(Viewport::from_points(x, y, width, height, pixels_per_point));
 
-                // Set the viewport which the custom graphics will render in
-                self.viewport = Some(Viewport::new(
-                    (x * pixels_per_point) as i32,
-                    (y * pixels_per_point) as i32,
-                    (width * pixels_per_point) as i32,
-                    (height * pixels_per_point) as i32,
-                ));
+                if self.foliage_painting {
+                    let scene = &mut *current_scene;
+                    self.paint_foliage(ui, rect, context, &*camera, scene, asset_loader);
+                }
+                // Rays from any other camera would miss the tiles that are drawn
+                if self.tile_painting && camera.as_orthographic_mut().is_some() {
+                    self.paint_tiles(ui, rect, &*camera, current_scene);
+                }
+                self.drop_asset(ui, rect, &*camera, current_scene, asset_loader);
+                if panels_visible {
+                    let scene = &*current_scene;
+                    self.axis_gizmo(ui, rect, &*camera, scene, active_camera_type);
+                }
+                if let Some(modal) = &mut self.modal_transform {
+                    // Picked up by the next frame's update
+                    let scene = &*current_scene;
+                    modal.snap_to(snap_target(ui, rect, &*camera, scene, asset_loader, modal));
+                }
+                // Clicks in the quad layout's side views don't select
+                let in_view = self.marquee_start.is_some()
+                    || viewport_response
+                        .interact_pointer_pos()
+                        .is_some_and(|pointer| rect.contains(pointer));
+                if !transforming
+                    && in_view
+                    && !self.foliage_painting
+                    && !self.tile_painting
+                    && !self.photo_mode.is_active()
+                {
+                    self.viewport_selection(ui, &viewport_response, rect, &*camera, current_scene);
+                }
             });
+
+            if self.foliage_painting {
+                self.foliage_window(ctx, context, current_scene, asset_loader);
+            }
+            if self.tile_painting {
+                self.tilemap_window(ctx, context, current_scene, asset_loader);
+            }
+            if self.launcher_open {
+                if let Some(project) = self.launcher.show(ctx, &mut self.launcher_open) {
+                    self.project_to_open = Some(project);
+                    self.launcher_open = false;
+                }
+            }
+            if self.preferences_open {
+                self.preferences_window(ctx);
+            }
+            self.save_scene_as_window(ctx);
+            self.close_scene_window(ctx, &scene_names);
+            if !self.pending_delete.is_empty() {
+                let objects = std::mem::take(&mut self.pending_delete);
+                let name = match objects.as_slice() {
+                    [SelectedObject::StaticMesh(index)] => current_scene
+                        .static_meshes
+                        .get(*index)
+                        .map(|mesh| mesh.name.clone()),
+                    [SelectedObject::Sprite(index)] => Some(format!("Sprite {}", index)),
+                    [_] => None,
+                    objects => Some(format!("{} objects", objects.len())),
+                };
+                let mut answer = None;
+                egui::Window::new("Delete")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                    .show(ctx, |ui| {
+                        ui.label(format!("Delete {}?", name.as_deref().unwrap_or("the object")));
+                        ui.horizontal(|ui| {
+                            if ui.button("Delete").clicked() {
+                                answer = Some(true);
+                            }
+                            if ui.button("Cancel").clicked() {
+                                answer = Some(false);
+                            }
+                        });
+                    });
+                match answer {
+                    Some(true) => {
+                        self.delete_objects(context, current_scene, asset_loader, objects)
+                    }
+                    Some(false) => {}
+                    None => self.pending_delete = objects,
+                }
+            }
+
+            if let Some((path, uses)) = self.pending_asset_delete.take() {
+                let mut answer = None;
+                egui::Window::new("Delete file")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                    .show(ctx, |ui| {
+                        ui.label(format!("Delete {:?}?", path));
+                        if uses > 0 {
+                            ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                format!(
+                                    "⚠ Still used {} times by the scene or saved tilemaps",
+                                    uses
+                                ),
+                            );
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Delete").clicked() {
+                                answer = Some(true);
+                            }
+                            if ui.button("Cancel").clicked() {
+                                answer = Some(false);
+                            }
+                        });
+                    });
+                match answer {
+                    Some(true) => match content_browser::delete_asset(&path) {
+                        Ok(()) => log::info!("Deleted {:?}", path),
+                        Err(e) => log::error!("{}", e),
+                    },
+                    Some(false) => {}
+                    None => self.pending_asset_delete = Some((path, uses)),
+                }
+            }
+
+            // The preview uses the same window a game would show
+            if let Some(runner) = &mut self.dialogue_preview {
+                dialogue::dialogue_window(ctx, runner, &mut self.dialogue_variables);
+                if runner.is_finished() {
+                    self.dialogue_preview = None;
+                }
+            }
+
+            self.photo_mode.ui(ctx, camera, delta_time as f32, editor_fov);
+
+            // Drawn last so it sits on top of every panel
+            self.tutorial.show(ctx);
         })
     }
 }
@@ -12,12 +12,16 @@ pub struct MaterialHandle(pub usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ShaderHandle(pub usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParticleEffectHandle(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AssetHandle {
     Texture(TextureHandle),
     Mesh(MeshHandle),
     Material(MaterialHandle),
     Shader(ShaderHandle),
+    ParticleEffect(ParticleEffectHandle),
 }
 
 impl AssetHandle {
@@ -52,4 +56,12 @@ impl AssetHandle {
             None
         }
     }
+
+    pub fn as_particle_effect_handle(&self) -> Option<ParticleEffectHandle> {
+        if let AssetHandle::ParticleEffect(handle) = *self {
+            Some(handle)
+        } else {
+            None
+        }
+    }
 }
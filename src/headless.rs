@@ -0,0 +1,479 @@
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use clap::{Arg, ArgAction, Command};
+use glow::HasContext;
+use glutin::{
+    config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+    context::ContextAttributesBuilder,
+    display::{Display, DisplayApiPreference},
+    prelude::*,
+    surface::{PbufferSurface, SurfaceAttributesBuilder, WindowSurface},
+};
+use winit::{
+    event_loop::ActiveEventLoop,
+    raw_window_handle::{HasDisplayHandle, HasWindowHandle},
+    window::Window,
+};
+
+use crate::{
+    camera::{Camera, PerspectiveCamera},
+    gl_debug,
+    handles::AssetHandle,
+    loader::{Asset, AssetLoader, AssetPriority},
+    scene_graph::SceneNode,
+    textures::Texture,
+    viewport::Viewport,
+};
+
+const ASSET_LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Settings for `--headless`, which renders to PNG files instead of opening the editor.
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub meshes: Vec<PathBuf>,
+    pub texture: Option<PathBuf>,
+    pub camera_position: cgmath::Point3<f32>,
+    pub camera_target: cgmath::Point3<f32>,
+    pub fov: f32,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub output: PathBuf,
+}
+
+fn parse_vector(value: &str) -> Result<[f32; 3], String> {
+    let parts: Vec<f32> = value
+        .split(',')
+        .map(|part| part.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid vector '{}': {}", value, e))?;
+
+    match parts.as_slice() {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(format!("Expected x,y,z but got '{}'", value)),
+    }
+}
+
+impl HeadlessOptions {
+    /// Returns `Ok(None)` when `--headless` was not passed and the editor should start as usual.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Self>, String> {
+        let cli = Command::new("cruel_game_engine")
+            .arg(
+                Arg::new("headless")
+                    .long("headless")
+                    .action(ArgAction::SetTrue)
+                    .help("Render offscreen to PNG files and exit"),
+            )
+            .arg(
+                Arg::new("mesh")
+                    .long("mesh")
+                    .action(ArgAction::Append)
+                    .help("glTF file to add to the scene, can be repeated"),
+            )
+            .arg(
+                Arg::new("texture")
+                    .long("texture")
+                    .help("Texture bound while rendering the scene"),
+            )
+            .arg(
+                Arg::new("camera")
+                    .long("camera")
+                    .default_value("0,0,3")
+                    .help("Camera position as x,y,z"),
+            )
+            .arg(
+                Arg::new("target")
+                    .long("target")
+                    .default_value("0,0,0")
+                    .help("Point the camera looks at as x,y,z"),
+            )
+            .arg(
+                Arg::new("fov")
+                    .long("fov")
+                    .value_parser(clap::value_parser!(f32))
+                    .default_value("45"),
+            )
+            .arg(
+                Arg::new("width")
+                    .long("width")
+                    .value_parser(clap::value_parser!(u32).range(1..))
+                    .default_value("1280"),
+            )
+            .arg(
+                Arg::new("height")
+                    .long("height")
+                    .value_parser(clap::value_parser!(u32).range(1..))
+                    .default_value("720"),
+            )
+            .arg(
+                Arg::new("frames")
+                    .long("frames")
+                    .value_parser(clap::value_parser!(u32).range(1..))
+                    .default_value("1"),
+            )
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .default_value("screenshots")
+                    .help("Directory the frames are written to"),
+            );
+
+        let matches = cli.try_get_matches_from(args).map_err(|e| e.to_string())?;
+        if !matches.get_flag("headless") {
+            return Ok(None);
+        }
+
+        let camera_position = parse_vector(matches.get_one::<String>("camera").unwrap())?;
+        let camera_target = parse_vector(matches.get_one::<String>("target").unwrap())?;
+
+        Ok(Some(Self {
+            meshes: matches
+                .get_many::<String>("mesh")
+                .map(|meshes| meshes.map(PathBuf::from).collect())
+                .unwrap_or_default(),
+            texture: matches.get_one::<String>("texture").map(PathBuf::from),
+            camera_position: camera_position.into(),
+            camera_target: camera_target.into(),
+            fov: *matches.get_one::<f32>("fov").unwrap(),
+            width: *matches.get_one::<u32>("width").unwrap(),
+            height: *matches.get_one::<u32>("height").unwrap(),
+            frames: *matches.get_one::<u32>("frames").unwrap(),
+            output: PathBuf::from(matches.get_one::<String>("output").unwrap()),
+        }))
+    }
+}
+
+/// A framebuffer with a color and depth renderbuffer, read back to the CPU after rendering.
+pub struct OffscreenTarget {
+    framebuffer: glow::Framebuffer,
+    color: glow::Renderbuffer,
+    depth: glow::Renderbuffer,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OffscreenTarget {
+    pub fn new(gl: &glow::Context, width: u32, height: u32) -> Result<Self, String> {
+        unsafe {
+            let framebuffer = gl.create_framebuffer()?;
+            let color = gl.create_renderbuffer()?;
+            let depth = gl.create_renderbuffer()?;
+
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(color));
+            gl.renderbuffer_storage(glow::RENDERBUFFER, glow::RGBA8, width as i32, height as i32);
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(depth));
+            gl.renderbuffer_storage(
+                glow::RENDERBUFFER,
+                glow::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::RENDERBUFFER,
+                Some(color),
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_STENCIL_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(depth),
+            );
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            let target = Self {
+                framebuffer,
+                color,
+                depth,
+                width,
+                height,
+            };
+
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                target.destroy(gl);
+                return Err(format!(
+                    "Offscreen framebuffer is incomplete: {:#x}",
+                    status
+                ));
+            }
+
+            Ok(target)
+        }
+    }
+
+    pub fn bind(&self, gl: &glow::Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+        }
+    }
+
+    /// Reads the color attachment, flipped so the first row is the top of the image.
+    pub fn read_image(&self, gl: &glow::Context) -> image::RgbaImage {
+        let mut pixels = vec![0u8; (self.width * self.height * 4) as usize];
+        unsafe {
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(Some(&mut pixels)),
+            );
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        }
+
+        let image = image::RgbaImage::from_raw(self.width, self.height, pixels).unwrap();
+        image::imageops::flip_vertical(&image)
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_renderbuffer(self.color);
+            gl.delete_renderbuffer(self.depth);
+        }
+    }
+}
+
+// Blocks until every one of `handles` has arrived from the loader, or one of them failed
+fn wait_for_assets(
+    asset_loader: &Mutex<AssetLoader>,
+    handles: &HashSet<AssetHandle>,
+) -> Result<(), String> {
+    let start = Instant::now();
+    let mut pending = handles.clone();
+
+    while !pending.is_empty() {
+        if start.elapsed() > ASSET_LOAD_TIMEOUT {
+            return Err(format!(
+                "Timed out waiting for assets, {} of {} loaded",
+                handles.len() - pending.len(),
+                handles.len()
+            ));
+        }
+
+        let mut asset_loader = asset_loader.lock().unwrap();
+        for (handle, asset) in asset_loader.poll_loaded() {
+            pending.remove(&handle);
+            match asset {
+                Asset::Mesh(loaded_mesh) => {
+                    asset_loader
+                        .loaded_mesh_data
+                        .insert(handle.as_mesh_handle().unwrap(), loaded_mesh);
+                }
+                Asset::Texture(loaded_texture) => {
+                    asset_loader
+                        .loaded_texture_data
+                        .insert(handle.as_texture_handle().unwrap(), loaded_texture);
+                }
+                _ => log::warn!("No handler for loaded asset {:?}", handle),
+            }
+        }
+        if let Some(e) = pending.iter().find_map(|handle| asset_loader.failure(*handle)) {
+            return Err(format!("Failed to load asset: {}", e));
+        }
+        drop(asset_loader);
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+/// Creates a hidden window and a pbuffer context, renders the requested frames and writes them as PNGs.
+pub fn run(
+    event_loop: &ActiveEventLoop,
+    options: &HeadlessOptions,
+    asset_loader: &Arc<Mutex<AssetLoader>>,
+) -> Result<(), String> {
+    // WGL needs a window to create a context at all, it is never shown
+    let window = event_loop
+        .create_window(Window::default_attributes().with_visible(false))
+        .map_err(|e| format!("Failed to create hidden window: {}", e))?;
+
+    let display_handle = window.display_handle().map_err(|e| e.to_string())?;
+    let window_handle = window.window_handle().map_err(|e| e.to_string())?;
+
+    let display = unsafe {
+        Display::new(
+            display_handle.into(),
+            DisplayApiPreference::Wgl(Some(window_handle.into())),
+        )
+        .map_err(|e| format!("Failed to create display: {}", e))?
+    };
+
+    let config_template = ConfigTemplateBuilder::new()
+        .with_surface_type(ConfigSurfaceTypes::PBUFFER | ConfigSurfaceTypes::WINDOW)
+        .build();
+    let config = unsafe {
+        display
+            .find_configs(config_template)
+            .map_err(|e| format!("Failed to find a GL config: {}", e))?
+            .next()
+            .ok_or("No GL config supports pbuffers")?
+    };
+
+    let context_attributes = ContextAttributesBuilder::new()
+        .with_debug(gl_debug::DEBUG_CONTEXT)
+        .build(Some(window_handle.into()));
+    let non_current_context = unsafe {
+        display
+            .create_context(&config, &context_attributes)
+            .map_err(|e| format!("Failed to create GL context: {}", e))?
+    };
+
+    // Rendering goes to an FBO, the pbuffer only exists to make the context current
+    let one = NonZeroU32::new(1).unwrap();
+    let pbuffer_attributes = SurfaceAttributesBuilder::<PbufferSurface>::new().build(one, one);
+    let (_current_context, _pbuffer, _window_surface) =
+        match unsafe { display.create_pbuffer_surface(&config, &pbuffer_attributes) } {
+            Ok(pbuffer) => {
+                let context = non_current_context
+                    .make_current(&pbuffer)
+                    .map_err(|e| format!("Failed to make the context current: {}", e))?;
+                (context, Some(pbuffer), None)
+            }
+            Err(e) => {
+                log::warn!(
+                    "Pbuffers are not available ({}), using the hidden window",
+                    e
+                );
+                let size = window.inner_size();
+                let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+                    window_handle.into(),
+                    NonZeroU32::new(size.width).unwrap_or(one),
+                    NonZeroU32::new(size.height).unwrap_or(one),
+                );
+                let surface = unsafe {
+                    display
+                        .create_window_surface(&config, &surface_attributes)
+                        .map_err(|e| format!("Failed to create window surface: {}", e))?
+                };
+                let context = non_current_context
+                    .make_current(&surface)
+                    .map_err(|e| format!("Failed to make the context current: {}", e))?;
+                (context, None, Some(surface))
+            }
+        };
+
+    let mut gl = unsafe {
+        glow::Context::from_loader_function(|s| {
+            let c_str = CString::new(s).unwrap();
+            display.get_proc_address(&c_str) as *const _
+        })
+    };
+    gl_debug::install(&mut gl);
+
+    // The same file passed twice is only loaded once
+    let mut handles = HashSet::new();
+    for mesh in &options.meshes {
+        let name = mesh
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let handle = asset_loader.lock().unwrap().request_mesh_with_priority(
+            mesh,
+            name,
+            AssetPriority::Blocking,
+        );
+        handles.insert(AssetHandle::Mesh(handle));
+    }
+    if let Some(texture) = &options.texture {
+        let name = texture
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let handle = asset_loader.lock().unwrap().request_texture_with_priority(
+            texture,
+            name,
+            AssetPriority::Blocking,
+        );
+        handles.insert(AssetHandle::Texture(handle));
+    }
+    wait_for_assets(asset_loader, &handles)?;
+
+    let mut scene = SceneNode::new("Headless Scene", &gl)?;
+    {
+        let mut asset_loader = asset_loader.lock().unwrap();
+        let handles: Vec<_> = asset_loader.loaded_mesh_data.keys().copied().collect();
+        for handle in handles {
+            let name = asset_loader.loaded_mesh_data[&handle].name.clone();
+            scene.add_mesh_asset(&gl, name, handle, &asset_loader)?;
+        }
+        // Every frame is written out, nothing can be missing from the first one
+        asset_loader.uploads.get_mut().unwrap().flush(&gl);
+        for (_, loaded_texture) in asset_loader.loaded_texture_data.drain() {
+            let name = loaded_texture.name.clone();
+            scene
+                .textures
+                .push(Texture::from_loaded_data(&gl, Some(name), loaded_texture)?);
+        }
+    }
+
+    let mut camera = PerspectiveCamera::new(
+        "Headless Camera".to_string(),
+        options.camera_position,
+        options.fov,
+        options.width,
+        options.height,
+        options.width as f32 / options.height as f32,
+        0.1,
+        100.0,
+        0.0,
+        0.0,
+    );
+    let direction = options.camera_target - options.camera_position;
+    if direction != cgmath::Vector3::new(0.0, 0.0, 0.0) {
+        camera.set_orientation(cgmath::InnerSpace::normalize(direction));
+    }
+
+    std::fs::create_dir_all(&options.output)
+        .map_err(|e| format!("Failed to create {:?}: {}", options.output, e))?;
+
+    let target = OffscreenTarget::new(&gl, options.width, options.height)?;
+    let viewport = Viewport::new(0, 0, options.width as i32, options.height as i32);
+
+    for frame in 0..options.frames {
+        target.bind(&gl);
+        unsafe {
+            gl.viewport(0, 0, options.width as i32, options.height as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+        }
+
+        scene.update(&mut camera);
+        scene.render(&gl, &mut camera, &viewport)?;
+
+        let path = options.output.join(format!("frame_{:04}.png", frame));
+        target
+            .read_image(&gl)
+            .save(&path)
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        log::info!("Wrote {:?}", path);
+    }
+
+    unsafe {
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+    }
+    target.destroy(&gl);
+
+    Ok(())
+}
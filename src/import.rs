@@ -0,0 +1,582 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use gltf::{buffer::Source, Gltf};
+
+use crate::{
+    compression::{CompressedLevel, CompressedTexture, TextureCompression},
+    data::*,
+    error::{EngineError, EngineResult},
+    loader::{self, LoadStage},
+    pack::{self, AssetPack},
+};
+
+/// Processed assets live here, named after a hash of their source so an edited file is
+/// imported again and an untouched one never is.
+pub const IMPORT_CACHE_DIRECTORY: &str = ".cache";
+
+// Bump when an importer or the artifact layout changes so old artifacts get ignored
+const IMPORT_VERSION: u32 = 5;
+const TEXTURE_MAGIC: &[u8; 4] = b"CTEX";
+const MESH_MAGIC: &[u8; 4] = b"CMSH";
+
+static NEXT_TEMPORARY: AtomicUsize = AtomicUsize::new(0); // Unique names for half written artifacts
+
+/// Decodes an image into a texture, compressed with `compression`. Compressed artifacts
+/// don't keep the RGBA pixels, `data` is empty when one was read.
+#[tracing::instrument(skip_all, fields(path = ?path))]
+pub fn import_texture(
+    path: &Path,
+    name: String,
+    compression: TextureCompression,
+    packs: &[AssetPack],
+    progress: &dyn Fn(LoadStage),
+) -> EngineResult<LoadedTexture> {
+    let source = pack::read_asset(packs, path)?;
+    progress(LoadStage::Read {
+        bytes: source.len(),
+    });
+    let artifact = artifact_path(
+        "textures",
+        content_hash(&source),
+        &format!("{}.tex", compression.name()),
+    );
+
+    match read_artifact(&artifact, TEXTURE_MAGIC, |reader| {
+        read_texture(reader, path, &name, compression)
+    }) {
+        Ok(Some(mut texture)) => {
+            // Compressed artifacts don't keep the pixels, a GPU without the format needs these
+            if texture.data.is_empty() {
+                texture.source = source;
+            }
+            progress(LoadStage::Decoded);
+            return Ok(texture);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+
+    let image = image::load_from_memory(&source)
+        .map_err(|e| EngineError::Asset {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?
+        .flipv()
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    let data = image.into_raw();
+    progress(LoadStage::Decoded);
+
+    // Slow for big textures, which is why it only happens on import
+    let compressed = {
+        let _span = tracing::info_span!("compress_texture").entered();
+        CompressedTexture::compress(&data, width, height, compression)
+    };
+
+    let texture = LoadedTexture {
+        name,
+        path: path.to_path_buf(),
+        width,
+        height,
+        data,
+        compressed,
+        source: Vec::new(),
+    };
+    let mut writer = ByteWriter::new(TEXTURE_MAGIC);
+    write_texture(&mut writer, &texture);
+    if let Err(e) = writer.save(&artifact) {
+        log::warn!("{}", e);
+    }
+    Ok(texture)
+}
+
+/// Loads a glTF file, or the vertex data it was turned into last time.
+#[tracing::instrument(skip_all, fields(path = ?path))]
+pub fn import_mesh(
+    path: &Path,
+    packs: &[AssetPack],
+    progress: &dyn Fn(LoadStage),
+) -> EngineResult<LoadedMesh> {
+    let source = pack::read_asset(packs, path)?;
+    progress(LoadStage::Read {
+        bytes: source.len(),
+    });
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    // External buffers hold the vertices, they have to be part of the key
+    let mut hash = content_hash(&source);
+    if let Ok(gltf) = Gltf::from_slice(&source) {
+        for buffer in gltf.buffers() {
+            if let Source::Uri(uri) = buffer.source() {
+                if let Ok(bytes) = pack::read_asset(packs, &directory.join(uri)) {
+                    hash = combine_hash(hash, content_hash(&bytes));
+                }
+            }
+        }
+    }
+    let artifact = artifact_path("meshes", hash, "mesh");
+
+    match read_artifact(&artifact, MESH_MAGIC, |reader| read_mesh(reader, path)) {
+        Ok(Some(mesh)) => {
+            progress(LoadStage::Decoded);
+            return Ok(mesh);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+
+    let mesh = loader::load_gltf_full(path, &source, packs)?;
+    progress(LoadStage::Decoded);
+    let mut writer = ByteWriter::new(MESH_MAGIC);
+    write_mesh(&mut writer, &mesh, directory);
+    if let Err(e) = writer.save(&artifact) {
+        log::warn!("{}", e);
+    }
+    Ok(mesh)
+}
+
+// FNV-1a, unlike DefaultHasher it gives the same result on every build
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn combine_hash(a: u64, b: u64) -> u64 {
+    content_hash(&[a.to_le_bytes(), b.to_le_bytes()].concat())
+}
+
+fn artifact_path(kind: &str, hash: u64, extension: &str) -> PathBuf {
+    Path::new(IMPORT_CACHE_DIRECTORY)
+        .join(kind)
+        .join(format!("{:016x}.{}", hash, extension))
+}
+
+// Ok(None) when there is no artifact yet, Err when there is one but it can't be used
+fn read_artifact<T>(
+    path: &Path,
+    magic: &[u8; 4],
+    read: impl FnOnce(&mut ByteReader) -> Option<T>,
+) -> Result<Option<T>, String> {
+    let Ok(bytes) = std::fs::read(path) else {
+        return Ok(None);
+    };
+    let mut reader = ByteReader {
+        bytes: &bytes,
+        offset: 0,
+    };
+    if reader.take(4) != Some(magic.as_slice()) {
+        return Err(format!("{:?} is not an import artifact", path));
+    }
+    if reader.u32() != Some(IMPORT_VERSION) {
+        return Ok(None); // Written by an older importer, just replace it
+    }
+    read(&mut reader)
+        .map(Some)
+        .ok_or_else(|| format!("Import artifact {:?} is invalid", path))
+}
+
+fn write_texture(writer: &mut ByteWriter, texture: &LoadedTexture) {
+    writer.u32(texture.width);
+    writer.u32(texture.height);
+    match &texture.compressed {
+        Some(compressed) => {
+            writer.u32(compressed.format.id());
+            writer.u32(compressed.levels.len() as u32);
+            for level in &compressed.levels {
+                writer.u32(level.width);
+                writer.u32(level.height);
+                writer.bytes(&level.data);
+            }
+        }
+        None => {
+            writer.u32(TextureCompression::None.id());
+            writer.bytes(&texture.data);
+        }
+    }
+}
+
+fn read_texture(
+    reader: &mut ByteReader,
+    path: &Path,
+    name: &str,
+    compression: TextureCompression,
+) -> Option<LoadedTexture> {
+    let width = reader.u32()?;
+    let height = reader.u32()?;
+    let format = TextureCompression::from_id(reader.u32()?)?;
+    if format != compression {
+        return None;
+    }
+
+    let (data, compressed) = match format {
+        TextureCompression::None => (reader.bytes()?, None),
+        format => {
+            let level_count = reader.u32()?;
+            let levels = (0..level_count)
+                .map(|_| {
+                    Some(CompressedLevel {
+                        width: reader.u32()?,
+                        height: reader.u32()?,
+                        data: reader.bytes()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (Vec::new(), Some(CompressedTexture { format, levels }))
+        }
+    };
+
+    Some(LoadedTexture {
+        name: name.to_string(),
+        path: path.to_path_buf(),
+        width,
+        height,
+        data,
+        compressed,
+        source: Vec::new(),
+    })
+}
+
+// Material textures are stored relative to the source so a moved file still finds them
+fn write_mesh(writer: &mut ByteWriter, mesh: &LoadedMesh, directory: &Path) {
+    writer.u32(mesh.primitives.len() as u32);
+    for primitive in &mesh.primitives {
+        write_primitive(writer, primitive, directory);
+    }
+    writer.u32(mesh.lods.len() as u32);
+    for lod in &mesh.lods {
+        writer.u32(lod.len() as u32);
+        for primitive in lod {
+            write_primitive(writer, primitive, directory);
+        }
+    }
+    writer.u32(mesh.nodes.len() as u32);
+    for node in &mesh.nodes {
+        write_node(writer, node);
+    }
+}
+
+fn read_mesh(reader: &mut ByteReader, path: &Path) -> Option<LoadedMesh> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let primitive_count = reader.u32()?;
+    let primitives = (0..primitive_count)
+        .map(|_| read_primitive(reader, directory))
+        .collect::<Option<Vec<_>>>()?;
+    let lod_count = reader.u32()?;
+    let lods = (0..lod_count)
+        .map(|_| {
+            let count = reader.u32()?;
+            (0..count)
+                .map(|_| read_primitive(reader, directory))
+                .collect::<Option<Vec<_>>>()
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let nodes = (0..reader.u32()?)
+        .map(|_| read_node(reader))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(LoadedMesh {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        path: path.to_path_buf(),
+        primitives,
+        lods,
+        nodes,
+    })
+}
+
+fn write_node(writer: &mut ByteWriter, node: &LoadedNode) {
+    writer.bytes(node.name.as_bytes());
+    writer.optional(node.parent, |writer, parent| writer.u32(parent as u32));
+    writer.u32(node.primitives.len() as u32);
+    for primitive in &node.primitives {
+        writer.u32(*primitive as u32);
+    }
+    writer.floats(&[node.translation]);
+    writer.floats(&[node.rotation]);
+    writer.floats(&[node.scale]);
+}
+
+fn read_node(reader: &mut ByteReader) -> Option<LoadedNode> {
+    let name = String::from_utf8(reader.bytes()?).ok()?;
+    let parent = reader.optional(|reader| Some(reader.u32()? as usize))?;
+    let primitives = (0..reader.u32()?)
+        .map(|_| Some(reader.u32()? as usize))
+        .collect::<Option<Vec<_>>>()?;
+    Some(LoadedNode {
+        name,
+        parent,
+        primitives,
+        translation: *reader.floats()?.first()?,
+        rotation: *reader.floats()?.first()?,
+        scale: *reader.floats()?.first()?,
+    })
+}
+
+fn write_primitive(writer: &mut ByteWriter, primitive: &LoadedPrimitive, directory: &Path) {
+    let vertex_data = &primitive.vertex_data;
+    writer.floats(&vertex_data.positions);
+    writer.optional(vertex_data.normals.as_ref(), |writer, normals| {
+        writer.floats(normals)
+    });
+    writer.optional(vertex_data.tangents.as_ref(), |writer, tangents| {
+        writer.floats(tangents)
+    });
+    writer.u32(vertex_data.texcoords.len() as u32);
+    for uv in &vertex_data.texcoords {
+        writer.floats(&uv.0);
+    }
+    writer.u32(vertex_data.colors.len() as u32);
+    for color in &vertex_data.colors {
+        write_color(writer, color);
+    }
+    writer.optional(vertex_data.joints.as_ref(), |writer, joints| {
+        writer.u32(joints.len() as u32);
+        for joint in joints.iter().flatten() {
+            writer.buffer.extend_from_slice(&joint.to_le_bytes());
+        }
+    });
+    writer.optional(vertex_data.weights.as_ref(), |writer, weights| {
+        writer.floats(weights)
+    });
+
+    writer.optional(primitive.material.as_ref(), |writer, material| {
+        for texture in [
+            &material.base_color_texture,
+            &material.metallic_roughness_texture,
+            &material.normal_texture,
+            &material.occlusion_texture,
+            &material.emissive_texture,
+        ] {
+            writer.optional(texture.as_ref(), |writer, texture| {
+                let relative = texture.strip_prefix(directory).unwrap_or(texture);
+                writer.bytes(relative.to_string_lossy().as_bytes());
+            });
+        }
+        writer.u32(material.normal_texcoord);
+        writer.u32(material.occlusion_texcoord);
+        write_color(writer, &material.base_color_factor);
+        writer.f32(material.metallic_factor);
+        writer.f32(material.roughness_factor);
+        writer.f32(material.occlusion_strength);
+        writer.floats(&[material.emissive_factor]);
+        writer.f32(material.emissive_strength);
+        writer.f32(material.clearcoat_factor);
+        writer.f32(material.clearcoat_roughness_factor);
+        writer.f32(material.transmission_factor);
+        writer.u32(material.alpha_mode as u32);
+        writer.u32(material.double_sided as u32);
+    });
+
+    writer.optional(primitive.indices.as_ref(), |writer, indices| {
+        writer.u32(indices.len() as u32);
+        for index in indices {
+            writer.u32(*index);
+        }
+    });
+    writer.u32(primitive.mode);
+}
+
+fn read_primitive(reader: &mut ByteReader, directory: &Path) -> Option<LoadedPrimitive> {
+    let positions = reader.floats()?;
+    let normals = reader.optional(|reader| reader.floats())?;
+    let tangents = reader.optional(|reader| reader.floats())?;
+    let texcoords = (0..reader.u32()?)
+        .map(|_| Some(Uv(reader.floats()?)))
+        .collect::<Option<Vec<_>>>()?;
+    let colors = (0..reader.u32()?)
+        .map(|_| read_color(reader))
+        .collect::<Option<Vec<_>>>()?;
+    let joints = reader.optional(|reader| {
+        (0..reader.u32()?)
+            .map(|_| {
+                let mut joint = [0u16; 4];
+                for value in &mut joint {
+                    *value = u16::from_le_bytes(reader.take(2)?.try_into().ok()?);
+                }
+                Some(joint)
+            })
+            .collect()
+    })?;
+    let weights = reader.optional(|reader| reader.floats())?;
+
+    let material = reader.optional(|reader| {
+        let mut texture = || {
+            reader.optional(|reader| {
+                let relative = String::from_utf8(reader.bytes()?).ok()?;
+                Some(directory.join(relative))
+            })
+        };
+        Some(LoadedMaterial {
+            base_color_texture: texture()?,
+            metallic_roughness_texture: texture()?,
+            normal_texture: texture()?,
+            occlusion_texture: texture()?,
+            emissive_texture: texture()?,
+            normal_texcoord: reader.u32()?,
+            occlusion_texcoord: reader.u32()?,
+            base_color_factor: read_color(reader)?,
+            metallic_factor: reader.f32()?,
+            roughness_factor: reader.f32()?,
+            occlusion_strength: reader.f32()?,
+            emissive_factor: *reader.floats()?.first()?,
+            emissive_strength: reader.f32()?,
+            clearcoat_factor: reader.f32()?,
+            clearcoat_roughness_factor: reader.f32()?,
+            transmission_factor: reader.f32()?,
+            alpha_mode: reader.u32()? != 0,
+            double_sided: reader.u32()? != 0,
+        })
+    })?;
+
+    let indices = reader.optional(|reader| (0..reader.u32()?).map(|_| reader.u32()).collect())?;
+
+    Some(LoadedPrimitive {
+        vertex_data: VertexData {
+            positions,
+            normals,
+            tangents,
+            texcoords,
+            colors,
+            joints,
+            weights,
+        },
+        material,
+        indices,
+        mode: reader.u32()?,
+    })
+}
+
+fn write_color(writer: &mut ByteWriter, color: &Color) {
+    match color {
+        Color::Rgb(colors) => {
+            writer.u32(3);
+            writer.floats(colors);
+        }
+        Color::Rgba(colors) => {
+            writer.u32(4);
+            writer.floats(colors);
+        }
+    }
+}
+
+fn read_color(reader: &mut ByteReader) -> Option<Color> {
+    match reader.u32()? {
+        3 => Some(Color::Rgb(reader.floats()?)),
+        4 => Some(Color::Rgba(reader.floats()?)),
+        _ => None,
+    }
+}
+
+// Little endian, every list is prefixed with its length
+struct ByteWriter {
+    buffer: Vec<u8>,
+}
+
+impl ByteWriter {
+    fn new(magic: &[u8; 4]) -> Self {
+        let mut writer = Self {
+            buffer: magic.to_vec(),
+        };
+        writer.u32(IMPORT_VERSION);
+        writer
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    fn floats<const N: usize>(&mut self, items: &[[f32; N]]) {
+        self.u32(items.len() as u32);
+        for value in items.iter().flatten() {
+            self.f32(*value);
+        }
+    }
+
+    fn optional<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.u32(value.is_some() as u32);
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        // Written next to it and renamed, so a worker reading the artifact while another
+        // one writes it never sees half a file
+        let id = NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed);
+        let temporary = path.with_extension(format!("{}.tmp", id));
+        std::fs::write(&temporary, &self.buffer)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|e| format!("Failed to write import artifact {:?}: {}", path, e))
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        let taken = self
+            .bytes
+            .get(self.offset..self.offset.checked_add(count)?)?;
+        self.offset += count;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        let length = self.u32()? as usize;
+        Some(self.take(length)?.to_vec())
+    }
+
+    fn floats<const N: usize>(&mut self) -> Option<Vec<[f32; N]>> {
+        let count = self.u32()? as usize;
+        let bytes = self.take(count.checked_mul(N * 4)?)?;
+        Some(
+            bytes
+                .chunks_exact(N * 4)
+                .map(|item| {
+                    std::array::from_fn(|i| {
+                        f32::from_le_bytes(item[i * 4..i * 4 + 4].try_into().unwrap())
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    // Some(None) for a value that was written as missing, None when the data ran out
+    fn optional<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.u32()? {
+            0 => Some(None),
+            _ => read(self).map(Some),
+        }
+    }
+}
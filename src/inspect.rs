@@ -0,0 +1,179 @@
+// Properties panel widgets for plain data, #[derive(Inspect)] writes them for whole types
+
+use std::path::PathBuf;
+
+use cgmath::{Point3, Vector3};
+
+pub use cruel_game_engine_derive::Inspect;
+pub use egui; // The derive refers to egui through here so users don't need their own
+
+/// Draws `self` as labeled widgets and returns whether anything was changed.
+pub trait Inspect {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NumberOptions {
+    pub speed: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for NumberOptions {
+    fn default() -> Self {
+        Self {
+            speed: 0.1,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+}
+
+/// A struct's fields, under a collapsing header unless `label` is empty.
+pub fn fields(ui: &mut egui::Ui, label: &str, add: impl FnOnce(&mut egui::Ui) -> bool) -> bool {
+    if label.is_empty() {
+        return add(ui);
+    }
+    egui::CollapsingHeader::new(label)
+        .default_open(true)
+        .show(ui, add)
+        .body_returned
+        .unwrap_or(false)
+}
+
+/// A drag value, or a slider when both ends of the range are set.
+pub fn number<N: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut N,
+    options: NumberOptions,
+) -> bool {
+    let range = N::from_f64(options.min)..=N::from_f64(options.max);
+    if options.min.is_finite() && options.max.is_finite() {
+        return ui
+            .add(egui::Slider::new(value, range).text(label))
+            .changed();
+    }
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(
+            egui::DragValue::new(value)
+                .speed(options.speed)
+                .range(range),
+        )
+        .changed()
+    })
+    .inner
+}
+
+pub trait Color {
+    fn edit(&mut self, ui: &mut egui::Ui) -> bool;
+}
+
+impl Color for [f32; 3] {
+    fn edit(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.color_edit_button_rgb(self).changed()
+    }
+}
+
+impl Color for [f32; 4] {
+    fn edit(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.color_edit_button_rgba_unmultiplied(self).changed()
+    }
+}
+
+pub fn color(ui: &mut egui::Ui, label: &str, value: &mut impl Color) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        value.edit(ui)
+    })
+    .inner
+}
+
+fn xyz(ui: &mut egui::Ui, label: &str, values: [&mut f32; 3]) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for value in values {
+            changed |= ui.add(egui::DragValue::new(value).speed(0.05)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+macro_rules! inspect_number {
+    ($($number:ty),*) => {
+        $(
+            impl Inspect for $number {
+                fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+                    let speed = if <$number as egui::emath::Numeric>::INTEGRAL { 1.0 } else { 0.1 };
+                    number(ui, label, self, NumberOptions { speed, ..Default::default() })
+                }
+            }
+        )*
+    };
+}
+
+inspect_number!(f32, f64, i32, u32, usize);
+
+impl Inspect for bool {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        ui.checkbox(self, label).changed()
+    }
+}
+
+impl Inspect for String {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.text_edit_singleline(self).changed()
+        })
+        .inner
+    }
+}
+
+impl Inspect for PathBuf {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        let mut path = self.to_string_lossy().into_owned();
+        let changed = path.inspect(ui, label);
+        if changed {
+            *self = PathBuf::from(path);
+        }
+        changed
+    }
+}
+
+impl Inspect for [f32; 3] {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        let [x, y, z] = self;
+        xyz(ui, label, [x, y, z])
+    }
+}
+
+impl Inspect for Vector3<f32> {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        xyz(ui, label, [&mut self.x, &mut self.y, &mut self.z])
+    }
+}
+
+impl Inspect for Point3<f32> {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        xyz(ui, label, [&mut self.x, &mut self.y, &mut self.z])
+    }
+}
+
+impl<T: Inspect> Inspect for Option<T> {
+    fn inspect(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        match self {
+            Some(value) => value.inspect(ui, label),
+            None => {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    ui.weak("None");
+                });
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    handles::{MeshHandle, TextureHandle},
+    loader::AssetLoader,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ItemProperty {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Str(String),
+}
+
+/// An item definition asset, stored as RON. Icons and meshes are referenced by asset name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub mesh: Option<String>,
+    pub max_stack: u32,
+    #[serde(default)]
+    pub properties: BTreeMap<String, ItemProperty>,
+
+    // Filled in by `ItemDatabase::resolve_handles` once the assets have loaded
+    #[serde(skip)]
+    pub icon_handle: Option<TextureHandle>,
+    #[serde(skip)]
+    pub mesh_handle: Option<MeshHandle>,
+}
+
+impl ItemDefinition {
+    pub fn new<T: ToString>(id: T, max_stack: u32) -> Self {
+        let id = id.to_string();
+        Self {
+            name: id.clone(),
+            id,
+            description: String::new(),
+            icon: None,
+            mesh: None,
+            max_stack: max_stack.max(1),
+            properties: BTreeMap::new(),
+            icon_handle: None,
+            mesh_handle: None,
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&ItemProperty> {
+        self.properties.get(name)
+    }
+}
+
+#[derive(Default)]
+pub struct ItemDatabase {
+    items: HashMap<String, ItemDefinition>,
+}
+
+impl ItemDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, item: ItemDefinition) {
+        if self.items.contains_key(&item.id) {
+            log::warn!("Item '{}' was registered twice, replacing it", item.id);
+        }
+        self.items.insert(item.id.clone(), item);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.items.values()
+    }
+
+    /// Loads a RON file containing a list of item definitions.
+    pub fn load(&mut self, path: &Path) -> Result<usize, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read items {:?}: {}", path, e))?;
+        let items: Vec<ItemDefinition> = ron::from_str(&contents)
+            .map_err(|e| format!("Failed to parse items {:?}: {}", path, e))?;
+
+        let count = items.len();
+        for item in items {
+            self.register(item);
+        }
+        Ok(count)
+    }
+
+    /// Loads every `.ron` file in `directory`, logging the files that fail.
+    pub fn load_directory(&mut self, directory: &Path) -> Result<usize, String> {
+        let entries = std::fs::read_dir(directory)
+            .map_err(|e| format!("Failed to read item directory {:?}: {}", directory, e))?;
+
+        let mut count = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "ron") {
+                match self.load(&path) {
+                    Ok(loaded) => count += loaded,
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut items: Vec<&ItemDefinition> = self.items.values().collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let contents = ron::ser::to_string_pretty(&items, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize items: {}", e))?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write items {:?}: {}", path, e))
+    }
+
+    /// Looks up icon and mesh handles by asset name, call again after new assets have loaded.
+    pub fn resolve_handles(&mut self, asset_loader: &AssetLoader) {
+        for item in self.items.values_mut() {
+            if let Some(icon) = &item.icon {
+                item.icon_handle = asset_loader
+                    .loaded_texture_data
+                    .iter()
+                    .find(|(_, texture)| &texture.name == icon)
+                    .map(|(handle, _)| *handle);
+            }
+            if let Some(mesh) = &item.mesh {
+                item.mesh_handle = asset_loader
+                    .loaded_mesh_data
+                    .iter()
+                    .find(|(_, loaded_mesh)| &loaded_mesh.name == mesh)
+                    .map(|(handle, _)| *handle);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+/// A fixed number of slots, each holding up to one stack.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    pub slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    pub fn new(slot_count: usize) -> Self {
+        Self {
+            slots: vec![None; slot_count],
+        }
+    }
+
+    /// Adds `count` of `item`, topping up existing stacks first. Returns how many did not fit.
+    pub fn add(&mut self, database: &ItemDatabase, item: &str, count: u32) -> Result<u32, String> {
+        let definition = database
+            .get(item)
+            .ok_or_else(|| format!("Unknown item '{}'", item))?;
+        let max_stack = definition.max_stack.max(1);
+        let mut remaining = count;
+
+        for stack in self.slots.iter_mut().flatten() {
+            if remaining == 0 {
+                break;
+            }
+            if stack.item == item && stack.count < max_stack {
+                let added = remaining.min(max_stack - stack.count);
+                stack.count += added;
+                remaining -= added;
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if remaining == 0 {
+                break;
+            }
+            let added = remaining.min(max_stack);
+            *slot = Some(ItemStack {
+                item: item.to_string(),
+                count: added,
+            });
+            remaining -= added;
+        }
+
+        Ok(remaining)
+    }
+
+    /// Removes up to `count` of `item`, taking from the last stacks first. Returns how many were removed.
+    pub fn remove(&mut self, item: &str, count: u32) -> u32 {
+        let mut removed = 0;
+
+        for slot in self.slots.iter_mut().rev() {
+            if removed == count {
+                break;
+            }
+            if let Some(stack) = slot {
+                if stack.item == item {
+                    let taken = (count - removed).min(stack.count);
+                    stack.count -= taken;
+                    removed += taken;
+                    if stack.count == 0 {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+
+        removed
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    pub fn contains(&self, item: &str, count: u32) -> bool {
+        self.count(item) >= count
+    }
+
+    pub fn free_slots(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_none()).count()
+    }
+
+    pub fn swap_slots(&mut self, a: usize, b: usize) {
+        if a < self.slots.len() && b < self.slots.len() {
+            self.slots.swap(a, b);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+    }
+}
+
+/// Default inventory widget: a grid of slots, clicking one slot and then another swaps them.
+/// `icon` maps an item's icon handle to an egui texture, items without one show their name.
+pub fn inventory_grid(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    inventory: &mut Inventory,
+    database: &ItemDatabase,
+    columns: usize,
+    selected: &mut Option<usize>,
+    icon: impl Fn(TextureHandle) -> Option<egui::TextureId>,
+) {
+    const SLOT_SIZE: f32 = 48.0;
+
+    let mut swap = None;
+
+    egui::Grid::new(id).spacing([4.0, 4.0]).show(ui, |ui| {
+        for (i, slot) in inventory.slots.iter().enumerate() {
+            let definition = slot.as_ref().and_then(|stack| database.get(&stack.item));
+            let texture = definition
+                .and_then(|definition| definition.icon_handle)
+                .and_then(&icon);
+
+            let size = egui::vec2(SLOT_SIZE, SLOT_SIZE);
+            let button = match (slot, texture) {
+                (Some(_), Some(texture)) => egui::Button::image((texture, size)),
+                (Some(stack), None) => egui::Button::new(
+                    egui::RichText::new(
+                        definition.map_or(stack.item.as_str(), |definition| &definition.name),
+                    )
+                    .small(),
+                )
+                .min_size(size),
+                (None, _) => egui::Button::new("").min_size(size),
+            };
+
+            let mut response = ui.add(button.selected(*selected == Some(i)));
+
+            if let Some(stack) = slot {
+                if stack.count > 1 {
+                    ui.painter().text(
+                        response.rect.right_bottom() - egui::vec2(3.0, 2.0),
+                        egui::Align2::RIGHT_BOTTOM,
+                        stack.count.to_string(),
+                        egui::FontId::proportional(12.0),
+                        ui.visuals().strong_text_color(),
+                    );
+                }
+                if let Some(definition) = definition {
+                    response = response
+                        .on_hover_text(format!("{}\n{}", definition.name, definition.description));
+                }
+            }
+
+            if response.clicked() {
+                match selected.take() {
+                    Some(first) if first != i => swap = Some((first, i)),
+                    Some(_) => {}
+                    None => {
+                        if slot.is_some() {
+                            *selected = Some(i);
+                        }
+                    }
+                }
+            }
+
+            if (i + 1) % columns.max(1) == 0 {
+                ui.end_row();
+            }
+        }
+    });
+
+    if let Some((a, b)) = swap {
+        inventory.swap_slots(a, b);
+    }
+}
@@ -0,0 +1,194 @@
+// Work spread over a pool of worker threads. Jobs are named for the profiler and can wait on
+// other jobs. Frame jobs borrow the caller's data and are all done when their scope ends.
+
+use std::{
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+};
+
+use crate::cvars::CVarRegistry;
+
+static GLOBAL: OnceLock<JobSystem> = OnceLock::new();
+
+/// Starts the shared job system with the `job_threads` cvar's worker count. Only the first
+/// call does anything, `global` starts it with the default count if nothing did.
+pub fn init(cvars: &CVarRegistry) {
+    let workers = cvars.get_int("job_threads").max(0) as usize;
+    if GLOBAL.set(JobSystem::new(workers)).is_err() {
+        log::warn!("The job system was already started");
+    }
+}
+
+pub fn global() -> &'static JobSystem {
+    GLOBAL.get_or_init(|| JobSystem::new(0))
+}
+
+// One core is left for the main thread
+fn default_worker_count() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    cores.saturating_sub(1).max(1)
+}
+
+pub struct JobSystem {
+    pool: Arc<rayon::ThreadPool>,
+}
+
+impl JobSystem {
+    /// Starts `workers` threads, 0 picks a count from the number of cores.
+    pub fn new(workers: usize) -> Self {
+        let workers = match workers {
+            0 => default_worker_count(),
+            workers => workers,
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .thread_name(|worker| format!("job-worker-{}", worker))
+            .build()
+            .expect("Failed to start the job system's workers");
+        log::info!("Job system started with {} workers", workers);
+        Self {
+            pool: Arc::new(pool),
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    pub fn spawn(&self, name: &'static str, job: impl FnOnce() + Send + 'static) -> JobHandle {
+        self.spawn_after(name, &[], job)
+    }
+
+    /// Runs `job` once every job in `after` is done.
+    pub fn spawn_after(
+        &self,
+        name: &'static str,
+        after: &[&JobHandle],
+        job: impl FnOnce() + Send + 'static,
+    ) -> JobHandle {
+        let handle = JobHandle(Arc::new(JobState {
+            name,
+            state: Mutex::new(State::default()),
+            finished: Condvar::new(),
+        }));
+        // One more than there are dependencies, so none of them can start it while the rest
+        // are still being added
+        let waiting = Arc::new(Waiting {
+            remaining: AtomicUsize::new(after.len() + 1),
+            job: Mutex::new(Some(Box::new(job))),
+            handle: handle.clone(),
+            pool: Arc::clone(&self.pool),
+        });
+        for dependency in after {
+            let mut state = dependency.0.state.lock().unwrap();
+            if state.done {
+                drop(state);
+                waiting.release();
+            } else {
+                state.waiting.push(Arc::clone(&waiting));
+            }
+        }
+        waiting.release();
+        handle
+    }
+
+    /// Runs `body` on this thread while the jobs it spawns run on the workers, and returns
+    /// once they're all done. They can borrow anything that outlives the call.
+    pub fn scope<'scope, R>(&self, body: impl FnOnce(&FrameJobs<'_, 'scope>) -> R) -> R {
+        self.pool.in_place_scope(|scope| body(&FrameJobs { scope }))
+    }
+}
+
+/// Spawns the jobs of a `JobSystem::scope`.
+pub struct FrameJobs<'a, 'scope> {
+    scope: &'a rayon::Scope<'scope>,
+}
+
+impl<'scope> FrameJobs<'_, 'scope> {
+    pub fn spawn(&self, name: &'static str, job: impl FnOnce() + Send + 'scope) {
+        self.scope.spawn(move |_| {
+            let _span = tracing::info_span!("job", name).entered();
+            job();
+        });
+    }
+}
+
+#[derive(Default)]
+struct State {
+    done: bool,
+    waiting: Vec<Arc<Waiting>>, // Jobs that need this one, started when it's done
+}
+
+struct JobState {
+    name: &'static str,
+    state: Mutex<State>,
+    finished: Condvar,
+}
+
+/// A job spawned with `JobSystem::spawn`, to wait on or start other jobs after.
+#[derive(Clone)]
+pub struct JobHandle(Arc<JobState>);
+
+impl JobHandle {
+    pub fn name(&self) -> &'static str {
+        self.0.name
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.0.state.lock().unwrap().done
+    }
+
+    /// Blocks until the job is done. A job waiting on another one ties up a worker, use
+    /// `spawn_after` there instead.
+    pub fn wait(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        while !state.done {
+            state = self.0.finished.wait(state).unwrap();
+        }
+    }
+
+    fn finish(&self) {
+        let waiting = {
+            let mut state = self.0.state.lock().unwrap();
+            state.done = true;
+            std::mem::take(&mut state.waiting)
+        };
+        self.0.finished.notify_all();
+        for waiting in waiting {
+            waiting.release();
+        }
+    }
+}
+
+// A job that's waiting on `remaining` more dependencies
+struct Waiting {
+    remaining: AtomicUsize,
+    job: Mutex<Option<Box<dyn FnOnce() + Send>>>,
+    handle: JobHandle,
+    pool: Arc<rayon::ThreadPool>,
+}
+
+impl Waiting {
+    fn release(&self) {
+        if self.remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let Some(job) = self.job.lock().unwrap().take() else {
+            return;
+        };
+        let handle = self.handle.clone();
+        self.pool.spawn(move || {
+            {
+                let _span = tracing::info_span!("job", name = handle.name()).entered();
+                // A panicking job still counts as done, or whatever waits on it never runs
+                if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    log::error!("Job {} panicked", handle.name());
+                }
+            }
+            handle.finish();
+        });
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use cruel_game_engine::{
+    particles::PARTICLE_DIRECTORY,
+    runtime::{self, GameManifest, GAME_MANIFEST},
+    tilemap::TILEMAP_DIRECTORY,
+};
+
+const RECENT_PROJECTS_FILE: &str = "recent_projects.ron";
+const MAX_RECENT_PROJECTS: usize = 10;
+
+// Created in every new project, the engine's asset paths are relative to the project
+const PROJECT_DIRECTORIES: [&str; 5] = [
+    "assets",
+    "models",
+    "scripts",
+    TILEMAP_DIRECTORY,
+    PARTICLE_DIRECTORY,
+];
+
+/// Where the editor keeps its own settings, outside of any project.
+pub fn config_directory() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("cruel_game_engine"))
+}
+
+/// Project directories, most recently opened first. Stored in the user's config directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecentProjects {
+    pub projects: Vec<PathBuf>,
+}
+
+impl RecentProjects {
+    /// Empty when nothing was saved yet or the file can't be read.
+    pub fn load() -> Self {
+        let Some(path) = config_directory().map(|directory| directory.join(RECENT_PROJECTS_FILE))
+        else {
+            return Self::default();
+        };
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        ron::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Failed to parse {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let directory = config_directory().ok_or("There is no config directory")?;
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| format!("Failed to create {:?}: {}", directory, e))?;
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize the recent projects: {}", e))?;
+        let path = directory.join(RECENT_PROJECTS_FILE);
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+
+    /// Moves `project` to the front, dropping the oldest past the limit.
+    pub fn add(&mut self, project: &Path) {
+        self.projects.retain(|recent| recent != project);
+        self.projects.insert(0, project.to_path_buf());
+        self.projects.truncate(MAX_RECENT_PROJECTS);
+    }
+}
+
+/// Scaffolds a project in `directory`: the asset folders, a copy of the engine's shaders and
+/// an empty game manifest.
+pub fn create_project(directory: &Path, engine_directory: &Path) -> Result<(), String> {
+    if directory.exists() {
+        return Err(format!("{:?} already exists", directory));
+    }
+    for folder in PROJECT_DIRECTORIES {
+        let path = directory.join(folder);
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+    }
+    runtime::copy_directory(
+        &engine_directory.join("shaders"),
+        &directory.join("shaders"),
+    )?;
+
+    let name = directory.file_name().unwrap_or_default().to_string_lossy();
+    let manifest = GameManifest {
+        title: name.to_string(),
+        camera_position: [0.0, 0.0, 3.0],
+        ..Default::default()
+    };
+    manifest.save(&directory.join(GAME_MANIFEST))
+}
+
+/// The window shown when the editor starts, picks the project to work in.
+pub struct Launcher {
+    recent: RecentProjects,
+    engine_directory: PathBuf, // Where the editor was started, its shaders seed new projects
+    new_name: String,
+    new_location: String,
+    open_path: String,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        let engine_directory = std::env::current_dir().unwrap_or_default();
+        let new_location = engine_directory
+            .parent()
+            .unwrap_or(&engine_directory)
+            .to_string_lossy()
+            .to_string();
+        Self {
+            recent: RecentProjects::load(),
+            engine_directory,
+            new_name: "New Project".to_string(),
+            new_location,
+            open_path: String::new(),
+        }
+    }
+
+    /// Makes `project` the working directory and remembers it.
+    pub fn open(&mut self, project: &Path) -> Result<(), String> {
+        let project = std::fs::canonicalize(project)
+            .map_err(|e| format!("Failed to open project {:?}: {}", project, e))?;
+        std::env::set_current_dir(&project)
+            .map_err(|e| format!("Failed to open project {:?}: {}", project, e))?;
+        self.recent.add(&project);
+        if let Err(e) = self.recent.save() {
+            log::error!("{}", e);
+        }
+        log::info!("Opened project {:?}", project);
+        Ok(())
+    }
+
+    /// Returns the project picked this frame, it still has to be opened with `open`. Closing
+    /// the window keeps the editor in the directory it was started in.
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool) -> Option<PathBuf> {
+        let mut picked = None;
+        egui::Window::new("🚀 Projects")
+            .open(open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.heading("Recent");
+                if self.recent.projects.is_empty() {
+                    ui.label("No recent projects");
+                }
+                let mut forget = None;
+                for (index, project) in self.recent.projects.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let exists = project.is_dir();
+                        let name = project.file_name().unwrap_or_default().to_string_lossy();
+                        if ui.add_enabled(exists, egui::Button::new(name)).clicked() {
+                            picked = Some(project.clone());
+                        }
+                        ui.weak(project.to_string_lossy());
+                        if ui.small_button("✖").on_hover_text("Forget").clicked() {
+                            forget = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = forget {
+                    self.recent.projects.remove(index);
+                    if let Err(e) = self.recent.save() {
+                        log::error!("{}", e);
+                    }
+                }
+
+                ui.separator();
+                ui.heading("Open");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.open_path);
+                    if ui.button("Open").clicked() {
+                        picked = Some(PathBuf::from(&self.open_path));
+                    }
+                });
+
+                ui.separator();
+                ui.heading("New Project");
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut self.new_name);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Location:");
+                    ui.text_edit_singleline(&mut self.new_location);
+                });
+                if ui.button("Create").clicked() {
+                    let directory = Path::new(&self.new_location).join(&self.new_name);
+                    match create_project(&directory, &self.engine_directory) {
+                        Ok(()) => {
+                            log::info!("Created project {:?}", directory);
+                            picked = Some(directory);
+                        }
+                        Err(e) => log::error!("{}", e),
+                    }
+                }
+            });
+        picked
+    }
+}
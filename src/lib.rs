@@ -0,0 +1,81 @@
+// The engine, shared by the editor (main.rs) and the standalone game runtime (bin/runtime.rs)
+
+// So #[derive(Inspect)] can name ::cruel_game_engine from inside the engine too
+extern crate self as cruel_game_engine;
+
+pub mod graphics;
+
+pub mod data;
+pub mod handles;
+
+pub mod shaders;
+
+pub mod cvars;
+pub mod logging;
+pub mod compression;
+pub mod jobs;
+pub mod rng;
+
+pub mod import;
+pub mod loader;
+pub mod pack;
+pub mod upload;
+
+pub mod ecs;
+
+pub mod textures;
+pub mod viewport;
+
+pub mod camera;
+pub mod environment;
+pub mod material;
+pub mod foliage;
+pub mod mesh;
+pub mod opengl;
+pub mod error;
+pub mod gl_debug;
+pub mod gl_state;
+pub mod geometry;
+
+pub mod scene_graph;
+pub mod batching;
+pub mod scene_file;
+pub mod component;
+pub mod inspect;
+pub mod streaming;
+pub mod scene_preview;
+pub mod raycast;
+pub mod socket;
+pub mod spatial;
+
+pub mod dialogue;
+pub mod inventory;
+pub mod stats;
+
+pub mod platform;
+pub mod window;
+pub mod telemetry;
+pub mod capture;
+
+pub mod accessibility;
+pub mod photo_mode;
+pub mod fog_of_war;
+
+pub mod transform;
+pub mod particles;
+pub mod sprites;
+pub mod tilemap;
+pub mod text;
+pub mod game_ui;
+pub mod view_mode;
+
+pub mod gpu_timer;
+pub mod render_graph;
+pub mod render_list;
+
+pub mod runtime;
+
+#[cfg(feature = "gameplay")]
+pub mod gameplay;
+#[cfg(feature = "gameplay")]
+pub mod grid_map;
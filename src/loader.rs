@@ -1,18 +1,85 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Condvar, Mutex, RwLock},
 };
 
 use crate::{
+    compression::TextureCompression,
     data::*,
-    handles::{AssetHandle, MaterialHandle, MeshHandle, ShaderHandle, TextureHandle},
+    error::{EngineError, EngineResult},
+    geometry,
+    handles::{
+        AssetHandle, MaterialHandle, MeshHandle, ParticleEffectHandle, ShaderHandle, TextureHandle,
+    },
+    import,
+    pack::{self, AssetPack},
+    particles::ParticleEffect,
+    upload::UploadQueue,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use gltf::{buffer::Source, Gltf, mesh::util::ReadColors};
 
-pub fn load_gltf_full(path: &Path) -> Result<LoadedMesh, String> {
-    let gltf = Gltf::open(path).map_err(|e| format!("GLTF open error: {:?}", e))?;
+const MAX_DEFAULT_WORKERS: usize = 4;
+
+// Material textures are relative to the glTF file
+fn image_path(directory: &Path, texture: gltf::Texture) -> Option<PathBuf> {
+    match texture.source().source() {
+        gltf::image::Source::Uri { uri, .. } => Some(directory.join(uri)),
+        gltf::image::Source::View { .. } => None, // Embedded images not supported here yet
+    }
+}
+
+// Different spellings of the same file share one cache entry
+fn path_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// One core is left for the main thread, more than a few workers mostly wait on the disk
+fn default_worker_count() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    cores.saturating_sub(1).clamp(1, MAX_DEFAULT_WORKERS)
+}
+
+// Vertex and index data held in memory, not counting the GPU copies
+fn primitive_bytes(primitive: &LoadedPrimitive) -> usize {
+    let vertex_data = &primitive.vertex_data;
+    let optional = |len: Option<usize>, size: usize| len.unwrap_or(0) * size;
+    vertex_data.positions.len() * 12
+        + optional(vertex_data.normals.as_ref().map(Vec::len), 12)
+        + optional(vertex_data.tangents.as_ref().map(Vec::len), 16)
+        + optional(vertex_data.joints.as_ref().map(Vec::len), 8)
+        + optional(vertex_data.weights.as_ref().map(Vec::len), 16)
+        + vertex_data.texcoords.iter().map(|uv| uv.0.len() * 8).sum::<usize>()
+        + vertex_data.colors.len() * vertex_data.positions.len() * 16 // At most RGBA floats
+        + optional(primitive.indices.as_ref().map(Vec::len), 4)
+}
+
+// Meshes named like "Rock_LOD2" are lower detail versions of the rest of the file
+fn lod_level(mesh_name: &str) -> Option<usize> {
+    let (_, suffix) = mesh_name.rsplit_once('_')?;
+    let level = suffix
+        .strip_prefix("LOD")
+        .or_else(|| suffix.strip_prefix("lod"))?
+        .parse()
+        .ok()?;
+    (level > 0).then_some(level)
+}
+
+#[tracing::instrument(skip_all, fields(path = ?path))]
+pub fn load_gltf_full(
+    path: &Path,
+    source: &[u8],
+    packs: &[AssetPack],
+) -> EngineResult<LoadedMesh> {
+    let asset_error = |message: String| EngineError::Asset {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let gltf =
+        Gltf::from_slice(source).map_err(|e| asset_error(format!("GLTF open error: {}", e)))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
 
     let mut raw_buffers = Vec::new();
     let blob = gltf.blob.as_ref().cloned();
@@ -21,19 +88,22 @@ pub fn load_gltf_full(path: &Path) -> Result<LoadedMesh, String> {
     for buffer in gltf.buffers() {
         let data = match buffer.source() {
             Source::Uri(uri) => {
-                let buf_path = path.parent().unwrap().join(uri);
-                std::fs::read(&buf_path).map_err(|e| format!("Buffer read error: {:?}", e))?
+                pack::read_asset(packs, &directory.join(uri))?
             }
             Source::Bin => blob
                 .clone()
-                .ok_or_else(|| "GLB binary chunk missing".to_string())?,
+                .ok_or_else(|| asset_error("GLB binary chunk missing".to_string()))?,
         };
         raw_buffers.push(data);
     }
 
     let mut primitives = Vec::new();
+    let mut lods: Vec<Vec<LoadedPrimitive>> = Vec::new();
+    let mut mesh_primitives = Vec::new(); // Per glTF mesh, where its primitives ended up
 
     for mesh in gltf.meshes() {
+        let lod = mesh.name().and_then(lod_level);
+        mesh_primitives.push(Vec::new());
         for primitive in mesh.primitives() {
             let reader = primitive.reader(|buffer| {
                 let index = buffer.index();
@@ -54,7 +124,7 @@ pub fn load_gltf_full(path: &Path) -> Result<LoadedMesh, String> {
             if let Some(position_iter) = reader.read_positions() {
                 vertex_data.positions = position_iter.collect();
             } else {
-                return Err("GLTF mesh is missing positions!".into());
+                return Err(asset_error("GLTF mesh is missing positions!".to_string()));
             }
 
             let vertex_count = vertex_data.positions.len();
@@ -102,8 +172,21 @@ pub fn load_gltf_full(path: &Path) -> Result<LoadedMesh, String> {
                     ReadColors::RgbaF32(rgba) => {
                         vertex_data.colors.push(Color::Rgba(rgba.collect()));
                     }
-                    ReadColors::RgbU16(iter) => todo!(),
-                    ReadColors::RgbaU16(iter) => todo!(),
+                    ReadColors::RgbU16(rgb) => {
+                        vertex_data.colors.push(Color::Rgb(rgb.map(|c| [
+                            c[0] as f32 / 65535.0,
+                            c[1] as f32 / 65535.0,
+                            c[2] as f32 / 65535.0,
+                        ]).collect()));
+                    }
+                    ReadColors::RgbaU16(rgba) => {
+                        vertex_data.colors.push(Color::Rgba(rgba.map(|c| [
+                            c[0] as f32 / 65535.0,
+                            c[1] as f32 / 65535.0,
+                            c[2] as f32 / 65535.0,
+                            c[3] as f32 / 65535.0,
+                        ]).collect()));
+                    }
                 }
             }
 
@@ -116,76 +199,164 @@ pub fn load_gltf_full(path: &Path) -> Result<LoadedMesh, String> {
             }
 
             // Indices:
-            let indices = reader.read_indices().map(|idx| idx.into_u32().collect());
+            let indices: Option<Vec<u32>> = reader.read_indices().map(|idx| idx.into_u32().collect());
+
+            let triangles = primitive.mode() == gltf::mesh::Mode::Triangles;
+
+            // Without normals there's nothing to light with
+            if vertex_data.normals.is_none() && triangles {
+                log::debug!("Generating normals for {:?}", mesh.name());
+                vertex_data.normals = Some(geometry::generate_normals(
+                    &vertex_data.positions,
+                    indices.as_deref(),
+                ));
+            }
+
+            // Normal maps need tangents, make them when the exporter didn't
+            if let (None, Some(normals), Some(uvs), true) = (
+                &vertex_data.tangents,
+                &vertex_data.normals,
+                vertex_data.texcoords.first(),
+                triangles,
+            ) {
+                vertex_data.tangents = Some(geometry::generate_tangents(
+                    &vertex_data.positions,
+                    normals,
+                    &uvs.0,
+                    indices.as_deref(),
+                ));
+            }
 
             // Material (optional):
             let material = primitive.material();
             let pbr = material.pbr_metallic_roughness();
 
+            // The gltf crate doesn't know clearcoat yet, read it from the JSON
+            let clearcoat = material.extension_value("KHR_materials_clearcoat");
+            let clearcoat_factor = |name: &str| {
+                clearcoat
+                    .and_then(|clearcoat| clearcoat.get(name)?.as_f64())
+                    .unwrap_or(0.0) as f32
+            };
+
             let loaded_material = Some(LoadedMaterial {
-                base_color_texture: pbr.base_color_texture().and_then(|info| {
-                    let image = info.texture().source();
-                    match image.source() {
-                        gltf::image::Source::Uri { uri, .. } => Some(PathBuf::from(uri)),
-                        gltf::image::Source::View { .. } => None, // Embedded images not supported here yet
-                    }
-                }),
-                metallic_roughness_texture: pbr.metallic_roughness_texture().and_then(|info| {
-                    let image = info.texture().source();
-                    match image.source() {
-                        gltf::image::Source::Uri { uri, .. } => Some(PathBuf::from(uri)),
-                        gltf::image::Source::View { .. } => None,
-                    }
-                }),
-                normal_texture: material.normal_texture().and_then(|info| {
-                    let image = info.texture().source();
-                    match image.source() {
-                        gltf::image::Source::Uri { uri, .. } => Some(PathBuf::from(uri)),
-                        gltf::image::Source::View { .. } => None,
-                    }
-                }),
-                occlusion_texture: material.occlusion_texture().and_then(|info| {
-                    let image = info.texture().source();
-                    match image.source() {
-                        gltf::image::Source::Uri { uri, .. } => Some(PathBuf::from(uri)),
-                        gltf::image::Source::View { .. } => None,
-                    }
-                }),
-                emissive_texture: material.emissive_texture().and_then(|info| {
-                    let image = info.texture().source();
-                    match image.source() {
-                        gltf::image::Source::Uri { uri, .. } => Some(PathBuf::from(uri)),
-                        gltf::image::Source::View { .. } => None,
-                    }
-                }),
+                base_color_texture: pbr
+                    .base_color_texture()
+                    .and_then(|info| image_path(directory, info.texture())),
+                metallic_roughness_texture: pbr
+                    .metallic_roughness_texture()
+                    .and_then(|info| image_path(directory, info.texture())),
+                normal_texture: material
+                    .normal_texture()
+                    .and_then(|info| image_path(directory, info.texture())),
+                occlusion_texture: material
+                    .occlusion_texture()
+                    .and_then(|info| image_path(directory, info.texture())),
+                emissive_texture: material
+                    .emissive_texture()
+                    .and_then(|info| image_path(directory, info.texture())),
+                normal_texcoord: material
+                    .normal_texture()
+                    .map_or(0, |info| info.tex_coord()),
+                occlusion_texcoord: material
+                    .occlusion_texture()
+                    .map_or(0, |info| info.tex_coord()),
                 base_color_factor: Color::Rgba(vec![pbr.base_color_factor()]),
                 metallic_factor: pbr.metallic_factor(),
                 roughness_factor: pbr.roughness_factor(),
+                occlusion_strength: material
+                    .occlusion_texture()
+                    .map_or(1.0, |info| info.strength()),
+                emissive_factor: material.emissive_factor(),
+                emissive_strength: material.emissive_strength().unwrap_or(1.0),
+                clearcoat_factor: clearcoat_factor("clearcoatFactor"),
+                clearcoat_roughness_factor: clearcoat_factor("clearcoatRoughnessFactor"),
+                transmission_factor: material
+                    .transmission()
+                    .map_or(0.0, |transmission| transmission.transmission_factor()),
                 alpha_mode: matches!(material.alpha_mode(), gltf::material::AlphaMode::Blend),
                 double_sided: material.double_sided(),
             });
 
-            primitives.push(LoadedPrimitive {
+            let loaded = LoadedPrimitive {
                 vertex_data,
                 material: loaded_material,
                 indices,
-            });
+                mode: primitive.mode().as_gl_enum(),
+            };
+            match lod {
+                Some(level) => {
+                    if lods.len() < level {
+                        lods.resize_with(level, Vec::new);
+                    }
+                    lods[level - 1].push(loaded);
+                }
+                None => {
+                    mesh_primitives[mesh.index()].push(primitives.len());
+                    primitives.push(loaded);
+                }
+            }
         }
     }
 
+    // A file with gaps, like LOD1 and LOD3, keeps the levels it has
+    lods.retain(|level| !level.is_empty());
+
     Ok(LoadedMesh {
-        name: path.file_name().unwrap().to_string_lossy().into_owned(),
+        name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
         path: path.to_path_buf(),
         primitives,
+        lods,
+        nodes: load_gltf_nodes(&gltf, &mesh_primitives),
     })
 }
 
+// The default scene depth first so parents come before their children. Nodes holding only
+// a LOD mesh are left out, the levels belong to the whole file.
+fn load_gltf_nodes(gltf: &Gltf, mesh_primitives: &[Vec<usize>]) -> Vec<LoadedNode> {
+    let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) else {
+        return Vec::new();
+    };
+
+    let mut nodes = Vec::new();
+    let mut stack: Vec<(gltf::Node, Option<usize>)> =
+        scene.nodes().map(|node| (node, None)).collect();
+    stack.reverse();
+    while let Some((node, parent)) = stack.pop() {
+        let primitives = node
+            .mesh()
+            .map_or_else(Vec::new, |mesh| mesh_primitives[mesh.index()].clone());
+        let mut children: Vec<_> = node.children().collect();
+        if node.mesh().is_some() && primitives.is_empty() && children.is_empty() {
+            continue;
+        }
+
+        let (translation, rotation, scale) = node.transform().decomposed();
+        nodes.push(LoadedNode {
+            name: node
+                .name()
+                .map_or_else(|| format!("Node {}", node.index()), str::to_string),
+            parent,
+            primitives,
+            translation,
+            rotation,
+            scale,
+        });
+
+        let index = nodes.len() - 1;
+        children.reverse();
+        stack.extend(children.into_iter().map(|child| (child, Some(index))));
+    }
+    nodes
+}
+
 #[derive(Debug)]
 pub enum Asset {
     Texture(LoadedTexture),
     Mesh(LoadedMesh),
     Material(LoadedMaterial),
     Shader(CompiledShaderProgram),
+    ParticleEffect(ParticleEffect),
     // ...
 }
 
@@ -221,116 +392,240 @@ impl Asset {
             None
         }
     }
+
+    pub fn into_particle_effect(self) -> Option<ParticleEffect> {
+        if let Asset::ParticleEffect(effect) = self {
+            Some(effect)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a requested asset is on its way to `poll_loaded`, in the order they happen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadStage {
+    Queued,
+    Started,
+    Read { bytes: usize }, // The source, or its import artifact, is in memory
+    Decoded,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl LoadStage {
+    /// Rough share of the work behind this stage, for progress bars.
+    pub fn fraction(&self) -> f32 {
+        match self {
+            LoadStage::Queued => 0.0,
+            LoadStage::Started => 0.1,
+            LoadStage::Read { .. } => 0.4,
+            LoadStage::Decoded => 0.9,
+            LoadStage::Done | LoadStage::Failed(_) | LoadStage::Cancelled => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetProgress {
+    pub handle: AssetHandle,
+    pub path: PathBuf,
+    pub stage: LoadStage,
+}
+
+/// Each worker takes the oldest request of the highest priority waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AssetPriority {
+    Blocking,   // Something is waiting on it right now, goes before everything else
+    High,       // Asked for by the user, like a mesh dropped into the scene
+    Background, // Thumbnails and prefetching
 }
 
 pub enum AssetRequest {
-    LoadTexture((PathBuf, String)),
-    LoadMesh((PathBuf, String)),
+    LoadTexture((TextureHandle, PathBuf, String)),
+    LoadMesh((MeshHandle, PathBuf, String)),
+    LoadParticleEffect(PathBuf),
     // ...
 }
 
+impl AssetRequest {
+    fn handle(&self) -> Option<AssetHandle> {
+        match self {
+            AssetRequest::LoadTexture((handle, _, _)) => Some(AssetHandle::Texture(*handle)),
+            AssetRequest::LoadMesh((handle, _, _)) => Some(AssetHandle::Mesh(*handle)),
+            AssetRequest::LoadParticleEffect(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingRequests {
+    requests: VecDeque<(AssetPriority, AssetRequest)>,
+    cancelled: HashSet<AssetHandle>, // Already started when cancelled, their results get dropped
+    closed: bool,
+}
+
+// Shared with the workers, which sleep on the condvar while there's nothing to do
+#[derive(Clone, Default)]
+struct RequestQueue(Arc<(Mutex<PendingRequests>, Condvar)>);
+
+impl RequestQueue {
+    fn push(&self, priority: AssetPriority, request: AssetRequest) {
+        let (pending, wake) = &*self.0;
+        pending.lock().unwrap().requests.push_back((priority, request));
+        wake.notify_one();
+    }
+
+    // Blocks until there's a request, None once the loader is gone
+    fn pop(&self) -> Option<AssetRequest> {
+        let (pending, wake) = &*self.0;
+        let mut pending = pending.lock().unwrap();
+        loop {
+            if pending.closed {
+                return None;
+            }
+            // min_by_key keeps the first of equal priorities, so each priority stays in order
+            let next = (0..pending.requests.len()).min_by_key(|&i| pending.requests[i].0);
+            if let Some(index) = next {
+                return pending.requests.remove(index).map(|(_, request)| request);
+            }
+            pending = wake.wait(pending).unwrap();
+        }
+    }
+
+    // A repeated request for something still queued can move it up, never down
+    fn raise(&self, handle: AssetHandle, priority: AssetPriority) {
+        let mut pending = self.0 .0.lock().unwrap();
+        for (queued, request) in pending.requests.iter_mut() {
+            if request.handle() == Some(handle) {
+                *queued = (*queued).min(priority);
+            }
+        }
+    }
+
+    // Returns false when the request isn't queued anymore, it might be loading already
+    fn remove(&self, handle: AssetHandle) -> bool {
+        let mut pending = self.0 .0.lock().unwrap();
+        let before = pending.requests.len();
+        pending.requests.retain(|(_, request)| request.handle() != Some(handle));
+        pending.requests.len() != before
+    }
+
+    fn cancel_started(&self, handle: AssetHandle) {
+        self.0 .0.lock().unwrap().cancelled.insert(handle);
+    }
+
+    fn is_cancelled(&self, handle: AssetHandle) -> bool {
+        self.0 .0.lock().unwrap().cancelled.contains(&handle)
+    }
+
+    fn take_cancelled(&self, handle: AssetHandle) -> bool {
+        self.0 .0.lock().unwrap().cancelled.remove(&handle)
+    }
+
+    fn close(&self) {
+        let (pending, wake) = &*self.0;
+        pending.lock().unwrap().closed = true;
+        wake.notify_all();
+    }
+}
+
+// What every loader worker gets a copy of
+#[derive(Clone)]
+struct WorkerShared {
+    requests: RequestQueue,
+    result_tx: Sender<(AssetHandle, Asset)>,
+    progress_tx: Sender<AssetProgress>,
+    next_handle_id: Arc<Mutex<usize>>,
+    texture_compression: Arc<Mutex<TextureCompression>>,
+    packs: Arc<RwLock<Vec<AssetPack>>>,
+}
+
 pub struct AssetLoader {
-    request_tx: Sender<AssetRequest>,
+    requests: RequestQueue,
     result_rx: Receiver<(AssetHandle, Asset)>,
+    progress_tx: Sender<AssetProgress>,
+    progress_rx: Receiver<AssetProgress>,
+    in_flight: HashMap<AssetHandle, AssetProgress>, // Latest stage of every unfinished request
+    failures: HashMap<AssetHandle, String>,          // Why requests failed, handles aren't reused
 
     next_handle_id: Arc<Mutex<usize>>,
+    texture_paths: HashMap<PathBuf, TextureHandle>,
+    mesh_paths: HashMap<PathBuf, MeshHandle>,
+    texture_compression: Arc<Mutex<TextureCompression>>, // Read by the workers per texture
+    packs: Arc<RwLock<Vec<AssetPack>>>, // Searched before loose files, newest first
+    references: HashMap<AssetHandle, usize>, // Uses in scenes, unloaded when it drops to zero
+    dependencies: HashMap<AssetHandle, Vec<AssetHandle>>, // Released along with their owner
+
+    pub uploads: Mutex<UploadQueue>, // GPU uploads of meshes built from the loaded data
 
     pub loaded_texture_data: HashMap<TextureHandle, LoadedTexture>,
     pub loaded_mesh_data: HashMap<MeshHandle, LoadedMesh>,
     pub loaded_material_data: HashMap<MaterialHandle, LoadedMaterial>,
     pub compiled_shader_programs: HashMap<ShaderHandle, CompiledShaderProgram>,
+    pub loaded_particle_effects: HashMap<ParticleEffectHandle, ParticleEffect>,
 }
 
 impl AssetLoader {
-    pub fn new() -> Self {
-        let (request_tx, request_rx) = unbounded::<AssetRequest>();
+    /// Starts `workers` loader threads, 0 picks a count from the number of cores.
+    pub fn new(workers: usize) -> Self {
+        let workers = match workers {
+            0 => default_worker_count(),
+            workers => workers,
+        };
+        let requests = RequestQueue::default();
         let (result_tx, result_rx) = unbounded::<(AssetHandle, Asset)>();
+        let (progress_tx, progress_rx) = unbounded::<AssetProgress>();
 
+        // Shared with the workers so they can generate handles
         let next_handle_id = Arc::new(Mutex::new(0usize));
 
-        // Pass next_handle_id to the loader thread so it can generate handles.
-        let thread_next_handle_id = Arc::clone(&next_handle_id);
-
-        std::thread::spawn(move || {
-            for request in request_rx {
-                match request {
-                    AssetRequest::LoadTexture((path, name)) => {
-                        println!("Loader thread: Loading texture {:?}", path);
-
-                        let img = match image::open(&path) {
-                            Ok(i) => i.flipv().to_rgba8(),
-                            Err(e) => {
-                                eprintln!("Failed to load image {:?}: {:?}", path, e);
-                                continue;
-                            }
-                        };
-
-                        let (width, height) = img.dimensions();
-                        let data = img.into_raw();
+        let texture_compression = Arc::new(Mutex::new(TextureCompression::None));
 
-                        let loaded_texture = LoadedTexture {
-                            path: path.clone(),
-                            name,
-                            width,
-                            height,
-                            data,
-                        };
-
-                        let texture_handle = {
-                            let mut id = thread_next_handle_id.lock().unwrap();
-                            let handle = TextureHandle(*id as usize);
-                            *id += 1;
-                            handle
-                        };
+        let packs = Arc::new(RwLock::new(Vec::new()));
 
-                        if let Err(e) = result_tx.send((
-                            AssetHandle::Texture(texture_handle),
-                            Asset::Texture(loaded_texture),
-                        )) {
-                            eprintln!("Failed to send loaded texture: {:?}", e);
-                            break;
-                        }
-                    }
+        let shared = WorkerShared {
+            requests: requests.clone(),
+            result_tx,
+            progress_tx: progress_tx.clone(),
+            next_handle_id: Arc::clone(&next_handle_id),
+            texture_compression: Arc::clone(&texture_compression),
+            packs: Arc::clone(&packs),
+        };
 
-                    AssetRequest::LoadMesh((path, name)) => {
-                        println!("Loader thread: Loading mesh {:?}", path);
-
-                        match load_gltf_full(&path) {
-                            Ok(mut loaded_mesh) => {
-                                loaded_mesh.name = name;
-
-                                let mesh_handle = {
-                                    let mut id = thread_next_handle_id.lock().unwrap();
-                                    let handle = MeshHandle(*id as usize);
-                                    *id += 1;
-                                    handle
-                                };
-
-                                if let Err(e) = result_tx.send((
-                                    AssetHandle::Mesh(mesh_handle),
-                                    Asset::Mesh(loaded_mesh),
-                                )) {
-                                    eprintln!("Failed to send loaded mesh: {:?}", e);
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to load mesh {:?}: {:?}", path, e);
-                            }
-                        }
-                    }
-                }
+        // Every worker takes the next request from the shared queue, so they load in parallel
+        // but still in priority order
+        for worker in 0..workers {
+            let shared = shared.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("asset-loader-{}", worker))
+                .spawn(move || run_worker(shared));
+            if let Err(e) = spawned {
+                log::error!("Failed to start asset loader worker {}: {}", worker, e);
             }
-        });
+        }
 
         Self {
-            request_tx,
+            requests,
             result_rx,
+            progress_tx,
+            progress_rx,
+            in_flight: HashMap::new(),
+            failures: HashMap::new(),
             next_handle_id,
+            texture_paths: HashMap::new(),
+            mesh_paths: HashMap::new(),
+            texture_compression,
+            packs,
+            references: HashMap::new(),
+            dependencies: HashMap::new(),
+            uploads: Mutex::new(UploadQueue::new()),
             loaded_texture_data: HashMap::new(),
             loaded_mesh_data: HashMap::new(),
             loaded_material_data: HashMap::new(),
             compiled_shader_programs: HashMap::new(),
+            loaded_particle_effects: HashMap::new(),
         }
     }
 
@@ -348,33 +643,454 @@ impl AssetLoader {
         handle
     }
 
-    /// Request an async load of a texture.
-    pub fn request_texture<P: AsRef<std::path::Path>>(&self, path: P, name: String) {
+    /// Shared with the workers, textures requested after a change use the new setting.
+    pub fn texture_compression(&self) -> Arc<Mutex<TextureCompression>> {
+        Arc::clone(&self.texture_compression)
+    }
+
+    fn queued(&self, handle: AssetHandle, path: &Path) {
+        let _ = self.progress_tx.send(AssetProgress {
+            handle,
+            path: path.to_path_buf(),
+            stage: LoadStage::Queued,
+        });
+    }
+
+    /// Requests that haven't finished or failed yet, as of the last `poll_loaded`.
+    pub fn in_flight(&self) -> impl Iterator<Item = &AssetProgress> {
+        self.in_flight.values()
+    }
+
+    /// The error a request failed with, once `poll_loaded` has seen it fail.
+    pub fn failure(&self, handle: AssetHandle) -> Option<&str> {
+        self.failures.get(&handle).map(String::as_str)
+    }
+
+    /// Later requests look in the pack before the loose files, and in packs mounted after it
+    /// before this one.
+    pub fn mount_pack(&mut self, path: &Path) -> Result<(), String> {
+        let pack = AssetPack::open(path)?;
+        log::info!("Mounted asset pack {:?} with {} files", path, pack.len());
+        self.packs.write().unwrap().insert(0, pack);
+        Ok(())
+    }
+
+    /// Request an async load of a texture. A path that was already requested gets the same
+    /// handle back and isn't loaded again.
+    pub fn request_texture<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        name: String,
+    ) -> TextureHandle {
+        self.request_texture_with_priority(path, name, AssetPriority::High)
+    }
+
+    pub fn request_texture_with_priority<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        name: String,
+        priority: AssetPriority,
+    ) -> TextureHandle {
+        let path_buf = path.as_ref().to_path_buf();
+        let key = path_key(&path_buf);
+        if let Some(handle) = self.texture_paths.get(&key) {
+            self.requests.raise(AssetHandle::Texture(*handle), priority);
+            return *handle;
+        }
+
+        let handle = self.generate_texture_handle();
+        self.texture_paths.insert(key, handle);
+        self.queued(AssetHandle::Texture(handle), &path_buf);
+        self.requests
+            .push(priority, AssetRequest::LoadTexture((handle, path_buf, name)));
+        handle
+    }
+
+    pub fn request_mesh<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        name: String,
+    ) -> MeshHandle {
+        self.request_mesh_with_priority(path, name, AssetPriority::High)
+    }
+
+    pub fn request_mesh_with_priority<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        name: String,
+        priority: AssetPriority,
+    ) -> MeshHandle {
         let path_buf = path.as_ref().to_path_buf();
-        if let Err(e) = self
-            .request_tx
-            .send(AssetRequest::LoadTexture((path_buf, name)))
-        {
-            eprintln!("AssetLoader: Failed to send load request: {:?}", e);
+        let key = path_key(&path_buf);
+        if let Some(handle) = self.mesh_paths.get(&key) {
+            self.requests.raise(AssetHandle::Mesh(*handle), priority);
+            return *handle;
+        }
+
+        let handle = self.generate_mesh_handle();
+        self.mesh_paths.insert(key, handle);
+        self.queued(AssetHandle::Mesh(handle), &path_buf);
+        self.requests
+            .push(priority, AssetRequest::LoadMesh((handle, path_buf, name)));
+        handle
+    }
+
+    /// The path a mesh was requested with, made absolute when the file exists.
+    pub fn mesh_path(&self, handle: MeshHandle) -> Option<&Path> {
+        self.mesh_paths
+            .iter()
+            .find(|(_, mesh)| **mesh == handle)
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// How many times the textures and meshes loaded from `path`, or from anywhere under it
+    /// for a directory, are retained.
+    pub fn uses_of_path(&self, path: &Path) -> usize {
+        let key = path_key(path);
+        let textures = self
+            .texture_paths
+            .iter()
+            .filter(|(path, _)| path.starts_with(&key))
+            .map(|(_, handle)| AssetHandle::Texture(*handle));
+        let meshes = self
+            .mesh_paths
+            .iter()
+            .filter(|(path, _)| path.starts_with(&key))
+            .map(|(_, handle)| AssetHandle::Mesh(*handle));
+        textures
+            .chain(meshes)
+            .map(|handle| self.references.get(&handle).copied().unwrap_or(0))
+            .sum()
+    }
+
+    /// Moves a file or directory on disk. What was loaded from it keeps its handle, and
+    /// requesting the new path gives that handle back.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        // Has to be made absolute while it still exists
+        let from_key = path_key(from);
+        std::fs::rename(from, to)
+            .map_err(|e| format!("Failed to move {:?} to {:?}: {}", from, to, e))?;
+        let to_key = path_key(to);
+        let moved = |path: &Path| {
+            path.strip_prefix(&from_key)
+                .ok()
+                .map(|rest| to_key.join(rest))
+        };
+
+        for (path, handle) in std::mem::take(&mut self.texture_paths) {
+            let Some(path) = moved(&path) else {
+                self.texture_paths.insert(path, handle);
+                continue;
+            };
+            if let Some(texture) = self.loaded_texture_data.get_mut(&handle) {
+                texture.path = path.clone();
+            }
+            self.texture_paths.insert(path, handle);
+        }
+        for (path, handle) in std::mem::take(&mut self.mesh_paths) {
+            let Some(path) = moved(&path) else {
+                self.mesh_paths.insert(path, handle);
+                continue;
+            };
+            if let Some(mesh) = self.loaded_mesh_data.get_mut(&handle) {
+                mesh.path = path.clone();
+            }
+            self.mesh_paths.insert(path, handle);
         }
+        Ok(())
     }
 
-    pub fn request_mesh<P: AsRef<std::path::Path>>(&self, path: P, name: String) {
+    pub fn request_particle_effect<P: AsRef<std::path::Path>>(&self, path: P) {
         let path_buf = path.as_ref().to_path_buf();
-        if let Err(e) = self
-            .request_tx
-            .send(AssetRequest::LoadMesh((path_buf, name)))
-        {
-            eprintln!("AssetLoader: Failed to send mesh load request: {:?}", e);
+        self.requests
+            .push(AssetPriority::High, AssetRequest::LoadParticleEffect(path_buf));
+    }
+
+    /// Abandons a texture or mesh request that hasn't finished yet, nothing is returned by
+    /// `poll_loaded` for it afterwards. Returns false if it already finished or failed.
+    pub fn cancel(&mut self, handle: AssetHandle) -> bool {
+        self.drain_progress();
+        if !self.in_flight.contains_key(&handle) {
+            return false;
+        }
+
+        if !self.requests.remove(handle) {
+            // A worker has it and drops the result when it's done
+            self.requests.cancel_started(handle);
+        }
+        self.forget(handle);
+        true
+    }
+
+    /// Counts a use of an asset, like a static mesh placed in a scene.
+    pub fn retain(&mut self, handle: AssetHandle) {
+        *self.references.entry(handle).or_default() += 1;
+    }
+
+    /// Takes back a `retain`, the asset is unloaded with its last use. Returns whether it was.
+    pub fn release(&mut self, handle: AssetHandle) -> bool {
+        let Some(count) = self.references.get_mut(&handle) else {
+            log::warn!("Released {:?} more often than it was retained", handle);
+            return false;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        self.unload(handle);
+        true
+    }
+
+    /// Keeps `dependency` loaded for as long as `owner` is, like the normal maps of a mesh.
+    pub fn add_dependency(&mut self, owner: AssetHandle, dependency: AssetHandle) {
+        self.retain(dependency);
+        self.dependencies.entry(owner).or_default().push(dependency);
+    }
+
+    /// Drops the loaded data whatever its count, and cancels the load if it isn't done yet.
+    /// GPU objects belong to whatever was built from the data, see `StaticMesh::destroy`.
+    pub fn unload(&mut self, handle: AssetHandle) {
+        self.cancel(handle);
+        self.references.remove(&handle);
+        match handle {
+            AssetHandle::Texture(texture) => {
+                self.loaded_texture_data.remove(&texture);
+            }
+            AssetHandle::Mesh(mesh) => {
+                self.loaded_mesh_data.remove(&mesh);
+            }
+            AssetHandle::Material(material) => {
+                self.loaded_material_data.remove(&material);
+            }
+            AssetHandle::Shader(shader) => {
+                self.compiled_shader_programs.remove(&shader);
+            }
+            AssetHandle::ParticleEffect(effect) => {
+                self.loaded_particle_effects.remove(&effect);
+            }
+        }
+        self.forget(handle);
+        log::debug!("Unloaded {:?}", handle);
+
+        for dependency in self.dependencies.remove(&handle).unwrap_or_default() {
+            self.release(dependency);
+        }
+    }
+
+    /// One line per loaded texture and mesh with its use count and rough memory size.
+    pub fn residency(&self) -> String {
+        let uses = |handle: AssetHandle| self.references.get(&handle).copied().unwrap_or(0);
+        let mut lines = Vec::new();
+        let mut total = 0;
+
+        for (handle, texture) in &self.loaded_texture_data {
+            let compressed = texture.compressed.as_ref().map_or(0, |compressed| {
+                compressed.levels.iter().map(|level| level.data.len()).sum()
+            });
+            let bytes = texture.data.len() + compressed;
+            total += bytes;
+            lines.push(format!(
+                "Texture {:>4}  {:<24} {:>3} uses  {:>8} KB  {}x{}",
+                handle.0,
+                texture.name,
+                uses(AssetHandle::Texture(*handle)),
+                bytes / 1024,
+                texture.width,
+                texture.height
+            ));
+        }
+        for (handle, mesh) in &self.loaded_mesh_data {
+            let primitives = mesh.primitives.iter().chain(mesh.lods.iter().flatten());
+            let bytes: usize = primitives.map(primitive_bytes).sum();
+            total += bytes;
+            lines.push(format!(
+                "Mesh    {:>4}  {:<24} {:>3} uses  {:>8} KB  {} primitives",
+                handle.0,
+                mesh.name,
+                uses(AssetHandle::Mesh(*handle)),
+                bytes / 1024,
+                mesh.primitives.len()
+            ));
+        }
+
+        lines.sort();
+        lines.push(format!(
+            "{} assets, {} KB, {} loading",
+            self.loaded_texture_data.len() + self.loaded_mesh_data.len(),
+            total / 1024,
+            self.in_flight.len()
+        ));
+        lines.join("\n")
+    }
+
+    // Forget the path so it can be requested again
+    fn forget(&mut self, forgotten: AssetHandle) {
+        match forgotten {
+            AssetHandle::Texture(forgotten) => {
+                self.texture_paths.retain(|_, handle| *handle != forgotten)
+            }
+            AssetHandle::Mesh(forgotten) => self.mesh_paths.retain(|_, handle| *handle != forgotten),
+            _ => {}
+        }
+        self.in_flight.remove(&forgotten);
+    }
+
+    fn drain_progress(&mut self) {
+        while let Ok(progress) = self.progress_rx.try_recv() {
+            match &progress.stage {
+                LoadStage::Done => {
+                    self.in_flight.remove(&progress.handle);
+                }
+                LoadStage::Failed(e) => {
+                    // Cancelled while it was failing, there's no result left to drop
+                    self.requests.take_cancelled(progress.handle);
+                    self.failures.insert(progress.handle, e.clone());
+                    self.forget(progress.handle);
+                }
+                LoadStage::Cancelled => {
+                    self.in_flight.remove(&progress.handle);
+                }
+                _ if self.requests.is_cancelled(progress.handle) => {}
+                _ => {
+                    self.in_flight.insert(progress.handle, progress);
+                }
+            }
         }
     }
 
     /// Poll to see if any assets have been loaded.
-    pub fn poll_loaded(&self) -> Vec<(AssetHandle, Asset)> {
+    /// Progress is drained here too, before the results so a request never shows as done
+    /// before its asset is returned.
+    pub fn poll_loaded(&mut self) -> Vec<(AssetHandle, Asset)> {
+        self.drain_progress();
+
         let mut loaded = Vec::new();
-        while let Ok(asset) = self.result_rx.try_recv() {
-            loaded.push(asset);
+        while let Ok((handle, asset)) = self.result_rx.try_recv() {
+            // Finished just before it was cancelled
+            if self.requests.take_cancelled(handle) {
+                continue;
+            }
+            loaded.push((handle, asset));
         }
         loaded
     }
 }
+
+// Loads requests until the loader is dropped
+fn run_worker(shared: WorkerShared) {
+    while let Some(request) = shared.requests.pop() {
+        match request {
+            AssetRequest::LoadTexture((texture_handle, path, name)) => {
+                let _span = tracing::info_span!("load_texture", path = ?path).entered();
+                log::debug!("Loader thread: Loading texture {:?}", path);
+
+                let handle = AssetHandle::Texture(texture_handle);
+                let progress = |stage| {
+                    let _ = shared.progress_tx.send(AssetProgress {
+                        handle,
+                        path: path.clone(),
+                        stage,
+                    });
+                };
+                progress(LoadStage::Started);
+
+                let compression = *shared.texture_compression.lock().unwrap();
+                let packs = shared.packs.read().unwrap();
+                let loaded_texture = match import::import_texture(
+                    &path,
+                    name,
+                    compression,
+                    &packs,
+                    &progress,
+                ) {
+                    Ok(texture) => texture,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        progress(LoadStage::Failed(e.to_string()));
+                        continue;
+                    }
+                };
+
+                if shared.requests.take_cancelled(handle) {
+                    progress(LoadStage::Cancelled);
+                    continue;
+                }
+                if let Err(e) = shared.result_tx.send((handle, Asset::Texture(loaded_texture))) {
+                    log::error!("Failed to send loaded texture: {:?}", e);
+                    break;
+                }
+                progress(LoadStage::Done);
+            }
+
+            AssetRequest::LoadMesh((mesh_handle, path, name)) => {
+                let _span = tracing::info_span!("load_mesh", path = ?path).entered();
+                log::debug!("Loader thread: Loading mesh {:?}", path);
+
+                let handle = AssetHandle::Mesh(mesh_handle);
+                let progress = |stage| {
+                    let _ = shared.progress_tx.send(AssetProgress {
+                        handle,
+                        path: path.clone(),
+                        stage,
+                    });
+                };
+                progress(LoadStage::Started);
+
+                match import::import_mesh(&path, &shared.packs.read().unwrap(), &progress) {
+                    Ok(_) if shared.requests.take_cancelled(handle) => {
+                        progress(LoadStage::Cancelled);
+                    }
+                    Ok(mut loaded_mesh) => {
+                        loaded_mesh.name = name;
+
+                        if let Err(e) = shared.result_tx.send((handle, Asset::Mesh(loaded_mesh))) {
+                            log::error!("Failed to send loaded mesh: {:?}", e);
+                            break;
+                        }
+                        progress(LoadStage::Done);
+                    }
+                    Err(e) => {
+                        log::error!("{}", e);
+                        progress(LoadStage::Failed(e.to_string()));
+                    }
+                }
+            }
+
+            AssetRequest::LoadParticleEffect(path) => {
+                let _span =
+                    tracing::info_span!("load_particle_effect", path = ?path).entered();
+
+                // Packs first, like every other asset
+                let effect = pack::read_asset(&shared.packs.read().unwrap(), &path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|contents| ParticleEffect::parse(&contents, &path));
+                match effect {
+                    Ok(effect) => {
+                        let handle = {
+                            let mut id = shared.next_handle_id.lock().unwrap();
+                            let handle = ParticleEffectHandle(*id);
+                            *id += 1;
+                            handle
+                        };
+
+                        if let Err(e) = shared.result_tx.send((
+                            AssetHandle::ParticleEffect(handle),
+                            Asset::ParticleEffect(effect),
+                        )) {
+                            log::error!("Failed to send loaded particle effect: {:?}", e);
+                            break;
+                        }
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // Lets the workers finish
+        self.requests.close();
+    }
+}
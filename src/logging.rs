@@ -0,0 +1,158 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+pub const LOG_DIRECTORY: &str = "logs";
+const LOG_FILE_NAME: &str = "cruel_engine.log";
+const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_ROTATED_LOG_FILES: usize = 5;
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// A formatted log record, forwarded to the editor console.
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: Option<File>,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(directory: &Path) -> Self {
+        let path = directory.join(LOG_FILE_NAME);
+        let mut rotating_file = Self {
+            path,
+            file: None,
+            written: 0,
+        };
+
+        if let Err(e) = fs::create_dir_all(directory) {
+            eprintln!("Failed to create log directory {:?}: {}", directory, e);
+            return rotating_file;
+        }
+
+        // Every run starts with a fresh file, the previous runs are kept as .1, .2, ...
+        rotating_file.rotate();
+        rotating_file
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) {
+        self.file = None;
+
+        let _ = fs::remove_file(self.rotated_path(MAX_ROTATED_LOG_FILES));
+        for index in (1..MAX_ROTATED_LOG_FILES).rev() {
+            let _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+        }
+        let _ = fs::rename(&self.path, self.rotated_path(1));
+
+        match OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+        {
+            Ok(file) => self.file = Some(file),
+            Err(e) => eprintln!("Failed to open log file {:?}: {}", self.path, e),
+        }
+        self.written = 0;
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.written >= MAX_LOG_FILE_SIZE {
+            self.rotate();
+        }
+
+        if let Some(file) = &mut self.file {
+            if writeln!(file, "{}", line).is_ok() {
+                self.written += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+struct EngineLogger {
+    console_tx: Sender<LogLine>,
+    file: Mutex<RotatingFile>,
+}
+
+impl Log for EngineLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies (winit, glutin, egui, ...) are only interesting when something goes wrong
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME")) || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = LogLine {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+
+        let elapsed = START.get_or_init(Instant::now).elapsed();
+        let formatted = format!(
+            "[{:>10.3}] {:<5} {}: {}",
+            elapsed.as_secs_f64(),
+            line.level,
+            line.target,
+            line.message
+        );
+
+        if line.level <= Level::Warn {
+            eprintln!("{}", formatted);
+        } else {
+            println!("{}", formatted);
+        }
+
+        self.file.lock().unwrap().write_line(&formatted);
+
+        let _ = self.console_tx.send(line);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &mut self.file.lock().unwrap().file {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Installs the engine logger. The returned receiver yields every record for the editor console.
+pub fn init() -> Receiver<LogLine> {
+    START.get_or_init(Instant::now);
+
+    let (console_tx, console_rx) = unbounded();
+    let logger = EngineLogger {
+        console_tx,
+        file: Mutex::new(RotatingFile::open(Path::new(LOG_DIRECTORY))),
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    } else {
+        eprintln!("A logger was already installed, engine logs will not reach the console");
+    }
+
+    console_rx
+}
@@ -5,7 +5,7 @@ use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use egui_glow::Painter;
-use glutin::config::ConfigTemplate;
+use glutin::config::{Config, ConfigTemplate};
 use glutin::context::{ContextAttributesBuilder, PossiblyCurrentContext};
 use glutin::display::{Display, DisplayApiPreference};
 use glutin::prelude::*;
@@ -17,43 +17,68 @@ use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::window::{Window, WindowId};
 
+use crossbeam_channel::{unbounded, Receiver};
 use egui_winit::State as EguiState;
 
-mod graphics;
-
-mod data;
-mod handles;
-
-mod shaders;
-
-mod loader;
-use loader::AssetLoader;
-
-mod ecs;
-
+// The engine itself is the library, only the editor's own modules live in the binary
+use cruel_game_engine::{
+    accessibility, camera, capture, component, compression, cvars, data, dialogue, environment,
+    error, foliage, game_ui, gl_debug, gpu_timer, handles, inspect, jobs, loader, logging, mesh,
+    opengl, pack, particles, photo_mode, platform, raycast, render_graph, render_list, runtime,
+    scene_file, scene_graph, scene_preview, socket, sprites, streaming, telemetry, text, textures,
+    tilemap, transform, view_mode, viewport, window,
+};
+
+mod content_browser;
+mod dock;
+mod extra_window;
+mod gizmo;
 mod gui;
+mod headless;
+mod launcher;
+mod preferences;
+mod quad_view;
+mod scene_templates;
+mod thumbnails;
+mod tutorial;
+mod undo;
+
+use accessibility::{AccessibilitySettings, ColorblindFilter};
+use camera::{Camera, PerspectiveCamera};
+use capture::FrameRecorder;
+use compression::TextureCompression;
+use cvars::{CVarRegistry, CVARS_CONFIG_PATH};
+use error::EngineError;
+use extra_window::{ExtraWindow, WindowKind};
+use game_ui::GameUiRenderer;
+use gpu_timer::GpuTimers;
 use gui::Gui;
-
-mod textures;
+use headless::HeadlessOptions;
+use loader::AssetLoader;
+use logging::LogLine;
+use particles::ParticleRenderer;
+use platform::{PlatformBackend, Presence};
+use preferences::EditorPreferences;
+use render_graph::{PassContext, RenderGraph, BACKBUFFER};
+use render_list::RenderStats;
+use scene_graph::{camera_frustum, SceneGraph};
+use sprites::SpriteRenderer;
+use streaming::LEVELS_FILE;
+use telemetry::Telemetry;
+use text::TextRenderer;
 use textures::Texture;
-
-mod viewport;
+use tilemap::TilemapRenderer;
+use view_mode::ViewMode;
+use window::{FramePacer, VSync, WindowMode, WindowTitle};
 use viewport::Viewport;
 
-mod camera;
-use camera::{Camera, PerspectiveCamera};
-mod material;
-mod mesh;
-mod opengl;
-
-mod scene_graph;
-use scene_graph::SceneGraph;
-
 use crate::camera::OrthographicCamera;
+use crate::handles::AssetHandle;
 use crate::loader::{Asset /* AssetHandle */};
 use crate::mesh::StaticMesh;
 use crate::opengl::Layout;
 use crate::scene_graph::SceneNode;
+use crate::environment::EnvironmentMap;
 
 #[derive(PartialEq, Clone, Copy)]
 enum CameraType {
@@ -100,26 +125,250 @@ struct App {
 
     asset_loader: Option<Arc<Mutex<AssetLoader>>>,
 
+    cvars: Option<Arc<Mutex<CVarRegistry>>>,
+    vsync_rx: Option<Receiver<VSync>>,
+    window_mode_rx: Option<Receiver<WindowMode>>,
+    log_rx: Option<Receiver<LogLine>>,
+
     context: Option<Arc<glow::Context>>,
     gui: Option<Gui>,
     active_editor_camera_type: Option<CameraType>,
     editor_cameras: Option<(Box<PerspectiveCamera>, Box<OrthographicCamera>)>,
     editor_cameras_updated: Option<bool>,
+    game_camera: Option<PerspectiveCamera>, // The scene's active camera while playing
 
     scene_graph: Option<SceneGraph>,
 
     egui_context: Option<egui::Context>,
     egui_painter: Option<Painter>,
     egui_state: Option<EguiState>,
+
+    gpu_timers: Option<GpuTimers>,
+    render_stats: RenderStats, // The main scene's, from its last render list
+    colorblind_filter: Option<ColorblindFilter>,
+    particle_renderer: Option<ParticleRenderer>,
+    sprite_renderer: Option<SpriteRenderer>,
+    tilemap_renderer: Option<TilemapRenderer>,
+    text_renderer: Option<TextRenderer>,
+    game_ui_renderer: Option<GameUiRenderer>,
+    render_graph: Option<RenderGraph<App>>,
+
+    headless: Option<HeadlessOptions>,
+    headless_failed: bool,
+
+    // Kept to make surfaces for the extra windows, which share the main window's context
+    gl_display: Option<Display>,
+    gl_config: Option<Config>,
+    extra_windows: Vec<ExtraWindow>,
+
+    platform: Option<Box<dyn PlatformBackend>>,
+    telemetry: Option<Telemetry>,
+    recorder: Option<FrameRecorder>,
+    frame_pacer: FramePacer,
+    window_title: WindowTitle,
+    last_render_error: Option<String>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(log_rx: Receiver<LogLine>) -> Self {
         let mut app = Self::default();
-        app.asset_loader = Some(Arc::new(Mutex::new(AssetLoader::new())));
+        app.log_rx = Some(log_rx);
+        app.window_title = WindowTitle::new("Cruel Engine v0.1");
+
+        let mut cvars = CVarRegistry::with_engine_defaults();
+        let config_path = std::path::Path::new(CVARS_CONFIG_PATH);
+        if config_path.exists() {
+            if let Err(e) = cvars.load(config_path) {
+                log::error!("{}", e);
+            }
+        }
+
+        jobs::init(&cvars);
+        let workers = cvars.get_int("asset_loader_threads").max(0) as usize;
+        app.asset_loader = Some(Arc::new(Mutex::new(AssetLoader::new(workers))));
+
+        // The swap interval can only be changed on the thread that owns the GL context
+        let (vsync_tx, vsync_rx) = unbounded();
+        cvars.on_change(
+            "r_vsync",
+            Box::new(move |value, _| {
+                let _ = vsync_tx.send(VSync::from_value(value));
+            }),
+        );
+        let (window_mode_tx, window_mode_rx) = unbounded();
+        cvars.on_change(
+            "r_window_mode",
+            Box::new(move |value, _| match WindowMode::from_name(&value.to_string()) {
+                Some(mode) => {
+                    let _ = window_mode_tx.send(mode);
+                }
+                None => log::warn!("Unknown window mode '{}'", value),
+            }),
+        );
+
+        if let Some(packs) = cvars.get("asset_packs").map(|packs| packs.to_string()) {
+            let mut asset_loader = app.asset_loader.as_ref().unwrap().lock().unwrap();
+            for path in packs.split(',').map(str::trim).filter(|path| !path.is_empty()) {
+                if let Err(e) = asset_loader.mount_pack(std::path::Path::new(path)) {
+                    log::error!("{}", e);
+                }
+            }
+        }
+
+        // The loader workers read this for every texture it decodes
+        let texture_compression = app
+            .asset_loader
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .texture_compression();
+        let set_texture_compression = move |value: &cvars::CVarValue| {
+            match TextureCompression::from_name(&value.to_string()) {
+                Some(compression) => *texture_compression.lock().unwrap() = compression,
+                None => log::warn!("Unknown texture compression '{}'", value),
+            }
+        };
+        if let Some(value) = cvars.get("asset_texture_compression").cloned() {
+            set_texture_compression(&value);
+        }
+        cvars.on_change(
+            "asset_texture_compression",
+            Box::new(move |value, _| set_texture_compression(value)),
+        );
+
+        app.platform = platform::create_backend(&cvars);
+        if let Some(platform) = &mut app.platform {
+            platform.set_presence(&Presence {
+                state: "In the editor".to_string(),
+                start_time: Some(std::time::SystemTime::now()),
+                ..Default::default()
+            });
+        }
+
+        let telemetry = Telemetry::from_cvars(&cvars);
+        telemetry.session_start();
+        app.telemetry = Some(telemetry);
+
+        app.cvars = Some(Arc::new(Mutex::new(cvars)));
+        app.vsync_rx = Some(vsync_rx);
+        app.window_mode_rx = Some(window_mode_rx);
         app
     }
 
+    fn open_window(&mut self, event_loop: &ActiveEventLoop, kind: WindowKind) {
+        // One of each is enough, asking again brings it to the front
+        if let Some(open) = self.extra_windows.iter().find(|window| window.kind == kind) {
+            open.window.focus_window();
+            return;
+        }
+        let (Some(display), Some(config), Some(context), Some(gl), Some(surface)) = (
+            self.gl_display.as_ref(),
+            self.gl_config.as_ref(),
+            self.current_context.as_ref(),
+            self.context.as_ref(),
+            self.surface.as_ref(),
+        ) else {
+            return;
+        };
+        let window = ExtraWindow::new(event_loop, kind, display, config, context, gl.clone());
+        // Creating its surface can leave the context current on it
+        if let Err(e) = context.make_current(surface) {
+            log::error!("Failed to get the main window back: {}", e);
+        }
+        match window {
+            Ok(window) => self.extra_windows.push(window),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    fn extra_window_event(&mut self, id: WindowId, event: WindowEvent) {
+        let Some(index) = self.extra_windows.iter().position(|window| window.id() == id) else {
+            return;
+        };
+        if self.extra_windows[index].on_window_event(&event) {
+            self.frame_pacer.request();
+        }
+        match event {
+            WindowEvent::CloseRequested => {
+                self.extra_windows.remove(index).destroy();
+            }
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                if let Some(context) = &self.current_context {
+                    self.extra_windows[index].resize(context);
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = self.redraw_extra_window(index) {
+                    log::error!("{}", e);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn redraw_extra_window(&mut self, index: usize) -> Result<(), String> {
+        let (Some(context), Some(gl), Some(surface), Some(scene_graph)) = (
+            self.current_context.as_ref(),
+            self.context.as_ref(),
+            self.surface.as_ref(),
+            self.scene_graph.as_mut(),
+        ) else {
+            return Ok(());
+        };
+        let extra_window = &mut self.extra_windows[index];
+
+        // The game view is seen through the scene's active camera, the editor's without one
+        let active = scene_graph.current_scene().and_then(SceneNode::active_camera);
+        let editor = self.editor_cameras.as_ref().map(|(persp, _)| persp.as_ref());
+        let Some(mut camera) = active.or(editor).cloned() else {
+            return Ok(());
+        };
+        let through = camera.name.clone();
+        let size = extra_window.window.inner_size();
+        camera.fit_viewport(size.width.max(1), size.height.max(1));
+        camera.update_matrices();
+
+        let draw_scene = |viewport: &Viewport| {
+            if let Some(scene) = scene_graph.current_scene() {
+                scene.render(gl, &mut camera, viewport).map_err(|e| e.to_string())?;
+                scene_graph
+                    .levels
+                    .render(gl, &mut camera, viewport)
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        };
+        let ui = |ctx: &egui::Context| {
+            egui::Area::new(egui::Id::new("Extra window label"))
+                .fixed_pos(egui::pos2(8.0, 6.0))
+                .show(ctx, |ui| ui.weak(&through));
+        };
+        extra_window.redraw(context, gl, surface, draw_scene, ui)
+    }
+
+    // The quad layout's side views, only the scene without the 2D and post passes
+    fn render_side_views(&mut self) -> Result<(), EngineError> {
+        let (Some(gl), Some(window), Some(gui), Some(scene_graph)) = (
+            self.context.as_ref(),
+            self.window.as_ref(),
+            self.gui.as_mut(),
+            self.scene_graph.as_mut(),
+        ) else {
+            return Ok(());
+        };
+        let window_height = window.inner_size().height;
+        for (camera, viewport) in gui.side_views(window_height) {
+            viewport.clear(gl);
+            camera.update_matrices();
+            if let Some(scene) = scene_graph.current_scene() {
+                scene.render(gl, camera, &viewport)?;
+                scene_graph.levels.render(gl, camera, &viewport)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn request_texture<P: AsRef<std::path::Path>>(&self, path: P, name: String) {
         if let Some(asset_loader) = &self.asset_loader {
             asset_loader
@@ -127,7 +376,7 @@ impl App {
                 .unwrap()
                 .request_texture(path, name);
         } else {
-            eprintln!("Asset loader not initialized when requesting texture!");
+            log::error!("Asset loader not initialized when requesting texture!");
         }
     }
 
@@ -135,7 +384,7 @@ impl App {
         if let Some(asset_loader) = &self.asset_loader {
             let asset_loader = Arc::clone(asset_loader);
             requests.par_iter().for_each(|(path, name)| {
-                let loader = asset_loader.lock().unwrap();
+                let mut loader = asset_loader.lock().unwrap();
                 loader.request_texture(path, name.clone());
             });
         }
@@ -148,7 +397,7 @@ impl App {
                 .unwrap()
                 .request_mesh(path, name);
         } else {
-            eprintln!("Asset loader not initialized when requesting mesh!");
+            log::error!("Asset loader not initialized when requesting mesh!");
         }
     }
 
@@ -156,7 +405,7 @@ impl App {
         if let Some(asset_loader) = &self.asset_loader {
             let asset_loader = Arc::clone(asset_loader);
             requests.par_iter().for_each(|(path, name)| {
-                let loader = asset_loader.lock().unwrap();
+                let mut loader = asset_loader.lock().unwrap();
                 loader.request_mesh(path, name.clone());
             });
         }
@@ -165,14 +414,26 @@ impl App {
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // Create a new window and store it in self.window
+        if let Some(options) = self.headless.take() {
+            let asset_loader = self.asset_loader.as_ref().unwrap();
+            if let Err(e) = headless::run(event_loop, &options, asset_loader) {
+                log::error!("Headless rendering failed: {}", e);
+                self.headless_failed = true;
+            }
+            event_loop.exit();
+            return;
+        }
+
+        // Create a new window where it was last time and store it in self.window
+        let placement = EditorPreferences::load().window;
         self.window = Some(
             event_loop
-                .create_window(Window::default_attributes())
+                .create_window(placement.attributes(event_loop, Window::default_attributes()))
                 .unwrap(),
         );
 
         let window = self.window.as_ref().unwrap();
+        WindowMode::from_cvars(&self.cvars.as_ref().unwrap().lock().unwrap()).apply(window);
 
         // Get platform-specific handles to the display and window
         let display_handle = window.display_handle().unwrap();
@@ -211,7 +472,9 @@ impl ApplicationHandler for App {
         );
 
         // Create context attributes (e.g., OpenGL version, flags)
-        let context_attributes = ContextAttributesBuilder::new().build(Some(window_handle.into()));
+        let context_attributes = ContextAttributesBuilder::new()
+            .with_debug(gl_debug::DEBUG_CONTEXT)
+            .build(Some(window_handle.into()));
 
         // Create the OpenGL window surface using the display and attributes
         let surface = unsafe {
@@ -230,21 +493,21 @@ impl ApplicationHandler for App {
         // Make the context current
         let current_context = non_current_context.make_current(&surface).unwrap();
 
-        surface
-            .set_swap_interval(
-                &current_context,
-                glutin::surface::SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
-            )
-            .expect("Failed to set vsync");
+        let vsync = VSync::from_cvars(&self.cvars.as_ref().unwrap().lock().unwrap());
+        vsync.apply(&surface, &current_context);
 
         // Create the glow context
-        let gl = unsafe {
-            Arc::new(glow::Context::from_loader_function(|s| {
+        let mut gl = unsafe {
+            glow::Context::from_loader_function(|s| {
                 let c_str = CString::new(s).unwrap();
                 display.get_proc_address(&c_str) as *const _
-            }))
+            })
         };
+        gl_debug::install(&mut gl);
+        let gl = Arc::new(gl);
 
+        self.gl_display = Some(display.clone());
+        self.gl_config = Some(config.clone());
         self.surface = Some(surface);
         self.current_context = Some(current_context);
         self.context = Some(gl);
@@ -321,7 +584,28 @@ impl ApplicationHandler for App {
         cube.set_render_data(render_data);
         */
 
-        let scene = SceneNode::new("Main Scene", &self.context.as_ref().unwrap());
+        // Keep the editor usable without shaders, the error is in the console
+        let mut scene = SceneNode::new("Main Scene", &self.context.as_ref().unwrap()).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            SceneNode::empty("Main Scene")
+        });
+
+        let environment_path = self
+            .cvars
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get("r_environment")
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        if !environment_path.is_empty() {
+            let path = std::path::Path::new(&environment_path);
+            match EnvironmentMap::from_hdr(self.context.as_ref().unwrap(), path) {
+                Ok(environment) => scene.environment = Some(environment),
+                Err(e) => log::error!("{}", e),
+            }
+        }
 
         // scene.add_static_mesh(cube);
 
@@ -339,18 +623,27 @@ impl ApplicationHandler for App {
                         .loaded_texture_data
                         .insert(handle.as_texture_handle().unwrap(), loaded_texture);
                 }
-                _ => unimplemented!(),
+                _ => log::warn!("No handler for loaded asset {:?}", handle),
             }
         }
 
-        self.scene_graph = Some(SceneGraph::new());
-        self.scene_graph
-            .as_mut()
-            .unwrap()
-            .scenes
-            .push(Box::new(scene));
+        let mut scene_graph = SceneGraph::new();
+        scene_graph.scenes.push(Box::new(scene));
+        if let Err(e) = scene_graph.levels.load_descriptors(
+            std::path::Path::new(LEVELS_FILE),
+            self.context.as_ref().unwrap(),
+            &mut asset_loader,
+        ) {
+            log::error!("{}", e);
+        }
 
-        self.gui = Some(Gui::new());
+        self.gui = Some(Gui::new(
+            Arc::clone(self.cvars.as_ref().unwrap()),
+            self.log_rx.take().unwrap(),
+            scene_graph.levels.requests(),
+            scene_graph.switch_requests(),
+        ));
+        self.scene_graph = Some(scene_graph);
 
         self.active_editor_camera_type = Some(CameraType::Perspective);
 
@@ -375,7 +668,7 @@ impl ApplicationHandler for App {
                 45.0,
                 window.inner_size().width,
                 window.inner_size().height,
-                (16.0 / 9.0) as f32,
+                window.inner_size().width as f32 / window.inner_size().height.max(1) as f32,
                 0.1,
                 100.0,
                 2.4,
@@ -402,25 +695,153 @@ impl ApplicationHandler for App {
         // Move to "new" function: self.asset_loader = Some(AssetLoader::new());
 
         self.timer = Some(Timer::new(Instant::now()));
+        self.gpu_timers = Some(GpuTimers::new());
+
+        match ColorblindFilter::new(self.context.as_ref().unwrap()) {
+            Ok(filter) => self.colorblind_filter = Some(filter),
+            Err(e) => log::error!("{}", e),
+        }
+        let gpu_particles = self
+            .cvars
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get_bool("r_gpu_particles");
+        match ParticleRenderer::new(self.context.as_ref().unwrap(), gpu_particles) {
+            Ok(renderer) => {
+                if let Some(gui) = self.gui.as_mut() {
+                    gui.set_gpu_particles(renderer.simulates_on_gpu());
+                }
+                self.particle_renderer = Some(renderer);
+            }
+            Err(e) => log::error!("{}", e),
+        }
+        match SpriteRenderer::new(self.context.as_ref().unwrap()) {
+            Ok(renderer) => self.sprite_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match TilemapRenderer::new(self.context.as_ref().unwrap()) {
+            Ok(renderer) => self.tilemap_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match TextRenderer::new(self.context.as_ref().unwrap()) {
+            Ok(renderer) => self.text_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match GameUiRenderer::new(self.context.as_ref().unwrap()) {
+            Ok(renderer) => self.game_ui_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        self.render_graph = Some(build_render_graph());
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let window = self.window.as_ref().unwrap();
-        window.set_title("Cruel Engine v0.1");
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        // No window in headless mode
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        if window.id() != id {
+            self.extra_window_event(id, event);
+            return;
+        }
 
-        // give egui any winit events
-        _ = self
+        // give egui any winit events, and draw another frame if it changed anything
+        let response = self
             .egui_state
             .as_mut()
             .unwrap()
             .on_window_event(window, &event);
+        if response.repaint {
+            self.frame_pacer.request();
+        }
 
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                log::info!("The close button was pressed; stopping");
+                self.gui.as_mut().unwrap().save_window_placement(window);
+                if let Some(telemetry) = &self.telemetry {
+                    telemetry.session_end();
+                }
+                if let Err(e) = self
+                    .cvars
+                    .as_ref()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .save(std::path::Path::new(CVARS_CONFIG_PATH))
+                {
+                    log::error!("{}", e);
+                }
                 event_loop.exit();
             }
+            WindowEvent::DroppedFile(path) => {
+                self.gui.as_mut().unwrap().drop_file(path);
+                self.frame_pacer.request();
+            }
+            // Going in and out of fullscreen too. On a new scale egui has already taken it, and
+            // the viewport follows next frame.
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                let size = window.inner_size();
+                if let (Some(surface), Some(context)) = (&self.surface, &self.current_context) {
+                    surface.resize(
+                        context,
+                        NonZeroU32::new(size.width.max(1)).unwrap(),
+                        NonZeroU32::new(size.height.max(1)).unwrap(),
+                    );
+                }
+                self.frame_pacer.request();
+            }
             WindowEvent::RedrawRequested => {
+                let _frame_span = tracing::info_span!("frame").entered();
+                let frame_start = Instant::now();
+                self.frame_pacer
+                    .set_max_fps(&self.cvars.as_ref().unwrap().lock().unwrap());
+                self.frame_pacer.begin_frame();
+                let update_span = tracing::info_span!("update").entered();
+
+                while let Ok(vsync) = self.vsync_rx.as_ref().unwrap().try_recv() {
+                    vsync.apply(
+                        self.surface.as_ref().unwrap(),
+                        self.current_context.as_ref().unwrap(),
+                    );
+                }
+                while let Ok(mode) = self.window_mode_rx.as_ref().unwrap().try_recv() {
+                    mode.apply(self.window.as_ref().unwrap());
+                }
+
+                if let Some(platform) = &mut self.platform {
+                    platform.update();
+                }
+
+                // Frozen while photo mode is open
+                let simulation_delta = self
+                    .gui
+                    .as_ref()
+                    .unwrap()
+                    .photo_mode()
+                    .simulation_delta(self.timer.as_ref().unwrap().delta_time as f32);
+                if let Some(scene) = self.scene_graph.as_mut().unwrap().current_scene_mut() {
+                    scene.update_sprites(simulation_delta);
+                }
+
+                let accessibility =
+                    AccessibilitySettings::from_cvars(&self.cvars.as_ref().unwrap().lock().unwrap());
+                self.egui_context
+                    .as_ref()
+                    .unwrap()
+                    .set_zoom_factor(accessibility.ui_scale);
+
+                if let Some((persp, ortho)) = &mut self.editor_cameras {
+                    let cvars = self.cvars.as_ref().unwrap().lock().unwrap();
+                    let photo_fov = self.gui.as_ref().unwrap().photo_mode().fov_override();
+                    persp.set_fov(photo_fov.unwrap_or(cvars.get_float("r_fov")));
+                    persp.set_speed(cvars.get_float("cam_speed"));
+                    persp.set_sensitivity(cvars.get_float("cam_sensitivity"));
+                    ortho.set_speed(cvars.get_float("cam_speed"));
+                    ortho.set_sensitivity(cvars.get_float("cam_sensitivity"));
+                }
+
                 // Clear the framebuffer
                 self.gui
                     .as_ref()
@@ -436,6 +857,9 @@ impl ApplicationHandler for App {
                     None => panic!("Editor cameras not initialized!"),
                 };
 
+                drop(update_span);
+                let egui_span = tracing::info_span!("egui").entered();
+
                 // Run the UI code
                 let full_output = self.gui.as_mut().unwrap().update(
                     self.egui_state.as_mut().unwrap().take_egui_input(window),
@@ -444,10 +868,16 @@ impl ApplicationHandler for App {
                     self.active_editor_camera_type.as_mut().unwrap(),
                     active_camera,
                     self.scene_graph.as_mut().unwrap(),
-                    &self.asset_loader.as_ref().unwrap().lock().unwrap(),
+                    &mut self.asset_loader.as_ref().unwrap().lock().unwrap(),
                     self.timer.as_ref().unwrap().delta_time,
                 );
 
+                // How soon egui wants to be drawn again, for reactive mode
+                let repaint_delay = full_output
+                    .viewport_output
+                    .get(&egui::ViewportId::ROOT)
+                    .map_or(std::time::Duration::MAX, |output| output.repaint_delay);
+
                 // Handle the platform output (like copy/paste)
                 self.egui_state
                     .as_mut()
@@ -463,6 +893,10 @@ impl ApplicationHandler for App {
 
                 // Paint the egui UI
                 let physical_size = window.inner_size();
+                self.gpu_timers
+                    .as_mut()
+                    .unwrap()
+                    .begin(self.context.as_ref().unwrap(), "egui");
                 self.egui_painter
                     .as_mut()
                     .unwrap()
@@ -472,18 +906,88 @@ impl ApplicationHandler for App {
                         &clipped_primitives,
                         &full_output.textures_delta,
                     );
+                self.gpu_timers
+                    .as_mut()
+                    .unwrap()
+                    .end(self.context.as_ref().unwrap());
+
+                drop(egui_span);
+
+                // The editor cameras follow the viewport's shape unless their aspect is fixed
+                if let (Some(viewport), Some((persp, ortho))) = (
+                    self.gui.as_ref().unwrap().get_viewport(window),
+                    self.editor_cameras.as_mut(),
+                ) {
+                    let (width, height) = (viewport.width.max(1), viewport.height.max(1));
+                    persp.fit_viewport(width as u32, height as u32);
+                    ortho.fit_viewport(width as u32, height as u32);
+                }
 
-                // let v = self.gui.as_ref().unwrap().get_viewport(window).unwrap();
-                // self.editor_cameras.as_mut().unwrap().0.fov = (v.width / v.height) as f32;
+                // The project is the directory the launcher opened
+                let gui = self.gui.as_ref().unwrap();
+                let scene_graph = self.scene_graph.as_ref().unwrap();
+                let title = &mut self.window_title;
+                title.project = std::env::current_dir()
+                    .ok()
+                    .and_then(|directory| Some(directory.file_name()?.to_string_lossy().into()));
+                title.scene = scene_graph.current_scene().map(|scene| scene.name.clone());
+                title.unsaved = gui.is_scene_unsaved(scene_graph.current_scene);
+                title.status = match (gui.is_recording(), gui.is_playing()) {
+                    (true, _) => Some("Recording".to_string()),
+                    (false, true) => Some("Playing".to_string()),
+                    (false, false) => None,
+                };
+                title.apply(window);
+
+                // Play mode is seen through a copy of the scene's active camera, if it has one
+                let playing = self.gui.as_ref().unwrap().is_playing();
+                self.game_camera = self
+                    .scene_graph
+                    .as_ref()
+                    .and_then(|scene_graph| scene_graph.current_scene())
+                    .and_then(SceneNode::active_camera)
+                    .filter(|_| playing)
+                    .cloned();
+                if let (Some(viewport), Some(camera)) = (
+                    self.gui.as_ref().unwrap().get_viewport(window),
+                    self.game_camera.as_mut(),
+                ) {
+                    let (width, height) = (viewport.width.max(1), viewport.height.max(1));
+                    camera.fit_viewport(width as u32, height as u32);
+                }
 
                 // Poll and integrate any newly loaded assets
                 if let Some(asset_loader) = &self.asset_loader {
+                    let _span = tracing::info_span!("poll_assets").entered();
+
                     let mut asset_loader = asset_loader.lock().unwrap();
                     let loaded_assets = asset_loader.poll_loaded();
                     for (handle, asset) in loaded_assets {
                         match asset {
                             Asset::Mesh(loaded_mesh) => {
-                                println!("Mesh loaded: {}", loaded_mesh.name);
+                                log::info!("Mesh loaded: {}", loaded_mesh.name);
+
+                                // Normal and occlusion maps have to be loaded before the mesh is added to a scene
+                                let materials = loaded_mesh
+                                    .primitives
+                                    .iter()
+                                    .chain(loaded_mesh.lods.iter().flatten())
+                                    .filter_map(|primitive| primitive.material.as_ref());
+                                for path in materials.flat_map(|material| {
+                                    [&material.normal_texture, &material.occlusion_texture]
+                                        .into_iter()
+                                        .flatten()
+                                        .cloned()
+                                }) {
+                                    let name = path
+                                        .file_name()
+                                        .unwrap_or_default()
+                                        .to_string_lossy()
+                                        .to_string();
+                                    let texture = asset_loader.request_texture(path, name);
+                                    asset_loader
+                                        .add_dependency(handle, AssetHandle::Texture(texture));
+                                }
 
                                 // Store mesh in AssetLoader/AssetLibrary instead of adding directly to scene
                                 asset_loader
@@ -493,71 +997,561 @@ impl ApplicationHandler for App {
                                 // Optionally: mark the mesh as "ready" for adding in the GUI
                             }
                             Asset::Texture(loaded_texture) => {
-                                println!("Texture loaded: {}", loaded_texture.name);
+                                log::info!("Texture loaded: {}", loaded_texture.name);
                                 asset_loader
                                     .loaded_texture_data
                                     .insert(handle.as_texture_handle().unwrap(), loaded_texture);
                             }
-                            _ => unimplemented!(),
+                            Asset::ParticleEffect(effect) => {
+                                log::info!("Particle effect loaded: {}", effect.name);
+                                asset_loader
+                                    .loaded_particle_effects
+                                    .insert(handle.as_particle_effect_handle().unwrap(), effect);
+                            }
+                            _ => log::warn!("No handler for loaded asset {:?}", handle),
                         }
                     }
+
+                    // Meshes added this frame only show up once their data is on the GPU
+                    let budget_kb = self
+                        .cvars
+                        .as_ref()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .get_int("r_upload_budget_kb")
+                        .max(1) as usize;
+                    asset_loader
+                        .uploads
+                        .get_mut()
+                        .unwrap()
+                        .process(self.context.as_ref().unwrap(), budget_kb * 1024);
+
+                    // Levels stream in and out around the camera
+                    if let (Some(scene_graph), Some(camera)) = (
+                        self.scene_graph.as_mut(),
+                        view_camera(
+                            &mut self.game_camera,
+                            &mut self.editor_cameras,
+                            self.active_editor_camera_type,
+                        ),
+                    ) {
+                        let viewer = camera.get_position();
+                        scene_graph.levels.update(
+                            self.context.as_ref().unwrap(),
+                            &mut asset_loader,
+                            viewer,
+                        );
+                    }
                 }
 
-                let active_camera: &mut dyn Camera = match &mut self.editor_cameras {
-                    Some((persp, ortho)) => match self.active_editor_camera_type {
-                        Some(CameraType::Perspective) => persp.as_mut(),
-                        Some(CameraType::Orthographic) => ortho.as_mut(),
-                        None => panic!("Editor cameras not initialized!"),
-                    },
-                    None => panic!("Editor cameras not initialized!"),
-                };
+                let active_camera = view_camera(
+                    &mut self.game_camera,
+                    &mut self.editor_cameras,
+                    self.active_editor_camera_type,
+                )
+                .expect("Editor cameras not initialized!");
 
                 active_camera.update_matrices();
 
-                // Render the scene
-                if let Some(sg) = self.scene_graph.as_mut() {
-                    if let Some(scene) = sg.current_scene_mut() {
-                        scene.update(active_camera);
-                        scene.render(self.context.as_ref().unwrap(), active_camera, &self.gui.as_ref().unwrap().get_viewport(window).expect(
-                        "Viewport not present, make sure to update the ui before calling this",
-                        ),);
+                // Scene and post passes, see build_render_graph
+                let render_span = tracing::info_span!("scene_render").entered();
+                let result = match self.gui.as_ref().unwrap().get_viewport(window) {
+                    Some(viewport) => {
+                        let gl = Arc::clone(self.context.as_ref().unwrap());
+                        let mut render_graph = self.render_graph.take().unwrap();
+                        let result = render_graph.execute(&gl, &viewport, self);
+                        self.render_graph = Some(render_graph);
+                        result.and_then(|()| self.render_side_views())
+                    }
+                    None => Err(EngineError::NoViewport),
+                };
+                drop(render_span);
+
+                // Only log when the error changes, otherwise the console fills up every frame
+                match result {
+                    Ok(()) => self.last_render_error = None,
+                    Err(e) => {
+                        let message = e.to_string();
+                        if self.last_render_error.as_ref() != Some(&message) {
+                            log::error!("{}", message);
+                            self.last_render_error = Some(message);
+                        }
+                    }
+                }
+
+                // The passes borrowed all of self
+                let window = self.window.as_ref().unwrap();
+
+                // Capture after the scene so the recording doesn't include the editor UI
+                let gui = self.gui.as_mut().unwrap();
+                if let Some(viewport) = gui.get_viewport(window) {
+                    match (gui.is_recording(), &mut self.recorder) {
+                        (true, Some(recorder)) => {
+                            recorder.capture(self.context.as_ref().unwrap(), &viewport);
+                        }
+                        (true, None) => match FrameRecorder::start(&viewport, 60) {
+                            Ok(recorder) => self.recorder = Some(recorder),
+                            Err(e) => {
+                                log::error!("{}", e);
+                                gui.set_recording(false);
+                            }
+                        },
+                        (false, Some(_)) => self.recorder.take().unwrap().stop(),
+                        (false, None) => {}
                     }
                 }
 
                 self.timer.as_mut().unwrap().update();
 
+                // Shown next frame, GPU results lag a few frames behind anyway
+                let gpu_timers = self.gpu_timers.as_mut().unwrap();
+                gpu_timers.end_frame(self.context.as_ref().unwrap());
+                let mut gpu_timings = gpu_timers.timings();
+                gpu_timings.extend(self.render_graph.as_ref().unwrap().timings());
+                self.gui.as_mut().unwrap().set_frame_timings(
+                    self.timer.as_ref().unwrap().delta_time as f32 * 1000.0,
+                    frame_start.elapsed().as_secs_f32() * 1000.0,
+                    gpu_timings,
+                );
+                self.gui.as_mut().unwrap().set_render_stats(self.render_stats);
+
                 // Swap the frame buffers
+                let swap_span = tracing::info_span!("swap").entered();
                 self.surface
                     .as_ref()
                     .unwrap()
                     .swap_buffers(self.current_context.as_ref().unwrap())
                     .unwrap();
+                drop(swap_span);
 
-                window.request_redraw();
+                #[cfg(feature = "tracy")]
+                tracing_tracy::client::frame_mark();
+
+                // Reactive mode only keeps drawing while something moves on its own, or a
+                // key or mouse button is held (flying the camera, dragging a gizmo)
+                let gui = self.gui.as_ref().unwrap();
+                let held = self
+                    .egui_context
+                    .as_ref()
+                    .unwrap()
+                    .input(|input| input.pointer.any_down() || !input.keys_down.is_empty());
+                let busy = gui.is_playing()
+                    || gui.is_recording()
+                    || held
+                    || self
+                        .asset_loader
+                        .as_ref()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .in_flight()
+                        .next()
+                        .is_some();
+                let reactive = self.cvars.as_ref().unwrap().lock().unwrap().get_bool("r_reactive");
+                if !reactive || busy || repaint_delay.is_zero() {
+                    self.frame_pacer.request();
+                } else if let Some(at) = Instant::now().checked_add(repaint_delay) {
+                    self.frame_pacer.request_at(at);
+                }
+
+                // The extra windows follow the main one
+                for kind in self.gui.as_mut().unwrap().take_window_requests() {
+                    self.open_window(event_loop, kind);
+                }
+                for extra_window in &self.extra_windows {
+                    extra_window.window.request_redraw();
+                }
             }
             _ => (),
         }
     }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Headless runs have no window to draw into
+        if let Some(window) = &self.window {
+            if self.frame_pacer.poll(event_loop) {
+                window.request_redraw();
+            }
+        }
+    }
+}
+
+// What the viewport is seen through: the game camera in play mode, otherwise the editor's
+fn view_camera<'a>(
+    game_camera: &'a mut Option<PerspectiveCamera>,
+    editor_cameras: &'a mut Option<(Box<PerspectiveCamera>, Box<OrthographicCamera>)>,
+    camera_type: Option<CameraType>,
+) -> Option<&'a mut dyn Camera> {
+    if let Some(camera) = game_camera {
+        return Some(camera);
+    }
+    let (persp, ortho) = editor_cameras.as_mut()?;
+    match camera_type {
+        Some(CameraType::Orthographic) => Some(ortho.as_mut()),
+        _ => Some(persp.as_mut()),
+    }
+}
+
+// The editor's frame: the scene, then photo mode and the accessibility filter over it.
+// Shadow maps and other offscreen passes get their own targets with add_target.
+fn build_render_graph() -> RenderGraph<App> {
+    let mut graph = RenderGraph::new();
+
+    graph.add_pass(
+        "scene",
+        &[],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let camera = view_camera(
+                &mut app.game_camera,
+                &mut app.editor_cameras,
+                app.active_editor_camera_type,
+            );
+            let (Some(scene_graph), Some(camera)) = (app.scene_graph.as_mut(), camera) else {
+                return Ok(());
+            };
+            let view_mode = app
+                .cvars
+                .as_ref()
+                .and_then(|cvars| {
+                    let name = cvars.lock().unwrap().get("r_view_mode")?.to_string();
+                    ViewMode::from_name(&name)
+                })
+                .unwrap_or(ViewMode::Lit);
+            match scene_graph.current_scene_mut() {
+                Some(scene) => {
+                    if scene.view_mode() != view_mode {
+                        scene.set_view_mode(ctx.gl, view_mode);
+                    }
+                    scene.update(camera);
+                    let visible = scene.visible_meshes(&camera_frustum(camera));
+                    let list = scene.render_list(camera, &visible);
+                    app.render_stats = list.stats();
+                    scene.submit(ctx.gl, camera, &ctx.viewport, &list, true)?;
+                    scene_graph.levels.render(ctx.gl, camera, &ctx.viewport)
+                }
+                None => Ok(()),
+            }
+        },
+    );
+
+    // 2D levels, always seen through the orthographic camera like the sprites on top
+    graph.add_pass(
+        "tilemaps",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(renderer), Some(scene_graph), Some((_, ortho)), Some(asset_loader)) = (
+                app.tilemap_renderer.as_mut(),
+                app.scene_graph.as_mut(),
+                app.editor_cameras.as_mut(),
+                app.asset_loader.as_ref(),
+            ) else {
+                return Ok(());
+            };
+            let Some(scene) = scene_graph.current_scene_mut() else {
+                return Ok(());
+            };
+            if scene.tilemaps.is_empty() {
+                return Ok(());
+            }
+            ortho.update_matrices();
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                ortho.as_ref(),
+                &mut scene.tilemaps,
+                &asset_loader.lock().unwrap(),
+            )
+        },
+    );
+
+    // 2D games and HUDs, always seen through the orthographic camera
+    graph.add_pass(
+        "sprites",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(renderer), Some(scene_graph), Some((_, ortho)), Some(asset_loader)) = (
+                app.sprite_renderer.as_mut(),
+                app.scene_graph.as_ref(),
+                app.editor_cameras.as_mut(),
+                app.asset_loader.as_ref(),
+            ) else {
+                return Ok(());
+            };
+            let Some(scene) = scene_graph.current_scene() else {
+                return Ok(());
+            };
+            if scene.sprites.is_empty() {
+                return Ok(());
+            }
+            ortho.update_matrices();
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                ortho.as_ref(),
+                &scene.sprites,
+                &asset_loader.lock().unwrap(),
+            )
+        },
+    );
+
+    // The Particles tab's preview
+    graph.add_pass(
+        "particles",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(renderer), Some(gui), Some(camera)) = (
+                app.particle_renderer.as_mut(),
+                app.gui.as_ref(),
+                view_camera(
+                    &mut app.game_camera,
+                    &mut app.editor_cameras,
+                    app.active_editor_camera_type,
+                ),
+            ) else {
+                return Ok(());
+            };
+            if let Some(system) = gui.particle_preview() {
+                renderer.render(ctx.gl, &ctx.viewport, camera, system);
+            }
+            Ok(())
+        },
+    );
+
+    // Name tags and whatever text the scene queued this frame
+    graph.add_pass(
+        "text",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(renderer), Some(scene_graph), Some(camera)) = (
+                app.text_renderer.as_mut(),
+                app.scene_graph.as_mut(),
+                view_camera(
+                    &mut app.game_camera,
+                    &mut app.editor_cameras,
+                    app.active_editor_camera_type,
+                ),
+            ) else {
+                return Ok(());
+            };
+            let Some(scene) = scene_graph.current_scene_mut() else {
+                return Ok(());
+            };
+            let name_tags = app
+                .cvars
+                .as_ref()
+                .is_some_and(|cvars| cvars.lock().unwrap().get_bool("r_name_tags"));
+            if name_tags {
+                for index in 0..scene.static_meshes.len() {
+                    let Some(bounds) = scene.static_mesh_world_bounds(index) else {
+                        continue;
+                    };
+                    let top = cgmath::Point3::new(
+                        (bounds.min.x + bounds.max.x) * 0.5,
+                        bounds.max.y,
+                        (bounds.min.z + bounds.max.z) * 0.5,
+                    );
+                    let name = scene.static_meshes[index].name.clone();
+                    scene.text.billboard(top, &name, 0.25, [1.0, 1.0, 1.0, 1.0]);
+                }
+            }
+            if scene.text.is_empty() {
+                return Ok(());
+            }
+            renderer.render(ctx.gl, &ctx.viewport, camera, &mut scene.text);
+            Ok(())
+        },
+    );
+
+    // The game's HUD, over everything in the viewport but under the editor's egui
+    graph.add_pass(
+        "game_ui",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(renderer), Some(scene_graph), Some(asset_loader)) = (
+                app.game_ui_renderer.as_mut(),
+                app.scene_graph.as_mut(),
+                app.asset_loader.as_ref(),
+            ) else {
+                return Ok(());
+            };
+            let Some(scene) = scene_graph.current_scene_mut() else {
+                return Ok(());
+            };
+            // Headless runs have no editor, so they are always playing
+            let playing = app.gui.as_ref().is_none_or(|gui| gui.is_playing());
+            if !playing {
+                scene.game_ui.clear();
+                return Ok(());
+            }
+            if scene.game_ui.is_empty() {
+                return Ok(());
+            }
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                &mut scene.game_ui,
+                &asset_loader.lock().unwrap(),
+            )
+        },
+    );
+
+    // A scene saved this frame gets its thumbnail before photo effects and filters are applied
+    graph.add_pass(
+        "scene_preview",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            let (Some(gui), Some(scene_graph)) = (app.gui.as_mut(), app.scene_graph.as_ref()) else {
+                return Ok(());
+            };
+            let Some(path) = gui.take_pending_preview() else {
+                return Ok(());
+            };
+            let Some(scene) = scene_graph.current_scene() else {
+                return Ok(());
+            };
+            if let Err(e) = scene_preview::save_preview(ctx.gl, &ctx.viewport, scene, &path) {
+                log::error!("{}", e);
+            }
+            Ok(())
+        },
+    );
+
+    // Photo effects and screenshots come before the accessibility filter
+    graph.add_pass(
+        "photo",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            if let (Some(gui), Some((persp, _))) = (app.gui.as_mut(), &app.editor_cameras) {
+                gui.photo_mode_mut().render(
+                    ctx.gl,
+                    &ctx.viewport,
+                    persp.near_plane,
+                    persp.far_plane,
+                );
+            }
+            Ok(())
+        },
+    );
+
+    graph.add_pass(
+        "colorblind",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, app: &mut App| {
+            if let (Some(filter), Some(cvars)) = (&app.colorblind_filter, &app.cvars) {
+                let settings = AccessibilitySettings::from_cvars(&cvars.lock().unwrap());
+                filter.apply(ctx.gl, &ctx.viewport, &settings);
+            }
+            Ok(())
+        },
+    );
+
+    graph
 }
 
 impl Drop for App {
     fn drop(&mut self) {
-        self.egui_painter.as_mut().unwrap().destroy();
+        if let Some(egui_painter) = &mut self.egui_painter {
+            egui_painter.destroy();
+        }
+        for extra_window in &mut self.extra_windows {
+            extra_window.destroy();
+        }
+        if let (Some(gpu_timers), Some(context)) = (&mut self.gpu_timers, &self.context) {
+            gpu_timers.destroy(context);
+        }
+        if let (Some(filter), Some(context)) = (&self.colorblind_filter, &self.context) {
+            filter.destroy(context);
+        }
+        if let (Some(renderer), Some(context)) = (&mut self.particle_renderer, &self.context) {
+            renderer.destroy(context);
+        }
+        if let (Some(renderer), Some(context)) = (&self.sprite_renderer, &self.context) {
+            renderer.destroy(context);
+        }
+        if let (Some(renderer), Some(context)) = (&self.tilemap_renderer, &self.context) {
+            renderer.destroy(context);
+        }
+        if let (Some(renderer), Some(context)) = (&self.text_renderer, &self.context) {
+            renderer.destroy(context);
+        }
+        if let (Some(renderer), Some(context)) = (&mut self.game_ui_renderer, &self.context) {
+            renderer.destroy(context);
+        }
+        if let (Some(render_graph), Some(context)) = (&mut self.render_graph, &self.context) {
+            render_graph.destroy(context);
+        }
+        if let (Some(gui), Some(context)) = (&mut self.gui, &self.context) {
+            gui.destroy(context);
+        }
+        if let (Some(scene_graph), Some(context)) = (&mut self.scene_graph, &self.context) {
+            for scene in &mut scene_graph.scenes {
+                scene.destroy(context);
+            }
+            scene_graph.levels.destroy(context);
+        }
     }
 }
 
 fn main() {
     let event_loop = EventLoop::new().unwrap();
 
-    // ControlFlow::Wait pauses the event loop if no events are available to process.
+    // ControlFlow::Wait pauses the event loop if no events are available to process. The
+    // frame pacer picks between them once there's a window, see about_to_wait
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::new();
+    let log_rx = logging::init();
+
+    // `cruel_game_engine pack ...` builds an asset pack without opening a window
+    if std::env::args().nth(1).as_deref() == Some("pack") {
+        if let Err(e) = pack::run_command(std::env::args().skip(1)) {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    let headless = match HeadlessOptions::from_args(std::env::args()) {
+        Ok(headless) => headless,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    #[cfg(feature = "tracy")]
+    {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(tracing_tracy::TracyLayer::default());
+        if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+            log::error!("Failed to install the Tracy subscriber: {}", e);
+        }
+    }
+
+    let mut app = App::new(log_rx);
 
     // Add entities, components and systems to the app here
-    app.request_texture("assets/texture.jpg", "sigma.jpg".to_string());
-    app.request_mesh("models/bunny_gltf.glb", "bunny.glb".to_string());
+    if headless.is_none() {
+        app.request_texture("assets/texture.jpg", "sigma.jpg".to_string());
+        app.request_mesh("models/bunny_gltf.glb", "bunny.glb".to_string());
+    }
+    app.headless = headless;
 
     // Run the app when behaviour is defined
     event_loop.run_app(&mut app).unwrap();
+
+    if app.headless_failed {
+        drop(app);
+        std::process::exit(1);
+    }
 }
@@ -1,16 +1,84 @@
-use cgmath::SquareMatrix;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use cgmath::{InnerSpace, SquareMatrix};
 use glow::HasContext;
 
 use crate::{
-    data::{Color, DynamicPrimitiveInstance, LoadedMesh, StaticPrimitiveInstance, VertexData},
-    handles::MeshHandle,
+    component::Component,
+    data::{
+        Color, DynamicPrimitiveInstance, LoadedMaterial, LoadedMesh, LoadedNode, LoadedPrimitive,
+        MaterialOverride, StaticPrimitiveInstance, VertexData,
+    },
+    error::{EngineError, EngineResult},
+    geometry,
+    gl_state::GlState,
+    handles::{MeshHandle, TextureHandle},
     loader::AssetLoader,
     opengl::{DynamicRenderData, Layout, StaticRenderData},
+    raycast::Aabb,
+    socket::{Attachment, Socket},
+    textures::Texture,
+    transform::{MeshTransform, TransformConstraints},
+    upload::{UploadQueue, UploadStatus},
     viewport::Viewport,
 };
 
+/// Per primitive uniforms of the program `StaticMesh::render` draws with, all optional.
+pub struct PrimitiveUniforms {
+    pub has_normal_map: Option<glow::UniformLocation>,
+    pub normal_map_uv: Option<glow::UniformLocation>,
+    pub has_occlusion_map: Option<glow::UniformLocation>,
+    pub occlusion_map_uv: Option<glow::UniformLocation>,
+    pub occlusion_strength: Option<glow::UniformLocation>,
+    pub has_vertex_colors: Option<glow::UniformLocation>,
+    pub metallic: Option<glow::UniformLocation>,
+    pub roughness: Option<glow::UniformLocation>,
+    pub emissive: Option<glow::UniformLocation>,
+    pub clearcoat: Option<glow::UniformLocation>,
+    pub clearcoat_roughness: Option<glow::UniformLocation>,
+    pub transmission: Option<glow::UniformLocation>,
+    pub instanced: Option<glow::UniformLocation>,
+}
+
+impl PrimitiveUniforms {
+    pub fn new(context: &glow::Context, program: glow::Program) -> Self {
+        let location = |name: &str| unsafe { context.get_uniform_location(program, name) };
+        Self {
+            has_normal_map: location("has_normal_map"),
+            normal_map_uv: location("normal_map_uv"),
+            has_occlusion_map: location("has_occlusion_map"),
+            occlusion_map_uv: location("occlusion_map_uv"),
+            occlusion_strength: location("occlusion_strength"),
+            has_vertex_colors: location("has_vertex_colors"),
+            metallic: location("metallic"),
+            roughness: location("roughness"),
+            emissive: location("emissive"),
+            clearcoat: location("clearcoat"),
+            clearcoat_roughness: location("clearcoat_roughness"),
+            transmission: location("transmission"),
+            instanced: location("instanced"),
+        }
+    }
+}
+
+/// Stays with a static mesh when meshes before it are removed or inserted, unlike its index.
+/// Not saved, a loaded scene's meshes get new ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StaticMeshId(pub u64);
+
+impl StaticMeshId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StaticMesh {
+    pub id: StaticMeshId,
     pub name: String,                             // Nametag
     pub handle: MeshHandle,                       // Reference to loaded mesh asset
     pub primitives: Vec<StaticPrimitiveInstance>, // For multi-material meshes
@@ -18,87 +86,802 @@ pub struct StaticMesh {
     pub translation: cgmath::Vector3<f32>,
     pub rotation: cgmath::Vector3<f32>, // Later: cgmath::Quaternion<f32>,
     pub scale: cgmath::Vector3<f32>,
+
+    pub sockets: Vec<Socket>,
+    pub attachment: Option<Attachment>, // Follows a socket on another mesh when set
+    pub constraints: TransformConstraints,
+
+    pub lods: Vec<MeshLod>, // Lower detail levels, nearest first
+
+    pub bounds: Option<Aabb>, // Local space, None if the mesh has no vertices
+
+    pub base_color_map: Option<(TextureHandle, glow::NativeTexture)>, // Replaces the scene's texture
+
+    pub components: Vec<Component>, // At most one of each kind
+}
+
+// Grid cells along the longest side for each generated level
+pub const LOD_RESOLUTIONS: [u32; 3] = [32, 16, 8];
+
+// How many times its own radius away a mesh switches to its first lower level,
+// every further level doubles it. Keeps switches at roughly the same size on screen.
+const LOD_DISTANCE_PER_RADIUS: f32 = 10.0;
+
+fn default_lod_distance(bounds: Option<&Aabb>, level: usize) -> f32 {
+    let radius = bounds.map_or(1.0, |bounds| (bounds.max - bounds.min).magnitude() * 0.5);
+    radius * LOD_DISTANCE_PER_RADIUS * 2f32.powi(level as i32)
+}
+
+/// A lower detail version of a static mesh, used from `distance` away from the camera on.
+#[derive(Debug, Clone)]
+pub struct MeshLod {
+    pub distance: f32,
+    pub primitives: Vec<StaticPrimitiveInstance>,
+    pub generated: bool, // Simplified from the full mesh rather than imported
 }
 
 impl StaticMesh {
+    #[tracing::instrument(name = "StaticMesh::new", skip_all, fields(name = %name))]
     pub fn new(
         context: &glow::Context,
         name: String,
         handle: MeshHandle,
         asset_loader: &AssetLoader,
-    ) -> Self {
+    ) -> EngineResult<Self> {
         let loaded_mesh = asset_loader
             .loaded_mesh_data
             .get(&handle)
-            .expect("Mesh handle not found in asset loader");
+            .ok_or(EngineError::MissingMesh(handle))?;
 
-        let mut primitives = Vec::new();
+        let all_primitives: Vec<usize> = (0..loaded_mesh.primitives.len()).collect();
+        let mut mesh = Self::from_primitives(context, name, handle, &all_primitives, asset_loader)?;
+        mesh.lods = mesh.imported_lods(context, asset_loader)?;
+        Ok(mesh)
+    }
 
-        for (i, primitive) in loaded_mesh.primitives.iter().enumerate() {
-            let layouts = determine_layouts(&primitive.vertex_data);
-            let stride = calculate_stride(&layouts);
+    fn imported_lods(
+        &self,
+        context: &glow::Context,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Vec<MeshLod>> {
+        let loaded_mesh = asset_loader
+            .loaded_mesh_data
+            .get(&self.handle)
+            .ok_or(EngineError::MissingMesh(self.handle))?;
+
+        let mut lods = Vec::new();
+        for loaded_primitives in &loaded_mesh.lods {
+            // Only the primitives this mesh draws, under the same indices
+            let primitives = self
+                .primitives
+                .iter()
+                .filter_map(|instance| {
+                    let i = instance.primitive_index;
+                    Some((i, loaded_primitives.get(i)?))
+                })
+                .map(|(i, primitive)| {
+                    let indices = primitive.indices.as_deref().unwrap_or(&[]);
+                    build_primitive(context, &self.name, i, primitive, indices, asset_loader)
+                })
+                .collect::<EngineResult<Vec<_>>>()?;
+            lods.push(MeshLod {
+                distance: default_lod_distance(self.bounds.as_ref(), lods.len()),
+                primitives,
+                generated: false,
+            });
+        }
+        Ok(lods)
+    }
 
-            let interleaved_vertices = interleave_vertex_data(&primitive.vertex_data);
+    /// A copy with its own GPU objects, built again from the same loaded mesh. Keeps the
+    /// transform, sockets, attachment and material values. The caller retains the handles.
+    pub fn duplicate(
+        &self,
+        context: &glow::Context,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Self> {
+        let primitive_indices: Vec<usize> = self
+            .primitives
+            .iter()
+            .map(|primitive| primitive.primitive_index)
+            .collect();
+        let mut copy = Self::from_primitives(
+            context,
+            self.name.clone(),
+            self.handle,
+            &primitive_indices,
+            asset_loader,
+        )?;
+        for (slot, primitive) in self.primitives.iter().enumerate() {
+            if primitive.material.is_some() {
+                let material = primitive.material.clone();
+                copy.set_primitive_material(context, slot, material, asset_loader)?;
+            }
+        }
 
-            let render_data = StaticRenderData::new(
-                context,
-                &interleaved_vertices,
-                &primitive.indices.as_deref().unwrap_or(&[]),
-                stride,
-                layouts,
-            );
+        // Edited in the Properties panel, so they may differ from the loaded material
+        for (copied, primitive) in copy.primitives.iter_mut().zip(&self.primitives) {
+            copied.metallic = primitive.metallic;
+            copied.roughness = primitive.roughness;
+            copied.emissive = primitive.emissive;
+            copied.clearcoat = primitive.clearcoat;
+            copied.clearcoat_roughness = primitive.clearcoat_roughness;
+            copied.transmission = primitive.transmission;
+            copied.occlusion_strength = primitive.occlusion_strength;
+        }
 
-            primitives.push(StaticPrimitiveInstance {
-                primitive_index: i,
-                render_data: Some(render_data),
-            });
+        if self.lods.iter().any(|lod| lod.generated) {
+            copy.generate_lods(context, asset_loader)?;
+        } else if !self.lods.is_empty() {
+            copy.lods = copy.imported_lods(context, asset_loader)?;
+            copy.apply_lod_materials(context, asset_loader)?;
+        }
+        for (copied, lod) in copy.lods.iter_mut().zip(&self.lods) {
+            copied.distance = lod.distance;
         }
 
-        StaticMesh {
+        if let Some((handle, _)) = self.base_color_map {
+            copy.set_base_color_map(context, handle, asset_loader)?;
+        }
+
+        copy.translation = self.translation;
+        copy.rotation = self.rotation;
+        copy.scale = self.scale;
+        copy.sockets = self.sockets.clone();
+        copy.attachment = self.attachment.clone();
+        copy.constraints = self.constraints;
+        copy.components = self.components.clone();
+        Ok(copy)
+    }
+
+    /// One node of an imported scene, with the node's local transform. Imported levels of
+    /// detail cover the whole file, so they're left out.
+    pub fn from_node(
+        context: &glow::Context,
+        handle: MeshHandle,
+        node: &LoadedNode,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Self> {
+        let mut mesh = Self::from_primitives(
+            context,
+            node.name.clone(),
+            handle,
+            &node.primitives,
+            asset_loader,
+        )?;
+        MeshTransform::from_node(node).apply_to(&mut mesh);
+        Ok(mesh)
+    }
+
+    /// Only some of a loaded mesh's primitives, `primitives` are indices into them.
+    pub fn from_primitives(
+        context: &glow::Context,
+        name: String,
+        handle: MeshHandle,
+        primitives: &[usize],
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Self> {
+        let loaded_mesh = asset_loader
+            .loaded_mesh_data
+            .get(&handle)
+            .ok_or(EngineError::MissingMesh(handle))?;
+        let loaded_primitives: Vec<(usize, &LoadedPrimitive)> = primitives
+            .iter()
+            .filter_map(|&i| Some((i, loaded_mesh.primitives.get(i)?)))
+            .collect();
+
+        let bounds = Aabb::from_points(
+            loaded_primitives
+                .iter()
+                .flat_map(|(_, primitive)| primitive.vertex_data.positions.iter().copied()),
+        );
+
+        let primitives = loaded_primitives
+            .into_iter()
+            .map(|(i, primitive)| {
+                let indices = primitive.indices.as_deref().unwrap_or(&[]);
+                build_primitive(context, &name, i, primitive, indices, asset_loader)
+            })
+            .collect::<EngineResult<Vec<_>>>()?;
+
+        Ok(StaticMesh {
+            id: StaticMeshId::next(),
             name,
             handle,
             primitives,
             translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
             rotation: cgmath::Vector3::new(0.0, 0.0, 0.0),
             scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            sockets: Vec::new(),
+            attachment: None,
+            constraints: TransformConstraints::default(),
+            lods: Vec::new(),
+            bounds,
+            base_color_map: None,
+            components: Vec::new(),
+        })
+    }
+
+    /// Replaces the levels of detail with simplified copies of the full detail mesh, one per
+    /// entry of `LOD_RESOLUTIONS`. A level that wouldn't remove any triangles is left out.
+    pub fn generate_lods(
+        &mut self,
+        context: &glow::Context,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        let loaded_mesh = asset_loader
+            .loaded_mesh_data
+            .get(&self.handle)
+            .ok_or(EngineError::MissingMesh(self.handle))?;
+        // Meshes of a single node only draw some of the file's primitives
+        let own_primitives: Vec<(usize, &LoadedPrimitive)> = self
+            .primitives
+            .iter()
+            .filter_map(|instance| {
+                let i = instance.primitive_index;
+                Some((i, loaded_mesh.primitives.get(i)?))
+            })
+            .collect();
+
+        let mut lods = Vec::new();
+        let mut previous_triangles = self.triangle_count(0);
+        for resolution in LOD_RESOLUTIONS {
+            let indices: Vec<Vec<u32>> = own_primitives
+                .iter()
+                .map(|(_, primitive)| match primitive.mode {
+                    glow::TRIANGLES => geometry::simplify(
+                        &primitive.vertex_data.positions,
+                        primitive.indices.as_deref(),
+                        resolution,
+                    ),
+                    // Strips, lines and points are kept as they are
+                    _ => primitive.indices.clone().unwrap_or_default(),
+                })
+                .collect();
+            let triangles = own_primitives
+                .iter()
+                .zip(&indices)
+                .map(|((_, primitive), indices)| match (primitive.mode, indices.len()) {
+                    (glow::TRIANGLES, count) => count / 3,
+                    (mode, 0) => triangles_drawn(mode, primitive.vertex_data.positions.len()),
+                    (mode, count) => triangles_drawn(mode, count),
+                })
+                .sum();
+            if triangles == 0 || triangles >= previous_triangles {
+                continue;
+            }
+            previous_triangles = triangles;
+
+            let primitives = own_primitives
+                .iter()
+                .zip(&indices)
+                .map(|((i, primitive), indices)| {
+                    build_primitive(context, &self.name, *i, primitive, indices, asset_loader)
+                })
+                .collect::<EngineResult<Vec<_>>>()?;
+            lods.push(MeshLod {
+                distance: default_lod_distance(self.bounds.as_ref(), lods.len()),
+                primitives,
+                generated: true,
+            });
+        }
+
+        self.clear_lods(context);
+        self.lods = lods;
+        self.apply_lod_materials(context, asset_loader)
+    }
+
+    /// Removes the lower levels of detail along with their buffers and textures.
+    pub fn clear_lods(&mut self, context: &glow::Context) {
+        for lod in self.lods.drain(..) {
+            for primitive in &lod.primitives {
+                destroy_primitive(context, primitive);
+            }
+        }
+    }
+
+    /// Deletes the buffers and textures of every level, the mesh can't be drawn afterwards.
+    pub fn destroy(&self, context: &glow::Context) {
+        let lod_primitives = self.lods.iter().flat_map(|lod| &lod.primitives);
+        for primitive in self.primitives.iter().chain(lod_primitives) {
+            destroy_primitive(context, primitive);
+        }
+        if let Some((_, texture)) = self.base_color_map {
+            unsafe { context.delete_texture(texture) };
+        }
+    }
+
+    /// Draws the mesh with a loaded texture instead of the scene's. Returns the handle of the one
+    /// it replaces, the caller keeps the references.
+    pub fn set_base_color_map(
+        &mut self,
+        context: &glow::Context,
+        handle: TextureHandle,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<Option<TextureHandle>> {
+        let loaded_texture = asset_loader
+            .loaded_texture_data
+            .get(&handle)
+            .ok_or(EngineError::MissingTexture(handle))?;
+        let texture = Texture::from_loaded_data(context, None, loaded_texture.clone())?;
+
+        let previous = self.base_color_map.replace((handle, texture.texture));
+        Ok(previous.map(|(previous_handle, previous_texture)| {
+            unsafe { context.delete_texture(previous_texture) };
+            previous_handle
+        }))
+    }
+
+    /// Draws a primitive, along with its lower levels of detail, with the material of a
+    /// primitive in another loaded mesh. None goes back to its own. The textures are copied, the
+    /// other mesh doesn't have to stay loaded.
+    pub fn set_primitive_material(
+        &mut self,
+        context: &glow::Context,
+        slot: usize,
+        material: Option<MaterialOverride>,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        let Some(primitive) = self.primitives.get_mut(slot) else {
+            return Ok(());
+        };
+        if let Some(material) = &material {
+            find_material(material, asset_loader)?;
         }
+        primitive.material = material;
+        self.apply_slot_material(context, slot, 0, asset_loader)
     }
 
+    // Levels from `first_level` on, 0 being full detail, get the slot's material again
+    fn apply_slot_material(
+        &mut self,
+        context: &glow::Context,
+        slot: usize,
+        first_level: usize,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        let loaded_mesh = asset_loader
+            .loaded_mesh_data
+            .get(&self.handle)
+            .ok_or(EngineError::MissingMesh(self.handle))?;
+        let primitive_index = self.primitives[slot].primitive_index;
+        let material = match &self.primitives[slot].material {
+            Some(material) => Some(find_material(material, asset_loader)?),
+            None => loaded_mesh
+                .primitives
+                .get(primitive_index)
+                .and_then(|primitive| primitive.material.as_ref()),
+        };
+
+        // Generated levels share the full detail vertex data, imported ones have their own
+        let mut levels = vec![(&mut self.primitives, &loaded_mesh.primitives)];
+        for (level, lod) in self.lods.iter_mut().enumerate() {
+            let loaded = match loaded_mesh.lods.get(level) {
+                Some(imported) if !lod.generated => imported,
+                _ => &loaded_mesh.primitives,
+            };
+            levels.push((&mut lod.primitives, loaded));
+        }
+
+        let mut uploads = UploadQueue::new();
+        let result = levels
+            .into_iter()
+            .skip(first_level)
+            .filter_map(|(instances, loaded)| Some((instances, loaded.get(primitive_index)?)))
+            .flat_map(|(instances, loaded)| {
+                instances
+                    .iter_mut()
+                    .filter(|instance| instance.primitive_index == primitive_index)
+                    .map(move |instance| (instance, loaded))
+            })
+            .try_for_each(|(instance, loaded)| {
+                apply_material(
+                    context,
+                    &self.name,
+                    instance,
+                    &loaded.vertex_data,
+                    material,
+                    asset_loader,
+                    &mut uploads,
+                )
+            });
+        uploads.flush(context);
+        result
+    }
+
+    // Lower levels that were just built only have their own materials
+    fn apply_lod_materials(
+        &mut self,
+        context: &glow::Context,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        for slot in 0..self.primitives.len() {
+            if self.primitives[slot].material.is_some() {
+                self.apply_slot_material(context, slot, 1, asset_loader)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 0 is full detail, `n` is `lods[n - 1]`.
+    pub fn lod_for_distance(&self, distance: f32) -> usize {
+        self.lods
+            .iter()
+            .rposition(|lod| distance >= lod.distance)
+            .map_or(0, |i| i + 1)
+    }
+
+    pub fn lod_primitives(&self, level: usize) -> &[StaticPrimitiveInstance] {
+        match level {
+            0 => &self.primitives,
+            level => self
+                .lods
+                .get(level - 1)
+                .map_or(&self.primitives, |lod| &lod.primitives),
+        }
+    }
+
+    pub fn triangle_count(&self, level: usize) -> usize {
+        self.lod_primitives(level)
+            .iter()
+            .filter_map(|primitive| Some((primitive.mode, primitive.render_data.as_ref()?)))
+            .map(|(mode, render_data)| match render_data.index_count {
+                0 => triangles_drawn(mode, render_data.vertex_count as usize),
+                indices => triangles_drawn(mode, indices as usize),
+            })
+            .sum()
+    }
+
+    // Rotation is stored in degrees since that is what the Properties panel edits
     pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
         cgmath::Matrix4::from_translation(self.translation)
-            * cgmath::Matrix4::from_angle_x(cgmath::Rad(self.rotation.x))
-            * cgmath::Matrix4::from_angle_y(cgmath::Rad(self.rotation.y))
-            * cgmath::Matrix4::from_angle_z(cgmath::Rad(self.rotation.z))
+            * cgmath::Matrix4::from_angle_x(cgmath::Deg(self.rotation.x))
+            * cgmath::Matrix4::from_angle_y(cgmath::Deg(self.rotation.y))
+            * cgmath::Matrix4::from_angle_z(cgmath::Deg(self.rotation.z))
             * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
     }
 
-    pub fn render(&self, context: &glow::Context) {
+    pub fn socket(&self, name: &str) -> Option<&Socket> {
+        self.sockets.iter().find(|socket| socket.name == name)
+    }
+
+    fn bind_primitive(
+        state: &mut GlState,
+        uniforms: &PrimitiveUniforms,
+        primitive: &StaticPrimitiveInstance,
+    ) {
+        state.bind_texture(1, primitive.normal_map);
+        state.bind_texture(OCCLUSION_MAP_UNIT, primitive.occlusion_map);
+        let context = state.gl();
+        unsafe {
+            context.uniform_1_i32(
+                uniforms.has_normal_map.as_ref(),
+                primitive.normal_map.is_some() as i32,
+            );
+            context.uniform_1_i32(
+                uniforms.normal_map_uv.as_ref(),
+                primitive.normal_map_uv as i32,
+            );
+            context.uniform_1_i32(
+                uniforms.has_occlusion_map.as_ref(),
+                primitive.occlusion_map.is_some() as i32,
+            );
+            context.uniform_1_i32(
+                uniforms.occlusion_map_uv.as_ref(),
+                primitive.occlusion_map_uv as i32,
+            );
+            context.uniform_1_f32(
+                uniforms.occlusion_strength.as_ref(),
+                primitive.occlusion_strength,
+            );
+            context.uniform_1_i32(
+                uniforms.has_vertex_colors.as_ref(),
+                primitive.vertex_colors as i32,
+            );
+            context.uniform_1_f32(uniforms.metallic.as_ref(), primitive.metallic);
+            context.uniform_1_f32(uniforms.roughness.as_ref(), primitive.roughness);
+            let [r, g, b] = primitive.emissive;
+            context.uniform_3_f32(uniforms.emissive.as_ref(), r, g, b);
+            context.uniform_1_f32(uniforms.clearcoat.as_ref(), primitive.clearcoat);
+            context.uniform_1_f32(
+                uniforms.clearcoat_roughness.as_ref(),
+                primitive.clearcoat_roughness,
+            );
+            context.uniform_1_f32(uniforms.transmission.as_ref(), primitive.transmission);
+        }
+    }
+
+    /// Draws a level of detail with the bound program, normal maps go to texture unit 1 and
+    /// occlusion maps to `OCCLUSION_MAP_UNIT`.
+    pub fn render(&self, state: &mut GlState, uniforms: &PrimitiveUniforms, lod: usize) {
+        for primitive in self.lod_primitives(lod) {
+            Self::render_primitive(state, uniforms, primitive);
+        }
+    }
+
+    /// Draws one primitive with its material, skipped until its buffers are uploaded.
+    pub fn render_primitive(
+        state: &mut GlState,
+        uniforms: &PrimitiveUniforms,
+        primitive: &StaticPrimitiveInstance,
+    ) {
+        if let Some(render_data) = &primitive.render_data {
+            Self::render_with_material(state, uniforms, primitive, render_data);
+        }
+    }
+
+    /// Draws `render_data` with `material`'s textures and factors, for buffers merged from
+    /// primitives that share a material. Skipped until `material` is uploaded.
+    pub fn render_with_material(
+        state: &mut GlState,
+        uniforms: &PrimitiveUniforms,
+        material: &StaticPrimitiveInstance,
+        render_data: &StaticRenderData,
+    ) {
+        if !material.upload.is_done() {
+            return;
+        }
+        Self::bind_primitive(state, uniforms, material);
+        state.bind_vertex_array(Some(render_data.vao));
+
+        let context = state.gl();
+        unsafe {
+            if render_data.ebo.is_some() {
+                context.draw_elements(
+                    material.mode,
+                    render_data.index_count,
+                    glow::UNSIGNED_INT,
+                    0,
+                );
+            } else {
+                context.draw_arrays(material.mode, 0, render_data.vertex_count);
+            }
+        }
+    }
+
+    /// Draws `count` copies, `instance_buffer` holds one column major model matrix per copy.
+    pub fn render_instanced(
+        &self,
+        state: &mut GlState,
+        uniforms: &PrimitiveUniforms,
+        instance_buffer: glow::Buffer,
+        count: i32,
+    ) {
+        if count == 0 {
+            return;
+        }
+        let context = state.gl();
         unsafe {
+            context.uniform_1_i32(uniforms.instanced.as_ref(), 1);
             for primitive in &self.primitives {
+                if !primitive.upload.is_done() {
+                    continue;
+                }
                 if let Some(render_data) = &primitive.render_data {
-                    render_data.bind(context);
+                    Self::bind_primitive(state, uniforms, primitive);
+                    state.bind_vertex_array(Some(render_data.vao));
+
+                    // A mat4 attribute takes four locations, one per column
+                    context.bind_buffer(glow::ARRAY_BUFFER, Some(instance_buffer));
+                    for column in 0..4 {
+                        let index = ATTRIB_INSTANCE_MODEL + column;
+                        context.vertex_attrib_pointer_f32(
+                            index,
+                            4,
+                            glow::FLOAT,
+                            false,
+                            16 * std::mem::size_of::<f32>() as i32,
+                            (column as usize * 4 * std::mem::size_of::<f32>()) as i32,
+                        );
+                        context.vertex_attrib_divisor(index, 1);
+                        context.enable_vertex_attrib_array(index);
+                    }
 
                     if render_data.ebo.is_some() {
-                        context.draw_elements(
-                            glow::TRIANGLES,
+                        context.draw_elements_instanced(
+                            primitive.mode,
                             render_data.index_count,
                             glow::UNSIGNED_INT,
                             0,
+                            count,
                         );
                     } else {
-                        context.draw_arrays(
-                            glow::TRIANGLES,
+                        context.draw_arrays_instanced(
+                            primitive.mode,
                             0,
                             render_data.vertex_count,
+                            count,
                         );
                     }
+
+                    // The vertex array is shared with plain draws of the same mesh
+                    for column in 0..4 {
+                        context.disable_vertex_attrib_array(ATTRIB_INSTANCE_MODEL + column);
+                    }
                 }
             }
+            context.uniform_1_i32(uniforms.instanced.as_ref(), 0);
         }
     }
 }
 
-#[derive(Debug, Clone)]
+// How many triangles a draw of `count` vertices or indices makes
+fn triangles_drawn(mode: u32, count: usize) -> usize {
+    match mode {
+        glow::TRIANGLES => count / 3,
+        glow::TRIANGLE_STRIP | glow::TRIANGLE_FAN => count.saturating_sub(2),
+        _ => 0,
+    }
+}
+
+fn destroy_primitive(context: &glow::Context, primitive: &StaticPrimitiveInstance) {
+    if let Some(render_data) = &primitive.render_data {
+        render_data.destroy(context);
+    }
+    for texture in [primitive.normal_map, primitive.occlusion_map]
+        .into_iter()
+        .flatten()
+    {
+        unsafe { context.delete_texture(texture) };
+    }
+}
+
+fn build_primitive(
+    context: &glow::Context,
+    name: &str,
+    index: usize,
+    primitive: &LoadedPrimitive,
+    indices: &[u32],
+    asset_loader: &AssetLoader,
+) -> EngineResult<StaticPrimitiveInstance> {
+    let layouts = determine_layouts(&primitive.vertex_data);
+    let stride = calculate_stride(&layouts)?;
+
+    let interleaved_vertices = interleave_vertex_data(&primitive.vertex_data)?;
+
+    // Filled over the next frames, the primitive is skipped until then
+    let mut uploads = asset_loader.uploads.lock().unwrap();
+    let upload = UploadStatus::new();
+    let render_data = StaticRenderData::new_queued(
+        context,
+        &interleaved_vertices,
+        indices,
+        stride,
+        layouts,
+        &mut uploads,
+        &upload,
+    )?;
+
+    let mut instance = StaticPrimitiveInstance {
+        primitive_index: index,
+        render_data: Some(render_data),
+        mode: primitive.mode,
+        normal_map: None,
+        normal_map_uv: 0,
+        occlusion_map: None,
+        occlusion_map_uv: 0,
+        occlusion_strength: 1.0,
+        vertex_colors: !primitive.vertex_data.colors.is_empty(),
+        metallic: 0.0,
+        roughness: 1.0,
+        emissive: [0.0; 3],
+        clearcoat: 0.0,
+        clearcoat_roughness: 0.0,
+        transmission: 0.0,
+        material: None,
+        upload,
+    };
+    if let Err(e) = apply_material(
+        context,
+        name,
+        &mut instance,
+        &primitive.vertex_data,
+        primitive.material.as_ref(),
+        asset_loader,
+        &mut uploads,
+    ) {
+        if let Some(render_data) = &instance.render_data {
+            render_data.destroy(context);
+        }
+        return Err(e);
+    }
+    Ok(instance)
+}
+
+fn find_material<'a>(
+    material: &MaterialOverride,
+    asset_loader: &'a AssetLoader,
+) -> EngineResult<&'a LoadedMaterial> {
+    asset_loader
+        .loaded_mesh_data
+        .values()
+        .find(|mesh| mesh.path == material.path)
+        .and_then(|mesh| mesh.primitives.get(material.primitive)?.material.as_ref())
+        .ok_or_else(|| EngineError::MissingMaterial {
+            path: material.path.clone(),
+            primitive: material.primitive,
+        })
+}
+
+// The primitive's fields that come from its material, replacing the textures it had. They're
+// created right away and filled by `uploads`.
+fn apply_material(
+    context: &glow::Context,
+    name: &str,
+    instance: &mut StaticPrimitiveInstance,
+    vertex_data: &VertexData,
+    material: Option<&LoadedMaterial>,
+    asset_loader: &AssetLoader,
+    uploads: &mut UploadQueue,
+) -> EngineResult<()> {
+    for texture in [instance.normal_map.take(), instance.occlusion_map.take()]
+        .into_iter()
+        .flatten()
+    {
+        unsafe { context.delete_texture(texture) };
+    }
+
+    // A set the mesh doesn't have falls back to the first one
+    let uv_set = |set: u32| {
+        if (set as usize) < vertex_data.texcoords.len() {
+            set
+        } else {
+            0
+        }
+    };
+
+    // The textures are requested when the mesh loads, see App::window_event
+    let upload = instance.upload.clone();
+    let mut material_texture = |path: Option<&PathBuf>, kind: &str| -> EngineResult<_> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        match asset_loader
+            .loaded_texture_data
+            .values()
+            .find(|texture| &texture.path == path)
+        {
+            Some(loaded) => Ok(Some(
+                Texture::from_loaded_data_queued(context, None, loaded.clone(), uploads, &upload)?
+                    .texture,
+            )),
+            None => {
+                log::warn!("{} {:?} for {} is not loaded yet", kind, path, name);
+                Ok(None)
+            }
+        }
+    };
+    instance.normal_map = match material.and_then(|material| material.normal_texture.as_ref()) {
+        Some(path) if vertex_data.tangents.is_some() => {
+            material_texture(Some(path), "Normal map")?
+        }
+        _ => None,
+    };
+    instance.occlusion_map = material_texture(
+        material.and_then(|material| material.occlusion_texture.as_ref()),
+        "Occlusion map",
+    )?;
+
+    // Plain dielectric when there's no material
+    let (metallic, roughness) = material.map_or((0.0, 1.0), |material| {
+        (material.metallic_factor, material.roughness_factor)
+    });
+    instance.metallic = metallic;
+    instance.roughness = roughness;
+    instance.emissive = material.map_or([0.0; 3], |material| {
+        material.emissive_factor.map(|channel| channel * material.emissive_strength)
+    });
+    instance.normal_map_uv = uv_set(material.map_or(0, |material| material.normal_texcoord));
+    instance.occlusion_map_uv = uv_set(material.map_or(0, |material| material.occlusion_texcoord));
+    instance.occlusion_strength = material.map_or(1.0, |material| material.occlusion_strength);
+    instance.clearcoat = material.map_or(0.0, |material| material.clearcoat_factor);
+    instance.clearcoat_roughness =
+        material.map_or(0.0, |material| material.clearcoat_roughness_factor);
+    instance.transmission = material.map_or(0.0, |material| material.transmission_factor);
+    Ok(())
+}
+
+#[derive(Debug)]
 pub struct DynamicMesh {
     pub name: String,                             // Nametag
     pub handle: MeshHandle,                       // Reference to loaded mesh asset
@@ -110,24 +893,25 @@ pub struct DynamicMesh {
 }
 
 impl DynamicMesh {
+    #[tracing::instrument(name = "DynamicMesh::new", skip_all, fields(name = %name))]
     pub fn new(
         context: &glow::Context,
         name: String,
         handle: MeshHandle,
         asset_loader: &AssetLoader,
-    ) -> Self {
+    ) -> EngineResult<Self> {
         let loaded_mesh = asset_loader
             .loaded_mesh_data
             .get(&handle)
-            .expect("Mesh handle not found in asset loader");
+            .ok_or(EngineError::MissingMesh(handle))?;
 
         let mut primitives = Vec::new();
 
         for (i, primitive) in loaded_mesh.primitives.iter().enumerate() {
             let layouts = determine_layouts(&primitive.vertex_data);
-            let stride = calculate_stride(&layouts);
+            let stride = calculate_stride(&layouts)?;
 
-            let interleaved_vertices = interleave_vertex_data(&primitive.vertex_data);
+            let interleaved_vertices = interleave_vertex_data(&primitive.vertex_data)?;
 
             let render_data = DynamicRenderData::new(
                 context,
@@ -135,22 +919,23 @@ impl DynamicMesh {
                 &primitive.indices.as_deref().unwrap_or(&[]),
                 stride,
                 layouts,
-            );
+            )?;
 
             primitives.push(DynamicPrimitiveInstance {
                 primitive_index: i,
                 render_data: Some(render_data),
+                mode: primitive.mode,
             });
         }
 
-        DynamicMesh {
+        Ok(DynamicMesh {
             name,
             handle,
             primitives,
             translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
             rotation: cgmath::Vector3::new(0.0, 0.0, 0.0),
             scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
-        }
+        })
     }
 
     pub fn update_vertices(&mut self, context: &glow::Context, new_vertices: &[f32]) {
@@ -161,6 +946,14 @@ impl DynamicMesh {
         }
     }
 
+    pub fn destroy(&mut self, context: &glow::Context) {
+        for primitive in &mut self.primitives {
+            if let Some(mut render_data) = primitive.render_data.take() {
+                render_data.destroy(context);
+            }
+        }
+    }
+
     pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
         cgmath::Matrix4::from_translation(self.translation)
             * cgmath::Matrix4::from_angle_x(cgmath::Rad(self.rotation.x))
@@ -177,14 +970,14 @@ impl DynamicMesh {
 
                     if render_data.ebo.is_some() {
                         context.draw_elements(
-                            glow::TRIANGLES,
+                            primitive.mode,
                             render_data.index_count,
                             glow::UNSIGNED_INT,
                             0,
                         );
                     } else {
                         context.draw_arrays(
-                            glow::TRIANGLES,
+                            primitive.mode,
                             0,
                             render_data.vertex_count,
                         );
@@ -331,13 +1124,27 @@ impl DynamicMesh {
 
 */
 
+// Fixed attribute locations so the shaders know where everything is,
+// whatever a mesh happens to have
+pub const ATTRIB_POSITION: u32 = 0;
+pub const ATTRIB_NORMAL: u32 = 1;
+pub const ATTRIB_TANGENT: u32 = 2;
+pub const ATTRIB_TEXCOORD: u32 = 3; // Two sets, 3 and 4
+pub const ATTRIB_COLOR: u32 = 5; // Two sets, 5 and 6
+pub const ATTRIB_JOINTS: u32 = 7;
+pub const ATTRIB_WEIGHTS: u32 = 8;
+pub const ATTRIB_INSTANCE_MODEL: u32 = 9; // Four locations, 9 to 12
+
+// Normal maps are on unit 1, 2 to 4 are the environment's
+pub const OCCLUSION_MAP_UNIT: u32 = 5;
+
 pub fn determine_layouts(vertex_data: &VertexData) -> Vec<Layout> {
     let mut layouts = Vec::new();
     let mut offset = 0;
 
     // Position: always present
     layouts.push(Layout {
-        index: 0,
+        index: ATTRIB_POSITION,
         size: 3,
         gl_type: glow::FLOAT,
         normalized: false,
@@ -345,42 +1152,37 @@ pub fn determine_layouts(vertex_data: &VertexData) -> Vec<Layout> {
     });
     offset += 3 * std::mem::size_of::<f32>();
 
-    let mut attrib_index = 1;
-
     if vertex_data.normals.is_some() {
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_NORMAL,
             size: 3,
             gl_type: glow::FLOAT,
             normalized: false,
             offset,
         });
         offset += 3 * std::mem::size_of::<f32>();
-        attrib_index += 1;
     }
 
     if vertex_data.tangents.is_some() {
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_TANGENT,
             size: 4,
             gl_type: glow::FLOAT,
             normalized: false,
             offset,
         });
         offset += 4 * std::mem::size_of::<f32>();
-        attrib_index += 1;
     }
 
-    for (i, uv) in vertex_data.texcoords.iter().enumerate() {
+    for (i, _) in vertex_data.texcoords.iter().enumerate() {
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_TEXCOORD + i as u32,
             size: 2,
             gl_type: glow::FLOAT,
             normalized: false,
             offset,
         });
         offset += 2 * std::mem::size_of::<f32>();
-        attrib_index += 1;
     }
 
     for (i, color) in vertex_data.colors.iter().enumerate() {
@@ -389,56 +1191,69 @@ pub fn determine_layouts(vertex_data: &VertexData) -> Vec<Layout> {
             Color::Rgba(_) => 4,
         };
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_COLOR + i as u32,
             size,
             gl_type: glow::FLOAT,
             normalized: false,
             offset,
         });
         offset += (size as usize * std::mem::size_of::<f32>()) as usize;
-        attrib_index += 1;
     }
 
     if vertex_data.joints.is_some() {
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_JOINTS,
             size: 4,
             gl_type: glow::UNSIGNED_SHORT,
             normalized: false,
             offset,
         });
         offset += 4 * std::mem::size_of::<u16>();
-        attrib_index += 1;
     }
 
     if vertex_data.weights.is_some() {
         layouts.push(Layout {
-            index: attrib_index,
+            index: ATTRIB_WEIGHTS,
             size: 4,
             gl_type: glow::FLOAT,
             normalized: false,
             offset,
         });
-        offset += 4 * std::mem::size_of::<f32>();
     }
 
     layouts
 }
 
-pub fn calculate_stride(layouts: &[Layout]) -> i32 {
+pub fn calculate_stride(layouts: &[Layout]) -> EngineResult<i32> {
     if let Some(last) = layouts.last() {
         let size_in_bytes = match last.gl_type {
             glow::FLOAT => 4,
             glow::UNSIGNED_SHORT => 2,
-            _ => panic!("Unsupported attribute type"),
+            other => {
+                return Err(EngineError::UnsupportedVertexData(format!(
+                    "attribute type {:#x}",
+                    other
+                )))
+            }
         };
-        (last.offset + (last.size as usize * size_in_bytes)) as i32
+        Ok((last.offset + (last.size as usize * size_in_bytes)) as i32)
     } else {
-        panic!("No layouts provided to calculate stride!");
+        Err(EngineError::UnsupportedVertexData(
+            "no layouts to calculate the stride from".to_string(),
+        ))
     }
 }
 
-pub fn interleave_vertex_data(vertex_data: &VertexData) -> Vec<f32> {
+pub fn interleave_vertex_data(vertex_data: &VertexData) -> EngineResult<Vec<f32>> {
+    // If you need support for u16 joints, you must make a separate buffer or cast them to f32,
+    // because interleaving f32 + u16 into the same VBO is problematic.
+    if vertex_data.joints.is_some() || vertex_data.weights.is_some() {
+        return Err(EngineError::UnsupportedVertexData(
+            "joints/weights are not implemented yet (interleaving u16+f32 needs a different approach)"
+                .to_string(),
+        ));
+    }
+
     let vertex_count = vertex_data.positions.len();
     let mut interleaved = Vec::with_capacity(vertex_count * 20); // estimate; grows automatically
 
@@ -468,15 +1283,7 @@ pub fn interleave_vertex_data(vertex_data: &VertexData) -> Vec<f32> {
                 Color::Rgba(colors) => interleaved.extend_from_slice(&colors[i]),
             }
         }
-
-        // For joints + weights: NOTE
-        // If you need support for u16 joints, you must make a separate buffer or cast them to f32,
-        // because interleaving f32 + u16 into the same VBO is problematic.
-        // For now: skip them or error until you handle them properly:
-        if vertex_data.joints.is_some() || vertex_data.weights.is_some() {
-            panic!("interleave_vertex_data: joints/weights not implemented yet (interleaving u16+f32 needs a different approach)");
-        }
     }
 
-    interleaved
+    Ok(interleaved)
 }
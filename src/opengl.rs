@@ -1,5 +1,11 @@
 use glow::*;
 
+use crate::{
+    error::{EngineError, EngineResult},
+    gl_debug,
+    upload::{UploadQueue, UploadStatus},
+};
+
 #[derive(Debug, Clone)]
 pub struct Layout {
     pub index: u32,
@@ -40,31 +46,82 @@ impl StaticRenderData {
         indices: &[u32],
         stride: i32,
         layouts: Vec<Layout>,
-    ) -> Self {
+    ) -> EngineResult<Self> {
+        let mut uploads = UploadQueue::new();
+        let render_data = Self::new_queued(
+            context,
+            vertices,
+            indices,
+            stride,
+            layouts,
+            &mut uploads,
+            &UploadStatus::new(),
+        )?;
+        uploads.flush(context);
+        Ok(render_data)
+    }
+
+    /// Allocates the buffers but leaves filling them to `uploads`, don't draw before
+    /// `status` is done.
+    #[tracing::instrument(
+        name = "StaticRenderData::new",
+        skip_all,
+        fields(bytes = vertices.len() * 4)
+    )]
+    pub fn new_queued(
+        context: &glow::Context,
+        vertices: &[f32],
+        indices: &[u32],
+        stride: i32,
+        layouts: Vec<Layout>,
+        uploads: &mut UploadQueue,
+        status: &UploadStatus,
+    ) -> EngineResult<Self> {
         unsafe {
-            let vao = context.create_vertex_array().expect("Failed to create VAO");
+            let vao = context.create_vertex_array().map_err(EngineError::Gl)?;
             context.bind_vertex_array(Some(vao));
 
-            let vbo = context.create_buffer().expect("Failed to create VBO");
+            let vbo = context.create_buffer().map_err(EngineError::Gl)?;
             context.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-            context.buffer_data_u8_slice(
+            context.buffer_data_size(
                 glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(vertices),
+                std::mem::size_of_val(vertices) as i32,
                 glow::STATIC_DRAW,
             );
+            gl_debug::check_errors(context, "vertex buffer_data");
+            uploads.buffer(vbo, bytemuck::cast_slice(vertices).to_vec(), status);
 
-            let ebo = context.create_buffer().expect("Failed to create EBO");
+            let ebo = context.create_buffer().map_err(EngineError::Gl)?;
             context.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
-            context.buffer_data_u8_slice(
+            context.buffer_data_size(
                 glow::ELEMENT_ARRAY_BUFFER,
-                bytemuck::cast_slice(indices),
+                std::mem::size_of_val(indices) as i32,
                 glow::STATIC_DRAW,
             );
+            gl_debug::check_errors(context, "index buffer_data");
+            uploads.buffer(ebo, bytemuck::cast_slice(indices).to_vec(), status);
+
+            // The vertex array keeps the layout and index buffer, so drawing only binds it
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            for layout in &layouts {
+                context.vertex_attrib_pointer_f32(
+                    layout.index,
+                    layout.size,
+                    layout.gl_type,
+                    layout.normalized,
+                    stride,
+                    layout.offset as i32,
+                );
+                context.enable_vertex_attrib_array(layout.index);
+            }
+            gl_debug::check_errors(context, "vertex_attrib_pointer");
+            context.bind_vertex_array(None);
+            context.bind_buffer(glow::ARRAY_BUFFER, None);
 
             let vertex_count = (vertices.len() as i32) / (stride / std::mem::size_of::<f32>() as i32);
             let index_count = indices.len() as i32;
 
-            Self {
+            Ok(Self {
                 vao,
                 vbo,
                 ebo: Some(ebo),
@@ -73,38 +130,213 @@ impl StaticRenderData {
 
                 vertex_count,
                 index_count,
-            }
+            })
         }
     }
 
+    /// The attributes were set up in `new`, the vertex array remembers them.
     pub fn bind(&self, context: &glow::Context) {
         unsafe {
             context.bind_vertex_array(Some(self.vao));
-            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+        }
+    }
 
-            for layout in &self.layouts {
-                context.vertex_attrib_pointer_f32(
-                    layout.index,
-                    layout.size,
-                    layout.gl_type,
-                    layout.normalized,
-                    self.stride,
-                    layout.offset as i32,
+    pub fn destroy(&self, context: &glow::Context) {
+        unsafe {
+            context.delete_vertex_array(self.vao);
+            context.delete_buffer(self.vbo);
+            if let Some(ebo) = self.ebo {
+                context.delete_buffer(ebo);
+            }
+        }
+    }
+}
+
+const STREAM_REGIONS: usize = 3; // One being written, two the GPU may still be reading
+const FENCE_TIMEOUT_NS: i32 = 1_000_000_000;
+
+/// True when buffers can stay mapped while they're drawn from, GL 4.4 or ARB_buffer_storage.
+pub fn supports_persistent_mapping(context: &glow::Context) -> bool {
+    let version = context.version();
+    (!version.is_embedded && (version.major, version.minor) >= (4, 4))
+        || context
+            .supported_extensions()
+            .contains("GL_ARB_buffer_storage")
+}
+
+/// A vertex buffer that's written every frame without waiting on draws that still read it.
+/// It's split into regions that are used in turn and fenced when they're left, so a region is
+/// only written again once the GPU is done with it. Where it's supported the buffer stays
+/// mapped, otherwise the regions are filled with buffer_sub_data.
+#[derive(Debug)]
+pub struct StreamBuffer {
+    buffer: NativeBuffer,
+    region_size: usize, // Bytes
+    region: usize,
+    written: usize, // Bytes already used in the current region
+    sync: RegionSync,
+}
+
+// The fences and the mapping are raw pointers, but they're only ever used on the thread the
+// context is current on
+#[derive(Debug)]
+struct RegionSync {
+    fences: [Option<Fence>; STREAM_REGIONS],
+    mapped: Option<*mut u8>,
+}
+
+unsafe impl Send for RegionSync {}
+unsafe impl Sync for RegionSync {}
+
+impl StreamBuffer {
+    /// `region_size` is how many bytes fit in a frame, it grows when more are written at once.
+    pub fn new(context: &glow::Context, region_size: usize) -> EngineResult<Self> {
+        let region_size = region_size.max(4);
+        let (buffer, mapped) = Self::allocate(context, region_size)?;
+        Ok(Self {
+            buffer,
+            region_size,
+            region: 0,
+            written: 0,
+            sync: RegionSync {
+                fences: [None; STREAM_REGIONS],
+                mapped,
+            },
+        })
+    }
+
+    fn allocate(
+        context: &glow::Context,
+        region_size: usize,
+    ) -> EngineResult<(NativeBuffer, Option<*mut u8>)> {
+        let size = (region_size * STREAM_REGIONS) as i32;
+        unsafe {
+            let buffer = context.create_buffer().map_err(EngineError::Gl)?;
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            let mapped = if supports_persistent_mapping(context) {
+                let access =
+                    glow::MAP_WRITE_BIT | glow::MAP_PERSISTENT_BIT | glow::MAP_COHERENT_BIT;
+                // Dynamic storage too, so buffer_sub_data still works if mapping fails
+                let flags = access | glow::DYNAMIC_STORAGE_BIT;
+                context.buffer_storage(glow::ARRAY_BUFFER, size, None, flags);
+                let pointer = context.map_buffer_range(glow::ARRAY_BUFFER, 0, size, access);
+                (!pointer.is_null()).then_some(pointer)
+            } else {
+                context.buffer_data_size(glow::ARRAY_BUFFER, size, glow::STREAM_DRAW);
+                None
+            };
+            gl_debug::check_errors(context, "stream buffer storage");
+            context.bind_buffer(glow::ARRAY_BUFFER, None);
+            Ok((buffer, mapped))
+        }
+    }
+
+    /// The buffer to draw from, it's a new one after a write that made it grow.
+    pub fn buffer(&self) -> NativeBuffer {
+        self.buffer
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.sync.mapped.is_some()
+    }
+
+    /// Copies `data` in after what was written before, moving on to the next region when it
+    /// doesn't fit. Returns the byte offset into `buffer` it went to.
+    pub fn write(&mut self, context: &glow::Context, data: &[u8]) -> EngineResult<usize> {
+        if data.len() > self.region_size {
+            self.grow(context, data.len())?;
+        } else if self.written + data.len() > self.region_size {
+            self.next_region(context);
+        }
+
+        let offset = self.region * self.region_size + self.written;
+        unsafe {
+            match self.sync.mapped {
+                Some(mapped) => {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), mapped.add(offset), data.len());
+                }
+                None => {
+                    context.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+                    context.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, offset as i32, data);
+                    gl_debug::check_errors(context, "stream buffer_sub_data");
+                    context.bind_buffer(glow::ARRAY_BUFFER, None);
+                }
+            }
+        }
+        // Kept 4 byte aligned for the next attribute offset
+        self.written += (data.len() + 3) & !3;
+        Ok(offset)
+    }
+
+    fn next_region(&mut self, context: &glow::Context) {
+        unsafe {
+            // Placed after every draw so far, the ones reading this region included
+            match context.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0) {
+                Ok(fence) => {
+                    if let Some(old) = self.sync.fences[self.region].replace(fence) {
+                        context.delete_sync(old);
+                    }
+                }
+                Err(e) => log::error!("Failed to fence a stream buffer region: {}", e),
+            }
+
+            self.region = (self.region + 1) % STREAM_REGIONS;
+            self.written = 0;
+
+            // Only blocks when the CPU is a whole ring of regions ahead of the GPU
+            if let Some(fence) = self.sync.fences[self.region].take() {
+                let status = context.client_wait_sync(
+                    fence,
+                    glow::SYNC_FLUSH_COMMANDS_BIT,
+                    FENCE_TIMEOUT_NS,
                 );
-                context.enable_vertex_attrib_array(layout.index);
+                if status == glow::TIMEOUT_EXPIRED || status == glow::WAIT_FAILED {
+                    log::warn!("Stream buffer region still in use, overwriting it anyway");
+                }
+                context.delete_sync(fence);
             }
+        }
+    }
 
-            if let Some(ebo) = self.ebo {
-                context.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
+    // Swaps in a buffer with regions of at least `size` bytes, GL frees the old one once the
+    // GPU is done with it
+    fn grow(&mut self, context: &glow::Context, size: usize) -> EngineResult<()> {
+        let region_size = size.next_power_of_two();
+        let (buffer, mapped) = Self::allocate(context, region_size)?;
+        self.destroy(context);
+        *self = Self {
+            buffer,
+            region_size,
+            region: 0,
+            written: 0,
+            sync: RegionSync {
+                fences: [None; STREAM_REGIONS],
+                mapped,
+            },
+        };
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, context: &glow::Context) {
+        unsafe {
+            for fence in self.sync.fences.iter_mut().filter_map(Option::take) {
+                context.delete_sync(fence);
             }
+            if self.sync.mapped.take().is_some() {
+                context.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+                context.unmap_buffer(glow::ARRAY_BUFFER);
+                context.bind_buffer(glow::ARRAY_BUFFER, None);
+            }
+            context.delete_buffer(self.buffer);
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct DynamicRenderData {
     pub vao: NativeVertexArray,
-    pub vbo: NativeBuffer,
+    pub vertices: StreamBuffer,
+    pub vertex_offset: usize, // Bytes into `vertices` where the latest vertices start
     pub ebo: Option<NativeBuffer>,
     pub stride: i32,
     pub layouts: Vec<Layout>,
@@ -114,67 +346,110 @@ pub struct DynamicRenderData {
 }
 
 impl DynamicRenderData {
+    #[tracing::instrument(
+        name = "DynamicRenderData::new",
+        skip_all,
+        fields(bytes = vertices.len() * 4)
+    )]
     pub fn new(
         context: &glow::Context,
         vertices: &[f32],
         indices: &[u32],
         stride: i32,
         layouts: Vec<Layout>,
-    ) -> Self {
+    ) -> EngineResult<Self> {
         unsafe {
-            let vao = context.create_vertex_array().unwrap();
+            let vao = context.create_vertex_array().map_err(EngineError::Gl)?;
             context.bind_vertex_array(Some(vao));
-            let vbo = context.create_buffer().unwrap();
-            context.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-            context.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(vertices),
-                glow::DYNAMIC_DRAW,
-            );
+            let mut vertex_buffer = StreamBuffer::new(context, std::mem::size_of_val(vertices))?;
+            let vertex_offset = vertex_buffer.write(context, bytemuck::cast_slice(vertices))?;
 
-            let ebo = context.create_buffer().unwrap();
+            let ebo = context.create_buffer().map_err(EngineError::Gl)?;
             context.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
             context.buffer_data_u8_slice(
                 glow::ELEMENT_ARRAY_BUFFER,
                 bytemuck::cast_slice(indices),
                 glow::DYNAMIC_DRAW,
             );
+            gl_debug::check_errors(context, "index buffer_data");
 
             let vertex_count = (vertices.len() as i32) / (stride / std::mem::size_of::<f32>() as i32);
             let index_count = indices.len() as i32;
 
-            Self {
+            Ok(Self {
                 vao,
-                vbo,
+                vertices: vertex_buffer,
+                vertex_offset,
                 ebo: Some(ebo),
                 stride,
                 layouts,
 
                 vertex_count,
                 index_count,
-            }
+            })
         }
     }
 
+    /// Writes the vertices to the next free part of the stream buffer, draws already queued
+    /// keep reading the old ones.
+    #[tracing::instrument(name = "DynamicRenderData::update_vertices", skip_all)]
     pub fn update_vertices(&mut self, context: &glow::Context, data: &[f32]) {
-        unsafe {
-            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-            context.buffer_sub_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                0,
-                bytemuck::cast_slice(data),
-            );
-            context.bind_buffer(glow::ARRAY_BUFFER, None);
+        match self.vertices.write(context, bytemuck::cast_slice(data)) {
+            Ok(offset) => {
+                self.vertex_offset = offset;
+                self.vertex_count =
+                    (data.len() as i32) / (self.stride / std::mem::size_of::<f32>() as i32);
+            }
+            Err(e) => log::error!("Failed to update dynamic vertices: {}", e),
         }
     }
 
+    /// Also points the attributes at where the latest vertices are.
     pub fn bind(&self, context: &glow::Context) {
         unsafe {
             context.bind_vertex_array(Some(self.vao));
-            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            context.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertices.buffer()));
+
+            for layout in &self.layouts {
+                context.vertex_attrib_pointer_f32(
+                    layout.index,
+                    layout.size,
+                    layout.gl_type,
+                    layout.normalized,
+                    self.stride,
+                    (self.vertex_offset + layout.offset) as i32,
+                );
+                context.enable_vertex_attrib_array(layout.index);
+            }
+            gl_debug::check_errors(context, "vertex_attrib_pointer");
+
             if let Some(ebo) = self.ebo {
                 context.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
             }
         }
     }
+
+    pub fn destroy(&mut self, context: &glow::Context) {
+        self.vertices.destroy(context);
+        unsafe {
+            context.delete_vertex_array(self.vao);
+            if let Some(ebo) = self.ebo {
+                context.delete_buffer(ebo);
+            }
+        }
+    }
+}
+
+/// Texture that the viewport gets copied into so a post process pass can sample it.
+pub fn create_copy_texture(gl: &glow::Context) -> Result<NativeTexture, String> {
+    unsafe {
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        Ok(texture)
+    }
 }
@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::error::{EngineError, EngineResult};
+
+const PACK_MAGIC: &[u8; 4] = b"CPAK";
+const PACK_VERSION: u32 = 1;
+pub const DEFAULT_COMPRESSION_LEVEL: &str = "19"; // Packs are built once and read many times
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    compressed_size: u64,
+    size: u64,
+}
+
+/// A read-only archive of zstd compressed files with an index up front. Files are looked up
+/// by the relative path they were packed with, like "assets/texture.jpg".
+#[derive(Debug)]
+pub struct AssetPack {
+    path: PathBuf,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl AssetPack {
+    /// Reads the index, file contents are only read when asked for.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let error = |message: String| format!("Failed to open asset pack {:?}: {}", path, message);
+        let mut file = File::open(path).map_err(|e| error(e.to_string()))?;
+
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)
+            .map_err(|e| error(e.to_string()))?;
+        if &header[0..4] != PACK_MAGIC {
+            return Err(error("not an asset pack".to_string()));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != PACK_VERSION {
+            return Err(error(format!("unsupported version {}", version)));
+        }
+        let index_size = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+
+        let mut index = vec![0u8; index_size];
+        file.read_exact(&mut index)
+            .map_err(|e| error(e.to_string()))?;
+        let entries =
+            read_index(&index).ok_or_else(|| error("the index is invalid".to_string()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// None when the file isn't in this pack.
+    pub fn read(&self, path: &Path) -> Option<Result<Vec<u8>, String>> {
+        let entry = *self.entries.get(&pack_name(path))?;
+        Some(self.read_entry(entry).map_err(|e| {
+            format!(
+                "Failed to read {:?} from asset pack {:?}: {}",
+                path, self.path, e
+            )
+        }))
+    }
+
+    fn read_entry(&self, entry: PackEntry) -> std::io::Result<Vec<u8>> {
+        // A handle per read so packs can be shared between threads without a lock
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut compressed = vec![0u8; entry.compressed_size as usize];
+        file.read_exact(&mut compressed)?;
+        zstd::bulk::decompress(&compressed, entry.size as usize)
+    }
+}
+
+/// Reads a file from the first pack that has it, or from disk when none do.
+pub fn read_asset(packs: &[AssetPack], path: &Path) -> EngineResult<Vec<u8>> {
+    let io_error = |message: String| EngineError::Io {
+        path: path.to_path_buf(),
+        message,
+    };
+    match packs.iter().find_map(|pack| pack.read(path)) {
+        Some(result) => result.map_err(io_error),
+        None => std::fs::read(path).map_err(|e| io_error(e.to_string())),
+    }
+}
+
+/// Collects files and writes them into one pack.
+#[derive(Debug, Default)]
+pub struct AssetPackWriter {
+    files: BTreeMap<String, PathBuf>, // Sorted so the same files always give the same pack
+}
+
+impl AssetPackWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, or everything below a directory, under the path as given.
+    pub fn add(&mut self, path: &Path) -> Result<(), String> {
+        if path.is_dir() {
+            let entries = std::fs::read_dir(path)
+                .map_err(|e| format!("Failed to read directory {:?}: {}", path, e))?;
+            for entry in entries {
+                let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+                self.add(&entry.path())?;
+            }
+        } else {
+            self.files.insert(pack_name(path), path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn write(&self, output: &Path, level: i32) -> Result<(), String> {
+        let mut blobs = Vec::new();
+        for (name, path) in &self.files {
+            let data =
+                std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            let compressed = zstd::bulk::compress(&data, level)
+                .map_err(|e| format!("Failed to compress {:?}: {}", path, e))?;
+            blobs.push((name, data.len() as u64, compressed));
+        }
+
+        // Offsets depend on the size of the index, which doesn't depend on the offsets
+        let index_size: usize = blobs
+            .iter()
+            .map(|(name, _, _)| 4 + name.len() + 8 * 3)
+            .sum::<usize>()
+            + 4;
+        let mut offset = (12 + index_size) as u64;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(PACK_MAGIC);
+        bytes.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(index_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&(blobs.len() as u32).to_le_bytes());
+        for (name, size, compressed) in &blobs {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&size.to_le_bytes());
+            offset += compressed.len() as u64;
+        }
+        for (_, _, compressed) in &blobs {
+            bytes.extend_from_slice(compressed);
+        }
+
+        if let Some(parent) = output
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::write(output, bytes).map_err(|e| format!("Failed to write {:?}: {}", output, e))
+    }
+}
+
+fn read_index(index: &[u8]) -> Option<HashMap<String, PackEntry>> {
+    let mut offset = 0;
+    let mut take = |count: usize| {
+        let taken = index.get(offset..offset + count)?;
+        offset += count;
+        Some(taken)
+    };
+    let read_u64 = |bytes: Option<&[u8]>| Some(u64::from_le_bytes(bytes?.try_into().ok()?));
+
+    let count = u32::from_le_bytes(take(4)?.try_into().ok()?);
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let length = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+        let name = String::from_utf8(take(length)?.to_vec()).ok()?;
+        let entry = PackEntry {
+            offset: read_u64(take(8))?,
+            compressed_size: read_u64(take(8))?,
+            size: read_u64(take(8))?,
+        };
+        entries.insert(name, entry);
+    }
+    Some(entries)
+}
+
+// "./assets\\texture.jpg" and "assets/texture.jpg" are the same file
+fn pack_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `cruel_game_engine pack <OUTPUT> <PATHS>...`, builds a pack and exits instead of starting
+/// the editor.
+pub fn run_command<I: IntoIterator<Item = String>>(args: I) -> Result<(), String> {
+    let cli = Command::new("pack")
+        .about("Packs files and directories into one compressed archive")
+        .arg(
+            Arg::new("output")
+                .required(true)
+                .help("The pack file to write"),
+        )
+        .arg(
+            Arg::new("paths")
+                .required(true)
+                .action(ArgAction::Append)
+                .help("Files and directories to pack, stored under the path as given"),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .value_parser(clap::value_parser!(i32).range(1..=22))
+                .default_value(DEFAULT_COMPRESSION_LEVEL)
+                .help("zstd compression level"),
+        );
+    let matches = cli.try_get_matches_from(args).map_err(|e| e.to_string())?;
+
+    let mut writer = AssetPackWriter::new();
+    for path in matches.get_many::<String>("paths").unwrap() {
+        writer.add(Path::new(path))?;
+    }
+    let output = Path::new(matches.get_one::<String>("output").unwrap());
+    writer.write(output, *matches.get_one::<i32>("level").unwrap())?;
+    log::info!("Packed {} files into {:?}", writer.len(), output);
+    Ok(())
+}
@@ -0,0 +1,889 @@
+use std::path::Path;
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+use glow::HasContext;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    jobs,
+    opengl::StreamBuffer,
+    rng::Rng,
+    shaders,
+    viewport::Viewport,
+};
+
+pub const PARTICLE_DIRECTORY: &str = "assets/particles";
+
+/// A value over a particle's life, keys are (time 0 to 1, value) sorted by time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub keys: Vec<(f32, f32)>,
+}
+
+impl Curve {
+    pub fn linear(from: f32, to: f32) -> Self {
+        Self {
+            keys: vec![(0.0, from), (1.0, to)],
+        }
+    }
+
+    pub fn sample(&self, t: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return 1.0;
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        for pair in self.keys.windows(2) {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return v0 + (v1 - v0) * f;
+            }
+        }
+        last.1
+    }
+}
+
+/// A color over a particle's life, keys are (time 0 to 1, RGBA) sorted by time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    pub keys: Vec<(f32, [f32; 4])>,
+}
+
+impl Gradient {
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return [1.0; 4];
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        for pair in self.keys.windows(2) {
+            let ((t0, c0), (t1, c1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return std::array::from_fn(|i| c0[i] + (c1[i] - c0[i]) * f);
+            }
+        }
+        last.1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmitterSettings {
+    pub name: String,
+    pub rate: f32,  // Particles per second
+    pub burst: u32, // Spawned at once when the effect starts
+    pub max_particles: usize,
+    pub lifetime: (f32, f32), // Seconds, min and max
+    pub speed: (f32, f32),
+    pub direction: [f32; 3],
+    pub spread: f32, // Degrees away from the direction, 180 is every direction
+    pub gravity: [f32; 3],
+    pub offset: [f32; 3], // From the effect's origin
+    pub size: f32,        // World units at a size curve value of 1
+    pub size_over_lifetime: Curve,
+    pub color_over_lifetime: Gradient,
+    pub additive: bool, // Glows instead of covering what's behind
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            name: "Emitter".to_string(),
+            rate: 20.0,
+            burst: 0,
+            max_particles: 1000,
+            lifetime: (1.0, 2.0),
+            speed: (1.0, 2.0),
+            direction: [0.0, 1.0, 0.0],
+            spread: 25.0,
+            gravity: [0.0, -1.0, 0.0],
+            offset: [0.0; 3],
+            size: 0.2,
+            size_over_lifetime: Curve::linear(1.0, 0.0),
+            color_over_lifetime: Gradient {
+                keys: vec![(0.0, [1.0, 0.8, 0.3, 1.0]), (1.0, [1.0, 0.2, 0.0, 0.0])],
+            },
+            additive: true,
+        }
+    }
+}
+
+/// A reusable effect, saved as RON under `PARTICLE_DIRECTORY`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParticleEffect {
+    pub name: String,
+    pub emitters: Vec<EmitterSettings>,
+}
+
+impl ParticleEffect {
+    pub fn new<T: ToString>(name: T) -> Self {
+        Self {
+            name: name.to_string(),
+            emitters: vec![EmitterSettings::default()],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read(path)
+            .map_err(|e| format!("Failed to read particle effect {:?}: {}", path, e))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Reads an effect from the contents of its file, `path` is only for errors.
+    pub fn parse(contents: &[u8], path: &Path) -> Result<Self, String> {
+        ron::de::from_bytes(contents)
+            .map_err(|e| format!("Failed to parse particle effect {:?}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize particle effect: {}", e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write particle effect {:?}: {}", path, e))
+    }
+}
+
+struct Particle {
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    lifetime: f32,
+}
+
+#[derive(Default)]
+struct EmitterState {
+    particles: Vec<Particle>,
+    spawn_accumulator: f32, // Fractional particles carried over to the next frame
+}
+
+/// Simulates an effect on the CPU, or only keeps its clock while a `ParticleRenderer`
+/// simulates it on the GPU. The effect can be edited while it runs.
+pub struct ParticleSystem {
+    pub effect: ParticleEffect,
+    pub origin: Vector3<f32>,
+    emitters: Vec<EmitterState>,
+    rng: Rng,
+    on_gpu: bool,
+    time: f32,     // Seconds it has been updated for
+    restarts: u32, // So the GPU simulation knows to start over too
+}
+
+impl ParticleSystem {
+    pub fn new(effect: ParticleEffect, origin: Vector3<f32>) -> Self {
+        let mut system = Self {
+            effect,
+            origin,
+            emitters: Vec::new(),
+            rng: Rng::new(0x9e3779b9),
+            on_gpu: false,
+            time: 0.0,
+            restarts: 0,
+        };
+        system.restart();
+        system
+    }
+
+    /// Clears every particle and fires the bursts again.
+    pub fn restart(&mut self) {
+        self.restarts = self.restarts.wrapping_add(1);
+        self.emitters.clear();
+        self.sync_emitters();
+        if self.on_gpu {
+            return; // The renderer fires the bursts
+        }
+        for index in 0..self.effect.emitters.len() {
+            for _ in 0..self.effect.emitters[index].burst {
+                self.spawn(index);
+            }
+        }
+    }
+
+    /// Leaves the particles to a renderer that `simulates_on_gpu`, which starts the effect over.
+    pub fn set_on_gpu(&mut self, on_gpu: bool) {
+        if self.on_gpu != on_gpu {
+            self.on_gpu = on_gpu;
+            self.restart();
+        }
+    }
+
+    pub fn is_on_gpu(&self) -> bool {
+        self.on_gpu
+    }
+
+    /// Only counts particles simulated on the CPU, the GPU's aren't read back.
+    pub fn particle_count(&self) -> usize {
+        self.emitters
+            .iter()
+            .map(|emitter| emitter.particles.len())
+            .sum()
+    }
+
+    // Emitters can be added and removed in the editor while the system runs
+    fn sync_emitters(&mut self) {
+        self.emitters
+            .resize_with(self.effect.emitters.len(), EmitterState::default);
+    }
+
+    fn spawn(&mut self, index: usize) {
+        let settings = &self.effect.emitters[index];
+        if self.emitters[index].particles.len() >= settings.max_particles {
+            return;
+        }
+        let (lifetime, speed) = (settings.lifetime, settings.speed);
+        let (direction, spread) = (Vector3::from(settings.direction), settings.spread);
+        let position = self.origin + Vector3::from(settings.offset);
+
+        // Uniform over the spherical cap around the direction
+        let direction = if direction.magnitude2() > 0.0 {
+            direction.normalize()
+        } else {
+            Vector3::unit_y()
+        };
+        let cos_spread = spread.clamp(0.0, 180.0).to_radians().cos();
+        let cos_theta = 1.0 - self.rng.next_f32() * (1.0 - cos_spread);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = self.rng.next_f32() * std::f32::consts::TAU;
+        let helper = if direction.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_z()
+        };
+        let tangent = direction.cross(helper).normalize();
+        let bitangent = direction.cross(tangent);
+        let velocity = (direction * cos_theta
+            + tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin()))
+            * self.rng.range(speed);
+
+        let lifetime = self.rng.range(lifetime).max(0.01);
+        self.emitters[index].particles.push(Particle {
+            position,
+            velocity,
+            age: 0.0,
+            lifetime,
+        });
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        if self.on_gpu {
+            return;
+        }
+        self.sync_emitters();
+
+        // Emitters move their particles on their own, spawning shares the random numbers
+        jobs::global().scope(|jobs| {
+            for (emitter, settings) in self.emitters.iter_mut().zip(&self.effect.emitters) {
+                let gravity = Vector3::from(settings.gravity);
+                jobs.spawn("update_particles", move || {
+                    for particle in &mut emitter.particles {
+                        particle.velocity += gravity * delta_time;
+                        particle.position += particle.velocity * delta_time;
+                        particle.age += delta_time;
+                    }
+                    emitter
+                        .particles
+                        .retain(|particle| particle.age < particle.lifetime);
+                });
+            }
+        });
+
+        for index in 0..self.effect.emitters.len() {
+            let emitter = &mut self.emitters[index];
+            emitter.spawn_accumulator += self.effect.emitters[index].rate.max(0.0) * delta_time;
+            let count = emitter.spawn_accumulator.floor();
+            emitter.spawn_accumulator -= count;
+            for _ in 0..count as usize {
+                self.spawn(index);
+            }
+        }
+    }
+}
+
+const INSTANCE_FLOATS: usize = 8; // Center, size and color
+const INSTANCE_BUFFER_BYTES: usize = 256 * 1024; // A frame's worth, it grows if it's not
+
+pub const MAX_GPU_PARTICLES: usize = 1 << 20; // Per emitter, 32 MB of buffer
+const GPU_PARTICLE_BYTES: usize = 32; // Position and age, velocity and lifetime
+const GPU_WORKGROUP_SIZE: u32 = 256; // local_size_x in particle_simulate.glsl
+const CURVE_SAMPLES: usize = 16; // Of each curve and gradient, sent to the vertex shader
+
+/// True when particles can be simulated in compute shaders, OpenGL 4.3 or the compute and
+/// storage buffer extensions.
+pub fn supports_gpu_particles(gl: &glow::Context) -> bool {
+    let version = gl.version();
+    let extensions = gl.supported_extensions();
+    (!version.is_embedded && (version.major, version.minor) >= (4, 3))
+        || (extensions.contains("GL_ARB_compute_shader")
+            && extensions.contains("GL_ARB_shader_storage_buffer_object"))
+}
+
+/// Draws particle systems as camera facing quads, one instanced draw per emitter.
+/// Call from a render pass after the scene so particles are depth tested against it.
+pub struct ParticleRenderer {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    quad_buffer: glow::Buffer,
+    instance_buffer: StreamBuffer,
+    instance_data: Vec<f32>,
+    gpu: Option<GpuParticles>, // None without compute, systems are simulated on the CPU then
+}
+
+impl ParticleRenderer {
+    /// With `gpu_simulation` systems that are `on_gpu` are simulated here, where it's supported.
+    pub fn new(gl: &glow::Context, gpu_simulation: bool) -> EngineResult<Self> {
+        let program =
+            shaders::load_program(gl, "shaders/particle_vertex.glsl", "shaders/particle.glsl")?;
+
+        let corners: [f32; 12] = [
+            -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, //
+            -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+        ];
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            let quad_buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+            let instance_buffer = StreamBuffer::new(gl, INSTANCE_BUFFER_BYTES)?;
+
+            gl.bind_vertex_array(Some(vertex_array));
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad_buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&corners),
+                glow::STATIC_DRAW,
+            );
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+
+            // Pointed at each emitter's part of the instance buffer when it's drawn
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_divisor(1, 1);
+            gl.enable_vertex_attrib_array(2);
+            gl.vertex_attrib_divisor(2, 1);
+
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            let gpu = if gpu_simulation && supports_gpu_particles(gl) {
+                match GpuParticles::new(gl, quad_buffer) {
+                    Ok(gpu) => Some(gpu),
+                    Err(e) => {
+                        log::warn!("Simulating particles on the CPU: {}", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            Ok(Self {
+                program,
+                vertex_array,
+                quad_buffer,
+                instance_buffer,
+                instance_data: Vec::new(),
+                gpu,
+            })
+        }
+    }
+
+    /// True when systems should be `set_on_gpu` before they're drawn with this.
+    pub fn simulates_on_gpu(&self) -> bool {
+        self.gpu.is_some()
+    }
+
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        system: &ParticleSystem,
+    ) {
+        if let (Some(gpu), true) = (&mut self.gpu, system.is_on_gpu()) {
+            gpu.simulate(gl, system);
+            gpu.render(gl, viewport, camera, system);
+            return;
+        }
+
+        begin_pass(gl, viewport, camera, self.program);
+        unsafe { gl.bind_vertex_array(Some(self.vertex_array)) };
+
+        for (settings, emitter) in system.effect.emitters.iter().zip(&system.emitters) {
+            if emitter.particles.is_empty() {
+                continue;
+            }
+
+            self.instance_data.clear();
+            for particle in &emitter.particles {
+                let t = particle.age / particle.lifetime;
+                let p = particle.position;
+                let size = settings.size * settings.size_over_lifetime.sample(t);
+                self.instance_data.extend_from_slice(&[p.x, p.y, p.z, size]);
+                self.instance_data
+                    .extend_from_slice(&settings.color_over_lifetime.sample(t));
+            }
+
+            let offset = match self
+                .instance_buffer
+                .write(gl, bytemuck::cast_slice(&self.instance_data))
+            {
+                Ok(offset) => offset as i32,
+                Err(e) => {
+                    log::error!("Failed to upload particles: {}", e);
+                    continue;
+                }
+            };
+
+            set_blend_func(gl, settings.additive);
+            unsafe {
+                let stride = (INSTANCE_FLOATS * std::mem::size_of::<f32>()) as i32;
+                gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer.buffer()));
+                gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, stride, offset);
+                gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, offset + 4 * 4);
+                gl.bind_buffer(glow::ARRAY_BUFFER, None);
+                gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, emitter.particles.len() as i32);
+            }
+        }
+
+        end_pass(gl);
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(gpu) = &mut self.gpu {
+            gpu.destroy(gl);
+        }
+        self.instance_buffer.destroy(gl);
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.quad_buffer);
+        }
+    }
+}
+
+// Blended, depth tested without writing depth, with the camera's uniforms set on `program`
+fn begin_pass(
+    gl: &glow::Context,
+    viewport: &Viewport,
+    camera: &dyn Camera,
+    program: glow::Program,
+) {
+    let view = camera.get_view();
+    let view_projection: Matrix4<f32> = camera.get_projection() * view;
+    let view_projection: &[f32; 16] = view_projection.as_ref();
+    // The first two rows of the view matrix are the camera's right and up in world space
+    let right = [view.x.x, view.y.x, view.z.x];
+    let up = [view.x.y, view.y.y, view.z.y];
+
+    unsafe {
+        gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        gl.enable(glow::BLEND);
+        gl.enable(glow::DEPTH_TEST);
+        gl.depth_mask(false);
+        gl.disable(glow::CULL_FACE);
+
+        let location = |name: &str| gl.get_uniform_location(program, name);
+        gl.use_program(Some(program));
+        gl.uniform_matrix_4_f32_slice(location("view_projection").as_ref(), false, view_projection);
+        gl.uniform_3_f32_slice(location("camera_right").as_ref(), &right);
+        gl.uniform_3_f32_slice(location("camera_up").as_ref(), &up);
+    }
+}
+
+fn set_blend_func(gl: &glow::Context, additive: bool) {
+    unsafe {
+        if additive {
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE);
+        } else {
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+}
+
+fn end_pass(gl: &glow::Context) {
+    unsafe {
+        gl.bind_vertex_array(None);
+        gl.use_program(None);
+        gl.depth_mask(true);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.disable(glow::BLEND);
+    }
+}
+
+struct GpuEmitter {
+    particles: glow::Buffer, // `capacity` slots, see particle_simulate.glsl
+    capacity: usize,
+    spawn_accumulator: f32, // Starts at the burst
+}
+
+impl GpuEmitter {
+    fn new(gl: &glow::Context, settings: &EmitterSettings) -> EngineResult<Self> {
+        let capacity = settings.max_particles.min(MAX_GPU_PARTICLES);
+        unsafe {
+            let particles = gl.create_buffer().map_err(EngineError::Gl)?;
+            // Zeroed slots are dead particles
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(particles));
+            gl.buffer_data_u8_slice(
+                glow::SHADER_STORAGE_BUFFER,
+                &vec![0; capacity.max(1) * GPU_PARTICLE_BYTES],
+                glow::DYNAMIC_COPY,
+            );
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+            Ok(Self {
+                particles,
+                capacity,
+                spawn_accumulator: settings.burst as f32,
+            })
+        }
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        unsafe { gl.delete_buffer(self.particles) };
+    }
+}
+
+// The particles of the system drawn with it live in storage buffers and never leave the GPU.
+// Only the system's clock, restarts and effect are read, so it simulates one system.
+struct GpuParticles {
+    simulate_program: glow::Program,
+    render_program: glow::Program,
+    vertex_array: glow::VertexArray, // Just the quad, instances come from the storage buffer
+    spawned: glow::Buffer,           // Counts the particles a dispatch has spawned
+    emitters: Vec<GpuEmitter>,
+    time: f32,
+    restarts: Option<u32>,
+    seed: u32,
+}
+
+impl GpuParticles {
+    fn new(gl: &glow::Context, quad_buffer: glow::Buffer) -> EngineResult<Self> {
+        let simulate_program = shaders::load_compute_program(gl, "shaders/particle_simulate.glsl")?;
+        let curve_samples = format!("CURVE_SAMPLES {}", CURVE_SAMPLES);
+        let render_program = match shaders::load_program_variant(
+            gl,
+            "shaders/particle_vertex.glsl",
+            "shaders/particle.glsl",
+            &["GPU_PARTICLES", &curve_samples],
+        ) {
+            Ok(program) => program,
+            Err(e) => {
+                unsafe { gl.delete_program(simulate_program) };
+                return Err(e);
+            }
+        };
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad_buffer));
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            let spawned = gl.create_buffer().map_err(EngineError::Gl)?;
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(spawned));
+            gl.buffer_data_size(glow::SHADER_STORAGE_BUFFER, 4, glow::DYNAMIC_DRAW);
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+
+            Ok(Self {
+                simulate_program,
+                render_program,
+                vertex_array,
+                spawned,
+                emitters: Vec::new(),
+                time: 0.0,
+                restarts: None,
+                seed: 0,
+            })
+        }
+    }
+
+    // Catches up with the system's clock, one dispatch per emitter
+    fn simulate(&mut self, gl: &glow::Context, system: &ParticleSystem) {
+        if self.restarts != Some(system.restarts) {
+            self.restarts = Some(system.restarts);
+            self.time = system.time;
+            for emitter in self.emitters.drain(..) {
+                emitter.destroy(gl);
+            }
+        }
+        let delta_time = (system.time - self.time).max(0.0);
+        self.time = system.time;
+
+        // Emitters added, removed or given a new maximum in the editor start over
+        let emitter_settings = &system.effect.emitters;
+        let kept = emitter_settings.len().min(self.emitters.len());
+        for emitter in self.emitters.drain(kept..) {
+            emitter.destroy(gl);
+        }
+        for (index, settings) in emitter_settings.iter().enumerate() {
+            let capacity = settings.max_particles.min(MAX_GPU_PARTICLES);
+            if self
+                .emitters
+                .get(index)
+                .is_some_and(|e| e.capacity == capacity)
+            {
+                continue;
+            }
+            let emitter = match GpuEmitter::new(gl, settings) {
+                Ok(emitter) => emitter,
+                Err(e) => {
+                    log::error!("Failed to create particle buffer: {}", e);
+                    break;
+                }
+            };
+            match self.emitters.get_mut(index) {
+                Some(old) => std::mem::replace(old, emitter).destroy(gl),
+                None => self.emitters.push(emitter),
+            }
+        }
+
+        let program = self.simulate_program;
+        let location = |name: &str| unsafe { gl.get_uniform_location(program, name) };
+        unsafe {
+            gl.use_program(Some(program));
+            gl.uniform_1_f32(location("delta_time").as_ref(), delta_time);
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.spawned));
+            gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 1, Some(self.spawned));
+        }
+        for (emitter, settings) in self.emitters.iter_mut().zip(emitter_settings) {
+            emitter.spawn_accumulator += settings.rate.max(0.0) * delta_time;
+            let spawn_count = emitter.spawn_accumulator.floor();
+            emitter.spawn_accumulator -= spawn_count;
+            if emitter.capacity == 0 {
+                continue;
+            }
+
+            let direction = Vector3::from(settings.direction);
+            let direction = if direction.magnitude2() > 0.0 {
+                direction.normalize()
+            } else {
+                Vector3::unit_y()
+            };
+            let origin = system.origin + Vector3::from(settings.offset);
+            let cos_spread = settings.spread.clamp(0.0, 180.0).to_radians().cos();
+            self.seed = self.seed.wrapping_add(1);
+
+            unsafe {
+                gl.buffer_sub_data_u8_slice(glow::SHADER_STORAGE_BUFFER, 0, &[0; 4]);
+                gl.uniform_1_u32(location("particle_count").as_ref(), emitter.capacity as u32);
+                gl.uniform_1_u32(location("spawn_count").as_ref(), spawn_count as u32);
+                gl.uniform_1_u32(location("seed").as_ref(), self.seed);
+                gl.uniform_3_f32(location("origin").as_ref(), origin.x, origin.y, origin.z);
+                let (x, y, z) = direction.into();
+                gl.uniform_3_f32(location("direction").as_ref(), x, y, z);
+                gl.uniform_1_f32(location("cos_spread").as_ref(), cos_spread);
+                let (min, max) = settings.lifetime;
+                gl.uniform_2_f32(location("lifetime").as_ref(), min, max);
+                let (min, max) = settings.speed;
+                gl.uniform_2_f32(location("speed").as_ref(), min, max);
+                gl.uniform_3_f32_slice(location("gravity").as_ref(), &settings.gravity);
+                gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 0, Some(emitter.particles));
+                let groups = (emitter.capacity as u32).div_ceil(GPU_WORKGROUP_SIZE);
+                gl.dispatch_compute(groups, 1, 1);
+                // The next dispatch resets the counter, the draws read the particles
+                gl.memory_barrier(
+                    glow::SHADER_STORAGE_BARRIER_BIT | glow::BUFFER_UPDATE_BARRIER_BIT,
+                );
+            }
+        }
+        unsafe {
+            gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 0, None);
+            gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 1, None);
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+            gl.use_program(None);
+        }
+    }
+
+    // Every slot is an instance, the vertex shader hides the dead ones
+    fn render(
+        &self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        system: &ParticleSystem,
+    ) {
+        begin_pass(gl, viewport, camera, self.render_program);
+        unsafe { gl.bind_vertex_array(Some(self.vertex_array)) };
+
+        let program = self.render_program;
+        let location = |name: &str| unsafe { gl.get_uniform_location(program, name) };
+        let sample_at = |i: usize| i as f32 / (CURVE_SAMPLES - 1) as f32;
+        for (emitter, settings) in self.emitters.iter().zip(&system.effect.emitters) {
+            if emitter.capacity == 0 {
+                continue;
+            }
+            let sizes: [f32; CURVE_SAMPLES] =
+                std::array::from_fn(|i| settings.size_over_lifetime.sample(sample_at(i)));
+            let colors: Vec<f32> = (0..CURVE_SAMPLES)
+                .flat_map(|i| settings.color_over_lifetime.sample(sample_at(i)))
+                .collect();
+
+            set_blend_func(gl, settings.additive);
+            unsafe {
+                gl.uniform_1_f32(location("size").as_ref(), settings.size);
+                gl.uniform_1_f32_slice(location("size_over_lifetime").as_ref(), &sizes);
+                gl.uniform_4_f32_slice(location("color_over_lifetime").as_ref(), &colors);
+                gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 0, Some(emitter.particles));
+                gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, emitter.capacity as i32);
+            }
+        }
+
+        unsafe { gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, 0, None) };
+        end_pass(gl);
+    }
+
+    fn destroy(&mut self, gl: &glow::Context) {
+        for emitter in self.emitters.drain(..) {
+            emitter.destroy(gl);
+        }
+        unsafe {
+            gl.delete_program(self.simulate_program);
+            gl.delete_program(self.render_program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.spawned);
+        }
+    }
+}
+
+/// Edits a curve with values from 0 to `max_value`. Drag keys to move them, double click
+/// to add one and right click a key to remove it.
+pub fn curve_ui(ui: &mut egui::Ui, id: &str, curve: &mut Curve, max_value: f32) -> bool {
+    let mut changed = false;
+    let size = egui::vec2(ui.available_width().min(320.0), 80.0);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::click());
+    let rect = response.rect.shrink(4.0);
+    let to_screen = |(t, v): (f32, f32)| {
+        egui::pos2(
+            rect.left() + t * rect.width(),
+            rect.bottom() - v / max_value * rect.height(),
+        )
+    };
+    let from_screen = |pos: egui::Pos2| {
+        (
+            ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+            ((rect.bottom() - pos.y) / rect.height() * max_value).clamp(0.0, max_value),
+        )
+    };
+
+    painter.rect_filled(response.rect, 2.0, ui.visuals().extreme_bg_color);
+    let line: Vec<egui::Pos2> = (0..=64)
+        .map(|i| {
+            let t = i as f32 / 64.0;
+            to_screen((t, curve.sample(t)))
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        line,
+        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+    ));
+
+    let mut removed = None;
+    for i in 0..curve.keys.len() {
+        let center = to_screen(curve.keys[i]);
+        let handle = ui.interact(
+            egui::Rect::from_center_size(center, egui::vec2(10.0, 10.0)),
+            ui.id().with((id, i)),
+            egui::Sense::click_and_drag(),
+        );
+        if handle.dragged() {
+            if let Some(pos) = handle.interact_pointer_pos() {
+                let (mut t, v) = from_screen(pos);
+                // Keys stay in order, the end keys stay at the ends
+                let min = if i == 0 { 0.0 } else { curve.keys[i - 1].0 };
+                let max = curve.keys.get(i + 1).map_or(1.0, |key| key.0);
+                t = if i == 0 || i == curve.keys.len() - 1 {
+                    curve.keys[i].0
+                } else {
+                    t.clamp(min, max)
+                };
+                curve.keys[i] = (t, v);
+                changed = true;
+            }
+        }
+        if handle.secondary_clicked() && curve.keys.len() > 2 && i != 0 && i != curve.keys.len() - 1
+        {
+            removed = Some(i);
+        }
+        let color = if handle.hovered() || handle.dragged() {
+            ui.visuals().strong_text_color()
+        } else {
+            ui.visuals().text_color()
+        };
+        painter.circle_filled(center, 4.0, color);
+    }
+    if let Some(i) = removed {
+        curve.keys.remove(i);
+        changed = true;
+    }
+
+    if response.double_clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let (t, _) = from_screen(pos);
+            let index = curve.keys.partition_point(|key| key.0 < t);
+            curve.keys.insert(index, (t, curve.sample(t)));
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Edits a gradient: a preview strip and one row per key.
+pub fn gradient_ui(ui: &mut egui::Ui, gradient: &mut Gradient) -> bool {
+    let mut changed = false;
+
+    let size = egui::vec2(ui.available_width().min(320.0), 16.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let steps = 48;
+    for i in 0..steps {
+        let t = (i as f32 + 0.5) / steps as f32;
+        let [r, g, b, a] = gradient.sample(t);
+        let x0 = rect.left() + rect.width() * i as f32 / steps as f32;
+        let x1 = rect.left() + rect.width() * (i + 1) as f32 / steps as f32;
+        ui.painter().rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x0, rect.top()), egui::pos2(x1, rect.bottom())),
+            0.0,
+            egui::Rgba::from_rgba_unmultiplied(r, g, b, a),
+        );
+    }
+
+    let mut removed = None;
+    let count = gradient.keys.len();
+    for (i, (t, color)) in gradient.keys.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::DragValue::new(t).range(0.0..=1.0).speed(0.01))
+                .changed();
+            changed |= ui.color_edit_button_rgba_unmultiplied(color).changed();
+            if count > 1 && ui.small_button("🗑").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+    if let Some(i) = removed {
+        gradient.keys.remove(i);
+        changed = true;
+    }
+    if ui.small_button("➕ Key").clicked() {
+        gradient.keys.push((1.0, gradient.sample(1.0)));
+        changed = true;
+    }
+
+    if changed {
+        gradient.keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    changed
+}
@@ -0,0 +1,390 @@
+use glow::HasContext;
+
+use crate::{
+    camera::{self, Camera},
+    capture, opengl, shaders,
+    viewport::Viewport,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotoFilter {
+    None,
+    Grayscale,
+    Sepia,
+}
+
+impl PhotoFilter {
+    pub const ALL: [PhotoFilter; 3] = [
+        PhotoFilter::None,
+        PhotoFilter::Grayscale,
+        PhotoFilter::Sepia,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PhotoFilter::None => "None",
+            PhotoFilter::Grayscale => "Grayscale",
+            PhotoFilter::Sepia => "Sepia",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PhotoSettings {
+    pub fov: f32,
+    pub focus_distance: f32,
+    pub blur_radius: f32, // Pixels, 0 turns depth of field off
+    pub exposure: f32,    // Stops
+    pub contrast: f32,
+    pub saturation: f32,
+    pub vignette: f32,
+    pub filter: PhotoFilter,
+    pub hide_ui: bool,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self {
+            fov: 45.0,
+            focus_distance: 10.0,
+            blur_radius: 0.0,
+            exposure: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            vignette: 0.0,
+            filter: PhotoFilter::None,
+            hide_ui: false,
+        }
+    }
+}
+
+/// Pauses the game and hands the player a free camera with photo controls.
+///
+/// A game enables it by keeping one around and calling `ui` inside its egui pass and `render`
+/// after the scene. Gameplay should advance by `simulation_delta` so it freezes while taking photos.
+pub struct PhotoMode {
+    pub toggle_key: egui::Key,
+    pub hide_ui_key: egui::Key,
+    pub settings: PhotoSettings,
+    active: bool,
+    saved_camera: Option<(cgmath::Point3<f32>, cgmath::Vector3<f32>)>,
+    screenshot_requested: bool,
+    post_process: Option<PhotoPostProcess>,
+    post_process_failed: bool,
+}
+
+impl PhotoMode {
+    pub fn new(toggle_key: egui::Key) -> Self {
+        Self {
+            toggle_key,
+            hide_ui_key: egui::Key::H,
+            settings: PhotoSettings::default(),
+            active: false,
+            saved_camera: None,
+            screenshot_requested: false,
+            post_process: None,
+            post_process_failed: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts photo mode at the current camera, `fov` is what the game was rendering with.
+    pub fn enter(&mut self, camera: &dyn Camera, fov: f32) {
+        if self.active {
+            return;
+        }
+        self.active = true;
+        self.saved_camera = Some((camera.get_position(), camera.get_orientation()));
+        self.settings.fov = fov;
+        self.settings.hide_ui = false;
+    }
+
+    /// Leaves photo mode and puts the camera back where the game had it.
+    pub fn exit(&mut self, camera: &mut dyn Camera) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        if let Some((position, orientation)) = self.saved_camera.take() {
+            camera.set_position(position);
+            camera.set_orientation(orientation);
+        }
+    }
+
+    /// The delta time gameplay should use, zero while photo mode is open.
+    pub fn simulation_delta(&self, delta_time: f32) -> f32 {
+        if self.active {
+            0.0
+        } else {
+            delta_time
+        }
+    }
+
+    pub fn fov_override(&self) -> Option<f32> {
+        self.active.then_some(self.settings.fov)
+    }
+
+    /// Whether the game should hide its HUD and menus.
+    pub fn ui_hidden(&self) -> bool {
+        self.active && self.settings.hide_ui
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Handles the keys, flies the camera and shows the controls. `fov` is the game's own field of view.
+    pub fn ui(&mut self, ctx: &egui::Context, camera: &mut dyn Camera, delta_time: f32, fov: f32) {
+        // Typing in a text field shouldn't toggle anything or move the camera
+        let typing = ctx.wants_keyboard_input();
+
+        if !typing && ctx.input(|input| input.key_pressed(self.toggle_key)) {
+            if self.active {
+                self.exit(camera);
+            } else {
+                self.enter(camera, fov);
+            }
+        }
+        if !self.active {
+            return;
+        }
+
+        if !typing && ctx.input(|input| input.key_pressed(self.hide_ui_key)) {
+            self.settings.hide_ui = !self.settings.hide_ui;
+        }
+
+        // Noclip, the camera goes wherever the player wants
+        if !typing && !ctx.wants_pointer_input() {
+            ctx.input(|input| camera::fly(camera, input, delta_time));
+        }
+
+        if self.settings.hide_ui {
+            return;
+        }
+
+        let mut exit = false;
+        egui::Window::new("Photo Mode")
+            .resizable(false)
+            .default_pos([20.0, 80.0])
+            .show(ctx, |ui| {
+                let settings = &mut self.settings;
+                egui::Grid::new("PhotoSettings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Field of view");
+                        ui.add(egui::Slider::new(&mut settings.fov, 10.0..=120.0).suffix("°"));
+                        ui.end_row();
+
+                        ui.label("Depth of field");
+                        ui.add(
+                            egui::Slider::new(&mut settings.blur_radius, 0.0..=16.0).suffix(" px"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Focus distance");
+                        ui.add_enabled(
+                            settings.blur_radius > 0.0,
+                            egui::Slider::new(&mut settings.focus_distance, 0.1..=100.0)
+                                .logarithmic(true),
+                        );
+                        ui.end_row();
+
+                        ui.label("Exposure");
+                        ui.add(egui::Slider::new(&mut settings.exposure, -3.0..=3.0));
+                        ui.end_row();
+
+                        ui.label("Contrast");
+                        ui.add(egui::Slider::new(&mut settings.contrast, 0.5..=2.0));
+                        ui.end_row();
+
+                        ui.label("Saturation");
+                        ui.add(egui::Slider::new(&mut settings.saturation, 0.0..=2.0));
+                        ui.end_row();
+
+                        ui.label("Vignette");
+                        ui.add(egui::Slider::new(&mut settings.vignette, 0.0..=1.0));
+                        ui.end_row();
+
+                        ui.label("Filter");
+                        egui::ComboBox::from_id_salt("PhotoFilter")
+                            .selected_text(settings.filter.label())
+                            .show_ui(ui, |ui| {
+                                for filter in PhotoFilter::ALL {
+                                    ui.selectable_value(
+                                        &mut settings.filter,
+                                        filter,
+                                        filter.label(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("📷 Take photo").clicked() {
+                        self.screenshot_requested = true;
+                    }
+                    if ui.button("Reset").clicked() {
+                        self.settings = PhotoSettings {
+                            fov,
+                            ..Default::default()
+                        };
+                    }
+                    exit = ui.button("Exit").clicked();
+                });
+                ui.weak(format!(
+                    "{:?} hides the UI, {:?} leaves photo mode",
+                    self.hide_ui_key, self.toggle_key
+                ));
+            });
+
+        if exit {
+            self.exit(camera);
+        }
+    }
+
+    /// Applies the photo effects to the viewport and saves a screenshot if one was requested.
+    /// Call after the scene has been rendered, `near_plane`/`far_plane` are the camera's.
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        near_plane: f32,
+        far_plane: f32,
+    ) {
+        if !self.active {
+            self.screenshot_requested = false;
+            return;
+        }
+
+        if self.post_process.is_none() && !self.post_process_failed {
+            match PhotoPostProcess::new(gl) {
+                Ok(post_process) => self.post_process = Some(post_process),
+                Err(e) => {
+                    log::error!("{}", e);
+                    self.post_process_failed = true;
+                }
+            }
+        }
+        if let Some(post_process) = &self.post_process {
+            post_process.apply(gl, viewport, &self.settings, near_plane, far_plane);
+        }
+
+        if std::mem::take(&mut self.screenshot_requested) {
+            if let Err(e) = capture::save_screenshot(gl, viewport) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(post_process) = self.post_process.take() {
+            post_process.destroy(gl);
+        }
+    }
+}
+
+struct PhotoPostProcess {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    color: glow::Texture,
+    depth: glow::Texture,
+}
+
+impl PhotoPostProcess {
+    fn new(gl: &glow::Context) -> Result<Self, String> {
+        let program = shaders::load_program(gl, "shaders/fullscreen.glsl", "shaders/photo.glsl")?;
+        unsafe {
+            Ok(Self {
+                program,
+                vertex_array: gl.create_vertex_array()?,
+                color: opengl::create_copy_texture(gl)?,
+                depth: opengl::create_copy_texture(gl)?,
+            })
+        }
+    }
+
+    fn apply(
+        &self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        settings: &PhotoSettings,
+        near_plane: f32,
+        far_plane: f32,
+    ) {
+        if viewport.width <= 0 || viewport.height <= 0 {
+            return;
+        }
+
+        let copy = |texture, format| unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.copy_tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                format,
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                0,
+            );
+        };
+
+        unsafe {
+            gl.active_texture(glow::TEXTURE1);
+            copy(self.depth, glow::DEPTH_COMPONENT24);
+            gl.active_texture(glow::TEXTURE0);
+            copy(self.color, glow::RGBA8);
+
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+
+            gl.use_program(Some(program));
+            gl.uniform_1_i32(location("image").as_ref(), 0);
+            gl.uniform_1_i32(location("depth").as_ref(), 1);
+            gl.uniform_1_f32(location("near_plane").as_ref(), near_plane);
+            gl.uniform_1_f32(location("far_plane").as_ref(), far_plane);
+            gl.uniform_1_f32(location("focus_distance").as_ref(), settings.focus_distance);
+            gl.uniform_1_f32(location("blur_radius").as_ref(), settings.blur_radius);
+            gl.uniform_1_f32(location("exposure").as_ref(), settings.exposure);
+            gl.uniform_1_f32(location("contrast").as_ref(), settings.contrast);
+            gl.uniform_1_f32(location("saturation").as_ref(), settings.saturation);
+            gl.uniform_1_f32(location("vignette").as_ref(), settings.vignette);
+            gl.uniform_1_i32(
+                location("filter_mode").as_ref(),
+                match settings.filter {
+                    PhotoFilter::None => 0,
+                    PhotoFilter::Grayscale => 1,
+                    PhotoFilter::Sepia => 2,
+                },
+            );
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_texture(self.color);
+            gl.delete_texture(self.depth);
+        }
+    }
+}
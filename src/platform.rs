@@ -0,0 +1,126 @@
+use std::time::SystemTime;
+
+use crate::{cvars::CVarRegistry, stats::StatsBackend};
+
+/// What the player is doing, shown by platforms that support rich presence.
+#[derive(Debug, Clone, Default)]
+pub struct Presence {
+    pub state: String,                  // e.g. "In a match"
+    pub details: String,                // e.g. "Level 3 - 1200 points"
+    pub large_image: Option<String>,    // Asset key uploaded to the platform
+    pub start_time: Option<SystemTime>, // Shown as elapsed time
+}
+
+/// A platform service (Discord, Steam, ...). Achievements arrive through `StatsBackend`.
+pub trait PlatformBackend: StatsBackend {
+    fn name(&self) -> &'static str;
+    fn set_presence(&mut self, presence: &Presence);
+    fn clear_presence(&mut self) {}
+
+    /// Called once per frame so the backend can pump its callbacks.
+    fn update(&mut self) {}
+}
+
+/// Creates the backend selected by the `plat_backend` cvar, if any.
+pub fn create_backend(cvars: &CVarRegistry) -> Option<Box<dyn PlatformBackend>> {
+    let backend = cvars.get("plat_backend").map(|value| value.to_string());
+
+    match backend.as_deref() {
+        None | Some("") | Some("none") => None,
+        #[cfg(feature = "discord")]
+        Some("discord") => {
+            let app_id = cvars
+                .get("plat_discord_app_id")
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            match discord::DiscordBackend::connect(&app_id) {
+                Ok(backend) => Some(Box::new(backend)),
+                Err(e) => {
+                    log::error!("{}", e);
+                    None
+                }
+            }
+        }
+        Some(other) => {
+            log::warn!(
+                "Platform backend '{}' is not available in this build",
+                other
+            );
+            None
+        }
+    }
+}
+
+#[cfg(feature = "discord")]
+mod discord {
+    use std::time::UNIX_EPOCH;
+
+    use discord_rich_presence::{
+        activity::{Activity, Assets, Timestamps},
+        DiscordIpc, DiscordIpcClient,
+    };
+
+    use super::{PlatformBackend, Presence};
+    use crate::stats::StatsBackend;
+
+    pub struct DiscordBackend {
+        client: DiscordIpcClient,
+    }
+
+    impl DiscordBackend {
+        pub fn connect(app_id: &str) -> Result<Self, String> {
+            if app_id.is_empty() {
+                return Err("plat_discord_app_id is not set".to_string());
+            }
+
+            let mut client = DiscordIpcClient::new(app_id);
+            client
+                .connect()
+                .map_err(|e| format!("Failed to connect to Discord: {}", e))?;
+            log::info!("Connected to Discord");
+
+            Ok(Self { client })
+        }
+    }
+
+    // Discord has no achievements
+    impl StatsBackend for DiscordBackend {}
+
+    impl PlatformBackend for DiscordBackend {
+        fn name(&self) -> &'static str {
+            "discord"
+        }
+
+        fn set_presence(&mut self, presence: &Presence) {
+            let mut activity = Activity::new()
+                .state(presence.state.as_str())
+                .details(presence.details.as_str());
+
+            if let Some(large_image) = &presence.large_image {
+                activity = activity.assets(Assets::new().large_image(large_image.as_str()));
+            }
+            if let Some(start) = presence
+                .start_time
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            {
+                activity = activity.timestamps(Timestamps::new().start(start.as_millis() as i64));
+            }
+
+            if let Err(e) = self.client.set_activity(activity) {
+                log::warn!("Failed to set Discord presence: {}", e);
+            }
+        }
+
+        fn clear_presence(&mut self) {
+            if let Err(e) = self.client.clear_activity() {
+                log::warn!("Failed to clear Discord presence: {}", e);
+            }
+        }
+    }
+
+    impl Drop for DiscordBackend {
+        fn drop(&mut self) {
+            let _ = self.client.close();
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use cruel_game_engine::{
+    accessibility::{MAX_UI_SCALE, MIN_UI_SCALE},
+    cvars::{CVarRegistry, CVarValue},
+    window::WindowPlacement,
+};
+
+use crate::launcher::config_directory;
+
+const PREFERENCES_FILE: &str = "preferences.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn visuals(self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+/// Editor settings that belong to the user rather than a project, stored in the user's config
+/// directory. The camera and UI scale ones are pushed into the cvars.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorPreferences {
+    pub camera_speed: f32,
+    pub camera_sensitivity: f32,
+    pub autosave_minutes: u32, // 0 turns autosave off
+    pub ui_scale: f32,
+    pub theme: Theme,
+    pub confirm_delete: bool,
+    pub spawn_dropped_meshes: bool, // Meshes dropped on the window from outside the editor
+    pub window: WindowPlacement,    // Saved when the editor closes
+}
+
+impl Default for EditorPreferences {
+    fn default() -> Self {
+        Self {
+            camera_speed: 2.4,
+            camera_sensitivity: 100.0,
+            autosave_minutes: 5,
+            ui_scale: 1.0,
+            theme: Theme::Dark,
+            confirm_delete: true,
+            spawn_dropped_meshes: true,
+            window: WindowPlacement::default(),
+        }
+    }
+}
+
+impl EditorPreferences {
+    /// The defaults when nothing was saved yet or the file can't be read.
+    pub fn load() -> Self {
+        let Some(path) = config_directory().map(|directory| directory.join(PREFERENCES_FILE))
+        else {
+            return Self::default();
+        };
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        ron::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Failed to parse {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let directory = config_directory().ok_or("There is no config directory")?;
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| format!("Failed to create {:?}: {}", directory, e))?;
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize the preferences: {}", e))?;
+        let path = directory.join(PREFERENCES_FILE);
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        (self.autosave_minutes > 0).then(|| Duration::from_secs(self.autosave_minutes as u64 * 60))
+    }
+
+    /// Sets the cvars these override. Has to be done again after a project's cvars are loaded.
+    pub fn apply(&self, cvars: &mut CVarRegistry) {
+        let changes = [
+            ("cam_speed", CVarValue::Float(self.camera_speed)),
+            ("cam_sensitivity", CVarValue::Float(self.camera_sensitivity)),
+            ("ui_scale", CVarValue::Float(self.ui_scale)),
+        ];
+        for (name, value) in changes {
+            if let Err(e) = cvars.set(name, value) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    /// Returns true when something was changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let before = self.clone();
+
+        egui::Grid::new("Preferences")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Camera speed");
+                ui.add(
+                    egui::DragValue::new(&mut self.camera_speed)
+                        .speed(0.1)
+                        .range(0.1..=100.0),
+                );
+                ui.end_row();
+
+                ui.label("Camera sensitivity");
+                ui.add(
+                    egui::DragValue::new(&mut self.camera_sensitivity)
+                        .speed(1.0)
+                        .range(1.0..=1000.0),
+                );
+                ui.end_row();
+
+                ui.label("Autosave every");
+                ui.add(
+                    egui::DragValue::new(&mut self.autosave_minutes)
+                        .range(0..=120)
+                        .suffix(" min"),
+                )
+                .on_hover_text("0 turns autosave off");
+                ui.end_row();
+
+                ui.label("UI scale");
+                ui.add(
+                    egui::Slider::new(&mut self.ui_scale, MIN_UI_SCALE..=MAX_UI_SCALE)
+                        .step_by(0.05),
+                );
+                ui.end_row();
+
+                ui.label("Theme");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.theme, Theme::Dark, "Dark");
+                    ui.radio_value(&mut self.theme, Theme::Light, "Light");
+                });
+                ui.end_row();
+
+                ui.label("Confirm on delete");
+                ui.checkbox(&mut self.confirm_delete, "");
+                ui.end_row();
+
+                ui.label("Spawn dropped meshes");
+                ui.checkbox(&mut self.spawn_dropped_meshes, "")
+                    .on_hover_text("Files dropped on the window are only loaded when off");
+                ui.end_row();
+            });
+
+        *self != before
+    }
+}
@@ -0,0 +1,166 @@
+// The quad viewport layout: the editor camera's view in the top left, top, front and right
+// views through their own orthographic cameras in the other three quarters
+
+use cruel_game_engine::{
+    camera::{self, AxisView, Camera, OrthographicCamera},
+    viewport::Viewport,
+};
+
+// How far the side cameras sit from what they look at
+const SIDE_VIEW_DISTANCE: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewportLayout {
+    Single,
+    Quad,
+}
+
+impl ViewportLayout {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ViewportLayout::Single => "⬜ Single",
+            ViewportLayout::Quad => "⊞ Quad",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            ViewportLayout::Single => ViewportLayout::Quad,
+            ViewportLayout::Quad => ViewportLayout::Single,
+        }
+    }
+}
+
+pub struct SideView {
+    pub axis: AxisView,
+    pub camera: OrthographicCamera,
+    pub half_height: f32, // World units from the middle to the top edge, scrolling zooms it
+    viewport: Option<Viewport>, // Pixels from the window's top left, like Gui.viewport
+}
+
+impl SideView {
+    fn new(axis: AxisView) -> Self {
+        let camera = OrthographicCamera::new(
+            format!("{} View Camera", axis.name()),
+            cgmath::point3(0.0, 0.0, 0.0),
+            1,
+            1,
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.1,
+            SIDE_VIEW_DISTANCE * 2.0,
+            0.4,
+            100.0,
+        );
+        Self {
+            axis,
+            camera,
+            half_height: 10.0,
+            viewport: None,
+        }
+    }
+
+    // Looks at `target` from its side, stretched to fit `rect`
+    fn fit(&mut self, rect: egui::Rect, target: cgmath::Point3<f32>) {
+        self.camera.left = 0.0;
+        self.camera.right = 0.0;
+        self.camera.bottom = -self.half_height;
+        self.camera.top = self.half_height;
+        self.camera
+            .fit_viewport(rect.width().max(1.0) as u32, rect.height().max(1.0) as u32);
+        self.camera.far_plane = SIDE_VIEW_DISTANCE * 2.0;
+        camera::look_from(&mut self.camera, self.axis, target, SIDE_VIEW_DISTANCE);
+    }
+}
+
+pub struct QuadView {
+    pub views: [SideView; 3],
+}
+
+impl QuadView {
+    pub fn new() -> Self {
+        Self {
+            views: [
+                SideView::new(AxisView::PositiveY),
+                SideView::new(AxisView::PositiveZ),
+                SideView::new(AxisView::PositiveX),
+            ],
+        }
+    }
+
+    /// Lays the side views out over `rect`, centered on `target`, and returns the top left
+    /// quarter that's left for the editor camera.
+    pub fn layout(
+        &mut self,
+        ui: &egui::Ui,
+        rect: egui::Rect,
+        target: cgmath::Point3<f32>,
+    ) -> egui::Rect {
+        let half = rect.size() / 2.0;
+        let corners = [
+            rect.min + egui::vec2(half.x, 0.0),
+            rect.min + egui::vec2(0.0, half.y),
+            rect.min + half,
+        ];
+        let pixels_per_point = ui.ctx().pixels_per_point();
+
+        for (view, corner) in self.views.iter_mut().zip(corners) {
+            let view_rect = egui::Rect::from_min_size(corner, half);
+            if ui.rect_contains_pointer(view_rect) {
+                let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+                view.half_height = (view.half_height * (1.0 - scroll * 0.002)).clamp(0.5, 1000.0);
+            }
+            view.fit(view_rect, target);
+            view.viewport = Some(Viewport::from_points(
+                view_rect.min.x,
+                view_rect.min.y,
+                view_rect.width(),
+                view_rect.height(),
+                pixels_per_point,
+            ));
+
+            let painter = ui.painter();
+            painter.rect_stroke(
+                view_rect,
+                0.0,
+                ui.visuals().widgets.noninteractive.bg_stroke,
+                egui::StrokeKind::Inside,
+            );
+            painter.text(
+                view_rect.left_top() + egui::vec2(8.0, 6.0),
+                egui::Align2::LEFT_TOP,
+                view.axis.name(),
+                egui::FontId::proportional(13.0),
+                ui.visuals().weak_text_color(),
+            );
+        }
+
+        egui::Rect::from_min_size(rect.min, half)
+    }
+
+    /// The side views' cameras and where they go in the window, in OpenGL's bottom up pixels.
+    pub fn views_mut(&mut self, window_height: u32) -> Vec<(&mut dyn Camera, Viewport)> {
+        self.views
+            .iter_mut()
+            .filter_map(|view| {
+                let viewport = view.viewport.as_ref()?;
+                let viewport = Viewport::new(
+                    viewport.x,
+                    window_height as i32 - viewport.y - viewport.height,
+                    viewport.width,
+                    viewport.height,
+                );
+                Some((&mut view.camera as &mut dyn Camera, viewport))
+            })
+            .collect()
+    }
+
+    /// Stops the side views from being drawn until they're laid out again.
+    pub fn hide(&mut self) {
+        for view in &mut self.views {
+            view.viewport = None;
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use cgmath::{EuclideanSpace, InnerSpace, Point3, SquareMatrix, Transform, Vector3};
+
+use crate::{camera::Camera, data::LoadedPrimitive, loader::AssetLoader, scene_graph::SceneNode};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>, // Always normalized
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// The ray through a point of the screen, `ndc` runs from -1 to 1 with +y up.
+    pub fn from_screen(camera: &dyn Camera, ndc: [f32; 2]) -> Option<Self> {
+        let inverse = (camera.get_projection() * camera.get_view()).invert()?;
+        let near = inverse.transform_point(Point3::new(ndc[0], ndc[1], -1.0));
+        let far = inverse.transform_point(Point3::new(ndc[0], ndc[1], 1.0));
+        Some(Self::new(near, far - near))
+    }
+}
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_points<I: IntoIterator<Item = [f32; 3]>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = Point3::from(points.next()?);
+        let mut aabb = Aabb {
+            min: first,
+            max: first,
+        };
+
+        for point in points {
+            aabb.grow(Point3::from(point));
+        }
+
+        Some(aabb)
+    }
+
+    pub fn grow(&mut self, point: Point3<f32>) {
+        self.min = Point3::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        aabb.grow(other.min);
+        aabb.grow(other.max);
+        aabb
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// True if `other` is completely inside this box.
+    pub fn contains(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// The box enclosing this box after it has been transformed by `matrix`.
+    pub fn transformed(&self, matrix: &cgmath::Matrix4<f32>) -> Aabb {
+        let corners = self.corners();
+        let mut aabb = Aabb {
+            min: matrix.transform_point(corners[0]),
+            max: matrix.transform_point(corners[0]),
+        };
+        for corner in &corners[1..] {
+            aabb.grow(matrix.transform_point(*corner));
+        }
+        aabb
+    }
+
+    /// Distance along the ray to the first intersection, using the slab method.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+
+        for axis in 0..3 {
+            let origin = ray.origin[axis];
+            let direction = ray.direction[axis];
+
+            if direction.abs() < f32::EPSILON {
+                if origin < self.min[axis] || origin > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inverse = 1.0 / direction;
+            let mut t0 = (self.min[axis] - origin) * inverse;
+            let mut t1 = (self.max[axis] - origin) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub static_mesh: usize, // Index into SceneNode.static_meshes
+    pub distance: f32,
+    pub point: Point3<f32>,
+}
+
+/// Finds the closest static mesh hit by `ray` within `max_distance`, testing world space bounds.
+/// Meshes are looked up in the scene's spatial index, which is as fresh as the last
+/// `SceneNode::update`.
+pub fn raycast(scene: &SceneNode, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+    raycast_filtered(scene, ray, max_distance, |_| true)
+}
+
+/// Like `raycast`, but only static meshes for which `filter` returns true are tested.
+pub fn raycast_filtered<F: Fn(usize) -> bool>(
+    scene: &SceneNode,
+    ray: &Ray,
+    max_distance: f32,
+    filter: F,
+) -> Option<RayHit> {
+    let mut closest: Option<RayHit> = None;
+
+    // A scene that hasn't been updated yet has no index to go by
+    let candidates: Vec<usize> = if scene.spatial_index().len() == scene.static_meshes.len() {
+        scene.spatial_index().query_ray(ray, max_distance)
+    } else {
+        (0..scene.static_meshes.len()).collect()
+    };
+
+    for i in candidates {
+        if !filter(i) {
+            continue;
+        }
+
+        let Some(world_bounds) = scene.static_mesh_world_bounds(i) else {
+            continue;
+        };
+
+        if let Some(distance) = world_bounds.intersect_ray(ray) {
+            let is_closer = closest.is_none_or(|hit| distance < hit.distance);
+            if distance <= max_distance && is_closer {
+                closest = Some(RayHit {
+                    static_mesh: i,
+                    distance,
+                    point: ray.at(distance),
+                });
+            }
+        }
+    }
+
+    closest
+}
+
+/// Like `raycast_filtered`, but against the triangles of the meshes instead of their bounds,
+/// so the hit point is on the surface. Slower, meant for editor tools.
+pub fn raycast_triangles<F: Fn(usize) -> bool>(
+    scene: &SceneNode,
+    asset_loader: &AssetLoader,
+    ray: &Ray,
+    max_distance: f32,
+    filter: F,
+) -> Option<RayHit> {
+    let mut closest: Option<RayHit> = None;
+    for i in 0..scene.static_meshes.len() {
+        let in_range = scene
+            .static_mesh_world_bounds(i)
+            .and_then(|bounds| bounds.intersect_ray(ray))
+            .is_some_and(|distance| distance <= max_distance);
+        if !in_range || !filter(i) {
+            continue;
+        }
+
+        let matrix = scene.static_mesh_world_matrix(i);
+        for primitive in loaded_primitives(scene, asset_loader, i) {
+            if primitive.mode != glow::TRIANGLES {
+                continue; // Strips, lines and points aren't surfaces to snap to
+            }
+            let positions = &primitive.vertex_data.positions;
+            let corner = |index: usize| matrix.transform_point(Point3::from(positions[index]));
+            let corners: Vec<usize> = match &primitive.indices {
+                Some(indices) => indices.iter().map(|&index| index as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+
+            for triangle in corners.chunks_exact(3) {
+                let [a, b, c] = [
+                    corner(triangle[0]),
+                    corner(triangle[1]),
+                    corner(triangle[2]),
+                ];
+                let Some(distance) = intersect_triangle(ray, a, b, c) else {
+                    continue;
+                };
+                let is_closer = closest.is_none_or(|hit| distance < hit.distance);
+                if distance <= max_distance && is_closer {
+                    closest = Some(RayHit {
+                        static_mesh: i,
+                        distance,
+                        point: ray.at(distance),
+                    });
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+/// The world space vertex of a static mesh closest to `point`.
+pub fn nearest_vertex(
+    scene: &SceneNode,
+    asset_loader: &AssetLoader,
+    static_mesh: usize,
+    point: Point3<f32>,
+) -> Option<Point3<f32>> {
+    let matrix = scene.static_mesh_world_matrix(static_mesh);
+    loaded_primitives(scene, asset_loader, static_mesh)
+        .into_iter()
+        .flat_map(|primitive| primitive.vertex_data.positions.iter())
+        .map(|&position| matrix.transform_point(Point3::from(position)))
+        .min_by(|a, b| {
+            let a = (a - point).magnitude2();
+            let b = (b - point).magnitude2();
+            a.total_cmp(&b)
+        })
+}
+
+// The full detail primitives a static mesh was built from
+fn loaded_primitives<'a>(
+    scene: &SceneNode,
+    asset_loader: &'a AssetLoader,
+    static_mesh: usize,
+) -> Vec<&'a LoadedPrimitive> {
+    let Some(mesh) = scene.static_meshes.get(static_mesh) else {
+        return Vec::new();
+    };
+    let Some(loaded) = asset_loader.loaded_mesh_data.get(&mesh.handle) else {
+        return Vec::new();
+    };
+    mesh.primitives
+        .iter()
+        .filter_map(|instance| loaded.primitives.get(instance.primitive_index))
+        .collect()
+}
+
+// Möller–Trumbore, both sides of the triangle count
+fn intersect_triangle(ray: &Ray, a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Option<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = ray.direction.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None; // Parallel to the triangle
+    }
+
+    let to_origin = ray.origin - a;
+    let u = to_origin.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(ab);
+    let v = ray.direction.dot(q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = ac.dot(q) / determinant;
+    (distance >= 0.0).then_some(distance)
+}
@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use glow::HasContext;
+
+use crate::{
+    error::{EngineError, EngineResult},
+    gpu_timer::GpuTimers,
+    viewport::Viewport,
+};
+
+/// The editor viewport of the window's framebuffer, what every graph ends up drawing to.
+pub const BACKBUFFER: &str = "backbuffer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFormat {
+    Rgba8,
+    Rgba16F,
+    Depth24,
+}
+
+impl TargetFormat {
+    fn is_depth(&self) -> bool {
+        matches!(self, TargetFormat::Depth24)
+    }
+
+    // (internal format, format, type)
+    fn gl_formats(&self) -> (u32, u32, u32) {
+        match self {
+            TargetFormat::Rgba8 => (glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE),
+            TargetFormat::Rgba16F => (glow::RGBA16F, glow::RGBA, glow::HALF_FLOAT),
+            TargetFormat::Depth24 => (
+                glow::DEPTH_COMPONENT24,
+                glow::DEPTH_COMPONENT,
+                glow::UNSIGNED_INT,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetSize {
+    Viewport,        // Follows the editor viewport
+    Scaled(f32),     // A fraction of the viewport, for half resolution effects
+    Fixed(i32, i32), // Shadow maps and the like
+}
+
+/// A texture the graph allocates for passes to render into and read from.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetDesc {
+    pub format: TargetFormat,
+    pub size: TargetSize,
+}
+
+impl TargetDesc {
+    pub fn new(format: TargetFormat, size: TargetSize) -> Self {
+        Self { format, size }
+    }
+
+    fn resolve(&self, viewport: &Viewport) -> (i32, i32) {
+        let (width, height) = match self.size {
+            TargetSize::Viewport => (viewport.width, viewport.height),
+            TargetSize::Scaled(scale) => (
+                (viewport.width as f32 * scale) as i32,
+                (viewport.height as f32 * scale) as i32,
+            ),
+            TargetSize::Fixed(width, height) => (width, height),
+        };
+        (width.max(1), height.max(1))
+    }
+}
+
+struct Target {
+    desc: TargetDesc,
+    texture: Option<glow::Texture>,
+    size: (i32, i32),
+}
+
+/// What a pass gets while it runs. The pass's outputs are already bound.
+pub struct PassContext<'a> {
+    pub gl: &'a glow::Context,
+    pub viewport: Viewport, // Where to draw, the editor viewport or the whole output target
+    targets: &'a HashMap<String, Target>,
+}
+
+impl PassContext<'_> {
+    /// One of the graph's targets, to sample from an input.
+    pub fn texture(&self, name: &str) -> EngineResult<glow::Texture> {
+        self.targets
+            .get(name)
+            .and_then(|target| target.texture)
+            .ok_or_else(|| EngineError::Gl(format!("Render target '{}' is not allocated", name)))
+    }
+}
+
+/// A step of the frame. `F` is whatever the passes need from the outside each frame.
+pub trait RenderPass<F> {
+    fn execute(&mut self, ctx: &PassContext, frame: &mut F) -> EngineResult<()>;
+}
+
+impl<F, T> RenderPass<F> for T
+where
+    T: FnMut(&PassContext, &mut F) -> EngineResult<()>,
+{
+    fn execute(&mut self, ctx: &PassContext, frame: &mut F) -> EngineResult<()> {
+        self(ctx, frame)
+    }
+}
+
+struct PassNode<F> {
+    name: &'static str,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    pass: Box<dyn RenderPass<F>>,
+    framebuffer: Option<glow::Framebuffer>,
+}
+
+/// Passes declare what they read and write, the graph works out the order, allocates the
+/// targets and binds each pass's outputs before running it.
+///
+/// Passes writing the same resource run in the order they were added, so post effects that
+/// read and write the backbuffer stack up in place. Passes that don't lead to the backbuffer
+/// are skipped.
+pub struct RenderGraph<F> {
+    passes: Vec<PassNode<F>>,
+    targets: HashMap<String, Target>,
+    order: Option<Vec<usize>>, // None until compiled, reset when passes change
+    timers: GpuTimers,
+}
+
+impl<F> Default for RenderGraph<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> RenderGraph<F> {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            targets: HashMap::new(),
+            order: None,
+            timers: GpuTimers::new(),
+        }
+    }
+
+    pub fn add_target(&mut self, name: &str, desc: TargetDesc) {
+        self.targets.insert(
+            name.to_string(),
+            Target {
+                desc,
+                texture: None,
+                size: (0, 0),
+            },
+        );
+        self.order = None;
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        inputs: &[&str],
+        outputs: &[&str],
+        pass: impl RenderPass<F> + 'static,
+    ) {
+        self.passes.push(PassNode {
+            name,
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            outputs: outputs.iter().map(|output| output.to_string()).collect(),
+            pass: Box::new(pass),
+            framebuffer: None,
+        });
+        self.order = None;
+    }
+
+    /// Checks the declarations and sorts the passes, called by `execute` when something changed.
+    pub fn compile(&mut self) -> EngineResult<()> {
+        for pass in &self.passes {
+            for resource in pass.inputs.iter().chain(&pass.outputs) {
+                if resource != BACKBUFFER && !self.targets.contains_key(resource) {
+                    return Err(EngineError::Gl(format!(
+                        "Pass '{}' uses '{}' which is not a render target",
+                        pass.name, resource
+                    )));
+                }
+            }
+            if pass.outputs.len() > 1 && pass.outputs.iter().any(|output| output == BACKBUFFER) {
+                return Err(EngineError::Gl(format!(
+                    "Pass '{}' can't write the backbuffer and a render target at once",
+                    pass.name
+                )));
+            }
+        }
+
+        // Edges go from writer to reader
+        let count = self.passes.len();
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for (reader, pass) in self.passes.iter().enumerate() {
+            for input in &pass.inputs {
+                let writes_it_too = pass.outputs.contains(input);
+                // Read-modify-write passes only wait for the writers added before them
+                let writers: Vec<usize> = (0..count)
+                    .filter(|&writer| {
+                        writer != reader && self.passes[writer].outputs.contains(input)
+                    })
+                    .filter(|&writer| !writes_it_too || writer < reader)
+                    .collect();
+                if writers.is_empty() && !writes_it_too && input != BACKBUFFER {
+                    return Err(EngineError::Gl(format!(
+                        "Pass '{}' reads '{}' but no pass writes it",
+                        pass.name, input
+                    )));
+                }
+                dependencies[reader].extend(writers);
+            }
+            // Passes writing the same resource keep the order they were added in
+            for output in &pass.outputs {
+                if let Some(writer) = (0..reader)
+                    .rev()
+                    .find(|&writer| self.passes[writer].outputs.contains(output))
+                {
+                    dependencies[reader].push(writer);
+                }
+            }
+            dependencies[reader].sort_unstable();
+            dependencies[reader].dedup();
+        }
+
+        // Only keep what ends up on screen
+        let mut needed = vec![false; count];
+        let mut stack: Vec<usize> = (0..count)
+            .filter(|&index| {
+                self.passes[index]
+                    .outputs
+                    .iter()
+                    .any(|output| output == BACKBUFFER)
+            })
+            .collect();
+        while let Some(index) = stack.pop() {
+            if std::mem::replace(&mut needed[index], true) {
+                continue;
+            }
+            stack.extend(&dependencies[index]);
+        }
+        for (index, pass) in self.passes.iter().enumerate() {
+            if !needed[index] {
+                log::warn!(
+                    "Render pass '{}' doesn't reach the backbuffer, skipping it",
+                    pass.name
+                );
+            }
+        }
+
+        // Kahn's algorithm, ties go to the pass that was added first
+        let mut remaining: Vec<usize> = dependencies.iter().map(|writers| writers.len()).collect();
+        let mut done = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&index| !done[index] && remaining[index] == 0) else {
+                let stuck: Vec<&str> = (0..count)
+                    .filter(|&index| !done[index])
+                    .map(|index| self.passes[index].name)
+                    .collect();
+                return Err(EngineError::Gl(format!(
+                    "Render passes depend on each other: {}",
+                    stuck.join(", ")
+                )));
+            };
+            done[next] = true;
+            order.push(next);
+            for (index, writers) in dependencies.iter().enumerate() {
+                if !done[index] && writers.contains(&next) {
+                    remaining[index] -= 1;
+                }
+            }
+        }
+
+        order.retain(|&index| needed[index]);
+        self.order = Some(order);
+        Ok(())
+    }
+
+    /// The pass names in the order they run.
+    pub fn schedule(&self) -> Vec<&'static str> {
+        self.order
+            .iter()
+            .flatten()
+            .map(|&index| self.passes[index].name)
+            .collect()
+    }
+
+    // Reallocates targets whose size changed, framebuffers using them are rebuilt too
+    fn allocate_targets(&mut self, gl: &glow::Context, viewport: &Viewport) -> EngineResult<()> {
+        let mut resized = Vec::new();
+        for (name, target) in &mut self.targets {
+            let size = target.desc.resolve(viewport);
+            if target.texture.is_some() && target.size == size {
+                continue;
+            }
+
+            let texture = match target.texture {
+                Some(texture) => texture,
+                None => unsafe { gl.create_texture() }.map_err(EngineError::Gl)?,
+            };
+            let (internal_format, format, data_type) = target.desc.format.gl_formats();
+            unsafe {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    internal_format as i32,
+                    size.0,
+                    size.1,
+                    0,
+                    format,
+                    data_type,
+                    glow::PixelUnpackData::Slice(None),
+                );
+                for (parameter, value) in [
+                    (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+                    (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+                    (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+                    (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+                ] {
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
+                }
+                gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+            crate::gl_debug::check_errors(gl, "allocating a render target");
+
+            target.texture = Some(texture);
+            target.size = size;
+            resized.push(name.clone());
+        }
+
+        for pass in &mut self.passes {
+            if pass.outputs.iter().any(|output| resized.contains(output)) {
+                if let Some(framebuffer) = pass.framebuffer.take() {
+                    unsafe { gl.delete_framebuffer(framebuffer) };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn framebuffer(
+        gl: &glow::Context,
+        pass: &PassNode<F>,
+        targets: &HashMap<String, Target>,
+    ) -> EngineResult<glow::Framebuffer> {
+        let framebuffer = unsafe { gl.create_framebuffer() }.map_err(EngineError::Gl)?;
+        let mut color_attachments = Vec::new();
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            for output in &pass.outputs {
+                let target = &targets[output];
+                let attachment = if target.desc.format.is_depth() {
+                    glow::DEPTH_ATTACHMENT
+                } else {
+                    color_attachments
+                        .push(glow::COLOR_ATTACHMENT0 + color_attachments.len() as u32);
+                    *color_attachments.last().unwrap()
+                };
+                gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_2D,
+                    target.texture,
+                    0,
+                );
+            }
+            if color_attachments.is_empty() {
+                gl.draw_buffer(glow::NONE); // Depth only, like a shadow map
+            } else {
+                gl.draw_buffers(&color_attachments);
+            }
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                gl.delete_framebuffer(framebuffer);
+                return Err(EngineError::Gl(format!(
+                    "Framebuffer for pass '{}' is incomplete ({:#x})",
+                    pass.name, status
+                )));
+            }
+        }
+        Ok(framebuffer)
+    }
+
+    /// Runs the passes. Every pass gets to run even if an earlier one failed,
+    /// the first error is returned.
+    pub fn execute(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        frame: &mut F,
+    ) -> EngineResult<()> {
+        if self.order.is_none() {
+            self.compile()?;
+        }
+        self.allocate_targets(gl, viewport)?;
+
+        let mut first_error = None;
+        for &index in self.order.as_ref().unwrap() {
+            let pass = &mut self.passes[index];
+            let writes_backbuffer = pass.outputs.iter().any(|output| output == BACKBUFFER);
+
+            let pass_viewport = if writes_backbuffer || pass.outputs.is_empty() {
+                unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, None) };
+                Viewport::new(viewport.x, viewport.y, viewport.width, viewport.height)
+            } else {
+                if pass.framebuffer.is_none() {
+                    match Self::framebuffer(gl, pass, &self.targets) {
+                        Ok(framebuffer) => pass.framebuffer = Some(framebuffer),
+                        Err(e) => {
+                            first_error.get_or_insert(e);
+                            continue;
+                        }
+                    }
+                }
+                // All outputs of a pass share a size, the first one decides
+                let (width, height) = self.targets[&pass.outputs[0]].size;
+                unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, pass.framebuffer) };
+                Viewport::new(0, 0, width, height)
+            };
+            unsafe {
+                gl.viewport(
+                    pass_viewport.x,
+                    pass_viewport.y,
+                    pass_viewport.width,
+                    pass_viewport.height,
+                );
+            }
+
+            let ctx = PassContext {
+                gl,
+                viewport: pass_viewport,
+                targets: &self.targets,
+            };
+            let _span = tracing::info_span!("render_pass", name = pass.name).entered();
+            self.timers.begin(gl, pass.name);
+            let result = pass.pass.execute(&ctx, frame);
+            self.timers.end(gl);
+
+            if let Err(e) = result {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        unsafe { gl.bind_framebuffer(glow::FRAMEBUFFER, None) };
+        self.timers.end_frame(gl);
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// GPU milliseconds per pass, a few frames old.
+    pub fn timings(&self) -> Vec<(&'static str, f32)> {
+        self.timers.timings()
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        for pass in &mut self.passes {
+            if let Some(framebuffer) = pass.framebuffer.take() {
+                unsafe { gl.delete_framebuffer(framebuffer) };
+            }
+        }
+        for target in self.targets.values_mut() {
+            if let Some(texture) = target.texture.take() {
+                unsafe { gl.delete_texture(texture) };
+            }
+        }
+        self.timers.destroy(gl);
+    }
+}
@@ -0,0 +1,58 @@
+// What a scene draws in a frame. Walking the scene fills a list without touching GL, the list
+// is then sorted and run on the context, so both halves can change (or move threads) apart.
+
+use cgmath::Matrix4;
+
+use crate::handles::{MeshHandle, TextureHandle};
+
+/// One static mesh to draw.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawCommand {
+    pub mesh: usize, // Into the scene's static meshes
+    pub handle: MeshHandle,
+    pub material: Option<TextureHandle>, // Its base color map, the scene's texture when None
+    pub lod: usize,
+    pub model: Matrix4<f32>,
+    pub distance: f32, // From the camera to the middle of the mesh
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub drawn: usize,
+    pub culled: usize,
+    pub batches: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderList {
+    pub commands: Vec<DrawCommand>,
+    pub batches: Vec<usize>, // Into the scene's static batches, drawn after the commands
+    pub culled: usize,       // Meshes left out because they're outside the view
+}
+
+impl RenderList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    /// Groups the commands by material and then by mesh so textures and buffers are bound as
+    /// few times as possible. Within a group the nearest go first, so depth testing rejects
+    /// more of what's behind them.
+    pub fn sort(&mut self) {
+        let key = |command: &DrawCommand| (command.material.map(|m| m.0), command.handle.0);
+        self.commands
+            .sort_by(|a, b| key(a).cmp(&key(b)).then(a.distance.total_cmp(&b.distance)));
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            drawn: self.commands.len(),
+            culled: self.culled,
+            batches: self.batches.len(),
+        }
+    }
+}
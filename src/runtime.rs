@@ -0,0 +1,802 @@
+use std::{
+    ffi::CString,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use cgmath::{InnerSpace, Vector3};
+use glow::HasContext;
+use glutin::{
+    config::ConfigTemplate,
+    context::{ContextAttributesBuilder, PossiblyCurrentContext},
+    display::{Display, DisplayApiPreference},
+    prelude::*,
+    surface::{Surface, SurfaceAttributesBuilder, WindowSurface},
+};
+use serde::{Deserialize, Serialize};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey},
+    raw_window_handle::{HasDisplayHandle, HasWindowHandle},
+    window::{Fullscreen, Window, WindowId},
+};
+
+use crate::{
+    camera::{Camera, OrthographicCamera, PerspectiveCamera},
+    cvars::{CVarRegistry, CVARS_CONFIG_PATH},
+    environment::EnvironmentMap,
+    game_ui::GameUiRenderer,
+    gl_debug,
+    handles::{AssetHandle, MeshHandle},
+    jobs,
+    loader::{Asset, AssetLoader},
+    logging,
+    mesh::StaticMesh,
+    pack::{AssetPackWriter, DEFAULT_COMPRESSION_LEVEL},
+    render_graph::{PassContext, RenderGraph, BACKBUFFER},
+    scene_graph::SceneNode,
+    sprites::SpriteRenderer,
+    text::TextRenderer,
+    tilemap::{Tilemap, TilemapRenderer, TILEMAP_DIRECTORY},
+    transform::MeshTransform,
+    viewport::Viewport,
+    window::{FramePacer, VSync},
+};
+
+pub const GAME_MANIFEST: &str = "game.ron";
+pub const GAME_PACK: &str = "game.cpak";
+pub const EXPORT_DIRECTORY: &str = "export";
+
+/// A static mesh of the exported scene, by the file it was loaded from and which of the file's
+/// primitives it draws. The transform is in world space, attachments aren't kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedMesh {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub primitives: Vec<usize>, // Empty for the whole file
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3], // Degrees, like MeshTransform
+    pub scale: [f32; 3],
+}
+
+/// What the runtime loads, written next to it by `export_game`. Paths are relative to the
+/// manifest, assets are read from the packs before loose files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameManifest {
+    pub title: String,
+    pub packs: Vec<PathBuf>,
+    pub meshes: Vec<PlacedMesh>,
+    pub tilemaps: Vec<PathBuf>,
+    pub environment: Option<PathBuf>,
+    pub camera_position: [f32; 3],
+    pub camera_target: [f32; 3],
+}
+
+impl GameManifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read game manifest {:?}: {}", path, e))?;
+        ron::from_str(&contents)
+            .map_err(|e| format!("Failed to parse game manifest {:?}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize game manifest: {}", e))?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write game manifest {:?}: {}", path, e))
+    }
+}
+
+// Relative to the working directory when it's below it, packs are looked up by relative path
+fn relative_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .and_then(std::fs::canonicalize)
+        .ok()
+        .and_then(|directory| path.strip_prefix(directory).ok())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+// A mesh file and the buffers and images it refers to
+fn mesh_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let gltf = gltf::Gltf::open(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let buffers = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => Some(uri),
+        gltf::buffer::Source::Bin => None,
+    });
+    let images = gltf.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+
+    let mut files = vec![path.to_path_buf()];
+    for uri in buffers.chain(images) {
+        if !uri.starts_with("data:") {
+            files.push(directory.join(uri));
+        }
+    }
+    Ok(files)
+}
+
+/// Gathers the current scene into `output`: a manifest, one pack with the meshes and textures,
+/// the tilemaps and environment HDR as loose files, the shaders and the runtime binary.
+/// Sprites aren't exported yet.
+pub fn export_game(
+    title: &str,
+    scene: &SceneNode,
+    camera: &dyn Camera,
+    environment: Option<&Path>,
+    asset_loader: &AssetLoader,
+    output: &Path,
+) -> Result<(), String> {
+    let create_directory = |directory: &Path| {
+        std::fs::create_dir_all(directory)
+            .map_err(|e| format!("Failed to create {:?}: {}", directory, e))
+    };
+    create_directory(output)?;
+
+    let position = camera.get_position();
+    let target = position + camera.get_orientation();
+    let mut manifest = GameManifest {
+        title: title.to_string(),
+        packs: vec![PathBuf::from(GAME_PACK)],
+        camera_position: position.into(),
+        camera_target: target.into(),
+        ..Default::default()
+    };
+    let mut writer = AssetPackWriter::new();
+
+    // Every placement is written, the files of a mesh only once
+    let mut exported = Vec::new();
+    for (index, mesh) in scene.static_meshes.iter().enumerate() {
+        let Some(path) = asset_loader.mesh_path(mesh.handle) else {
+            log::warn!("{} has no file to export", mesh.name);
+            continue;
+        };
+        let path = relative_path(path);
+        if !exported.contains(&mesh.handle) {
+            exported.push(mesh.handle);
+            for file in mesh_files(&path)? {
+                writer.add(&file)?;
+            }
+        }
+        let transform = MeshTransform::from_matrix(&scene.static_mesh_world_matrix(index));
+        manifest.meshes.push(PlacedMesh {
+            path,
+            primitives: mesh
+                .primitives
+                .iter()
+                .map(|primitive| primitive.primitive_index)
+                .collect(),
+            translation: transform.translation.into(),
+            rotation: transform.rotation.into(),
+            scale: transform.scale.into(),
+        });
+    }
+
+    for tilemap in &scene.tilemaps {
+        writer.add(&tilemap.atlas)?;
+        let path = Path::new(TILEMAP_DIRECTORY).join(format!("{}.ron", tilemap.name));
+        tilemap.save(&output.join(&path))?;
+        manifest.tilemaps.push(path);
+    }
+
+    if !scene.sprites.is_empty() {
+        log::warn!("Sprites aren't exported yet, the game will be without them");
+    }
+
+    // The environment is baked from the HDR file when the game starts
+    if let Some(path) = environment {
+        let path = relative_path(path);
+        if let Some(parent) = output.join(&path).parent() {
+            create_directory(parent)?;
+        }
+        std::fs::copy(&path, output.join(&path))
+            .map_err(|e| format!("Failed to copy {:?}: {}", path, e))?;
+        manifest.environment = Some(path);
+    }
+
+    let level = DEFAULT_COMPRESSION_LEVEL.parse().unwrap();
+    writer.write(&output.join(GAME_PACK), level)?;
+    manifest.save(&output.join(GAME_MANIFEST))?;
+
+    // Shaders are read from loose files
+    copy_directory(Path::new("shaders"), &output.join("shaders"))?;
+
+    let runtime = std::env::current_exe()
+        .map_err(|e| format!("Failed to find the editor's executable: {}", e))?
+        .with_file_name(format!("runtime{}", std::env::consts::EXE_SUFFIX));
+    if !runtime.exists() {
+        return Err(format!(
+            "{:?} is missing, build it with `cargo build --bin runtime`",
+            runtime
+        ));
+    }
+    let runtime_output = output.join(runtime.file_name().unwrap());
+    std::fs::copy(&runtime, &runtime_output)
+        .map_err(|e| format!("Failed to copy {:?}: {}", runtime, e))?;
+
+    log::info!(
+        "Exported {} with {} assets to {:?}",
+        title,
+        writer.len(),
+        output
+    );
+    Ok(())
+}
+
+/// Copies everything below `from` into `to`, overwriting what's there.
+pub fn copy_directory(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {:?}: {}", to, e))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read {:?}: {}", from, e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read {:?}: {}", from, e))?
+            .path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_directory(&path, &target)?;
+        } else {
+            std::fs::copy(&path, &target)
+                .map_err(|e| format!("Failed to copy {:?}: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// The game without the editor: a fullscreen window playing the exported scene. Escape quits.
+struct Runtime {
+    manifest: GameManifest,
+    cvars: CVarRegistry,
+    asset_loader: AssetLoader,
+
+    window: Option<Window>,
+    surface: Option<Surface<WindowSurface>>,
+    current_context: Option<PossiblyCurrentContext>,
+    gl: Option<Arc<glow::Context>>,
+
+    scene: Option<SceneNode>,
+    pending_meshes: Vec<(MeshHandle, PlacedMesh)>, // Added once everything they need is loaded
+    batches_pending: bool, // Static batches are built once the meshes are on the GPU
+    camera: Option<PerspectiveCamera>,
+    ortho_camera: Option<OrthographicCamera>, // For the tilemaps and sprites, like the editor
+
+    tilemap_renderer: Option<TilemapRenderer>,
+    sprite_renderer: Option<SpriteRenderer>,
+    text_renderer: Option<TextRenderer>,
+    game_ui_renderer: Option<GameUiRenderer>,
+    render_graph: Option<RenderGraph<Runtime>>,
+
+    last_frame: Instant,
+    frame_pacer: FramePacer,
+    last_render_error: Option<String>,
+}
+
+impl Runtime {
+    fn new(manifest: GameManifest) -> Self {
+        let mut cvars = CVarRegistry::with_engine_defaults();
+        let config_path = Path::new(CVARS_CONFIG_PATH);
+        if config_path.exists() {
+            if let Err(e) = cvars.load(config_path) {
+                log::error!("{}", e);
+            }
+        }
+
+        jobs::init(&cvars);
+        let workers = cvars.get_int("asset_loader_threads").max(0) as usize;
+        let mut asset_loader = AssetLoader::new(workers);
+        for pack in &manifest.packs {
+            if let Err(e) = asset_loader.mount_pack(pack) {
+                log::error!("{}", e);
+            }
+        }
+
+        let mut pending_meshes = Vec::new();
+        for mesh in &manifest.meshes {
+            let name = mesh.path.file_name().unwrap_or_default().to_string_lossy();
+            let handle = asset_loader.request_mesh(&mesh.path, name.to_string());
+            pending_meshes.push((handle, mesh.clone()));
+        }
+
+        Self {
+            manifest,
+            cvars,
+            asset_loader,
+            window: None,
+            surface: None,
+            current_context: None,
+            gl: None,
+            scene: None,
+            pending_meshes,
+            batches_pending: false,
+            camera: None,
+            ortho_camera: None,
+            tilemap_renderer: None,
+            sprite_renderer: None,
+            text_renderer: None,
+            game_ui_renderer: None,
+            render_graph: None,
+            last_frame: Instant::now(),
+            frame_pacer: FramePacer::new(),
+            last_render_error: None,
+        }
+    }
+
+    // Same as the editor, loaded assets are kept and normal and occlusion maps requested
+    fn poll_assets(&mut self) {
+        for (handle, asset) in self.asset_loader.poll_loaded() {
+            match asset {
+                Asset::Mesh(loaded_mesh) => {
+                    let materials = loaded_mesh
+                        .primitives
+                        .iter()
+                        .chain(loaded_mesh.lods.iter().flatten())
+                        .filter_map(|primitive| primitive.material.as_ref());
+                    let paths: Vec<PathBuf> = materials
+                        .flat_map(|material| {
+                            [&material.normal_texture, &material.occlusion_texture]
+                                .into_iter()
+                                .flatten()
+                                .cloned()
+                        })
+                        .collect();
+                    for path in paths {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        let texture = self.asset_loader.request_texture(&path, name.to_string());
+                        self.asset_loader
+                            .add_dependency(handle, AssetHandle::Texture(texture));
+                    }
+                    self.asset_loader
+                        .loaded_mesh_data
+                        .insert(handle.as_mesh_handle().unwrap(), loaded_mesh);
+                }
+                Asset::Texture(loaded_texture) => {
+                    self.asset_loader
+                        .loaded_texture_data
+                        .insert(handle.as_texture_handle().unwrap(), loaded_texture);
+                }
+                _ => log::warn!("No handler for loaded asset {:?}", handle),
+            }
+        }
+
+        // Meshes wait for their textures, nothing is left in flight once those are in
+        let (Some(gl), Some(scene)) = (self.gl.as_ref(), self.scene.as_mut()) else {
+            return;
+        };
+        if !self.pending_meshes.is_empty() && self.asset_loader.in_flight().next().is_none() {
+            for (handle, placed) in std::mem::take(&mut self.pending_meshes) {
+                let name = placed
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let first = scene.static_meshes.len();
+                let added = match placed.primitives.is_empty() {
+                    true => scene.add_mesh_asset(gl, name.to_string(), handle, &self.asset_loader),
+                    false => StaticMesh::from_primitives(
+                        gl,
+                        name.to_string(),
+                        handle,
+                        &placed.primitives,
+                        &self.asset_loader,
+                    )
+                    .map(|mesh| {
+                        scene.add_static_mesh(mesh);
+                        1
+                    }),
+                };
+                match added {
+                    Ok(count) => {
+                        for _ in 0..count {
+                            self.asset_loader.retain(AssetHandle::Mesh(handle));
+                        }
+                        let transform = MeshTransform {
+                            translation: placed.translation.into(),
+                            rotation: placed.rotation.into(),
+                            scale: placed.scale.into(),
+                        };
+                        transform.apply_to(&mut scene.static_meshes[first]);
+                    }
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            self.batches_pending = self.cvars.get_bool("r_static_batching");
+        }
+
+        let budget_kb = self.cvars.get_int("r_upload_budget_kb").max(1) as usize;
+        let uploads = self.asset_loader.uploads.get_mut().unwrap();
+        uploads.process(gl, budget_kb * 1024);
+
+        // Only uploaded meshes are batched, so it waits for the last of them
+        if self.batches_pending && uploads.is_empty() {
+            self.batches_pending = false;
+            scene.build_static_batches(gl, &self.asset_loader);
+        }
+    }
+}
+
+impl ApplicationHandler for Runtime {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let attributes = Window::default_attributes()
+            .with_title(&self.manifest.title)
+            .with_fullscreen(Some(Fullscreen::Borderless(None)));
+        let window = event_loop.create_window(attributes).unwrap();
+
+        let display_handle = window.display_handle().unwrap();
+        let window_handle = window.window_handle().unwrap();
+        let display = unsafe {
+            Display::new(
+                display_handle.into(),
+                DisplayApiPreference::Wgl(Some(window_handle.into())),
+            )
+            .expect("Failed to create Wgl display")
+        };
+        let config = unsafe {
+            display
+                .find_configs(ConfigTemplate::default())
+                .unwrap()
+                .next()
+                .unwrap()
+        };
+
+        let size = window.inner_size();
+        let width = NonZeroU32::new(size.width.max(1)).unwrap();
+        let height = NonZeroU32::new(size.height.max(1)).unwrap();
+        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            window_handle.into(),
+            width,
+            height,
+        );
+        let context_attributes = ContextAttributesBuilder::new()
+            .with_debug(gl_debug::DEBUG_CONTEXT)
+            .build(Some(window_handle.into()));
+        let surface = unsafe {
+            display
+                .create_window_surface(&config, &surface_attributes)
+                .unwrap()
+        };
+        let current_context = unsafe {
+            display
+                .create_context(&config, &context_attributes)
+                .unwrap()
+        }
+        .make_current(&surface)
+        .unwrap();
+
+        VSync::from_cvars(&self.cvars).apply(&surface, &current_context);
+
+        let mut gl = unsafe {
+            glow::Context::from_loader_function(|s| {
+                let c_str = CString::new(s).unwrap();
+                display.get_proc_address(&c_str) as *const _
+            })
+        };
+        gl_debug::install(&mut gl);
+        let gl = Arc::new(gl);
+
+        let mut scene = SceneNode::new(&self.manifest.title, &gl).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            SceneNode::empty(&self.manifest.title)
+        });
+        if let Some(path) = &self.manifest.environment {
+            match EnvironmentMap::from_hdr(&gl, path) {
+                Ok(environment) => scene.environment = Some(environment),
+                Err(e) => log::error!("{}", e),
+            }
+        }
+        for path in &self.manifest.tilemaps {
+            match Tilemap::load(path) {
+                Ok(mut tilemap) => {
+                    tilemap.request_atlas(&mut self.asset_loader);
+                    scene.tilemaps.push(tilemap);
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+
+        let mut camera = PerspectiveCamera::new(
+            "Game Camera".to_string(),
+            self.manifest.camera_position.into(),
+            self.cvars.get_float("r_fov"),
+            size.width,
+            size.height,
+            size.width as f32 / size.height.max(1) as f32,
+            0.1,
+            100.0,
+            0.0,
+            0.0,
+        );
+        let direction = Vector3::from(self.manifest.camera_target)
+            - Vector3::from(self.manifest.camera_position);
+        if direction.magnitude2() > 0.0 {
+            camera.set_orientation(direction.normalize());
+        }
+        self.camera = Some(camera);
+        self.ortho_camera = Some(OrthographicCamera::new(
+            "Game Orthographic Camera".to_string(),
+            cgmath::point3(0.0, 0.0, 3.0),
+            size.width,
+            size.height,
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.1,
+            100.0,
+            0.0,
+            0.0,
+        ));
+
+        match TilemapRenderer::new(&gl) {
+            Ok(renderer) => self.tilemap_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match SpriteRenderer::new(&gl) {
+            Ok(renderer) => self.sprite_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match TextRenderer::new(&gl) {
+            Ok(renderer) => self.text_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        match GameUiRenderer::new(&gl) {
+            Ok(renderer) => self.game_ui_renderer = Some(renderer),
+            Err(e) => log::error!("{}", e),
+        }
+        self.render_graph = Some(build_render_graph());
+
+        self.scene = Some(scene);
+        self.window = Some(window);
+        self.surface = Some(surface);
+        self.current_context = Some(current_context);
+        self.gl = Some(gl);
+        self.last_frame = Instant::now();
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state.is_pressed()
+                    && event.logical_key == Key::Named(NamedKey::Escape) =>
+            {
+                event_loop.exit();
+            }
+            WindowEvent::Resized(size) => {
+                if let (Some(surface), Some(context)) = (&self.surface, &self.current_context) {
+                    surface.resize(
+                        context,
+                        NonZeroU32::new(size.width.max(1)).unwrap(),
+                        NonZeroU32::new(size.height.max(1)).unwrap(),
+                    );
+                }
+                if let Some(camera) = &mut self.camera {
+                    camera.fit_viewport(size.width, size.height);
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                self.frame_pacer.set_max_fps(&self.cvars);
+                self.frame_pacer.begin_frame();
+                let now = Instant::now();
+                let delta_time = now.duration_since(self.last_frame).as_secs_f32();
+                self.last_frame = now;
+
+                self.poll_assets();
+                if let Some(scene) = &mut self.scene {
+                    scene.update_sprites(delta_time);
+                }
+
+                let Some(gl) = self.gl.clone() else {
+                    return;
+                };
+                let size = self.window.as_ref().unwrap().inner_size();
+                let viewport = Viewport::new(0, 0, size.width as i32, size.height as i32);
+                unsafe {
+                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                    gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+                }
+
+                let mut render_graph = self.render_graph.take().unwrap();
+                let result = render_graph.execute(&gl, &viewport, self);
+                self.render_graph = Some(render_graph);
+                // Only log when the error changes, otherwise the log fills up every frame
+                match result {
+                    Ok(()) => self.last_render_error = None,
+                    Err(e) => {
+                        let message = e.to_string();
+                        if self.last_render_error.as_ref() != Some(&message) {
+                            log::error!("{}", message);
+                            self.last_render_error = Some(message);
+                        }
+                    }
+                }
+
+                self.surface
+                    .as_ref()
+                    .unwrap()
+                    .swap_buffers(self.current_context.as_ref().unwrap())
+                    .unwrap();
+                self.frame_pacer.request();
+            }
+            _ => (),
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(window) = &self.window {
+            if self.frame_pacer.poll(event_loop) {
+                window.request_redraw();
+            }
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        let Some(gl) = &self.gl else {
+            return;
+        };
+        if let Some(renderer) = &self.tilemap_renderer {
+            renderer.destroy(gl);
+        }
+        if let Some(renderer) = &self.sprite_renderer {
+            renderer.destroy(gl);
+        }
+        if let Some(renderer) = &self.text_renderer {
+            renderer.destroy(gl);
+        }
+        if let Some(renderer) = &mut self.game_ui_renderer {
+            renderer.destroy(gl);
+        }
+        if let Some(render_graph) = &mut self.render_graph {
+            render_graph.destroy(gl);
+        }
+        if let Some(scene) = &mut self.scene {
+            scene.destroy(gl);
+        }
+    }
+}
+
+// The editor's graph without its previews and post effects, the game UI always draws
+fn build_render_graph() -> RenderGraph<Runtime> {
+    let mut graph = RenderGraph::new();
+
+    graph.add_pass(
+        "scene",
+        &[],
+        &[BACKBUFFER],
+        |ctx: &PassContext, runtime: &mut Runtime| {
+            let (Some(scene), Some(camera)) = (runtime.scene.as_mut(), runtime.camera.as_mut())
+            else {
+                return Ok(());
+            };
+            scene.update(camera);
+            scene.render(ctx.gl, camera, &ctx.viewport)
+        },
+    );
+
+    graph.add_pass(
+        "tilemaps",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, runtime: &mut Runtime| {
+            let (Some(renderer), Some(scene), Some(ortho)) = (
+                runtime.tilemap_renderer.as_mut(),
+                runtime.scene.as_mut(),
+                runtime.ortho_camera.as_mut(),
+            ) else {
+                return Ok(());
+            };
+            if scene.tilemaps.is_empty() {
+                return Ok(());
+            }
+            ortho.update_matrices();
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                ortho,
+                &mut scene.tilemaps,
+                &runtime.asset_loader,
+            )
+        },
+    );
+
+    graph.add_pass(
+        "sprites",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, runtime: &mut Runtime| {
+            let (Some(renderer), Some(scene), Some(ortho)) = (
+                runtime.sprite_renderer.as_mut(),
+                runtime.scene.as_ref(),
+                runtime.ortho_camera.as_mut(),
+            ) else {
+                return Ok(());
+            };
+            if scene.sprites.is_empty() {
+                return Ok(());
+            }
+            ortho.update_matrices();
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                ortho,
+                &scene.sprites,
+                &runtime.asset_loader,
+            )
+        },
+    );
+
+    graph.add_pass(
+        "text",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, runtime: &mut Runtime| {
+            let (Some(renderer), Some(scene), Some(camera)) = (
+                runtime.text_renderer.as_mut(),
+                runtime.scene.as_mut(),
+                runtime.camera.as_ref(),
+            ) else {
+                return Ok(());
+            };
+            renderer.render(ctx.gl, &ctx.viewport, camera, &mut scene.text);
+            Ok(())
+        },
+    );
+
+    graph.add_pass(
+        "game_ui",
+        &[BACKBUFFER],
+        &[BACKBUFFER],
+        |ctx: &PassContext, runtime: &mut Runtime| {
+            let (Some(renderer), Some(scene)) =
+                (runtime.game_ui_renderer.as_mut(), runtime.scene.as_mut())
+            else {
+                return Ok(());
+            };
+            if scene.game_ui.is_empty() {
+                return Ok(());
+            }
+            renderer.render(
+                ctx.gl,
+                &ctx.viewport,
+                &mut scene.game_ui,
+                &runtime.asset_loader,
+            )
+        },
+    );
+
+    graph
+}
+
+/// The runtime binary's entry point. The game is read from `GAME_MANIFEST` next to the
+/// executable.
+pub fn run() -> Result<(), String> {
+    let _ = logging::init(); // Only the log file, there's no console
+
+    // Everything the export wrote is relative to the executable
+    let executable =
+        std::env::current_exe().map_err(|e| format!("Failed to find the executable: {}", e))?;
+    if let Some(directory) = executable.parent() {
+        std::env::set_current_dir(directory)
+            .map_err(|e| format!("Failed to enter {:?}: {}", directory, e))?;
+    }
+
+    let manifest = GameManifest::load(Path::new(GAME_MANIFEST))?;
+    let event_loop = EventLoop::new().map_err(|e| e.to_string())?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    let mut runtime = Runtime::new(manifest);
+    event_loop.run_app(&mut runtime).map_err(|e| e.to_string())
+}
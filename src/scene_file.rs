@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::PerspectiveCamera,
+    component::Component,
+    data::MaterialOverride,
+    error::{EngineError, EngineResult},
+    handles::AssetHandle,
+    loader::AssetLoader,
+    mesh::StaticMesh,
+    scene_graph::SceneNode,
+    socket::{Attachment, Socket},
+};
+
+pub const SCENE_DIRECTORY: &str = "assets/scenes";
+pub const SCENE_EXTENSION: &str = "scene";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedSocket {
+    pub name: String,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+/// A static mesh by the file it was loaded from and which of the file's primitives it draws.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedMesh {
+    pub name: String,
+    pub path: PathBuf,
+    pub primitives: Vec<usize>,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3], // Degrees, like MeshTransform
+    pub scale: [f32; 3],
+    pub sockets: Vec<SavedSocket>,
+    pub attachment: Option<(usize, String)>, // Parent index and socket name
+    pub base_color_map: Option<PathBuf>,
+    #[serde(default)]
+    pub materials: Vec<Option<MaterialOverride>>, // By slot, like `primitives`
+    #[serde(default)]
+    pub components: Vec<Component>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub name: String,
+    pub position: [f32; 3],
+    pub orientation: [f32; 3],
+    pub fov: f32,
+    pub near_plane: f32,
+    pub far_plane: f32,
+}
+
+/// A scene on disk. Meshes and textures are stored by path and loaded again when it's opened.
+/// Sprites aren't saved yet, tilemaps have files of their own.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub name: String,
+    pub meshes: Vec<SavedMesh>,
+    pub cameras: Vec<SavedCamera>,
+    #[serde(default)]
+    pub active_camera: Option<usize>, // Into cameras
+}
+
+impl SceneFile {
+    pub fn from_scene(scene: &SceneNode, asset_loader: &AssetLoader) -> Self {
+        let meshes = scene
+            .static_meshes
+            .iter()
+            .map(|mesh| SavedMesh {
+                name: mesh.name.clone(),
+                path: asset_loader
+                    .loaded_mesh_data
+                    .get(&mesh.handle)
+                    .map(|loaded| loaded.path.clone())
+                    .unwrap_or_default(),
+                primitives: mesh
+                    .primitives
+                    .iter()
+                    .map(|primitive| primitive.primitive_index)
+                    .collect(),
+                translation: mesh.translation.into(),
+                rotation: mesh.rotation.into(),
+                scale: mesh.scale.into(),
+                sockets: mesh
+                    .sockets
+                    .iter()
+                    .map(|socket| SavedSocket {
+                        name: socket.name.clone(),
+                        translation: socket.translation.into(),
+                        rotation: socket.rotation.into(),
+                        scale: socket.scale.into(),
+                    })
+                    .collect(),
+                attachment: mesh
+                    .attachment
+                    .as_ref()
+                    .map(|attachment| (attachment.parent, attachment.socket.clone())),
+                base_color_map: mesh.base_color_map.and_then(|(handle, _)| {
+                    let texture = asset_loader.loaded_texture_data.get(&handle)?;
+                    Some(texture.path.clone())
+                }),
+                materials: mesh
+                    .primitives
+                    .iter()
+                    .map(|primitive| primitive.material.clone())
+                    .collect(),
+                components: mesh.components.clone(),
+            })
+            .collect();
+
+        let cameras = scene
+            .perspective_cameras
+            .iter()
+            .map(|camera| SavedCamera {
+                name: camera.name.clone(),
+                position: camera.position.into(),
+                orientation: camera.orientation.into(),
+                fov: camera.fov,
+                near_plane: camera.near_plane,
+                far_plane: camera.far_plane,
+            })
+            .collect();
+
+        Self {
+            name: scene.name.clone(),
+            meshes,
+            cameras,
+            active_camera: scene.active_camera,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scene {:?}: {}", path, e))?;
+        ron::from_str(&contents).map_err(|e| format!("Failed to parse scene {:?}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|e| format!("Failed to create {:?}: {}", directory, e))?;
+        }
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize scene: {}", e))?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write scene {:?}: {}", path, e))
+    }
+
+    /// Starts loading everything the scene refers to. `instantiate` once none of it is in
+    /// flight anymore.
+    pub fn request_assets(&self, asset_loader: &mut AssetLoader) -> Vec<AssetHandle> {
+        let mut handles = Vec::new();
+        for mesh in &self.meshes {
+            let name = file_name(&mesh.path);
+            handles.push(AssetHandle::Mesh(
+                asset_loader.request_mesh(&mesh.path, name),
+            ));
+            if let Some(path) = &mesh.base_color_map {
+                let handle = asset_loader.request_texture(path, file_name(path));
+                handles.push(AssetHandle::Texture(handle));
+            }
+            for material in mesh.materials.iter().flatten() {
+                let handle = asset_loader.request_mesh(&material.path, file_name(&material.path));
+                handles.push(AssetHandle::Mesh(handle));
+            }
+        }
+        // Meshes used more than once are only loaded once
+        let mut unique = Vec::new();
+        for handle in handles {
+            if !unique.contains(&handle) {
+                unique.push(handle);
+            }
+        }
+        unique
+    }
+
+    /// Adds the saved objects to `scene` and retains their assets. Meshes whose file didn't
+    /// load are left out and logged, along with attachments to them.
+    pub fn instantiate(
+        &self,
+        context: &glow::Context,
+        scene: &mut SceneNode,
+        asset_loader: &mut AssetLoader,
+    ) {
+        let mut placed = Vec::new(); // Where each saved mesh ended up
+        for saved in &self.meshes {
+            match self.build_mesh(context, saved, asset_loader) {
+                Ok(mesh) => {
+                    placed.push(Some(scene.static_meshes.len()));
+                    asset_loader.retain(AssetHandle::Mesh(mesh.handle));
+                    if let Some((handle, _)) = mesh.base_color_map {
+                        asset_loader.retain(AssetHandle::Texture(handle));
+                    }
+                    scene.add_static_mesh(mesh);
+                }
+                Err(e) => {
+                    log::error!("Left {} out of the scene: {}", saved.name, e);
+                    placed.push(None);
+                }
+            }
+        }
+
+        for (saved, index) in self.meshes.iter().zip(&placed) {
+            let (Some(index), Some((parent, socket))) = (index, &saved.attachment) else {
+                continue;
+            };
+            if let Some(&Some(parent)) = placed.get(*parent) {
+                scene.static_meshes[*index].attachment = Some(Attachment {
+                    parent,
+                    socket: socket.clone(),
+                });
+            }
+        }
+
+        let first_camera = scene.perspective_cameras.len();
+        if let Some(active) = self.active_camera.filter(|&i| i < self.cameras.len()) {
+            scene.active_camera = Some(first_camera + active);
+        }
+        for saved in &self.cameras {
+            let mut camera = PerspectiveCamera::new(
+                saved.name.clone(),
+                saved.position.into(),
+                saved.fov,
+                1280,
+                720,
+                16.0 / 9.0,
+                saved.near_plane,
+                saved.far_plane,
+                2.4,
+                100.0,
+            );
+            camera.orientation = saved.orientation.into();
+            scene.add_perspective_camera(camera);
+        }
+    }
+
+    fn build_mesh(
+        &self,
+        context: &glow::Context,
+        saved: &SavedMesh,
+        asset_loader: &mut AssetLoader,
+    ) -> EngineResult<StaticMesh> {
+        let handle = asset_loader.request_mesh(&saved.path, file_name(&saved.path));
+        if !asset_loader.loaded_mesh_data.contains_key(&handle) {
+            return Err(EngineError::MissingMesh(handle));
+        }
+        let mut mesh = StaticMesh::from_primitives(
+            context,
+            saved.name.clone(),
+            handle,
+            &saved.primitives,
+            asset_loader,
+        )?;
+        mesh.components = saved.components.clone();
+        mesh.translation = saved.translation.into();
+        mesh.rotation = saved.rotation.into();
+        mesh.scale = saved.scale.into();
+        mesh.sockets = saved
+            .sockets
+            .iter()
+            .map(|saved| {
+                let mut socket = Socket::new(&saved.name);
+                socket.translation = saved.translation.into();
+                socket.rotation = saved.rotation.into();
+                socket.scale = saved.scale.into();
+                socket
+            })
+            .collect();
+
+        if let Some(path) = &saved.base_color_map {
+            let texture = asset_loader.request_texture(path, file_name(path));
+            if let Err(e) = mesh.set_base_color_map(context, texture, asset_loader) {
+                log::warn!("{} keeps the scene's texture: {}", saved.name, e);
+            }
+        }
+        for (slot, material) in saved.materials.iter().enumerate() {
+            if material.is_none() {
+                continue;
+            }
+            let result = mesh.set_primitive_material(context, slot, material.clone(), asset_loader);
+            if let Err(e) = result {
+                log::warn!("{} keeps its own material: {}", saved.name, e);
+            }
+        }
+        Ok(mesh)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
@@ -1,20 +1,45 @@
-use std::fs;
-
 use crate::{
+    batching::{self, StaticBatch},
     camera::{Camera, PerspectiveCamera},
+    environment::{self, EnvironmentMap},
+    error::{EngineError, EngineResult},
+    foliage::FoliageLayer,
+    game_ui::GameUi,
+    gl_state::GlState,
+    handles::{AssetHandle, MeshHandle},
+    jobs,
+    loader::AssetLoader,
     material::Material,
-    mesh::{DynamicMesh, StaticMesh},
+    mesh::{DynamicMesh, PrimitiveUniforms, StaticMesh, StaticMeshId, OCCLUSION_MAP_UNIT},
+    raycast::Aabb,
+    render_list::{DrawCommand, RenderList},
+    shaders,
+    socket::{Attachment, Socket, ORIGIN_SOCKET},
+    spatial::{Bvh, Frustum},
+    sprites::Sprite,
+    streaming::LevelStreamer,
+    text::TextQueue,
+    tilemap::Tilemap,
     textures::Texture,
+    transform::MeshTransform,
+    view_mode::ViewMode,
     viewport::Viewport,
 };
-use cgmath::{Deg, Matrix, Rad, Rotation3};
+use std::collections::{HashMap, HashSet};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use cgmath::{Matrix, MetricSpace, Point3, Rad, Rotation3, SquareMatrix, Transform};
 use egui::*;
 use glow::HasContext;
 
+// Flipbooks are cheap, a job per sprite would cost more than it saves
+const SPRITES_PER_JOB: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SelectedObject {
     StaticMesh(usize),
     DynamicMesh(usize),
     PerspectiveCamera(usize),
+    Sprite(usize),
     // Material(usize),
 }
 
@@ -22,39 +47,174 @@ pub struct SceneNode {
     pub name: String,
 
     pub perspective_cameras: Vec<PerspectiveCamera>,
+    pub active_camera: Option<usize>, // Into perspective_cameras, play mode is seen through it
 
     pub static_meshes: Vec<StaticMesh>,
     pub dynamic_meshes: Vec<DynamicMesh>,
+    pub foliage: Vec<FoliageLayer>,
+    pub sprites: Vec<Sprite>, // Drawn by the sprite pass over the 3D scene
+    pub tilemaps: Vec<Tilemap>, // Drawn under the sprites
+    pub text: TextQueue,        // Labels for this frame, emptied by the text pass
+    pub game_ui: GameUi,        // The HUD for this frame, drawn in play mode
     // pub stream_meshes: Vec<StreamMesh>,
     pub textures: Vec<Texture>,
     pub materials: Vec<Material>,
     // pub shaders: Vec<ShaderProgram>,
     pub scripts: Vec<String>,
 
-    pub default_program: glow::NativeProgram,
+    pub default_program: Option<glow::NativeProgram>, // None when the default shaders failed to build
+    pub environment: Option<EnvironmentMap>, // Ambient light, unlit apart from the sun without it
+    view_mode: ViewMode,
+    view_mode_programs: HashMap<ViewMode, Option<glow::NativeProgram>>, // None when the variant failed to build
+    spatial_index: Bvh, // World bounds of the static meshes, by index
+    moved_meshes: HashSet<usize>, // Refit in the spatial index on its next refresh
+    static_batches: Vec<StaticBatch>, // Small static meshes merged for play, see `batching`
     // pub children: Vec<SceneNode>,
 }
 
 impl SceneNode {
-    pub fn new<T: ToString>(name: T, context: &glow::Context) -> Self {
+    pub fn new<T: ToString>(name: T, context: &glow::Context) -> EngineResult<Self> {
+        let mut scene = Self::empty(name);
+        scene.default_program = Some(shaders::load_program(
+            context,
+            "shaders/vertex.glsl",
+            "shaders/fragment.glsl",
+        )?);
+        Ok(scene)
+    }
+
+    /// A scene without shaders, it can be edited but `render` fails until it has a program.
+    pub fn empty<T: ToString>(name: T) -> Self {
         Self {
             name: name.to_string(),
             perspective_cameras: Vec::new(),
+            active_camera: None,
             static_meshes: Vec::new(),
             dynamic_meshes: Vec::new(),
+            foliage: Vec::new(),
+            sprites: Vec::new(),
+            tilemaps: Vec::new(),
+            text: TextQueue::default(),
+            game_ui: GameUi::default(),
             textures: Vec::new(),
             materials: Vec::new(),
             scripts: Vec::new(),
-            default_program: Self::create_shader_program(
-                context,
-                "shaders/vertex.glsl",
-                "shaders/fragment.glsl",
-            ),
+            default_program: None,
+            environment: None,
+            view_mode: ViewMode::Lit,
+            view_mode_programs: HashMap::new(),
+            spatial_index: Bvh::new(),
+            moved_meshes: HashSet::new(),
+            static_batches: Vec::new(),
         }
     }
 
     pub fn add_static_mesh(&mut self, mesh: StaticMesh) {
         self.static_meshes.push(mesh);
+        self.mark_moved(self.static_meshes.len() - 1);
+    }
+
+    /// Adds a loaded mesh, as one static mesh per node when its file has a scene with more
+    /// than one, attached to each other like the nodes were. Returns how many were added.
+    pub fn add_mesh_asset(
+        &mut self,
+        context: &glow::Context,
+        name: String,
+        handle: MeshHandle,
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<usize> {
+        let loaded_mesh = asset_loader
+            .loaded_mesh_data
+            .get(&handle)
+            .ok_or(EngineError::MissingMesh(handle))?;
+
+        if loaded_mesh.nodes.len() <= 1 {
+            let mut mesh = StaticMesh::new(context, name, handle, asset_loader)?;
+            if let Some(node) = loaded_mesh.nodes.first() {
+                MeshTransform::from_node(node).apply_to(&mut mesh);
+            }
+            self.add_static_mesh(mesh);
+            return Ok(1);
+        }
+
+        // Build them all first so a failure doesn't leave half a hierarchy behind
+        let mut meshes = Vec::new();
+        for node in &loaded_mesh.nodes {
+            match StaticMesh::from_node(context, handle, node, asset_loader) {
+                Ok(mesh) => meshes.push(mesh),
+                Err(e) => {
+                    for mesh in &meshes {
+                        mesh.destroy(context);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let first = self.static_meshes.len();
+        for (node, mut mesh) in loaded_mesh.nodes.iter().zip(meshes) {
+            // Parents come first, so they're in the scene already
+            if let Some(parent) = node.parent.map(|parent| first + parent) {
+                let parent_mesh = &mut self.static_meshes[parent];
+                if parent_mesh.socket(ORIGIN_SOCKET).is_none() {
+                    parent_mesh.sockets.push(Socket::new(ORIGIN_SOCKET));
+                }
+                mesh.attachment = Some(Attachment {
+                    parent,
+                    socket: ORIGIN_SOCKET.to_string(),
+                });
+            }
+            self.add_static_mesh(mesh);
+        }
+        Ok(loaded_mesh.nodes.len())
+    }
+
+    /// Deletes the mesh's GPU objects and returns its asset so the caller can release it.
+    /// Meshes after it move down one index, attachments to it are dropped.
+    pub fn remove_static_mesh(
+        &mut self,
+        context: &glow::Context,
+        index: usize,
+    ) -> Option<MeshHandle> {
+        let mesh = self.take_static_mesh(index)?;
+        mesh.destroy(context);
+        Some(mesh.handle)
+    }
+
+    /// Like `remove_static_mesh` but hands the mesh over with its GPU objects, so it can be
+    /// put back with `insert_static_mesh`.
+    pub fn take_static_mesh(&mut self, index: usize) -> Option<StaticMesh> {
+        if index >= self.static_meshes.len() {
+            return None;
+        }
+        let mesh = self.static_meshes.remove(index);
+
+        for (i, other) in self.static_meshes.iter_mut().enumerate() {
+            match &mut other.attachment {
+                Some(attachment) if attachment.parent == index => {
+                    other.attachment = None;
+                    self.moved_meshes.insert(i);
+                }
+                Some(attachment) if attachment.parent > index => attachment.parent -= 1,
+                _ => {}
+            }
+        }
+        self.mark_moved_from(index);
+        Some(mesh)
+    }
+
+    /// Meshes from `index` on move up one, attachments to them follow.
+    pub fn insert_static_mesh(&mut self, index: usize, mesh: StaticMesh) {
+        let index = index.min(self.static_meshes.len());
+        for other in &mut self.static_meshes {
+            if let Some(attachment) = &mut other.attachment {
+                if attachment.parent >= index {
+                    attachment.parent += 1;
+                }
+            }
+        }
+        self.static_meshes.insert(index, mesh);
+        self.mark_moved_from(index);
     }
 
     pub fn add_dynamic_mesh(&mut self, mesh: DynamicMesh) {
@@ -69,100 +229,538 @@ impl SceneNode {
         self.perspective_cameras.push(camera);
     }
 
-    pub fn create_shader_program(
-        gl: &glow::Context,
-        vertex_shader_path: &str,
-        fragment_shader_path: &str,
-    ) -> glow::NativeProgram {
-        unsafe {
-            let shader_source = fs::read_to_string(vertex_shader_path).unwrap();
-            let vertex_shader = gl.create_shader(glow::VERTEX_SHADER).unwrap();
-            gl.shader_source(vertex_shader, &shader_source);
-            gl.compile_shader(vertex_shader);
-
-            if !gl.get_shader_compile_status(vertex_shader) {
-                panic!(
-                    "Error compiling vertex shader: {}",
-                    gl.get_shader_info_log(vertex_shader)
-                );
+    /// The camera play mode renders from, None to use the editor camera.
+    pub fn active_camera(&self) -> Option<&PerspectiveCamera> {
+        self.perspective_cameras.get(self.active_camera?)
+    }
+
+    /// Attaches the static mesh at `child` to the socket named `socket` on the mesh at `parent`.
+    pub fn attach_to_socket(
+        &mut self,
+        child: usize,
+        parent: usize,
+        socket: &str,
+    ) -> Result<(), String> {
+        let parent_mesh = self
+            .static_meshes
+            .get(parent)
+            .ok_or_else(|| format!("No static mesh at index {}", parent))?;
+
+        if parent_mesh.socket(socket).is_none() {
+            return Err(format!(
+                "'{}' has no socket named '{}'",
+                parent_mesh.name, socket
+            ));
+        }
+
+        if child >= self.static_meshes.len() {
+            return Err(format!("No static mesh at index {}", child));
+        }
+
+        if self.is_ancestor(child, parent) {
+            return Err("Attaching would create a cycle".to_string());
+        }
+
+        self.static_meshes[child].attachment = Some(Attachment {
+            parent,
+            socket: socket.to_string(),
+        });
+        self.mark_moved(child);
+
+        Ok(())
+    }
+
+    /// Whether `ancestor` is `index` or anywhere above it.
+    pub fn is_ancestor(&self, ancestor: usize, index: usize) -> bool {
+        let mut current = Some(index);
+        while let Some(index) = current {
+            if index == ancestor {
+                return true;
             }
+            current = self
+                .static_meshes
+                .get(index)
+                .and_then(|mesh| mesh.attachment.as_ref())
+                .map(|attachment| attachment.parent);
+        }
+        false
+    }
 
-            let shader_source = fs::read_to_string(fragment_shader_path).unwrap();
-            let fragment_shader = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
-            gl.shader_source(fragment_shader, &shader_source);
-            gl.compile_shader(fragment_shader);
+    /// Hangs `child` off the origin socket of `parent`, or detaches it with None, without
+    /// moving it in the world. Its local transform is worked out again for the new parent.
+    pub fn reparent(&mut self, child: usize, parent: Option<usize>) -> Result<(), String> {
+        if child >= self.static_meshes.len() {
+            return Err(format!("No static mesh at index {}", child));
+        }
+        if parent.is_some_and(|parent| self.is_ancestor(child, parent)) {
+            return Err("Attaching would create a cycle".to_string());
+        }
+        let world = self.static_mesh_world_matrix(child);
+
+        let parent_world = match parent {
+            Some(parent) => {
+                let parent_mesh = self
+                    .static_meshes
+                    .get_mut(parent)
+                    .ok_or_else(|| format!("No static mesh at index {}", parent))?;
+                if parent_mesh.socket(ORIGIN_SOCKET).is_none() {
+                    parent_mesh.sockets.push(Socket::new(ORIGIN_SOCKET));
+                }
+                self.socket_world_matrix(parent, ORIGIN_SOCKET).unwrap()
+            }
+            None => cgmath::Matrix4::identity(),
+        };
+        let inverse = parent_world
+            .invert()
+            .ok_or("The new parent is scaled to zero")?;
+
+        match parent {
+            Some(parent) => self.attach_to_socket(child, parent, ORIGIN_SOCKET)?,
+            None => self.detach(child),
+        }
+        MeshTransform::from_matrix(&(inverse * world)).apply_to(&mut self.static_meshes[child]);
+        Ok(())
+    }
 
-            if !gl.get_shader_compile_status(fragment_shader) {
-                panic!(
-                    "Error compiling fragment shader: {}",
-                    gl.get_shader_info_log(fragment_shader)
-                );
+    pub fn detach(&mut self, child: usize) {
+        if let Some(mesh) = self.static_meshes.get_mut(child) {
+            mesh.attachment = None;
+            self.mark_moved(child);
+        }
+    }
+
+    /// World transform of a socket, including every attachment above its owner.
+    pub fn socket_world_matrix(
+        &self,
+        owner: usize,
+        socket: &str,
+    ) -> Option<cgmath::Matrix4<f32>> {
+        let socket = self.static_meshes.get(owner)?.socket(socket)?;
+        Some(self.static_mesh_world_matrix(owner) * socket.local_matrix())
+    }
+
+    /// Where the mesh is now, its index changes as meshes before it are removed.
+    pub fn static_mesh_index(&self, id: StaticMeshId) -> Option<usize> {
+        self.static_meshes.iter().position(|mesh| mesh.id == id)
+    }
+
+    pub fn static_mesh_world_matrix(&self, index: usize) -> cgmath::Matrix4<f32> {
+        let mesh = &self.static_meshes[index];
+
+        // A dangling attachment (missing parent or socket) falls back to the mesh's own transform
+        let parent_matrix = mesh.attachment.as_ref().and_then(|attachment| {
+            self.socket_world_matrix(attachment.parent, &attachment.socket)
+        });
+
+        match parent_matrix {
+            Some(parent_matrix) => parent_matrix * mesh.model_matrix(),
+            None => mesh.model_matrix(),
+        }
+    }
+
+    /// World space bounds, None if the mesh has no vertices.
+    pub fn static_mesh_world_bounds(&self, index: usize) -> Option<Aabb> {
+        let bounds = self.static_meshes.get(index)?.bounds?;
+        Some(bounds.transformed(&self.static_mesh_world_matrix(index)))
+    }
+
+    pub fn spatial_index(&self) -> &Bvh {
+        &self.spatial_index
+    }
+
+    /// Call after changing a static mesh's transform or sockets outside of the scene's own
+    /// methods. It's refit in the spatial index on the next refresh, with whatever is attached
+    /// below it.
+    pub fn mark_moved(&mut self, index: usize) {
+        self.moved_meshes.insert(index);
+    }
+
+    // Every mesh from `first` on has a new index
+    fn mark_moved_from(&mut self, first: usize) {
+        self.moved_meshes.extend(first..self.static_meshes.len());
+    }
+
+    /// Refits the meshes marked as moved since the last refresh, and the meshes attached below
+    /// them. Only the ones that left their padded box in the index touch the tree.
+    pub fn refresh_spatial_index(&mut self) {
+        self.spatial_index.truncate(self.static_meshes.len());
+        if self.moved_meshes.is_empty() {
+            return;
+        }
+        let mut moved = std::mem::take(&mut self.moved_meshes);
+
+        // Attachments can't form cycles, so walking up always ends
+        let follows_moved = |mut index: usize| {
+            while let Some(attachment) = self
+                .static_meshes
+                .get(index)
+                .and_then(|mesh| mesh.attachment.as_ref())
+            {
+                index = attachment.parent;
+                if moved.contains(&index) {
+                    return true;
+                }
+            }
+            false
+        };
+        let followers: Vec<usize> = (0..self.static_meshes.len())
+            .filter(|i| !moved.contains(i) && follows_moved(*i))
+            .collect();
+        moved.extend(followers);
+
+        for i in moved.into_iter().filter(|&i| i < self.static_meshes.len()) {
+            match self.static_mesh_world_bounds(i) {
+                Some(bounds) => {
+                    self.spatial_index.update(i, bounds);
+                }
+                None => self.spatial_index.remove(i),
             }
+        }
+    }
 
-            let shader_program = gl.create_program().unwrap();
-            gl.attach_shader(shader_program, vertex_shader);
-            gl.attach_shader(shader_program, fragment_shader);
-            gl.link_program(shader_program);
+    /// Static meshes whose world bounds overlap `region`.
+    pub fn static_meshes_in(&self, region: &Aabb) -> Vec<usize> {
+        self.spatial_index
+            .query_aabb(region)
+            .into_iter()
+            .filter(|&i| {
+                self.static_mesh_world_bounds(i)
+                    .is_some_and(|bounds| bounds.intersects(region))
+            })
+            .collect()
+    }
 
-            gl.delete_shader(vertex_shader);
-            gl.delete_shader(fragment_shader);
+    pub fn view_mode(&self) -> ViewMode {
+        self.view_mode
+    }
 
-            if !gl.get_program_link_status(shader_program) {
-                panic!(
-                    "Shader link error: {}",
-                    gl.get_program_info_log(shader_program)
-                );
+    /// Renders with a debug variant of the default shader from now on, building it the first
+    /// time. A variant that fails to build is reported once and the lit shader is used instead.
+    pub fn set_view_mode(&mut self, context: &glow::Context, mode: ViewMode) {
+        self.view_mode = mode;
+        let Some(define) = mode.define() else {
+            return;
+        };
+        self.view_mode_programs.entry(mode).or_insert_with(|| {
+            match shaders::load_program_variant(
+                context,
+                "shaders/vertex.glsl",
+                "shaders/fragment.glsl",
+                &[define],
+            ) {
+                Ok(program) => Some(program),
+                Err(e) => {
+                    log::error!("{} view mode: {}", mode.label(), e);
+                    None
+                }
+            }
+        });
+    }
+
+    /// Frees what the scene only needs while it's open, the view mode variants. `resume` builds
+    /// them again.
+    pub fn suspend(&mut self, context: &glow::Context) {
+        unsafe {
+            for program in self.view_mode_programs.drain().filter_map(|(_, program)| program) {
+                context.delete_program(program);
+            }
+        }
+    }
+
+    /// Gets the scene ready to render again after `suspend`, or for the first time when it was
+    /// made without shaders.
+    pub fn resume(&mut self, context: &glow::Context) {
+        if self.default_program.is_none() {
+            match shaders::load_program(context, "shaders/vertex.glsl", "shaders/fragment.glsl") {
+                Ok(program) => self.default_program = Some(program),
+                Err(e) => log::error!("{} can't be rendered: {}", self.name, e),
+            }
+        }
+        self.set_view_mode(context, self.view_mode);
+        self.refresh_spatial_index();
+    }
+
+    fn program(&self) -> Option<glow::NativeProgram> {
+        match self.view_mode_programs.get(&self.view_mode) {
+            Some(Some(program)) => Some(*program),
+            _ => self.default_program,
+        }
+    }
+
+    pub fn destroy(&mut self, context: &glow::Context) {
+        self.suspend(context);
+        unsafe {
+            if let Some(program) = self.default_program.take() {
+                context.delete_program(program);
             }
+        }
+        if let Some(environment) = self.environment.take() {
+            environment.destroy(context);
+        }
+        for layer in self.foliage.drain(..) {
+            layer.destroy(context);
+        }
+        for dynamic_mesh in &mut self.dynamic_meshes {
+            dynamic_mesh.destroy(context);
+        }
+        self.clear_static_batches(context);
+        for tilemap in &mut self.tilemaps {
+            tilemap.destroy(context);
+        }
+    }
 
-            shader_program
+    /// Hands back everything the scene retained in the loader, call before `destroy`.
+    pub fn release_assets(&mut self, asset_loader: &mut AssetLoader) {
+        for mesh in &self.static_meshes {
+            asset_loader.release(AssetHandle::Mesh(mesh.handle));
+            if let Some((handle, _)) = mesh.base_color_map {
+                asset_loader.release(AssetHandle::Texture(handle));
+            }
         }
+        for sprite in &self.sprites {
+            if let Some(handle) = sprite.texture {
+                asset_loader.release(AssetHandle::Texture(handle));
+            }
+        }
+        for tilemap in &mut self.tilemaps {
+            tilemap.release_atlas(asset_loader);
+        }
+        for layer in &self.foliage {
+            asset_loader.release(AssetHandle::Mesh(layer.handle));
+        }
+    }
+
+    pub fn static_batches(&self) -> &[StaticBatch] {
+        &self.static_batches
+    }
+
+    /// Merges the small static meshes that share a material into batches, replacing the old
+    /// ones. Needs the meshes' vertex data, so only meshes whose assets are loaded go in.
+    pub fn build_static_batches(&mut self, context: &glow::Context, asset_loader: &AssetLoader) {
+        self.clear_static_batches(context);
+        self.static_batches = batching::build(context, self, asset_loader);
+        let meshes: usize = self.static_batches.iter().map(|b| b.meshes().count()).sum();
+        log::info!(
+            "Batched {} static meshes into {} draws",
+            meshes,
+            self.static_batches.len()
+        );
+    }
+
+    pub fn clear_static_batches(&mut self, context: &glow::Context) {
+        for batch in self.static_batches.drain(..) {
+            batch.destroy(context);
+        }
+    }
+
+    /// Steps the flipbooks.
+    pub fn update_sprites(&mut self, delta_time: f32) {
+        jobs::global().scope(|jobs| {
+            for sprites in self.sprites.chunks_mut(SPRITES_PER_JOB) {
+                jobs.spawn("animate_sprites", move || {
+                    for sprite in sprites {
+                        sprite.update(delta_time);
+                    }
+                });
+            }
+        });
     }
 
     pub fn update(&mut self, camera: &mut dyn Camera) {
         camera.update_matrices();
+        self.refresh_spatial_index();
+    }
+
+    pub fn render(
+        &self,
+        context: &glow::Context,
+        camera: &mut dyn Camera,
+        viewport: &Viewport,
+    ) -> EngineResult<()> {
+        let visible = self.visible_meshes(&camera_frustum(camera));
+        let list = self.render_list(camera, &visible);
+        self.submit(context, camera, viewport, &list, true)
+    }
+
+    /// Like `render` but keeps the depth of what was drawn before, for streamed levels.
+    pub fn render_over(
+        &self,
+        context: &glow::Context,
+        camera: &mut dyn Camera,
+        viewport: &Viewport,
+    ) -> EngineResult<()> {
+        let visible = self.visible_meshes(&camera_frustum(camera));
+        self.render_culled(context, camera, viewport, &visible)
+    }
+
+    /// `render_over` with the static meshes already culled by `visible_meshes`.
+    pub fn render_culled(
+        &self,
+        context: &glow::Context,
+        camera: &mut dyn Camera,
+        viewport: &Viewport,
+        visible: &[bool],
+    ) -> EngineResult<()> {
+        let list = self.render_list(camera, visible);
+        self.submit(context, camera, viewport, &list, false)
+    }
+
+    /// Which static meshes are in `frustum`, by index. Only reads the spatial index, so it can
+    /// run in a job while something else is drawn.
+    pub fn visible_meshes(&self, frustum: &Frustum) -> Vec<bool> {
+        // Meshes without bounds aren't in the index and are always drawn
+        let mut visible: Vec<bool> = self
+            .static_meshes
+            .iter()
+            .map(|mesh| mesh.bounds.is_none())
+            .collect();
+        for i in self.spatial_index.query_frustum(frustum) {
+            if let Some(visible) = visible.get_mut(i) {
+                *visible = true;
+            }
+        }
+        visible
     }
 
-    pub fn render(&self, context: &glow::Context, camera: &mut dyn Camera, viewport: &Viewport) {
+    /// Walks the static meshes `visible` lets through into a sorted list of what to draw,
+    /// without any GL calls.
+    pub fn render_list(&self, camera: &dyn Camera, visible: &[bool]) -> RenderList {
+        let camera_position = camera.get_position();
+        let mut list = RenderList::new();
+        let is_visible = |i: usize| visible.get(i).copied().unwrap_or(true);
+
+        // A batch is drawn whole when any of its meshes is in view, moved meshes break it up
+        let mut batched = vec![false; self.static_meshes.len()];
+        for (index, batch) in self.static_batches.iter().enumerate() {
+            if !batch.is_current(self) {
+                continue;
+            }
+            for mesh in batch.meshes() {
+                batched[mesh] = true;
+            }
+            match batch.meshes().any(is_visible) {
+                true => list.batches.push(index),
+                false => list.culled += batch.meshes().count(),
+            }
+        }
+
+        for (i, static_mesh) in self.static_meshes.iter().enumerate() {
+            if batched[i] {
+                continue;
+            }
+            if !is_visible(i) {
+                list.culled += 1;
+                continue;
+            }
+            let model = self.static_mesh_world_matrix(i);
+
+            // Measured to the middle of the mesh so big meshes don't switch too early
+            let center = static_mesh
+                .bounds
+                .map_or(Point3::new(0.0, 0.0, 0.0), |bounds| bounds.center());
+            let distance = model.transform_point(center).distance(camera_position);
+            let lod = match static_mesh.bounds {
+                Some(_) => static_mesh.lod_for_distance(distance),
+                None => 0,
+            };
+
+            list.push(DrawCommand {
+                mesh: i,
+                handle: static_mesh.handle,
+                material: static_mesh.base_color_map.map(|(handle, _)| handle),
+                lod,
+                model,
+                distance,
+            });
+        }
+        list.sort();
+        list
+    }
+
+    /// Draws `list` on the GL context, then the foliage and dynamic meshes which aren't in
+    /// the list yet.
+    pub fn submit(
+        &self,
+        context: &glow::Context,
+        camera: &dyn Camera,
+        viewport: &Viewport,
+        list: &RenderList,
+        clear_depth: bool,
+    ) -> EngineResult<()> {
         // Simple rendering logic, later the ecs will query the entities with a render system material and mesh's
 
+        let program = self.program().ok_or(EngineError::MissingShaderProgram)?;
+        let camera_matrix_uniform = unsafe { context.get_uniform_location(program, "camMatrix") }
+            .ok_or_else(|| EngineError::MissingUniform("camMatrix".to_string()))?;
+        // Only used for lighting, a shader without it still draws
+        let model_uniform = unsafe { context.get_uniform_location(program, "model") };
+        let location = |name: &str| unsafe { context.get_uniform_location(program, name) };
+        let primitive_uniforms = PrimitiveUniforms::new(context, program);
+
+        // Sorted commands bind the same textures and meshes one after another, those binds are
+        // skipped
+        let mut state = GlState::new(context);
+        let overdraw = self.view_mode == ViewMode::Overdraw;
+        // Every layer adds up in overdraw, hidden ones too
+        state.set_cull_face(!overdraw);
+        state.set_depth_test(!overdraw);
+        state.depth_func(glow::LESS);
+        if overdraw {
+            state.set_blend(true);
+            state.blend_func(glow::ONE, glow::ONE);
+        }
         unsafe {
-            context.clear(glow::DEPTH_BUFFER_BIT);
-            context.enable(glow::CULL_FACE);
-            context.enable(glow::DEPTH_TEST);
-            context.depth_func(glow::LESS);
+            if clear_depth {
+                context.clear(glow::DEPTH_BUFFER_BIT);
+            }
             // Makes sure that everything is renderered in the central panel of the ui
             context.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
         }
 
+        let scene_texture = self.textures.first().map(|texture| texture.texture);
+        state.use_program(Some(program));
         unsafe {
-            // Very bad, just in place to make it run
-            if self.textures.len() > 0 {
-                context.bind_texture(
-                    glow::TEXTURE_2D,
-                    Some(self.textures.get(0).unwrap().texture),
-                );
+            // Optimized out in the view modes that don't sample it
+            context.uniform_1_i32(location("image").as_ref(), 0);
+            context.uniform_1_i32(location("normal_map").as_ref(), 1);
+            context.uniform_1_i32(location("occlusion_map").as_ref(), OCCLUSION_MAP_UNIT as i32);
+            // Always set, samplers of different types must never share a unit
+            context.uniform_1_i32(
+                location("irradiance_map").as_ref(),
+                environment::IRRADIANCE_UNIT as i32,
+            );
+            context.uniform_1_i32(
+                location("prefiltered_map").as_ref(),
+                environment::PREFILTERED_UNIT as i32,
+            );
+            context.uniform_1_i32(
+                location("brdf_lut").as_ref(),
+                environment::BRDF_LUT_UNIT as i32,
+            );
+            context.uniform_1_i32(
+                location("has_environment").as_ref(),
+                self.environment.is_some() as i32,
+            );
+            if let Some(environment) = &self.environment {
+                environment.bind(context, program);
+                state.forget_textures();
             }
-            
-            context.use_program(Some(self.default_program));
-
-            context.active_texture(glow::TEXTURE0);
 
-            let texture_uniform = context
-                .get_uniform_location(self.default_program, "image")
-                .expect("Could not find the uniform called 'image'");
-            context.uniform_1_i32(Some(&texture_uniform), 0);
+            let position = camera.get_position();
+            context.uniform_3_f32(
+                location("camera_position").as_ref(),
+                position.x,
+                position.y,
+                position.z,
+            );
         }
 
-        for static_mesh in &self.static_meshes {
-            let model_matrix = cgmath::Matrix4::from_translation(static_mesh.translation)
-                * cgmath::Matrix4::from_angle_x(Deg(static_mesh.rotation.x))
-                * cgmath::Matrix4::from_angle_y(Deg(static_mesh.rotation.y))
-                * cgmath::Matrix4::from_angle_z(Deg(static_mesh.rotation.z))
-                * cgmath::Matrix4::from_nonuniform_scale(
-                    static_mesh.scale.x,
-                    static_mesh.scale.y,
-                    static_mesh.scale.z,
-                );
+        for command in &list.commands {
+            // Commands from an older list can outlive their mesh
+            let Some(static_mesh) = self.static_meshes.get(command.mesh) else {
+                continue;
+            };
+            let model_matrix = command.model;
 
             let mvp_matrix = camera.get_projection() * camera.get_view() * model_matrix;
 
@@ -170,36 +768,165 @@ impl SceneNode {
             // Later we can use a more efficient way to convert the matrix to a slice
             let mvp_array: &[f32; 16] = unsafe { std::mem::transmute(&mvp_matrix) };
 
+            let model_array: &[f32; 16] = model_matrix.as_ref();
+
+            let base_color = static_mesh.base_color_map.map(|(_, texture)| texture);
             unsafe {
-                let camera_matrix_uniform = context
-                    .get_uniform_location(self.default_program, "camMatrix")
-                    .expect("Could not find the uniform called 'camMatrix'");
                 context.uniform_matrix_4_f32_slice(Some(&camera_matrix_uniform), false, mvp_array);
+                context.uniform_matrix_4_f32_slice(model_uniform.as_ref(), false, model_array);
+            }
+            state.bind_texture(0, base_color.or(scene_texture));
+
+            static_mesh.render(&mut state, &primitive_uniforms, command.lod);
+        }
+
+        // Batches and foliage instances are already in world space
+        let view_projection = camera.get_projection() * camera.get_view();
+        let view_projection_array: &[f32; 16] = view_projection.as_ref();
+        let identity = cgmath::Matrix4::<f32>::identity();
+        let identity_array: &[f32; 16] = identity.as_ref();
+        if !list.batches.is_empty() || !self.foliage.is_empty() {
+            unsafe {
+                context.uniform_matrix_4_f32_slice(
+                    Some(&camera_matrix_uniform),
+                    false,
+                    view_projection_array,
+                );
+                context.uniform_matrix_4_f32_slice(model_uniform.as_ref(), false, identity_array);
             }
+        }
 
-            static_mesh.render(context);
+        for batch in list.batches.iter().filter_map(|&i| self.static_batches.get(i)) {
+            let Some((material, base_color)) = batch.material(self) else {
+                continue;
+            };
+            state.bind_texture(0, base_color.or(scene_texture));
+            let uniforms = &primitive_uniforms;
+            StaticMesh::render_with_material(&mut state, uniforms, material, &batch.render_data);
+        }
+
+        if !self.foliage.is_empty() {
+            state.bind_texture(0, scene_texture);
+            for layer in &self.foliage {
+                layer.render(&mut state, &primitive_uniforms);
+            }
         }
 
         for dynamic_mesh in &self.dynamic_meshes {
             dynamic_mesh.render(context);
         }
+        state.forget();
+
+        if overdraw {
+            state.set_blend(false);
+            state.blend_func(glow::ONE, glow::ZERO);
+        }
+        // Later passes bind textures on the plain context and expect the first unit active
+        unsafe { context.active_texture(glow::TEXTURE0) };
+
+        Ok(())
+    }
+}
+
+/// A scene in the graph by its position or its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneRef {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for SceneRef {
+    fn from(index: usize) -> Self {
+        SceneRef::Index(index)
+    }
+}
+
+impl From<&str> for SceneRef {
+    fn from(name: &str) -> Self {
+        SceneRef::Name(name.to_string())
+    }
+}
+
+impl From<String> for SceneRef {
+    fn from(name: String) -> Self {
+        SceneRef::Name(name)
     }
 }
 
 pub struct SceneGraph {
     pub current_scene: usize,
     pub scenes: Vec<Box<SceneNode>>,
+    pub levels: LevelStreamer, // Loaded alongside the current scene
+    switch_tx: Sender<SceneRef>,
+    switch_rx: Receiver<SceneRef>,
 }
 
 impl SceneGraph {
     pub fn new() -> Self {
+        let (switch_tx, switch_rx) = unbounded();
         Self {
             current_scene: 0,
             scenes: Vec::new(),
+            levels: LevelStreamer::new(),
+            switch_tx,
+            switch_rx,
+        }
+    }
+
+    pub fn find_scene(&self, scene: &SceneRef) -> Option<usize> {
+        match scene {
+            SceneRef::Index(index) => (*index < self.scenes.len()).then_some(*index),
+            SceneRef::Name(name) => self.scenes.iter().position(|scene| scene.name == *name),
+        }
+    }
+
+    /// Makes another scene the one that's updated and rendered. The streamed levels are unloaded
+    /// and stream in again around the new scene, the old one keeps its meshes but gives back its
+    /// view mode shaders until it's switched to again.
+    pub fn set_current_scene(
+        &mut self,
+        scene: impl Into<SceneRef>,
+        context: &glow::Context,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<(), String> {
+        let scene = scene.into();
+        let index = self.find_scene(&scene).ok_or_else(|| match &scene {
+            SceneRef::Index(index) => format!("There is no scene {}", index),
+            SceneRef::Name(name) => format!("There is no scene called {}", name),
+        })?;
+        if index == self.current_scene {
+            return Ok(());
+        }
+
+        self.levels.unload_all(context, asset_loader);
+        if let Some(previous) = self.scenes.get_mut(self.current_scene) {
+            previous.suspend(context);
         }
+        self.scenes[index].resume(context);
+        self.current_scene = index;
+        log::info!("Switched to {}", self.scenes[index].name);
+        Ok(())
+    }
+
+    /// For the console and scripts, the switch happens at the start of the next frame.
+    pub fn switch_requests(&self) -> Sender<SceneRef> {
+        self.switch_tx.clone()
+    }
+
+    /// The latest switch that was asked for, earlier ones are dropped.
+    pub fn take_switch_request(&self) -> Option<SceneRef> {
+        self.switch_rx.try_iter().last()
+    }
+
+    pub fn current_scene(&self) -> Option<&SceneNode> {
+        self.scenes.get(self.current_scene).map(|scene| scene.as_ref())
     }
 
     pub fn current_scene_mut(&mut self) -> Option<&mut Box<SceneNode>> {
         self.scenes.get_mut(self.current_scene)
     }
 }
+
+pub fn camera_frustum(camera: &dyn Camera) -> Frustum {
+    Frustum::from_matrix(&(camera.get_projection() * camera.get_view()))
+}
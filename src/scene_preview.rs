@@ -0,0 +1,229 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{capture, scene_graph::SceneNode, viewport::Viewport};
+
+const THUMBNAIL_WIDTH: u32 = 256;
+
+// Previews live next to the scene file: level.scene -> level.scene.thumb.png, level.scene.meta.ron
+fn sidecar_path(scene_path: &Path, extension: &str) -> PathBuf {
+    let mut path = scene_path.as_os_str().to_owned();
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+pub fn thumbnail_path(scene_path: &Path) -> PathBuf {
+    sidecar_path(scene_path, ".thumb.png")
+}
+
+pub fn metadata_path(scene_path: &Path) -> PathBuf {
+    sidecar_path(scene_path, ".meta.ron")
+}
+
+/// What the Open dialog shows about a scene without loading it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SceneMetadata {
+    pub name: String,
+    pub static_meshes: usize,
+    pub dynamic_meshes: usize,
+    pub cameras: usize,
+    pub textures: usize,
+    pub materials: usize,
+    pub saved_at: u64, // Seconds since the Unix epoch
+}
+
+impl SceneMetadata {
+    pub fn from_scene(scene: &SceneNode) -> Self {
+        Self {
+            name: scene.name.clone(),
+            static_meshes: scene.static_meshes.len(),
+            dynamic_meshes: scene.dynamic_meshes.len(),
+            cameras: scene.perspective_cameras.len(),
+            textures: scene.textures.len(),
+            materials: scene.materials.len(),
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// Writes the thumbnail and metadata for a scene that was just saved to `scene_path`.
+/// Call right after the scene was rendered so the viewport still shows it.
+pub fn save_preview(
+    gl: &glow::Context,
+    viewport: &Viewport,
+    scene: &SceneNode,
+    scene_path: &Path,
+) -> Result<(), String> {
+    let metadata = SceneMetadata::from_scene(scene);
+    let contents = ron::ser::to_string_pretty(&metadata, ron::ser::PrettyConfig::default())
+        .map_err(|e| format!("Failed to serialize scene metadata: {}", e))?;
+    let path = metadata_path(scene_path);
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+
+    let width = viewport.width.max(0) as u32;
+    let height = viewport.height.max(0) as u32;
+    let image = image::RgbaImage::from_raw(width, height, capture::read_viewport(gl, viewport))
+        .ok_or("The viewport is empty")?;
+
+    // Scaling and encoding are slow enough to hitch the editor
+    let path = thumbnail_path(scene_path);
+    std::thread::spawn(move || {
+        let thumbnail_height = (height * THUMBNAIL_WIDTH / width.max(1)).max(1);
+        let thumbnail = image::imageops::thumbnail(
+            &image::imageops::flip_vertical(&image),
+            THUMBNAIL_WIDTH,
+            thumbnail_height,
+        );
+        if let Err(e) = thumbnail.save(&path) {
+            log::error!("Failed to write {:?}: {}", path, e);
+        }
+    });
+
+    Ok(())
+}
+
+/// A scene file's preview as loaded for the Open dialog. Everything is optional,
+/// scenes saved before previews existed only have a modification time.
+pub struct ScenePreview {
+    pub path: PathBuf,
+    pub metadata: Option<SceneMetadata>,
+    pub modified: Option<SystemTime>,
+    pub thumbnail: Option<egui::ColorImage>,
+}
+
+impl ScenePreview {
+    pub fn load(scene_path: &Path) -> Self {
+        let metadata = std::fs::read_to_string(metadata_path(scene_path))
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok());
+
+        let thumbnail = image::open(thumbnail_path(scene_path)).ok().map(|image| {
+            let image = image.to_rgba8();
+            egui::ColorImage::from_rgba_unmultiplied(
+                [image.width() as usize, image.height() as usize],
+                image.as_raw(),
+            )
+        });
+
+        Self {
+            path: scene_path.to_path_buf(),
+            metadata,
+            modified: std::fs::metadata(scene_path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            thumbnail,
+        }
+    }
+}
+
+fn format_age(time: SystemTime) -> String {
+    let Ok(age) = SystemTime::now().duration_since(time) else {
+        return "just now".to_string();
+    };
+    match age.as_secs() {
+        0..60 => "just now".to_string(),
+        seconds @ 60..3600 => format!("{} min ago", seconds / 60),
+        seconds @ 3600..86400 => format!("{} h ago", seconds / 3600),
+        seconds => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// The preview panel of the Open dialog. `texture` caches the thumbnail between frames,
+/// clear it when a different scene is selected.
+pub fn preview_ui(
+    ui: &mut egui::Ui,
+    preview: &ScenePreview,
+    texture: &mut Option<egui::TextureHandle>,
+) {
+    match &preview.thumbnail {
+        Some(image) => {
+            let handle = texture.get_or_insert_with(|| {
+                ui.ctx().load_texture(
+                    format!("ScenePreview {:?}", preview.path),
+                    image.clone(),
+                    egui::TextureOptions::LINEAR,
+                )
+            });
+            let size = handle.size_vec2();
+            let scale = (ui.available_width() / size.x).min(1.0);
+            ui.image((handle.id(), size * scale));
+        }
+        None => {
+            ui.weak("No thumbnail, save the scene to create one");
+        }
+    }
+
+    egui::Grid::new("ScenePreview")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("File");
+            ui.label(
+                preview
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            );
+            ui.end_row();
+
+            ui.label("Modified");
+            ui.label(
+                preview
+                    .modified
+                    .map(format_age)
+                    .unwrap_or("Unknown".to_string()),
+            );
+            ui.end_row();
+
+            if let Some(metadata) = &preview.metadata {
+                ui.label("Scene");
+                ui.label(&metadata.name);
+                ui.end_row();
+
+                for (label, count) in [
+                    ("Static meshes", metadata.static_meshes),
+                    ("Dynamic meshes", metadata.dynamic_meshes),
+                    ("Cameras", metadata.cameras),
+                    ("Textures", metadata.textures),
+                    ("Materials", metadata.materials),
+                ] {
+                    ui.label(label);
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+            }
+        });
+}
+
+/// The preview of the last scene hovered, so it's only read from disk again when another one
+/// is. Clear it when a scene is saved.
+#[derive(Default)]
+pub struct ScenePreviewCache {
+    shown: Option<(ScenePreview, Option<egui::TextureHandle>)>,
+}
+
+impl ScenePreviewCache {
+    pub fn ui(&mut self, ui: &mut egui::Ui, scene_path: &Path) {
+        if self
+            .shown
+            .as_ref()
+            .is_none_or(|(preview, _)| preview.path != scene_path)
+        {
+            self.shown = Some((ScenePreview::load(scene_path), None));
+        }
+        if let Some((preview, texture)) = &mut self.shown {
+            preview_ui(ui, preview, texture);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.shown = None;
+    }
+}
@@ -0,0 +1,115 @@
+// Starting points for File > New Scene
+
+use std::path::Path;
+
+use cgmath::{InnerSpace, Point3};
+
+use cruel_game_engine::{
+    camera::{Camera, PerspectiveCamera},
+    handles::MeshHandle,
+    loader::AssetLoader,
+    scene_graph::SceneNode,
+    sprites::Sprite,
+};
+
+use crate::CameraType;
+
+const GROUND_PLANE_PATH: &str = "assets/meshes/ground_plane.gltf";
+// Written into projects that don't have it yet
+const GROUND_PLANE: &str = include_str!("../assets/meshes/ground_plane.gltf");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneTemplate {
+    Empty,
+    Basic,
+    TwoD,
+}
+
+/// A scene made from a template. The meshes are still loading and get placed once they're in.
+pub struct NewScene {
+    pub scene: SceneNode,
+    pub meshes: Vec<(MeshHandle, Point3<f32>)>,
+    pub camera_type: CameraType,
+}
+
+impl SceneTemplate {
+    pub const ALL: [SceneTemplate; 3] = [
+        SceneTemplate::Empty,
+        SceneTemplate::Basic,
+        SceneTemplate::TwoD,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SceneTemplate::Empty => "Empty",
+            SceneTemplate::Basic => "Basic 3D",
+            SceneTemplate::TwoD => "2D",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            SceneTemplate::Empty => "Nothing in it",
+            SceneTemplate::Basic => "A ground plane under the sun and a camera looking at it",
+            SceneTemplate::TwoD => {
+                "Seen through the orthographic camera, with a sprite to start from"
+            }
+        }
+    }
+
+    pub fn create(
+        self,
+        name: &str,
+        context: &glow::Context,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<NewScene, String> {
+        let mut new_scene = NewScene {
+            scene: SceneNode::new(name, context)?,
+            meshes: Vec::new(),
+            camera_type: CameraType::Perspective,
+        };
+
+        match self {
+            SceneTemplate::Empty => {}
+            SceneTemplate::Basic => {
+                let path = ground_plane()?;
+                let handle = asset_loader.request_mesh(path, "Ground".to_string());
+                new_scene.meshes.push((handle, Point3::new(0.0, 0.0, 0.0)));
+
+                let mut camera = PerspectiveCamera::new(
+                    "Main Camera".to_string(),
+                    Point3::new(0.0, 3.0, 8.0),
+                    45.0,
+                    1280,
+                    720,
+                    16.0 / 9.0,
+                    0.1,
+                    100.0,
+                    2.4,
+                    100.0,
+                );
+                camera.set_orientation(cgmath::vec3(0.0, -3.0, -8.0).normalize());
+                new_scene.scene.add_perspective_camera(camera);
+            }
+            SceneTemplate::TwoD => {
+                new_scene.scene.sprites.push(Sprite::new(None));
+                new_scene.camera_type = CameraType::Orthographic;
+            }
+        }
+        Ok(new_scene)
+    }
+}
+
+// The plane is in the editor's assets, a project gets its own copy the first time it's used
+fn ground_plane() -> Result<&'static Path, String> {
+    let path = Path::new(GROUND_PLANE_PATH);
+    if !path.exists() {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|e| format!("Failed to create {:?}: {}", directory, e))?;
+        }
+        std::fs::write(path, GROUND_PLANE)
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    }
+    Ok(path)
+}
@@ -1,7 +1,131 @@
-use crate::handles::ShaderHandle;
+use glow::HasContext;
+
+use crate::{
+    error::{EngineError, EngineResult},
+    gl_debug,
+    handles::ShaderHandle,
+};
 
 #[derive(Debug)]
 pub struct ShaderProgram {
     pub name: String,
     pub handle: ShaderHandle,
 }
+
+fn compile_shader(
+    gl: &glow::Context,
+    kind: u32,
+    path: &str,
+    defines: &[&str],
+) -> EngineResult<glow::Shader> {
+    let source = std::fs::read_to_string(path).map_err(|e| EngineError::Io {
+        path: path.into(),
+        message: e.to_string(),
+    })?;
+    let source = with_defines(&source, defines);
+    unsafe {
+        let shader = gl.create_shader(kind).map_err(EngineError::Gl)?;
+        gl.shader_source(shader, &source);
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            let log = gl.get_shader_info_log(shader);
+            gl.delete_shader(shader);
+            return Err(EngineError::ShaderCompile {
+                path: path.to_string(),
+                log,
+            });
+        }
+        Ok(shader)
+    }
+}
+
+// The defines have to come after #version, which must be the first line
+fn with_defines(source: &str, defines: &[&str]) -> String {
+    if defines.is_empty() {
+        return source.to_string();
+    }
+    let defines: String = defines
+        .iter()
+        .map(|define| format!("#define {}\n", define))
+        .collect();
+    match source
+        .find("#version")
+        .and_then(|start| source[start..].find('\n').map(|end| start + end + 1))
+    {
+        Some(split) => format!("{}{}{}", &source[..split], defines, &source[split..]),
+        None => format!("{}{}", defines, source),
+    }
+}
+
+/// Compiles and links a program from two GLSL files.
+pub fn load_program(
+    gl: &glow::Context,
+    vertex_path: &str,
+    fragment_path: &str,
+) -> EngineResult<glow::Program> {
+    load_program_variant(gl, vertex_path, fragment_path, &[])
+}
+
+/// Like `load_program` with `#define`s in front of both shaders, for `#ifdef` variants of
+/// the same files.
+pub fn load_program_variant(
+    gl: &glow::Context,
+    vertex_path: &str,
+    fragment_path: &str,
+    defines: &[&str],
+) -> EngineResult<glow::Program> {
+    let vertex = compile_shader(gl, glow::VERTEX_SHADER, vertex_path, defines)?;
+    let fragment = match compile_shader(gl, glow::FRAGMENT_SHADER, fragment_path, defines) {
+        Ok(fragment) => fragment,
+        Err(e) => {
+            unsafe { gl.delete_shader(vertex) };
+            return Err(e);
+        }
+    };
+
+    unsafe {
+        let program = gl.create_program().map_err(EngineError::Gl)?;
+        gl.attach_shader(program, vertex);
+        gl.attach_shader(program, fragment);
+        gl.link_program(program);
+        gl.delete_shader(vertex);
+        gl.delete_shader(fragment);
+
+        if !gl.get_program_link_status(program) {
+            let log = gl.get_program_info_log(program);
+            gl.delete_program(program);
+            return Err(EngineError::ShaderLink {
+                name: format!("{} and {}", vertex_path, fragment_path),
+                log,
+            });
+        }
+
+        gl_debug::check_errors(gl, "link_program");
+
+        Ok(program)
+    }
+}
+
+/// Compiles and links a compute program, needs OpenGL 4.3 or ARB_compute_shader.
+pub fn load_compute_program(gl: &glow::Context, path: &str) -> EngineResult<glow::Program> {
+    let shader = compile_shader(gl, glow::COMPUTE_SHADER, path, &[])?;
+    unsafe {
+        let program = gl.create_program().map_err(EngineError::Gl)?;
+        gl.attach_shader(program, shader);
+        gl.link_program(program);
+        gl.delete_shader(shader);
+
+        if !gl.get_program_link_status(program) {
+            let log = gl.get_program_info_log(program);
+            gl.delete_program(program);
+            return Err(EngineError::ShaderLink {
+                name: path.to_string(),
+                log,
+            });
+        }
+
+        gl_debug::check_errors(gl, "link_program");
+
+        Ok(program)
+    }
+}
@@ -0,0 +1,40 @@
+use cgmath::Deg;
+
+/// Imported children hang off an identity socket with this name on their parent.
+pub const ORIGIN_SOCKET: &str = "Origin";
+
+/// A named attachment point, stored as a local offset relative to its owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Socket {
+    pub name: String,
+
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Vector3<f32>, // In degrees, like the mesh rotation
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Socket {
+    pub fn new<T: ToString>(name: T) -> Self {
+        Self {
+            name: name.to_string(),
+            translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn local_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.translation)
+            * cgmath::Matrix4::from_angle_x(Deg(self.rotation.x))
+            * cgmath::Matrix4::from_angle_y(Deg(self.rotation.y))
+            * cgmath::Matrix4::from_angle_z(Deg(self.rotation.z))
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Links a static mesh to a socket on another static mesh in the same scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub parent: usize, // Index into SceneNode.static_meshes
+    pub socket: String,
+}
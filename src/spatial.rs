@@ -0,0 +1,251 @@
+use cgmath::{Matrix, Matrix4, Vector4};
+
+use crate::raycast::{Aabb, Ray};
+
+// Leaves are padded by this much of their size (and at least FAT_MARGIN_MIN) so small moves
+// don't touch the tree
+const FAT_MARGIN: f32 = 0.1;
+const FAT_MARGIN_MIN: f32 = 0.05;
+
+/// The six planes of a camera's view volume, pointing inwards.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i);
+        Self {
+            planes: [
+                row(3) + row(0), // Left
+                row(3) - row(0), // Right
+                row(3) + row(1), // Bottom
+                row(3) - row(1), // Top
+                row(3) + row(2), // Near
+                row(3) - row(2), // Far
+            ],
+        }
+    }
+
+    /// Conservative, a box near a corner of the frustum can pass without being inside.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal
+            let corner = |axis: usize| match plane[axis] >= 0.0 {
+                true => aabb.max[axis],
+                false => aabb.min[axis],
+            };
+            plane.x * corner(0) + plane.y * corner(1) + plane.z * corner(2) + plane.w >= 0.0
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    Leaf(usize), // The item
+    Branch([usize; 2]),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    aabb: Aabb, // Fat for leaves, the union of both children for branches
+    parent: Option<usize>,
+    kind: NodeKind,
+}
+
+/// A dynamic bounding volume hierarchy over items identified by index, like static meshes.
+/// Items can be inserted, moved and removed one at a time without rebuilding the tree.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: Vec<Option<usize>>, // Item to its leaf node
+}
+
+impl Bvh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.iter().filter(|leaf| leaf.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Moves an item, inserting it if it isn't in the tree yet. Returns whether the tree
+    /// changed, which only happens once the item leaves its padded box.
+    pub fn update(&mut self, item: usize, aabb: Aabb) -> bool {
+        if let Some(Some(leaf)) = self.leaves.get(item) {
+            if self.nodes[*leaf].aabb.contains(&aabb) {
+                return false;
+            }
+            self.remove(item);
+        }
+        self.insert(item, aabb);
+        true
+    }
+
+    pub fn remove(&mut self, item: usize) {
+        let Some(leaf) = self.leaves.get_mut(item).and_then(Option::take) else {
+            return;
+        };
+        self.free_nodes.push(leaf);
+
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return;
+        };
+        self.free_nodes.push(parent);
+
+        // The sibling takes the parent's place
+        let NodeKind::Branch(children) = self.nodes[parent].kind else {
+            unreachable!("a parent is always a branch");
+        };
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(grandparent);
+            }
+            None => self.root = Some(sibling),
+        }
+    }
+
+    /// Drops every item from `len` on, for when the list the items index into gets shorter.
+    pub fn truncate(&mut self, len: usize) {
+        for item in len..self.leaves.len() {
+            self.remove(item);
+        }
+        self.leaves.truncate(len);
+    }
+
+    fn insert(&mut self, item: usize, aabb: Aabb) {
+        let size = aabb.max - aabb.min;
+        let margin = |extent: f32| (extent * FAT_MARGIN).max(FAT_MARGIN_MIN);
+        let fat = Aabb {
+            min: aabb.min - size.map(margin),
+            max: aabb.max + size.map(margin),
+        };
+        let leaf = self.allocate(Node {
+            aabb: fat,
+            parent: None,
+            kind: NodeKind::Leaf(item),
+        });
+        if self.leaves.len() <= item {
+            self.leaves.resize(item + 1, None);
+        }
+        self.leaves[item] = Some(leaf);
+
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            return;
+        };
+
+        // Walk down towards whichever child grows the least by taking the new box
+        let mut sibling = root;
+        while let NodeKind::Branch([left, right]) = self.nodes[sibling].kind {
+            let growth = |node: usize| {
+                let aabb = &self.nodes[node].aabb;
+                aabb.union(&fat).surface_area() - aabb.surface_area()
+            };
+            sibling = if growth(left) <= growth(right) {
+                left
+            } else {
+                right
+            };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.union(&fat),
+            parent: old_parent,
+            kind: NodeKind::Branch([sibling, leaf]),
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(old_parent) => {
+                self.replace_child(old_parent, sibling, parent);
+                self.refit(old_parent);
+            }
+            None => self.root = Some(parent),
+        }
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let NodeKind::Branch(children) = &mut self.nodes[parent].kind {
+            for child in children.iter_mut().filter(|child| **child == old) {
+                *child = new;
+            }
+        }
+    }
+
+    // Shrinks or grows every box from `node` up to the root to fit its children again
+    fn refit(&mut self, node: usize) {
+        let mut current = Some(node);
+        while let Some(index) = current {
+            if let NodeKind::Branch([left, right]) = self.nodes[index].kind {
+                self.nodes[index].aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            }
+            current = self.nodes[index].parent;
+        }
+    }
+
+    /// Every item whose padded box passes `test`, which has to pass for any box that contains
+    /// one that passes.
+    pub fn query<F: Fn(&Aabb) -> bool>(&self, test: F) -> Vec<usize> {
+        let mut items = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.aabb) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf(item) => items.push(item),
+                NodeKind::Branch(children) => stack.extend(children),
+            }
+        }
+        items
+    }
+
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        self.query(|node| node.intersects(aabb))
+    }
+
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<usize> {
+        self.query(|node| frustum.intersects_aabb(node))
+    }
+
+    /// Items the ray might hit within `max_distance`, the exact bounds still need testing.
+    pub fn query_ray(&self, ray: &Ray, max_distance: f32) -> Vec<usize> {
+        self.query(|node| {
+            node.intersect_ray(ray)
+                .is_some_and(|distance| distance <= max_distance)
+        })
+    }
+}
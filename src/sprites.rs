@@ -0,0 +1,520 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use cgmath::Matrix4;
+use glow::HasContext;
+use serde::Deserialize;
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    handles::TextureHandle,
+    loader::AssetLoader,
+    shaders,
+    textures::Texture,
+    viewport::Viewport,
+};
+
+/// A textured quad on the XY plane, the one the orthographic camera looks down on.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub texture: Option<TextureHandle>, // A plain colored quad without one
+    pub position: [f32; 2],             // Center
+    pub size: [f32; 2],
+    pub color: [f32; 4],            // Multiplies the texture
+    pub layer: i32,                 // Higher layers are drawn on top
+    pub sheet: Option<SpriteSheet>, // The whole texture is one frame without one
+    pub frame: usize,
+    pub flipbook: Option<Flipbook>, // Steps `frame` through the sheet
+}
+
+impl Sprite {
+    pub fn new(texture: Option<TextureHandle>) -> Self {
+        Self {
+            texture,
+            position: [0.0, 0.0],
+            size: [1.0, 1.0],
+            color: [1.0; 4],
+            layer: 0,
+            sheet: None,
+            frame: 0,
+            flipbook: None,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        let (Some(sheet), Some(flipbook)) = (&self.sheet, &mut self.flipbook) else {
+            return;
+        };
+        if let Some(frame) = flipbook.advance(delta_time, sheet.frame_count()) {
+            self.frame = frame;
+        }
+    }
+
+    /// The part of the texture to show, (u, v, width, height) with v from the bottom.
+    pub fn uv_rect(&self) -> [f32; 4] {
+        self.sheet
+            .as_ref()
+            .map_or([0.0, 0.0, 1.0, 1.0], |sheet| sheet.frame(self.frame))
+    }
+}
+
+/// How a texture is cut into frames. Frames are numbered left to right, top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpriteSheet {
+    Grid {
+        columns: u32,
+        rows: u32,
+    },
+    // Frames are UV rects like `Sprite::uv_rect`
+    Atlas {
+        path: PathBuf,
+        frames: Vec<[f32; 4]>,
+    },
+}
+
+impl SpriteSheet {
+    /// Reads a TexturePacker style atlas, with "frames" as a map or an array of
+    /// {"frame": {"x", "y", "w", "h"}} in pixels from the top left and "meta": {"size"}.
+    /// Frames of a map are ordered by name.
+    pub fn load_atlas(path: &Path) -> Result<Self, String> {
+        let error = |message: String| format!("Failed to load atlas {:?}: {}", path, message);
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let atlas: AtlasFile = serde_json::from_str(&text).map_err(|e| error(e.to_string()))?;
+        let size = atlas.meta.size;
+        if size.w <= 0.0 || size.h <= 0.0 {
+            return Err(error("the atlas has no size".to_string()));
+        }
+
+        let rects: Vec<AtlasRect> = match atlas.frames {
+            AtlasFrames::Map(frames) => frames.into_values().map(|frame| frame.frame).collect(),
+            AtlasFrames::List(frames) => frames.into_iter().map(|frame| frame.frame).collect(),
+        };
+        // Textures are flipped on load, so v counts from the bottom
+        let frames = rects
+            .iter()
+            .map(|rect| {
+                [
+                    rect.x / size.w,
+                    1.0 - (rect.y + rect.h) / size.h,
+                    rect.w / size.w,
+                    rect.h / size.h,
+                ]
+            })
+            .collect();
+        Ok(SpriteSheet::Atlas {
+            path: path.to_path_buf(),
+            frames,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        match self {
+            SpriteSheet::Grid { columns, rows } => (*columns * *rows) as usize,
+            SpriteSheet::Atlas { frames, .. } => frames.len(),
+        }
+    }
+
+    /// Frames past the end wrap around.
+    pub fn frame(&self, index: usize) -> [f32; 4] {
+        let count = self.frame_count();
+        if count == 0 {
+            return [0.0, 0.0, 1.0, 1.0];
+        }
+        let index = index % count;
+        match self {
+            SpriteSheet::Grid { columns, rows } => {
+                let (width, height) = (1.0 / *columns as f32, 1.0 / *rows as f32);
+                let column = (index % *columns as usize) as f32;
+                let row = (index / *columns as usize) as f32;
+                [column * width, 1.0 - (row + 1.0) * height, width, height]
+            }
+            SpriteSheet::Atlas { frames, .. } => frames[index],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AtlasFile {
+    frames: AtlasFrames,
+    meta: AtlasMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AtlasFrames {
+    Map(BTreeMap<String, AtlasFrame>),
+    List(Vec<AtlasFrame>),
+}
+
+#[derive(Deserialize)]
+struct AtlasFrame {
+    frame: AtlasRect,
+}
+
+#[derive(Deserialize)]
+struct AtlasRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+struct AtlasMeta {
+    size: AtlasSize,
+}
+
+#[derive(Deserialize)]
+struct AtlasSize {
+    w: f32,
+    h: f32,
+}
+
+/// Plays the frames of a sprite's sheet in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flipbook {
+    pub frame_rate: f32, // Frames per second
+    pub looping: bool,   // Otherwise it stops on the last frame
+    pub playing: bool,
+    time: f32,
+}
+
+impl Flipbook {
+    pub fn new(frame_rate: f32) -> Self {
+        Self {
+            frame_rate,
+            looping: true,
+            playing: true,
+            time: 0.0,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    // The frame to show, None while paused
+    fn advance(&mut self, delta_time: f32, frame_count: usize) -> Option<usize> {
+        if !self.playing || frame_count == 0 || self.frame_rate <= 0.0 {
+            return None;
+        }
+        self.time += delta_time;
+        let frame = (self.time * self.frame_rate) as usize;
+        if self.looping {
+            // Kept within one loop so it doesn't lose precision over time
+            self.time %= frame_count as f32 / self.frame_rate;
+            Some(frame % frame_count)
+        } else if frame >= frame_count {
+            self.playing = false;
+            Some(frame_count - 1)
+        } else {
+            Some(frame)
+        }
+    }
+}
+
+const INSTANCE_FLOATS: usize = 12; // Center, size, color and UV rect
+
+/// Draws sprites in layer order, one instanced draw per run of sprites sharing a texture.
+/// Sprites of the same layer are grouped by texture, their order among each other is not kept.
+pub struct SpriteRenderer {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    quad_buffer: glow::Buffer,
+    instance_buffer: glow::Buffer,
+    instance_data: Vec<f32>,
+    white: glow::Texture, // Bound for sprites without a texture
+    textures: HashMap<TextureHandle, Texture>, // Uploaded the first time a sprite uses them
+}
+
+impl SpriteRenderer {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let program =
+            shaders::load_program(gl, "shaders/sprite_vertex.glsl", "shaders/sprite.glsl")?;
+
+        let corners: [f32; 12] = [
+            -0.5, -0.5, 0.5, -0.5, 0.5, 0.5, //
+            -0.5, -0.5, 0.5, 0.5, -0.5, 0.5,
+        ];
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            let quad_buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+            let instance_buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+
+            gl.bind_vertex_array(Some(vertex_array));
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad_buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&corners),
+                glow::STATIC_DRAW,
+            );
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+
+            let stride = (INSTANCE_FLOATS * std::mem::size_of::<f32>()) as i32;
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(instance_buffer));
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, stride, 0);
+            gl.vertex_attrib_divisor(1, 1);
+            gl.enable_vertex_attrib_array(2);
+            gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, 4 * 4);
+            gl.vertex_attrib_divisor(2, 1);
+            gl.enable_vertex_attrib_array(3);
+            gl.vertex_attrib_pointer_f32(3, 4, glow::FLOAT, false, stride, 8 * 4);
+            gl.vertex_attrib_divisor(3, 1);
+
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            let white = gl.create_texture().map_err(EngineError::Gl)?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(white));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA as i32,
+                1,
+                1,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(&[255; 4])),
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                quad_buffer,
+                instance_buffer,
+                instance_data: Vec::new(),
+                white,
+                textures: HashMap::new(),
+            })
+        }
+    }
+
+    /// Draws over whatever is in the viewport, without depth testing.
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        sprites: &[Sprite],
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        // Unloaded textures go, new ones are uploaded
+        self.textures.retain(|handle, texture| {
+            let loaded = asset_loader.loaded_texture_data.contains_key(handle);
+            if !loaded {
+                unsafe { gl.delete_texture(texture.texture) };
+            }
+            loaded
+        });
+        for handle in sprites.iter().filter_map(|sprite| sprite.texture) {
+            if self.textures.contains_key(&handle) {
+                continue;
+            }
+            if let Some(loaded) = asset_loader.loaded_texture_data.get(&handle) {
+                let texture = Texture::from_loaded_data(gl, None, loaded.clone())?;
+                self.textures.insert(handle, texture);
+            }
+        }
+
+        let mut order: Vec<&Sprite> = sprites
+            .iter()
+            .filter(|sprite| match sprite.texture {
+                Some(handle) => self.textures.contains_key(&handle),
+                None => true,
+            })
+            .collect();
+        if order.is_empty() {
+            return Ok(());
+        }
+        order.sort_by_key(|sprite| (sprite.layer, sprite.texture.map(|handle| handle.0)));
+
+        let view_projection: Matrix4<f32> = camera.get_projection() * camera.get_view();
+        let view_projection: &[f32; 16] = view_projection.as_ref();
+
+        unsafe {
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+            gl.use_program(Some(program));
+            gl.uniform_matrix_4_f32_slice(
+                location("view_projection").as_ref(),
+                false,
+                view_projection,
+            );
+            gl.uniform_1_i32(location("image").as_ref(), 0);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_vertex_array(Some(self.vertex_array));
+        }
+
+        for batch in order.chunk_by(|a, b| a.texture == b.texture) {
+            self.instance_data.clear();
+            for sprite in batch {
+                self.instance_data.extend_from_slice(&sprite.position);
+                self.instance_data.extend_from_slice(&sprite.size);
+                self.instance_data.extend_from_slice(&sprite.color);
+                self.instance_data.extend_from_slice(&sprite.uv_rect());
+            }
+
+            let texture = match batch[0].texture {
+                Some(handle) => self.textures[&handle].texture,
+                None => self.white,
+            };
+            unsafe {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instance_buffer));
+                gl.buffer_data_u8_slice(
+                    glow::ARRAY_BUFFER,
+                    bytemuck::cast_slice(&self.instance_data),
+                    glow::STREAM_DRAW,
+                );
+                gl.bind_buffer(glow::ARRAY_BUFFER, None);
+                gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, batch.len() as i32);
+            }
+        }
+
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+            gl.disable(glow::BLEND);
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.quad_buffer);
+            gl.delete_buffer(self.instance_buffer);
+            gl.delete_texture(self.white);
+            for texture in self.textures.values() {
+                gl.delete_texture(texture.texture);
+            }
+        }
+    }
+}
+
+/// The Properties panel of a sprite. `atlas_path` is the text field the atlas is loaded from.
+pub fn sprite_ui(ui: &mut egui::Ui, sprite: &mut Sprite, atlas_path: &mut String) {
+    ui.horizontal(|ui| {
+        ui.label("Position");
+        ui.add(egui::DragValue::new(&mut sprite.position[0]).speed(0.05));
+        ui.add(egui::DragValue::new(&mut sprite.position[1]).speed(0.05));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Size");
+        for value in &mut sprite.size {
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.05)
+                    .range(0.0..=f32::MAX),
+            );
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Color");
+        ui.color_edit_button_rgba_unmultiplied(&mut sprite.color);
+        ui.label("Layer");
+        ui.add(egui::DragValue::new(&mut sprite.layer));
+    });
+
+    ui.heading("Sprite Sheet");
+    ui.horizontal(|ui| {
+        if ui
+            .selectable_label(sprite.sheet.is_none(), "None")
+            .clicked()
+        {
+            sprite.sheet = None;
+        }
+        let grid = matches!(sprite.sheet, Some(SpriteSheet::Grid { .. }));
+        if ui.selectable_label(grid, "Grid").clicked() && !grid {
+            sprite.sheet = Some(SpriteSheet::Grid {
+                columns: 1,
+                rows: 1,
+            });
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Atlas");
+        ui.text_edit_singleline(atlas_path);
+        if ui.button("Load").clicked() {
+            match SpriteSheet::load_atlas(Path::new(atlas_path.as_str())) {
+                Ok(sheet) => sprite.sheet = Some(sheet),
+                Err(e) => log::error!("{}", e),
+            }
+        }
+    });
+    match &mut sprite.sheet {
+        Some(SpriteSheet::Grid { columns, rows }) => {
+            ui.horizontal(|ui| {
+                ui.label("Columns");
+                ui.add(egui::DragValue::new(columns).range(1..=256));
+                ui.label("Rows");
+                ui.add(egui::DragValue::new(rows).range(1..=256));
+            });
+        }
+        Some(SpriteSheet::Atlas { path, frames }) => {
+            ui.label(format!("{} frames from {:?}", frames.len(), path));
+        }
+        None => {}
+    }
+
+    let Some(frame_count) = sprite.sheet.as_ref().map(SpriteSheet::frame_count) else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label("Frame");
+        ui.add(egui::DragValue::new(&mut sprite.frame).range(0..=frame_count.saturating_sub(1)));
+    });
+
+    ui.heading("Flipbook");
+    let mut animated = sprite.flipbook.is_some();
+    if ui.checkbox(&mut animated, "Animated").changed() {
+        sprite.flipbook = animated.then(|| Flipbook::new(12.0));
+    }
+    if let Some(flipbook) = &mut sprite.flipbook {
+        ui.horizontal(|ui| {
+            ui.label("Frame rate");
+            ui.add(
+                egui::DragValue::new(&mut flipbook.frame_rate)
+                    .range(0.0..=120.0)
+                    .suffix(" fps"),
+            );
+            ui.checkbox(&mut flipbook.looping, "Loop");
+        });
+        ui.horizontal(|ui| {
+            let label = if flipbook.playing {
+                "⏸ Pause"
+            } else {
+                "▶ Play"
+            };
+            if ui.button(label).clicked() {
+                flipbook.playing = !flipbook.playing;
+            }
+            if ui.button("⏮ Restart").clicked() {
+                flipbook.restart();
+            }
+        });
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What has to happen for an achievement to unlock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AchievementRequirement {
+    CounterAtLeast(String, i64),
+    Flag(String),
+    Manual, // Only unlocked by calling `Stats::unlock`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub requirement: AchievementRequirement,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Platform services (Steam, console trophies, ...) implement this to mirror progress.
+pub trait StatsBackend: Send {
+    fn counter_changed(&mut self, _name: &str, _value: i64) {}
+    fn flag_changed(&mut self, _name: &str, _value: bool) {}
+    fn achievement_unlocked(&mut self, _id: &str) {}
+}
+
+/// The persistent part of `Stats`, stored inside save games.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub counters: BTreeMap<String, i64>,
+    pub flags: BTreeMap<String, bool>,
+    pub unlocked: BTreeSet<String>,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    state: StatsSnapshot,
+    achievements: Vec<AchievementDefinition>,
+    backends: Vec<Box<dyn StatsBackend>>,
+    newly_unlocked: Vec<String>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define_achievement(&mut self, achievement: AchievementDefinition) {
+        self.achievements.retain(|a| a.id != achievement.id);
+        self.achievements.push(achievement);
+        self.check_achievements();
+    }
+
+    /// Loads a RON file containing a list of achievement definitions.
+    pub fn load_achievements(&mut self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read achievements {:?}: {}", path, e))?;
+        let achievements: Vec<AchievementDefinition> = ron::from_str(&contents)
+            .map_err(|e| format!("Failed to parse achievements {:?}: {}", path, e))?;
+
+        for achievement in achievements {
+            self.define_achievement(achievement);
+        }
+        Ok(())
+    }
+
+    pub fn achievements(&self) -> &[AchievementDefinition] {
+        &self.achievements
+    }
+
+    pub fn add_backend(&mut self, backend: Box<dyn StatsBackend>) {
+        self.backends.push(backend);
+    }
+
+    pub fn counter(&self, name: &str) -> i64 {
+        self.state.counters.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn set_counter(&mut self, name: &str, value: i64) {
+        if self.state.counters.get(name) == Some(&value) {
+            return;
+        }
+
+        self.state.counters.insert(name.to_string(), value);
+        for backend in &mut self.backends {
+            backend.counter_changed(name, value);
+        }
+        self.check_achievements();
+    }
+
+    pub fn increment(&mut self, name: &str, amount: i64) {
+        self.set_counter(name, self.counter(name) + amount);
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.state.flags.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        if self.state.flags.get(name) == Some(&value) {
+            return;
+        }
+
+        self.state.flags.insert(name.to_string(), value);
+        for backend in &mut self.backends {
+            backend.flag_changed(name, value);
+        }
+        self.check_achievements();
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.state.unlocked.contains(id)
+    }
+
+    pub fn unlock(&mut self, id: &str) -> Result<(), String> {
+        if !self.achievements.iter().any(|a| a.id == id) {
+            return Err(format!("Unknown achievement '{}'", id));
+        }
+
+        if self.state.unlocked.insert(id.to_string()) {
+            log::info!("Achievement unlocked: {}", id);
+            for backend in &mut self.backends {
+                backend.achievement_unlocked(id);
+            }
+            self.newly_unlocked.push(id.to_string());
+        }
+        Ok(())
+    }
+
+    /// Achievements unlocked since the last call, for popups.
+    pub fn drain_unlocked(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.newly_unlocked)
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        self.state.clone()
+    }
+
+    /// Replaces all progress, e.g. when a save game is loaded. Backends are not notified.
+    pub fn restore(&mut self, snapshot: StatsSnapshot) {
+        self.state = snapshot;
+        self.newly_unlocked.clear();
+        self.check_achievements();
+    }
+
+    pub fn reset(&mut self) {
+        self.restore(StatsSnapshot::default());
+    }
+
+    fn check_achievements(&mut self) {
+        let ready: Vec<String> = self
+            .achievements
+            .iter()
+            .filter(|a| !self.state.unlocked.contains(&a.id))
+            .filter(|a| match &a.requirement {
+                AchievementRequirement::CounterAtLeast(name, value) => self.counter(name) >= *value,
+                AchievementRequirement::Flag(name) => self.flag(name),
+                AchievementRequirement::Manual => false,
+            })
+            .map(|a| a.id.clone())
+            .collect();
+
+        for id in ready {
+            let _ = self.unlock(&id);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+// Levels loaded next to the open scene, by distance or when asked to
+
+use std::path::{Path, PathBuf};
+
+use cgmath::{MetricSpace, Point3};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    error::EngineResult,
+    handles::AssetHandle,
+    jobs,
+    loader::AssetLoader,
+    scene_file::SceneFile,
+    scene_graph::{camera_frustum, SceneNode},
+    viewport::Viewport,
+};
+
+/// The project's streamed levels, read when a project is opened.
+pub const LEVELS_FILE: &str = "assets/levels.ron";
+
+/// Streams a level in once the viewer is within `load_distance` of `center` and out again past
+/// `unload_distance`. The gap between the two keeps it from loading and unloading every frame
+/// at the edge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamingVolume {
+    pub center: [f32; 3],
+    pub load_distance: f32,
+    pub unload_distance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelDescriptor {
+    pub name: String,
+    pub path: PathBuf, // A scene file
+    #[serde(default)]
+    pub streaming: Option<StreamingVolume>, // Only loaded on request without one
+}
+
+/// Sent from anywhere, the console or a script, and carried out on the next update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelRequest {
+    Load(String),
+    Unload(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelState {
+    Unloaded,
+    Loading,
+    Resident,
+}
+
+enum Residency {
+    Unloaded,
+    Loading(Vec<AssetHandle>, SceneFile), // Waiting for the assets it refers to
+    Resident(Box<SceneNode>),
+}
+
+struct Level {
+    descriptor: LevelDescriptor,
+    residency: Residency,
+    requested: bool, // Loaded or unloaded on request, distance leaves it alone from then on
+}
+
+/// Which levels are resident. Their scenes are drawn over the open one, sharing its depth.
+pub struct LevelStreamer {
+    levels: Vec<Level>,
+    request_tx: Sender<LevelRequest>,
+    request_rx: Receiver<LevelRequest>,
+}
+
+impl Default for LevelStreamer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LevelStreamer {
+    pub fn new() -> Self {
+        let (request_tx, request_rx) = unbounded();
+        Self {
+            levels: Vec::new(),
+            request_tx,
+            request_rx,
+        }
+    }
+
+    /// For whoever wants to load or unload levels without access to the scene graph.
+    pub fn requests(&self) -> Sender<LevelRequest> {
+        self.request_tx.clone()
+    }
+
+    /// Replaces the known levels with the ones in `path`, unloading the resident ones first.
+    pub fn load_descriptors(
+        &mut self,
+        path: &Path,
+        context: &glow::Context,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<(), String> {
+        self.unload_all(context, asset_loader);
+        self.levels.clear();
+        if !path.exists() {
+            return Ok(());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read levels {:?}: {}", path, e))?;
+        let descriptors: Vec<LevelDescriptor> = ron::from_str(&contents)
+            .map_err(|e| format!("Failed to parse levels {:?}: {}", path, e))?;
+        for descriptor in descriptors {
+            self.add_level(descriptor);
+        }
+        Ok(())
+    }
+
+    pub fn add_level(&mut self, descriptor: LevelDescriptor) {
+        self.levels
+            .retain(|level| level.descriptor.name != descriptor.name);
+        self.levels.push(Level {
+            descriptor,
+            residency: Residency::Unloaded,
+            requested: false,
+        });
+    }
+
+    pub fn levels(&self) -> impl Iterator<Item = (&LevelDescriptor, LevelState)> {
+        self.levels.iter().map(|level| {
+            let state = match level.residency {
+                Residency::Unloaded => LevelState::Unloaded,
+                Residency::Loading(..) => LevelState::Loading,
+                Residency::Resident(_) => LevelState::Resident,
+            };
+            (&level.descriptor, state)
+        })
+    }
+
+    pub fn resident(&self) -> impl Iterator<Item = &SceneNode> {
+        self.levels
+            .iter()
+            .filter_map(|level| match &level.residency {
+                Residency::Resident(scene) => Some(scene.as_ref()),
+                _ => None,
+            })
+    }
+
+    pub fn resident_mut(&mut self) -> impl Iterator<Item = &mut SceneNode> {
+        self.levels
+            .iter_mut()
+            .filter_map(|level| match &mut level.residency {
+                Residency::Resident(scene) => Some(scene.as_mut()),
+                _ => None,
+            })
+    }
+
+    /// Starts loading a level, it's resident once its assets are in.
+    pub fn load(&mut self, name: &str, asset_loader: &mut AssetLoader) -> Result<(), String> {
+        let level = self.level_mut(name)?;
+        level.requested = true;
+        begin_loading(level, asset_loader)
+    }
+
+    pub fn unload(
+        &mut self,
+        name: &str,
+        context: &glow::Context,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<(), String> {
+        let level = self.level_mut(name)?;
+        level.requested = true;
+        unload_level(level, context, asset_loader);
+        Ok(())
+    }
+
+    pub fn unload_all(&mut self, context: &glow::Context, asset_loader: &mut AssetLoader) {
+        for level in &mut self.levels {
+            unload_level(level, context, asset_loader);
+            level.requested = false;
+        }
+    }
+
+    fn level_mut(&mut self, name: &str) -> Result<&mut Level, String> {
+        self.levels
+            .iter_mut()
+            .find(|level| level.descriptor.name == name)
+            .ok_or_else(|| format!("There is no level called {}", name))
+    }
+
+    /// Carries out the requests, streams levels by their distance to `viewer` and makes the
+    /// ones whose assets finished loading resident. Call once a frame after polling the loader.
+    pub fn update(
+        &mut self,
+        context: &glow::Context,
+        asset_loader: &mut AssetLoader,
+        viewer: Point3<f32>,
+    ) {
+        while let Ok(request) = self.request_rx.try_recv() {
+            let result = match &request {
+                LevelRequest::Load(name) => self.load(name, asset_loader),
+                LevelRequest::Unload(name) => self.unload(name, context, asset_loader),
+            };
+            if let Err(e) = result {
+                log::error!("{}", e);
+            }
+        }
+
+        for level in &mut self.levels {
+            let Some(volume) = level
+                .descriptor
+                .streaming
+                .as_ref()
+                .filter(|_| !level.requested)
+            else {
+                continue;
+            };
+            let distance = viewer.distance(volume.center.into());
+            match level.residency {
+                Residency::Unloaded if distance <= volume.load_distance => {
+                    if let Err(e) = begin_loading(level, asset_loader) {
+                        log::error!("{}", e);
+                        // Not again every frame
+                        level.requested = true;
+                    }
+                }
+                Residency::Loading(..) | Residency::Resident(_)
+                    if distance > volume.unload_distance =>
+                {
+                    unload_level(level, context, asset_loader)
+                }
+                _ => {}
+            }
+        }
+
+        for level in &mut self.levels {
+            let Residency::Loading(handles, _) = &level.residency else {
+                continue;
+            };
+            let loading = asset_loader
+                .in_flight()
+                .any(|progress| handles.contains(&progress.handle));
+            if loading {
+                continue;
+            }
+            let Residency::Loading(_, file) =
+                std::mem::replace(&mut level.residency, Residency::Unloaded)
+            else {
+                unreachable!()
+            };
+            match SceneNode::new(&level.descriptor.name, context) {
+                Ok(mut scene) => {
+                    file.instantiate(context, &mut scene, asset_loader);
+                    log::info!("Level {} is resident", level.descriptor.name);
+                    level.residency = Residency::Resident(Box::new(scene));
+                }
+                Err(e) => log::error!("Failed to stream in {}: {}", level.descriptor.name, e),
+            }
+        }
+    }
+
+    /// Draws every resident level on top of what was rendered before, with its depth.
+    pub fn render(
+        &mut self,
+        context: &glow::Context,
+        camera: &mut dyn Camera,
+        viewport: &Viewport,
+    ) -> EngineResult<()> {
+        let frustum = camera_frustum(camera);
+        let mut scenes: Vec<&mut SceneNode> = self.resident_mut().collect();
+        for scene in &mut scenes {
+            scene.refresh_spatial_index();
+        }
+
+        // Every level is culled in its own job before any of them is drawn
+        let mut visible = vec![Vec::new(); scenes.len()];
+        jobs::global().scope(|jobs| {
+            for (visible, scene) in visible.iter_mut().zip(&scenes) {
+                let frustum = &frustum;
+                jobs.spawn("cull_level", move || *visible = scene.visible_meshes(frustum));
+            }
+        });
+
+        for (scene, visible) in scenes.iter().zip(&visible) {
+            scene.render_culled(context, camera, viewport, visible)?;
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, context: &glow::Context) {
+        for level in &mut self.levels {
+            if let Residency::Resident(scene) = &mut level.residency {
+                scene.destroy(context);
+            }
+            level.residency = Residency::Unloaded;
+        }
+    }
+}
+
+fn begin_loading(level: &mut Level, asset_loader: &mut AssetLoader) -> Result<(), String> {
+    if !matches!(level.residency, Residency::Unloaded) {
+        return Ok(());
+    }
+    let file = SceneFile::load(&level.descriptor.path)?;
+    let handles = file.request_assets(asset_loader);
+    log::info!("Streaming in {}", level.descriptor.name);
+    level.residency = Residency::Loading(handles, file);
+    Ok(())
+}
+
+fn unload_level(level: &mut Level, context: &glow::Context, asset_loader: &mut AssetLoader) {
+    if let Residency::Resident(scene) = &mut level.residency {
+        scene.release_assets(asset_loader);
+        scene.destroy(context);
+        log::info!("Streamed out {}", level.descriptor.name);
+    }
+    level.residency = Residency::Unloaded;
+}
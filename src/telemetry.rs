@@ -0,0 +1,235 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use serde::Serialize;
+
+use crate::cvars::CVarRegistry;
+
+const BATCH_SIZE: usize = 32;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryEvent {
+    pub name: String,
+    pub session: String,
+    pub timestamp_ms: u64, // Unix time
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
+
+/// Where batches of events end up.
+pub trait TelemetrySink: Send {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<(), String>;
+}
+
+/// Appends one JSON object per line.
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TelemetrySink for FileSink {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {:?}: {}", self.path, e))?;
+
+        for event in events {
+            let line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+            writeln!(file, "{}", line)
+                .map_err(|e| format!("Failed to write {:?}: {}", self.path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// POSTs each batch as a JSON array.
+#[cfg(feature = "telemetry-http")]
+pub struct HttpSink {
+    endpoint: String,
+}
+
+#[cfg(feature = "telemetry-http")]
+impl HttpSink {
+    pub fn new<T: ToString>(endpoint: T) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "telemetry-http")]
+impl TelemetrySink for HttpSink {
+    fn send(&mut self, events: &[TelemetryEvent]) -> Result<(), String> {
+        let body = serde_json::to_string(events).map_err(|e| e.to_string())?;
+        ureq::post(&self.endpoint)
+            .set("Content-Type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .send_string(&body)
+            .map_err(|e| format!("Failed to send telemetry to {}: {}", self.endpoint, e))?;
+        Ok(())
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Opt-in event reporting. Events are batched and written by a background thread,
+/// when telemetry is disabled every call is a no-op.
+pub struct Telemetry {
+    session: String,
+    event_tx: Option<Sender<TelemetryEvent>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Telemetry {
+    pub fn disabled() -> Self {
+        Self {
+            session: String::new(),
+            event_tx: None,
+            worker: None,
+        }
+    }
+
+    pub fn new(mut sink: Box<dyn TelemetrySink>) -> Self {
+        let (event_tx, event_rx) = unbounded::<TelemetryEvent>();
+
+        let worker = std::thread::spawn(move || {
+            let mut batch = Vec::new();
+            let mut last_flush = Instant::now();
+            loop {
+                let disconnected = match event_rx.recv_timeout(FLUSH_INTERVAL) {
+                    Ok(event) => {
+                        batch.push(event);
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+
+                let flush_due = batch.len() >= BATCH_SIZE
+                    || last_flush.elapsed() >= FLUSH_INTERVAL
+                    || disconnected;
+                if flush_due && !batch.is_empty() {
+                    if let Err(e) = sink.send(&batch) {
+                        log::warn!("{}", e);
+                    }
+                    batch.clear();
+                    last_flush = Instant::now();
+                }
+
+                if disconnected {
+                    break;
+                }
+            }
+        });
+
+        // Unique enough to tell play sessions apart without pulling in a uuid crate
+        let session = format!("{:x}-{:x}", unix_time_ms(), std::process::id());
+
+        Self {
+            session,
+            event_tx: Some(event_tx),
+            worker: Some(worker),
+        }
+    }
+
+    /// Reads the `tel_*` cvars, telemetry stays off unless `tel_enabled` is set.
+    pub fn from_cvars(cvars: &CVarRegistry) -> Self {
+        if !cvars.get_bool("tel_enabled") {
+            return Self::disabled();
+        }
+
+        let sink = cvars
+            .get("tel_sink")
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        let endpoint = cvars
+            .get("tel_endpoint")
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+
+        match sink.as_str() {
+            "file" => Self::new(Box::new(FileSink::new(endpoint))),
+            #[cfg(feature = "telemetry-http")]
+            "http" => Self::new(Box::new(HttpSink::new(endpoint))),
+            other => {
+                log::warn!("Telemetry sink '{}' is not available in this build", other);
+                Self::disabled()
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.event_tx.is_some()
+    }
+
+    pub fn event(&self, name: &str, properties: BTreeMap<String, serde_json::Value>) {
+        let Some(event_tx) = &self.event_tx else {
+            return;
+        };
+
+        let _ = event_tx.send(TelemetryEvent {
+            name: name.to_string(),
+            session: self.session.clone(),
+            timestamp_ms: unix_time_ms(),
+            properties,
+        });
+    }
+
+    pub fn session_start(&self) {
+        let mut properties = BTreeMap::new();
+        properties.insert(
+            "engine_version".to_string(),
+            env!("CARGO_PKG_VERSION").into(),
+        );
+        properties.insert("os".to_string(), std::env::consts::OS.into());
+        self.event("session_start", properties);
+    }
+
+    pub fn session_end(&self) {
+        self.event("session_end", BTreeMap::new());
+    }
+
+    pub fn level_loaded(&self, level: &str, load_time: Duration) {
+        let mut properties = BTreeMap::new();
+        properties.insert("level".to_string(), level.into());
+        properties.insert(
+            "load_time_ms".to_string(),
+            (load_time.as_millis() as u64).into(),
+        );
+        self.event("level_loaded", properties);
+    }
+}
+
+impl Drop for Telemetry {
+    // Closes the channel so the worker flushes what is left before the process exits
+    fn drop(&mut self) {
+        self.event_tx = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontArc, GlyphId, PxScale, ScaleFont};
+use cgmath::{Matrix4, Point3, Transform, Vector3};
+use glow::HasContext;
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    shaders,
+    viewport::Viewport,
+};
+
+const FONT_PIXELS: f32 = 48.0; // Glyphs are rasterized at this size, then scaled
+const ATLAS_SIZE: u32 = 512;
+const GLYPH_PADDING: u32 = 2; // Keeps filtering from picking up the neighbours
+const VERTEX_FLOATS: usize = 9; // Position, UV and color
+
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    id: GlyphId,
+    uv: [f32; 4],     // Min and max corner in the atlas
+    bounds: [f32; 4], // Min and max corner in pixels from the pen, y up
+    advance: f32,
+}
+
+/// One glyph of laid out text.
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphQuad {
+    pub rect: [f32; 4], // Min and max corner, in lines
+    pub uv: [f32; 4],
+}
+
+/// The printable ASCII characters of a font rasterized into one texture. Anything else is
+/// drawn as '?'.
+pub struct FontAtlas {
+    font: FontArc,
+    texture: glow::Texture,
+    glyphs: HashMap<char, Glyph>,
+    line_height: f32, // Pixels
+    ascent: f32,
+}
+
+impl FontAtlas {
+    pub fn new(gl: &glow::Context, font: FontArc) -> EngineResult<Self> {
+        let scaled = font.as_scaled(PxScale::from(FONT_PIXELS));
+        let mut pixels = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE) as usize];
+        let mut glyphs = HashMap::new();
+
+        // Packed in rows, each as high as its tallest glyph
+        let (mut x, mut y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        for c in (' '..='~').chain(['?']) {
+            let glyph = scaled.scaled_glyph(c);
+            let id = glyph.id;
+            let advance = scaled.h_advance(id);
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                // Spaces have nothing to draw
+                glyphs.insert(
+                    c,
+                    Glyph {
+                        id,
+                        uv: [0.0; 4],
+                        bounds: [0.0; 4],
+                        advance,
+                    },
+                );
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            let (width, height) = (bounds.width().ceil() as u32, bounds.height().ceil() as u32);
+            if x + width + GLYPH_PADDING > ATLAS_SIZE {
+                x = GLYPH_PADDING;
+                y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            if y + height + GLYPH_PADDING > ATLAS_SIZE {
+                log::warn!("The font atlas is full, {:?} is left out", c);
+                continue;
+            }
+            outlined.draw(|gx, gy, coverage| {
+                let index = ((y + gy) * ATLAS_SIZE + x + gx) as usize;
+                pixels[index] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+            });
+
+            let size = ATLAS_SIZE as f32;
+            glyphs.insert(
+                c,
+                Glyph {
+                    id,
+                    // Rows go down in the pixels, so the top of the glyph has the lower v
+                    uv: [
+                        x as f32 / size,
+                        (y + height) as f32 / size,
+                        (x + width) as f32 / size,
+                        y as f32 / size,
+                    ],
+                    bounds: [bounds.min.x, -bounds.max.y, bounds.max.x, -bounds.min.y],
+                    advance,
+                },
+            );
+            x += width + GLYPH_PADDING;
+            row_height = row_height.max(height);
+        }
+
+        let texture = unsafe {
+            let texture = gl.create_texture().map_err(EngineError::Gl)?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::R8 as i32,
+                ATLAS_SIZE as i32,
+                ATLAS_SIZE as i32,
+                0,
+                glow::RED,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(&pixels)),
+            );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+            for filter in [glow::TEXTURE_MIN_FILTER, glow::TEXTURE_MAG_FILTER] {
+                gl.tex_parameter_i32(glow::TEXTURE_2D, filter, glow::LINEAR as i32);
+            }
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            texture
+        };
+
+        Ok(Self {
+            line_height: scaled.height() + scaled.line_gap(),
+            ascent: scaled.ascent(),
+            font,
+            texture,
+            glyphs,
+        })
+    }
+
+    /// egui's default proportional font, so the engine needs no font file of its own.
+    pub fn default_font() -> EngineResult<FontArc> {
+        let definitions = egui::FontDefinitions::default();
+        let data = definitions
+            .families
+            .get(&egui::FontFamily::Proportional)
+            .and_then(|names| names.first())
+            .and_then(|name| definitions.font_data.get(name))
+            .ok_or_else(|| EngineError::Gl("egui has no default font".to_string()))?;
+        FontArc::try_from_vec(data.font.to_vec()).map_err(|e| EngineError::Gl(e.to_string()))
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Quads of `text` in lines of height 1, the first line's top at y = 0 and lines going
+    /// down. Returns the quads and the width of the widest line.
+    pub fn layout(&self, text: &str) -> (Vec<GlyphQuad>, f32) {
+        let scale = 1.0 / self.line_height;
+        let scaled = self.font.as_scaled(PxScale::from(FONT_PIXELS));
+        let mut quads = Vec::new();
+        let mut widest: f32 = 0.0;
+        for (line_index, line) in text.lines().enumerate() {
+            let baseline = -(self.ascent + line_index as f32 * self.line_height);
+            let mut pen = 0.0;
+            let mut previous: Option<GlyphId> = None;
+            for c in line.chars() {
+                let Some(glyph) = self.glyph(c) else {
+                    continue;
+                };
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, glyph.id);
+                }
+                if glyph.bounds[2] > glyph.bounds[0] {
+                    let [x0, y0, x1, y1] = glyph.bounds;
+                    let rect = [
+                        (pen + x0) * scale,
+                        (baseline + y0) * scale,
+                        (pen + x1) * scale,
+                        (baseline + y1) * scale,
+                    ];
+                    quads.push(GlyphQuad { rect, uv: glyph.uv });
+                }
+                pen += glyph.advance;
+                previous = Some(glyph.id);
+            }
+            widest = widest.max(pen * scale);
+        }
+        (quads, widest)
+    }
+
+    pub fn texture(&self) -> glow::Texture {
+        self.texture
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe { gl.delete_texture(self.texture) };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TextPlacement {
+    Billboard(Point3<f32>), // Centered above the point, always facing the camera
+    World(Matrix4<f32>),    // Flat on the matrix's XY plane, top left at its origin
+}
+
+#[derive(Debug, Clone)]
+pub struct TextItem {
+    pub text: String,
+    pub placement: TextPlacement,
+    pub size: f32, // World units per line
+    pub color: [f32; 4],
+}
+
+/// Text to draw this frame. Debug draws and gameplay add to it, the text pass draws and
+/// clears it.
+#[derive(Debug, Default)]
+pub struct TextQueue {
+    items: Vec<TextItem>,
+}
+
+impl TextQueue {
+    pub fn billboard(&mut self, position: Point3<f32>, text: &str, size: f32, color: [f32; 4]) {
+        self.items.push(TextItem {
+            text: text.to_string(),
+            placement: TextPlacement::Billboard(position),
+            size,
+            color,
+        });
+    }
+
+    pub fn world(&mut self, transform: Matrix4<f32>, text: &str, size: f32, color: [f32; 4]) {
+        self.items.push(TextItem {
+            text: text.to_string(),
+            placement: TextPlacement::World(transform),
+            size,
+            color,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// Draws a `TextQueue` in one call, depth tested against the scene.
+pub struct TextRenderer {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    buffer: glow::Buffer,
+    vertex_data: Vec<f32>,
+    atlas: FontAtlas,
+}
+
+impl TextRenderer {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let atlas = FontAtlas::new(gl, FontAtlas::default_font()?)?;
+        let program = shaders::load_program(gl, "shaders/text_vertex.glsl", "shaders/text.glsl")?;
+
+        unsafe {
+            let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+            let buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            let stride = (VERTEX_FLOATS * std::mem::size_of::<f32>()) as i32;
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, stride, 0);
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_pointer_f32(1, 2, glow::FLOAT, false, stride, 3 * 4);
+            gl.enable_vertex_attrib_array(2);
+            gl.vertex_attrib_pointer_f32(2, 4, glow::FLOAT, false, stride, 5 * 4);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                buffer,
+                vertex_data: Vec::new(),
+                atlas,
+            })
+        }
+    }
+
+    /// Draws everything queued and empties the queue.
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        queue: &mut TextQueue,
+    ) {
+        let view = camera.get_view();
+        // The first two rows of the view matrix are the camera's right and up in world space
+        let right = Vector3::new(view.x.x, view.y.x, view.z.x);
+        let up = Vector3::new(view.x.y, view.y.y, view.z.y);
+
+        self.vertex_data.clear();
+        for item in queue.items.drain(..) {
+            let (quads, width) = self.atlas.layout(&item.text);
+            // Maps a point of the layout to world space
+            let place = |x: f32, y: f32| -> Point3<f32> {
+                match &item.placement {
+                    TextPlacement::Billboard(anchor) => {
+                        let lines = item.text.lines().count().max(1) as f32;
+                        let x = (x - width * 0.5) * item.size;
+                        let y = (y + lines) * item.size;
+                        anchor + right * x + up * y
+                    }
+                    TextPlacement::World(transform) => {
+                        transform.transform_point(Point3::new(x * item.size, y * item.size, 0.0))
+                    }
+                }
+            };
+            for quad in quads {
+                let ([x0, y0, x1, y1], [u0, v0, u1, v1]) = (quad.rect, quad.uv);
+                let corners = [
+                    (place(x0, y0), [u0, v0]),
+                    (place(x1, y0), [u1, v0]),
+                    (place(x1, y1), [u1, v1]),
+                    (place(x0, y0), [u0, v0]),
+                    (place(x1, y1), [u1, v1]),
+                    (place(x0, y1), [u0, v1]),
+                ];
+                for (position, uv) in corners {
+                    self.vertex_data
+                        .extend_from_slice(&[position.x, position.y, position.z]);
+                    self.vertex_data.extend_from_slice(&uv);
+                    self.vertex_data.extend_from_slice(&item.color);
+                }
+            }
+        }
+        if self.vertex_data.is_empty() {
+            return;
+        }
+
+        let view_projection: Matrix4<f32> = camera.get_projection() * view;
+        let view_projection: &[f32; 16] = view_projection.as_ref();
+        unsafe {
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.enable(glow::DEPTH_TEST);
+            gl.depth_mask(false);
+            gl.disable(glow::CULL_FACE); // Flat text is readable from behind, mirrored
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+            gl.use_program(Some(program));
+            gl.uniform_matrix_4_f32_slice(
+                location("view_projection").as_ref(),
+                false,
+                view_projection,
+            );
+            gl.uniform_1_i32(location("atlas").as_ref(), 0);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.atlas.texture));
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&self.vertex_data),
+                glow::STREAM_DRAW,
+            );
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            gl.draw_arrays(
+                glow::TRIANGLES,
+                0,
+                (self.vertex_data.len() / VERTEX_FLOATS) as i32,
+            );
+
+            gl.bind_vertex_array(None);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.use_program(None);
+            gl.depth_mask(true);
+            gl.disable(glow::BLEND);
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        self.atlas.destroy(gl);
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.buffer);
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use glow::HasContext;
 
-use crate::data::LoadedTexture;
+use crate::{
+    data::LoadedTexture,
+    error::{EngineError, EngineResult},
+    gl_debug,
+    upload::{UploadQueue, UploadStatus},
+};
 
 pub struct Texture {
     pub name: String,
@@ -15,9 +20,30 @@ impl Texture {
         context: &glow::Context,
         name: Option<String>,
         data: LoadedTexture,
-    ) -> Self {
+    ) -> EngineResult<Self> {
+        let mut uploads = UploadQueue::new();
+        let texture =
+            Self::from_loaded_data_queued(context, name, data, &mut uploads, &UploadStatus::new())?;
+        uploads.flush(context);
+        Ok(texture)
+    }
+
+    /// Creates the texture right away but leaves the pixels to `uploads`, it can only be
+    /// sampled once `status` is done.
+    #[tracing::instrument(
+        name = "Texture::from_loaded_data",
+        skip_all,
+        fields(width = data.width, height = data.height)
+    )]
+    pub fn from_loaded_data_queued(
+        context: &glow::Context,
+        name: Option<String>,
+        mut data: LoadedTexture,
+        uploads: &mut UploadQueue,
+        status: &UploadStatus,
+    ) -> EngineResult<Self> {
         unsafe {
-            let texture = context.create_texture().unwrap();
+            let texture = context.create_texture().map_err(EngineError::Gl)?;
             context.bind_texture(glow::TEXTURE_2D, Some(texture));
 
             context.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
@@ -33,42 +59,95 @@ impl Texture {
                 glow::LINEAR as i32,
             );
 
-            context.tex_image_2d(
-                glow::TEXTURE_2D,
-                0,
-                glow::RGBA as i32,
-                data.width as i32,
-                data.height as i32,
-                0,
-                glow::RGBA,
-                glow::UNSIGNED_BYTE,
-                glow::PixelUnpackData::Slice(Some(&data.data)),
-            );
+            match data.compressed.take() {
+                Some(compressed) if compressed.format.is_supported(context) => {
+                    // Compressed textures can't generate their own mipmaps, the import made them
+                    context.tex_parameter_i32(
+                        glow::TEXTURE_2D,
+                        glow::TEXTURE_MAX_LEVEL,
+                        compressed.levels.len() as i32 - 1,
+                    );
+                    let internal_format = compressed.format.internal_format().unwrap();
+                    for (i, level) in compressed.levels.into_iter().enumerate() {
+                        uploads.compressed_level(
+                            texture,
+                            i as i32,
+                            (level.width, level.height),
+                            internal_format,
+                            level.data,
+                            status,
+                        );
+                    }
+                }
+                compressed => {
+                    if let Some(compressed) = &compressed {
+                        log::warn!(
+                            "{} compression is not supported, uploading {:?} uncompressed",
+                            compressed.format.name(),
+                            data.path
+                        );
+                    }
+
+                    // Compressed import artifacts don't keep the pixels, decode the source again.
+                    // It was read by the loader, from a pack if the file is in one.
+                    if data.data.is_empty() {
+                        data.data = image::load_from_memory(&data.source)
+                            .map_err(|e| EngineError::Asset {
+                                path: data.path.clone(),
+                                message: e.to_string(),
+                            })?
+                            .flipv()
+                            .to_rgba8()
+                            .into_raw();
+                    }
 
-            context.generate_mipmap(glow::TEXTURE_2D);
+                    // Only the storage here, the queue fills it
+                    context.tex_image_2d(
+                        glow::TEXTURE_2D,
+                        0,
+                        glow::RGBA as i32,
+                        data.width as i32,
+                        data.height as i32,
+                        0,
+                        glow::RGBA,
+                        glow::UNSIGNED_BYTE,
+                        glow::PixelUnpackData::Slice(None),
+                    );
+                    gl_debug::check_errors(context, "tex_image_2d");
+
+                    uploads.texture_rgba(texture, data.width, data.data, true, status);
+                }
+            }
+            context.bind_texture(glow::TEXTURE_2D, None);
 
             let name = match name {
                 Some(n) => n,
                 None => data.name,
             };
 
-            Texture {
+            Ok(Texture {
                 name,
                 texture,
                 width: data.width,
                 height: data.height,
-                data: Some(data.data),
-            }
+                data: None, // Handed to the upload queue
+            })
         }
     }
 
-    fn create_texture(gl: &glow::Context, image_path: &str) -> glow::NativeTexture {
-        let img = image::open(image_path).unwrap().flipv().to_rgba8();
+    fn create_texture(gl: &glow::Context, image_path: &str) -> EngineResult<glow::NativeTexture> {
+        let img = image::open(image_path)
+            .map_err(|e| EngineError::Asset {
+                path: image_path.into(),
+                message: e.to_string(),
+            })?
+            .flipv()
+            .to_rgba8();
         let (width, height) = img.dimensions();
         let data = img.into_raw();
 
         unsafe {
-            let texture = gl.create_texture().unwrap();
+            let texture = gl.create_texture().map_err(EngineError::Gl)?;
             gl.bind_texture(glow::TEXTURE_2D, Some(texture));
 
             gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
@@ -96,9 +175,12 @@ impl Texture {
                 glow::PixelUnpackData::Slice(Some(&data)),
             );
 
+            gl_debug::check_errors(gl, "tex_image_2d");
+
             gl.generate_mipmap(glow::TEXTURE_2D);
+            gl_debug::check_errors(gl, "generate_mipmap");
 
-            texture
+            Ok(texture)
         }
     }
 }
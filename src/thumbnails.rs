@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use glow::HasContext;
+
+use cruel_game_engine::{
+    camera::{Camera, PerspectiveCamera},
+    handles::{AssetHandle, MeshHandle},
+    import::{content_hash, IMPORT_CACHE_DIRECTORY},
+    loader::{AssetLoader, AssetPriority},
+    scene_graph::SceneNode,
+    viewport::Viewport,
+};
+
+use crate::{content_browser::AssetKind, headless::OffscreenTarget};
+
+const THUMBNAIL_SIZE: u32 = 96;
+
+// Named after a hash of the file, so an edited asset gets a new thumbnail
+fn cache_path(bytes: &[u8]) -> PathBuf {
+    Path::new(IMPORT_CACHE_DIRECTORY)
+        .join("thumbnails")
+        .join(format!("{:016x}.png", content_hash(bytes)))
+}
+
+fn color_image(image: &image::RgbaImage) -> egui::ColorImage {
+    egui::ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    )
+}
+
+fn save_thumbnail(image: &image::RgbaImage, path: &Path) {
+    if let Some(directory) = path.parent() {
+        let _ = std::fs::create_dir_all(directory);
+    }
+    if let Err(e) = image.save(path) {
+        log::warn!("Failed to write thumbnail {:?}: {}", path, e);
+    }
+}
+
+enum ThumbnailResult {
+    Ready(PathBuf, egui::ColorImage),
+    NeedsRender(PathBuf, PathBuf), // A mesh without a cached thumbnail, and where to cache it
+    Failed,
+}
+
+// Off the main thread, everything but the mesh renders
+fn find_or_make(path: PathBuf, kind: AssetKind) -> ThumbnailResult {
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(_) => return ThumbnailResult::Failed,
+    };
+    let cached = cache_path(&bytes);
+    if let Ok(image) = image::open(&cached) {
+        return ThumbnailResult::Ready(path, color_image(&image.to_rgba8()));
+    }
+
+    match kind {
+        AssetKind::Texture => match image::load_from_memory(&bytes) {
+            Ok(image) => {
+                let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8();
+                save_thumbnail(&thumbnail, &cached);
+                ThumbnailResult::Ready(path, color_image(&thumbnail))
+            }
+            Err(_) => ThumbnailResult::Failed,
+        },
+        AssetKind::Mesh => ThumbnailResult::NeedsRender(path, cached),
+        _ => ThumbnailResult::Failed,
+    }
+}
+
+/// Renders one mesh at a time into a small offscreen target, the same way `--headless` does.
+struct MeshRenderer {
+    scene: SceneNode,
+    target: OffscreenTarget,
+    camera: PerspectiveCamera,
+}
+
+impl MeshRenderer {
+    fn new(gl: &glow::Context) -> Result<Self, String> {
+        let scene = SceneNode::new("Thumbnail Scene", gl)?;
+        let target = OffscreenTarget::new(gl, THUMBNAIL_SIZE, THUMBNAIL_SIZE)?;
+        let camera = PerspectiveCamera::new(
+            "Thumbnail Camera".to_string(),
+            Point3::new(0.0, 0.0, 3.0),
+            40.0,
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+            1.0,
+            0.01,
+            1000.0,
+            0.0,
+            0.0,
+        );
+        Ok(Self {
+            scene,
+            target,
+            camera,
+        })
+    }
+
+    fn render(
+        &mut self,
+        gl: &glow::Context,
+        handle: MeshHandle,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<image::RgbaImage, String> {
+        self.scene
+            .add_mesh_asset(gl, "Thumbnail".to_string(), handle, asset_loader)?;
+        asset_loader.uploads.get_mut().unwrap().flush(gl);
+
+        // Looks at the whole mesh from above and to the side
+        let bounds = (0..self.scene.static_meshes.len())
+            .filter_map(|i| self.scene.static_mesh_world_bounds(i))
+            .reduce(|a, b| a.union(&b));
+        let (center, radius) = match bounds {
+            Some(bounds) => (bounds.center(), (bounds.max - bounds.min).magnitude() / 2.0),
+            None => (Point3::origin(), 1.0),
+        };
+        let direction = Vector3::new(1.0, 0.7, 1.0).normalize();
+        self.camera
+            .set_position(center + direction * radius.max(0.01) * 3.0);
+        self.camera.set_orientation(-direction);
+
+        let viewport = Viewport::new(0, 0, THUMBNAIL_SIZE as i32, THUMBNAIL_SIZE as i32);
+        let mut previous_viewport = [0; 4];
+        unsafe {
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut previous_viewport);
+            self.target.bind(gl);
+            gl.clear_color(0.18, 0.18, 0.2, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+        }
+
+        self.scene.update(&mut self.camera);
+        let rendered = self.scene.render(gl, &mut self.camera, &viewport);
+        let image = self.target.read_image(gl);
+
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.viewport(
+                previous_viewport[0],
+                previous_viewport[1],
+                previous_viewport[2],
+                previous_viewport[3],
+            );
+        }
+        while !self.scene.static_meshes.is_empty() {
+            self.scene.remove_static_mesh(gl, 0);
+        }
+
+        rendered?;
+        Ok(image)
+    }
+
+    fn destroy(&mut self, gl: &glow::Context) {
+        self.scene.destroy(gl);
+        self.target.destroy(gl);
+    }
+}
+
+// A mesh loaded only for its thumbnail is unloaded again afterwards
+struct PendingMesh {
+    path: PathBuf,
+    cache: PathBuf,
+    handle: MeshHandle,
+    was_loaded: bool,
+}
+
+/// Thumbnails for the content browser. Texture ones are scaled down on a worker, mesh ones
+/// rendered on the main thread one per frame. Both are cached on disk.
+pub struct Thumbnails {
+    textures: HashMap<PathBuf, Option<egui::TextureHandle>>, // None while making it or when there is none
+    result_tx: Sender<ThumbnailResult>,
+    result_rx: Receiver<ThumbnailResult>,
+    mesh_queue: VecDeque<(PathBuf, PathBuf)>,
+    pending_mesh: Option<PendingMesh>,
+    renderer: Option<MeshRenderer>, // Created with the first mesh thumbnail
+}
+
+impl Thumbnails {
+    pub fn new() -> Self {
+        let (result_tx, result_rx) = unbounded();
+        Self {
+            textures: HashMap::new(),
+            result_tx,
+            result_rx,
+            mesh_queue: VecDeque::new(),
+            pending_mesh: None,
+            renderer: None,
+        }
+    }
+
+    /// The thumbnail for a file, starts making it the first time it's asked for.
+    pub fn get(&mut self, path: &Path, kind: AssetKind) -> Option<&egui::TextureHandle> {
+        if !self.textures.contains_key(path) {
+            self.textures.insert(path.to_path_buf(), None);
+            if matches!(kind, AssetKind::Texture | AssetKind::Mesh) {
+                let path = path.to_path_buf();
+                let result_tx = self.result_tx.clone();
+                rayon::spawn(move || {
+                    let _ = result_tx.send(find_or_make(path, kind));
+                });
+            }
+        }
+        self.textures.get(path).and_then(|texture| texture.as_ref())
+    }
+
+    /// Forgets every thumbnail, after the working directory changed.
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.mesh_queue.clear();
+    }
+
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        gl: &glow::Context,
+        asset_loader: &mut AssetLoader,
+    ) {
+        while let Ok(result) = self.result_rx.try_recv() {
+            match result {
+                ThumbnailResult::Ready(path, image) => self.set(ctx, path, image),
+                ThumbnailResult::NeedsRender(path, cache) => {
+                    self.mesh_queue.push_back((path, cache))
+                }
+                ThumbnailResult::Failed => {}
+            }
+        }
+
+        if self.pending_mesh.is_none() {
+            if let Some((path, cache)) = self.mesh_queue.pop_front() {
+                let was_loaded = asset_loader
+                    .loaded_mesh_data
+                    .values()
+                    .any(|mesh| mesh.path == path);
+                let name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                let handle =
+                    asset_loader.request_mesh_with_priority(&path, name, AssetPriority::Background);
+                self.pending_mesh = Some(PendingMesh {
+                    path,
+                    cache,
+                    handle,
+                    was_loaded,
+                });
+            }
+        }
+
+        let Some(pending) = self.pending_mesh.take() else {
+            return;
+        };
+        let handle = AssetHandle::Mesh(pending.handle);
+        if !asset_loader.loaded_mesh_data.contains_key(&pending.handle) {
+            // Still loading, or it failed and there's nothing to render
+            if asset_loader
+                .in_flight()
+                .any(|progress| progress.handle == handle)
+            {
+                self.pending_mesh = Some(pending);
+            }
+            return;
+        }
+
+        if self.renderer.is_none() {
+            match MeshRenderer::new(gl) {
+                Ok(renderer) => self.renderer = Some(renderer),
+                Err(e) => {
+                    log::error!("Failed to create the thumbnail renderer: {}", e);
+                    self.mesh_queue.clear();
+                    return;
+                }
+            }
+        }
+        let renderer = self.renderer.as_mut().unwrap();
+        match renderer.render(gl, pending.handle, asset_loader) {
+            Ok(image) => {
+                self.set(ctx, pending.path.clone(), color_image(&image));
+                let cache = pending.cache.clone();
+                rayon::spawn(move || save_thumbnail(&image, &cache));
+            }
+            Err(e) => log::warn!("Failed to render a thumbnail of {:?}: {}", pending.path, e),
+        }
+        if !pending.was_loaded {
+            asset_loader.unload(handle);
+        }
+    }
+
+    fn set(&mut self, ctx: &egui::Context, path: PathBuf, image: egui::ColorImage) {
+        let name = format!("Thumbnail {}", path.to_string_lossy());
+        let texture = ctx.load_texture(name, image, egui::TextureOptions::LINEAR);
+        self.textures.insert(path, Some(texture));
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(mut renderer) = self.renderer.take() {
+            renderer.destroy(gl);
+        }
+    }
+}
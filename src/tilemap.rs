@@ -0,0 +1,402 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use cgmath::{Matrix4, Point3};
+use glow::HasContext;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    error::{EngineError, EngineResult},
+    handles::{AssetHandle, TextureHandle},
+    loader::AssetLoader,
+    raycast::{Aabb, Ray},
+    shaders,
+    spatial::Frustum,
+    sprites::SpriteSheet,
+    textures::Texture,
+    viewport::Viewport,
+};
+
+pub const TILEMAP_DIRECTORY: &str = "assets/tilemaps";
+
+// Tiles along each side of a chunk, a painted tile only rebuilds its own chunk
+const CHUNK_SIZE: u32 = 16;
+const VERTEX_FLOATS: usize = 4; // Position and UV
+
+/// A grid of tiles on the XY plane, each an index into the frames of an atlas texture cut
+/// into a grid like `SpriteSheet::Grid`. Saved as RON under `TILEMAP_DIRECTORY`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tilemap {
+    pub name: String,
+    pub atlas: PathBuf,
+    pub atlas_columns: u32,
+    pub atlas_rows: u32,
+    pub origin: [f32; 2], // Lower left corner of tile (0, 0)
+    pub tile_size: f32,
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<u32>>, // Row major from the bottom row, None is empty
+
+    #[serde(skip)]
+    pub texture: Option<TextureHandle>, // Set once the atlas is requested, see `request_atlas`
+    #[serde(skip)]
+    chunks: HashMap<(u32, u32), TileChunk>,
+    #[serde(skip)]
+    dirty: HashSet<(u32, u32)>,
+}
+
+#[derive(Debug)]
+struct TileChunk {
+    vertex_array: glow::VertexArray,
+    buffer: glow::Buffer,
+    vertex_count: i32,
+}
+
+impl Tilemap {
+    pub fn new(name: String, atlas: PathBuf, width: u32, height: u32) -> Self {
+        Self {
+            name,
+            atlas,
+            atlas_columns: 8,
+            atlas_rows: 8,
+            origin: [0.0, 0.0],
+            tile_size: 1.0,
+            width,
+            height,
+            tiles: vec![None; (width * height) as usize],
+            texture: None,
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tilemap {:?}: {}", path, e))?;
+        let tilemap: Self = ron::from_str(&contents)
+            .map_err(|e| format!("Failed to parse tilemap {:?}: {}", path, e))?;
+        if tilemap.tiles.len() != (tilemap.width * tilemap.height) as usize {
+            return Err(format!(
+                "Failed to load tilemap {:?}: {} tiles for a {}x{} map",
+                path,
+                tilemap.tiles.len(),
+                tilemap.width,
+                tilemap.height
+            ));
+        }
+        Ok(tilemap)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize tilemap: {}", e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write tilemap {:?}: {}", path, e))
+    }
+
+    /// Requests the atlas texture and keeps it loaded, `release_atlas` gives it back.
+    pub fn request_atlas(&mut self, asset_loader: &mut AssetLoader) {
+        if self.texture.is_some() {
+            return;
+        }
+        let name = self
+            .atlas
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let handle = asset_loader.request_texture(&self.atlas, name);
+        asset_loader.retain(AssetHandle::Texture(handle));
+        self.texture = Some(handle);
+    }
+
+    pub fn release_atlas(&mut self, asset_loader: &mut AssetLoader) {
+        if let Some(handle) = self.texture.take() {
+            asset_loader.release(AssetHandle::Texture(handle));
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.atlas_columns * self.atlas_rows
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Returns whether the tile changed.
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<u32>) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let slot = &mut self.tiles[(y * self.width + x) as usize];
+        if *slot == tile {
+            return false;
+        }
+        *slot = tile;
+        self.dirty.insert((x / CHUNK_SIZE, y / CHUNK_SIZE));
+        true
+    }
+
+    /// Rebuilds every chunk, for after the atlas layout, origin or tile size changed.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.extend(self.chunks.keys().copied());
+    }
+
+    /// The tile under a point on the XY plane.
+    pub fn tile_at(&self, x: f32, y: f32) -> Option<(u32, u32)> {
+        let column = ((x - self.origin[0]) / self.tile_size).floor();
+        let row = ((y - self.origin[1]) / self.tile_size).floor();
+        if column < 0.0 || row < 0.0 || column >= self.width as f32 || row >= self.height as f32 {
+            return None;
+        }
+        Some((column as u32, row as u32))
+    }
+
+    /// The tile a ray through the viewport points at.
+    pub fn tile_under_ray(&self, ray: &Ray) -> Option<(u32, u32)> {
+        if ray.direction.z.abs() < 1e-6 {
+            return None;
+        }
+        let distance = -ray.origin.z / ray.direction.z;
+        if distance < 0.0 {
+            return None;
+        }
+        let point = ray.at(distance);
+        self.tile_at(point.x, point.y)
+    }
+
+    fn chunk_bounds(&self, (cx, cy): (u32, u32)) -> Aabb {
+        let chunk = CHUNK_SIZE as f32 * self.tile_size;
+        let x = self.origin[0] + cx as f32 * chunk;
+        let y = self.origin[1] + cy as f32 * chunk;
+        Aabb {
+            min: Point3::new(x, y, 0.0),
+            max: Point3::new(x + chunk, y + chunk, 0.0),
+        }
+    }
+
+    fn chunk_vertices(&self, (cx, cy): (u32, u32)) -> Vec<f32> {
+        let sheet = SpriteSheet::Grid {
+            columns: self.atlas_columns.max(1),
+            rows: self.atlas_rows.max(1),
+        };
+        let mut vertices = Vec::new();
+        for y in cy * CHUNK_SIZE..((cy + 1) * CHUNK_SIZE).min(self.height) {
+            for x in cx * CHUNK_SIZE..((cx + 1) * CHUNK_SIZE).min(self.width) {
+                let Some(tile) = self.tile(x, y) else {
+                    continue;
+                };
+                let [u, v, w, h] = sheet.frame(tile as usize);
+                let x0 = self.origin[0] + x as f32 * self.tile_size;
+                let y0 = self.origin[1] + y as f32 * self.tile_size;
+                let (x1, y1) = (x0 + self.tile_size, y0 + self.tile_size);
+                #[rustfmt::skip]
+                vertices.extend_from_slice(&[
+                    x0, y0, u, v,
+                    x1, y0, u + w, v,
+                    x1, y1, u + w, v + h,
+                    x0, y0, u, v,
+                    x1, y1, u + w, v + h,
+                    x0, y1, u, v + h,
+                ]);
+            }
+        }
+        vertices
+    }
+
+    fn build_chunk(&mut self, gl: &glow::Context, key: (u32, u32)) -> EngineResult<()> {
+        let vertices = self.chunk_vertices(key);
+        let chunk = match self.chunks.remove(&key) {
+            Some(chunk) => chunk,
+            None => unsafe {
+                let vertex_array = gl.create_vertex_array().map_err(EngineError::Gl)?;
+                let buffer = gl.create_buffer().map_err(EngineError::Gl)?;
+                gl.bind_vertex_array(Some(vertex_array));
+                gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+                let stride = (VERTEX_FLOATS * std::mem::size_of::<f32>()) as i32;
+                gl.enable_vertex_attrib_array(0);
+                gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, stride, 0);
+                gl.enable_vertex_attrib_array(1);
+                gl.vertex_attrib_pointer_f32(1, 2, glow::FLOAT, false, stride, 2 * 4);
+                gl.bind_vertex_array(None);
+                TileChunk {
+                    vertex_array,
+                    buffer,
+                    vertex_count: 0,
+                }
+            },
+        };
+        unsafe {
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(chunk.buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&vertices),
+                glow::STATIC_DRAW,
+            );
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+        self.chunks.insert(
+            key,
+            TileChunk {
+                vertex_count: (vertices.len() / VERTEX_FLOATS) as i32,
+                ..chunk
+            },
+        );
+        Ok(())
+    }
+
+    // Builds missing and dirty chunks in view, then draws them with the bound program
+    fn render(&mut self, gl: &glow::Context, frustum: &Frustum) -> EngineResult<()> {
+        let chunks_x = self.width.div_ceil(CHUNK_SIZE);
+        let chunks_y = self.height.div_ceil(CHUNK_SIZE);
+        for cy in 0..chunks_y {
+            for cx in 0..chunks_x {
+                let key = (cx, cy);
+                if !frustum.intersects_aabb(&self.chunk_bounds(key)) {
+                    continue;
+                }
+                if self.dirty.remove(&key) || !self.chunks.contains_key(&key) {
+                    self.build_chunk(gl, key)?;
+                }
+                let chunk = &self.chunks[&key];
+                if chunk.vertex_count > 0 {
+                    unsafe {
+                        gl.bind_vertex_array(Some(chunk.vertex_array));
+                        gl.draw_arrays(glow::TRIANGLES, 0, chunk.vertex_count);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        for (_, chunk) in self.chunks.drain() {
+            unsafe {
+                gl.delete_vertex_array(chunk.vertex_array);
+                gl.delete_buffer(chunk.buffer);
+            }
+        }
+    }
+}
+
+/// Draws the tilemaps of a scene below its sprites, chunks outside the camera are skipped.
+pub struct TilemapRenderer {
+    program: glow::Program,
+    textures: HashMap<TextureHandle, Texture>, // Uploaded the first time a tilemap uses them
+}
+
+impl TilemapRenderer {
+    pub fn new(gl: &glow::Context) -> EngineResult<Self> {
+        let program =
+            shaders::load_program(gl, "shaders/tilemap_vertex.glsl", "shaders/tilemap.glsl")?;
+        Ok(Self {
+            program,
+            textures: HashMap::new(),
+        })
+    }
+
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        viewport: &Viewport,
+        camera: &dyn Camera,
+        tilemaps: &mut [Tilemap],
+        asset_loader: &AssetLoader,
+    ) -> EngineResult<()> {
+        self.textures.retain(|handle, texture| {
+            let loaded = asset_loader.loaded_texture_data.contains_key(handle);
+            if !loaded {
+                unsafe { gl.delete_texture(texture.texture) };
+            }
+            loaded
+        });
+
+        let view_projection: Matrix4<f32> = camera.get_projection() * camera.get_view();
+        let frustum = Frustum::from_matrix(&view_projection);
+        let view_projection: &[f32; 16] = view_projection.as_ref();
+
+        unsafe {
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::CULL_FACE);
+
+            let program = self.program;
+            let location = |name: &str| gl.get_uniform_location(program, name);
+            gl.use_program(Some(program));
+            gl.uniform_matrix_4_f32_slice(
+                location("view_projection").as_ref(),
+                false,
+                view_projection,
+            );
+            gl.uniform_1_i32(location("atlas").as_ref(), 0);
+            gl.active_texture(glow::TEXTURE0);
+        }
+
+        for tilemap in tilemaps {
+            // Drawn once the atlas is loaded
+            let Some(handle) = tilemap.texture else {
+                continue;
+            };
+            let texture = match self.textures.entry(handle) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let Some(loaded) = asset_loader.loaded_texture_data.get(&handle) else {
+                        continue;
+                    };
+                    let texture = Texture::from_loaded_data(gl, None, loaded.clone())?;
+                    // Neighbouring tiles of the atlas would bleed in with filtering
+                    unsafe {
+                        gl.bind_texture(glow::TEXTURE_2D, Some(texture.texture));
+                        for filter in [glow::TEXTURE_MIN_FILTER, glow::TEXTURE_MAG_FILTER] {
+                            gl.tex_parameter_i32(glow::TEXTURE_2D, filter, glow::NEAREST as i32);
+                        }
+                    }
+                    entry.insert(texture)
+                }
+            };
+            unsafe {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture.texture));
+            }
+            tilemap.render(gl, &frustum)?;
+        }
+
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+            gl.disable(glow::BLEND);
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            for texture in self.textures.values() {
+                gl.delete_texture(texture.texture);
+            }
+        }
+    }
+}
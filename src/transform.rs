@@ -0,0 +1,248 @@
+use cgmath::{
+    Deg, ElementWise, EuclideanSpace, Euler, InnerSpace, Matrix3, Matrix4, Quaternion, Rotation,
+    Rotation3, Vector3,
+};
+
+use crate::{data::LoadedNode, mesh::StaticMesh, scene_graph::SceneNode};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl MeshTransform {
+    pub fn from_mesh(mesh: &StaticMesh) -> Self {
+        Self {
+            translation: mesh.translation,
+            rotation: mesh.rotation,
+            scale: mesh.scale,
+        }
+    }
+
+    /// The transform of the static mesh at `index`, None if it doesn't exist.
+    pub fn of(scene: &SceneNode, index: usize) -> Option<Self> {
+        scene.static_meshes.get(index).map(Self::from_mesh)
+    }
+
+    /// The local transform of an imported node.
+    pub fn from_node(node: &LoadedNode) -> Self {
+        let [x, y, z, w] = node.rotation;
+        let euler = Euler::from(Quaternion::new(w, x, y, z));
+        Self {
+            translation: node.translation.into(),
+            rotation: Vector3::new(
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ),
+            scale: node.scale.into(),
+        }
+    }
+
+    /// Splits a model matrix back up. Shear, from a parent scaled unevenly, is lost.
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let scale = Vector3::new(
+            matrix.x.truncate().magnitude(),
+            matrix.y.truncate().magnitude(),
+            matrix.z.truncate().magnitude(),
+        );
+        // Zero scale has no rotation left to find
+        let axis = |column: Vector3<f32>, length: f32| match length > f32::EPSILON {
+            true => column / length,
+            false => column,
+        };
+        let rotation = Matrix3::from_cols(
+            axis(matrix.x.truncate(), scale.x),
+            axis(matrix.y.truncate(), scale.y),
+            axis(matrix.z.truncate(), scale.z),
+        );
+        let euler = Euler::from(Quaternion::from(rotation));
+        Self {
+            translation: matrix.w.truncate(),
+            rotation: Vector3::new(
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ),
+            scale,
+        }
+    }
+
+    pub fn apply_to(&self, mesh: &mut StaticMesh) {
+        mesh.translation = self.translation;
+        mesh.rotation = self.rotation;
+        mesh.scale = self.scale;
+    }
+
+    // Same order as StaticMesh::model_matrix, X then Y then Z
+    pub fn orientation(&self) -> Quaternion<f32> {
+        Quaternion::from(Euler::new(
+            Deg(self.rotation.x),
+            Deg(self.rotation.y),
+            Deg(self.rotation.z),
+        ))
+    }
+
+    /// Moved `amount` units along `axis` (0 to 2) of `space`.
+    pub fn translated(&self, space: GizmoSpace, axis: usize, amount: f32) -> Self {
+        Self {
+            translation: self.translation + space.axis(self, axis) * amount,
+            ..*self
+        }
+    }
+
+    /// Turned `degrees` around `axis` (0 to 2) of `space`.
+    pub fn rotated(&self, space: GizmoSpace, axis: usize, degrees: f32) -> Self {
+        self.turned(space.axis(self, axis), degrees)
+    }
+
+    /// `after` moved so the mesh space `pivot` stays where it is with `self`, for rotating
+    /// and scaling around something other than the origin.
+    pub fn around(&self, after: MeshTransform, pivot: Vector3<f32>) -> Self {
+        MeshTransform {
+            translation: after.translation + self.offset(pivot) - after.offset(pivot),
+            ..after
+        }
+    }
+
+    /// Where a mesh space point is, in the space `translation` is in.
+    pub fn point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.translation + self.offset(point)
+    }
+
+    // Where a mesh space point ends up relative to the origin
+    fn offset(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.orientation()
+            .rotate_vector(point.mul_element_wise(self.scale))
+    }
+
+    /// Turned `degrees` around a world space direction.
+    pub fn turned(&self, direction: Vector3<f32>, degrees: f32) -> Self {
+        let turn = Quaternion::from_axis_angle(direction.normalize(), Deg(degrees));
+        let euler = Euler::from(turn * self.orientation());
+        Self {
+            rotation: Vector3::new(
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ),
+            ..*self
+        }
+    }
+}
+
+/// Which axes gizmos and modal transforms move along. Scale is always local,
+/// a rotated object can't be stretched along world axes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GizmoSpace {
+    #[default]
+    World,
+    Local, // The object's own axes, following its rotation
+}
+
+impl GizmoSpace {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GizmoSpace::World => "🌐 World",
+            GizmoSpace::Local => "📦 Local",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            GizmoSpace::World => GizmoSpace::Local,
+            GizmoSpace::Local => GizmoSpace::World,
+        }
+    }
+
+    /// Unit direction of `axis` (0 to 2) for an object with `transform`.
+    pub fn axis(&self, transform: &MeshTransform, axis: usize) -> Vector3<f32> {
+        let world = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()][axis];
+        match self {
+            GizmoSpace::World => world,
+            GizmoSpace::Local => transform.orientation().rotate_vector(world),
+        }
+    }
+}
+
+/// What modal transforms rotate and scale each selected object around.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Pivot {
+    #[default]
+    Origin,
+    BoundsCenter,
+    BoundsBottom,         // Keeps things standing on the floor while they're scaled
+    Custom(Vector3<f32>), // Offset from the origin in the mesh's own space
+}
+
+impl Pivot {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Pivot::Origin => "Origin",
+            Pivot::BoundsCenter => "Bounds Center",
+            Pivot::BoundsBottom => "Bounds Bottom",
+            Pivot::Custom(_) => "Custom",
+        }
+    }
+
+    /// The pivot in `mesh`'s own space, the origin for meshes without vertices.
+    pub fn point(&self, mesh: &StaticMesh) -> Vector3<f32> {
+        match (self, mesh.bounds) {
+            (Pivot::Custom(offset), _) => *offset,
+            (Pivot::BoundsCenter, Some(bounds)) => bounds.center().to_vec(),
+            (Pivot::BoundsBottom, Some(bounds)) => {
+                let center = bounds.center();
+                Vector3::new(center.x, bounds.min.y, center.z)
+            }
+            _ => Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Per object editing locks. Every tool that moves an object (the Properties panel, gizmos)
+/// runs its result through `constrain` so the locks hold no matter how the edit was made.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransformConstraints {
+    pub lock_translation: [bool; 3],
+    pub lock_rotation: [bool; 3],
+    pub lock_scale: [bool; 3],
+    pub uniform_scale: bool, // Scaling one axis scales the others by the same factor
+}
+
+impl TransformConstraints {
+    /// `after` with locked components put back to `before` and the scale kept uniform.
+    pub fn constrain(&self, before: MeshTransform, after: MeshTransform) -> MeshTransform {
+        let mut result = after;
+
+        if self.uniform_scale {
+            // Follow the first axis that changed
+            if let Some(axis) = (0..3)
+                .find(|&axis| after.scale[axis] != before.scale[axis] && !self.lock_scale[axis])
+            {
+                for other in 0..3 {
+                    result.scale[other] = if before.scale[axis] != 0.0 {
+                        before.scale[other] * after.scale[axis] / before.scale[axis]
+                    } else {
+                        after.scale[axis] // Nothing to scale from, copy the value instead
+                    };
+                }
+            }
+        }
+
+        for axis in 0..3 {
+            if self.lock_translation[axis] {
+                result.translation[axis] = before.translation[axis];
+            }
+            if self.lock_rotation[axis] {
+                result.rotation[axis] = before.rotation[axis];
+            }
+            if self.lock_scale[axis] {
+                result.scale[axis] = before.scale[axis];
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+pub const TUTORIAL_DIRECTORY: &str = "assets/tutorials";
+
+/// What moves a tutorial on to its next step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StepTrigger {
+    Next,          // The player presses "Next"
+    Event(String), // A named event passed to `TutorialOverlay::notify`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TutorialStep {
+    pub title: String,
+    pub text: String,
+    #[serde(default)]
+    pub highlight: Option<String>, // Name of a region registered with `register_region`
+    pub trigger: StepTrigger,
+}
+
+/// A tutorial asset, stored as RON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tutorial {
+    pub name: String,
+    pub steps: Vec<TutorialStep>,
+}
+
+impl Tutorial {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tutorial {:?}: {}", path, e))?;
+        ron::from_str(&contents).map_err(|e| format!("Failed to parse tutorial {:?}: {}", path, e))
+    }
+}
+
+/// Lists the tutorials shipped in `directory`.
+pub fn find_tutorials(directory: &Path) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// Draws the current tutorial step on top of the UI and highlights the region it points at.
+/// UI code registers the screen rects of named regions every frame so steps can refer to them.
+#[derive(Default)]
+pub struct TutorialOverlay {
+    regions: HashMap<String, egui::Rect>,
+    active: Option<(Tutorial, usize)>,
+}
+
+impl TutorialOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_region(&mut self, name: &str, rect: egui::Rect) {
+        self.regions.insert(name.to_string(), rect);
+    }
+
+    pub fn start(&mut self, tutorial: Tutorial) {
+        if tutorial.steps.is_empty() {
+            log::warn!("Tutorial '{}' has no steps", tutorial.name);
+            return;
+        }
+        log::info!("Started tutorial: {}", tutorial.name);
+        self.active = Some((tutorial, 0));
+    }
+
+    pub fn stop(&mut self) {
+        self.active = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.active.is_some()
+    }
+
+    pub fn current_step(&self) -> Option<&TutorialStep> {
+        self.active
+            .as_ref()
+            .and_then(|(tutorial, step)| tutorial.steps.get(*step))
+    }
+
+    /// Advances the tutorial if the current step waits for `event`.
+    pub fn notify(&mut self, event: &str) {
+        let waiting = matches!(
+            self.current_step().map(|step| &step.trigger),
+            Some(StepTrigger::Event(name)) if name == event
+        );
+        if waiting {
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
+        if let Some((tutorial, step)) = &mut self.active {
+            *step += 1;
+            if *step >= tutorial.steps.len() {
+                log::info!("Finished tutorial: {}", tutorial.name);
+                self.active = None;
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let Some((tutorial, index)) = &self.active else {
+            return;
+        };
+        let step = &tutorial.steps[*index];
+        let step_count = tutorial.steps.len();
+        let highlight = step
+            .highlight
+            .as_ref()
+            .and_then(|name| self.regions.get(name))
+            .copied();
+
+        if let Some(rect) = highlight {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("TutorialHighlight"),
+            ));
+            painter.rect_stroke(
+                rect.shrink(1.0),
+                egui::CornerRadius::same(4),
+                egui::Stroke::new(3.0, egui::Color32::from_rgb(255, 200, 0)),
+                egui::StrokeKind::Inside,
+            );
+        }
+
+        let mut next = false;
+        let mut skip = false;
+
+        let window = egui::Window::new(format!("{} ({}/{})", step.title, index + 1, step_count))
+            .id(egui::Id::new(("TutorialStep", *index)))
+            .collapsible(false)
+            .resizable(false)
+            .order(egui::Order::Foreground);
+
+        // Sit next to the highlighted region, otherwise in the middle of the screen
+        let window = match highlight {
+            Some(rect) => window.default_pos(rect.center()),
+            None => window.anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0]),
+        };
+
+        window.show(ctx, |ui| {
+            ui.label(&step.text);
+            ui.separator();
+            ui.horizontal(|ui| {
+                match &step.trigger {
+                    StepTrigger::Next => {
+                        let label = if index + 1 == step_count {
+                            "Finish"
+                        } else {
+                            "Next"
+                        };
+                        next = ui.button(label).clicked();
+                    }
+                    StepTrigger::Event(_) => {
+                        ui.weak("Complete the task to continue");
+                    }
+                }
+                skip = ui.button("Skip tutorial").clicked();
+            });
+        });
+
+        if skip {
+            self.stop();
+        } else if next {
+            self.advance();
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    handles::AssetHandle, loader::AssetLoader, mesh::StaticMesh, scene_graph::SceneNode,
+    socket::Attachment, transform::MeshTransform,
+};
+
+/// An edit to the scene that can be taken back.
+pub trait EditCommand {
+    fn name(&self) -> &str;
+    fn apply(&mut self, scene: &mut SceneNode);
+    fn revert(&mut self, scene: &mut SceneNode);
+
+    /// Frees whatever the edit holds on to once it's dropped from the history.
+    fn discard(&mut self, _context: &glow::Context, _asset_loader: &mut AssetLoader) {}
+}
+
+/// New transforms for any number of static meshes, undone together.
+pub struct TransformEdit {
+    pub name: String,
+    pub changes: Vec<(usize, MeshTransform, MeshTransform)>, // Index, before, after
+}
+
+impl EditCommand for TransformEdit {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, scene: &mut SceneNode) {
+        for (index, _, after) in &self.changes {
+            if let Some(mesh) = scene.static_meshes.get_mut(*index) {
+                after.apply_to(mesh);
+                scene.mark_moved(*index);
+            }
+        }
+    }
+
+    fn revert(&mut self, scene: &mut SceneNode) {
+        for (index, before, _) in &self.changes {
+            if let Some(mesh) = scene.static_meshes.get_mut(*index) {
+                before.apply_to(mesh);
+                scene.mark_moved(*index);
+            }
+        }
+    }
+}
+
+/// Static meshes added to or deleted from the scene. Meshes out of the scene are kept here, GPU
+/// objects, retained assets and all, so they can be put back as they were.
+pub struct StaticMeshesEdit {
+    name: String,
+    indices: Vec<usize>,     // Where the meshes are while in the scene, ascending
+    parked: Vec<StaticMesh>, // Out of the scene, in the order of `indices`
+    attachments: Vec<Option<Attachment>>, // Every static mesh's, while these are in the scene
+    added: bool,
+}
+
+impl StaticMeshesEdit {
+    /// Records meshes that were just added to the scene.
+    pub fn added(name: String, scene: &SceneNode, mut indices: Vec<usize>) -> Self {
+        indices.sort_unstable();
+        Self {
+            name,
+            indices,
+            parked: Vec::new(),
+            attachments: attachments(scene),
+            added: true,
+        }
+    }
+
+    /// Takes the meshes out of the scene. Undoing puts them back.
+    pub fn delete(name: String, scene: &mut SceneNode, mut indices: Vec<usize>) -> Self {
+        indices.sort_unstable();
+        indices.dedup();
+        indices.retain(|&index| index < scene.static_meshes.len());
+        let mut edit = Self {
+            name,
+            indices,
+            parked: Vec::new(),
+            attachments: attachments(scene),
+            added: false,
+        };
+        edit.take_out(scene);
+        edit
+    }
+
+    fn take_out(&mut self, scene: &mut SceneNode) {
+        // From the back so the indices in front stay put
+        let mut parked: Vec<StaticMesh> = self
+            .indices
+            .iter()
+            .rev()
+            .filter_map(|&index| scene.take_static_mesh(index))
+            .collect();
+        parked.reverse();
+        self.parked = parked;
+    }
+
+    fn put_back(&mut self, scene: &mut SceneNode) {
+        for (&index, mesh) in self.indices.iter().zip(self.parked.drain(..)) {
+            scene.insert_static_mesh(index, mesh);
+        }
+        // Taking them out dropped the attachments to them. Only those come back, the rest may
+        // have been attached somewhere else since.
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            let Some(attachment) = attachment else {
+                continue;
+            };
+            if !self.indices.contains(&attachment.parent) {
+                continue;
+            }
+            let Some(mesh) = scene.static_meshes.get_mut(index) else {
+                break;
+            };
+            if mesh.attachment.is_none() {
+                mesh.attachment = Some(attachment.clone());
+                scene.mark_moved(index);
+            }
+        }
+    }
+}
+
+fn attachments(scene: &SceneNode) -> Vec<Option<Attachment>> {
+    scene
+        .static_meshes
+        .iter()
+        .map(|mesh| mesh.attachment.clone())
+        .collect()
+}
+
+impl EditCommand for StaticMeshesEdit {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, scene: &mut SceneNode) {
+        match self.added {
+            true => self.put_back(scene),
+            false => self.take_out(scene),
+        }
+    }
+
+    fn revert(&mut self, scene: &mut SceneNode) {
+        match self.added {
+            true => self.take_out(scene),
+            false => self.put_back(scene),
+        }
+    }
+
+    fn discard(&mut self, context: &glow::Context, asset_loader: &mut AssetLoader) {
+        for mesh in self.parked.drain(..) {
+            mesh.destroy(context);
+            asset_loader.release(AssetHandle::Mesh(mesh.handle));
+            if let Some((handle, _)) = mesh.base_color_map {
+                asset_loader.release(AssetHandle::Texture(handle));
+            }
+        }
+    }
+}
+
+pub struct UndoStack {
+    undo: Vec<Box<dyn EditCommand>>,
+    redo: Vec<Box<dyn EditCommand>>,
+    discarded: Vec<Box<dyn EditCommand>>, // Freed by `free_discarded`, which has a GL context
+    limit: usize,
+}
+
+impl UndoStack {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            discarded: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Records an edit that has already been made to the scene.
+    pub fn push(&mut self, command: Box<dyn EditCommand>) {
+        self.undo.push(command);
+        self.discarded.append(&mut self.redo);
+        if self.undo.len() > self.limit {
+            self.discarded.push(self.undo.remove(0));
+        }
+    }
+
+    /// Lets go of the edits that fell out of the history, call once a frame.
+    pub fn free_discarded(&mut self, context: &glow::Context, asset_loader: &mut AssetLoader) {
+        for mut command in self.discarded.drain(..) {
+            command.discard(context, asset_loader);
+        }
+    }
+
+    pub fn undo(&mut self, scene: &mut SceneNode) {
+        if let Some(mut command) = self.undo.pop() {
+            command.revert(scene);
+            log::info!("Undo: {}", command.name());
+            self.redo.push(command);
+        }
+    }
+
+    pub fn redo(&mut self, scene: &mut SceneNode) {
+        if let Some(mut command) = self.redo.pop() {
+            command.apply(scene);
+            log::info!("Redo: {}", command.name());
+            self.undo.push(command);
+        }
+    }
+
+    /// Forgets every edit, for changes the recorded ones can't be applied across.
+    pub fn clear(&mut self) {
+        self.discarded.append(&mut self.undo);
+        self.discarded.append(&mut self.redo);
+    }
+
+    pub fn undo_name(&self) -> Option<&str> {
+        self.undo.last().map(|command| command.name())
+    }
+
+    pub fn redo_name(&self) -> Option<&str> {
+        self.redo.last().map(|command| command.name())
+    }
+}
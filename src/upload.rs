@@ -0,0 +1,232 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use glow::HasContext;
+
+use crate::gl_debug;
+
+// Even with a tiny budget every frame moves at least this much so the queue drains
+const MIN_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Shared by every upload queued for one object, it's done once all of them reached the GPU.
+/// Objects start drawing only after that so they never show half filled buffers.
+#[derive(Debug, Clone, Default)]
+pub struct UploadStatus {
+    pending: Arc<AtomicUsize>,
+}
+
+impl UploadStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.load(Ordering::Acquire) == 0
+    }
+}
+
+enum UploadKind {
+    // Storage is allocated up front, the data is copied in with buffer_sub_data
+    Buffer(glow::Buffer),
+    // RGBA8 rows from the bottom up, mipmaps are generated after the last one
+    TextureRows {
+        texture: glow::Texture,
+        width: u32,
+        generate_mipmaps: bool,
+    },
+    // Compressed levels can't be split, each one goes in a single call
+    CompressedLevel {
+        texture: glow::Texture,
+        level: i32,
+        width: u32,
+        height: u32,
+        internal_format: u32,
+    },
+}
+
+struct Upload {
+    kind: UploadKind,
+    data: Vec<u8>,
+    uploaded: usize, // Bytes of `data` already on the GPU
+    status: UploadStatus,
+}
+
+/// Buffer and texture uploads spread over frames, at most a byte budget per frame, so
+/// loading a big asset doesn't stall the frame it is used in.
+#[derive(Default)]
+pub struct UploadQueue {
+    pending: VecDeque<Upload>,
+}
+
+impl UploadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills `buffer`, which needs its storage allocated with `buffer_data_size` already.
+    pub fn buffer(&mut self, buffer: glow::Buffer, data: Vec<u8>, status: &UploadStatus) {
+        self.push(UploadKind::Buffer(buffer), data, status);
+    }
+
+    /// Fills level 0 of a `TEXTURE_2D` allocated with `tex_image_2d` and no data.
+    pub fn texture_rgba(
+        &mut self,
+        texture: glow::Texture,
+        width: u32,
+        data: Vec<u8>,
+        generate_mipmaps: bool,
+        status: &UploadStatus,
+    ) {
+        let kind = UploadKind::TextureRows {
+            texture,
+            width,
+            generate_mipmaps,
+        };
+        self.push(kind, data, status);
+    }
+
+    pub fn compressed_level(
+        &mut self,
+        texture: glow::Texture,
+        level: i32,
+        (width, height): (u32, u32),
+        internal_format: u32,
+        data: Vec<u8>,
+        status: &UploadStatus,
+    ) {
+        let kind = UploadKind::CompressedLevel {
+            texture,
+            level,
+            width,
+            height,
+            internal_format,
+        };
+        self.push(kind, data, status);
+    }
+
+    fn push(&mut self, kind: UploadKind, data: Vec<u8>, status: &UploadStatus) {
+        if data.is_empty() {
+            return;
+        }
+        status.pending.fetch_add(1, Ordering::AcqRel);
+        self.pending.push_back(Upload {
+            kind,
+            data,
+            uploaded: 0,
+            status: status.clone(),
+        });
+    }
+
+    /// Uploads in queue order until `budget` bytes went to the GPU this call.
+    #[tracing::instrument(name = "UploadQueue::process", skip_all)]
+    pub fn process(&mut self, gl: &glow::Context, budget: usize) {
+        let mut spent = 0;
+        while spent < budget {
+            let Some(upload) = self.pending.front_mut() else {
+                break;
+            };
+            let allowance = (budget - spent).max(MIN_CHUNK_BYTES);
+            spent += unsafe { upload.upload_chunk(gl, allowance) };
+
+            if upload.uploaded >= upload.data.len() {
+                let upload = self.pending.pop_front().unwrap();
+                unsafe { upload.finish(gl) };
+                upload.status.pending.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Uploads everything right away, for when the result is needed this frame.
+    pub fn flush(&mut self, gl: &glow::Context) {
+        self.process(gl, usize::MAX);
+    }
+}
+
+impl Upload {
+    // Returns how many bytes went up
+    unsafe fn upload_chunk(&mut self, gl: &glow::Context, allowance: usize) -> usize {
+        let remaining = &self.data[self.uploaded..];
+        let sent = match self.kind {
+            UploadKind::Buffer(buffer) => {
+                // COPY_WRITE_BUFFER so the bound VAO's index buffer isn't touched
+                let length = remaining.len().min(allowance);
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(buffer));
+                gl.buffer_sub_data_u8_slice(
+                    glow::COPY_WRITE_BUFFER,
+                    self.uploaded as i32,
+                    &remaining[..length],
+                );
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
+                gl_debug::check_errors(gl, "upload buffer_sub_data");
+                length
+            }
+            UploadKind::TextureRows { texture, width, .. } => {
+                let row_bytes = width as usize * 4;
+                let rows = (allowance / row_bytes).clamp(1, remaining.len() / row_bytes);
+                let first_row = self.uploaded / row_bytes;
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.tex_sub_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    0,
+                    first_row as i32,
+                    width as i32,
+                    rows as i32,
+                    glow::RGBA,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelUnpackData::Slice(Some(&remaining[..rows * row_bytes])),
+                );
+                gl.bind_texture(glow::TEXTURE_2D, None);
+                gl_debug::check_errors(gl, "upload tex_sub_image_2d");
+                rows * row_bytes
+            }
+            UploadKind::CompressedLevel {
+                texture,
+                level,
+                width,
+                height,
+                internal_format,
+            } => {
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.compressed_tex_image_2d(
+                    glow::TEXTURE_2D,
+                    level,
+                    internal_format as i32,
+                    width as i32,
+                    height as i32,
+                    0,
+                    remaining.len() as i32,
+                    remaining,
+                );
+                gl.bind_texture(glow::TEXTURE_2D, None);
+                gl_debug::check_errors(gl, "upload compressed_tex_image_2d");
+                remaining.len()
+            }
+        };
+        self.uploaded += sent;
+        sent
+    }
+
+    unsafe fn finish(&self, gl: &glow::Context) {
+        if let UploadKind::TextureRows {
+            texture,
+            generate_mipmaps: true,
+            ..
+        } = self.kind
+        {
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.generate_mipmap(glow::TEXTURE_2D);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl_debug::check_errors(gl, "upload generate_mipmap");
+        }
+    }
+}
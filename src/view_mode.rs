@@ -0,0 +1,62 @@
+/// Debug visualizations of the scene, each one a variant of the default shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViewMode {
+    Lit,
+    Unlit,    // Base color only
+    Normals,  // World space, after normal mapping
+    Depth,    // Distance from the camera, white is far
+    Overdraw, // Brighter where more surfaces cover a pixel
+    UvChecker,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 6] = [
+        ViewMode::Lit,
+        ViewMode::Unlit,
+        ViewMode::Normals,
+        ViewMode::Depth,
+        ViewMode::Overdraw,
+        ViewMode::UvChecker,
+    ];
+
+    /// The value stored in the `r_view_mode` cvar.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::Lit => "lit",
+            ViewMode::Unlit => "unlit",
+            ViewMode::Normals => "normals",
+            ViewMode::Depth => "depth",
+            ViewMode::Overdraw => "overdraw",
+            ViewMode::UvChecker => "uv_checker",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ViewMode::Lit => "Lit",
+            ViewMode::Unlit => "Unlit",
+            ViewMode::Normals => "Normals",
+            ViewMode::Depth => "Depth",
+            ViewMode::Overdraw => "Overdraw",
+            ViewMode::UvChecker => "UV Checker",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The `#define` that selects this mode in shaders/fragment.glsl, None for the lit shader.
+    pub fn define(&self) -> Option<&'static str> {
+        match self {
+            ViewMode::Lit => None,
+            ViewMode::Unlit => Some("VIEW_UNLIT"),
+            ViewMode::Normals => Some("VIEW_NORMALS"),
+            ViewMode::Depth => Some("VIEW_DEPTH"),
+            ViewMode::Overdraw => Some("VIEW_OVERDRAW"),
+            ViewMode::UvChecker => Some("VIEW_UV_CHECKER"),
+        }
+    }
+}
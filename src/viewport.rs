@@ -1,3 +1,5 @@
+use glow::HasContext;
+
 #[derive(Debug)]
 pub struct Viewport {
     pub x: i32,
@@ -15,4 +17,24 @@ impl Viewport {
             height,
         }
     }
+
+    /// A rectangle in UI points (egui's) in pixels. Edges are rounded rather than the size so
+    /// views next to each other don't leave a gap between them.
+    pub fn from_points(x: f32, y: f32, width: f32, height: f32, pixels_per_point: f32) -> Self {
+        let left = (x * pixels_per_point).round() as i32;
+        let top = (y * pixels_per_point).round() as i32;
+        let right = ((x + width) * pixels_per_point).round() as i32;
+        let bottom = ((y + height) * pixels_per_point).round() as i32;
+        Self::new(left, top, right - left, bottom - top)
+    }
+
+    /// Clears only this part of the framebuffer, for views sharing a window.
+    pub fn clear(&self, context: &glow::Context) {
+        unsafe {
+            context.enable(glow::SCISSOR_TEST);
+            context.scissor(self.x, self.y, self.width, self.height);
+            context.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+            context.disable(glow::SCISSOR_TEST);
+        }
+    }
 }
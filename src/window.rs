@@ -0,0 +1,351 @@
+// Window and swap chain settings shared by the editor and the game runtime
+
+use std::{
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
+
+use crate::cvars::{CVarRegistry, CVarValue};
+use glutin::{
+    context::PossiblyCurrentContext,
+    prelude::*,
+    surface::{Surface, SwapInterval, WindowSurface},
+};
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowAttributes},
+};
+
+/// How buffer swaps wait for the display, the `r_vsync` cvar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VSync {
+    Off,
+    On,
+    Adaptive, // Waits, unless the frame is already late, then it tears instead
+}
+
+impl VSync {
+    pub const ALL: [VSync; 3] = [VSync::Off, VSync::On, VSync::Adaptive];
+
+    /// The value stored in the `r_vsync` cvar.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VSync::Off => "off",
+            VSync::On => "on",
+            VSync::Adaptive => "adaptive",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VSync::Off => "Off",
+            VSync::On => "On",
+            VSync::Adaptive => "Adaptive",
+        }
+    }
+
+    /// Also takes the true and false `r_vsync` used to be, so old configs keep working.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Some(VSync::On),
+            "false" | "0" => Some(VSync::Off),
+            name => Self::ALL.into_iter().find(|mode| mode.name() == name),
+        }
+    }
+
+    /// The mode `value` names, on if it doesn't name one.
+    pub fn from_value(value: &CVarValue) -> Self {
+        Self::from_name(&value.to_string()).unwrap_or_else(|| {
+            log::warn!("Unknown r_vsync value '{}', using on", value);
+            VSync::On
+        })
+    }
+
+    pub fn from_cvars(cvars: &CVarRegistry) -> Self {
+        cvars.get("r_vsync").map_or(VSync::On, Self::from_value)
+    }
+
+    /// Sets the surface's swap interval, on the thread the context is current on. glutin has
+    /// no way to ask for a late swap, so adaptive waits like on.
+    pub fn apply(&self, surface: &Surface<WindowSurface>, context: &PossiblyCurrentContext) {
+        let interval = match self {
+            VSync::Off => SwapInterval::DontWait,
+            VSync::On => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+            VSync::Adaptive => {
+                log::warn!("Adaptive vsync isn't supported here, using regular vsync");
+                SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+            }
+        };
+        if let Err(e) = surface.set_swap_interval(context, interval) {
+            log::error!("Failed to set vsync: {}", e);
+        }
+    }
+}
+
+/// How the window covers the screen, the `r_window_mode` cvar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    Borderless, // A window the size of the monitor, switching to other windows is instant
+    Exclusive,  // Takes over the monitor at its highest resolution
+}
+
+impl WindowMode {
+    pub const ALL: [WindowMode; 3] = [
+        WindowMode::Windowed,
+        WindowMode::Borderless,
+        WindowMode::Exclusive,
+    ];
+
+    /// The value stored in the `r_window_mode` cvar.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Borderless => "borderless",
+            WindowMode::Exclusive => "exclusive",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless Fullscreen",
+            WindowMode::Exclusive => "Exclusive Fullscreen",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn from_cvars(cvars: &CVarRegistry) -> Self {
+        let name = cvars.get("r_window_mode").map(|value| value.to_string());
+        name.and_then(|name| Self::from_name(&name))
+            .unwrap_or(WindowMode::Windowed)
+    }
+
+    /// What F11 switches to, fullscreen goes back to a window.
+    pub fn toggled(&self) -> Self {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            _ => WindowMode::Windowed,
+        }
+    }
+
+    /// Puts `window` in this mode on the monitor it's on. The surface follows along through
+    /// the Resized event.
+    pub fn apply(&self, window: &Window) {
+        let fullscreen = match self {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(window.current_monitor())),
+            WindowMode::Exclusive => {
+                let mode = window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|mode| {
+                        let size = mode.size();
+                        (size.width * size.height, mode.refresh_rate_millihertz())
+                    })
+                });
+                match mode {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => {
+                        log::warn!("No video modes for exclusive fullscreen, using borderless");
+                        Some(Fullscreen::Borderless(window.current_monitor()))
+                    }
+                }
+            }
+        };
+        window.set_fullscreen(fullscreen);
+    }
+}
+
+/// Where a window opens, in physical pixels. Whatever is left out is up to the OS.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowPlacement {
+    pub size: Option<[u32; 2]>,     // Inside the frame
+    pub position: Option<[i32; 2]>, // The frame's top left on the desktop
+    pub monitor: Option<String>,    // Centered on it when there's no position
+    pub maximized: bool,
+}
+
+impl WindowPlacement {
+    /// Adds the placement to `attributes`. A position that's on no monitor anymore (one was
+    /// unplugged, say) is dropped so the window doesn't open off screen.
+    pub fn attributes(
+        &self,
+        event_loop: &ActiveEventLoop,
+        mut attributes: WindowAttributes,
+    ) -> WindowAttributes {
+        if let Some([width, height]) = self.size {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+
+        let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+        let on_screen = |[x, y]: [i32; 2]| {
+            monitors.iter().any(|monitor| {
+                let (position, size) = (monitor.position(), monitor.size());
+                x >= position.x
+                    && y >= position.y
+                    && x < position.x + size.width as i32
+                    && y < position.y + size.height as i32
+            })
+        };
+        let monitor = self.monitor.as_ref().and_then(|name| {
+            monitors
+                .iter()
+                .find(|monitor| monitor.name().as_ref() == Some(name))
+        });
+
+        let position = self.position.filter(|&position| on_screen(position));
+        match (position, monitor) {
+            (Some([x, y]), _) => {
+                attributes = attributes.with_position(PhysicalPosition::new(x, y));
+            }
+            (None, Some(monitor)) => {
+                let [width, height] = self.size.unwrap_or([1280, 720]);
+                let (position, size) = (monitor.position(), monitor.size());
+                let x = position.x + (size.width as i32 - width as i32).max(0) / 2;
+                let y = position.y + (size.height as i32 - height as i32).max(0) / 2;
+                attributes = attributes.with_position(PhysicalPosition::new(x, y));
+            }
+            (None, None) => {}
+        }
+
+        attributes.with_maximized(self.maximized)
+    }
+
+    /// Takes where `window` is now. While it's maximized or fullscreen the size and position
+    /// it goes back to are kept, since the OS doesn't say what they are.
+    pub fn update(&mut self, window: &Window) {
+        self.maximized = window.is_maximized();
+        self.monitor = window.current_monitor().and_then(|monitor| monitor.name());
+        if self.maximized || window.fullscreen().is_some() {
+            return;
+        }
+        let size = window.inner_size();
+        self.size = Some([size.width, size.height]);
+        self.position = window
+            .outer_position()
+            .ok()
+            .map(|position| [position.x, position.y]);
+    }
+}
+
+/// The window's title, "project – scene – * (unsaved)" and then a status if something set one.
+/// It's only sent to the OS when it changes.
+#[derive(Debug, Default)]
+pub struct WindowTitle {
+    pub app: String, // Always last
+    pub project: Option<String>,
+    pub scene: Option<String>,
+    pub unsaved: bool,
+    pub status: Option<String>, // e.g. "Playing"
+    shown: Option<String>,
+}
+
+impl WindowTitle {
+    pub fn new(app: impl Into<String>) -> Self {
+        Self {
+            app: app.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut parts: Vec<&str> = [&self.project, &self.scene]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|part| !part.is_empty())
+            .collect();
+        if self.unsaved {
+            parts.push("* (unsaved)");
+        }
+        parts.extend(self.status.as_deref());
+        parts.push(&self.app);
+        parts.join(" – ")
+    }
+
+    /// Sets `window`'s title if it changed since the last call.
+    pub fn apply(&mut self, window: &Window) {
+        let text = self.text();
+        if self.shown.as_ref() != Some(&text) {
+            window.set_title(&text);
+            self.shown = Some(text);
+        }
+    }
+}
+
+/// Decides when the next frame is drawn: no sooner than the `r_max_fps` cap allows, and not
+/// at all until something asks for one.
+pub struct FramePacer {
+    min_frame_time: Duration,
+    last_frame: Instant,
+    next_frame: Option<Instant>,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            min_frame_time: Duration::ZERO,
+            last_frame: Instant::now(),
+            next_frame: Some(Instant::now()),
+        }
+    }
+
+    /// Takes the cap from the cvars, 0 or less is uncapped.
+    pub fn set_max_fps(&mut self, cvars: &CVarRegistry) {
+        let max_fps = cvars.get_int("r_max_fps");
+        self.min_frame_time = match max_fps > 0 {
+            true => Duration::from_secs_f64(1.0 / max_fps as f64),
+            false => Duration::ZERO,
+        };
+    }
+
+    /// Call when a frame starts being drawn.
+    pub fn begin_frame(&mut self) {
+        self.last_frame = Instant::now();
+        self.next_frame = None;
+    }
+
+    /// Asks for another frame, as soon as the cap allows.
+    pub fn request(&mut self) {
+        self.request_at(Instant::now());
+    }
+
+    /// Asks for another frame at `at`, or later if the cap says so.
+    pub fn request_at(&mut self, at: Instant) {
+        let at = at.max(self.last_frame + self.min_frame_time);
+        self.next_frame = Some(self.next_frame.map_or(at, |next| next.min(at)));
+    }
+
+    /// Call from `about_to_wait`. True when it's time to draw, otherwise the event loop is
+    /// told to sleep until then, or until the next event if nothing asked for a frame.
+    pub fn poll(&mut self, event_loop: &ActiveEventLoop) -> bool {
+        match self.next_frame {
+            Some(at) if Instant::now() >= at => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                true
+            }
+            Some(at) => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(at));
+                false
+            }
+            None => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                false
+            }
+        }
+    }
+}